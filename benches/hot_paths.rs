@@ -0,0 +1,89 @@
+//! Benchmarks for the hot paths: wire encoding, message rendering and the
+//! little formatters called for every file pane row.
+//!
+//! Run with `cargo bench`; criterion keeps the previous run under
+//! `target/criterion/` and reports changes against it, so run once before
+//! a performance change and once after.
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use iroh::SecretKey;
+use ratatui::Terminal;
+use ratatui::backend::TestBackend;
+
+use piper_chat::chat::{App, LineCache, ui};
+use piper_chat::net::{self, Message};
+use piper_chat::transfer::{format_file_size, progress_bar};
+
+/// Messages in the synthetic room used by the render benchmarks.
+const FIXTURE_MESSAGES: usize = 10_000;
+
+/// An `App` holding `count` messages of mixed kinds, a minute apart, as a
+/// long-running room would after a day or so of chat.
+fn fixture(count: usize) -> App {
+    let mut app = App::new();
+    let start = 1_700_000_000_000;
+    for i in 0..count {
+        let ts = start + i as u64 * 60_000;
+        let id = (i as u128).to_le_bytes();
+        match i % 10 {
+            0 => app.system(format!("peer-{} joined", i % 7)),
+            1 => app.spoiler(format!("peer-{}", i % 7), "the butler did it".into(), id, ts),
+            // Every so often a multi-line paste.
+            2 if i % 100 == 2 => app.chat(
+                format!("peer-{}", i % 7),
+                (0..30).map(|n| format!("line {n}")).collect::<Vec<_>>().join("\n"),
+                id,
+                ts,
+            ),
+            _ => app.chat(format!("peer-{}", i % 7), format!("message number {i}, hello there"), id, ts),
+        }
+    }
+    app
+}
+
+fn encoding(c: &mut Criterion) {
+    let key = SecretKey::from_bytes(&[1u8; 32]);
+    let msg = Message::Chat {
+        nickname: "Alice".into(),
+        text: "The quick brown fox jumps over the lazy dog".into(),
+        message_id: [7; 16],
+        timestamp_ms: 1_700_000_000_000,
+    };
+    let bytes = net::proto::encode(&key, &msg).unwrap();
+    c.bench_function("message encode", |b| b.iter(|| net::proto::encode(&key, &msg).unwrap()));
+    c.bench_function("message decode", |b| b.iter(|| net::proto::decode(&bytes).unwrap()));
+}
+
+fn rendering(c: &mut Criterion) {
+    let mut terminal = Terminal::new(TestBackend::new(120, 40)).unwrap();
+    // Steady state: nothing changed since the last frame.
+    let mut app = fixture(FIXTURE_MESSAGES);
+    c.bench_function("ui 10k messages (cached)", |b| {
+        b.iter(|| {
+            terminal.draw(|f| ui(f, &mut app)).unwrap();
+        })
+    });
+    // Every line built from scratch, as on a resize or theme toggle.
+    c.bench_function("ui 10k messages (cold)", |b| {
+        b.iter(|| {
+            app.line_cache = LineCache::default();
+            terminal.draw(|f| ui(f, &mut app)).unwrap();
+        })
+    });
+}
+
+fn formatting(c: &mut Criterion) {
+    c.bench_function("format_file_size", |b| {
+        b.iter(|| {
+            for size in [512, 1536, 5 << 20, 3 << 30] {
+                std::hint::black_box(format_file_size(std::hint::black_box(size)));
+            }
+        })
+    });
+    c.bench_function("progress_bar", |b| {
+        b.iter(|| progress_bar(std::hint::black_box(768), std::hint::black_box(1024), false))
+    });
+}
+
+criterion_group!(benches, encoding, rendering, formatting);
+criterion_main!(benches);
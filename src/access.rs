@@ -0,0 +1,350 @@
+//! Transfer permissions: who may offer us files, and who may download ours.
+//!
+//! Two settings in the config file control this:
+//!
+//! - `offers_from` — whose `FileOffer`s reach the files pane: `"everyone"`
+//!   (the default), `"verified"` (contacts marked with `/verify`), or
+//!   `"nobody"`. Checked in the gossip receive path in `main.rs`; a refused
+//!   offer leaves a one-line notice instead of a download prompt.
+//! - `serve_to` — who may fetch blobs from us: `"anyone"` holding the hash
+//!   (the default, and how iroh-blobs behaves out of the box), or only the
+//!   room's current `"members"`.
+//!
+//! A hash is only as secret as the room it was posted in, so `"members"`
+//! matters when a ticket leaks or a peer leaves: they can no longer pull
+//! files they remember the hash of. It's enforced inside the blobs protocol
+//! itself — `Node::spawn_members_only` asks iroh-blobs to hand every
+//! incoming connection to `gate` for a verdict before any request is read.
+//! (Every chat node hands `gate` its get requests too, to refuse blobs whose
+//! referenced file changed, see `blobstore`, and to note who fetched what
+//! for the file pane's download counters, see `Fetched`.)
+//!
+//! ## Room tokens
+//!
+//! Our peers map only holds who we've heard from, and a downloader can be in
+//! the room without that: a peer whose first message hasn't reached us yet,
+//! or one gossip never made our neighbor. So a downloader may also vouch for
+//! itself with a **room token**, a keyed BLAKE3 hash of its endpoint ID under
+//! the room's topic ID. Only someone holding the ticket can compute it, and
+//! it's bound to the presenter's ID, so a token overheard on the way is no
+//! use to anyone else.
+//!
+//! ```text
+//! downloader ──AUTH_ALPN: token──► provider   (checks it, remembers the ID)
+//!            ◄──────── 1 ─────────
+//! downloader ──BLOBS_ALPN: get────► provider   (gate: member or vouched)
+//! ```
+//!
+//! Downloads present the token first (`present`) whenever they know the room;
+//! a provider serving anyone doesn't listen on `AUTH_ALPN`, and the failed
+//! connect is ignored.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, RwLock};
+
+use iroh::endpoint::Connection;
+use iroh::protocol::{AcceptError, ProtocolHandler};
+use iroh::{Endpoint, EndpointAddr, EndpointId};
+use iroh_blobs::Hash;
+use iroh_blobs::provider::events::{AbortReason, ProviderMessage, RequestUpdate};
+use iroh_gossip::proto::TopicId;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+
+use crate::blobstore::Sources;
+use crate::error::{Result, ResultExt};
+use crate::metrics::METRICS;
+
+/// ALPN for presenting a room token before a blob download. Versioned like
+/// `DIRECT_ALPN`.
+pub const AUTH_ALPN: &[u8] = b"piper-chat/blob-auth/0";
+
+/// The room token `id` presents to providers in room `topic`.
+pub fn token(topic: &TopicId, id: &EndpointId) -> [u8; 32] {
+    let mut hasher = blake3::Hasher::new_keyed(topic.as_bytes());
+    hasher.update(b"piper-chat blob token");
+    hasher.update(id.as_bytes());
+    hasher.finalize().into()
+}
+
+/// Whose file offers we accept.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OfferPolicy {
+    #[default]
+    Everyone,
+    /// Only contacts the user has marked verified.
+    Verified,
+    Nobody,
+}
+
+impl OfferPolicy {
+    /// Whether an offer from a sender whose contact is (or isn't) verified
+    /// gets through.
+    pub fn accepts(self, verified: bool) -> bool {
+        match self {
+            OfferPolicy::Everyone => true,
+            OfferPolicy::Verified => verified,
+            OfferPolicy::Nobody => false,
+        }
+    }
+}
+
+/// Who may download the blobs we share.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ServePolicy {
+    /// Anyone who knows a blob's hash.
+    #[default]
+    Anyone,
+    /// Only peers currently in the room.
+    Members,
+}
+
+/// The endpoints allowed to fetch blobs under `ServePolicy::Members`.
+///
+/// A cheap-to-clone handle: `App` keeps it in step with its peers map, and
+/// the `gate` task reads it for every incoming blob connection. It also
+/// knows the room we're in, to check and present room tokens.
+#[derive(Debug, Clone, Default)]
+pub struct Members(Arc<RwLock<Allowed>>);
+
+#[derive(Debug, Default)]
+struct Allowed {
+    /// Peers in the peers map.
+    peers: HashSet<EndpointId>,
+    /// Endpoints that presented a valid token for `room`.
+    vouched: HashSet<EndpointId>,
+    room: Option<TopicId>,
+}
+
+impl Members {
+    pub fn insert(&self, id: EndpointId) {
+        self.0.write().unwrap_or_else(|e| e.into_inner()).peers.insert(id);
+    }
+
+    pub fn remove(&self, id: &EndpointId) {
+        self.0.write().unwrap_or_else(|e| e.into_inner()).peers.remove(id);
+    }
+
+    pub fn contains(&self, id: &EndpointId) -> bool {
+        let allowed = self.0.read().unwrap_or_else(|e| e.into_inner());
+        allowed.peers.contains(id) || allowed.vouched.contains(id)
+    }
+
+    /// Switch to room `topic`. Tokens for the room we left stop counting.
+    pub fn set_room(&self, topic: TopicId) {
+        let mut allowed = self.0.write().unwrap_or_else(|e| e.into_inner());
+        if allowed.room != Some(topic) {
+            allowed.vouched.clear();
+        }
+        allowed.room = Some(topic);
+    }
+
+    pub fn room(&self) -> Option<TopicId> {
+        self.0.read().unwrap_or_else(|e| e.into_inner()).room
+    }
+
+    /// Let `id` in if `presented` is its token for our room.
+    pub fn vouch(&self, id: EndpointId, presented: &[u8; 32]) -> bool {
+        let mut allowed = self.0.write().unwrap_or_else(|e| e.into_inner());
+        // `blake3::Hash` compares in constant time, so a guesser can't
+        // learn how many leading bytes were right.
+        let valid = allowed
+            .room
+            .is_some_and(|room| blake3::Hash::from(token(&room, &id)) == blake3::Hash::from(*presented));
+        if valid {
+            allowed.vouched.insert(id);
+        }
+        valid
+    }
+}
+
+/// Blobs of ours that peers fetched to the end, as `(hash, peer)`, in the
+/// order they finished.
+///
+/// A cheap-to-clone handle like `Members`: `gate` notes each get request it
+/// saw served completely, and the chat's clock tick `take`s them for the
+/// file pane's download counters. A resumed download counts once it has
+/// fetched the rest.
+#[derive(Debug, Clone, Default)]
+pub struct Fetched(Arc<Mutex<Vec<(Hash, EndpointId)>>>);
+
+impl Fetched {
+    pub fn push(&self, hash: Hash, by: EndpointId) {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).push((hash, by));
+    }
+
+    /// The fetches noted since the last call.
+    pub fn take(&self) -> Vec<(Hash, EndpointId)> {
+        std::mem::take(&mut *self.0.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+}
+
+// ── Token handshake ──────────────────────────────────────────────────────────
+
+/// Checks room tokens on `AUTH_ALPN`: reads one, answers `1` (accepted) or
+/// `0`, and waits for the downloader to hang up.
+///
+/// Registered on the router by `Node::spawn_members_only`.
+#[derive(Debug, Clone)]
+pub struct AuthProtocol {
+    members: Members,
+}
+
+impl AuthProtocol {
+    pub fn new(members: Members) -> Self {
+        Self { members }
+    }
+}
+
+impl ProtocolHandler for AuthProtocol {
+    async fn accept(&self, connection: Connection) -> std::result::Result<(), AcceptError> {
+        let from = connection.remote_id();
+        let (mut send, mut recv) = connection.accept_bi().await?;
+        let mut presented = [0; 32];
+        recv.read_exact(&mut presented).await.map_err(AcceptError::from_err)?;
+        let accepted = self.members.vouch(from, &presented);
+        send.write_all(&[accepted as u8]).await.map_err(AcceptError::from_err)?;
+        send.finish().map_err(AcceptError::from_err)?;
+        connection.closed().await;
+        Ok(())
+    }
+}
+
+/// Present our token for room `topic` to `provider`. `Ok(false)` means it
+/// was refused, which only happens if the provider is in another room.
+pub async fn present(endpoint: &Endpoint, provider: impl Into<EndpointAddr>, topic: &TopicId) -> Result<bool> {
+    let connection = endpoint.connect(provider, AUTH_ALPN).await.network()?;
+    let (mut send, mut recv) = connection.open_bi().await.network()?;
+    send.write_all(&token(topic, &endpoint.id())).await.network()?;
+    send.finish().network()?;
+    let mut verdict = [0; 1];
+    recv.read_exact(&mut verdict).await.network()?;
+    connection.close(0u32.into(), b"done");
+    Ok(verdict[0] == 1)
+}
+
+/// Answer the blobs protocol's checks until it shuts down; whatever fails
+/// one is refused with `Permission`.
+///
+/// - Connections, with `members` (`ConnectMode::Intercept`): members are let
+///   in, everyone else — including a connection without a known endpoint
+///   ID — is not.
+/// - Get requests (`RequestMode::InterceptLog`): a blob imported by
+///   reference whose file changed since is refused (see
+///   `blobstore::Sources`), rather than served with bytes that don't match
+///   its hash. The ones let through are followed to the end, to note a
+///   complete one in `fetched` under the peer behind its connection and
+///   count the bytes sent in `METRICS`.
+pub async fn gate(mut events: mpsc::Receiver<ProviderMessage>, members: Option<Members>, sources: Sources, fetched: Fetched) {
+    let verdict = |allowed: bool| if allowed { Ok(()) } else { Err(AbortReason::Permission) };
+    // Who's behind each blob connection, to credit its fetches to.
+    let mut connections: HashMap<u64, EndpointId> = HashMap::new();
+    // The connection may already be gone when we answer; nothing to do then.
+    while let Some(event) = events.recv().await {
+        match event {
+            ProviderMessage::ClientConnected(msg) => {
+                let allowed = msg.inner.endpoint_id.is_some_and(|id| members.as_ref().is_none_or(|m| m.contains(&id)));
+                if let Some(id) = msg.inner.endpoint_id.filter(|_| allowed) {
+                    connections.insert(msg.inner.connection_id, id);
+                }
+                let _ = msg.tx.send(verdict(allowed)).await;
+            }
+            ProviderMessage::ClientConnectedNotify(msg) => {
+                if let Some(id) = msg.inner.endpoint_id {
+                    connections.insert(msg.inner.connection_id, id);
+                }
+            }
+            ProviderMessage::ConnectionClosed(msg) => {
+                connections.remove(&msg.inner.connection_id);
+            }
+            ProviderMessage::GetRequestReceived(msg) => {
+                let hash = msg.inner.request.hash;
+                let by = connections.get(&msg.inner.connection_id).copied();
+                // The updates must be read even when there's no one to
+                // credit: the provider gives up on a request whose progress
+                // can't be delivered.
+                let mut updates = msg.rx;
+                let fetched = fetched.clone();
+                tokio::spawn(async move {
+                    while let Ok(Some(update)) = updates.recv().await {
+                        match update {
+                            RequestUpdate::Completed(done) => {
+                                METRICS.sent(done.stats.payload_bytes_sent);
+                                if let Some(by) = by {
+                                    fetched.push(hash, by);
+                                }
+                            }
+                            RequestUpdate::Aborted(aborted) => METRICS.sent(aborted.stats.payload_bytes_sent),
+                            RequestUpdate::Started(_) | RequestUpdate::Progress(_) => {}
+                        }
+                    }
+                });
+                let _ = msg.tx.send(verdict(sources.allows(&hash))).await;
+            }
+            ProviderMessage::GetManyRequestReceived(msg) => {
+                let allowed = msg.inner.request.hashes.iter().all(|hash| sources.allows(hash));
+                let _ = msg.tx.send(verdict(allowed)).await;
+            }
+            _ => {}
+        }
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offer_policy() {
+        assert!(OfferPolicy::Everyone.accepts(false));
+        assert!(OfferPolicy::Verified.accepts(true));
+        assert!(!OfferPolicy::Verified.accepts(false));
+        assert!(!OfferPolicy::Nobody.accepts(true));
+    }
+
+    #[test]
+    fn members_are_shared_between_clones() {
+        let id = iroh::SecretKey::from_bytes(&[3; 32]).public();
+        let members = Members::default();
+        let gate_view = members.clone();
+        members.insert(id);
+        assert!(gate_view.contains(&id));
+        members.remove(&id);
+        assert!(!gate_view.contains(&id));
+    }
+
+    #[test]
+    fn fetches_are_taken_once() {
+        let (bob, carol) = (iroh::SecretKey::from_bytes(&[3; 32]).public(), iroh::SecretKey::from_bytes(&[4; 32]).public());
+        let fetched = Fetched::default();
+        let gate_view = fetched.clone();
+        gate_view.push(Hash::from_bytes([1; 32]), bob);
+        gate_view.push(Hash::from_bytes([1; 32]), carol);
+        assert_eq!(fetched.take(), [(Hash::from_bytes([1; 32]), bob), (Hash::from_bytes([1; 32]), carol)]);
+        assert!(fetched.take().is_empty());
+    }
+
+    #[test]
+    fn room_tokens_vouch_for_their_presenter_only() {
+        let (room, other_room) = (TopicId::from_bytes([1; 32]), TopicId::from_bytes([2; 32]));
+        let (bob, carol) = (iroh::SecretKey::from_bytes(&[3; 32]).public(), iroh::SecretKey::from_bytes(&[4; 32]).public());
+        let members = Members::default();
+        // Not in a room yet: nothing to check tokens against.
+        assert!(!members.vouch(bob, &token(&room, &bob)));
+
+        members.set_room(room);
+        assert!(!members.vouch(bob, &token(&room, &carol)));
+        assert!(!members.vouch(bob, &token(&other_room, &bob)));
+        assert!(members.vouch(bob, &token(&room, &bob)));
+        assert!(members.contains(&bob));
+        // Leaving the peers map doesn't undo a token; leaving the room does.
+        members.remove(&bob);
+        assert!(members.contains(&bob));
+        members.set_room(other_room);
+        assert!(!members.contains(&bob));
+    }
+}
@@ -0,0 +1,379 @@
+//! Room admission: a member cap and knock-to-join.
+//!
+//! A room's creator can fix two limits when making it (`create --max-members
+//! N --knock`), which travel in every ticket as its `RoomPolicy`:
+//!
+//! - **Member cap** — at most N members at once, the creator included. In an
+//!   open room each peer lets newcomers in on their `Join` while there's
+//!   room, and ignores whoever turns up after that.
+//! - **Knock-to-join** — a newcomer sends `Message::JoinRequest` instead of
+//!   `Join` and waits. The creator gets a y/n prompt; a yes is an `Approval`
+//!   signed with the creator's key, broadcast with every earlier approval so
+//!   the newcomer learns the whole roster. Other members just see a notice,
+//!   since only the creator's signature counts. With a cap too, the creator
+//!   turns knocks away once that many have been let in.
+//!
+//! **Co-owners.** The creator can share all of that with `/owner <name>`: a
+//! `Message::GrantOwner` carries every `OwnerGrant` made so far, each signed
+//! by an owner, and owners pass the full set on to each new neighbor just
+//! like the approvals. Every owner gets the prompt and signs approvals with
+//! their own key, so the room keeps letting people in after its creator has
+//! left. An owner can name further owners; a grant only counts if a chain
+//! of them leads back to the creator in the ticket.
+//!
+//! Either way, messages from peers not let in are dropped in the gossip
+//! receive path in `main.rs` (`Admission::screen`) before anything sees them.
+
+use std::collections::{BTreeSet, VecDeque};
+
+use iroh::{EndpointId, SecretKey};
+use iroh_gossip::proto::TopicId;
+use ratatui::layout::Rect;
+use ratatui::style::{Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Paragraph};
+
+use crate::i18n::Lang;
+use crate::net::proto::{Approval, OwnerGrant, verify_approvals, verify_owners};
+use crate::net::ticket::RoomPolicy;
+use crate::net::Message;
+use crate::t;
+use crate::theme::Theme;
+
+/// What became of a `JoinRequest`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Knock {
+    /// A new request, now waiting for the creator.
+    New,
+    /// The same peer asking again (it sends one per new neighbor).
+    Again,
+    /// Already let in.
+    Member,
+    /// The room is at its cap.
+    Full,
+}
+
+/// Who may take part in a room with a `RoomPolicy`, from our point of view.
+#[derive(Debug)]
+pub struct Admission {
+    pub policy: RoomPolicy,
+    topic: TopicId,
+    us: EndpointId,
+    /// Everyone let in: the creator, us once we're in, and each peer
+    /// approved (knock) or seen joining while there was room (cap only).
+    admitted: BTreeSet<EndpointId>,
+    /// The approvals we've seen, passed on in full by the owners.
+    approvals: Vec<Approval>,
+    /// The room's owners: the creator first, then each co-owner.
+    owners: Vec<EndpointId>,
+    /// The grants behind `owners`, passed on in full by the owners.
+    grants: Vec<OwnerGrant>,
+    /// Join requests not yet answered, oldest first, with the name each
+    /// gave. Only owners' clients prompt for them.
+    pub pending: VecDeque<(EndpointId, String)>,
+}
+
+impl Admission {
+    pub fn new(policy: RoomPolicy, topic: TopicId, us: EndpointId) -> Self {
+        let mut admitted = BTreeSet::from([policy.creator]);
+        if !policy.knock {
+            admitted.insert(us);
+        }
+        Self {
+            owners: vec![policy.creator],
+            policy,
+            topic,
+            us,
+            admitted,
+            approvals: Vec::new(),
+            grants: Vec::new(),
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Whether we own the room, as its creator or a co-owner.
+    pub fn is_owner(&self) -> bool {
+        self.owners.contains(&self.us)
+    }
+
+    /// The room's owners, the creator first.
+    pub fn owners(&self) -> &[EndpointId] {
+        &self.owners
+    }
+
+    /// The grants to broadcast so everyone has the full owner set.
+    pub fn grants(&self) -> &[OwnerGrant] {
+        &self.grants
+    }
+
+    /// Whether we ourselves are in — always, except while knocking.
+    pub fn let_in(&self) -> bool {
+        self.admitted.contains(&self.us)
+    }
+
+    pub fn admits(&self, id: &EndpointId) -> bool {
+        self.admitted.contains(id)
+    }
+
+    /// Whether the room has reached its member cap.
+    pub fn full(&self) -> bool {
+        self.policy.max_members.is_some_and(|max| self.admitted.len() >= max as usize)
+    }
+
+    /// Whether the owner's y/n prompt is up.
+    pub fn prompting(&self) -> bool {
+        self.is_owner() && !self.pending.is_empty()
+    }
+
+    /// The approvals to broadcast so everyone has the full roster.
+    pub fn approvals(&self) -> &[Approval] {
+        &self.approvals
+    }
+
+    /// Whether a message from `from` gets handled at all. The knock
+    /// messages always do; they check their own credentials. In an open
+    /// room with a cap, a `Join` takes a free place.
+    pub fn screen(&mut self, from: EndpointId, msg: &Message) -> bool {
+        match msg {
+            Message::JoinRequest { .. }
+            | Message::JoinApproval { .. }
+            | Message::JoinDenial { .. }
+            | Message::GrantOwner { .. } => true,
+            _ if self.admitted.contains(&from) => true,
+            Message::Join { .. } if !self.policy.knock && !self.full() => {
+                self.admitted.insert(from);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// A peer left: in an open room its place frees up. Approvals in a
+    /// knock room stand, so a member who reconnects is still in.
+    pub fn leave(&mut self, id: &EndpointId) {
+        if !self.policy.knock && !self.owners.contains(id) && *id != self.us {
+            self.admitted.remove(id);
+        }
+    }
+
+    /// Note a `JoinRequest` from `id`, calling itself `name`.
+    pub fn knock(&mut self, id: EndpointId, name: String) -> Knock {
+        if self.admitted.contains(&id) {
+            Knock::Member
+        } else if self.pending.iter().any(|(p, _)| *p == id) {
+            Knock::Again
+        } else if self.full() {
+            Knock::Full
+        } else {
+            self.pending.push_back((id, name));
+            Knock::New
+        }
+    }
+
+    /// Let in the oldest waiting peer, signing its approval with our own
+    /// owner's `key`. Returns who it was.
+    pub fn approve(&mut self, key: &SecretKey) -> Option<(EndpointId, String)> {
+        let (id, name) = self.pending.pop_front()?;
+        self.approvals.push(Approval::sign(key, &self.topic, id));
+        self.admitted.insert(id);
+        Some((id, name))
+    }
+
+    /// Turn away the oldest waiting peer. Returns who it was.
+    pub fn deny(&mut self) -> Option<(EndpointId, String)> {
+        self.pending.pop_front()
+    }
+
+    /// Take in the approvals from a `JoinApproval`, keeping the ones an
+    /// owner really signed for this room. Returns whoever is newly in,
+    /// with the name they knocked under if we saw it.
+    pub fn accept(&mut self, approvals: Vec<Approval>) -> Vec<(EndpointId, Option<String>)> {
+        let valid = verify_approvals(&approvals, &self.topic, &self.owners);
+        let mut new = Vec::new();
+        for approval in approvals.into_iter().filter(|a| valid.contains(&a.endpoint_id)) {
+            let id = approval.endpoint_id;
+            if self.admitted.insert(id) {
+                self.approvals.push(approval);
+                let name = self.pending.iter().position(|(p, _)| *p == id).and_then(|i| self.pending.remove(i));
+                new.push((id, name.map(|(_, name)| name)));
+            }
+        }
+        new
+    }
+
+    /// Make `owner` a co-owner, signing the grant with our own owner's
+    /// `key`. False if we aren't an owner or they already are one.
+    pub fn grant(&mut self, key: &SecretKey, owner: EndpointId) -> bool {
+        if !self.is_owner() || self.owners.contains(&owner) {
+            return false;
+        }
+        self.grants.push(OwnerGrant::sign(key, &self.topic, owner));
+        self.owners.push(owner);
+        // An owner can always take part.
+        self.admitted.insert(owner);
+        true
+    }
+
+    /// Take in the grants from a `GrantOwner`, keeping the ones that chain
+    /// back to the creator. Returns whoever is newly an owner.
+    pub fn accept_grants(&mut self, grants: Vec<OwnerGrant>) -> Vec<EndpointId> {
+        let mut all = self.grants.clone();
+        all.extend(grants.into_iter().filter(|g| !self.owners.contains(&g.owner)));
+        let owners = verify_owners(&all, &self.topic, &self.policy.creator);
+        let new: Vec<EndpointId> = owners.iter().filter(|o| !self.owners.contains(o)).copied().collect();
+        self.grants = all.into_iter().filter(|g| owners.contains(&g.owner) && owners.contains(&g.granted_by) && g.verify(&self.topic)).collect();
+        // Keep one grant per owner.
+        let mut seen = BTreeSet::new();
+        self.grants.retain(|g| seen.insert(g.owner));
+        for owner in &new {
+            self.admitted.insert(*owner);
+        }
+        self.owners = owners;
+        new
+    }
+
+    /// Forget a peer's request after an owner turned it away. Returns
+    /// the name it knocked under, if we saw it.
+    pub fn denied(&mut self, id: &EndpointId) -> Option<String> {
+        let at = self.pending.iter().position(|(p, _)| p == id)?;
+        self.pending.remove(at).map(|(_, name)| name)
+    }
+}
+
+/// Draw the creator's prompt for the oldest waiting knock, centered over
+/// the whole frame. Nothing is drawn when there's nothing to answer.
+pub fn render_prompt(f: &mut ratatui::Frame, admission: Option<&Admission>, lang: Lang, theme: &Theme) {
+    let Some(admission) = admission.filter(|a| a.prompting()) else { return };
+    let (id, name) = &admission.pending[0];
+
+    let area = f.area();
+    let width = 48.min(area.width);
+    let card = Rect::new(area.width.saturating_sub(width) / 2, area.height.saturating_sub(5) / 2, width, 5.min(area.height));
+    f.render_widget(ratatui::widgets::Clear, card);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_set(theme.border_set())
+        .style(Style::default().bg(theme.bg))
+        .border_style(Style::default().fg(theme.title))
+        .title(Line::styled(format!(" {} ", t!(lang, "knock.title")), Style::default().fg(theme.title).add_modifier(Modifier::BOLD)).centered())
+        .title_bottom(
+            Line::from(vec![
+                Span::styled(" y", Style::default().fg(theme.hint_key)),
+                Span::styled(format!(" {}  ", t!(lang, "knock.approve")), Style::default().fg(theme.hint_text)),
+                Span::styled("n", Style::default().fg(theme.hint_key)),
+                Span::styled(format!(" {} ", t!(lang, "knock.deny")), Style::default().fg(theme.hint_text)),
+            ])
+            .centered(),
+        );
+    let waiting = admission.pending.len() - 1;
+    let mut lines = vec![
+        Line::styled(format!("{name} ({})", id.fmt_short()), Style::default().fg(theme.text).add_modifier(Modifier::BOLD)).centered(),
+        Line::styled(t!(lang, "knock.asks_short"), Style::default().fg(theme.text)).centered(),
+    ];
+    if waiting > 0 {
+        lines.push(Line::styled(t!(lang, "knock.more", count = waiting), Style::default().fg(theme.text_muted)).centered());
+    }
+    f.render_widget(Paragraph::new(lines).block(block), card);
+}
+
+// ── Tests ────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(n: u8) -> SecretKey {
+        SecretKey::from_bytes(&[n; 32])
+    }
+
+    fn join(n: u8) -> Message {
+        Message::Join { nickname: format!("peer{n}"), endpoint_id: key(n).public() }
+    }
+
+    #[test]
+    fn open_room_fills_to_its_cap() {
+        let creator = key(1).public();
+        let policy = RoomPolicy { creator, max_members: Some(3), knock: false };
+        let mut admission = Admission::new(policy, TopicId::from_bytes([0; 32]), key(2).public());
+        assert!(admission.let_in());
+
+        // Creator and us take two places; the first newcomer gets the last.
+        assert!(admission.screen(key(3).public(), &join(3)));
+        assert!(!admission.screen(key(4).public(), &join(4)));
+        let chat = Message::Chat { nickname: "peer4".into(), text: "hi".into(), message_id: [0; 16], timestamp_ms: 0 };
+        assert!(!admission.screen(key(4).public(), &chat));
+        assert!(admission.screen(creator, &chat));
+
+        admission.leave(&key(3).public());
+        assert!(admission.screen(key(4).public(), &join(4)));
+    }
+
+    #[test]
+    fn knocks_are_answered_by_the_creator_only() {
+        let topic = TopicId::from_bytes([5; 32]);
+        let policy = RoomPolicy { creator: key(1).public(), max_members: Some(2), knock: true };
+        let mut creator = Admission::new(policy, topic, key(1).public());
+        let mut member = Admission::new(policy, topic, key(2).public());
+        assert!(!member.let_in() && !member.screen(key(2).public(), &join(2)));
+
+        assert_eq!(creator.knock(key(2).public(), "bob".into()), Knock::New);
+        assert_eq!(creator.knock(key(2).public(), "bob".into()), Knock::Again);
+        assert_eq!(creator.knock(key(3).public(), "eve".into()), Knock::New);
+        assert!(creator.prompting());
+        assert_eq!(creator.approve(&key(1)), Some((key(2).public(), "bob".into())));
+        // Two of two places are taken now.
+        assert!(creator.full());
+        assert_eq!(creator.knock(key(4).public(), "dan".into()), Knock::Full);
+
+        // Bob learns he's in; a self-made approval from Eve counts for nothing.
+        let mut approvals = creator.approvals().to_vec();
+        approvals.push(Approval::sign(&key(3), &topic, key(3).public()));
+        assert_eq!(member.accept(approvals), vec![(key(2).public(), None)]);
+        assert!(member.let_in() && !member.admits(&key(3).public()));
+        assert!(!member.prompting());
+    }
+
+    #[test]
+    fn co_owners_answer_knocks_after_the_creator_leaves() {
+        let topic = TopicId::from_bytes([5; 32]);
+        let policy = RoomPolicy { creator: key(1).public(), max_members: None, knock: true };
+        let mut creator = Admission::new(policy, topic, key(1).public());
+        let mut alice = Admission::new(policy, topic, key(2).public());
+        let mut bob = Admission::new(policy, topic, key(3).public());
+
+        // Only an owner can grant, and only once per owner.
+        assert!(!alice.grant(&key(2), key(2).public()));
+        assert!(creator.grant(&key(1), key(2).public()));
+        assert!(!creator.grant(&key(1), key(2).public()));
+        assert_eq!(alice.accept_grants(creator.grants().to_vec()), vec![key(2).public()]);
+        assert!(alice.is_owner() && alice.let_in());
+        assert!(alice.accept_grants(creator.grants().to_vec()).is_empty());
+
+        // With the creator gone, Alice's approval still lets Bob in.
+        assert_eq!(alice.knock(key(3).public(), "bob".into()), Knock::New);
+        assert!(alice.prompting());
+        alice.approve(&key(2));
+        assert!(bob.accept(alice.approvals().to_vec()).is_empty());
+        assert_eq!(bob.accept_grants(alice.grants().to_vec()), vec![key(2).public()]);
+        assert_eq!(bob.accept(alice.approvals().to_vec()), vec![(key(3).public(), None)]);
+        assert!(bob.let_in() && !bob.is_owner());
+
+        // A grant Bob signs for himself is ignored.
+        let forged = OwnerGrant::sign(&key(3), &topic, key(3).public());
+        assert!(creator.accept_grants(vec![forged]).is_empty());
+        assert_eq!(creator.owners(), [key(1).public(), key(2).public()]);
+    }
+
+    #[test]
+    fn snapshot_knock_prompt() {
+        let policy = RoomPolicy { creator: key(1).public(), max_members: None, knock: true };
+        let mut admission = Admission::new(policy, TopicId::from_bytes([5; 32]), key(1).public());
+        admission.knock(key(2).public(), "Bob".into());
+        admission.knock(key(3).public(), "Eve".into());
+
+        let theme = Theme::dark();
+        let mut terminal = ratatui::Terminal::new(ratatui::backend::TestBackend::new(52, 7)).unwrap();
+        terminal.draw(|f| render_prompt(f, Some(&admission), Lang::En, &theme)).unwrap();
+        insta::assert_snapshot!(terminal.backend());
+    }
+}
@@ -0,0 +1,343 @@
+//! Where a node keeps its blobs: on disk, or only in memory.
+//!
+//! By default blobs go into an `FsStore` — a `redb` database plus data
+//! files under the data dir — so our shares survive a restart and a crashed
+//! session can pick them up again. `--mem-store` (or `mem_store = true` in
+//! the config) keeps them in a `MemStore` instead, for ephemeral sessions:
+//! the store writes nothing, and whatever we shared or downloaded into it is
+//! gone when the process exits. Files saved to the download folder are
+//! still saved there; that's where a download is meant to end up.
+//!
+//! Both backends are the same `iroh_blobs::api::Store` underneath. What
+//! differs is captured by `BlobStorage`, and `Blobs` is the cheap handle the
+//! rest of the code passes around, so nothing past `Node::spawn` needs to
+//! know which backend it got.
+//!
+//! ## Shares by reference
+//!
+//! Importing a file normally copies it into the store. With
+//! `reference_min_mb` set, files at least that big are imported by
+//! reference instead (`ImportMode::TryReference`): the store keeps only the
+//! hash tree and reads the data from the original file when serving it, so
+//! a 20 GB video doesn't take another 20 GB. The catch is that the file
+//! must stay as it was. If it's edited the bytes no longer match the hash
+//! and every download fails verification halfway; if it's moved there's
+//! nothing to serve.
+//!
+//! So `Sources` remembers what each referenced file looked like (size and
+//! modification time) when it was imported. Every request for one is
+//! checked against the file first (`access::gate`), and the chat rechecks
+//! them all on its clock tick, so a file that changed is caught before
+//! anyone gets a corrupt transfer: its requests are refused, and the offer
+//! is withdrawn and shown as invalidated in the file pane.
+//!
+//! The in-memory store always copies.
+
+use std::collections::HashMap;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use iroh::EndpointId;
+use iroh_blobs::Hash;
+use iroh_blobs::api::Store;
+use iroh_blobs::store::GcConfig;
+use iroh_blobs::store::fs::{FsStore, options::Options};
+use iroh_blobs::store::mem::{self, MemStore};
+
+use crate::error::{Result, ResultExt};
+
+/// How often the blob store garbage-collects untagged blobs.
+const GC_INTERVAL: Duration = Duration::from_secs(300);
+
+/// Garbage collection deletes blobs no tag points at anymore — a revoked
+/// share, a download already exported to disk — so they don't pile up,
+/// on disk or in memory.
+fn gc() -> GcConfig {
+    GcConfig { interval: GC_INTERVAL, add_protected: None }
+}
+
+/// A blob store backend.
+///
+/// `Send + Sync + 'static` so a `Blobs` handle can move into background
+/// tasks.
+pub trait BlobStorage: Send + Sync + 'static {
+    /// The store's API: adding, reading, tagging and fetching blobs.
+    fn api(&self) -> &Store;
+
+    /// Whether blobs are still there after the process exits.
+    fn persistent(&self) -> bool;
+}
+
+impl BlobStorage for FsStore {
+    fn api(&self) -> &Store {
+        self
+    }
+
+    fn persistent(&self) -> bool {
+        true
+    }
+}
+
+impl BlobStorage for MemStore {
+    fn api(&self) -> &Store {
+        self
+    }
+
+    fn persistent(&self) -> bool {
+        false
+    }
+}
+
+/// A handle to whichever backend the node was spawned with, and the
+/// source files of its blobs imported by reference.
+///
+/// Cloning is cheap (`Arc`s), and it derefs to `Store`, so
+/// `blobs.blobs().add_bytes(..)` reads the same as on a concrete store.
+#[derive(Clone)]
+pub struct Blobs {
+    storage: Arc<dyn BlobStorage>,
+    sources: Sources,
+}
+
+impl Blobs {
+    pub fn new(storage: impl BlobStorage) -> Self {
+        Self { storage: Arc::new(storage), sources: Sources::default() }
+    }
+
+    /// Whether blobs outlive the process (see `BlobStorage::persistent`).
+    pub fn persistent(&self) -> bool {
+        self.storage.persistent()
+    }
+
+    /// Source files of blobs imported by reference.
+    pub fn sources(&self) -> &Sources {
+        &self.sources
+    }
+}
+
+impl Deref for Blobs {
+    type Target = Store;
+
+    fn deref(&self) -> &Store {
+        self.storage.api()
+    }
+}
+
+/// Where `Node::spawn` should keep blobs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Storage<'a> {
+    /// An `FsStore` in a directory of its own under this root.
+    Disk(&'a Path),
+    /// A `MemStore`: nothing written, nothing kept.
+    Memory,
+}
+
+/// A path is a root on disk, so callers that always want one can pass it.
+impl<'a> From<&'a Path> for Storage<'a> {
+    fn from(root: &'a Path) -> Self {
+        Storage::Disk(root)
+    }
+}
+
+impl<'a> From<&'a PathBuf> for Storage<'a> {
+    fn from(root: &'a PathBuf) -> Self {
+        Storage::Disk(root)
+    }
+}
+
+impl Storage<'_> {
+    /// Open the store for endpoint `id`.
+    ///
+    /// On disk, each endpoint gets its own directory under the root, keyed
+    /// by its short ID. This avoids `redb` lock contention when several
+    /// peers run on one machine.
+    pub async fn open(self, id: EndpointId) -> Result<Blobs> {
+        match self {
+            Storage::Disk(root) => {
+                let dir = root.join(id.fmt_short().to_string());
+                // `FsStore::load_with_opts()` opens (or creates) the redb
+                // database at the given path. It's async because it may need
+                // to perform I/O to initialize the database.
+                let options = Options { gc: Some(gc()), ..Options::new(&dir) };
+                let store = FsStore::load_with_opts(dir.join("blobs.db"), options).await.storage()?;
+                Ok(Blobs::new(store))
+            }
+            Storage::Memory => Ok(Blobs::new(MemStore::new_with_opts(mem::Options { gc_config: Some(gc()) }))),
+        }
+    }
+}
+
+// ── Shares by reference ──────────────────────────────────────────────────────
+
+/// What a file imported by reference looked like when it was imported.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Source {
+    pub path: PathBuf,
+    pub size: u64,
+    /// `None` on platforms without modification times; then only the size
+    /// is compared.
+    pub modified: Option<SystemTime>,
+}
+
+impl Source {
+    /// What the file at `path` looks like now.
+    pub fn of(path: &Path) -> std::io::Result<Self> {
+        let meta = std::fs::metadata(path)?;
+        Ok(Self { path: path.to_path_buf(), size: meta.len(), modified: meta.modified().ok() })
+    }
+
+    /// How the file stopped matching, if it has.
+    pub fn check(&self) -> Option<Invalid> {
+        let Ok(now) = Self::of(&self.path) else { return Some(Invalid::Missing) };
+        if now.size != self.size {
+            Some(Invalid::Resized)
+        } else if now.modified != self.modified {
+            Some(Invalid::Modified)
+        } else {
+            None
+        }
+    }
+}
+
+/// Why a referenced file can no longer be served.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Invalid {
+    /// Moved, deleted, or no longer readable.
+    Missing,
+    /// A different size: truncated or appended to.
+    Resized,
+    /// The same size, but written to since.
+    Modified,
+}
+
+impl std::fmt::Display for Invalid {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Invalid::Missing => "moved or deleted",
+            Invalid::Resized => "resized",
+            Invalid::Modified => "modified",
+        })
+    }
+}
+
+/// The blobs whose data lives in a file outside the store, keyed by hash.
+///
+/// A cheap-to-clone handle, like `access::Members`: `share_file` adds to it,
+/// the serving gate and the chat's clock tick check it.
+#[derive(Debug, Clone, Default)]
+pub struct Sources(Arc<Mutex<HashMap<Hash, Watched>>>);
+
+#[derive(Debug)]
+struct Watched {
+    source: Source,
+    /// Set once the file is found changed; it stays refused from then on.
+    invalid: Option<Invalid>,
+    /// Whether `recheck` has returned it yet.
+    reported: bool,
+}
+
+impl Sources {
+    /// Watch `source` as the data of blob `hash`.
+    pub fn watch(&self, hash: Hash, source: Source) {
+        self.lock().insert(hash, Watched { source, invalid: None, reported: false });
+    }
+
+    /// Stop watching `hash`: its offer is gone and its tags deleted.
+    pub fn forget(&self, hash: &Hash) {
+        self.lock().remove(hash);
+    }
+
+    /// Whether `hash` may be served: it isn't a referenced file, or it's
+    /// one that still matches. A changed file is remembered as invalid for
+    /// `recheck` to report.
+    pub fn allows(&self, hash: &Hash) -> bool {
+        let mut watched = self.lock();
+        let Some(entry) = watched.get_mut(hash) else { return true };
+        if entry.invalid.is_none() {
+            entry.invalid = entry.source.check();
+        }
+        entry.invalid.is_none()
+    }
+
+    /// Check every watched file, returning those found changed since the
+    /// last call — here or by `allows` — with why.
+    pub fn recheck(&self) -> Vec<(Hash, Invalid)> {
+        let mut changed = Vec::new();
+        for (hash, entry) in self.lock().iter_mut() {
+            if entry.invalid.is_none() {
+                entry.invalid = entry.source.check();
+            }
+            if let Some(why) = entry.invalid
+                && !entry.reported
+            {
+                entry.reported = true;
+                changed.push((*hash, why));
+            }
+        }
+        changed
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<Hash, Watched>> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn both_backends_store_and_return_blobs() {
+        let dir = tempfile::tempdir().unwrap();
+        let id = iroh::SecretKey::from_bytes(&[4; 32]).public();
+
+        let disk = Storage::from(dir.path()).open(id).await.unwrap();
+        let memory = Storage::Memory.open(id).await.unwrap();
+        assert!(disk.persistent());
+        assert!(!memory.persistent());
+
+        for blobs in [&disk, &memory] {
+            let tag = blobs.blobs().add_bytes(b"hello".to_vec()).await.unwrap();
+            assert_eq!(blobs.blobs().get_bytes(tag.hash).await.unwrap().as_ref(), b"hello");
+        }
+        // Only the disk store left anything behind.
+        assert!(dir.path().join(id.fmt_short().to_string()).join("blobs.db").exists());
+        disk.shutdown().await.unwrap();
+        memory.shutdown().await.unwrap();
+    }
+
+    #[test]
+    fn changed_sources_are_refused_and_reported_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let (kept, edited, moved) = (dir.path().join("kept"), dir.path().join("edited"), dir.path().join("moved"));
+        for path in [&kept, &edited, &moved] {
+            std::fs::write(path, b"0123456789").unwrap();
+        }
+        let hash = |b| Hash::from_bytes([b; 32]);
+        let sources = Sources::default();
+        for (b, path) in [(1, &kept), (2, &edited), (3, &moved)] {
+            sources.watch(hash(b), Source::of(path).unwrap());
+        }
+        assert!(sources.allows(&hash(9)), "not a referenced blob");
+        assert!(sources.recheck().is_empty());
+
+        std::fs::write(&edited, b"0123456789abc").unwrap();
+        std::fs::rename(&moved, dir.path().join("elsewhere")).unwrap();
+        assert!(!sources.allows(&hash(2)));
+        assert!(sources.allows(&hash(1)));
+        let mut changed = sources.recheck();
+        changed.sort_by_key(|(h, _)| *h.as_bytes());
+        assert_eq!(changed, [(hash(2), Invalid::Resized), (hash(3), Invalid::Missing)]);
+        assert!(sources.recheck().is_empty());
+
+        // Putting the file back doesn't make a refused blob servable again.
+        std::fs::rename(dir.path().join("elsewhere"), &moved).unwrap();
+        assert!(!sources.allows(&hash(3)));
+        sources.forget(&hash(3));
+        assert!(sources.allows(&hash(3)));
+    }
+}
@@ -7,13 +7,15 @@
 // `BTreeMap` is an ordered map backed by a B-tree. Unlike `HashMap`, it keeps
 // keys sorted — so the peers panel always displays peers in a consistent
 // (deterministic) order based on their `EndpointId`.
-use std::collections::{BTreeMap, HashSet};
-use std::time::Instant;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::hash::Hasher;
+use std::time::{Duration, Instant};
 
 use crate::net::{HistoryEntry, HistoryEntryKind, MessageId};
 
 // `EndpointId` is a 32-byte public key that uniquely identifies each iroh node.
 use iroh::EndpointId;
+use serde::{Deserialize, Serialize};
 // Ratatui types for building terminal UIs:
 // - `Layout` / `Constraint`: split the terminal into regions (vertical/horizontal)
 // - `Style` / `Color` / `Modifier`: text styling (foreground, bold, italic, etc.)
@@ -32,9 +34,35 @@ use ratatui::{
 // are network types, and `TransferManager` manages file transfer state.
 use ratatui::layout::Rect;
 
+use crate::access::Members;
+use crate::admission::{self, Admission};
+use crate::config::Config;
+use crate::contacts::Contacts;
+use crate::eventlog::{self, EventLog};
+use crate::scratch::Artifacts;
 use crate::filepicker::FilePicker;
+use crate::filter::Filters;
+use crate::fit;
+use crate::highlight::{self, Row, Token};
+use crate::i18n::Lang;
+use crate::input;
+use crate::joining::{self, JoinPhase};
+use crate::palette::{self, Palette};
+use crate::lock::{self, Lock};
+use crate::t;
+use crate::journal::HistoryWriter;
+use crate::gallery::{self, Gallery};
+use crate::map::ConnMap;
+use crate::net::keepalive::Keepalive;
+use crate::net::tracker::{self, Reachability};
 use crate::net::{ConnType, PeerInfo};
-use crate::theme::Theme;
+use crate::node::Paths;
+use crate::prefs::RoomPrefs;
+use crate::seal;
+use crate::spell::Dictionary;
+use crate::theme::{Theme, ThemeMode};
+use crate::timers::{self, Timer, Timers};
+use crate::toast::{self, ToastKind, Toasts};
 use crate::transfer::{self, TransferManager};
 
 // ── App state ────────────────────────────────────────────────────────────────
@@ -55,6 +83,12 @@ pub enum AppMode {
     FilePicker,
     /// The file share pane has focus (navigate with Up/Down, Enter to act).
     FilePane,
+    /// A message in the log is selected (Up/Down to move, Enter to act on it).
+    MessageSelect,
+    /// The `/map` connection map overlay is open.
+    Map,
+    /// The `/files` gallery is open.
+    Gallery,
 }
 
 /// A clickable region tracked by `ui()` for mouse interaction.
@@ -68,9 +102,11 @@ pub enum ClickAction {
     FocusChat,
     FocusFilePane,
     CopyTicket,
-    DownloadTransfer(iroh_blobs::Hash),
-    OpenTransfer(iroh_blobs::Hash),
-    UnshareTransfer(iroh_blobs::Hash),
+    /// The "…and N more" row of the peers pane: show the next page.
+    NextPeersPage,
+    DownloadTransfer(transfer::EntryId),
+    OpenTransfer(transfer::EntryId),
+    UnshareTransfer(transfer::EntryId),
 }
 
 /// A single line in the chat message log.
@@ -80,21 +116,54 @@ pub enum ClickAction {
 /// - `System(String)` is a *tuple variant* — it wraps a single unnamed value.
 /// - `Chat { nickname, text }` is a *struct variant* — it has named fields.
 ///
-/// Pattern matching on this enum (in `ui()`) forces you to handle all variants
+/// Pattern matching on this enum (in `push_message_lines()`) forces you to handle all variants
 /// at compile time — the compiler won't let you forget one (exhaustiveness checking).
 pub enum ChatLine {
     /// System notification (e.g. "peer connected", "waiting for peers...")
     System(String),
     /// The room's shareable ticket string, displayed prominently
     Ticket(String),
-    /// A chat message from a peer, with their display name
+    /// A chat message from a peer, with their display name. Messages longer
+    /// than `LARGE_TEXT_LINES` render as a collapsed code block until
+    /// `expanded` is toggled on (select the message and press Enter).
     Chat {
         nickname: String,
         text: String,
         timestamp_ms: u64,
+        expanded: bool,
+        /// Identifies the message, so thread replies can point at it.
+        message_id: MessageId,
+        /// The root of the thread this was posted in, `None` in the main
+        /// flow. Replies show only in the thread view; the main pane shows
+        /// a "↳ N replies" line under the root instead.
+        thread: Option<MessageId>,
+        /// The message this one quote-replies to, shown dimmed above it.
+        quote: Option<Quote>,
+    },
+    /// A spoiler message, rendered as redaction blocks until `revealed` is
+    /// toggled on by the local viewer. The flag is purely local render state.
+    Spoiler {
+        nickname: String,
+        text: String,
+        timestamp_ms: u64,
+        revealed: bool,
     },
 }
 
+/// The quoted message above a reply. Resolved from our log when the reply
+/// arrives, since the wire only carries the quoted message's ID.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Quote {
+    pub message_id: MessageId,
+    /// Who wrote the quoted message; empty if it isn't in our log.
+    pub nickname: String,
+    /// Its first line, shortened to `QUOTE_EXCERPT` characters.
+    pub excerpt: String,
+}
+
+/// Longest quote excerpt, in characters, before it's cut with "…".
+pub const QUOTE_EXCERPT: usize = 40;
+
 /// The main application state for the chat session.
 ///
 /// All fields are `pub` because `main.rs` reads and writes them directly
@@ -110,22 +179,52 @@ pub struct App {
     pub messages: Vec<ChatLine>,
     /// The current text being typed by the user (not yet sent).
     pub input: String,
-    /// Cursor position within `input`, measured in bytes (safe because we only
-    /// insert ASCII-range characters one at a time from keyboard input).
+    /// Cursor position within `input`, measured in bytes. Always kept on a
+    /// grapheme boundary by the helpers in `crate::input`.
     pub cursor_pos: usize,
     /// Set to `true` when the user presses Esc — the event loop checks this
     /// after each iteration and breaks if true.
     pub should_quit: bool,
-    /// Connected peers keyed by their endpoint ID.
+    /// Connected peers keyed by their endpoint ID. Add and remove through
+    /// `add_peer` / `remove_peer`, which keep it within `MAX_PEERS`.
     pub peers: BTreeMap<EndpointId, PeerInfo>,
+    /// Peers that connected but never sent a `Join`, oldest first. These are
+    /// the first evicted when `peers` is full.
+    pub anonymous_peers: VecDeque<EndpointId>,
+    /// The same IDs as `peers`, shared with the blobs protocol so that
+    /// `serve_to = "members"` can check who's in the room (see `access`).
+    pub members: Members,
+    /// Page of the peers pane shown when the list doesn't fit; clicking the
+    /// "…and N more" row advances it.
+    pub peers_page: usize,
+    /// The order peers were added in, for `PeerSort::Joined`. A count
+    /// rather than a time, so peers who join in the same millisecond
+    /// still keep their order.
+    joined: HashMap<EndpointId, u64>,
+    joins: u64,
     /// Which UI element currently has keyboard focus.
     pub mode: AppMode,
     /// The modal file picker (present only while the overlay is open).
     /// `Option<FilePicker>` is Rust's null-safe pattern — `None` means the
     /// picker is closed, `Some(picker)` means it's open. No null pointers.
     pub file_picker: Option<FilePicker>,
+    /// The `/map` overlay (present only while open).
+    pub map: Option<ConnMap>,
+    /// The `/files` gallery (present only while open).
+    pub gallery: Option<Gallery>,
+    /// When the room last had traffic; the status clock tick sends a
+    /// `KeepAlive` once it has been quiet for `keepalive_secs`.
+    pub keepalive: Keepalive,
     /// All file transfer entries (sent and received).
     pub transfers: TransferManager,
+    /// Transfer notices waiting to flash on the input bar's border.
+    pub toasts: Toasts,
+    /// Shared `/timer` countdowns still running, soonest first.
+    pub timers: Timers,
+    /// The status line's clock and session time, e.g. "14:05 · 1h 12m".
+    /// Refreshed by the main loop's once-a-second clock tick; `None` until
+    /// then, or when `status_clock` is off.
+    pub clock: Option<String>,
     /// The active color theme (dark or light), toggled with Ctrl+T.
     pub theme: Theme,
     /// Serializable history log for sync with new peers.
@@ -140,11 +239,129 @@ pub struct App {
     pub click_regions: Vec<ClickRegion>,
     /// The room's ticket string, stored for clipboard copy.
     pub ticket_str: Option<String>,
+    /// The room's name from its ticket, shown in the chat pane's title.
+    pub room_name: Option<String>,
+    /// The network paths this session is limited to, shown on the status
+    /// line unless it's the default of both.
+    pub paths: Paths,
     /// When set, the copy button shows "Copied!" until this instant.
     pub copy_feedback_until: Option<Instant>,
     /// When set, the next file picker selection will send a targeted offer
     /// to this nickname instead of broadcasting to all peers.
     pub pending_send_target: Option<String>,
+    /// A multi-line bracketed paste awaiting confirmation. Enter sends it as
+    /// one message, Esc discards it.
+    pub pending_paste: Option<String>,
+    /// Index into `messages` of the selected message while in
+    /// `AppMode::MessageSelect`. Only chat-style lines are selectable.
+    pub selected_message: Option<usize>,
+    /// An in-flight `/who` roll call collecting `Here` replies.
+    pub roll_call: Option<RollCall>,
+    /// Persistent settings. `main.rs` loads it at startup and saves it when
+    /// a remembered value changes; `App::new()` starts from defaults so tests
+    /// never touch the user's real config file.
+    pub config: Config,
+    /// Word list for input-bar spellcheck; `None` when disabled in config or
+    /// no dictionary could be loaded.
+    pub spell: Option<Dictionary>,
+    /// Filters run on chat text in both directions. Built from config at
+    /// startup; register more here to extend the session.
+    pub filters: Filters,
+    /// Set when the input was restored from a saved draft; shows a "draft"
+    /// label on the input bar until the message is sent.
+    pub draft_restored: bool,
+    /// Wall-clock time (unix ms) as of the last UI tick. Relative timestamps
+    /// are computed against this rather than the live clock, so they refresh
+    /// on the tick branch and render deterministically in tests.
+    pub now_ms: u64,
+    /// Away message set by `/afk`. While set, the first message from each
+    /// peer that @mentions us gets one automatic reply. Any keypress clears it.
+    pub afk: Option<String>,
+    /// This room's `/mute` and `/focus` settings (saved in `prefs`).
+    pub room_prefs: RoomPrefs,
+    /// Our nickname and the configured highlight words; messages matching
+    /// them are drawn bold in the accent color and listed by `/mentions`.
+    pub highlights: Highlights,
+    /// Peers (by nickname) already sent the AFK auto-reply this time away.
+    pub afk_replied: HashSet<String>,
+    /// The neighbor we asked for a lag backfill, while waiting on its answer.
+    /// Backfill responses from anyone else are ignored.
+    pub backfill_from: Option<EndpointId>,
+    /// Address book of peers we've met. `main.rs` loads it at startup and
+    /// saves it on quit; `App::new()` starts empty.
+    pub contacts: Contacts,
+    /// Blobs made for our shares (see `scratch`). `main.rs` loads the list
+    /// at startup and deletes them on quit; `App::new()` starts empty.
+    pub artifacts: Artifacts,
+    /// Rendered message lines kept between frames (see `LineCache`).
+    pub line_cache: LineCache,
+    /// Background writer journaling new history entries while in a room;
+    /// `None` when journaling is off (tests, encrypted archives).
+    pub journal: Option<HistoryWriter>,
+    /// The `--json-log` writer, if one was asked for (see `eventlog`).
+    pub event_log: Option<EventLog>,
+    /// Who's let in, for a room whose ticket carries a `RoomPolicy`;
+    /// `None` in an open room.
+    pub admission: Option<Admission>,
+    /// Whether we're still waiting for a first neighbor after joining, and
+    /// whether the overlay for a join that stayed empty is up.
+    pub joining: JoinPhase,
+    /// The Ctrl+K command palette, while it's open.
+    pub palette: Option<Palette>,
+    /// The session lock, while the screen is locked (or a passphrase is
+    /// being chosen for it).
+    pub lock: Option<Lock>,
+    /// Thread index: how many replies each thread has, keyed by the
+    /// `message_id` of its root.
+    pub threads: HashMap<MessageId, usize>,
+    /// The thread whose view is open (by root ID). While it's set,
+    /// messages we send go into that thread.
+    pub open_thread: Option<MessageId>,
+    /// The message our next message quote-replies to (`r` in message
+    /// select); Esc drops it.
+    pub replying_to: Option<Quote>,
+}
+
+/// Most peers tracked per room. Beyond this, peers that never identified
+/// themselves are evicted (see `App::add_peer`).
+pub const MAX_PEERS: usize = 256;
+
+/// Messages with more lines than this are "large": received ones render as a
+/// collapsed code block, and pasting one offers to send it as an attachment.
+pub const LARGE_TEXT_LINES: usize = 20;
+
+/// Lines of a collapsed code block shown before the "… more" footer.
+const CODE_PREVIEW_LINES: usize = 5;
+
+/// Whether `text` has more than `LARGE_TEXT_LINES` lines.
+pub fn is_large_text(text: &str) -> bool {
+    text.lines().count() > LARGE_TEXT_LINES
+}
+
+/// How many of our latest message IDs a backfill request carries.
+const BACKFILL_KNOWN: usize = 64;
+
+/// Most history entries one backfill response may carry.
+const MAX_BACKFILL_ENTRIES: usize = 200;
+
+/// How long a `/who` roll call waits for replies before printing the roster.
+pub const ROLL_CALL_WINDOW: Duration = Duration::from_secs(3);
+
+/// Columns of a message shown per row of the `/mentions` list.
+const MENTION_EXCERPT: usize = 48;
+
+/// State for an in-flight `/who` roll call.
+///
+/// Replies are keyed by endpoint ID so a peer answering twice (e.g. the
+/// gossip layer delivering a duplicate) is only listed once.
+pub struct RollCall {
+    /// Matches replies to this particular request.
+    pub request_id: MessageId,
+    /// When the request was broadcast — the roster is printed once
+    /// `ROLL_CALL_WINDOW` has elapsed.
+    pub started: Instant,
+    /// `(nickname, round-trip ms)` for each peer that answered.
+    pub replies: BTreeMap<EndpointId, (String, u64)>,
 }
 
 /// The `impl` block contains methods associated with the `App` type.
@@ -166,8 +383,19 @@ impl App {
             cursor_pos: 0,
             should_quit: false,
             peers: BTreeMap::new(),
+            anonymous_peers: VecDeque::new(),
+            members: Members::default(),
+            peers_page: 0,
+            joined: HashMap::new(),
+            joins: 0,
             mode: AppMode::Chat,
             file_picker: None,
+            map: None,
+            gallery: None,
+            keepalive: Keepalive::new(0, Instant::now()),
+            toasts: Toasts::default(),
+            timers: Timers::default(),
+            clock: None,
             transfers: TransferManager::new(),
             theme: Theme::dark(),
             history: Vec::new(),
@@ -176,24 +404,118 @@ impl App {
             scroll_offset: 0,
             click_regions: Vec::new(),
             ticket_str: None,
+            room_name: None,
+            paths: Paths::default(),
             copy_feedback_until: None,
             pending_send_target: None,
+            pending_paste: None,
+            selected_message: None,
+            roll_call: None,
+            config: Config::default(),
+            spell: None,
+            filters: Filters::default(),
+            draft_restored: false,
+            now_ms: 0,
+            afk: None,
+            room_prefs: RoomPrefs::default(),
+            highlights: Highlights::default(),
+            afk_replied: HashSet::new(),
+            backfill_from: None,
+            contacts: Contacts::default(),
+            artifacts: Artifacts::default(),
+            line_cache: LineCache::default(),
+            journal: None,
+            event_log: None,
+            admission: None,
+            joining: JoinPhase::Idle,
+            palette: None,
+            lock: None,
+            threads: HashMap::new(),
+            open_thread: None,
+            replying_to: None,
         }
     }
 
     /// Open the modal file picker overlay.
     ///
-    /// `if let Ok(picker) = FilePicker::new()` is a *refutable pattern* — it
-    /// tries to construct the picker and only sets it if construction succeeded.
-    /// If the current directory is unreadable, the picker silently fails to open
+    /// The picker starts in the directory the last file was shared from
+    /// (`config.last_share_dir`), falling back to the current working
+    /// directory if there is none or it has since been removed.
+    ///
+    /// `if let Ok(picker) = ...` is a *refutable pattern* — it tries to
+    /// construct the picker and only sets it if construction succeeded.
+    /// If the directory is unreadable, the picker silently fails to open
     /// (a more robust app would show an error message).
     pub fn open_file_picker(&mut self) {
-        if let Ok(picker) = FilePicker::new(&self.theme) {
+        let remembered = self
+            .config
+            .last_share_dir
+            .clone()
+            .and_then(|dir| FilePicker::open_at(dir).ok());
+        if let Some(picker) = remembered.or_else(|| FilePicker::new().ok()) {
             self.file_picker = Some(picker);
             self.mode = AppMode::FilePicker;
         }
     }
 
+    /// Put a saved draft back in the input bar, cursor at the end.
+    pub fn restore_draft(&mut self, text: &str) {
+        self.input = text.to_string();
+        self.cursor_pos = self.input.len();
+        self.draft_restored = true;
+    }
+
+    /// Put `text` in the input bar with the cursor at its end, for a
+    /// command the user finishes typing (`e` in the join overlay).
+    pub fn prefill(&mut self, text: &str) {
+        self.input = text.to_string();
+        self.cursor_pos = self.input.len();
+    }
+
+    /// Whether the input bar should carry the "draft" label: a restored
+    /// draft, or unsent text left behind while focus is elsewhere.
+    pub fn has_draft(&self) -> bool {
+        !self.input.is_empty() && (self.draft_restored || !matches!(self.mode, AppMode::Chat))
+    }
+
+    /// Go AFK with `message` as the auto-reply.
+    pub fn set_afk(&mut self, message: String) {
+        self.system(t!(self.config.lang, "afk.on", message = message));
+        self.afk = Some(message);
+        self.afk_replied.clear();
+    }
+
+    /// Leave AFK mode, if in it. Called on every keypress.
+    pub fn clear_afk(&mut self) {
+        if self.afk.take().is_some() {
+            self.afk_replied.clear();
+            self.system(t!(self.config.lang, "afk.off"));
+        }
+    }
+
+    /// The auto-reply to send for a message `text` from `from`, if we're AFK,
+    /// it @mentions `our_nickname`, and `from` hasn't already had one.
+    pub fn afk_reply(&mut self, from: &str, text: &str, our_nickname: &str) -> Option<String> {
+        let message = self.afk.as_ref()?;
+        if from == our_nickname || !mentions(text, our_nickname) {
+            return None;
+        }
+        self.afk_replied
+            .insert(from.to_string())
+            .then(|| t!(self.config.lang, "afk.reply", nickname = our_nickname, message = message))
+    }
+
+    /// Remember the directory of a file just shared, so the next picker opens
+    /// there. Returns `true` if it changed (and the config should be saved).
+    pub fn remember_share_dir(&mut self, file: &std::path::Path) -> bool {
+        let dir = file.parent().map(std::path::Path::to_path_buf);
+        if dir.is_none() || dir == self.config.last_share_dir {
+            return false;
+        }
+        self.config.last_share_dir = dir;
+        true
+    }
+
     /// Close the file picker overlay and return to chat mode.
     ///
     /// Setting `file_picker` to `None` drops the `FilePicker` value — Rust's
@@ -203,13 +525,50 @@ impl App {
         self.mode = AppMode::Chat;
     }
 
+    /// Open the `/map` connection map overlay.
+    pub fn open_map(&mut self) {
+        self.selected_message = None;
+        self.map = Some(ConnMap::new());
+        self.mode = AppMode::Map;
+    }
+
+    /// Close the connection map and return to chat mode.
+    pub fn close_map(&mut self) {
+        self.map = None;
+        self.mode = AppMode::Chat;
+    }
+
+    /// Open the `/files` gallery.
+    pub fn open_gallery(&mut self) {
+        self.selected_message = None;
+        self.gallery = Some(Gallery::new());
+        self.mode = AppMode::Gallery;
+    }
+
+    /// Close the gallery and return to chat mode.
+    pub fn close_gallery(&mut self) {
+        self.gallery = None;
+        self.mode = AppMode::Chat;
+    }
+
+    /// What the gallery lists: every file offered in the room (see
+    /// `gallery::items`). Empty until we're in the peers list ourselves.
+    pub fn gallery_items(&self) -> Vec<gallery::Item> {
+        let Some((our_id, us)) = self.peers.iter().find(|(_, p)| matches!(p.conn_type, ConnType::You)) else {
+            return Vec::new();
+        };
+        gallery::items(&self.history, &self.transfers, &us.name, *our_id, self.members.room())
+    }
+
     /// Move focus to the file share pane.
     pub fn focus_file_pane(&mut self) {
+        self.selected_message = None;
         self.mode = AppMode::FilePane;
     }
 
     /// Return focus to chat input.
     pub fn focus_chat(&mut self) {
+        self.selected_message = None;
         self.mode = AppMode::Chat;
     }
 
@@ -226,11 +585,229 @@ impl App {
         self.messages.push(ChatLine::System(msg.into()));
     }
 
+    /// Raise a toast for a transfer outcome — unless the files pane has
+    /// focus, where the entry itself shows what happened. Rings the bell
+    /// too when `toast_bell` is on.
+    pub fn transfer_toast(&mut self, text: String, kind: ToastKind) {
+        if matches!(self.mode, AppMode::FilePane) {
+            return;
+        }
+        self.notify(text, kind);
+    }
+
+    /// Show a toast, with the bell when `toast_bell` is on — or nothing,
+    /// in a muted room.
+    fn notify(&mut self, text: String, kind: ToastKind) {
+        if self.room_prefs.muted {
+            return;
+        }
+        self.toasts.push(text, kind, Instant::now());
+        if self.config.toast_bell {
+            toast::bell();
+        }
+    }
+
+    /// `/mute`: toggle the bell and toasts for this room.
+    pub fn toggle_mute(&mut self) {
+        self.room_prefs.muted = !self.room_prefs.muted;
+        let key = if self.room_prefs.muted { "mute.on" } else { "mute.off" };
+        self.system(self.config.lang.text(key));
+    }
+
+    /// `/focus`: toggle hiding system messages and the peers pane.
+    pub fn toggle_focus(&mut self) {
+        self.room_prefs.focus = !self.room_prefs.focus;
+        let key = if self.room_prefs.focus { "focus.on" } else { "focus.off" };
+        self.system(self.config.lang.text(key));
+    }
+
+    /// Start a `/timer` countdown, ours or a peer's, with a notice.
+    /// A timer already running (or already over) is ignored.
+    pub fn start_timer(&mut self, timer: Timer) {
+        let notice = t!(
+            self.config.lang,
+            "timer.started",
+            name = timer.set_by,
+            label = timer.label,
+            time = timers::format_remaining(timer.ends_ms.saturating_sub(self.now_ms))
+        );
+        if self.timers.schedule(timer, self.now_ms) {
+            self.system(notice);
+        }
+    }
+
+    /// Announce every timer that has run out by `now_ms`: a chat line and
+    /// a toast (see `notify`).
+    pub fn finish_timers(&mut self, now_ms: u64) {
+        for timer in self.timers.due(now_ms) {
+            let text = t!(self.config.lang, "timer.done", label = timer.label, name = timer.set_by);
+            self.system(text.clone());
+            self.notify(text, ToastKind::Success);
+        }
+    }
+
+    /// Refresh the status line's clock: the time at `now_ms` and how long
+    /// the session has run.
+    pub fn update_clock(&mut self, now_ms: u64, session: Duration) {
+        self.clock = self.config.status_clock.then(|| {
+            format!("{} · {}", format_timestamp(now_ms), format_session(session))
+        });
+    }
+
     /// Append a ticket display line to the message log.
     pub fn ticket(&mut self, ticket: impl Into<String>) {
         self.messages.push(ChatLine::Ticket(ticket.into()));
     }
 
+    /// Add or update a peer, keeping `peers` within `MAX_PEERS`.
+    ///
+    /// `identified` is true once the peer has told us its nickname (a
+    /// `Join`). A hostile peer can connect under endless fresh endpoint IDs,
+    /// so when the map is full a new peer takes the slot of the oldest one
+    /// that never identified itself; if every slot belongs to an identified
+    /// peer, the newcomer is turned away. Returns whether it was added.
+    pub fn add_peer(&mut self, id: EndpointId, info: PeerInfo, identified: bool) -> bool {
+        if !self.peers.contains_key(&id) && self.peers.len() >= MAX_PEERS {
+            let Some(stale) = self.anonymous_peers.pop_front() else {
+                return false;
+            };
+            self.peers.remove(&stale);
+            self.members.remove(&stale);
+            self.joined.remove(&stale);
+        }
+        if identified {
+            self.anonymous_peers.retain(|p| *p != id);
+        } else if !self.anonymous_peers.contains(&id) {
+            self.anonymous_peers.push_back(id);
+        }
+        self.peers.insert(id, info);
+        self.members.insert(id);
+        if let std::collections::hash_map::Entry::Vacant(slot) = self.joined.entry(id) {
+            self.joins += 1;
+            slot.insert(self.joins);
+        }
+        true
+    }
+
+    /// Remove a peer that left, returning its info if it was known.
+    pub fn remove_peer(&mut self, id: &EndpointId) -> Option<PeerInfo> {
+        self.anonymous_peers.retain(|p| p != id);
+        self.members.remove(id);
+        self.joined.remove(id);
+        self.peers.remove(id)
+    }
+
+    /// The peers in the order the peers pane shows them: us first, then
+    /// verified contacts, then everyone else, each group in the order
+    /// `config.peer_sort` picks. Ties go by endpoint ID, so the order is
+    /// stable from frame to frame.
+    pub fn peer_order(&self) -> Vec<EndpointId> {
+        let mut peers: Vec<(&EndpointId, &PeerInfo)> = self.peers.iter().collect();
+        let verified = |id: &EndpointId| self.contacts.get(id).is_some_and(|c| c.verified);
+        // Peers added straight to the map (never through `add_peer`) sort
+        // after everyone with a place in the join order.
+        let joined = |id: &EndpointId| self.joined.get(id).copied().unwrap_or(u64::MAX);
+        peers.sort_by(|(a_id, a), (b_id, b)| {
+            let group = |id, peer: &PeerInfo| (!matches!(peer.conn_type, ConnType::You), !verified(id));
+            group(*a_id, a).cmp(&group(*b_id, b)).then_with(|| match self.config.peer_sort {
+                PeerSort::Joined => joined(a_id).cmp(&joined(b_id)),
+                PeerSort::Name => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+                // Peers never measured go last.
+                PeerSort::Latency => a.rtt_ms.unwrap_or(u64::MAX).cmp(&b.rtt_ms.unwrap_or(u64::MAX)),
+                PeerSort::Connection => conn_rank(&a.conn_type).cmp(&conn_rank(&b.conn_type)),
+            })
+        });
+        peers.into_iter().map(|(id, _)| *id).collect()
+    }
+
+    /// The name to show for `nickname` from `from`: tagged with a short ID
+    /// (`bob#a1b2`) when another peer in the room goes by the same name,
+    /// so the two can be told apart.
+    pub fn display_name(&self, from: &EndpointId, nickname: String) -> String {
+        if self.peers.iter().any(|(id, p)| id != from && p.name == nickname) {
+            tag_name(&nickname, from)
+        } else {
+            nickname
+        }
+    }
+
+    /// Warn when a joining peer takes a name someone in the room already
+    /// has. Each side gets the warning from the other's `Join`, so both
+    /// users hear about it.
+    pub fn warn_name_clash(&mut self, from: &EndpointId, nickname: &str) {
+        let clash = self.peers.iter().find(|(id, p)| *id != from && p.name == nickname);
+        let Some((other, peer)) = clash else { return };
+        let text = if matches!(peer.conn_type, ConnType::You) {
+            t!(self.config.lang, "nick.clash_you", name = nickname, shown = tag_name(nickname, from))
+        } else {
+            t!(self.config.lang, "nick.clash", name = nickname, first = tag_name(nickname, other), second = tag_name(nickname, from))
+        };
+        self.system(text);
+    }
+
+    /// Switch the peers pane to the next `PeerSort` (Ctrl+O).
+    pub fn cycle_peer_sort(&mut self) {
+        self.config.peer_sort = self.config.peer_sort.next();
+        self.peers_page = 0;
+    }
+
+    /// The machine slept for `slept` (see `net::wake`). Every connection
+    /// may be dead, so the peers pane is cleared down to ourselves rather
+    /// than left showing stale paths; the caller re-subscribes to the room,
+    /// and whoever is still there comes back through `NeighborUp`.
+    pub fn resumed(&mut self, slept: Duration) {
+        self.peers.retain(|_, p| matches!(p.conn_type, ConnType::You));
+        self.anonymous_peers.clear();
+        self.peers_page = 0;
+        self.system(t!(self.config.lang, "wake.resumed", away = format_session(slept)));
+    }
+
+    /// Reset per-room state to enter another room (used by `/dm` and `/join`).
+    ///
+    /// Messages, peers (except ourselves), history, transfers and any
+    /// in-flight prompt belong to the room being left; settings, contacts,
+    /// theme and the typed input carry over.
+    pub fn enter_room(&mut self, ticket: String) {
+        let ours = self.peers.iter().find(|(_, p)| matches!(p.conn_type, ConnType::You));
+        let ours = ours.map(|(id, p)| {
+            (*id, PeerInfo { name: p.name.clone(), conn_type: ConnType::You, rtt_ms: None, addr: None })
+        });
+        self.peers = ours.into_iter().collect();
+        self.anonymous_peers.clear();
+        self.joined.clear();
+        self.peers_page = 0;
+        self.messages.clear();
+        self.history.clear();
+        self.seen_ids.clear();
+        self.history_synced = false;
+        self.transfers = TransferManager::new();
+        self.scroll_offset = 0;
+        self.selected_message = None;
+        self.roll_call = None;
+        self.pending_paste = None;
+        self.pending_send_target = None;
+        self.backfill_from = None;
+        self.admission = None;
+        self.threads.clear();
+        self.open_thread = None;
+        self.replying_to = None;
+        self.timers.clear();
+        self.joining = JoinPhase::Idle;
+        self.mode = AppMode::Chat;
+        self.ticket(ticket.clone());
+        self.ticket_str = Some(ticket);
+    }
+
+    /// The endpoint ID to open a DM with for `name`: a peer in the current
+    /// room first, otherwise a saved contact.
+    pub fn dm_target(&self, name: &str) -> Option<EndpointId> {
+        self.peers
+            .iter()
+            .find(|(_, p)| !matches!(p.conn_type, ConnType::You) && p.name.eq_ignore_ascii_case(name))
+            .map(|(id, _)| *id)
+            .or_else(|| self.contacts.find(name).map(|(id, _)| id))
+    }
+
     /// Append a chat message to the message log and history.
     pub fn chat(
         &mut self,
@@ -244,6 +821,10 @@ impl App {
             nickname: nickname.clone(),
             text: text.clone(),
             timestamp_ms,
+            expanded: false,
+            message_id,
+            thread: None,
+            quote: None,
         });
         self.push_history(HistoryEntry {
             message_id,
@@ -252,197 +833,1560 @@ impl App {
         });
     }
 
+    /// Append a quote-reply to `reply_to` to the message log and history.
+    pub fn reply(
+        &mut self,
+        nickname: String,
+        text: String,
+        message_id: MessageId,
+        timestamp_ms: u64,
+        reply_to: MessageId,
+    ) {
+        self.seen_ids.insert(message_id);
+        let quote = quote_of(&self.messages, reply_to);
+        self.messages.push(ChatLine::Chat {
+            nickname: nickname.clone(),
+            text: text.clone(),
+            timestamp_ms,
+            expanded: false,
+            message_id,
+            thread: None,
+            quote: Some(quote),
+        });
+        self.push_history(HistoryEntry {
+            message_id,
+            timestamp_ms,
+            kind: HistoryEntryKind::Reply { nickname, text, reply_to },
+        });
+    }
 
-    /// Push a history entry, capping at 1000 entries.
-    pub fn push_history(&mut self, entry: HistoryEntry) {
-        self.history.push(entry);
-        if self.history.len() > 1000 {
-            self.history.remove(0);
-        }
+    /// Append a reply in the thread rooted at `thread_id` to the message
+    /// log and history, and count it in the thread index.
+    pub fn thread_chat(
+        &mut self,
+        nickname: String,
+        text: String,
+        message_id: MessageId,
+        timestamp_ms: u64,
+        thread_id: MessageId,
+    ) {
+        self.seen_ids.insert(message_id);
+        *self.threads.entry(thread_id).or_default() += 1;
+        self.messages.push(ChatLine::Chat {
+            nickname: nickname.clone(),
+            text: text.clone(),
+            timestamp_ms,
+            expanded: false,
+            message_id,
+            thread: Some(thread_id),
+            quote: None,
+        });
+        self.push_history(HistoryEntry {
+            message_id,
+            timestamp_ms,
+            kind: HistoryEntryKind::ThreadChat { nickname, text, thread_id },
+        });
     }
-}
 
-// ── UI ───────────────────────────────────────────────────────────────────────
-//
-// Ratatui uses an "immediate mode" rendering model: every frame, we build up
-// the entire UI from scratch based on current state. No retained widget tree,
-// no diffing — just draw what the state says. This is simple and fast for TUIs.
 
-/// Format a unix timestamp (ms) as `HH:MM` UTC.
-fn format_timestamp(ts_ms: u64) -> String {
-    let secs = (ts_ms / 1000) as i64;
-    let hours = (secs / 3600) % 24;
-    let minutes = (secs / 60) % 60;
-    format!("{hours:02}:{minutes:02}")
-}
+    /// Append a spoiler message to the message log and history.
+    pub fn spoiler(
+        &mut self,
+        nickname: String,
+        text: String,
+        message_id: MessageId,
+        timestamp_ms: u64,
+    ) {
+        self.seen_ids.insert(message_id);
+        self.messages.push(ChatLine::Spoiler {
+            nickname: nickname.clone(),
+            text: text.clone(),
+            timestamp_ms,
+            revealed: false,
+        });
+        self.push_history(HistoryEntry {
+            message_id,
+            timestamp_ms,
+            kind: HistoryEntryKind::Spoiler { nickname, text },
+        });
+    }
 
-/// Render the chat UI into a terminal frame.
-///
-/// Takes `&mut App` because it rebuilds `click_regions` each frame.
-///
-/// `ratatui::Frame` is a mutable drawing surface for one frame. It provides
-/// `render_widget()` to place widgets at specific screen rectangles, and
-/// `set_cursor_position()` to show the blinking cursor.
-pub fn ui(f: &mut ratatui::Frame, app: &mut App) {
-    // Clear click regions — they're rebuilt every frame.
-    app.click_regions.clear();
-    // Paint the full-screen background so the theme bg covers the terminal area.
-    let bg_block = Block::default().style(Style::default().bg(app.theme.bg));
-    f.render_widget(bg_block, f.area());
+    // ── Message selection ────────────────────────────────────────────────
 
-    // Build the vertical layout — conditionally include the file pane row when
-    // there are active offers/transfers. This demonstrates ratatui's `Layout`
-    // system: you specify constraints (Min, Length, Percentage) and the layout
-    // engine computes the actual pixel dimensions. `split()` returns a `Vec<Rect>`.
-    let rows = if app.transfers.has_entries() {
-        // Dynamic height: number of entries + 2 for the border, capped at 8.
-        let file_pane_height = (app.transfers.entries.len() as u16 + 2).min(8);
-        Layout::vertical([
-            Constraint::Min(1),                    // Messages pane (fills remaining space)
-            Constraint::Length(file_pane_height),   // File pane (fixed height)
-            Constraint::Length(3),                  // Input bar (3 rows: border + text + border)
-        ])
-        .split(f.area())
-    } else {
-        // No file transfers — just messages and input.
-        Layout::vertical([Constraint::Min(1), Constraint::Length(3)]).split(f.area())
-    };
-    // Split the top row into left (messages, flexible) and right (peers, 24 cols).
-    // `Layout::horizontal` works the same as vertical but splits left-to-right.
-    let top = Layout::horizontal([Constraint::Min(1), Constraint::Length(24)]).split(rows[0]);
+    /// Enter message-select mode on the newest selectable message.
+    /// Does nothing if there are no chat messages yet.
+    pub fn enter_message_select(&mut self) {
+        if let Some(idx) = self.messages.iter().rposition(is_selectable) {
+            self.selected_message = Some(idx);
+            self.mode = AppMode::MessageSelect;
+        }
+    }
 
-    // ── Messages pane (top left) ─────────────────────────────────────────
+    /// Leave message-select mode and return focus to the input bar.
+    pub fn exit_message_select(&mut self) {
+        self.selected_message = None;
+        self.mode = AppMode::Chat;
+    }
 
-    // Transform each `ChatLine` into a styled ratatui `Line` using iterators.
-    //
-    // `.iter()` borrows each element; `.map()` transforms it; `.collect()`
-    // gathers results into a `Vec<Line>`. This is Rust's iterator chain
-    // pattern — lazy evaluation, zero allocation overhead (the compiler fuses
-    // the iterator chain into a single loop).
-    let theme = &app.theme;
-    let mut lines: Vec<Line> = Vec::new();
-    for msg in &app.messages {
-        match msg {
-            ChatLine::System(text) => {
-                lines.push(Line::from(Span::styled(
-                    format!("[system] {text}"),
-                    Style::default()
-                        .fg(theme.text_dim)
-                        .add_modifier(Modifier::ITALIC),
-                )));
-            }
-            ChatLine::Ticket(ticket) => {
-                lines.push(Line::from(vec![
-                    Span::styled(
-                        "Ticket: ",
-                        Style::default()
-                            .fg(theme.ticket_label)
-                            .add_modifier(Modifier::BOLD),
-                    ),
-                    Span::styled(ticket.as_str(), Style::default().fg(theme.ticket_value)),
-                ]));
-            }
-            ChatLine::Chat {
-                nickname,
-                text,
-                timestamp_ms,
-            } => {
-                let ts = format_timestamp(*timestamp_ms);
-                lines.push(Line::from(vec![
-                    Span::styled(
-                        format!("{ts} "),
-                        Style::default().fg(theme.timestamp),
-                    ),
-                    Span::styled(
-                        nickname.as_str(),
-                        Style::default()
-                            .fg(theme.nickname)
-                            .add_modifier(Modifier::BOLD),
-                    ),
-                    Span::styled(format!(": {text}"), Style::default().fg(theme.text)),
-                ]));
-            }
+    /// Move the selection to the previous (older) selectable message.
+    pub fn select_prev_message(&mut self) {
+        if let Some(cur) = self.selected_message
+            && let Some(idx) = self.messages[..cur].iter().rposition(is_selectable)
+        {
+            self.selected_message = Some(idx);
         }
     }
 
-    // Auto-scroll: calculate how many lines to skip so the newest messages
-    // are always visible. `saturating_sub` returns 0 instead of underflowing.
-    // `scroll_offset` allows manual scrollback via mouse wheel.
-    let visible = top[0].height.saturating_sub(2) as usize;
-    let max_scroll = lines.len().saturating_sub(visible) as u16;
-    // Clamp scroll_offset so it can't exceed actual content overflow.
-    // Without this, scrolling up past the top accumulates "dead" offset
-    // that makes scrolling back down feel unresponsive.
-    app.scroll_offset = app.scroll_offset.min(max_scroll);
-    let scroll = max_scroll - app.scroll_offset;
+    /// Move the selection to the next (newer) selectable message.
+    pub fn select_next_message(&mut self) {
+        if let Some(cur) = self.selected_message
+            && let Some(off) = self.messages[cur + 1..].iter().position(is_selectable)
+        {
+            self.selected_message = Some(cur + 1 + off);
+        }
+    }
 
-    let mut msg_block = Block::default()
-        .borders(Borders::ALL)
-        .style(Style::default().bg(theme.bg))
-        .border_style(Style::default().fg(theme.border))
-        .title("piper-chat")
-        .title_style(Style::default().fg(theme.title));
-    if app.scroll_offset > 0 {
-        msg_block = msg_block.title_bottom(
-            Line::from(Span::styled(
-                format!(" ↑ {}/{} ", app.scroll_offset, max_scroll),
-                Style::default().fg(theme.accent),
-            ))
-            .alignment(Alignment::Right),
-        );
+    /// Open the thread of the selected message — the one it starts, or the
+    /// one it's a reply in — and return to the input bar to reply there.
+    pub fn open_selected_thread(&mut self) {
+        if let Some(ChatLine::Chat { message_id, thread, .. }) = self.selected_message.and_then(|idx| self.messages.get(idx)) {
+            self.open_thread = Some(thread.unwrap_or(*message_id));
+            self.replying_to = None;
+            self.exit_message_select();
+        }
     }
-    let messages_widget = Paragraph::new(lines)
-        .scroll((scroll, 0))
-        .block(msg_block);
-    f.render_widget(messages_widget, top[0]);
 
-    // Register click region for messages pane → focus chat (lower priority).
-    app.click_regions.push(ClickRegion {
-        rect: top[0],
-        action: ClickAction::FocusChat,
-    });
+    /// Quote-reply to the selected message: our next message carries its
+    /// ID and shows an excerpt of it. Back to the input bar to type it.
+    pub fn reply_to_selected(&mut self) {
+        if let Some(ChatLine::Chat { message_id, thread: None, .. }) = self.selected_message.and_then(|idx| self.messages.get(idx)) {
+            self.replying_to = Some(quote_of(&self.messages, *message_id));
+            self.open_thread = None;
+            self.exit_message_select();
+        }
+    }
 
-    // ── Peers pane (top right) ───────────────────────────────────────────
+    /// The open thread's messages, root first, oldest to newest. The root
+    /// may be missing if it's older than our history.
+    pub fn thread_messages(&self) -> Vec<&ChatLine> {
+        let Some(root) = self.open_thread else { return Vec::new() };
+        self.messages
+            .iter()
+            .filter(|m| matches!(m, ChatLine::Chat { message_id, thread, .. } if *message_id == root || *thread == Some(root)))
+            .collect()
+    }
 
-    // Split the peers area: peer list on top, copy-ticket button on bottom.
-    let show_copy_btn = app.ticket_str.is_some();
-    let btn_height = if show_copy_btn { 3 } else { 0 };
-    let peers_split = Layout::vertical([
-        Constraint::Min(1),
+    /// Act on the selected message: reveal / re-hide a spoiler, or expand /
+    /// collapse a long message's code block.
+    pub fn toggle_selected(&mut self) {
+        match self.selected_message.and_then(|idx| self.messages.get_mut(idx)) {
+            Some(ChatLine::Spoiler { revealed, .. }) => *revealed = !*revealed,
+            Some(ChatLine::Chat { text, expanded, .. }) if is_large_text(text) => {
+                *expanded = !*expanded;
+            }
+            _ => {}
+        }
+    }
+
+    /// Code to copy from the selected message: its fenced blocks, or the
+    /// whole text of a long message (which renders as one code block).
+    pub fn selected_code(&self) -> Option<String> {
+        match self.selected_message.and_then(|idx| self.messages.get(idx)) {
+            Some(ChatLine::Chat { text, .. }) => {
+                let blocks = highlight::code_blocks(text);
+                if !blocks.is_empty() {
+                    Some(blocks.join("\n\n"))
+                } else if is_large_text(text) {
+                    Some(text.clone())
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Display help text as system messages (`/help`).
+    /// Print the address book (`/contacts`), most recently seen first.
+    pub fn show_contacts(&mut self) {
+        if self.contacts.is_empty() {
+            self.system(t!(self.config.lang, "contacts.none"));
+            return;
+        }
+        let lang = self.config.lang;
+        self.system(t!(lang, "contacts.header"));
+        let lines: Vec<String> = self
+            .contacts
+            .by_recency()
+            .into_iter()
+            .map(|(id, c)| {
+                let mark = if c.verified { "✓" } else { " " };
+                let rooms = match c.rooms.len() {
+                    1 => t!(lang, "contacts.rooms.one"),
+                    count => t!(lang, "contacts.rooms", count = count),
+                };
+                t!(
+                    lang,
+                    "contacts.row",
+                    mark = mark,
+                    nickname = format!("{:<12}", c.nickname),
+                    id = id.fmt_short(),
+                    seen = format_relative(c.last_seen_ms, self.now_ms),
+                    rooms = rooms,
+                )
+            })
+            .collect();
+        for line in lines {
+            self.system(line);
+        }
+        self.system(t!(lang, "contacts.footer"));
+    }
+
+    /// `/net`: which address families we can reach the internet over, the
+    /// relay peers find us through, and the path each peer uses now. Helps
+    /// tell "they're relayed because I'm on an IPv6-only network" from "they
+    /// haven't connected yet".
+    pub fn show_net(&mut self, reach: Option<&Reachability>) {
+        let lang = self.config.lang;
+        self.system(t!(lang, "net.header"));
+        match reach {
+            None => self.system(t!(lang, "net.pending")),
+            Some(reach) => {
+                for (family, f) in [("IPv4", &reach.v4), ("IPv6", &reach.v6)] {
+                    self.system(match (f.udp, f.public) {
+                        (true, Some(addr)) => t!(lang, "net.up", family = family, addr = addr),
+                        (true, None) => t!(lang, "net.up.bare", family = family),
+                        (false, _) => t!(lang, "net.down", family = family),
+                    });
+                }
+                if let Some(relay) = &reach.relay {
+                    self.system(t!(lang, "net.relay", relay = relay));
+                }
+                match (reach.v4.udp, reach.v6.udp) {
+                    (false, true) => self.system(t!(lang, "net.v6_only")),
+                    (true, false) => self.system(t!(lang, "net.v4_only")),
+                    (false, false) => self.system(t!(lang, "net.no_udp")),
+                    (true, true) => {}
+                }
+            }
+        }
+        let lines: Vec<String> = self
+            .peer_order()
+            .iter()
+            .filter_map(|id| self.peers.get(id))
+            .filter_map(|peer| match (&peer.conn_type, peer.addr) {
+                (ConnType::You, _) => None,
+                (ConnType::Direct, Some(addr)) => {
+                    Some(t!(lang, "net.peer.direct", name = peer.name, family = tracker::family(&addr), addr = addr))
+                }
+                (ConnType::Direct, None) => Some(t!(lang, "net.peer.direct.bare", name = peer.name)),
+                (ConnType::Relay, _) => Some(t!(lang, "net.peer.relay", name = peer.name)),
+                (ConnType::Unknown, _) => Some(t!(lang, "net.peer.unknown", name = peer.name)),
+            })
+            .collect();
+        for line in lines {
+            self.system(line);
+        }
+    }
+
+    /// `/mentions`: every message in the log that `highlights` matches —
+    /// @mentions of us and messages with a highlight word — oldest first.
+    pub fn show_mentions(&mut self) {
+        let lang = self.config.lang;
+        let ellipsis = self.theme.glyph("…", "...");
+        let rows: Vec<String> = self
+            .messages
+            .iter()
+            .filter_map(|msg| match msg {
+                ChatLine::Chat { nickname, text, timestamp_ms, .. } if self.highlights.matches(nickname, text) => {
+                    let first = text.lines().next().unwrap_or_default();
+                    Some(t!(
+                        lang,
+                        "mentions.row",
+                        time = format_timestamp(*timestamp_ms),
+                        nickname = nickname,
+                        text = fit::end(first, MENTION_EXCERPT, ellipsis)
+                    ))
+                }
+                _ => None,
+            })
+            .collect();
+        if rows.is_empty() {
+            self.system(t!(lang, "mentions.none"));
+            return;
+        }
+        self.system(t!(lang, "mentions.header", count = rows.len()));
+        for row in rows {
+            self.system(row);
+        }
+    }
+
+    pub fn show_help(&mut self) {
+        for line in self.config.lang.text("help").lines() {
+            self.system(line);
+        }
+    }
+
+    // ── Roll call ────────────────────────────────────────────────────────
+
+    /// Begin collecting replies for a `/who` roll call.
+    pub fn start_roll_call(&mut self, request_id: MessageId) {
+        self.roll_call = Some(RollCall {
+            request_id,
+            started: Instant::now(),
+            replies: BTreeMap::new(),
+        });
+        self.system(t!(self.config.lang, "rollcall.sent"));
+    }
+
+    /// Record a `Here` reply. Replies to stale or unknown requests are ignored.
+    ///
+    /// A reply also proves the peer is in the room, so it is added to the
+    /// peers panel if we missed its `Join` (and its nickname refreshed if
+    /// it was still shown as a short ID). Replies to the `/map` overlay's
+    /// pings only refresh the peer's round-trip time.
+    pub fn record_here(
+        &mut self,
+        request_id: MessageId,
+        endpoint_id: EndpointId,
+        nickname: String,
+        rtt_ms: u64,
+    ) {
+        let map_ping = self.map.as_ref().is_some_and(|m| m.ping_id == Some(request_id));
+        if map_ping {
+            if let Some(peer) = self.peers.get_mut(&endpoint_id) {
+                peer.rtt_ms = Some(rtt_ms);
+            }
+            return;
+        }
+        let Some(call) = self.roll_call.as_mut() else {
+            return;
+        };
+        if call.request_id != request_id {
+            return;
+        }
+        call.replies.insert(endpoint_id, (nickname.clone(), rtt_ms));
+        let peer = self.peers.entry(endpoint_id).or_insert_with(|| PeerInfo {
+            name: nickname.clone(),
+            conn_type: ConnType::Unknown,
+            rtt_ms: None,
+            addr: None,
+        });
+        peer.name = nickname;
+        peer.rtt_ms = Some(rtt_ms);
+    }
+
+    /// Print the roster and clear the roll call once its window has elapsed.
+    pub fn finish_roll_call_if_due(&mut self, now: Instant) {
+        let due = self
+            .roll_call
+            .as_ref()
+            .is_some_and(|c| now.duration_since(c.started) >= ROLL_CALL_WINDOW);
+        if !due {
+            return;
+        }
+        let call = self.roll_call.take().unwrap();
+        let lines = roster_lines(&call, &self.peers, self.config.lang);
+        for line in lines {
+            self.system(line);
+        }
+    }
+
+    /// Handle a bracketed paste from the terminal.
+    ///
+    /// Single-line pastes are inserted at the cursor like typed text.
+    /// Multi-line pastes are held in `pending_paste` for confirmation, so
+    /// embedded newlines can't fire Enter and send partial lines.
+    pub fn paste(&mut self, text: &str) {
+        let text = normalize_paste(text);
+        if text.contains('\n') {
+            self.pending_paste = Some(text);
+        } else {
+            input::insert_str(&mut self.input, &mut self.cursor_pos, &text);
+        }
+    }
+
+    /// Push a history entry, capping at 1000 entries.
+    pub fn push_history(&mut self, entry: HistoryEntry) {
+        if let Some(journal) = &self.journal {
+            journal.append(entry.clone());
+        }
+        if let Some(log) = &self.event_log
+            && let Some(event) = eventlog::history_event(&entry)
+        {
+            log.log(&event);
+        }
+        self.history.push(entry);
+        if self.history.len() > 1000 {
+            self.history.remove(0);
+        }
+    }
+
+    /// The bounds for a lag backfill request: the timestamp of the oldest of
+    /// our last `BACKFILL_KNOWN` history entries, and their message IDs.
+    pub fn backfill_bounds(&self) -> (u64, Vec<MessageId>) {
+        let recent = &self.history[self.history.len().saturating_sub(BACKFILL_KNOWN)..];
+        let since_ms = recent.first().map(|e| e.timestamp_ms).unwrap_or(0);
+        (since_ms, recent.iter().map(|e| e.message_id).collect())
+    }
+
+    /// Answer a backfill request: our history entries from `since_ms` on
+    /// that aren't in `known`, newest `MAX_BACKFILL_ENTRIES` at most.
+    pub fn backfill_for(&self, since_ms: u64, known: &[MessageId]) -> Vec<HistoryEntry> {
+        let known: HashSet<&MessageId> = known.iter().collect();
+        let missing: Vec<&HistoryEntry> = self
+            .history
+            .iter()
+            .filter(|e| e.timestamp_ms >= since_ms && !known.contains(&e.message_id))
+            .collect();
+        missing[missing.len().saturating_sub(MAX_BACKFILL_ENTRIES)..]
+            .iter()
+            .map(|e| (*e).clone())
+            .collect()
+    }
+
+    /// Merge history entries received from a peer (a history sync or a
+    /// lag backfill) into our own history.
+    ///
+    /// Entries already seen are skipped; file offers become downloadable
+    /// (sealed ones with the key from the `FileKey` entry recorded before
+    /// them) and revocations withdraw the one offer they name, never one
+    /// of ours. Returns the chat lines for the new entries,
+    /// oldest first, and how many entries were new. The caller decides where
+    /// the lines go — a history sync prepends them, a backfill appends them.
+    /// `nickname` is ours, for skipping targeted offers meant for others.
+    pub fn absorb_history(
+        &mut self,
+        mut entries: Vec<HistoryEntry>,
+        nickname: &str,
+    ) -> (Vec<ChatLine>, u32) {
+        entries.sort_by_key(|e| e.timestamp_ms);
+        let mut merged = 0u32;
+        let mut lines = Vec::new();
+        let mut keys = HashMap::new();
+        let ours = self.peers.iter().find(|(_, p)| matches!(p.conn_type, ConnType::You)).map(|(id, _)| *id);
+        for entry in entries {
+            if !self.seen_ids.insert(entry.message_id) {
+                continue;
+            }
+            merged += 1;
+            match &entry.kind {
+                HistoryEntryKind::Chat { nickname: nick, text } => {
+                    lines.push(ChatLine::Chat {
+                        nickname: nick.clone(),
+                        text: text.clone(),
+                        timestamp_ms: entry.timestamp_ms,
+                        expanded: false,
+                        message_id: entry.message_id,
+                        thread: None,
+                        quote: None,
+                    });
+                }
+                HistoryEntryKind::Reply { nickname: nick, text, reply_to } => {
+                    // The quoted message may be among the entries just merged.
+                    let mut quote = quote_of(&lines, *reply_to);
+                    if quote.nickname.is_empty() {
+                        quote = quote_of(&self.messages, *reply_to);
+                    }
+                    lines.push(ChatLine::Chat {
+                        nickname: nick.clone(),
+                        text: text.clone(),
+                        timestamp_ms: entry.timestamp_ms,
+                        expanded: false,
+                        message_id: entry.message_id,
+                        thread: None,
+                        quote: Some(quote),
+                    });
+                }
+                HistoryEntryKind::ThreadChat { nickname: nick, text, thread_id } => {
+                    *self.threads.entry(*thread_id).or_default() += 1;
+                    lines.push(ChatLine::Chat {
+                        nickname: nick.clone(),
+                        text: text.clone(),
+                        timestamp_ms: entry.timestamp_ms,
+                        expanded: false,
+                        message_id: entry.message_id,
+                        thread: Some(*thread_id),
+                        quote: None,
+                    });
+                }
+                HistoryEntryKind::FileOffer {
+                    nickname: nick,
+                    endpoint_id,
+                    filename,
+                    size,
+                    hash,
+                    target,
+                    ..
+                } => {
+                    // Skip targeted offers not meant for us.
+                    if target.as_ref().is_some_and(|t| t != nickname) {
+                        continue;
+                    }
+                    // Add to TransferManager so synced offers are downloadable.
+                    self.transfers.add_offer(transfer::FileOffer {
+                        sender_nickname: nick.clone(),
+                        sender_id: *endpoint_id,
+                        filename: filename.clone(),
+                        size: *size,
+                        hash: iroh_blobs::Hash::from_bytes(*hash),
+                        key: keys.get(hash).copied(),
+                        offer_id: entry.message_id,
+                    });
+                    lines.push(ChatLine::System(t!(
+                        self.config.lang,
+                        "file.shared",
+                        name = nick,
+                        filename = filename,
+                        size = transfer::format_file_size(*size),
+                    )));
+                }
+                // Names neither the offer nor its author, so it could
+                // withdraw anyone's file: ignored, like the live message.
+                HistoryEntryKind::FileRetract { .. } => {}
+                // History isn't signed, so this only ever touches the exact
+                // offer it names, and never one of ours: we withdraw our
+                // own offers ourselves.
+                HistoryEntryKind::FileRevoke { hash, endpoint_id, offer_id } => {
+                    if ours != Some(*endpoint_id) {
+                        let id = transfer::EntryId {
+                            hash: iroh_blobs::Hash::from_bytes(*hash),
+                            sender: *endpoint_id,
+                            offer_id: *offer_id,
+                        };
+                        let expiry = transfer::Expiry { at_ms: entry.timestamp_ms, message_id: entry.message_id };
+                        self.transfers.revoke(&id, expiry, entry.timestamp_ms);
+                    }
+                }
+                HistoryEntryKind::FileKey { hash, wrapped_key } => {
+                    if let Some(key) = self.members.room().and_then(|room| seal::unwrap(&room, wrapped_key)) {
+                        keys.insert(*hash, key);
+                    }
+                }
+                HistoryEntryKind::System(text) => {
+                    lines.push(ChatLine::System(text.clone()));
+                }
+                HistoryEntryKind::Spoiler { nickname: nick, text } => {
+                    lines.push(ChatLine::Spoiler {
+                        nickname: nick.clone(),
+                        text: text.clone(),
+                        timestamp_ms: entry.timestamp_ms,
+                        revealed: false,
+                    });
+                }
+            }
+            if let Some(journal) = &self.journal {
+                journal.append(entry.clone());
+            }
+            self.history.push(entry);
+        }
+        // Cap history at 1000.
+        if self.history.len() > 1000 {
+            self.history.drain(0..self.history.len() - 1000);
+        }
+        (lines, merged)
+    }
+}
+
+/// `Default` delegates to `new()` so `App` works with APIs that expect
+/// `T: Default` (e.g. `std::mem::take`).
+impl Default for App {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Format the `/who` roster: one line per reply, then any known peers that
+/// stayed silent (they may be lagging or have left without a `NeighborDown`).
+fn roster_lines(call: &RollCall, peers: &BTreeMap<EndpointId, PeerInfo>, lang: Lang) -> Vec<String> {
+    let mut lines = vec![t!(lang, "rollcall.header", count = call.replies.len() + 1)];
+    if let Some(me) = peers.values().find(|p| matches!(p.conn_type, ConnType::You)) {
+        lines.push(format!("  {:<16} [you]", me.name));
+    }
+    for (id, (name, rtt_ms)) in &call.replies {
+        let conn = match peers.get(id).map(|p| &p.conn_type) {
+            Some(ConnType::Direct) => "[direct]",
+            Some(ConnType::Relay) => "[relay]",
+            _ => "[?]",
+        };
+        lines.push(format!("  {name:<16} {conn:<8} {rtt_ms} ms"));
+    }
+    for (id, peer) in peers {
+        if !matches!(peer.conn_type, ConnType::You) && !call.replies.contains_key(id) {
+            lines.push(format!("  {:<16} {}", peer.name, t!(lang, "rollcall.silent")));
+        }
+    }
+    lines
+}
+
+/// The quote of message `id` as found in `messages`, newest first; a
+/// quote with an empty nickname if it isn't there.
+fn quote_of(messages: &[ChatLine], id: MessageId) -> Quote {
+    let found = messages.iter().rev().find_map(|m| match m {
+        ChatLine::Chat { nickname, text, message_id, .. } if *message_id == id => Some((nickname, text)),
+        _ => None,
+    });
+    match found {
+        Some((nickname, text)) => Quote { message_id: id, nickname: nickname.clone(), excerpt: excerpt(text) },
+        None => Quote { message_id: id, nickname: String::new(), excerpt: String::new() },
+    }
+}
+
+/// The first line of `text`, cut to `QUOTE_EXCERPT` characters.
+fn excerpt(text: &str) -> String {
+    let first = text.lines().next().unwrap_or_default();
+    if first.chars().count() > QUOTE_EXCERPT || text.contains('\n') {
+        let cut: String = first.chars().take(QUOTE_EXCERPT).collect();
+        format!("{}…", cut.trim_end())
+    } else {
+        first.to_string()
+    }
+}
+
+/// How the peers pane orders peers after us and verified contacts.
+/// Saved in the config as `peer_sort`; Ctrl+O cycles through them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PeerSort {
+    /// Oldest in the room first.
+    #[default]
+    Joined,
+    /// Alphabetically by nickname, ignoring case.
+    Name,
+    /// Lowest round-trip time first (as measured by `/who` or `/map`).
+    Latency,
+    /// Direct connections, then relayed, then not yet known.
+    Connection,
+}
+
+impl PeerSort {
+    pub fn next(self) -> Self {
+        match self {
+            PeerSort::Joined => PeerSort::Name,
+            PeerSort::Name => PeerSort::Latency,
+            PeerSort::Latency => PeerSort::Connection,
+            PeerSort::Connection => PeerSort::Joined,
+        }
+    }
+
+    /// Its label in the peers pane.
+    pub fn label(self, lang: Lang) -> &'static str {
+        lang.text(match self {
+            PeerSort::Joined => "peers.sort.joined",
+            PeerSort::Name => "peers.sort.name",
+            PeerSort::Latency => "peers.sort.latency",
+            PeerSort::Connection => "peers.sort.connection",
+        })
+    }
+}
+
+/// `name` with the start of `id` appended, to tell apart two peers who go
+/// by the same nickname.
+pub fn tag_name(name: &str, id: &EndpointId) -> String {
+    let id = id.to_string();
+    format!("{name}#{}", &id[..4])
+}
+
+/// Sort rank of a connection type for `PeerSort::Connection`.
+fn conn_rank(conn: &ConnType) -> u8 {
+    match conn {
+        ConnType::You => 0,
+        ConnType::Direct => 1,
+        ConnType::Relay => 2,
+        ConnType::Unknown => 3,
+    }
+}
+
+/// Whether a message line can be selected in message-select mode. Thread
+/// replies aren't: they don't show in the main pane.
+fn is_selectable(line: &ChatLine) -> bool {
+    matches!(line, ChatLine::Chat { thread: None, .. } | ChatLine::Spoiler { .. })
+}
+
+/// Normalize line endings in pasted text and strip trailing newlines.
+///
+/// Terminals deliver pasted newlines as `\r`, `\r\n`, or `\n` depending on
+/// the platform — we collapse them all to `\n` so the text renders and
+/// serializes the same way everywhere.
+pub fn normalize_paste(text: &str) -> String {
+    text.replace("\r\n", "\n")
+        .replace('\r', "\n")
+        .trim_end_matches('\n')
+        .to_string()
+}
+
+/// Render the input buffer on a single row, showing embedded newlines from
+/// multi-line compose (Shift+Enter) as a visible `newline` marker (`⏎`).
+fn input_display(input: &str, newline: &str) -> String {
+    input.replace('\n', newline)
+}
+
+/// Spans for the input bar text, with misspelled words underlined.
+///
+/// The word touching the cursor is left alone while it's being typed, so
+/// half-finished words don't flash red on every keystroke.
+fn input_spans(input: &str, cursor: usize, spell: Option<&Dictionary>, theme: &Theme) -> Vec<Span<'static>> {
+    let plain = Style::default().fg(theme.text);
+    let wrong = plain
+        .add_modifier(Modifier::UNDERLINED)
+        .underline_color(theme.error);
+    let ranges = spell.map(|d| d.misspelled(input)).unwrap_or_default();
+    let newline = theme.glyph("⏎", "\\n");
+    let mut spans = Vec::new();
+    let mut at = 0;
+    for range in ranges.into_iter().filter(|r| r.end != cursor) {
+        spans.push(Span::styled(input_display(&input[at..range.start], newline), plain));
+        spans.push(Span::styled(input_display(&input[range.clone()], newline), wrong));
+        at = range.end;
+    }
+    spans.push(Span::styled(input_display(&input[at..], newline), plain));
+    spans
+}
+
+/// Replace every non-whitespace character with a solid block, preserving
+/// spaces and newlines so the redacted text keeps its shape.
+fn redact(text: &str) -> String {
+    text.chars()
+        .map(|c| if c.is_whitespace() { c } else { '█' })
+        .collect()
+}
+
+/// Append the rendered lines for one chat-style message.
+///
+/// Multi-line messages (from pastes) render the first line after the
+/// nickname and indent continuation lines under it.
+///
+/// `relative_to` switches the timestamp from `HH:MM` to "2m ago" style,
+/// measured against the given time.
+fn push_chat_lines(
+    lines: &mut Vec<Line<'static>>,
+    timestamp_ms: u64,
+    relative_to: Option<u64>,
+    nickname: &str,
+    text: &str,
+    text_style: Style,
+    theme: &Theme,
+) {
+    let ts = match relative_to {
+        Some(now) => format_relative(timestamp_ms, now),
+        None => format_timestamp(timestamp_ms),
+    };
+    let mut text_lines = text.split('\n');
+    let first = text_lines.next().unwrap_or_default();
+    lines.push(Line::from(vec![
+        Span::styled(format!("{ts} "), Style::default().fg(theme.timestamp)),
+        Span::styled(
+            fit::middle(nickname, NICKNAME_COLUMNS, theme.glyph("…", "...")).into_owned(),
+            Style::default()
+                .fg(theme.nickname)
+                .add_modifier(Modifier::BOLD),
+        ),
+        Span::styled(format!(": {first}"), text_style),
+    ]));
+    for cont in text_lines {
+        lines.push(Line::from(Span::styled(format!("      {cont}"), text_style)));
+    }
+}
+
+/// Widest a nickname gets in front of a message; longer ones are cut in
+/// the middle, keeping any `#a1b2` tag at the end.
+const NICKNAME_COLUMNS: usize = 20;
+
+/// Whether `text` @mentions `nickname` (case-insensitive, trailing
+/// punctuation ignored, so `@Alice,` and `@alice!` both count).
+pub fn mentions(text: &str, nickname: &str) -> bool {
+    text.split_whitespace().any(|token| {
+        token
+            .strip_prefix('@')
+            .map(|name| name.trim_end_matches(|c: char| c.is_ascii_punctuation()))
+            .is_some_and(|name| name.eq_ignore_ascii_case(nickname))
+    })
+}
+
+/// What makes a message stand out in the log: an @mention of our
+/// nickname, or one of the `highlight_words` from the config (a real name,
+/// a project codename). Words match whole and ignore case, so `ana` lights
+/// up "Ana, look" but not "banana".
+#[derive(Default)]
+pub struct Highlights {
+    nickname: String,
+    /// Lowercased, blanks dropped.
+    words: Vec<String>,
+}
+
+impl Highlights {
+    pub fn new(nickname: &str, words: &[String]) -> Self {
+        let words = words.iter().map(|w| w.trim().to_lowercase()).filter(|w| !w.is_empty()).collect();
+        Self { nickname: nickname.to_string(), words }
+    }
+
+    /// Whether a message by `author` should be highlighted. Our own
+    /// messages never are.
+    pub fn matches(&self, author: &str, text: &str) -> bool {
+        if author == self.nickname {
+            return false;
+        }
+        if !self.nickname.is_empty() && mentions(text, &self.nickname) {
+            return true;
+        }
+        if self.words.is_empty() {
+            return false;
+        }
+        let text = text.to_lowercase();
+        self.words.iter().any(|word| contains_word(&text, word))
+    }
+}
+
+/// Whether `word` occurs in `text` with no letter or digit either side.
+fn contains_word(text: &str, word: &str) -> bool {
+    text.match_indices(word).any(|(at, _)| {
+        let before = text[..at].chars().next_back();
+        let after = text[at + word.len()..].chars().next();
+        !before.is_some_and(char::is_alphanumeric) && !after.is_some_and(char::is_alphanumeric)
+    })
+}
+
+/// Append a large message's body as a code block under its header line.
+///
+/// Collapsed, only the first `CODE_PREVIEW_LINES` lines show, followed by a
+/// footer counting the rest; expanded, every line shows. A left gutter sets
+/// the block apart from surrounding chat.
+fn push_code_block(
+    lines: &mut Vec<Line<'static>>,
+    text: &str,
+    expanded: bool,
+    lang: Lang,
+    theme: &Theme,
+) {
+    let gutter = Style::default().fg(theme.border);
+    let body = Style::default().fg(theme.text);
+    let total = text.lines().count();
+    let shown = if expanded { total } else { CODE_PREVIEW_LINES.min(total) };
+    // `rows` classifies line by line, so it zips with `lines()`.
+    for (line, row) in text.lines().zip(highlight::rows(text)).take(shown) {
+        let mut spans = vec![Span::styled(theme.glyph("      │ ", "      | "), gutter)];
+        match row {
+            Row::Code(tokens) => spans.extend(token_spans(&tokens, theme)),
+            Row::Open(_) | Row::Close => {
+                spans.push(Span::styled(line.to_string(), Style::default().fg(theme.text_muted)));
+            }
+            Row::Prose(_) => spans.push(Span::styled(line.to_string(), body)),
+        }
+        lines.push(Line::from(spans));
+    }
+    let footer = if expanded {
+        t!(lang, "code.collapse")
+    } else {
+        t!(lang, "code.more", count = total - shown)
+    };
+    lines.push(Line::from(vec![
+        Span::styled(theme.glyph("      └ ", "      ` "), gutter),
+        Span::styled(theme.text(&footer).into_owned(), Style::default().fg(theme.text_muted).add_modifier(Modifier::ITALIC)),
+    ]));
+}
+
+/// Append a message containing fenced code. Prose renders like any other
+/// message; each fenced block is set apart by a gutter, labelled with its
+/// language, and highlighted.
+fn push_fenced_lines(
+    lines: &mut Vec<Line<'static>>,
+    timestamp_ms: u64,
+    relative_to: Option<u64>,
+    nickname: &str,
+    text: &str,
+    theme: &Theme,
+) {
+    let mut rows = highlight::rows(text).into_iter().peekable();
+    // Prose before the first fence goes on the header line, as usual.
+    let first = match rows.next_if(|row| matches!(row, Row::Prose(_))) {
+        Some(Row::Prose(line)) => line,
+        _ => "",
+    };
+    let body = Style::default().fg(theme.text);
+    push_chat_lines(lines, timestamp_ms, relative_to, nickname, first, body, theme);
+    let gutter = Style::default().fg(theme.border);
+    for row in rows {
+        lines.push(match row {
+            Row::Prose(line) => Line::from(Span::styled(format!("      {line}"), body)),
+            Row::Open(tag) => Line::from(vec![
+                Span::styled(theme.glyph("      ┌ ", "      + "), gutter),
+                Span::styled(
+                    tag.unwrap_or("code").to_string(),
+                    Style::default().fg(theme.text_muted).add_modifier(Modifier::ITALIC),
+                ),
+            ]),
+            Row::Code(tokens) => {
+                let mut spans = vec![Span::styled(theme.glyph("      │ ", "      | "), gutter)];
+                spans.extend(token_spans(&tokens, theme));
+                Line::from(spans)
+            }
+            Row::Close => Line::from(Span::styled(theme.glyph("      └", "      +"), gutter)),
+        });
+    }
+}
+
+/// Styled spans for a highlighted code line.
+fn token_spans(tokens: &[(Token, &str)], theme: &Theme) -> impl Iterator<Item = Span<'static>> {
+    tokens.iter().map(|&(token, text)| {
+        let style = match token {
+            Token::Plain => Style::default().fg(theme.text),
+            Token::Keyword => Style::default().fg(theme.code_keyword).add_modifier(Modifier::BOLD),
+            Token::Str => Style::default().fg(theme.code_string),
+            Token::Comment => Style::default().fg(theme.code_comment).add_modifier(Modifier::ITALIC),
+            Token::Number => Style::default().fg(theme.code_number),
+        };
+        Span::styled(text.to_string(), style)
+    })
+}
+
+/// Display column of the cursor within `input_display(input, newline)`.
+fn input_cursor_column(input: &str, cursor: usize, newline: &str) -> u16 {
+    let before = input_display(&input[..cursor], newline);
+    input::cursor_column(&before, before.len())
+}
+
+// ── Line cache ───────────────────────────────────────────────────────────────
+//
+// Immediate-mode rendering (see below) rebuilds the whole UI every frame, but
+// re-styling thousands of history lines 20 times a second would keep a core
+// busy for nothing. The cache keeps each message's rendered lines and only
+// renders messages that are new or changed since the last frame.
+
+/// Everything outside a message that changes how it renders. A new key
+/// throws the whole cache away.
+#[derive(Clone, Copy, PartialEq)]
+pub struct CacheKey {
+    /// Active palette; toggling the theme restyles everything.
+    pub theme: ThemeMode,
+    /// Messages pane width. Lines don't wrap today, but a resize is rare and
+    /// cheap to honour, and it keeps wrapping from needing a cache change.
+    pub width: u16,
+    /// Relative timestamps ("2m ago") instead of `HH:MM`. The text ages,
+    /// but `LineCache::sync` rewrites just those spans in place, so only
+    /// switching the setting throws the cache away.
+    pub relative: bool,
+    /// UI language, for the few labels drawn inside the messages pane.
+    pub lang: Lang,
+    /// Focus mode: system messages take no lines.
+    pub focus: bool,
+}
+
+/// Cheap fingerprint of a `ChatLine`, compared each frame to spot messages
+/// that were replaced, edited, reordered or toggled (spoiler reveal,
+/// expand).
+///
+/// The strings are fingerprinted by content, not by address: an edited
+/// string can keep its allocation (and length), so only its bytes tell
+/// that it changed.
+#[derive(Clone, Copy, PartialEq)]
+struct Stamp {
+    /// Hash of the text and nickname.
+    content: u64,
+    timestamp_ms: u64,
+    flag: Option<bool>,
+    /// Replies to the message, from the thread index: a new reply changes
+    /// the "↳ N replies" line under it.
+    replies: usize,
+}
+
+impl Stamp {
+    fn of(msg: &ChatLine, threads: &HashMap<MessageId, usize>) -> Self {
+        let mut replies = 0;
+        let (text, nickname, timestamp_ms, flag) = match msg {
+            ChatLine::System(text) => (text, None, 0, None),
+            ChatLine::Ticket(text) => (text, None, 0, Some(false)),
+            ChatLine::Chat { nickname, text, timestamp_ms, expanded, message_id, .. } => {
+                replies = threads.get(message_id).copied().unwrap_or(0);
+                (text, Some(nickname), *timestamp_ms, Some(*expanded))
+            }
+            ChatLine::Spoiler { nickname, text, timestamp_ms, revealed } => {
+                (text, Some(nickname), *timestamp_ms, Some(*revealed))
+            }
+        };
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        hasher.write(text.as_bytes());
+        // A separator, so text and nickname can't trade bytes unnoticed.
+        hasher.write_u8(0xff);
+        hasher.write(nickname.map_or(&[][..], |n| n.as_bytes()));
+        Stamp {
+            content: hasher.finish(),
+            timestamp_ms,
+            flag,
+            replies,
+        }
+    }
+}
+
+/// Where one message's lines sit in `LineCache::lines`.
+struct CachedMessage {
+    stamp: Stamp,
+    /// First line, including any day separator above the message.
+    start: usize,
+    /// First line of the message itself.
+    body: usize,
+    /// UTC day of the newest timestamped message up to and including this
+    /// one, so the next message knows whether it needs a separator.
+    day: Option<u64>,
+    /// The line holding the message's timestamp, and the timestamp, for
+    /// messages that show one.
+    stamped: Option<(usize, u64)>,
+}
+
+/// Rendered lines for `App::messages`, reused across frames.
+#[derive(Default)]
+pub struct LineCache {
+    key: Option<CacheKey>,
+    messages: Vec<CachedMessage>,
+    /// Every message's lines, in order.
+    pub lines: Vec<Line<'static>>,
+    /// `now` to the second when relative timestamps were last brought up
+    /// to date.
+    relative_secs: Option<u64>,
+}
+
+impl LineCache {
+    /// Bring the cache up to date with `messages`.
+    ///
+    /// Cached messages are kept up to the first one whose stamp no longer
+    /// matches; everything from there on is rendered again. Appending a
+    /// message therefore renders just that message. With relative
+    /// timestamps on, their spans are rewritten once a second, leaving the
+    /// rest of each line alone.
+    pub fn sync(
+        &mut self,
+        messages: &[ChatLine],
+        threads: &HashMap<MessageId, usize>,
+        key: CacheKey,
+        theme: &Theme,
+        relative_to: Option<u64>,
+        highlights: &Highlights,
+    ) {
+        if self.key != Some(key) {
+            self.key = Some(key);
+            self.messages.clear();
+            self.lines.clear();
+            self.relative_secs = None;
+        }
+        let valid = self
+            .messages
+            .iter()
+            .zip(messages)
+            .take_while(|(cached, msg)| cached.stamp == Stamp::of(msg, threads))
+            .count();
+        if let Some(stale) = self.messages.get(valid) {
+            self.lines.truncate(stale.start);
+            self.messages.truncate(valid);
+        }
+        let mut last_day = self.messages.last().and_then(|m| m.day);
+        for msg in &messages[valid..] {
+            let start = self.lines.len();
+            let stamp = Stamp::of(msg, threads);
+            // Thread replies take no lines here; they show in the thread view.
+            // In focus mode neither do system messages.
+            if matches!(msg, ChatLine::Chat { thread: Some(_), .. }) || (key.focus && matches!(msg, ChatLine::System(_))) {
+                self.messages.push(CachedMessage { stamp, start, body: start, day: last_day, stamped: None });
+                continue;
+            }
+            if let ChatLine::Chat { timestamp_ms, .. } | ChatLine::Spoiler { timestamp_ms, .. } = msg {
+                let day = timestamp_ms / DAY_MS;
+                if last_day.is_some_and(|last| last != day) {
+                    self.lines.push(
+                        Line::styled(theme.text(&day_separator(*timestamp_ms)).into_owned(), Style::default().fg(theme.text_muted))
+                            .centered(),
+                    );
+                }
+                last_day = Some(day);
+            }
+            let body = self.lines.len();
+            push_message_lines(&mut self.lines, msg, relative_to, key.lang, theme, highlights);
+            if stamp.replies > 0 {
+                self.lines.push(replies_line(stamp.replies, key.lang, theme));
+            }
+            // The timestamp starts the header line, below any quote.
+            let stamped = match msg {
+                ChatLine::Chat { timestamp_ms, quote, .. } => Some((body + usize::from(quote.is_some()), *timestamp_ms)),
+                ChatLine::Spoiler { timestamp_ms, .. } => Some((body, *timestamp_ms)),
+                _ => None,
+            };
+            self.messages.push(CachedMessage { stamp, start, body, day: last_day, stamped });
+        }
+        if let Some(now) = relative_to
+            && self.relative_secs != Some(now / 1000)
+        {
+            self.relative_secs = Some(now / 1000);
+            for (line, timestamp_ms) in self.messages.iter().filter_map(|m| m.stamped) {
+                let text = format!("{} ", format_relative(timestamp_ms, now));
+                if let Some(span) = self.lines.get_mut(line).and_then(|l| l.spans.first_mut())
+                    && span.content != text
+                {
+                    span.content = text.into();
+                }
+            }
+        }
+    }
+
+    /// Line range `(start, end)` of message `idx`, not counting its day
+    /// separator.
+    pub fn body_range(&self, idx: usize) -> Option<(usize, usize)> {
+        let msg = self.messages.get(idx)?;
+        let end = self.messages.get(idx + 1).map_or(self.lines.len(), |next| next.start);
+        Some((msg.body, end))
+    }
+}
+
+/// Draw the open thread over the messages pane: its root, then the
+/// replies, scrolled so the newest is at the bottom like the main pane.
+fn render_thread(f: &mut ratatui::Frame, area: Rect, app: &App) {
+    let theme = &app.theme;
+    let lang = app.config.lang;
+    let relative_to = app.config.relative_timestamps.then_some(app.now_ms);
+    let replies = app.open_thread.and_then(|root| app.threads.get(&root)).copied().unwrap_or(0);
+    let mut lines = Vec::new();
+    for msg in app.thread_messages() {
+        push_message_lines(&mut lines, msg, relative_to, lang, theme, &app.highlights);
+    }
+    let visible = area.height.saturating_sub(2) as usize;
+    let lines = lines.split_off(lines.len().saturating_sub(visible));
+
+    f.render_widget(ratatui::widgets::Clear, area);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_set(theme.border_set())
+        .style(Style::default().bg(theme.bg))
+        .border_style(Style::default().fg(theme.border_focused))
+        .title(Span::styled(format!(" {} ", t!(lang, "thread.title", count = replies)), Style::default().fg(theme.title)))
+        .title_bottom(
+            Line::from(vec![
+                Span::styled(" Esc", Style::default().fg(theme.hint_key)),
+                Span::styled(format!(" {} ", t!(lang, "thread.close")), Style::default().fg(theme.hint_text)),
+            ])
+            .alignment(Alignment::Right),
+        );
+    f.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+/// The "↳ 3 replies" line under a thread's root.
+fn replies_line(count: usize, lang: Lang, theme: &Theme) -> Line<'static> {
+    let text = if count == 1 { t!(lang, "thread.reply") } else { t!(lang, "thread.replies", count = count) };
+    Line::styled(
+        format!("      {} {}", theme.glyph("↳", "->"), text),
+        Style::default().fg(theme.accent).add_modifier(Modifier::ITALIC),
+    )
+}
+
+/// The dimmed "╭ Alice: excerpt" line above a quote-reply.
+fn quote_line(quote: &Quote, lang: Lang, theme: &Theme) -> Line<'static> {
+    let body = if quote.nickname.is_empty() {
+        t!(lang, "quote.missing")
+    } else {
+        format!("{}: {}", quote.nickname, quote.excerpt)
+    };
+    Line::styled(
+        theme.text(&format!("      {} {body}", theme.glyph("╭", ","))).into_owned(),
+        Style::default().fg(theme.text_muted).add_modifier(Modifier::ITALIC),
+    )
+}
+
+/// Append the rendered lines for one message (without day separator).
+fn push_message_lines(
+    lines: &mut Vec<Line<'static>>,
+    msg: &ChatLine,
+    relative_to: Option<u64>,
+    lang: Lang,
+    theme: &Theme,
+    highlights: &Highlights,
+) {
+    if let ChatLine::Chat { quote: Some(quote), .. } = msg {
+        lines.push(quote_line(quote, lang, theme));
+    }
+    match msg {
+        ChatLine::System(text) => {
+            lines.push(Line::from(Span::styled(
+                theme.text(&t!(lang, "line.system", text = text)).into_owned(),
+                Style::default()
+                    .fg(theme.text_dim)
+                    .add_modifier(Modifier::ITALIC),
+            )));
+        }
+        ChatLine::Ticket(ticket) => {
+            lines.push(Line::from(vec![
+                Span::styled(
+                    t!(lang, "line.ticket"),
+                    Style::default()
+                        .fg(theme.ticket_label)
+                        .add_modifier(Modifier::BOLD),
+                ),
+                Span::styled(ticket.clone(), Style::default().fg(theme.ticket_value)),
+            ]));
+        }
+        ChatLine::Chat {
+            nickname,
+            text,
+            timestamp_ms,
+            expanded,
+            ..
+        } if is_large_text(text) => {
+            let header = theme.text(&t!(lang, "line.large", count = text.lines().count())).into_owned();
+            push_chat_lines(
+                lines,
+                *timestamp_ms,
+                relative_to,
+                nickname,
+                &header,
+                Style::default().fg(theme.border),
+                theme,
+            );
+            push_code_block(lines, text, *expanded, lang, theme);
+        }
+        ChatLine::Chat {
+            nickname,
+            text,
+            timestamp_ms,
+            ..
+        } if highlight::has_code(text) => {
+            push_fenced_lines(lines, *timestamp_ms, relative_to, nickname, text, theme);
+        }
+        ChatLine::Chat {
+            nickname,
+            text,
+            timestamp_ms,
+            ..
+        } => {
+            let style = if highlights.matches(nickname, text) {
+                Style::default().fg(theme.accent).add_modifier(Modifier::BOLD)
+            } else {
+                Style::default().fg(theme.text)
+            };
+            push_chat_lines(lines, *timestamp_ms, relative_to, nickname, text, style, theme);
+        }
+        ChatLine::Spoiler {
+            nickname,
+            text,
+            timestamp_ms,
+            revealed,
+        } => {
+            // Hidden spoilers show one block per character (keeping the
+            // message's shape) until the viewer reveals them.
+            let (body, style) = if *revealed {
+                (text.clone(), Style::default().fg(theme.text))
+            } else {
+                (
+                    theme.text(&redact(text)).into_owned(),
+                    Style::default().fg(theme.text_muted),
+                )
+            };
+            push_chat_lines(lines, *timestamp_ms, relative_to, nickname, &body, style, theme);
+        }
+    }
+}
+
+// ── UI ───────────────────────────────────────────────────────────────────────
+//
+// Ratatui uses an "immediate mode" rendering model: every frame, we build up
+// the entire UI from scratch based on current state. No retained widget tree,
+// no diffing — just draw what the state says. This is simple and fast for TUIs.
+
+/// Format a unix timestamp (ms) as `HH:MM` UTC.
+pub fn format_timestamp(ts_ms: u64) -> String {
+    let secs = (ts_ms / 1000) as i64;
+    let hours = (secs / 3600) % 24;
+    let minutes = (secs / 60) % 60;
+    format!("{hours:02}:{minutes:02}")
+}
+
+/// Format how long a session has run: "0m", "12m", "1h 05m".
+pub fn format_session(elapsed: Duration) -> String {
+    let mins = elapsed.as_secs() / 60;
+    match mins / 60 {
+        0 => format!("{mins}m"),
+        hours => format!("{hours}h {:02}m", mins % 60),
+    }
+}
+
+/// Format a unix timestamp (ms) relative to `now_ms`: "now", "5m ago",
+/// "3h ago", "2d ago". Future times (peer clock skew) read as "now".
+pub fn format_relative(ts_ms: u64, now_ms: u64) -> String {
+    let secs = now_ms.saturating_sub(ts_ms) / 1000;
+    match secs {
+        0..60 => "now".to_string(),
+        60..3_600 => format!("{}m ago", secs / 60),
+        3_600..86_400 => format!("{}h ago", secs / 3_600),
+        _ => format!("{}d ago", secs / 86_400),
+    }
+}
+
+/// Format a span of time still to go, coarsely: "45s", "12m", "3h", "2d".
+pub fn format_remaining(ms: u64) -> String {
+    let secs = ms / 1000;
+    match secs {
+        0..60 => format!("{secs}s"),
+        60..3_600 => format!("{}m", secs / 60),
+        3_600..86_400 => format!("{}h", secs / 3_600),
+        _ => format!("{}d", secs / 86_400),
+    }
+}
+
+/// Parse a duration typed in a command: a number with a unit of `s`, `m`,
+/// `h` or `d` ("90s", "10m", "2h", "7d"). A bare number is minutes.
+pub fn parse_duration(text: &str) -> Option<std::time::Duration> {
+    let text = text.trim();
+    let split = text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number: u64 = number.parse().ok()?;
+    let secs = match unit {
+        "s" => number,
+        "" | "m" => number.checked_mul(60)?,
+        "h" => number.checked_mul(3_600)?,
+        "d" => number.checked_mul(86_400)?,
+        _ => return None,
+    };
+    (secs > 0).then(|| std::time::Duration::from_secs(secs))
+}
+
+/// Milliseconds in a day; `ts_ms / DAY_MS` is the UTC day number.
+const DAY_MS: u64 = 86_400_000;
+
+/// Convert a unix timestamp (ms) to a UTC `(year, month, day)`.
+///
+/// This is Howard Hinnant's `civil_from_days` algorithm: shift the epoch to
+/// March 1, year 0 so leap days fall at the end of a 400-year "era", then
+/// derive the date with integer arithmetic. It avoids pulling in a date
+/// crate for one separator line.
+pub fn civil_date(ts_ms: u64) -> (i64, u32, u32) {
+    let z = (ts_ms / DAY_MS) as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097); // day of era, [0, 146096]
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365; // year of era
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100); // day of year (from March 1)
+    let mp = (5 * doy + 2) / 153; // month, March = 0
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// The text of a day-change separator: "— March 4 —" (UTC).
+fn day_separator(ts_ms: u64) -> String {
+    const MONTHS: [&str; 12] = [
+        "January", "February", "March", "April", "May", "June", "July", "August",
+        "September", "October", "November", "December",
+    ];
+    let (_, month, day) = civil_date(ts_ms);
+    format!("— {} {day} —", MONTHS[month as usize - 1])
+}
+
+/// Render the chat UI into a terminal frame.
+///
+/// Takes `&mut App` because it rebuilds `click_regions` each frame.
+///
+/// `ratatui::Frame` is a mutable drawing surface for one frame. It provides
+/// `render_widget()` to place widgets at specific screen rectangles, and
+/// `set_cursor_position()` to show the blinking cursor.
+pub fn ui(f: &mut ratatui::Frame, app: &mut App) {
+    // Clear click regions — they're rebuilt every frame.
+    app.click_regions.clear();
+    // Paint the full-screen background so the theme bg covers the terminal area.
+    let bg_block = Block::default().style(Style::default().bg(app.theme.bg));
+    f.render_widget(bg_block, f.area());
+
+    // Build the vertical layout — conditionally include the file pane row when
+    // there are active offers/transfers. This demonstrates ratatui's `Layout`
+    // system: you specify constraints (Min, Length, Percentage) and the layout
+    // engine computes the actual pixel dimensions. `split()` returns a `Vec<Rect>`.
+    let rows = if app.transfers.has_entries() {
+        // Dynamic height: number of entries + 2 for the border, capped at 8.
+        let file_pane_height = (app.transfers.entries.len() as u16 + 2).min(8);
+        Layout::vertical([
+            Constraint::Min(1),                    // Messages pane (fills remaining space)
+            Constraint::Length(file_pane_height),   // File pane (fixed height)
+            Constraint::Length(3),                  // Input bar (3 rows: border + text + border)
+        ])
+        .split(f.area())
+    } else {
+        // No file transfers — just messages and input.
+        Layout::vertical([Constraint::Min(1), Constraint::Length(3)]).split(f.area())
+    };
+    // Split the top row into left (messages, flexible) and right (peers, 24 cols).
+    // `Layout::horizontal` works the same as vertical but splits left-to-right.
+    // Focus mode gives the messages the whole row.
+    let peers_width = if app.room_prefs.focus { 0 } else { 24 };
+    let top = Layout::horizontal([Constraint::Min(1), Constraint::Length(peers_width)]).split(rows[0]);
+
+    // ── Messages pane (top left) ─────────────────────────────────────────
+
+    // Turn each `ChatLine` into styled ratatui `Line`s. Building them is the
+    // most expensive part of a frame once history grows, so they're kept in
+    // `app.line_cache` and only new or changed messages are rendered here.
+    let theme = &app.theme;
+    let relative_to = app.config.relative_timestamps.then_some(app.now_ms);
+    let key = CacheKey {
+        theme: theme.mode,
+        width: top[0].width,
+        relative: relative_to.is_some(),
+        lang: app.config.lang,
+        focus: app.room_prefs.focus,
+    };
+    app.line_cache.sync(&app.messages, &app.threads, key, theme, relative_to, &app.highlights);
+    let total_lines = app.line_cache.lines.len();
+    // Line range `(start, end)` occupied by the selected message, so we can
+    // scroll it into view below.
+    let selected_lines = app.selected_message.and_then(|idx| app.line_cache.body_range(idx));
+
+    // Auto-scroll: calculate how many lines to skip so the newest messages
+    // are always visible. `saturating_sub` returns 0 instead of underflowing.
+    // `scroll_offset` allows manual scrollback via mouse wheel.
+    let visible = top[0].height.saturating_sub(2) as usize;
+    let max_scroll = total_lines.saturating_sub(visible) as u16;
+    // Clamp scroll_offset so it can't exceed actual content overflow.
+    // Without this, scrolling up past the top accumulates "dead" offset
+    // that makes scrolling back down feel unresponsive.
+    app.scroll_offset = app.scroll_offset.min(max_scroll);
+    // Keep the selected message on screen while navigating in select mode.
+    if let Some((start, end)) = selected_lines {
+        let top_line = (max_scroll - app.scroll_offset) as usize;
+        let top_line = if start < top_line {
+            start
+        } else if end > top_line + visible {
+            end.saturating_sub(visible)
+        } else {
+            top_line
+        };
+        app.scroll_offset = max_scroll - (top_line as u16).min(max_scroll);
+    }
+    let scroll = max_scroll - app.scroll_offset;
+
+    let mut msg_block = Block::default()
+        .borders(Borders::ALL)
+        .border_set(theme.border_set())
+        .style(Style::default().bg(theme.bg))
+        .border_style(Style::default().fg(theme.border))
+        .title(match &app.room_name {
+            Some(name) => format!("piper-chat · {name}"),
+            None => "piper-chat".to_string(),
+        })
+        .title_style(Style::default().fg(theme.title));
+    if app.scroll_offset > 0 {
+        msg_block = msg_block.title_bottom(
+            Line::from(Span::styled(
+                format!(" {} {}/{} ", theme.glyph("↑", "^"), app.scroll_offset, max_scroll),
+                Style::default().fg(theme.accent),
+            ))
+            .alignment(Alignment::Right),
+        );
+    }
+    // Only the lines on screen are copied out of the cache, so a frame's
+    // cost doesn't grow with the length of the history.
+    let first = scroll as usize;
+    let mut lines = app.line_cache.lines[first..(first + visible).min(total_lines)].to_vec();
+    if let Some((start, end)) = selected_lines {
+        // Paint the selection background across every span of the message.
+        for line in lines.iter_mut().take(end.saturating_sub(first)).skip(start.saturating_sub(first)) {
+            for span in &mut line.spans {
+                span.style = span.style.bg(theme.selection_bg);
+            }
+        }
+    }
+    let messages_widget = Paragraph::new(lines).block(msg_block);
+    f.render_widget(messages_widget, top[0]);
+    if app.open_thread.is_some() {
+        render_thread(f, top[0], app);
+    }
+
+    // Register click region for messages pane → focus chat (lower priority).
+    app.click_regions.push(ClickRegion {
+        rect: top[0],
+        action: ClickAction::FocusChat,
+    });
+
+    // ── Peers pane (top right) ───────────────────────────────────────────
+    //
+    // In focus mode `top[1]` has no width, so nothing here draws and the
+    // click regions it registers are empty.
+
+    // Split the peers area: peer list on top, copy-ticket button on bottom.
+    let show_copy_btn = app.ticket_str.is_some();
+    let btn_height = if show_copy_btn { 3 } else { 0 };
+    let peers_split = Layout::vertical([
+        Constraint::Min(1),
         Constraint::Length(btn_height),
     ])
     .split(top[1]);
 
-    // `.values()` iterates only over the `PeerInfo` values in the BTreeMap,
-    // skipping the keys. The `match` on `peer.conn_type` maps each connection
-    // type to a display tag and color.
-    // Sort peers so the local user (ConnType::You) appears first, then
-    // all other peers in their existing BTreeMap order.
-    let mut sorted_peers: Vec<&PeerInfo> = app.peers.values().collect();
-    sorted_peers.sort_by_key(|p| !matches!(p.conn_type, ConnType::You));
-    let peer_lines: Vec<Line> = sorted_peers
+    // The local user (ConnType::You) comes first, then verified contacts,
+    // then everyone else in the order picked with Ctrl+O. The `match` on
+    // `peer.conn_type` below maps each connection type to a tag and color.
+    let order = app.peer_order();
+    let sorted_peers: Vec<(&EndpointId, &PeerInfo)> = order.iter().filter_map(|id| Some((id, app.peers.get(id)?))).collect();
+    // When the list overflows the pane, show one page of it and use the
+    // last row for a summary that pages onward when clicked.
+    let visible = peers_split[0].height.saturating_sub(2) as usize;
+    let (page, summary) = if sorted_peers.len() > visible && visible >= 2 {
+        let per_page = visible - 1;
+        let pages = sorted_peers.len().div_ceil(per_page);
+        app.peers_page %= pages;
+        let start = app.peers_page * per_page;
+        let end = (start + per_page).min(sorted_peers.len());
+        let rest = sorted_peers.len() - end;
+        let summary = if rest > 0 {
+            t!(app.config.lang, "peers.more", count = rest)
+        } else {
+            t!(app.config.lang, "peers.top")
+        };
+        let summary = theme.text(&summary).into_owned();
+        (&sorted_peers[start..end], Some(summary))
+    } else {
+        app.peers_page = 0;
+        (&sorted_peers[..], None)
+    };
+    // Names more than one peer goes by get an ID tag in the list.
+    let mut name_counts: HashMap<&str, usize> = HashMap::new();
+    for (_, peer) in &sorted_peers {
+        *name_counts.entry(peer.name.as_str()).or_default() += 1;
+    }
+    let mut peer_lines: Vec<Line> = page
         .iter()
-        .map(|peer| {
+        .map(|(id, peer)| {
             let (tag, tag_color) = match peer.conn_type {
                 ConnType::Direct => ("[direct]", theme.conn_direct),
                 ConnType::Relay => ("[relay]", theme.conn_relay),
                 ConnType::Unknown => ("[?]", theme.conn_unknown),
                 ConnType::You => ("[you]", theme.conn_you),
             };
+            let name = if name_counts[peer.name.as_str()] > 1 { tag_name(&peer.name, id) } else { peer.name.clone() };
+            // Long names are cut to the pane so rows never wrap.
+            let name_width = (peers_split[0].width as usize).saturating_sub(3 + tag.len());
             Line::from(vec![
                 Span::styled(format!("{tag} "), Style::default().fg(tag_color)),
-                Span::styled(peer.name.as_str(), Style::default().fg(theme.peer_name)),
+                Span::styled(fit::middle(&name, name_width, theme.glyph("…", "...")).into_owned(), Style::default().fg(theme.peer_name)),
             ])
         })
         .collect();
-    let peers_widget = Paragraph::new(peer_lines).block(
-        Block::default()
-            .borders(Borders::ALL)
-            .style(Style::default().bg(theme.bg))
-            .border_style(Style::default().fg(theme.border))
-            .title("peers")
-            .title_style(Style::default().fg(theme.title)),
-    );
+    if let Some(summary) = summary {
+        peer_lines.push(Line::from(Span::styled(summary, Style::default().fg(theme.conn_unknown))));
+        let area = peers_split[0];
+        app.click_regions.push(ClickRegion {
+            rect: Rect::new(area.x + 1, area.y + 1 + page.len() as u16, area.width.saturating_sub(2), 1),
+            action: ClickAction::NextPeersPage,
+        });
+    }
+    let mut peers_block = Block::default()
+        .borders(Borders::ALL)
+        .border_set(theme.border_set())
+        .style(Style::default().bg(theme.bg))
+        .border_style(Style::default().fg(theme.border))
+        .title(t!(app.config.lang, "peers.title"))
+        .title_style(Style::default().fg(theme.title));
+    // The current sort, on the bottom border when the pane has one of its own.
+    if peers_split[0].height >= 2 {
+        peers_block = peers_block.title_bottom(
+            Line::from(Span::styled(
+                format!(" {} {} ", theme.glyph("↕", "sort:"), app.config.peer_sort.label(app.config.lang)),
+                Style::default().fg(theme.text_muted),
+            ))
+            .alignment(Alignment::Right),
+        );
+    }
+    let peers_widget = Paragraph::new(peer_lines).block(peers_block);
     f.render_widget(peers_widget, peers_split[0]);
 
     // Render the copy-ticket button below the peer list.
@@ -452,7 +2396,7 @@ pub fn ui(f: &mut ratatui::Frame, app: &mut App) {
             .is_some_and(|t| t > Instant::now());
         let (label, style) = if is_feedback {
             (
-                " Copied! ",
+                t!(app.config.lang, "ticket.copied"),
                 Style::default()
                     .fg(theme.bg)
                     .bg(theme.accent)
@@ -460,7 +2404,7 @@ pub fn ui(f: &mut ratatui::Frame, app: &mut App) {
             )
         } else {
             (
-                " Copy Ticket (Ctrl+Y) ",
+                t!(app.config.lang, "ticket.copy"),
                 Style::default()
                     .fg(theme.accent)
                     .add_modifier(Modifier::BOLD),
@@ -471,6 +2415,7 @@ pub fn ui(f: &mut ratatui::Frame, app: &mut App) {
             .block(
                 Block::default()
                     .borders(Borders::ALL)
+                    .border_set(theme.border_set())
                     .style(Style::default().bg(theme.bg))
                     .border_style(Style::default().fg(theme.accent)),
             );
@@ -497,16 +2442,98 @@ pub fn ui(f: &mut ratatui::Frame, app: &mut App) {
     } else {
         theme.border
     };
-    let input_widget = Paragraph::new(Line::from(vec![
-        Span::styled("> ", Style::default().fg(theme.input_prompt)),
-        Span::styled(&app.input, Style::default().fg(theme.text)),
-    ]))
-    .block(
-        Block::default()
-            .borders(Borders::ALL)
-            .style(Style::default().bg(theme.bg))
-            .border_style(Style::default().fg(input_border_color)),
-    );
+    let input_line = if let Some(pasted) = &app.pending_paste {
+        let count = pasted.lines().count();
+        let prompt = if is_large_text(pasted) {
+            t!(app.config.lang, "paste.large", count = count)
+        } else {
+            t!(app.config.lang, "paste.pending", count = count)
+        };
+        let prompt = theme.text(&prompt).into_owned();
+        Line::from(vec![
+            Span::styled("> ", Style::default().fg(theme.input_prompt)),
+            Span::styled(prompt, Style::default().fg(theme.accent).add_modifier(Modifier::ITALIC)),
+        ])
+    } else {
+        let mut spans = vec![Span::styled("> ", Style::default().fg(theme.input_prompt))];
+        spans.extend(input_spans(&app.input, app.cursor_pos, app.spell.as_ref(), theme));
+        Line::from(spans)
+    };
+    let mut input_block = Block::default()
+        .borders(Borders::ALL)
+        .border_set(theme.border_set())
+        .style(Style::default().bg(theme.bg))
+        .border_style(Style::default().fg(input_border_color));
+    if app.has_draft() {
+        input_block = input_block.title(Span::styled(
+            t!(app.config.lang, "input.draft"),
+            Style::default().fg(theme.text_muted).add_modifier(Modifier::ITALIC),
+        ));
+    }
+    if let Some(quote) = &app.replying_to {
+        input_block = input_block.title(Span::styled(
+            t!(app.config.lang, "input.reply", name = quote.nickname),
+            Style::default().fg(theme.accent).add_modifier(Modifier::ITALIC),
+        ));
+    }
+    if app.open_thread.is_some() {
+        input_block = input_block.title(Span::styled(
+            t!(app.config.lang, "input.thread"),
+            Style::default().fg(theme.accent).add_modifier(Modifier::ITALIC),
+        ));
+    }
+    if app.afk.is_some() {
+        input_block = input_block.title(Span::styled(
+            t!(app.config.lang, "input.afk"),
+            Style::default().fg(theme.accent).add_modifier(Modifier::ITALIC),
+        ));
+    }
+    // The status line: a path restriction, mute and focus, and the soonest
+    // shared timer counting down.
+    let paths = match app.paths {
+        Paths::Any => None,
+        Paths::Relay => Some(t!(app.config.lang, "paths.relay")),
+        Paths::Direct => Some(t!(app.config.lang, "paths.direct")),
+    };
+    if let Some(paths) = paths {
+        input_block = input_block.title_bottom(Line::from(Span::styled(
+            format!(" {} {paths} ", theme.glyph("⇄", "<>")),
+            Style::default().fg(theme.text_muted),
+        )));
+    }
+    for (on, key) in [(app.room_prefs.muted, "status.muted"), (app.room_prefs.focus, "status.focus")] {
+        if on {
+            input_block = input_block.title_bottom(Line::from(Span::styled(
+                format!(" {} ", app.config.lang.text(key)),
+                Style::default().fg(theme.text_muted),
+            )));
+        }
+    }
+    if let Some((timer, more)) = app.timers.next() {
+        let left = timers::format_remaining(timer.ends_ms.saturating_sub(app.now_ms));
+        let more = if more > 0 { format!(" (+{more})") } else { String::new() };
+        input_block = input_block.title_bottom(Line::from(Span::styled(
+            format!(" {} {} {left}{more} ", theme.glyph("⏱", "timer"), timer.label),
+            Style::default().fg(theme.accent),
+        )));
+    }
+    if let Some(clock) = &app.clock {
+        input_block = input_block.title_bottom(
+            Line::from(Span::styled(format!(" {clock} "), Style::default().fg(theme.text_muted))).alignment(Alignment::Right),
+        );
+    }
+    if let Some((toast, waiting)) = app.toasts.current() {
+        let color = match toast.kind {
+            ToastKind::Success => theme.success,
+            ToastKind::Error => theme.error,
+        };
+        let more = if waiting > 0 { format!(" (+{waiting})") } else { String::new() };
+        input_block = input_block.title_bottom(
+            Line::from(Span::styled(format!(" {}{more} ", toast.text), Style::default().fg(color).add_modifier(Modifier::BOLD)))
+                .alignment(Alignment::Right),
+        );
+    }
+    let input_widget = Paragraph::new(input_line).block(input_block);
     f.render_widget(input_widget, rows[input_row]);
 
     // Register click region for input bar → focus chat.
@@ -519,8 +2546,10 @@ pub fn ui(f: &mut ratatui::Frame, app: &mut App) {
     // `x + 2` accounts for the border (1) and the "> " prompt prefix (1 for ">").
     // Wait — actually it's: border(1) + ">" (1) + space is included in the +2.
     // `y + 1` accounts for the top border.
+    // The column is the *display width* of the text before the cursor, so
+    // double-width CJK characters move the cursor two cells.
     f.set_cursor_position((
-        rows[input_row].x + 2 + app.cursor_pos as u16,
+        rows[input_row].x + 2 + input_cursor_column(&app.input, app.cursor_pos, theme.glyph("⏎", "\\n")),
         rows[input_row].y + 1,
     ));
 
@@ -528,7 +2557,7 @@ pub fn ui(f: &mut ratatui::Frame, app: &mut App) {
 
     if app.transfers.has_entries() {
         let focused = matches!(app.mode, AppMode::FilePane);
-        transfer::render_file_pane(f, rows[1], &app.transfers, focused, theme);
+        transfer::render_file_pane(f, rows[1], &app.transfers, focused, app.now_ms, theme);
 
         // Register per-entry click regions for download/open actions.
         // Inner area is the pane area minus the 1-cell border on each side.
@@ -551,13 +2580,13 @@ pub fn ui(f: &mut ratatui::Frame, app: &mut App) {
             };
             let action = match &entry.state {
                 transfer::TransferState::Pending => {
-                    Some(ClickAction::DownloadTransfer(entry.offer.hash))
+                    Some(ClickAction::DownloadTransfer(entry.offer.entry_id()))
                 }
                 transfer::TransferState::Complete(_) => {
-                    Some(ClickAction::OpenTransfer(entry.offer.hash))
+                    Some(ClickAction::OpenTransfer(entry.offer.entry_id()))
                 }
                 transfer::TransferState::Sharing => {
-                    Some(ClickAction::UnshareTransfer(entry.offer.hash))
+                    Some(ClickAction::UnshareTransfer(entry.offer.entry_id()))
                 }
                 _ => None,
             };
@@ -582,7 +2611,26 @@ pub fn ui(f: &mut ratatui::Frame, app: &mut App) {
     // file picker is open (`Some`), we render it on top of everything else.
     // Because this is rendered *last*, it visually overlays the chat UI.
     if let Some(picker) = &app.file_picker {
-        picker.render(f);
+        picker.render(f, &app.theme);
+    }
+    if let Some(map) = &app.map {
+        map.render(f, &app.peers, app.config.lang, &app.theme);
+    }
+    if let Some(gallery) = &app.gallery {
+        gallery.render(f, &app.gallery_items(), app.config.lang, &app.theme);
+    }
+    transfer::render_details(f, &app.transfers, app.now_ms, &app.theme);
+    if matches!(app.mode, AppMode::FilePane) {
+        transfer::render_preview(f, &app.transfers, &app.theme);
+    }
+    transfer::render_confirm(f, &app.transfers, &app.theme);
+    palette::render(f, app.palette.as_ref(), app.config.lang, &app.theme);
+    admission::render_prompt(f, app.admission.as_ref(), app.config.lang, &app.theme);
+    joining::render_overlay(f, app.joining, app.now_ms, app.config.lang, &app.theme);
+    // Last, so nothing shows through a locked screen.
+    if let Some(session_lock) = &app.lock {
+        let new = app.history.iter().filter(|e| e.timestamp_ms >= session_lock.since_ms).count();
+        lock::render(f, Some(session_lock), new, app.config.lang, &app.theme);
     }
 }
 
@@ -663,4 +2711,961 @@ mod tests {
         assert!(matches!(&app.messages[1], ChatLine::Chat { .. }));
         assert!(matches!(&app.messages[2], ChatLine::Ticket(_)));
     }
+
+    /// Selection skips system lines and stops at both ends of the log.
+    #[test]
+    fn message_select_navigation() {
+        let mut app = App::new();
+        app.chat("A".into(), "one".into(), crate::net::new_message_id(), 1);
+        app.system("between");
+        app.spoiler("B".into(), "two".into(), crate::net::new_message_id(), 2);
+        app.system("after");
+
+        app.enter_message_select();
+        assert!(matches!(app.mode, AppMode::MessageSelect));
+        assert_eq!(app.selected_message, Some(2));
+        app.select_next_message();
+        assert_eq!(app.selected_message, Some(2));
+        app.select_prev_message();
+        assert_eq!(app.selected_message, Some(0));
+        app.select_prev_message();
+        assert_eq!(app.selected_message, Some(0));
+        app.exit_message_select();
+        assert!(app.selected_message.is_none());
+        assert!(matches!(app.mode, AppMode::Chat));
+    }
+
+    /// Entering select mode with no chat messages is a no-op.
+    #[test]
+    fn message_select_requires_messages() {
+        let mut app = App::new();
+        app.system("only system");
+        app.enter_message_select();
+        assert!(matches!(app.mode, AppMode::Chat));
+        assert!(app.selected_message.is_none());
+    }
+
+    /// Spoilers start hidden and toggle when selected.
+    #[test]
+    fn spoiler_reveal_toggle() {
+        let mut app = App::new();
+        app.spoiler("B".into(), "secret".into(), crate::net::new_message_id(), 1);
+        assert!(matches!(&app.messages[0], ChatLine::Spoiler { revealed: false, .. }));
+        app.enter_message_select();
+        app.toggle_selected();
+        assert!(matches!(&app.messages[0], ChatLine::Spoiler { revealed: true, .. }));
+        assert_eq!(redact("ab c"), "██ █");
+    }
+
+    /// Single-line pastes go straight into the input at the cursor.
+    #[test]
+    fn paste_single_line_inserts_at_cursor() {
+        let mut app = App::new();
+        app.input = "ad".into();
+        app.cursor_pos = 1;
+        app.paste("bc");
+        assert_eq!(app.input, "abcd");
+        assert_eq!(app.cursor_pos, 3);
+        assert!(app.pending_paste.is_none());
+    }
+
+    /// Multi-line pastes are held for confirmation instead of being typed.
+    #[test]
+    fn paste_multi_line_is_held() {
+        let mut app = App::new();
+        app.paste("one\r\ntwo\r\n");
+        assert!(app.input.is_empty());
+        assert_eq!(app.pending_paste.as_deref(), Some("one\ntwo"));
+    }
+
+    /// Newlines from multi-line compose show as one-cell `⏎` markers.
+    #[test]
+    fn input_display_marks_newlines() {
+        assert_eq!(input_display("a\nb", "⏎"), "a⏎b");
+        assert_eq!(input_cursor_column("a\nb", 2, "⏎"), 2);
+    }
+
+    /// Line endings are normalized to `\n` and trailing newlines dropped.
+    #[test]
+    fn normalize_paste_line_endings() {
+        assert_eq!(normalize_paste("a\rb\r\nc\n"), "a\nb\nc");
+        assert_eq!(normalize_paste("plain"), "plain");
+    }
+
+    /// Roll-call replies are collected until the window closes, then a
+    /// roster lists responders and silent peers.
+    #[test]
+    fn roll_call_collects_and_reports() {
+        let mut app = App::new();
+        let bob = EndpointId::from_bytes(&[1u8; 32]).unwrap();
+        let carol = EndpointId::from_bytes(&[3u8; 32]).unwrap();
+        app.peers.insert(carol, PeerInfo { name: "Carol".into(), conn_type: ConnType::Relay, rtt_ms: None, addr: None });
+        let rid = crate::net::new_message_id();
+        app.start_roll_call(rid);
+        // Replies to another request are ignored.
+        app.record_here(crate::net::new_message_id(), carol, "Carol".into(), 5);
+        app.record_here(rid, bob, "Bob".into(), 42);
+        assert!(app.peers.contains_key(&bob));
+
+        let started = app.roll_call.as_ref().unwrap().started;
+        app.finish_roll_call_if_due(started);
+        assert!(app.roll_call.is_some());
+        app.finish_roll_call_if_due(started + ROLL_CALL_WINDOW);
+        assert!(app.roll_call.is_none());
+        let text: Vec<_> = app
+            .messages
+            .iter()
+            .filter_map(|m| match m {
+                ChatLine::System(s) => Some(s.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert!(text.iter().any(|l| l.contains("Bob") && l.contains("42 ms")));
+        assert!(text.iter().any(|l| l.contains("Carol") && l.contains("no reply")));
+    }
+
+    /// Thread replies are counted against their root, skipped by message
+    /// selection, and rebuilt from history the same way.
+    #[test]
+    fn threads_index_replies_by_root() {
+        let mut app = App::new();
+        app.chat("Alice".into(), "standup?".into(), [1; 16], 1_700_000_000_000);
+        app.thread_chat("Bob".into(), "in 5".into(), [2; 16], 1_700_000_060_000, [1; 16]);
+        app.thread_chat("Carol".into(), "ok".into(), [3; 16], 1_700_000_120_000, [1; 16]);
+        assert_eq!(app.threads[&[1; 16]], 2);
+
+        // The newest selectable message is the root, not a reply.
+        app.enter_message_select();
+        assert_eq!(app.selected_message, Some(0));
+        app.open_selected_thread();
+        assert_eq!(app.open_thread, Some([1; 16]));
+        assert!(matches!(app.mode, AppMode::Chat));
+        assert_eq!(app.thread_messages().len(), 3);
+
+        let mut synced = App::new();
+        let (lines, merged) = synced.absorb_history(app.history.clone(), "Dan");
+        assert_eq!((lines.len(), merged), (3, 3));
+        assert_eq!(synced.threads, app.threads);
+    }
+
+    #[test]
+    fn replies_quote_the_original() {
+        let mut app = App::new();
+        app.chat("Alice".into(), "a".repeat(50), [1; 16], 1_700_000_000_000);
+        app.enter_message_select();
+        app.reply_to_selected();
+        let quote = app.replying_to.clone().unwrap();
+        assert_eq!((quote.nickname.as_str(), quote.excerpt.chars().count()), ("Alice", QUOTE_EXCERPT + 1));
+        assert!(quote.excerpt.ends_with('…'));
+
+        app.reply("Bob".into(), "same".into(), [2; 16], 1_700_000_060_000, [1; 16]);
+        app.reply("Bob".into(), "what?".into(), [3; 16], 1_700_000_120_000, [9; 16]);
+        let ChatLine::Chat { quote: Some(missing), .. } = &app.messages[2] else { panic!() };
+        assert!(missing.nickname.is_empty());
+
+        // A synced log resolves the quote from entries merged alongside it.
+        let mut synced = App::new();
+        let (lines, _) = synced.absorb_history(app.history.clone(), "Dan");
+        let ChatLine::Chat { quote: Some(found), .. } = &lines[1] else { panic!() };
+        assert_eq!(found, &quote);
+    }
+
+    #[test]
+    fn peers_sort_with_verified_contacts_first() {
+        let mut app = App::new();
+        let peer = |name: &str, conn_type, rtt_ms| PeerInfo { name: name.into(), conn_type, rtt_ms, addr: None };
+        app.add_peer(peer_id(1), peer("me", ConnType::You, None), true);
+        app.add_peer(peer_id(2), peer("zed", ConnType::Relay, Some(40)), true);
+        app.add_peer(peer_id(3), peer("Amy", ConnType::Unknown, None), true);
+        app.add_peer(peer_id(4), peer("bob", ConnType::Direct, Some(90)), true);
+        let names = |app: &App| app.peer_order().iter().map(|id| app.peers[id].name.clone()).collect::<Vec<_>>();
+        assert_eq!(names(&app), ["me", "zed", "Amy", "bob"]);
+
+        app.cycle_peer_sort();
+        assert_eq!(names(&app), ["me", "Amy", "bob", "zed"]);
+        app.cycle_peer_sort();
+        assert_eq!(names(&app), ["me", "zed", "bob", "Amy"]);
+        app.cycle_peer_sort();
+        assert_eq!(names(&app), ["me", "bob", "zed", "Amy"]);
+
+        // A verified contact jumps ahead in any order.
+        app.contacts.saw(peer_id(3), "Amy", &iroh_gossip::proto::TopicId::from_bytes([0; 32]), 0);
+        app.contacts.verify("Amy");
+        assert_eq!(names(&app), ["me", "Amy", "bob", "zed"]);
+        app.cycle_peer_sort();
+        assert_eq!(app.config.peer_sort, PeerSort::Joined);
+        assert_eq!(names(&app), ["me", "Amy", "zed", "bob"]);
+    }
+
+    #[test]
+    fn shared_nicknames_get_an_id_tag() {
+        let mut app = app_with_peers();
+        let bob2 = peer_id(4);
+        assert_eq!(app.display_name(&bob2, "Dan".into()), "Dan");
+        assert_eq!(app.display_name(&peer_id(2), "Bob".into()), "Bob");
+        let tagged = app.display_name(&bob2, "Bob".into());
+        assert_eq!(tagged, tag_name("Bob", &bob2));
+        assert!(tagged.starts_with("Bob#") && tagged.len() == 8);
+
+        // Both sides of a clash are warned: a second Bob, and a peer
+        // taking our own name.
+        app.warn_name_clash(&bob2, "Bob");
+        app.warn_name_clash(&bob2, "Alice");
+        app.warn_name_clash(&bob2, "Dan");
+        let ChatLine::System(clash) = &app.messages[0] else { panic!() };
+        assert!(clash.contains(&tag_name("Bob", &peer_id(2))) && clash.contains(&tagged));
+        let ChatLine::System(clash_you) = &app.messages[1] else { panic!() };
+        assert!(clash_you.starts_with("someone else joined as Alice"));
+        assert_eq!(app.messages.len(), 2);
+    }
+
+    #[test]
+    fn status_clock_follows_the_config() {
+        assert_eq!(format_session(Duration::from_secs(59)), "0m");
+        assert_eq!(format_session(Duration::from_secs(3_900)), "1h 05m");
+        let mut app = App::new();
+        app.update_clock(1_700_000_000_000, Duration::from_secs(720));
+        assert_eq!(app.clock.as_deref(), Some("22:13 · 12m"));
+        app.config.status_clock = false;
+        app.update_clock(1_700_000_000_000, Duration::from_secs(780));
+        assert_eq!(app.clock, None);
+    }
+
+    #[test]
+    fn timers_are_announced_once_when_up() {
+        let mut app = App::new();
+        app.now_ms = 1_000;
+        let timer = Timer { id: [1; 16], label: "standup".into(), set_by: "Alice".into(), ends_ms: 61_000 };
+        app.start_timer(timer.clone());
+        app.start_timer(timer);
+        assert_eq!(app.messages.len(), 1);
+
+        app.finish_timers(60_999);
+        assert_eq!(app.messages.len(), 1);
+        app.finish_timers(61_000);
+        app.finish_timers(62_000);
+        assert_eq!(app.messages.len(), 2);
+        assert!(app.toasts.current().unwrap().0.text.contains("standup"));
+    }
+
+    // ── Rendering snapshots ──────────────────────────────────────────────
+    //
+    // `TestBackend` is an in-memory ratatui backend: `ui()` draws into a
+    // plain buffer instead of a real terminal, so the rendered screen can be
+    // compared against a stored snapshot (`src/snapshots/*.snap`).
+
+    /// A valid endpoint ID derived from a fixed secret key.
+    fn peer_id(n: u8) -> EndpointId {
+        iroh::SecretKey::from_bytes(&[n; 32]).public()
+    }
+
+    /// Render `app` into a `width`×`height` test terminal.
+    fn render(app: &mut App, width: u16, height: u16) -> ratatui::backend::TestBackend {
+        let mut terminal =
+            ratatui::Terminal::new(ratatui::backend::TestBackend::new(width, height)).unwrap();
+        terminal.draw(|f| ui(f, app)).unwrap();
+        terminal.backend().clone()
+    }
+
+    /// An app with the local user plus two remote peers.
+    fn app_with_peers() -> App {
+        let mut app = App::new();
+        app.peers.insert(peer_id(1), PeerInfo { name: "Alice".into(), conn_type: ConnType::You, rtt_ms: None, addr: None });
+        app.peers.insert(peer_id(2), PeerInfo { name: "Bob".into(), conn_type: ConnType::Direct, rtt_ms: None, addr: None });
+        app.peers.insert(peer_id(3), PeerInfo { name: "Carol".into(), conn_type: ConnType::Relay, rtt_ms: None, addr: None });
+        app
+    }
+
+    /// The main pane shows a reply count under a thread's root; the open
+    /// thread covers it with the whole conversation.
+    #[test]
+    fn snapshot_thread_view() {
+        let mut app = app_with_peers();
+        app.chat("Alice".into(), "standup?".into(), [1; 16], 1_700_000_000_000);
+        app.thread_chat("Bob".into(), "in 5".into(), [2; 16], 1_700_000_060_000, [1; 16]);
+        app.thread_chat("Carol".into(), "ok".into(), [3; 16], 1_700_000_120_000, [1; 16]);
+        app.chat("Bob".into(), "lunch after?".into(), [4; 16], 1_700_000_180_000);
+        insta::assert_snapshot!("thread_main_pane", render(&mut app, 60, 10));
+        app.open_thread = Some([1; 16]);
+        insta::assert_snapshot!("thread_open", render(&mut app, 60, 10));
+    }
+
+    /// The soonest running timer counts down on the input bar's border.
+    #[test]
+    fn snapshot_timer_status() {
+        let mut app = app_with_peers();
+        app.now_ms = 1_700_000_000_000;
+        app.timers.schedule(Timer { id: [1; 16], label: "standup".into(), set_by: "Bob".into(), ends_ms: app.now_ms + 545_000 }, app.now_ms);
+        app.timers.schedule(Timer { id: [2; 16], label: "lunch".into(), set_by: "Bob".into(), ends_ms: app.now_ms + 3_600_000 }, app.now_ms);
+        insta::assert_snapshot!(render(&mut app, 60, 8));
+    }
+
+    /// The clock and session time sit at the right of the status line, a
+    /// path restriction at the left.
+    #[test]
+    fn snapshot_status_clock() {
+        let mut app = app_with_peers();
+        app.update_clock(1_700_000_000_000, Duration::from_secs(4_000));
+        app.paths = Paths::Relay;
+        insta::assert_snapshot!(render(&mut app, 60, 8));
+    }
+
+    /// Focus mode drops system lines and the peers pane; both it and mute
+    /// are named on the status line.
+    #[test]
+    fn snapshot_focus_mode() {
+        let mut app = app_with_peers();
+        app.system("Bob joined");
+        app.chat("Bob".into(), "anyone around?".into(), [1; 16], 1_700_000_000_000);
+        app.toggle_mute();
+        app.toggle_focus();
+        app.transfer_toast("report.pdf downloaded".into(), ToastKind::Success);
+        assert!(app.toasts.current().is_none());
+        insta::assert_snapshot!(render(&mut app, 60, 8));
+    }
+
+    /// A quote-reply shows a dimmed excerpt of the original above it.
+    #[test]
+    fn snapshot_quote_reply() {
+        let mut app = app_with_peers();
+        app.chat("Alice".into(), "deploy is done, can someone check the dashboard?".into(), [1; 16], 1_700_000_000_000);
+        app.reply("Bob".into(), "looks green".into(), [2; 16], 1_700_000_060_000, [1; 16]);
+        app.replying_to = Some(quote_of(&app.messages, [2; 16]));
+        insta::assert_snapshot!(render(&mut app, 60, 10));
+    }
+
+    #[test]
+    fn snapshot_connection_map() {
+        let mut app = app_with_peers();
+        app.open_map();
+        let ping = app.map.as_mut().unwrap().ping_due(Instant::now()).unwrap();
+        app.record_here(ping, peer_id(2), "Bob".into(), 23);
+        // A ping reply only updates the round trip; no roster is printed.
+        assert!(app.messages.is_empty());
+        assert_eq!(app.peers[&peer_id(2)].rtt_ms, Some(23));
+        insta::assert_snapshot!("connection_map", render(&mut app, 60, 16));
+        app.close_map();
+        assert!(matches!(app.mode, AppMode::Chat));
+    }
+
+    #[test]
+    fn snapshot_conversation() {
+        let mut app = app_with_peers();
+        app.system("Bob joined");
+        app.chat("Bob".into(), "hi all".into(), [1; 16], 1_700_000_000_000);
+        app.chat(
+            "Alice".into(),
+            "a much longer message that has to wrap onto several lines in a narrow pane".into(),
+            [2; 16],
+            1_700_000_060_000,
+        );
+        app.spoiler("Carol".into(), "the butler did it".into(), [3; 16], 1_700_000_120_000);
+        app.input = "typing…".into();
+        app.cursor_pos = app.input.len();
+        insta::assert_snapshot!(render(&mut app, 60, 14));
+    }
+
+    #[test]
+    fn snapshot_many_peers() {
+        let mut app = app_with_peers();
+        for n in 10..30 {
+            app.peers.insert(
+                peer_id(n),
+                PeerInfo { name: format!("peer-{n}"), conn_type: ConnType::Unknown, rtt_ms: None, addr: None },
+            );
+        }
+        insta::assert_snapshot!(render(&mut app, 60, 12));
+        // Clicking the summary row shows the next page.
+        app.peers_page += 1;
+        insta::assert_snapshot!("snapshot_many_peers_page_2", render(&mut app, 60, 12));
+    }
+
+    #[test]
+    fn snapshot_with_transfers() {
+        let mut app = app_with_peers();
+        for (i, name) in ["a.txt", "b.png", "c.zip", "d.pdf", "e.mp3"].iter().enumerate() {
+            app.transfers.add_offer(transfer::FileOffer {
+                sender_nickname: "Bob".into(),
+                sender_id: peer_id(2),
+                filename: name.to_string(),
+                size: 2048 * (i as u64 + 1),
+                hash: iroh_blobs::Hash::from_bytes([i as u8; 32]),
+                key: None,
+                offer_id: [i as u8; 16],
+            });
+        }
+        let id = |i: u8| app.transfers.entries[usize::from(i)].offer.entry_id();
+        let (one, two, three) = (id(1), id(2), id(3));
+        app.transfers.start_download(&one);
+        app.transfers.update_progress(&one, 1024, 4096);
+        app.transfers.complete_download(&two, "piper-files/c.zip".into());
+        app.transfers.fail_download(&three, "connection lost".into());
+        app.transfers.add_sent(transfer::FileOffer {
+            sender_nickname: "Alice".into(),
+            sender_id: peer_id(1),
+            filename: "mine.rs".into(),
+            size: 512,
+            hash: iroh_blobs::Hash::from_bytes([9; 32]),
+            key: None,
+            offer_id: [9; 16],
+        });
+        app.focus_file_pane();
+        insta::assert_snapshot!(render(&mut app, 70, 16));
+    }
+
+    /// `/files` lists the room's history: offers from before this session
+    /// too, with this session's state where there is one.
+    #[test]
+    fn snapshot_file_gallery() {
+        let mut app = app_with_peers();
+        for (i, (name, from, filename)) in
+            [("Bob", 2, "release-1.0.tar.gz"), ("Carol", 3, "standup-notes.md"), ("Bob", 2, "team-photo.jpg")].into_iter().enumerate()
+        {
+            let i = i as u8 + 1;
+            app.push_history(HistoryEntry {
+                message_id: [i; 16],
+                timestamp_ms: 1_700_000_000_000 + u64::from(i) * 3_600_000,
+                kind: HistoryEntryKind::FileOffer {
+                    nickname: name.into(),
+                    endpoint_id: peer_id(from),
+                    filename: filename.into(),
+                    size: 4096 * u64::from(i),
+                    hash: [i; 32],
+                    mime_type: None,
+                    target: None,
+                },
+            });
+        }
+        // Only the photo was offered this session, and it's downloaded.
+        let photo = app.gallery_items()[0].offer.clone();
+        app.transfers.add_offer(photo.clone());
+        app.transfers.complete_download(&photo.entry_id(), "piper-files/team-photo.jpg".into());
+        app.open_gallery();
+        insta::assert_snapshot!("file_gallery", render(&mut app, 80, 10));
+        app.close_gallery();
+        assert!(matches!(app.mode, AppMode::Chat));
+    }
+
+    /// Long names are cut in the middle so tags and states stay on screen.
+    #[test]
+    fn snapshot_long_names() {
+        let mut app = app_with_peers();
+        let long = "Bartholomew-the-Magnificent-III";
+        app.peers.insert(peer_id(4), PeerInfo { name: long.into(), conn_type: ConnType::Relay, rtt_ms: None, addr: None });
+        app.chat(long.into(), "hi".into(), [1; 16], 1_700_000_000_000);
+        app.transfers.add_offer(transfer::FileOffer {
+            sender_nickname: long.into(),
+            sender_id: peer_id(4),
+            filename: format!("{}-final.tar.gz", "quarterly-report-".repeat(8)),
+            size: 4096,
+            hash: iroh_blobs::Hash::from_bytes([1; 32]),
+            key: None,
+            offer_id: [1; 16],
+        });
+        insta::assert_snapshot!(render(&mut app, 70, 14));
+    }
+
+    #[test]
+    fn snapshot_help() {
+        let mut app = app_with_peers();
+        app.show_help();
+        insta::assert_snapshot!(render(&mut app, 72, 44));
+    }
+
+    /// A terminal far too small for the layout must still render without
+    /// panicking on underflowing rect arithmetic.
+    #[test]
+    fn snapshot_tiny_terminal() {
+        let mut app = app_with_peers();
+        app.chat("Bob".into(), "hello".into(), [1; 16], 0);
+        insta::assert_snapshot!(render(&mut app, 16, 4));
+    }
+
+    #[test]
+    fn file_picker_opens_in_remembered_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("photo.png");
+        let mut app = App::new();
+        assert!(app.remember_share_dir(&file));
+        assert!(!app.remember_share_dir(&file));
+        app.open_file_picker();
+        assert_eq!(app.file_picker.as_ref().unwrap().cwd(), dir.path());
+
+        // A remembered directory that no longer exists falls back to the cwd.
+        app.close_file_picker();
+        app.config.last_share_dir = Some(dir.path().join("gone"));
+        app.open_file_picker();
+        assert_eq!(app.file_picker.as_ref().unwrap().cwd(), std::env::current_dir().unwrap());
+    }
+
+    /// Misspelled words are underlined, except the one being typed.
+    #[test]
+    fn input_spans_underline_misspellings() {
+        let dict = Dictionary::from_words("hello\nworld\n");
+        let theme = Theme::dark();
+        let underlined = |spans: &[Span]| -> Vec<String> {
+            spans
+                .iter()
+                .filter(|s| s.style.add_modifier.contains(Modifier::UNDERLINED))
+                .map(|s| s.content.to_string())
+                .collect()
+        };
+        let spans = input_spans("helo wrld", 0, Some(&dict), &theme);
+        assert_eq!(underlined(&spans), ["helo", "wrld"]);
+        // Cursor at the end of "wrld": still being typed.
+        let spans = input_spans("helo wrld", 9, Some(&dict), &theme);
+        assert_eq!(underlined(&spans), ["helo"]);
+        let text: String = spans.iter().map(|s| s.content.as_ref()).collect();
+        assert_eq!(text, "helo wrld");
+        // No dictionary, no underlines.
+        assert!(underlined(&input_spans("helo", 0, None, &theme)).is_empty());
+    }
+
+    /// A restored draft is labelled until sent; unsent text is labelled
+    /// while focus is away from the input bar.
+    #[test]
+    fn draft_label() {
+        let mut app = App::new();
+        assert!(!app.has_draft());
+        app.restore_draft("half typed");
+        assert_eq!(app.cursor_pos, app.input.len());
+        assert!(app.has_draft());
+
+        app.draft_restored = false;
+        assert!(!app.has_draft());
+        app.mode = AppMode::FilePane;
+        assert!(app.has_draft());
+        let backend = render(&mut app, 60, 12);
+        assert!(backend.to_string().contains("(draft)"));
+    }
+
+    #[test]
+    fn civil_date_conversion() {
+        assert_eq!(civil_date(0), (1970, 1, 1));
+        // 2000-02-29 (leap day) and 2024-03-04, both at noon UTC.
+        assert_eq!(civil_date(951_825_600_000), (2000, 2, 29));
+        assert_eq!(civil_date(1_709_553_600_000), (2024, 3, 4));
+        assert_eq!(day_separator(1_709_553_600_000), "— March 4 —");
+    }
+
+    #[test]
+    fn relative_timestamps() {
+        let now = 10 * DAY_MS;
+        assert_eq!(format_relative(now - 5_000, now), "now");
+        assert_eq!(format_relative(now - 120_000, now), "2m ago");
+        assert_eq!(format_relative(now - 3 * 3_600_000, now), "3h ago");
+        assert_eq!(format_relative(now - 2 * DAY_MS, now), "2d ago");
+        assert_eq!(format_relative(now + 60_000, now), "now");
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("90s"), Some(std::time::Duration::from_secs(90)));
+        assert_eq!(parse_duration("10"), Some(std::time::Duration::from_secs(600)));
+        assert_eq!(parse_duration("2h"), Some(std::time::Duration::from_secs(7_200)));
+        assert_eq!(parse_duration("1d"), Some(std::time::Duration::from_secs(86_400)));
+        assert_eq!(parse_duration("0m"), None);
+        assert_eq!(parse_duration("soon"), None);
+        assert_eq!(format_remaining(45_000), "45s");
+        assert_eq!(format_remaining(12 * 60_000), "12m");
+        assert_eq!(format_remaining(3 * 3_600_000), "3h");
+    }
+
+    /// A separator appears between messages on different UTC days, not
+    /// before the first one.
+    #[test]
+    fn snapshot_day_separator() {
+        let mut app = App::new();
+        let day1 = 1_709_553_600_000; // 2024-03-04 12:00 UTC
+        app.chat("Alice".into(), "see you tomorrow".into(), crate::net::new_message_id(), day1);
+        app.chat("Bob".into(), "later".into(), crate::net::new_message_id(), day1 + 60_000);
+        app.chat("Alice".into(), "morning!".into(), crate::net::new_message_id(), day1 + DAY_MS);
+        app.config.relative_timestamps = true;
+        app.now_ms = day1 + DAY_MS + 5 * 60_000;
+        insta::assert_snapshot!(render(&mut app, 60, 10));
+    }
+
+    /// Long messages collapse to a preview until selected and toggled.
+    #[test]
+    fn snapshot_large_message_code_block() {
+        let mut app = App::new();
+        let text: Vec<String> = (1..=30).map(|i| format!("line {i}")).collect();
+        app.chat("Alice".into(), text.join("\n"), crate::net::new_message_id(), 0);
+        insta::assert_snapshot!("large_message_collapsed", render(&mut app, 60, 14));
+
+        app.enter_message_select();
+        app.toggle_selected();
+        assert!(matches!(&app.messages[0], ChatLine::Chat { expanded: true, .. }));
+        app.toggle_selected();
+        assert!(matches!(&app.messages[0], ChatLine::Chat { expanded: false, .. }));
+    }
+
+    #[test]
+    fn snapshot_fenced_code() {
+        let mut app = App::new();
+        let text = "try this:\n```rust\nfn main() {} // entry\n```\nworks?";
+        app.chat("Alice".into(), text.into(), crate::net::new_message_id(), 0);
+        insta::assert_snapshot!("fenced_code", render(&mut app, 60, 10));
+
+        app.enter_message_select();
+        assert_eq!(app.selected_code().as_deref(), Some("fn main() {} // entry"));
+        app.chat("Bob".into(), "no code".into(), crate::net::new_message_id(), 0);
+        app.select_next_message();
+        assert_eq!(app.selected_code(), None);
+    }
+
+    /// A large paste offers to go as an attachment.
+    #[test]
+    fn large_paste_prompts_for_attachment() {
+        let mut app = App::new();
+        let text: Vec<String> = (1..=25).map(|i| format!("line {i}")).collect();
+        app.paste(&text.join("\n"));
+        let screen = render(&mut app, 120, 8).to_string();
+        assert!(screen.contains("send 25 lines as attachment instead?"));
+        app.pending_paste = Some("a\nb".into());
+        let screen = render(&mut app, 120, 8).to_string();
+        assert!(screen.contains("[pasted 2 lines"));
+    }
+
+    #[test]
+    fn mention_detection() {
+        assert!(mentions("hey @Alice, you there?", "alice"));
+        assert!(mentions("@alice!", "Alice"));
+        assert!(!mentions("alice without an at", "Alice"));
+        assert!(!mentions("@alicea", "Alice"));
+    }
+
+    #[test]
+    fn highlight_words_match_whole_words() {
+        let highlights = Highlights::new("alice", &["Ana".into(), "  ".into(), "Piper".into()]);
+        assert!(highlights.matches("Bob", "Ana, look"));
+        assert!(!highlights.matches("Bob", "a banana"));
+        assert!(highlights.matches("Bob", "hey @Alice"));
+        assert!(highlights.matches("Bob", "shipping PIPER today"));
+        assert!(!highlights.matches("Bob", "bagpipers"));
+        assert!(!highlights.matches("alice", "talking about piper"));
+    }
+
+    #[test]
+    fn mentions_list_counts_matching_messages() {
+        let mut app = App::new();
+        app.show_mentions();
+        assert!(matches!(app.messages.last(), Some(ChatLine::System(s)) if s.starts_with("no mentions yet")));
+
+        app.highlights = Highlights::new("Ana", &["piper".into()]);
+        app.chat("Bob".into(), "@ana lunch?".into(), crate::net::new_message_id(), 1);
+        app.chat("Bob".into(), "unrelated".into(), crate::net::new_message_id(), 2);
+        app.chat("Carol".into(), "piper v2 is out".into(), crate::net::new_message_id(), 3);
+        let before = app.messages.len();
+        app.show_mentions();
+        let rows: Vec<_> = app.messages[before..]
+            .iter()
+            .map(|m| match m {
+                ChatLine::System(s) => s.as_str(),
+                _ => "",
+            })
+            .collect();
+        assert!(rows[0].contains("Mentions (2)"));
+        assert!(rows[1].ends_with("Bob: @ana lunch?"));
+        assert!(rows[2].ends_with("Carol: piper v2 is out"));
+    }
+
+    #[test]
+    fn afk_replies_once_per_peer() {
+        let mut app = App::new();
+        assert_eq!(app.afk_reply("Bob", "@Alice ping", "Alice"), None);
+
+        app.set_afk("back at 3pm".into());
+        assert_eq!(
+            app.afk_reply("Bob", "@Alice ping", "Alice").as_deref(),
+            Some("Alice is AFK: back at 3pm")
+        );
+        assert_eq!(app.afk_reply("Bob", "@Alice again", "Alice"), None);
+        assert_eq!(app.afk_reply("Carol", "no mention", "Alice"), None);
+        assert!(app.afk_reply("Carol", "@alice hi", "Alice").is_some());
+
+        app.clear_afk();
+        assert!(app.afk.is_none());
+        assert_eq!(app.afk_reply("Dave", "@Alice hi", "Alice"), None);
+    }
+
+    #[test]
+    fn backfill_returns_only_missed_entries() {
+        let entry = |id: u8, ts: u64| HistoryEntry {
+            message_id: [id; 16],
+            timestamp_ms: ts,
+            kind: HistoryEntryKind::Chat {
+                nickname: "Bob".into(),
+                text: format!("msg {id}"),
+            },
+        };
+        // We saw 1 and 3 but lagged through 2 and 4.
+        let mut ours = App::new();
+        ours.history = vec![entry(1, 100), entry(3, 300)];
+        let (since_ms, known) = ours.backfill_bounds();
+        assert_eq!(since_ms, 100);
+
+        let mut theirs = App::new();
+        theirs.history = vec![entry(0, 50), entry(1, 100), entry(2, 200), entry(3, 300), entry(4, 400)];
+        let missed = theirs.backfill_for(since_ms, &known);
+        let ids: Vec<u8> = missed.iter().map(|e| e.message_id[0]).collect();
+        assert_eq!(ids, [2, 4]);
+
+        for e in &ours.history {
+            ours.seen_ids.insert(e.message_id);
+        }
+        let (lines, merged) = ours.absorb_history(missed, "Alice");
+        assert_eq!(merged, 2);
+        assert!(matches!(&lines[0], ChatLine::Chat { text, .. } if text == "msg 2"));
+        assert_eq!(ours.history.len(), 4);
+    }
+
+    #[test]
+    fn synced_revocations_withdraw_only_the_named_offer() {
+        let (us, bob, carol) = (
+            iroh::SecretKey::from_bytes(&[1; 32]).public(),
+            iroh::SecretKey::from_bytes(&[2; 32]).public(),
+            iroh::SecretKey::from_bytes(&[3; 32]).public(),
+        );
+        let hash = [7u8; 32];
+        let offer = |sender, offer_id| transfer::FileOffer {
+            sender_nickname: "x".into(),
+            sender_id: sender,
+            filename: "f.txt".into(),
+            size: 1,
+            hash: iroh_blobs::Hash::from_bytes(hash),
+            key: None,
+            offer_id,
+        };
+        let mut app = App::new();
+        app.add_peer(us, PeerInfo { name: "Alice".into(), conn_type: ConnType::You, rtt_ms: None, addr: None }, true);
+        app.transfers.add_sent(offer(us, [1; 16]));
+        app.transfers.add_offer(offer(bob, [2; 16]));
+        app.transfers.add_offer(offer(carol, [3; 16]));
+
+        let entry = |id: u8, kind| HistoryEntry { message_id: [id; 16], timestamp_ms: 100, kind };
+        app.absorb_history(
+            vec![
+                // Hash-only, and a revocation claiming to be ours: ignored.
+                entry(10, HistoryEntryKind::FileRetract { hash }),
+                entry(11, HistoryEntryKind::FileRevoke { hash, endpoint_id: us, offer_id: [1; 16] }),
+                // Names an offer Carol never made.
+                entry(12, HistoryEntryKind::FileRevoke { hash, endpoint_id: carol, offer_id: [2; 16] }),
+                entry(13, HistoryEntryKind::FileRevoke { hash, endpoint_id: bob, offer_id: [2; 16] }),
+            ],
+            "Alice",
+        );
+        let left: Vec<_> = app.transfers.entries.iter().map(|e| e.offer.sender_id).collect();
+        assert_eq!(left, [us, carol]);
+    }
+
+    #[test]
+    fn contacts_listing() {
+        let mut app = App::new();
+        app.show_contacts();
+        assert!(matches!(app.messages.last(), Some(ChatLine::System(s)) if s.starts_with("no contacts")));
+
+        let bob = EndpointId::from_bytes(&[1u8; 32]).unwrap();
+        let room = iroh_gossip::proto::TopicId::from_bytes([1; 32]);
+        app.contacts.saw(bob, "Bob", &room, 0);
+        app.contacts.verify("Bob");
+        app.now_ms = 2 * 3_600_000;
+        app.show_contacts();
+        let listing: Vec<&str> = app
+            .messages
+            .iter()
+            .filter_map(|m| match m {
+                ChatLine::System(s) => Some(s.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert!(listing.iter().any(|l| l.contains("✓ Bob") && l.contains("seen 2h ago") && l.contains("1 room")));
+    }
+
+    #[test]
+    fn resuming_clears_stale_peers() {
+        let mut app = App::new();
+        app.peers.insert(peer_id(1), PeerInfo { name: "Alice".into(), conn_type: ConnType::You, rtt_ms: None, addr: None });
+        app.peers.insert(peer_id(2), PeerInfo { name: "Bob".into(), conn_type: ConnType::Direct, rtt_ms: Some(9), addr: None });
+        app.resumed(Duration::from_secs(2 * 3600 + 300));
+        assert_eq!(app.peers.keys().collect::<Vec<_>>(), [&peer_id(1)]);
+        assert!(matches!(app.messages.last(), Some(ChatLine::System(s)) if s.contains("2h 05m")));
+    }
+
+    #[test]
+    fn net_diagnostics_name_families_and_paths() {
+        let mut app = App::new();
+        let peer = |name: &str, conn_type, addr: Option<&str>| PeerInfo {
+            name: name.into(),
+            conn_type,
+            rtt_ms: None,
+            addr: addr.map(|a| a.parse().unwrap()),
+        };
+        app.peers.insert(peer_id(1), peer("Bob", ConnType::Direct, Some("[2001:db8::7]:4433")));
+        app.peers.insert(peer_id(2), peer("Carol", ConnType::Relay, None));
+        let reach = Reachability {
+            v4: tracker::Family::default(),
+            v6: tracker::Family { udp: true, public: Some("[2001:db8::1]:5000".parse().unwrap()) },
+            relay: None,
+        };
+        app.show_net(Some(&reach));
+        let listing: Vec<&str> = app
+            .messages
+            .iter()
+            .filter_map(|m| match m {
+                ChatLine::System(s) => Some(s.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert!(listing.contains(&"  IPv4: unreachable (no UDP round trip)"));
+        assert!(listing.contains(&"  IPv6: reachable, public address [2001:db8::1]:5000"));
+        assert!(listing.iter().any(|l| l.starts_with("  IPv6 only")));
+        assert!(listing.contains(&"  Bob: direct over IPv6 ([2001:db8::7]:4433)"));
+        assert!(listing.contains(&"  Carol: relay"));
+
+        app.messages.clear();
+        app.show_net(None);
+        assert!(matches!(&app.messages[1], ChatLine::System(s) if s.contains("no network report")));
+    }
+
+    #[test]
+    fn dm_target_and_room_switch() {
+        let mut app = app_with_peers();
+        let contact = EndpointId::from_bytes(&[3u8; 32]).unwrap();
+        let room = iroh_gossip::proto::TopicId::from_bytes([1; 32]);
+        app.contacts.saw(contact, "Zed", &room, 0);
+        let (peer_id, peer_name) = app
+            .peers
+            .iter()
+            .find(|(_, p)| !matches!(p.conn_type, ConnType::You))
+            .map(|(id, p)| (*id, p.name.clone()))
+            .unwrap();
+        assert_eq!(app.dm_target(&peer_name.to_uppercase()), Some(peer_id));
+        assert_eq!(app.dm_target("zed"), Some(contact));
+        assert_eq!(app.dm_target("nobody"), None);
+
+        app.chat("Bob".into(), "old room".into(), crate::net::new_message_id(), 0);
+        app.input = "keep me".into();
+        app.enter_room("dmticket".into());
+        assert!(app.peers.values().all(|p| matches!(p.conn_type, ConnType::You)));
+        assert!(app.history.is_empty());
+        assert!(matches!(app.messages.as_slice(), [ChatLine::Ticket(t)] if t == "dmticket"));
+        assert_eq!(app.input, "keep me");
+    }
+
+    #[test]
+    fn peers_map_evicts_anonymous_first() {
+        let mut app = App::new();
+        let anon = |n: u32| PeerInfo { name: format!("anon-{n}"), conn_type: ConnType::Unknown, rtt_ms: None, addr: None };
+        let id = |n: u32| {
+            let mut seed = [0u8; 32];
+            seed[..4].copy_from_slice(&n.to_le_bytes());
+            iroh::SecretKey::from_bytes(&seed).public()
+        };
+        for n in 0..MAX_PEERS as u32 {
+            assert!(app.add_peer(id(n), anon(n), n % 2 == 0));
+        }
+        // Full: the newcomer replaces the oldest anonymous peer (n = 1).
+        assert!(app.add_peer(id(1000), anon(1000), false));
+        assert_eq!(app.peers.len(), MAX_PEERS);
+        assert!(!app.peers.contains_key(&id(1)));
+        assert!(app.peers.contains_key(&id(0)));
+        // The blobs protocol's view follows along.
+        assert!(!app.members.contains(&id(1)));
+        assert!(app.members.contains(&id(0)));
+        // Identifying takes a peer out of the eviction queue.
+        app.add_peer(id(3), anon(3), true);
+        app.add_peer(id(1001), anon(1001), false);
+        assert!(app.peers.contains_key(&id(3)));
+        assert!(!app.peers.contains_key(&id(5)));
+        // Once only identified peers remain, newcomers are turned away.
+        while let Some(stale) = app.anonymous_peers.front().copied() {
+            app.remove_peer(&stale);
+        }
+        while app.peers.len() < MAX_PEERS {
+            let n = 2000 + app.peers.len() as u32;
+            app.add_peer(id(n), anon(n), true);
+        }
+        assert!(!app.add_peer(id(5000), anon(5000), false));
+    }
+
+    #[test]
+    fn line_cache_renders_only_changes() {
+        let theme = Theme::dark();
+        let key = CacheKey { theme: theme.mode, width: 60, relative: false, lang: Lang::En, focus: false };
+        let mut app = App::new();
+        app.system("hello");
+        app.spoiler("Bob".into(), "secret".into(), [1; 16], 1_700_000_000_000);
+        let mut cache = LineCache::default();
+        cache.sync(&app.messages, &app.threads, key, &theme, None, &Highlights::default());
+        assert_eq!(cache.lines.len(), 2);
+
+        // Mark the first line: it survives as long as the cache reuses it.
+        let marker = Line::from("cached");
+        cache.lines[0] = marker.clone();
+        app.system("another");
+        cache.sync(&app.messages, &app.threads, key, &theme, None, &Highlights::default());
+        assert_eq!(cache.lines.len(), 3);
+        assert_eq!(cache.lines[0], marker);
+        assert_eq!(cache.body_range(2), Some((2, 3)));
+
+        // Revealing the spoiler re-renders it (and what follows), not "hello".
+        app.selected_message = Some(1);
+        app.toggle_selected();
+        cache.sync(&app.messages, &app.threads, key, &theme, None, &Highlights::default());
+        assert_eq!(cache.lines[0], marker);
+        assert!(cache.lines[1].to_string().contains("secret"));
+
+        // A theme toggle or resize starts over.
+        cache.sync(&app.messages, &app.threads, CacheKey { width: 80, ..key }, &theme, None, &Highlights::default());
+        assert_ne!(cache.lines[0], marker);
+    }
+
+    /// An edit in place — same allocation, same length — still re-renders.
+    #[test]
+    fn line_cache_sees_edits_in_place() {
+        let theme = Theme::dark();
+        let key = CacheKey { theme: theme.mode, width: 60, relative: false, lang: Lang::En, focus: false };
+        let mut app = App::new();
+        app.system("hello");
+        let mut cache = LineCache::default();
+        cache.sync(&app.messages, &app.threads, key, &theme, None, &Highlights::default());
+        let ChatLine::System(text) = &mut app.messages[0] else { unreachable!() };
+        text.replace_range(.., "jello");
+        cache.sync(&app.messages, &app.threads, key, &theme, None, &Highlights::default());
+        assert!(cache.lines[0].to_string().contains("jello"));
+    }
+
+    /// Relative timestamps age in place: the clock moving on rewrites the
+    /// timestamp span and keeps the rest of the cached line.
+    #[test]
+    fn relative_timestamps_age_without_a_rerender() {
+        let theme = Theme::dark();
+        let key = CacheKey { theme: theme.mode, width: 60, relative: true, lang: Lang::En, focus: false };
+        let now = 1_700_000_000_000;
+        let mut app = App::new();
+        app.spoiler("Bob".into(), "secret".into(), [1; 16], now);
+        let mut cache = LineCache::default();
+        cache.sync(&app.messages, &app.threads, key, &theme, Some(now), &Highlights::default());
+        assert!(cache.lines[0].to_string().starts_with("now "));
+
+        // Mark the rest of the line: it survives the refresh.
+        cache.lines[0].spans[1] = Span::raw("cached");
+        cache.sync(&app.messages, &app.threads, key, &theme, Some(now + 500), &Highlights::default());
+        cache.sync(&app.messages, &app.threads, key, &theme, Some(now + 120_000), &Highlights::default());
+        assert_eq!(cache.lines[0].spans[0].content, "2m ago ");
+        assert_eq!(cache.lines[0].spans[1].content, "cached");
+    }
+
+    #[test]
+    fn transfer_toasts_skip_the_focused_file_pane() {
+        let mut app = App::new();
+        app.transfer_toast("download complete: a.txt".into(), ToastKind::Success);
+        assert_eq!(app.toasts.current().unwrap().0.text, "download complete: a.txt");
+
+        let mut app = App::new();
+        app.focus_file_pane();
+        app.transfer_toast("download complete: a.txt".into(), ToastKind::Success);
+        assert!(app.toasts.current().is_none());
+    }
 }
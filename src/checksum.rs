@@ -0,0 +1,115 @@
+//! Checksums for downloaded files: `.sha256` sidecars and `/verifyfile`.
+//!
+//! A download is verified against its BLAKE3 hash while it streams in, but
+//! once it's on disk nothing notices if it's later corrupted or edited. Two
+//! tools cover that:
+//!
+//! - **Sidecars** — with `checksum_sidecar = true` in the config, every
+//!   completed download gets a `<file>.sha256` next to it, in the format
+//!   `sha256sum -c` reads. SHA-256 rather than BLAKE3 because that's what
+//!   every OS ships a checker for. Opening the file from the files pane
+//!   re-checks the sidecar first and refuses on a mismatch.
+//! - **`/verifyfile <n>`** — re-hashes the file on disk with BLAKE3 and
+//!   compares it to the hash it was offered under, which needs no sidecar.
+//!
+//! Both read the whole file, so callers run them on a blocking thread.
+
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use iroh_blobs::Hash;
+use sha2::{Digest, Sha256};
+
+/// How much of a file is hashed per read.
+const CHUNK: usize = 64 * 1024;
+
+/// Feed the file at `path` through `update`, one chunk at a time.
+fn read_chunks(path: &Path, mut update: impl FnMut(&[u8])) -> io::Result<()> {
+    let mut file = File::open(path)?;
+    let mut buf = vec![0u8; CHUNK];
+    loop {
+        match file.read(&mut buf)? {
+            0 => return Ok(()),
+            n => update(&buf[..n]),
+        }
+    }
+}
+
+/// SHA-256 of the file at `path`, as lowercase hex.
+pub fn sha256_file(path: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    read_chunks(path, |chunk| hasher.update(chunk))?;
+    Ok(hasher.finalize().iter().map(|b| format!("{b:02x}")).collect())
+}
+
+/// BLAKE3 of the file at `path` — the same hash iroh-blobs names it by.
+pub fn blake3_file(path: &Path) -> io::Result<Hash> {
+    let mut hasher = blake3::Hasher::new();
+    read_chunks(path, |chunk| {
+        hasher.update(chunk);
+    })?;
+    Ok(Hash::from(hasher.finalize()))
+}
+
+/// Where the sidecar for `path` goes: `photo.jpg` → `photo.jpg.sha256`.
+pub fn sidecar_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".sha256");
+    PathBuf::from(name)
+}
+
+/// Hash the file at `path` and write its sidecar, as
+/// `<hex>  <file name>\n` (two spaces: `sha256sum`'s binary-safe format).
+pub fn write_sidecar(path: &Path) -> io::Result<()> {
+    let hex = sha256_file(path)?;
+    let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    std::fs::write(sidecar_path(path), format!("{hex}  {name}\n"))
+}
+
+/// Check the file at `path` against its sidecar: `None` if there is no
+/// sidecar, otherwise whether the hashes match. A sidecar that doesn't
+/// start with a hex digest counts as a mismatch.
+pub fn check_sidecar(path: &Path) -> io::Result<Option<bool>> {
+    let text = match std::fs::read_to_string(sidecar_path(path)) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let expected = text.split_whitespace().next().unwrap_or_default().to_ascii_lowercase();
+    Ok(Some(expected == sha256_file(path)?))
+}
+
+// ── Tests ────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sidecar_roundtrip_detects_tampering() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.txt");
+        std::fs::write(&path, b"abc").unwrap();
+        assert_eq!(check_sidecar(&path).unwrap(), None);
+
+        write_sidecar(&path).unwrap();
+        let sidecar = std::fs::read_to_string(dir.path().join("notes.txt.sha256")).unwrap();
+        // The well-known SHA-256 test vector for "abc".
+        assert_eq!(sidecar, "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad  notes.txt\n");
+        assert_eq!(check_sidecar(&path).unwrap(), Some(true));
+
+        std::fs::write(&path, b"abd").unwrap();
+        assert_eq!(check_sidecar(&path).unwrap(), Some(false));
+    }
+
+    #[test]
+    fn blake3_matches_the_blob_hash() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("data.bin");
+        // Bigger than one read, so chunking is exercised.
+        let data = vec![42u8; CHUNK * 2 + 17];
+        std::fs::write(&path, &data).unwrap();
+        assert_eq!(blake3_file(&path).unwrap(), Hash::new(&data));
+    }
+}
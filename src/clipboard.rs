@@ -0,0 +1,70 @@
+//! System clipboard access without a native clipboard dependency.
+//!
+//! **Copying** uses the OSC 52 escape sequence: the terminal itself sets the
+//! clipboard, which works over SSH and is supported by most modern terminals
+//! (kitty, iTerm2, alacritty, wezterm, Windows Terminal, etc.).
+//!
+//! **Pasting** is harder — an OSC 52 *query* makes the terminal reply on
+//! stdin, where the reply would be mixed into crossterm's key event stream,
+//! and many terminals refuse it for security anyway. Instead we ask the
+//! platform's clipboard tool (`pbpaste`, `wl-paste`, `xclip`, ...) for the
+//! contents. Bracketed paste (the terminal's own paste shortcut) remains the
+//! most reliable path; this is the Ctrl+V convenience on top.
+
+use std::process::{Command, Stdio};
+
+/// Put `text` on the clipboard via OSC 52. Best-effort: write errors are
+/// ignored because there is nothing useful to do about them mid-frame.
+pub fn copy(text: &str) {
+    use base64::Engine;
+    use std::io::Write;
+    let b64 = base64::engine::general_purpose::STANDARD.encode(text.as_bytes());
+    // OSC 52: set clipboard. `c` = system clipboard.
+    let osc = format!("\x1b]52;c;{b64}\x07");
+    let mut stdout = std::io::stdout();
+    let _ = stdout.write_all(osc.as_bytes());
+    let _ = stdout.flush();
+}
+
+/// Read the system clipboard as text, or `None` if no clipboard tool is
+/// available (or the clipboard is empty / not text).
+///
+/// Candidates are tried in order; the first that runs successfully wins.
+/// `Stdio::null()` keeps the tools from inheriting our raw-mode terminal.
+pub fn paste() -> Option<String> {
+    paste_commands().iter().find_map(|(program, args)| {
+        let output = Command::new(program)
+            .args(*args)
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output()
+            .ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        let text = String::from_utf8(output.stdout).ok()?;
+        (!text.is_empty()).then_some(text)
+    })
+}
+
+/// Clipboard-reading commands for the current platform, most specific first.
+fn paste_commands() -> Vec<(&'static str, &'static [&'static str])> {
+    if cfg!(target_os = "macos") {
+        vec![("pbpaste", &[])]
+    } else if cfg!(windows) {
+        vec![(
+            "powershell",
+            &["-NoProfile", "-Command", "Get-Clipboard -Raw"],
+        )]
+    } else {
+        let mut cmds: Vec<(&'static str, &'static [&'static str])> = Vec::new();
+        // Prefer the Wayland tool inside a Wayland session; XWayland's
+        // clipboard can lag behind the compositor's.
+        if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+            cmds.push(("wl-paste", &["--no-newline"]));
+        }
+        cmds.push(("xclip", &["-selection", "clipboard", "-o"]));
+        cmds.push(("xsel", &["--clipboard", "--output"]));
+        cmds
+    }
+}
@@ -0,0 +1,358 @@
+//! Slash commands: the table of them, parsing their arguments, and Tab
+//! completion.
+//!
+//! Every command is listed once in `COMMANDS` with its usage string, so
+//! the usage shown after a bad argument and the hint Tab completion gives
+//! can't drift apart. Arguments are split like a shell would, only
+//! simpler:
+//!
+//! ```text
+//! /export --format html "book club.html"   →  flag format=html, word "book club.html"
+//! /export --format=json                     →  flag format=json
+//! /dm 'Mary Jane'                           →  word "Mary Jane"
+//! ```
+//!
+//! Double or single quotes group words, a backslash takes the next
+//! character as it is, and `--` ends the flags. Flags only count unquoted.
+//!
+//! Commands whose argument is free text (`/spoiler`, `/afk`, `/shrug`, a
+//! `/timer` label) are in the table for their usage and completion but
+//! read their text raw, so an apostrophe in a message isn't taken for a
+//! quote.
+//!
+//! Anything typed that starts with `/` is a command: `check` turns an
+//! unknown name, or arguments its spec refuses, into a notice before the
+//! input is dispatched, so a typo like `/mpa` never reaches the room as
+//! chat.
+
+use crate::i18n::Lang;
+use crate::t;
+
+/// One slash command.
+#[derive(Debug, PartialEq)]
+pub struct Spec {
+    pub name: &'static str,
+    pub usage: &'static str,
+    /// Flags it knows, each taking a value (`--format html`).
+    pub flags: &'static [&'static str],
+    /// How many words it needs.
+    pub min: usize,
+    /// How many words it takes. With `rest`, words past the last are
+    /// joined onto it, so `/dm Mary Jane` needs no quotes.
+    pub max: usize,
+    pub rest: bool,
+    /// Reads its argument as raw free text, so `check` leaves it alone.
+    pub raw: bool,
+}
+
+const fn spec(name: &'static str, usage: &'static str, min: usize, max: usize) -> Spec {
+    Spec { name, usage, flags: &[], min, max, rest: false, raw: false }
+}
+
+/// A command whose last word takes whatever follows it.
+const fn rest(name: &'static str, usage: &'static str, min: usize, max: usize) -> Spec {
+    Spec { name, usage, flags: &[], min, max, rest: true, raw: false }
+}
+
+/// A command that reads its argument as free text (see the module docs).
+const fn text(name: &'static str, usage: &'static str, min: usize, max: usize) -> Spec {
+    Spec { name, usage, flags: &[], min, max, rest: true, raw: true }
+}
+
+/// Every slash command, in the order `/help` lists them.
+pub const COMMANDS: &[Spec] = &[
+    spec("/help", "/help", 0, 0),
+    spec("/send", "/send", 0, 0),
+    rest("/sendto", "/sendto <name>", 1, 1),
+    text("/shrug", "/shrug [text]", 0, 1),
+    text("/spoiler", "/spoiler <text>", 1, 1),
+    text("/afk", "/afk [message]", 0, 1),
+    spec("/contacts", "/contacts", 0, 0),
+    rest("/dm", "/dm <name>", 1, 1),
+    spec("/join", "/join <ticket>", 1, 1),
+    rest("/verify", "/verify <name>", 1, 1),
+    rest("/owner", "/owner [name]", 0, 1),
+    spec("/who", "/who", 0, 0),
+    text("/timer", "/timer <10m|90s|1h> [label]", 1, 2),
+    spec("/map", "/map", 0, 0),
+    spec("/files", "/files", 0, 0),
+    spec("/mute", "/mute", 0, 0),
+    spec("/mentions", "/mentions", 0, 0),
+    spec("/focus", "/focus", 0, 0),
+    spec("/net", "/net", 0, 0),
+    spec("/lock", "/lock", 0, 0),
+    spec("/unshare", "/unshare <n>", 1, 1),
+    spec("/expire", "/expire <n> <30m|2h|1d>", 2, 2),
+    spec("/verifyfile", "/verifyfile <n>", 1, 1),
+    Spec { name: "/export", usage: "/export [--format text|html|json] [file]", flags: &["format"], min: 0, max: 1, rest: false, raw: false },
+];
+
+/// The command called `name` (with its slash).
+pub fn find(name: &str) -> Option<&'static Spec> {
+    COMMANDS.iter().find(|c| c.name == name)
+}
+
+/// The usage string of command `name`, for a `usage` notice.
+pub fn usage(name: &str) -> &'static str {
+    find(name).map_or("/help", |c| c.usage)
+}
+
+/// What was wrong with a command's arguments.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    /// A quote was opened and never closed.
+    Unclosed,
+    /// A `--flag` the command doesn't know.
+    UnknownFlag(String),
+    /// A `--flag` with nothing after it.
+    NoValue(String),
+    /// Fewer words than the command needs.
+    Missing,
+    /// More words than it takes.
+    TooMany,
+    /// A flag's value that makes no sense, like `--format pdf`.
+    BadValue(String, String),
+}
+
+impl ParseError {
+    /// The notice for a bad `name` command: what went wrong, then its usage.
+    pub fn notice(&self, name: &str, lang: Lang) -> String {
+        let usage = t!(lang, "usage", usage = usage(name));
+        match self {
+            ParseError::Unclosed => t!(lang, "command.unclosed", usage = usage),
+            ParseError::UnknownFlag(flag) => t!(lang, "command.unknown_flag", flag = flag, usage = usage),
+            ParseError::NoValue(flag) => t!(lang, "command.no_value", flag = flag, usage = usage),
+            ParseError::BadValue(flag, value) => t!(lang, "command.bad_value", flag = flag, value = value, usage = usage),
+            ParseError::Missing | ParseError::TooMany => usage,
+        }
+    }
+}
+
+/// A command's parsed arguments.
+#[derive(Debug, Default, PartialEq)]
+pub struct Args {
+    pub words: Vec<String>,
+    /// Flag names (without `--`) and their values, in the order given.
+    pub flags: Vec<(String, String)>,
+}
+
+impl Args {
+    /// Word `i`, or "" if there are fewer.
+    pub fn word(&self, i: usize) -> &str {
+        self.words.get(i).map_or("", String::as_str)
+    }
+
+    /// The value of `--name`, the last one if it was given twice.
+    pub fn flag(&self, name: &str) -> Option<&str> {
+        self.flags.iter().rev().find(|(n, _)| n == name).map(|(_, v)| v.as_str())
+    }
+}
+
+/// Split `s` into words, honoring quotes and backslashes. Each word comes
+/// with whether any of it was quoted.
+fn split(s: &str) -> Result<Vec<(String, bool)>, ParseError> {
+    let mut words = Vec::new();
+    let mut word: Option<(String, bool)> = None;
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' | '\'' => {
+                let (text, quoted) = word.get_or_insert_with(Default::default);
+                *quoted = true;
+                loop {
+                    match chars.next() {
+                        Some(q) if q == c => break,
+                        Some('\\') if c == '"' => text.push(chars.next().ok_or(ParseError::Unclosed)?),
+                        Some(ch) => text.push(ch),
+                        None => return Err(ParseError::Unclosed),
+                    }
+                }
+            }
+            '\\' => {
+                let (text, _) = word.get_or_insert_with(Default::default);
+                if let Some(ch) = chars.next() {
+                    text.push(ch);
+                }
+            }
+            c if c.is_whitespace() => words.extend(word.take()),
+            c => word.get_or_insert_with(Default::default).0.push(c),
+        }
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+/// Parse `args` (what follows the command name) for `spec`.
+pub fn parse_args(spec: &Spec, args: &str) -> Result<Args, ParseError> {
+    let mut parsed = Args::default();
+    let mut flags_done = false;
+    let mut words = split(args)?.into_iter();
+    while let Some((word, quoted)) = words.next() {
+        let flag = word.strip_prefix("--").filter(|_| !quoted && !flags_done);
+        match flag {
+            Some("") => flags_done = true,
+            Some(flag) => {
+                let (name, value) = match flag.split_once('=') {
+                    Some((name, value)) => (name, Some(value.to_string())),
+                    None => (flag, None),
+                };
+                if !spec.flags.contains(&name) {
+                    return Err(ParseError::UnknownFlag(format!("--{name}")));
+                }
+                let value = value.or_else(|| words.next().map(|(w, _)| w)).ok_or_else(|| ParseError::NoValue(format!("--{name}")))?;
+                parsed.flags.push((name.to_string(), value));
+            }
+            None => parsed.words.push(word),
+        }
+    }
+    if spec.rest && spec.max > 0 && parsed.words.len() > spec.max {
+        let tail = parsed.words.split_off(spec.max - 1).join(" ");
+        parsed.words.push(tail);
+    }
+    if parsed.words.len() < spec.min {
+        Err(ParseError::Missing)
+    } else if parsed.words.len() > spec.max {
+        Err(ParseError::TooMany)
+    } else {
+        Ok(parsed)
+    }
+}
+
+/// If `text` is the command `name`, its arguments parsed by its spec.
+/// `None` if `text` is some other command or no command at all.
+pub fn parse(text: &str, name: &str) -> Option<Result<Args, ParseError>> {
+    let spec = find(name)?;
+    let text = text.trim();
+    let args = text.strip_prefix(name)?;
+    if !args.is_empty() && !args.starts_with(char::is_whitespace) {
+        return None;
+    }
+    Some(parse_args(spec, args))
+}
+
+/// The notice for `text` if it's a command that can't run: a name that
+/// isn't in `COMMANDS`, or arguments its spec refuses. `None` for plain
+/// chat, for free-text commands, and for commands that parse.
+pub fn check(text: &str, lang: Lang) -> Option<String> {
+    let text = text.trim();
+    if !text.starts_with('/') {
+        return None;
+    }
+    let name = text.split(char::is_whitespace).next().unwrap_or(text);
+    let Some(spec) = find(name) else {
+        return Some(t!(lang, "command.unknown", name = name));
+    };
+    if spec.raw {
+        return None;
+    }
+    parse_args(spec, &text[name.len()..]).err().map(|e| e.notice(name, lang))
+}
+
+/// What Tab does to the input.
+#[derive(Debug, PartialEq)]
+pub enum Completion {
+    /// Not a command, or nothing starts like it.
+    None,
+    /// The input should become this.
+    Complete(String),
+    /// Several commands start like it: the input should become their
+    /// common prefix, and here they are.
+    Choices(String, Vec<&'static Spec>),
+    /// A whole command name already: here's how to use it.
+    Usage(&'static Spec),
+}
+
+/// Complete a command name at the start of `input`.
+pub fn complete(input: &str) -> Completion {
+    if !input.starts_with('/') {
+        return Completion::None;
+    }
+    if let Some((name, _)) = input.split_once(char::is_whitespace) {
+        return find(name).map_or(Completion::None, Completion::Usage);
+    }
+    let matches: Vec<&'static Spec> = COMMANDS.iter().filter(|c| c.name.starts_with(input)).collect();
+    match matches.as_slice() {
+        [] => Completion::None,
+        [only] if only.name == input => Completion::Usage(only),
+        [only] => Completion::Complete(format!("{} ", only.name)),
+        [first, ..] => {
+            let common = matches.iter().fold(first.name, |common, c| {
+                let len = common.chars().zip(c.name.chars()).take_while(|(a, b)| a == b).count();
+                &common[..len]
+            });
+            // An exact name that is also a prefix of others (`/verify`,
+            // `/verifyfile`) still gets its usage.
+            match find(input) {
+                Some(exact) if common == input => Completion::Usage(exact),
+                _ => Completion::Choices(common.to_string(), matches),
+            }
+        }
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(s: &str) -> Vec<String> {
+        split(s).unwrap().into_iter().map(|(w, _)| w).collect()
+    }
+
+    #[test]
+    fn quotes_group_words() {
+        assert_eq!(words(r#"a "b c" 'd "e"' f\ g"#), ["a", "b c", r#"d "e""#, "f g"]);
+        assert_eq!(words(r#""say \"hi\"" x"y"z"#), [r#"say "hi""#, "xyz"]);
+        assert_eq!(words("  "), Vec::<String>::new());
+        assert_eq!(split(r#"a "b"#), Err(ParseError::Unclosed));
+    }
+
+    #[test]
+    fn flags_and_arity_follow_the_spec() {
+        let export = find("/export").unwrap();
+        let args = parse_args(export, r#"--format html "book club.html""#).unwrap();
+        assert_eq!((args.flag("format"), args.word(0)), (Some("html"), "book club.html"));
+        assert_eq!(parse_args(export, "--format=json").unwrap().flag("format"), Some("json"));
+        // Quoted, or after `--`, it's a file name.
+        assert_eq!(parse_args(export, r#""--format""#).unwrap().word(0), "--format");
+        assert_eq!(parse_args(export, "-- --x").unwrap().word(0), "--x");
+        assert_eq!(parse_args(export, "--colour red"), Err(ParseError::UnknownFlag("--colour".into())));
+        assert_eq!(parse_args(export, "--format"), Err(ParseError::NoValue("--format".into())));
+        assert_eq!(parse_args(export, "a b"), Err(ParseError::TooMany));
+
+        assert_eq!(parse("/expire 2", "/expire"), Some(Err(ParseError::Missing)));
+        assert_eq!(parse("/dm Mary Jane", "/dm").unwrap().unwrap().words, ["Mary Jane"]);
+        assert_eq!(parse("/dmx", "/dm"), None);
+        assert_eq!(parse("/verifyfile 1", "/verify"), None);
+    }
+
+    #[test]
+    fn check_refuses_unknown_commands_and_bad_arguments() {
+        let lang = Lang::En;
+        assert_eq!(check("hello /map", lang), None);
+        assert_eq!(check("/map", lang), None);
+        assert_eq!(check(" /lock ", lang), None);
+        assert_eq!(check("/mpa", lang), Some("unknown command /mpa — see /help".into()));
+        assert_eq!(check("/map x", lang), Some("usage: /map".into()));
+        assert_eq!(check("/lock now", lang), Some("usage: /lock".into()));
+        assert_eq!(check("/expire 2", lang), Some("usage: /expire <n> <30m|2h|1d>".into()));
+        // Free text isn't split, so an apostrophe is fine.
+        assert_eq!(check("/spoiler it's Bob", lang), None);
+        assert_eq!(check("/afk", lang), None);
+    }
+
+    #[test]
+    fn tab_completes_names_and_shows_usage() {
+        assert_eq!(complete("/expo"), Completion::Complete("/export ".into()));
+        let Completion::Choices(common, choices) = complete("/m") else { panic!("expected choices") };
+        assert_eq!(common, "/m");
+        assert_eq!(choices.iter().map(|c| c.name).collect::<Vec<_>>(), ["/map", "/mute", "/mentions"]);
+        assert_eq!(complete("/verify"), Completion::Usage(find("/verify").unwrap()));
+        assert_eq!(complete("/export --f"), Completion::Usage(find("/export").unwrap()));
+        assert_eq!(complete("/zzz"), Completion::None);
+        assert_eq!(complete("hello"), Completion::None);
+        // Every usage starts with its name, so the two can't drift.
+        assert!(COMMANDS.iter().all(|c| c.usage.starts_with(c.name)));
+    }
+}
@@ -0,0 +1,244 @@
+//! Persistent user settings.
+//!
+//! Settings live in a small TOML file under the platform config directory
+//! (`~/.config/piper-chat/config.toml` on Linux). The file is optional: a
+//! missing or unreadable file just means defaults, so the app never fails to
+//! start over config.
+//!
+//! `#[serde(default)]` on the struct lets older files (missing newer fields)
+//! and newer files (fields this build doesn't know are ignored) both load.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::access::{OfferPolicy, ServePolicy};
+use crate::chat::PeerSort;
+use crate::error::{Result, ResultExt};
+use crate::filter::FilterConfig;
+use crate::hooks::Hooks;
+use crate::i18n::Lang;
+use crate::journal::SyncPolicy;
+use crate::joining::DEFAULT_JOIN_TIMEOUT_SECS;
+use crate::net::keepalive::DEFAULT_KEEPALIVE_SECS;
+use crate::node::Paths;
+use crate::profile;
+use crate::theme::ThemeMode;
+use crate::transfer::DEFAULT_RISKY_EXTENSIONS;
+
+/// Settings remembered across restarts.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Directory the last shared file came from; the file picker opens here.
+    pub last_share_dir: Option<PathBuf>,
+    /// Underline misspelled words in the input bar.
+    pub spellcheck: bool,
+    /// Word list to check against (one word per line). When unset, the
+    /// system list (`/usr/share/dict/words`) is used if present.
+    pub spellcheck_dictionary: Option<PathBuf>,
+    /// Show message times as "2m ago" instead of `HH:MM`.
+    pub relative_timestamps: bool,
+    /// Commands to run on chat events (the `[hooks]` table).
+    pub hooks: Hooks,
+    /// Built-in message filters to enable (the `[filters]` table).
+    pub filters: FilterConfig,
+    /// When the history journal is fsynced: "never", "batch" or "periodic".
+    pub journal_sync: SyncPolicy,
+    /// Language of the chat screen: "en" or "es".
+    pub lang: Lang,
+    /// Whose file offers we accept: "everyone", "verified" or "nobody".
+    pub offers_from: OfferPolicy,
+    /// Who may download what we share: "anyone" or "members".
+    pub serve_to: ServePolicy,
+    /// Write a `<file>.sha256` next to every completed download, and check
+    /// it before opening the download.
+    pub checksum_sidecar: bool,
+    /// File extensions (no dot) flagged as risky: marked in the files pane
+    /// and only downloaded or opened after a confirmation.
+    pub risky_extensions: Vec<String>,
+    /// Ring the terminal bell along with each transfer toast.
+    pub toast_bell: bool,
+    /// Show the time and how long this session has run on the input bar's
+    /// bottom border.
+    pub status_clock: bool,
+    /// Order of the peers pane: "joined", "name", "latency" or
+    /// "connection" (Ctrl+O cycles and saves it).
+    pub peer_sort: PeerSort,
+    /// Network paths to use: "any", "relay" (never reveal our IP to peers)
+    /// or "direct" (no relay servers). `--paths` overrides it per run.
+    pub paths: Paths,
+    /// Send relay traffic through this proxy: `socks5://host:port` (Tor's
+    /// is `socks5://127.0.0.1:9050`) or `http://host:port`. Forces relay-only
+    /// paths. `--proxy` overrides it per run.
+    pub proxy: Option<String>,
+    /// Seconds of silence before the room gets a keepalive, so NAT
+    /// mappings and relay connections don't expire. 0 turns it off.
+    pub keepalive_secs: u64,
+    /// Seconds after joining someone else's room with no peer connected
+    /// before the "nobody here yet" overlay offers to retry. 0 turns it off.
+    pub join_timeout_secs: u64,
+    /// Extra words that highlight a message like an @mention of us: a
+    /// real name, a project codename. Whole words, any case.
+    pub highlight_words: Vec<String>,
+    /// Encrypt files we share with a fresh key per offer (see `seal`), so
+    /// the blob is unreadable to anyone outside the room. Peers on older
+    /// versions don't see sealed offers.
+    pub seal_files: bool,
+    /// Argon2 hash (PHC string) of the passphrase that unlocks a locked
+    /// session (see `lock`). Set by the first `/lock`.
+    pub lock_passphrase: Option<String>,
+    /// Seconds without a key press before the session locks itself, once
+    /// a passphrase is set. 0 turns it off.
+    pub idle_lock_secs: u64,
+    /// Keep blobs in memory rather than on disk (see `blobstore`), for
+    /// sessions that leave nothing behind. `--mem-store` turns it on per run.
+    pub mem_store: bool,
+    /// Share files of at least this many MB by reference instead of copying
+    /// them into the blob store (see `blobstore`). 0 always copies.
+    pub reference_min_mb: u64,
+    /// The profile to run as when `--profile` isn't given (see `profile`).
+    /// Only read from the default profile's config file.
+    pub profile: Option<String>,
+    /// Palette: "dark" or "light". Ctrl+T toggles and saves it, on the
+    /// welcome screens as in the chat.
+    pub theme: ThemeMode,
+}
+
+/// Written out by hand (rather than derived) because `spellcheck` and
+/// `status_clock` default to on. `#[serde(default)]` fills missing fields from this impl.
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            last_share_dir: None,
+            spellcheck: true,
+            spellcheck_dictionary: None,
+            relative_timestamps: false,
+            hooks: Hooks::default(),
+            filters: FilterConfig::default(),
+            journal_sync: SyncPolicy::default(),
+            lang: Lang::default(),
+            offers_from: OfferPolicy::default(),
+            serve_to: ServePolicy::default(),
+            checksum_sidecar: false,
+            risky_extensions: DEFAULT_RISKY_EXTENSIONS.iter().map(|e| e.to_string()).collect(),
+            toast_bell: false,
+            status_clock: true,
+            peer_sort: PeerSort::default(),
+            paths: Paths::default(),
+            proxy: None,
+            keepalive_secs: DEFAULT_KEEPALIVE_SECS,
+            join_timeout_secs: DEFAULT_JOIN_TIMEOUT_SECS,
+            highlight_words: Vec::new(),
+            seal_files: false,
+            lock_passphrase: None,
+            idle_lock_secs: 0,
+            mem_store: false,
+            reference_min_mb: 0,
+            profile: None,
+            theme: ThemeMode::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Default location of the config file, if the platform has a config dir.
+    pub fn path() -> Option<PathBuf> {
+        profile::config_dir().map(|d| d.join("config.toml"))
+    }
+
+    /// Load from the default location, falling back to defaults.
+    pub fn load() -> Self {
+        Self::path().map(|p| Self::load_from(&p)).unwrap_or_default()
+    }
+
+    /// Load from `path`. A missing or malformed file yields the defaults.
+    pub fn load_from(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|s| toml::from_str(&s).ok())
+            .unwrap_or_default()
+    }
+
+    /// Save to the default location. A no-op if there is no config dir.
+    pub fn save(&self) -> Result<()> {
+        match Self::path() {
+            Some(path) => self.save_to(&path),
+            None => Ok(()),
+        }
+    }
+
+    /// Save to `path`, creating parent directories as needed.
+    pub fn save_to(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).storage()?;
+        }
+        std::fs::write(path, toml::to_string_pretty(self).storage()?).storage()?;
+        Ok(())
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip_through_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nested").join("config.toml");
+        let config = Config {
+            last_share_dir: Some("/home/alice/Pictures".into()),
+            spellcheck: false,
+            lang: Lang::Es,
+            theme: ThemeMode::Light,
+            ..Config::default()
+        };
+        config.save_to(&path).unwrap();
+        assert_eq!(Config::load_from(&path), config);
+    }
+
+    #[test]
+    fn missing_or_malformed_file_is_default() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        assert_eq!(Config::load_from(&path), Config::default());
+        std::fs::write(&path, "this is = = not toml").unwrap();
+        assert_eq!(Config::load_from(&path), Config::default());
+    }
+
+    #[test]
+    fn unknown_fields_are_ignored() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(&path, "last_share_dir = \"/tmp\"\nfuture_setting = 3\n").unwrap();
+        let config = Config::load_from(&path);
+        assert_eq!(config.last_share_dir, Some("/tmp".into()));
+        // Fields missing from the file take their defaults.
+        assert!(config.spellcheck);
+    }
+
+    #[test]
+    fn hooks_table() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(&path, "[hooks]\non_peer_join = \"printf '\\\\a'\"\n").unwrap();
+        let config = Config::load_from(&path);
+        assert_eq!(config.hooks.on_peer_join.as_deref(), Some("printf '\\a'"));
+        assert_eq!(config.hooks.on_message, None);
+    }
+
+    #[test]
+    fn transfer_permissions() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(&path, "offers_from = \"verified\"\nserve_to = \"members\"\n").unwrap();
+        let config = Config::load_from(&path);
+        assert_eq!(config.offers_from, OfferPolicy::Verified);
+        assert_eq!(config.serve_to, ServePolicy::Members);
+        assert!(config.risky_extensions.iter().any(|e| e == "exe"));
+
+        std::fs::write(&path, "risky_extensions = [\"iso\"]\n").unwrap();
+        assert_eq!(Config::load_from(&path).risky_extensions, ["iso"]);
+    }
+}
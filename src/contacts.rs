@@ -0,0 +1,173 @@
+//! Persistent address book of peers met in rooms.
+//!
+//! Every peer that announces itself with a `Join` message is recorded here:
+//! their latest nickname, when we last saw them, and which rooms we shared.
+//! Contacts are keyed by `EndpointId`, which only means something across
+//! sessions because our own identity is persisted too (see
+//! `node::load_secret_key`) — and theirs, if they run a build that does.
+//!
+//! "Verified" is a flag the user sets with `/verify <name>` after checking
+//! a peer's ID out of band; nothing sets it automatically. The book is a
+//! TOML file in the data directory (`~/.local/share/piper-chat/contacts.toml`
+//! on Linux), loaded and saved like `drafts.rs`.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use iroh::EndpointId;
+use iroh_gossip::proto::TopicId;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Result, ResultExt};
+use crate::profile;
+
+/// What we know about one peer.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Contact {
+    /// The nickname they last used.
+    pub nickname: String,
+    /// Set by the user after checking the peer's ID out of band.
+    pub verified: bool,
+    /// When they last joined a room we were in (unix ms).
+    pub last_seen_ms: u64,
+    /// Topic IDs (hex) of rooms we've shared.
+    pub rooms: BTreeSet<String>,
+}
+
+/// The address book.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Contacts {
+    /// Contacts keyed by endpoint ID in its string form (TOML keys must be
+    /// strings).
+    contacts: BTreeMap<String, Contact>,
+}
+
+impl Contacts {
+    /// Default location of the address book, if the platform has a data dir.
+    pub fn path() -> Option<PathBuf> {
+        profile::data_dir().map(|d| d.join("contacts.toml"))
+    }
+
+    /// Load from the default location, falling back to an empty book.
+    pub fn load() -> Self {
+        Self::path().map(|p| Self::load_from(&p)).unwrap_or_default()
+    }
+
+    /// Load from `path`. A missing or malformed file yields an empty book.
+    pub fn load_from(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|s| toml::from_str(&s).ok())
+            .unwrap_or_default()
+    }
+
+    /// Save to the default location. A no-op if there is no data dir.
+    pub fn save(&self) -> Result<()> {
+        match Self::path() {
+            Some(path) => self.save_to(&path),
+            None => Ok(()),
+        }
+    }
+
+    /// Save to `path`, creating parent directories as needed.
+    pub fn save_to(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).storage()?;
+        }
+        std::fs::write(path, toml::to_string_pretty(self).storage()?).storage()?;
+        Ok(())
+    }
+
+    /// Record that `id` joined `topic` as `nickname` at `now_ms`.
+    pub fn saw(&mut self, id: EndpointId, nickname: &str, topic: &TopicId, now_ms: u64) {
+        let contact = self.contacts.entry(id.to_string()).or_default();
+        contact.nickname = nickname.to_string();
+        contact.last_seen_ms = contact.last_seen_ms.max(now_ms);
+        contact.rooms.insert(topic.to_string());
+    }
+
+    /// The contact for `id`, if known.
+    pub fn get(&self, id: &EndpointId) -> Option<&Contact> {
+        self.contacts.get(&id.to_string())
+    }
+
+    /// Find a contact by nickname (case-insensitive). When several share
+    /// the name, the most recently seen wins.
+    pub fn find(&self, nickname: &str) -> Option<(EndpointId, &Contact)> {
+        self.by_recency()
+            .into_iter()
+            .find(|(_, c)| c.nickname.eq_ignore_ascii_case(nickname))
+    }
+
+    /// Mark the contact named `nickname` as verified. Returns its ID, or
+    /// `None` if there's no such contact.
+    pub fn verify(&mut self, nickname: &str) -> Option<EndpointId> {
+        let (id, _) = self.find(nickname)?;
+        if let Some(contact) = self.contacts.get_mut(&id.to_string()) {
+            contact.verified = true;
+        }
+        Some(id)
+    }
+
+    /// All contacts, most recently seen first.
+    pub fn by_recency(&self) -> Vec<(EndpointId, &Contact)> {
+        let mut all: Vec<_> = self
+            .contacts
+            .iter()
+            .filter_map(|(id, c)| EndpointId::from_str(id).ok().map(|id| (id, c)))
+            .collect();
+        all.sort_by_key(|(_, c)| std::cmp::Reverse(c.last_seen_ms));
+        all
+    }
+
+    /// Whether the book is empty.
+    pub fn is_empty(&self) -> bool {
+        self.contacts.is_empty()
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn id(byte: u8) -> EndpointId {
+        EndpointId::from_bytes(&[byte; 32]).unwrap()
+    }
+
+    #[test]
+    fn saw_updates_and_persists() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("contacts.toml");
+        let room_a = TopicId::from_bytes([1; 32]);
+        let room_b = TopicId::from_bytes([2; 32]);
+
+        let mut book = Contacts::default();
+        book.saw(id(1), "bob", &room_a, 100);
+        book.saw(id(1), "Bobby", &room_b, 200);
+        book.saw(id(3), "carol", &room_a, 150);
+        book.save_to(&path).unwrap();
+
+        let loaded = Contacts::load_from(&path);
+        assert_eq!(loaded, book);
+        let bob = loaded.get(&id(1)).unwrap();
+        assert_eq!(bob.nickname, "Bobby");
+        assert_eq!(bob.last_seen_ms, 200);
+        assert_eq!(bob.rooms.len(), 2);
+        let order: Vec<_> = loaded.by_recency().into_iter().map(|(i, _)| i).collect();
+        assert_eq!(order, [id(1), id(3)]);
+    }
+
+    #[test]
+    fn verify_by_nickname() {
+        let mut book = Contacts::default();
+        book.saw(id(1), "Bob", &TopicId::from_bytes([1; 32]), 100);
+        assert_eq!(book.verify("bob"), Some(id(1)));
+        assert!(book.get(&id(1)).unwrap().verified);
+        assert_eq!(book.verify("nobody"), None);
+    }
+}
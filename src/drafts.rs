@@ -0,0 +1,109 @@
+//! Unsent input saved per room across restarts.
+//!
+//! When the app quits with text still in the input bar, that text is stored
+//! against the room's `TopicId` and put back the next time the same room is
+//! joined. Drafts live next to the blob store under the platform data
+//! directory (`~/.local/share/piper-chat/drafts.toml` on Linux), following
+//! the same load-or-default pattern as `config.rs`.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use iroh_gossip::proto::TopicId;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Result, ResultExt};
+use crate::profile;
+
+/// Saved drafts keyed by room.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Drafts {
+    /// Draft text keyed by the room's topic ID as hex. (TOML table keys must
+    /// be strings, so the `TopicId` itself can't be the key.)
+    rooms: BTreeMap<String, String>,
+}
+
+impl Drafts {
+    /// Default location of the drafts file, if the platform has a data dir.
+    pub fn path() -> Option<PathBuf> {
+        profile::data_dir().map(|d| d.join("drafts.toml"))
+    }
+
+    /// Load from the default location, falling back to no drafts.
+    pub fn load() -> Self {
+        Self::path().map(|p| Self::load_from(&p)).unwrap_or_default()
+    }
+
+    /// Load from `path`. A missing or malformed file yields no drafts.
+    pub fn load_from(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|s| toml::from_str(&s).ok())
+            .unwrap_or_default()
+    }
+
+    /// Save to the default location. A no-op if there is no data dir.
+    pub fn save(&self) -> Result<()> {
+        match Self::path() {
+            Some(path) => self.save_to(&path),
+            None => Ok(()),
+        }
+    }
+
+    /// Save to `path`, creating parent directories as needed.
+    pub fn save_to(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).storage()?;
+        }
+        std::fs::write(path, toml::to_string_pretty(self).storage()?).storage()?;
+        Ok(())
+    }
+
+    /// The draft for `topic`, if one was saved.
+    pub fn get(&self, topic: &TopicId) -> Option<&str> {
+        self.rooms.get(&topic.to_string()).map(String::as_str)
+    }
+
+    /// Store `text` as the draft for `topic`. Blank text removes the draft.
+    pub fn set(&mut self, topic: &TopicId, text: &str) {
+        if text.trim().is_empty() {
+            self.rooms.remove(&topic.to_string());
+        } else {
+            self.rooms.insert(topic.to_string(), text.to_string());
+        }
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drafts_are_per_room_and_persist() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("drafts.toml");
+        let a = TopicId::from_bytes([1; 32]);
+        let b = TopicId::from_bytes([2; 32]);
+
+        let mut drafts = Drafts::default();
+        drafts.set(&a, "half a thought\nand a second line");
+        drafts.set(&b, "other room");
+        drafts.save_to(&path).unwrap();
+
+        let loaded = Drafts::load_from(&path);
+        assert_eq!(loaded.get(&a), Some("half a thought\nand a second line"));
+        assert_eq!(loaded.get(&b), Some("other room"));
+    }
+
+    #[test]
+    fn blank_draft_clears() {
+        let topic = TopicId::from_bytes([1; 32]);
+        let mut drafts = Drafts::default();
+        drafts.set(&topic, "hello");
+        drafts.set(&topic, "   ");
+        assert_eq!(drafts.get(&topic), None);
+    }
+}
@@ -0,0 +1,122 @@
+//! The library's error type.
+//!
+//! Core operations — networking, tickets, files on disk, transfers, the
+//! terminal — return `error::Result`, whose `Error` says which of those
+//! failed. That lets the TUI decide what to do: a network hiccup is worth
+//! reporting and retrying, while a storage failure means something is wrong
+//! with the machine and quitting with a message is the honest response.
+//!
+//! The binary and the scripting subcommands still use `anyhow`; `Error`
+//! implements `std::error::Error`, so `?` converts it there automatically.
+//!
+//! Inside the library, `ResultExt` tags any error with its category in the
+//! style of anyhow's `.context()`:
+//!
+//! ```ignore
+//! let topic = gossip.subscribe(id, peers).await.network()?;
+//! ```
+
+/// Any underlying error, boxed. `anyhow::Error` converts into this too.
+pub type BoxError = Box<dyn std::error::Error + Send + Sync + 'static>;
+
+/// `Result` with the library's `Error` as the default error type.
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// What kind of operation failed, wrapping the underlying cause.
+///
+/// Display shows the cause's whole `source()` chain, so an `anyhow` cause
+/// keeps its context ("transfer failed: connect: timed out").
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// Binding the endpoint, joining a topic or sending to peers.
+    #[error("network error: {}", Chain(.0))]
+    Network(BoxError),
+    /// A ticket that doesn't parse.
+    #[error("invalid ticket: {}", Chain(.0))]
+    Ticket(BoxError),
+    /// Reading or writing local files: config, archives, keys, blob store.
+    #[error("storage error: {}", Chain(.0))]
+    Storage(BoxError),
+    /// Fetching a blob from a peer.
+    #[error("transfer failed: {}", Chain(.0))]
+    Transfer(BoxError),
+    /// Driving the terminal (raw mode, drawing, reading input).
+    #[error("terminal error: {}", Chain(.0))]
+    Terminal(BoxError),
+}
+
+/// Displays an error followed by each of its sources, separated by ": ".
+struct Chain<'a>(&'a BoxError);
+
+impl std::fmt::Display for Chain<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)?;
+        let mut source = self.0.source();
+        while let Some(cause) = source {
+            write!(f, ": {cause}")?;
+            source = cause.source();
+        }
+        Ok(())
+    }
+}
+
+impl Error {
+    /// Whether trying again later might succeed. Network and transfer
+    /// failures are often transient; the rest won't fix themselves.
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, Error::Network(_) | Error::Transfer(_))
+    }
+}
+
+/// Tag an error with its `Error` category.
+pub trait ResultExt<T> {
+    fn network(self) -> Result<T>;
+    fn ticket(self) -> Result<T>;
+    fn storage(self) -> Result<T>;
+    fn transfer(self) -> Result<T>;
+    fn terminal(self) -> Result<T>;
+}
+
+impl<T, E: Into<BoxError>> ResultExt<T> for std::result::Result<T, E> {
+    fn network(self) -> Result<T> {
+        self.map_err(|e| Error::Network(e.into()))
+    }
+
+    fn ticket(self) -> Result<T> {
+        self.map_err(|e| Error::Ticket(e.into()))
+    }
+
+    fn storage(self) -> Result<T> {
+        self.map_err(|e| Error::Storage(e.into()))
+    }
+
+    fn transfer(self) -> Result<T> {
+        self.map_err(|e| Error::Transfer(e.into()))
+    }
+
+    fn terminal(self) -> Result<T> {
+        self.map_err(|e| Error::Terminal(e.into()))
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn categories_and_messages() {
+        let err = Err::<(), _>(anyhow::anyhow!("timed out"))
+            .context("connect")
+            .transfer()
+            .unwrap_err();
+        assert!(err.is_retryable());
+        assert_eq!(err.to_string(), "transfer failed: connect: timed out");
+
+        let err = std::fs::read("/nonexistent/piper-chat").storage().unwrap_err();
+        assert!(!err.is_retryable());
+        assert!(err.to_string().starts_with("storage error: "));
+    }
+}
@@ -0,0 +1,268 @@
+//! Structured event log: `--json-log <path>` writes what happens in the
+//! chat as JSON lines, for auditing and analysis after the fact.
+//!
+//! Unlike `/export`, which renders a transcript for people, this is a flat
+//! record of events as they happen, one JSON object per line:
+//!
+//! ```text
+//! {"ts_ms":1718000000000,"event":"message","kind":"chat","message_id":"…","nickname":"Bob","text":"hi"}
+//! {"ts_ms":1718000004000,"event":"path","endpoint_id":"…","nickname":"Bob","path":"direct","addr":"192.0.2.7:4433"}
+//! {"ts_ms":1718000009000,"event":"transfer","hash":"…","offer_id":"…","filename":"a.png","step":"reached","detail":40}
+//! ```
+//!
+//! - `message` and `offer`/`retract` — every entry added to the live history,
+//!   ours and peers' alike (history synced from peers isn't repeated)
+//! - `join` / `leave` — a peer announcing itself, a neighbor going away
+//! - `transfer` — each step in a file pane entry's timeline
+//!   (`transfer::Milestone`), stamped with when it happened
+//! - `path` — a peer's connection switching between relay and direct
+//!
+//! The file is appended to, never truncated. Lines are written by a
+//! blocking thread, so the event loop only pushes onto a channel.
+
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use iroh::EndpointId;
+use serde::Serialize;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use crate::error::{Result, ResultExt};
+use crate::net::{HistoryEntry, HistoryEntryKind, MessageId, now_ms};
+use crate::transfer::{EntryId, Milestone, TransferManager};
+
+/// One event, serialized with its name in `"event"`.
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum LogEvent<'a> {
+    Message {
+        /// `chat`, `thread`, `reply` or `spoiler`.
+        kind: &'static str,
+        message_id: String,
+        nickname: &'a str,
+        text: &'a str,
+        /// The message a `reply` quotes.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        reply_to: Option<String>,
+        /// The root of a `thread` message's thread.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        thread_id: Option<String>,
+    },
+    Offer {
+        message_id: String,
+        nickname: &'a str,
+        #[serde(serialize_with = "display")]
+        endpoint_id: EndpointId,
+        filename: &'a str,
+        size: u64,
+        hash: String,
+    },
+    /// An author withdrew their offer `offer_id` of `hash`.
+    Retract {
+        hash: String,
+        #[serde(serialize_with = "display")]
+        endpoint_id: EndpointId,
+        offer_id: String,
+    },
+    Join {
+        #[serde(serialize_with = "display")]
+        endpoint_id: EndpointId,
+        nickname: &'a str,
+    },
+    Leave {
+        #[serde(serialize_with = "display")]
+        endpoint_id: EndpointId,
+        nickname: &'a str,
+    },
+    Transfer {
+        hash: String,
+        /// The offer's message ID: the same file offered twice is two entries.
+        offer_id: String,
+        filename: &'a str,
+        #[serde(flatten)]
+        step: &'a Milestone,
+    },
+    Path {
+        #[serde(serialize_with = "display")]
+        endpoint_id: EndpointId,
+        nickname: &'a str,
+        /// `direct` or `relay`.
+        path: &'static str,
+        addr: Option<String>,
+    },
+}
+
+/// Serialize a value through its `Display` impl (endpoint IDs as hex).
+fn display<S: serde::Serializer>(value: &EndpointId, s: S) -> std::result::Result<S::Ok, S::Error> {
+    s.collect_str(value)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// A line of the log: the event stamped with when it happened.
+#[derive(Serialize)]
+struct Line<'a> {
+    ts_ms: u64,
+    #[serde(flatten)]
+    event: &'a LogEvent<'a>,
+}
+
+/// The event for a new history entry, if it's one the log records.
+pub fn history_event(entry: &HistoryEntry) -> Option<LogEvent<'_>> {
+    let message_id: &MessageId = &entry.message_id;
+    let message = |kind, nickname, text, reply_to: Option<&MessageId>, thread_id: Option<&MessageId>| LogEvent::Message {
+        kind,
+        message_id: hex(message_id),
+        nickname,
+        text,
+        reply_to: reply_to.map(|id| hex(id)),
+        thread_id: thread_id.map(|id| hex(id)),
+    };
+    Some(match &entry.kind {
+        HistoryEntryKind::Chat { nickname, text } => message("chat", nickname, text, None, None),
+        HistoryEntryKind::ThreadChat { nickname, text, thread_id } => message("thread", nickname, text, None, Some(thread_id)),
+        HistoryEntryKind::Reply { nickname, text, reply_to } => message("reply", nickname, text, Some(reply_to), None),
+        HistoryEntryKind::Spoiler { nickname, text } => message("spoiler", nickname, text, None, None),
+        HistoryEntryKind::FileOffer { nickname, endpoint_id, filename, size, hash, .. } => LogEvent::Offer {
+            message_id: hex(message_id),
+            nickname,
+            endpoint_id: *endpoint_id,
+            filename,
+            size: *size,
+            hash: hex(hash),
+        },
+        HistoryEntryKind::FileRevoke { hash, endpoint_id, offer_id } => LogEvent::Retract {
+            hash: hex(hash),
+            endpoint_id: *endpoint_id,
+            offer_id: hex(offer_id),
+        },
+        HistoryEntryKind::System(_) | HistoryEntryKind::FileKey { .. } | HistoryEntryKind::FileRetract { .. } => {
+            return None;
+        }
+    })
+}
+
+/// Handle to the log's writer thread.
+pub struct EventLog {
+    tx: mpsc::UnboundedSender<String>,
+    task: JoinHandle<std::io::Result<()>>,
+    /// Timeline steps already logged per transfer.
+    logged: HashMap<EntryId, usize>,
+}
+
+impl EventLog {
+    /// Open `path` for appending (so a bad path fails at startup) and start
+    /// the writer. Must be called from within the tokio runtime.
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path).storage()?;
+        let (tx, rx) = mpsc::unbounded_channel();
+        let task = tokio::task::spawn_blocking(move || write_loop(file, rx));
+        Ok(Self { tx, task, logged: HashMap::new() })
+    }
+
+    /// Log `event` as happening now.
+    pub fn log(&self, event: &LogEvent) {
+        Self::send(&self.tx, now_ms(), event);
+    }
+
+    fn send(tx: &mpsc::UnboundedSender<String>, ts_ms: u64, event: &LogEvent) {
+        if let Ok(line) = serde_json::to_string(&Line { ts_ms, event }) {
+            // Fails only if the writer died on an I/O error; `close` says so.
+            let _ = tx.send(line);
+        }
+    }
+
+    /// Log the timeline steps of `transfers` not logged yet.
+    pub fn transfers(&mut self, transfers: &TransferManager) {
+        for entry in &transfers.entries {
+            let logged = self.logged.entry(entry.offer.entry_id()).or_default();
+            // A shorter timeline is a new entry for the same file.
+            if *logged > entry.timeline.len() {
+                *logged = 0;
+            }
+            for (at_ms, step) in &entry.timeline[*logged..] {
+                let event = LogEvent::Transfer {
+                    hash: entry.offer.hash.to_hex(),
+                    offer_id: hex(&entry.offer.offer_id),
+                    filename: &entry.offer.filename,
+                    step,
+                };
+                Self::send(&self.tx, *at_ms, &event);
+            }
+            *logged = entry.timeline.len();
+        }
+    }
+
+    /// Write out what's queued and stop the writer.
+    pub async fn close(self) -> Result<()> {
+        drop(self.tx);
+        self.task.await.storage()?.storage()
+    }
+}
+
+/// The writer thread: append each line, flushing whenever the queue empties.
+fn write_loop(file: File, mut rx: mpsc::UnboundedReceiver<String>) -> std::io::Result<()> {
+    let mut out = BufWriter::new(file);
+    while let Some(line) = rx.blocking_recv() {
+        writeln!(out, "{line}")?;
+        if rx.is_empty() {
+            out.flush()?;
+        }
+    }
+    out.flush()
+}
+
+// ── Tests ────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transfer::FileOffer;
+
+    #[tokio::test]
+    async fn events_are_written_as_json_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("events.jsonl");
+        let mut log = EventLog::open(&path).unwrap();
+        let bob = EndpointId::from_bytes(&[1u8; 32]).unwrap();
+
+        let chat = HistoryEntry { message_id: [7; 16], timestamp_ms: 5, kind: HistoryEntryKind::Chat { nickname: "Bob".into(), text: "hi".into() } };
+        log.log(&history_event(&chat).unwrap());
+        let system = HistoryEntry { message_id: [8; 16], timestamp_ms: 6, kind: HistoryEntryKind::System("x".into()) };
+        assert!(history_event(&system).is_none());
+        log.log(&LogEvent::Join { endpoint_id: bob, nickname: "Bob" });
+
+        let mut transfers = TransferManager::new();
+        let offer = FileOffer {
+            sender_nickname: "Bob".into(),
+            sender_id: bob,
+            filename: "a.png".into(),
+            size: 100,
+            hash: iroh_blobs::Hash::from_bytes([2; 32]),
+            key: None,
+            offer_id: [9; 16],
+        };
+        let id = offer.entry_id();
+        transfers.add_offer(offer);
+        log.transfers(&transfers);
+        transfers.start_download(&id);
+        log.transfers(&transfers);
+        log.transfers(&transfers);
+        log.close().await.unwrap();
+
+        let lines: Vec<serde_json::Value> =
+            std::fs::read_to_string(&path).unwrap().lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+        let events: Vec<&str> = lines.iter().map(|l| l["event"].as_str().unwrap()).collect();
+        assert_eq!(events, ["message", "join", "transfer", "transfer"]);
+        assert_eq!(lines[0]["text"], "hi");
+        assert_eq!(lines[1]["endpoint_id"], bob.to_string());
+        assert_eq!((&lines[2]["step"], &lines[3]["step"]), (&"offered".into(), &"started".into()));
+        assert_eq!(lines[2]["offer_id"], "09".repeat(16));
+        assert!(lines.iter().all(|l| l["ts_ms"].as_u64().is_some()));
+    }
+}
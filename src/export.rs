@@ -0,0 +1,409 @@
+//! Transcript export: `/export [--format text|html|json] [file]`.
+//!
+//! Renders the room's history log (`App::history` — the same entries that
+//! are synced to peers and archived on quit) as a standalone file for
+//! keeping or sharing outside the room:
+//!
+//! - **text** — one line per entry, like `piper-chat tail` prints them.
+//! - **html** — a single page with its styles inline, colored with the
+//!   active theme, so it opens anywhere without the app.
+//! - **json** — the entries themselves, losslessly, for `piper-chat import`
+//!   to load into another room's local history (a migrated or re-keyed
+//!   room keeps its past conversation).
+//!
+//! File offers are recorded with their BLAKE3 hash, so a transcript can be
+//! matched against the files themselves later. Spoilers stay hidden: folded
+//! behind a `<details>` toggle in HTML, replaced by `[spoiler]` in text.
+
+use std::fmt::Write;
+use std::path::PathBuf;
+
+use iroh::EndpointId;
+use ratatui::style::Color;
+use serde::{Deserialize, Serialize};
+
+use crate::chat::{civil_date, format_timestamp};
+use crate::command::{self, ParseError};
+use crate::error::{Error, Result, ResultExt};
+use crate::net::{HistoryEntry, HistoryEntryKind, MessageId};
+use crate::theme::Theme;
+use crate::transfer::format_file_size;
+
+/// Output format of an export.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Text,
+    Html,
+    Json,
+}
+
+impl Format {
+    /// Parse a `--format` value.
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "text" | "txt" => Some(Format::Text),
+            "html" => Some(Format::Html),
+            "json" => Some(Format::Json),
+            _ => None,
+        }
+    }
+
+    /// File extension for the default file name.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Text => "txt",
+            Format::Html => "html",
+            Format::Json => "json",
+        }
+    }
+}
+
+/// A parsed `/export` command.
+#[derive(Debug, PartialEq)]
+pub struct ExportArgs {
+    pub format: Format,
+    /// Where to write; `None` picks a dated name in the download directory.
+    pub path: Option<PathBuf>,
+}
+
+/// Parse the arguments after `/export` (see `command` for quoting), or say
+/// what's wrong with them.
+pub fn parse_args(args: &str) -> std::result::Result<ExportArgs, ParseError> {
+    let spec = command::find("/export").expect("/export is in the command table");
+    let args = command::parse_args(spec, args)?;
+    let format = match args.flag("format") {
+        None => Format::Text,
+        Some(value) => Format::parse(value).ok_or_else(|| ParseError::BadValue("--format".into(), value.into()))?,
+    };
+    let path = Some(args.word(0)).filter(|p| !p.is_empty()).map(PathBuf::from);
+    Ok(ExportArgs { format, path })
+}
+
+/// Default file name for an export made at `now_ms`, e.g.
+/// `transcript-2025-03-04-1530.html`.
+pub fn default_file_name(format: Format, now_ms: u64) -> String {
+    let (year, month, day) = civil_date(now_ms);
+    let time = format_timestamp(now_ms).replace(':', "");
+    format!("transcript-{year}-{month:02}-{day:02}-{time}.{}", format.extension())
+}
+
+/// Render `history` in `format`. `theme` colors the HTML page; `now_ms`
+/// goes in its header.
+pub fn render(history: &[HistoryEntry], format: Format, theme: &Theme, now_ms: u64) -> String {
+    match format {
+        Format::Text => render_text(history),
+        Format::Html => render_html(history, theme, now_ms),
+        Format::Json => render_json(history),
+    }
+}
+
+/// Version of the JSON transcript layout, bumped on incompatible changes.
+pub const TRANSCRIPT_VERSION: u32 = 1;
+
+/// The JSON transcript: a version and the history entries as stored.
+#[derive(Serialize, Deserialize)]
+struct Transcript {
+    version: u32,
+    entries: Vec<HistoryEntry>,
+}
+
+fn render_json(history: &[HistoryEntry]) -> String {
+    let transcript = Transcript { version: TRANSCRIPT_VERSION, entries: history.to_vec() };
+    // Serializing plain data to a string can't fail.
+    serde_json::to_string_pretty(&transcript).expect("transcript serializes") + "\n"
+}
+
+/// Read a JSON transcript written by `/export --format json`.
+///
+/// Text and HTML exports are for people and can't be read back; anything
+/// that isn't a JSON transcript of a version we know is a storage error.
+pub fn parse_transcript(json: &str) -> Result<Vec<HistoryEntry>> {
+    let transcript: Transcript = serde_json::from_str(json)
+        .map_err(|e| anyhow::anyhow!("not a JSON transcript (export with --format json): {e}"))
+        .storage()?;
+    if transcript.version != TRANSCRIPT_VERSION {
+        let msg = format!("unsupported transcript version {}", transcript.version);
+        return Err(Error::Storage(msg.into()));
+    }
+    Ok(transcript.entries)
+}
+
+/// `YYYY-MM-DD`, UTC.
+fn date(ts_ms: u64) -> String {
+    let (year, month, day) = civil_date(ts_ms);
+    format!("{year}-{month:02}-{day:02}")
+}
+
+/// Name of the file a retract withdraws, from the offer earlier in
+/// `history`: the one named by `offer` (its author and `offer_id`), or for
+/// an old hash-only retract, any offer of `hash`.
+fn retracted_name(history: &[HistoryEntry], hash: &[u8; 32], offer: Option<(&EndpointId, &MessageId)>) -> Option<String> {
+    history.iter().find_map(|entry| match &entry.kind {
+        HistoryEntryKind::FileOffer { hash: offered, endpoint_id, filename, .. }
+            if offered == hash && offer.is_none_or(|(by, id)| endpoint_id == by && entry.message_id == *id) =>
+        {
+            Some(filename.clone())
+        }
+        _ => None,
+    })
+}
+
+/// Who wrote the message a reply quotes, if it's earlier in `history`.
+fn quoted_name(history: &[HistoryEntry], id: &MessageId) -> Option<String> {
+    history.iter().find_map(|entry| match &entry.kind {
+        HistoryEntryKind::Chat { nickname, .. }
+        | HistoryEntryKind::ThreadChat { nickname, .. }
+        | HistoryEntryKind::Reply { nickname, .. }
+        | HistoryEntryKind::Spoiler { nickname, .. }
+            if entry.message_id == *id =>
+        {
+            Some(nickname.clone())
+        }
+        _ => None,
+    })
+}
+
+/// Hex form of a BLAKE3 hash, as iroh prints it.
+fn hex(hash: &[u8; 32]) -> String {
+    iroh_blobs::Hash::from_bytes(*hash).to_string()
+}
+
+fn render_text(history: &[HistoryEntry]) -> String {
+    let mut out = String::new();
+    for entry in history {
+        let ts = format!("{} {}", date(entry.timestamp_ms), format_timestamp(entry.timestamp_ms));
+        let _ = match &entry.kind {
+            HistoryEntryKind::Chat { nickname, text } => {
+                // Continuation lines are indented under the first, as in the chat.
+                writeln!(out, "{ts} {nickname}: {}", text.replace('\n', "\n                   "))
+            }
+            // Thread replies are marked, since they're out of the main flow.
+            HistoryEntryKind::ThreadChat { nickname, text, .. } => {
+                writeln!(out, "{ts} ↳ {nickname}: {}", text.replace('\n', "\n                     "))
+            }
+            HistoryEntryKind::Reply { nickname, text, reply_to } => {
+                let to = quoted_name(history, reply_to).unwrap_or_else(|| "?".into());
+                writeln!(out, "{ts} {nickname} (re {to}): {}", text.replace('\n', "\n                   "))
+            }
+            HistoryEntryKind::Spoiler { nickname, .. } => writeln!(out, "{ts} {nickname}: [spoiler]"),
+            HistoryEntryKind::System(text) => writeln!(out, "{ts} * {text}"),
+            HistoryEntryKind::FileOffer { nickname, filename, size, hash, .. } => writeln!(
+                out,
+                "{ts} {nickname} shared {filename} ({}, blake3 {})",
+                format_file_size(*size),
+                hex(hash)
+            ),
+            HistoryEntryKind::FileRetract { hash } => {
+                let name = retracted_name(history, hash, None).unwrap_or_else(|| hex(hash));
+                writeln!(out, "{ts} * {name} is no longer shared")
+            }
+            HistoryEntryKind::FileRevoke { hash, endpoint_id, offer_id } => {
+                let name = retracted_name(history, hash, Some((endpoint_id, offer_id))).unwrap_or_else(|| hex(hash));
+                writeln!(out, "{ts} * {name} is no longer shared")
+            }
+            HistoryEntryKind::FileKey { .. } => Ok(()),
+        };
+    }
+    out
+}
+
+/// A theme color as CSS. Only `Rgb` colors are used by the palettes.
+fn css(color: Color) -> String {
+    match color {
+        Color::Rgb(r, g, b) => format!("#{r:02x}{g:02x}{b:02x}"),
+        _ => "inherit".to_string(),
+    }
+}
+
+/// Escape text for HTML element content and attribute values.
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn render_html(history: &[HistoryEntry], theme: &Theme, now_ms: u64) -> String {
+    let mut out = String::new();
+    let _ = write!(
+        out,
+        "<!DOCTYPE html>
+<html>
+<head>
+<meta charset=\"utf-8\">
+<title>piper-chat transcript</title>
+<style>
+body {{ background: {bg}; color: {text}; font-family: ui-monospace, monospace; max-width: 60rem; margin: 2rem auto; padding: 0 1rem; }}
+h1 {{ color: {title}; font-size: 1.3rem; }}
+.meta, .day {{ color: {muted}; }}
+.day {{ text-align: center; margin: 1rem 0; }}
+.msg {{ margin: 0.2rem 0; white-space: pre-wrap; }}
+.ts {{ color: {timestamp}; }}
+.nick {{ color: {nickname}; font-weight: bold; }}
+.system {{ color: {dim}; font-style: italic; }}
+.file {{ color: {file}; }}
+.hash {{ color: {muted}; font-size: 0.85em; }}
+summary {{ color: {muted}; cursor: pointer; }}
+</style>
+</head>
+<body>
+<h1>piper-chat transcript</h1>
+<p class=\"meta\">Exported {date} {time} UTC &middot; {count} entries</p>
+",
+        bg = css(theme.bg),
+        text = css(theme.text),
+        title = css(theme.title),
+        muted = css(theme.text_muted),
+        timestamp = css(theme.timestamp),
+        nickname = css(theme.nickname),
+        dim = css(theme.text_dim),
+        file = css(theme.transfer_sharing),
+        date = date(now_ms),
+        time = format_timestamp(now_ms),
+        count = history.len(),
+    );
+
+    let mut last_day = None;
+    for entry in history {
+        let day = date(entry.timestamp_ms);
+        if last_day.as_ref() != Some(&day) {
+            let _ = writeln!(out, "<div class=\"day\">{day}</div>");
+            last_day = Some(day);
+        }
+        let ts = format!("<span class=\"ts\">{}</span>", format_timestamp(entry.timestamp_ms));
+        let nick = |name: &str| format!("<span class=\"nick\">{}</span>", escape(name));
+        let _ = match &entry.kind {
+            HistoryEntryKind::Chat { nickname, text } => {
+                writeln!(out, "<div class=\"msg\">{ts} {}: {}</div>", nick(nickname), escape(text))
+            }
+            HistoryEntryKind::ThreadChat { nickname, text, .. } => {
+                writeln!(out, "<div class=\"msg\">{ts} &#8627; {}: {}</div>", nick(nickname), escape(text))
+            }
+            HistoryEntryKind::Reply { nickname, text, reply_to } => {
+                let to = quoted_name(history, reply_to).unwrap_or_else(|| "?".into());
+                writeln!(out, "<div class=\"msg\">{ts} {} <span class=\"hash\">re {}</span>: {}</div>", nick(nickname), escape(&to), escape(text))
+            }
+            HistoryEntryKind::Spoiler { nickname, text } => writeln!(
+                out,
+                "<div class=\"msg\">{ts} {}: <details><summary>spoiler</summary>{}</details></div>",
+                nick(nickname),
+                escape(text)
+            ),
+            HistoryEntryKind::System(text) => {
+                writeln!(out, "<div class=\"msg system\">{ts} {}</div>", escape(text))
+            }
+            HistoryEntryKind::FileOffer { nickname, filename, size, hash, .. } => writeln!(
+                out,
+                "<div class=\"msg file\">{ts} {} shared <b>{}</b> ({}) <span class=\"hash\">blake3 {}</span></div>",
+                nick(nickname),
+                escape(filename),
+                format_file_size(*size),
+                hex(hash)
+            ),
+            HistoryEntryKind::FileRetract { hash } => {
+                let name = retracted_name(history, hash, None).unwrap_or_else(|| hex(hash));
+                writeln!(out, "<div class=\"msg system\">{ts} {} is no longer shared</div>", escape(&name))
+            }
+            HistoryEntryKind::FileRevoke { hash, endpoint_id, offer_id } => {
+                let name = retracted_name(history, hash, Some((endpoint_id, offer_id))).unwrap_or_else(|| hex(hash));
+                writeln!(out, "<div class=\"msg system\">{ts} {} is no longer shared</div>", escape(&name))
+            }
+            HistoryEntryKind::FileKey { .. } => Ok(()),
+        };
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+// ── Tests ────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history() -> Vec<HistoryEntry> {
+        let entry = |timestamp_ms, kind| HistoryEntry { message_id: [0; 16], timestamp_ms, kind };
+        let peer = iroh::SecretKey::from_bytes(&[1; 32]).public();
+        vec![
+            entry(0, HistoryEntryKind::Chat { nickname: "Alice".into(), text: "<b>hi</b>\nthere".into() }),
+            entry(60_000, HistoryEntryKind::Spoiler { nickname: "Bob".into(), text: "secret".into() }),
+            entry(
+                120_000,
+                HistoryEntryKind::FileOffer {
+                    nickname: "Bob".into(),
+                    endpoint_id: peer,
+                    filename: "notes.txt".into(),
+                    size: 2048,
+                    hash: [0xab; 32],
+                    mime_type: None,
+                    target: None,
+                },
+            ),
+            entry(180_000, HistoryEntryKind::FileRevoke { hash: [0xab; 32], endpoint_id: peer, offer_id: [0; 16] }),
+        ]
+    }
+
+    #[test]
+    fn args_parse() {
+        assert_eq!(parse_args(""), Ok(ExportArgs { format: Format::Text, path: None }));
+        assert_eq!(
+            parse_args("--format html out.html"),
+            Ok(ExportArgs { format: Format::Html, path: Some("out.html".into()) })
+        );
+        assert_eq!(parse_args("--format=TXT").unwrap().format, Format::Text);
+        assert_eq!(parse_args("--format json").unwrap().format, Format::Json);
+        assert_eq!(parse_args("'my notes.txt'").unwrap().path, Some("my notes.txt".into()));
+        assert_eq!(parse_args("--format pdf"), Err(ParseError::BadValue("--format".into(), "pdf".into())));
+        assert_eq!(parse_args("a b"), Err(ParseError::TooMany));
+        assert_eq!(default_file_name(Format::Html, 0), "transcript-1970-01-01-0000.html");
+    }
+
+    #[test]
+    fn text_transcript() {
+        let text = render(&history(), Format::Text, &Theme::dark(), 0);
+        let hash = "ab".repeat(32);
+        assert_eq!(
+            text,
+            format!(
+                "1970-01-01 00:00 Alice: <b>hi</b>
+                   there
+1970-01-01 00:01 Bob: [spoiler]
+1970-01-01 00:02 Bob shared notes.txt (2.0 KB, blake3 {hash})
+1970-01-01 00:03 * notes.txt is no longer shared
+"
+            )
+        );
+    }
+
+    #[test]
+    fn html_transcript_is_escaped_and_themed() {
+        let html = render(&history(), Format::Html, &Theme::dark(), 0);
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("Alice</span>: &lt;b&gt;hi&lt;/b&gt;\nthere</div>"));
+        assert!(html.contains("<details><summary>spoiler</summary>secret</details>"));
+        assert!(html.contains(&format!("blake3 {}", "ab".repeat(32))));
+        assert!(html.contains(&format!("background: {}", css(Theme::dark().bg))));
+        assert!(html.ends_with("</html>\n"));
+    }
+
+    #[test]
+    fn json_transcript_roundtrips() {
+        let json = render(&history(), Format::Json, &Theme::dark(), 0);
+        let entries = parse_transcript(&json).unwrap();
+        assert_eq!(entries.len(), 4);
+        assert!(matches!(&entries[0].kind, HistoryEntryKind::Chat { text, .. } if text == "<b>hi</b>\nthere"));
+        assert!(matches!(entries[2].kind, HistoryEntryKind::FileOffer { size: 2048, .. }));
+
+        let Err(err) = parse_transcript("1970-01-01 00:00 Alice: hi") else { panic!("text parsed") };
+        assert!(err.to_string().contains("--format json"));
+        assert!(parse_transcript(r#"{"version": 99, "entries": []}"#).is_err());
+    }
+}
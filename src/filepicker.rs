@@ -1,4 +1,4 @@
-//! Modal file picker overlay using `ratatui-explorer`.
+//! Modal file picker overlay.
 //!
 //! Presents a centered card overlay where the user navigates their filesystem
 //! and selects a file to share. Uses the same `Clear` pattern as `welcome.rs`
@@ -13,36 +13,51 @@
 //! 2. Setting `AppMode::FilePicker` to route all key events to the picker.
 //! 3. Rendering the picker *last* in `ui()`, so it draws on top of everything.
 //! 4. Using `Clear` widget to erase the area behind the overlay.
+//!
+//! ## Entry model
+//!
+//! The picker keeps its own list of `Entry` values (name, size, modified time)
+//! rather than delegating to a third-party explorer widget, so it can hide
+//! dotfiles, sort by size or age, and show those columns in the listing.
+//! Directories always sort before files, and `../` is always first.
+//!
+//! ## Getting around quickly
+//!
+//! Typing narrows the listing (a case-insensitive substring filter), Ctrl+L
+//! opens a box to type or paste a path directly, and Alt+1… jump to the
+//! Home / Downloads / Desktop bookmarks.
 
 // `anyhow::Result` — convenient error type for functions that can fail.
 // The `?` operator works with anyhow to convert any `std::error::Error` automatically.
 use anyhow::Result;
 // `crossterm::event::Event` — the full terminal event enum (key, mouse, resize).
-// The ratatui-explorer widget expects this type for its `handle()` method.
-use crossterm::event::Event;
+use crossterm::event::{Event, KeyCode, KeyEventKind, KeyModifiers};
 // Ratatui types:
-// - `Alignment`: text alignment (Left, Center, Right) — used for the title.
+// - `Alignment`: text alignment (Left, Center, Right) — used for the titles.
 // - `Rect`: a rectangle (x, y, width, height) — all positioning in ratatui uses `Rect`.
-// - `Style` / `Color`: styling primitives for colors and text attributes.
-// - `Block` / `Borders`: bordered container widget — wraps the explorer widget.
+// - `Layout` / `Constraint`: split the card into bookmark, input, and list rows.
+// - `Block` / `Borders`: bordered container widget — frames the card.
+// - `Paragraph`: single-line text for the bookmark and filter rows.
 // - `Clear`: a special widget that erases (fills with spaces) a rectangular area.
 //   Used for overlays to prevent the underlying UI from showing through.
+// - `List` / `ListItem` / `ListState`: a scrollable list; the state holds the
+//   selected index so ratatui can keep the selection in view.
 use ratatui::{
-    layout::{Alignment, Rect},
-    style::Style,
-    widgets::{Block, Borders, Clear},
+    layout::{Alignment, Constraint, Layout, Rect},
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph},
 };
-// `ratatui_explorer` provides a ready-made filesystem browser widget:
-// - `FileExplorer`: the main widget — handles directory traversal, file listing,
-//   and keyboard navigation (up/down to select, left to go to parent, right/enter
-//   to enter a directory or select a file).
-// - `Theme`: builder API for customizing the explorer's appearance (border style,
-//   highlight colors, etc.). Uses the builder pattern: chain `.with_*()` methods
-//   to configure, then pass to `FileExplorer::with_theme()`.
-use ratatui_explorer::{FileExplorer, Theme as ExplorerTheme};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use unicode_width::UnicodeWidthStr;
 
+use crate::fit;
 use crate::theme::Theme;
+use crate::transfer::format_file_size;
+
+/// How far PageUp/PageDown move the selection.
+const PAGE: usize = 12;
 
 // ── Types ────────────────────────────────────────────────────────────────────
 
@@ -53,97 +68,430 @@ use crate::theme::Theme;
 pub enum FilePickerResult {
     /// User selected a file at this path.
     Selected(PathBuf),
+    /// User chose to share this whole directory (Ctrl+D), which gets
+    /// packed into an archive (see `pack`).
+    Folder(PathBuf),
     /// User cancelled (Esc).
     Cancelled,
     /// Still browsing — no action taken yet.
     Browsing,
 }
 
+/// Ordering of the entries within each group (directories, then files).
+///
+/// Ctrl+S cycles Name → Size → Modified → Name. Size and Modified put the
+/// largest / newest first, since that's what you're usually hunting for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortMode {
+    Name,
+    Size,
+    Modified,
+}
+
+impl SortMode {
+    /// The next mode in the Ctrl+S cycle.
+    pub fn next(self) -> Self {
+        match self {
+            SortMode::Name => SortMode::Size,
+            SortMode::Size => SortMode::Modified,
+            SortMode::Modified => SortMode::Name,
+        }
+    }
+
+    /// Short label for the hint line.
+    pub fn label(self) -> &'static str {
+        match self {
+            SortMode::Name => "name",
+            SortMode::Size => "size",
+            SortMode::Modified => "modified",
+        }
+    }
+}
+
+/// One row in the picker: a file, a directory, or the `../` parent link.
+#[derive(Debug, Clone)]
+struct Entry {
+    /// Display name; directories carry a trailing `/`.
+    name: String,
+    path: PathBuf,
+    is_dir: bool,
+    /// Size in bytes (0 for directories).
+    size: u64,
+    /// Last modification time, if the platform reports one.
+    modified: Option<SystemTime>,
+}
+
+/// Whether a file name is "hidden" by Unix convention (leading dot).
+fn is_hidden(name: &str) -> bool {
+    name.starts_with('.')
+}
+
+/// Read `dir` into a sorted entry list, dotfiles included.
+///
+/// Hidden entries are filtered at display time (see `FilePicker::refresh`)
+/// so toggling them or typing a `.`-prefixed filter doesn't re-read the disk.
+///
+/// Metadata is read through `std::fs::metadata` (which follows symlinks) so a
+/// link to a directory is browsable like the directory itself. Entries whose
+/// metadata can't be read are still listed, as zero-sized files.
+fn read_entries(dir: &Path, sort: SortMode) -> Result<Vec<Entry>> {
+    let mut entries: Vec<Entry> = std::fs::read_dir(dir)?
+        .filter_map(|e| e.ok())
+        .map(|e| {
+            let name = e.file_name().to_string_lossy().into_owned();
+            let path = e.path();
+            let meta = std::fs::metadata(&path).ok();
+            let is_dir = meta.as_ref().is_some_and(|m| m.is_dir());
+            Entry {
+                name: if is_dir { format!("{name}/") } else { name },
+                path,
+                is_dir,
+                size: meta.as_ref().filter(|m| !m.is_dir()).map_or(0, |m| m.len()),
+                modified: meta.and_then(|m| m.modified().ok()),
+            }
+        })
+        .collect();
+    // `sort_by_key` is stable, so ties keep the name order from the first
+    // pass. `Reverse` flips the key's ordering for largest / newest first.
+    entries.sort_by_key(|e| e.name.to_lowercase());
+    match sort {
+        SortMode::Name => {}
+        SortMode::Size => entries.sort_by_key(|e| std::cmp::Reverse(e.size)),
+        SortMode::Modified => entries.sort_by_key(|e| std::cmp::Reverse(e.modified)),
+    }
+    // Directories first — `false < true`, so sort on `!is_dir`.
+    entries.sort_by_key(|e| !e.is_dir);
+
+    if let Some(parent) = dir.parent() {
+        entries.insert(
+            0,
+            Entry {
+                name: "../".to_string(),
+                path: parent.to_path_buf(),
+                is_dir: true,
+                size: 0,
+                modified: None,
+            },
+        );
+    }
+    Ok(entries)
+}
+
+/// Whether `entry` is listed under the current filter.
+///
+/// The filter is a case-insensitive substring match. `../` only shows with
+/// an empty filter (you're narrowing *this* directory), and a filter that
+/// starts with `.` reveals dotfiles even when they're toggled off — typing
+/// `.git` should find `.gitignore`.
+fn is_visible(entry: &Entry, filter: &str, show_hidden: bool) -> bool {
+    if entry.name == "../" {
+        return filter.is_empty();
+    }
+    if is_hidden(&entry.name) && !show_hidden && !filter.starts_with('.') {
+        return false;
+    }
+    entry.name.to_lowercase().contains(&filter.to_lowercase())
+}
+
+/// Expand a leading `~` to the home directory and resolve relative paths
+/// against `base`, for the Ctrl+L path box.
+fn resolve_path(input: &str, base: &Path) -> PathBuf {
+    let input = input.trim();
+    let expanded = match (input.strip_prefix('~'), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest.trim_start_matches('/')),
+        _ => PathBuf::from(input),
+    };
+    if expanded.is_absolute() { expanded } else { base.join(expanded) }
+}
+
+/// Quick-jump directories shown above the listing, bound to Alt+1, Alt+2, …
+///
+/// `dirs` returns `None` for folders the platform doesn't define (e.g. no
+/// Desktop on a headless box); those are left out.
+fn default_bookmarks() -> Vec<(&'static str, PathBuf)> {
+    [
+        ("Home", dirs::home_dir()),
+        ("Downloads", dirs::download_dir()),
+        ("Desktop", dirs::desktop_dir()),
+    ]
+    .into_iter()
+    .filter_map(|(label, dir)| Some((label, dir?)))
+    .filter(|(_, dir)| dir.is_dir())
+    .collect()
+}
+
+/// Format how long ago `then` was, relative to `now`: `"42s"`, `"5m"`,
+/// `"3h"`, `"12d"`, `"2y"`. Times in the future (clock skew) read as `"now"`.
+fn format_age(now: SystemTime, then: SystemTime) -> String {
+    let Ok(age) = now.duration_since(then) else {
+        return "now".to_string();
+    };
+    let secs = age.as_secs();
+    match secs {
+        0..60 => format!("{secs}s"),
+        60..3_600 => format!("{}m", secs / 60),
+        3_600..86_400 => format!("{}h", secs / 3_600),
+        86_400..31_536_000 => format!("{}d", secs / 86_400),
+        _ => format!("{}y", secs / 31_536_000),
+    }
+}
+
 // ── FilePicker ───────────────────────────────────────────────────────────────
 
-/// A modal file picker that wraps `ratatui_explorer::FileExplorer`.
+/// A modal file picker over a directory listing.
 ///
 /// Created on demand when the user presses Ctrl+F or types `/send`, and
 /// destroyed when they select a file or press Esc. This **create-on-demand,
 /// destroy-on-close** pattern keeps the picker stateless between uses — each
 /// opening starts fresh from the current working directory.
 pub struct FilePicker {
-    /// The underlying filesystem explorer widget from `ratatui-explorer`.
-    explorer: FileExplorer,
+    /// Directory being listed.
+    cwd: PathBuf,
+    /// Everything in `cwd` (dotfiles included), already sorted.
+    entries: Vec<Entry>,
+    /// Indices into `entries` that pass the filter — what's on screen.
+    visible: Vec<usize>,
+    /// Index into `visible` of the highlighted row.
+    selected: usize,
+    /// Whether dotfiles are listed (Ctrl+A toggles, like `ls -a`).
+    show_hidden: bool,
+    /// Ordering within the directory and file groups (Ctrl+S cycles).
+    sort: SortMode,
+    /// Type-ahead filter; printable keys append, Backspace deletes.
+    filter: String,
+    /// `Some` while the Ctrl+L path box is open.
+    path_input: Option<String>,
+    /// Last navigation error, shown until the next key press.
+    error: Option<String>,
+    /// Quick-jump directories (Alt+1, Alt+2, …).
+    bookmarks: Vec<(&'static str, PathBuf)>,
 }
 
 impl FilePicker {
     /// Create a new file picker starting at the current working directory.
     ///
-    /// `Result<Self>` because `FileExplorer::with_theme()` can fail if the
-    /// current directory is unreadable. The `?` operator propagates any error
-    /// to the caller, which displays it as a system message.
-    pub fn new(theme: &Theme) -> Result<Self> {
-        let explorer_theme = ExplorerTheme::default()
-            .with_block(
-                Block::default()
-                    .borders(Borders::ALL)
-                    .style(Style::default().bg(theme.bg))
-                    .border_style(Style::default().fg(theme.border_focused))
-                    .title(" Select File (Enter=select, Esc=cancel) ")
-                    .title_alignment(Alignment::Center)
-                    .title_style(Style::default().fg(theme.title)),
-            )
-            .with_highlight_item_style(
-                Style::default()
-                    .fg(theme.picker_highlight_file_fg)
-                    .bg(theme.picker_highlight_file_bg),
-            )
-            .with_highlight_dir_style(
-                Style::default()
-                    .fg(theme.picker_highlight_dir_fg)
-                    .bg(theme.picker_highlight_dir_bg),
-            )
-            .add_default_title();
+    /// `Result<Self>` because the current directory may be unreadable. The
+    /// `?` operator propagates any error to the caller, which displays it as
+    /// a system message.
+    pub fn new() -> Result<Self> {
+        Self::open_at(std::env::current_dir()?)
+    }
+
+    /// Create a picker listing `dir`.
+    pub fn open_at(dir: PathBuf) -> Result<Self> {
+        let entries = read_entries(&dir, SortMode::Name)?;
+        let mut picker = Self {
+            cwd: dir,
+            entries,
+            visible: Vec::new(),
+            selected: 0,
+            show_hidden: false,
+            sort: SortMode::Name,
+            filter: String::new(),
+            path_input: None,
+            error: None,
+            bookmarks: default_bookmarks(),
+        };
+        picker.refresh(None);
+        Ok(picker)
+    }
+
+    /// The directory currently being listed.
+    pub fn cwd(&self) -> &Path {
+        &self.cwd
+    }
+
+    /// The highlighted entry, if anything is listed.
+    fn current(&self) -> Option<&Entry> {
+        self.visible.get(self.selected).map(|&i| &self.entries[i])
+    }
+
+    /// Recompute `visible` from the filter and hidden toggle, selecting
+    /// `keep` if it is still listed and the first entry otherwise.
+    fn refresh(&mut self, keep: Option<PathBuf>) {
+        self.visible = (0..self.entries.len())
+            .filter(|&i| is_visible(&self.entries[i], &self.filter, self.show_hidden))
+            .collect();
+        self.selected = keep
+            .and_then(|p| self.visible.iter().position(|&i| self.entries[i].path == p))
+            .unwrap_or(0);
+    }
+
+    /// Re-read the current directory, keeping the highlighted entry selected
+    /// if it is still listed (e.g. after a sort change).
+    fn reload(&mut self) -> Result<()> {
+        let current = self.current().map(|e| e.path.clone());
+        self.entries = read_entries(&self.cwd, self.sort)?;
+        self.refresh(current);
+        Ok(())
+    }
+
+    /// Navigate into `dir`, clearing the filter.
+    ///
+    /// Going up lands on the directory we came from, so Left then Right is a
+    /// no-op rather than a trip back to the top of the list. On failure (e.g.
+    /// permission denied) the picker stays where it was and shows the error.
+    fn enter(&mut self, dir: PathBuf) {
+        match read_entries(&dir, self.sort) {
+            Ok(entries) => {
+                let came_from = std::mem::replace(&mut self.cwd, dir);
+                self.entries = entries;
+                self.filter.clear();
+                self.refresh(Some(came_from));
+                // `../` of a child has the same path as where we came from
+                // when going *down*; never land on it.
+                if self.current().is_some_and(|e| e.name == "../") {
+                    self.selected = 0;
+                }
+            }
+            Err(e) => self.error = Some(format!("{}: {e}", dir.display())),
+        }
+    }
 
-        let explorer = FileExplorer::with_theme(explorer_theme)?;
-        Ok(Self { explorer })
+    /// Act on the Ctrl+L path box: open a directory, or select a file.
+    fn submit_path(&mut self, input: &str) -> FilePickerResult {
+        let path = resolve_path(input, &self.cwd);
+        if path.is_dir() {
+            self.path_input = None;
+            self.enter(path);
+        } else if path.is_file() {
+            return FilePickerResult::Selected(path);
+        } else {
+            self.error = Some(format!("not found: {}", path.display()));
+        }
+        FilePickerResult::Browsing
     }
 
     /// Handle a crossterm event. Returns the picker result.
     ///
-    /// - Enter on a file → `Selected(path)`
-    /// - Esc → `Cancelled`
-    /// - Everything else is delegated to the explorer for navigation.
+    /// - Enter on a file → `Selected(path)`; on a directory → open it
+    /// - Printable keys → type-ahead filter (Backspace deletes, Esc clears)
+    /// - Ctrl+L → path box; type or paste a path, Enter to go there
+    /// - Alt+1, Alt+2, … → jump to a bookmark
+    /// - Ctrl+A → toggle dotfiles; Ctrl+S → cycle sort mode
+    /// - Ctrl+D → `Folder(path)`: the highlighted directory, or the one
+    ///   being browsed when a file or `../` is highlighted
+    /// - Arrows, Home/End, PageUp/PageDown → navigation
+    /// - Esc → `Cancelled` (once the filter / path box is empty)
+    ///
+    /// A bracketed paste goes to the path box if it looks like a path
+    /// (starts with `/` or `~`), otherwise to the filter.
     ///
-    /// We check for Esc/Enter *before* delegating to avoid the explorer
-    /// consuming these keys for its own navigation (e.g. Enter on a directory
-    /// means "enter the directory", not "select it").
+    /// A directory that can't be read leaves the picker where it was rather
+    /// than closing it, so one unreadable folder doesn't abort the share.
     pub fn handle(&mut self, event: &Event) -> Result<FilePickerResult> {
-        // `if let Event::Key(key) = event` is a *refutable pattern match* —
-        // it only enters the block if `event` is the `Key` variant.
-        if let Event::Key(key) = event {
-            // Filter out non-Press events (Windows sends Release events too).
-            if key.kind != crossterm::event::KeyEventKind::Press {
+        let key = match event {
+            Event::Key(key) => key,
+            Event::Paste(text) => {
+                let text = text.lines().next().unwrap_or("").trim();
+                match &mut self.path_input {
+                    Some(input) => input.push_str(text),
+                    None if text.starts_with('/') || text.starts_with('~') => {
+                        self.path_input = Some(text.to_string());
+                    }
+                    None => {
+                        self.filter.push_str(text);
+                        self.refresh(None);
+                    }
+                }
                 return Ok(FilePickerResult::Browsing);
             }
+            _ => return Ok(FilePickerResult::Browsing),
+        };
+        // Filter out non-Press events (Windows sends Release events too).
+        if key.kind != KeyEventKind::Press {
+            return Ok(FilePickerResult::Browsing);
+        }
+        self.error = None;
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        let alt = key.modifiers.contains(KeyModifiers::ALT);
+
+        // ── Path box: a one-line editor that owns the keyboard ───────────
+        if let Some(input) = &mut self.path_input {
             match key.code {
-                crossterm::event::KeyCode::Esc => return Ok(FilePickerResult::Cancelled),
-                crossterm::event::KeyCode::Enter => {
-                    // `.current()` returns the currently highlighted `DirEntry`.
-                    // `.is_file()` checks the filesystem entry type — returns
-                    // `true` for regular files, `false` for directories/symlinks.
-                    let current = self.explorer.current();
-                    if current.is_file() {
-                        // `.path()` returns a reference to the entry's `PathBuf`.
-                        // `.clone()` creates an owned copy to return to the caller.
-                        return Ok(FilePickerResult::Selected(current.path().clone()));
-                    }
-                    // If it's a directory, fall through to let the explorer
-                    // handle Enter as "navigate into this directory".
+                KeyCode::Esc => self.path_input = None,
+                KeyCode::Enter => {
+                    let input = input.clone();
+                    return Ok(self.submit_path(&input));
+                }
+                KeyCode::Backspace => {
+                    input.pop();
                 }
+                KeyCode::Char(c) if !ctrl && !alt => input.push(c),
                 _ => {}
             }
+            return Ok(FilePickerResult::Browsing);
         }
 
-        // Delegate all other events to the explorer for navigation
-        // (arrow keys, typing to filter, etc.).
-        self.explorer.handle(event)?;
+        // The listing can be empty (nothing matches the filter), so `last`
+        // saturates to 0 and the selection stays put.
+        let last = self.visible.len().saturating_sub(1);
+        match key.code {
+            KeyCode::Esc if !self.filter.is_empty() => {
+                self.filter.clear();
+                self.refresh(None);
+            }
+            KeyCode::Esc => return Ok(FilePickerResult::Cancelled),
+            KeyCode::Up => {
+                self.selected = if self.selected == 0 { last } else { self.selected - 1 };
+            }
+            KeyCode::Down => {
+                self.selected = if self.selected >= last { 0 } else { self.selected + 1 };
+            }
+            KeyCode::Home => self.selected = 0,
+            KeyCode::End => self.selected = last,
+            KeyCode::PageUp => self.selected = self.selected.saturating_sub(PAGE),
+            KeyCode::PageDown => self.selected = (self.selected + PAGE).min(last),
+            KeyCode::Backspace if !self.filter.is_empty() => {
+                self.filter.pop();
+                self.refresh(None);
+            }
+            KeyCode::Left | KeyCode::Backspace => {
+                if let Some(parent) = self.cwd.parent().map(Path::to_path_buf) {
+                    self.enter(parent);
+                }
+            }
+            KeyCode::Right | KeyCode::Enter => {
+                let Some(entry) = self.current() else {
+                    return Ok(FilePickerResult::Browsing);
+                };
+                if entry.is_dir {
+                    let path = entry.path.clone();
+                    self.enter(path);
+                } else if key.code == KeyCode::Enter {
+                    return Ok(FilePickerResult::Selected(entry.path.clone()));
+                }
+            }
+            KeyCode::Char('l') if ctrl => self.path_input = Some(String::new()),
+            KeyCode::Char('d') if ctrl => {
+                let dir = match self.current() {
+                    Some(entry) if entry.is_dir && entry.name != "../" => entry.path.clone(),
+                    _ => self.cwd.clone(),
+                };
+                return Ok(FilePickerResult::Folder(dir));
+            }
+            KeyCode::Char('a') if ctrl => {
+                self.show_hidden = !self.show_hidden;
+                let current = self.current().map(|e| e.path.clone());
+                self.refresh(current);
+            }
+            KeyCode::Char('s') if ctrl => {
+                self.sort = self.sort.next();
+                self.reload()?;
+            }
+            KeyCode::Char(c @ '1'..='9') if alt => {
+                let index = c as usize - '1' as usize;
+                if let Some((_, dir)) = self.bookmarks.get(index) {
+                    let dir = dir.clone();
+                    self.enter(dir);
+                }
+            }
+            KeyCode::Char(c) if !ctrl && !alt => {
+                self.filter.push(c);
+                self.refresh(None);
+            }
+            _ => {}
+        }
         Ok(FilePickerResult::Browsing)
     }
 
@@ -153,7 +501,13 @@ impl FilePicker {
     /// 1. Calculate card dimensions as a percentage of the terminal size.
     /// 2. Clamp to reasonable min/max bounds with `.max()` and `.min()`.
     /// 3. Center by computing offsets with `saturating_sub()` / 2.
-    pub fn render(&self, f: &mut ratatui::Frame) {
+    ///
+    /// Inside the card, the first row holds the bookmarks, the second the
+    /// filter or path box, and the rest the listing.
+    ///
+    /// The theme is passed in at render time (not captured at creation) so
+    /// Ctrl+T recolors an open picker too.
+    pub fn render(&self, f: &mut ratatui::Frame, theme: &Theme) {
         let area = f.area();
 
         // Centered card: 70% width, 70% height, clamped to reasonable bounds.
@@ -169,12 +523,345 @@ impl FilePicker {
         let card = Rect::new(x, y, card_w, card_h);
 
         // `Clear` erases the card area (fills with spaces) so the underlying
-        // chat UI doesn't show through the overlay. Without this, the explorer
-        // widget would be drawn on top of the existing characters, creating a
-        // visual mess.
+        // chat UI doesn't show through the overlay.
         f.render_widget(Clear, card);
-        // `.widget()` returns a ratatui `Widget` that can be rendered into a `Rect`.
-        // The `&` borrow is needed because `widget()` returns a reference-based type.
-        f.render_widget(&self.explorer.widget(), card);
+
+        let key = |k: &str| Span::styled(k.to_string(), Style::default().fg(theme.hint_key));
+        let text = |t: &str| Span::styled(t.to_string(), Style::default().fg(theme.hint_text));
+        let hints = Line::from(vec![
+            key(" Enter"),
+            text(" select  "),
+            key("^D"),
+            text(" folder  "),
+            key("^L"),
+            text(" path  "),
+            key("^A"),
+            text(if self.show_hidden { " hide dotfiles  " } else { " dotfiles  " }),
+            key("^S"),
+            text(&format!(" sort: {}  ", self.sort.label())),
+            key("Esc"),
+            text(" cancel "),
+        ]);
+        let ellipsis = theme.glyph("…", "...");
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .border_set(theme.border_set())
+            .style(Style::default().bg(theme.bg))
+            .border_style(Style::default().fg(theme.border_focused))
+            .title(Line::styled(" Select File ", Style::default().fg(theme.title)).centered())
+            .title(
+                Line::styled(
+                    format!(" {} ", fit::end(&self.cwd.display().to_string(), card_w.saturating_sub(4) as usize, ellipsis)),
+                    Style::default().fg(theme.text_dim),
+                )
+                .left_aligned(),
+            )
+            .title_bottom(hints)
+            .title_alignment(Alignment::Center);
+        let inner = block.inner(card);
+        f.render_widget(block, card);
+
+        let [bookmark_row, input_row, list_area] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Length(1),
+            Constraint::Min(0),
+        ])
+        .areas(inner);
+
+        // ── Bookmarks ────────────────────────────────────────────────────
+        let mut spans = vec![Span::raw(" ")];
+        for (i, (label, _)) in self.bookmarks.iter().enumerate() {
+            spans.push(key(&format!("Alt+{}", i + 1)));
+            spans.push(text(&format!(" {label}  ")));
+        }
+        f.render_widget(Paragraph::new(Line::from(spans)), bookmark_row);
+
+        // ── Filter / path box / error ────────────────────────────────────
+        let input_line = if let Some(error) = &self.error {
+            Line::styled(format!(" {error}"), Style::default().fg(theme.error))
+        } else if let Some(input) = &self.path_input {
+            Line::from(vec![
+                Span::styled(" Go to: ", Style::default().fg(theme.input_prompt)),
+                Span::styled(input.clone(), Style::default().fg(theme.text)),
+            ])
+        } else if self.filter.is_empty() {
+            Line::styled(" type to filter", Style::default().fg(theme.text_muted))
+        } else {
+            Line::from(vec![
+                Span::styled(" Filter: ", Style::default().fg(theme.input_prompt)),
+                Span::styled(self.filter.clone(), Style::default().fg(theme.text)),
+                Span::styled(
+                    format!("  ({} match{})", self.visible.len(), if self.visible.len() == 1 { "" } else { "es" }),
+                    Style::default().fg(theme.text_muted),
+                ),
+            ])
+        };
+        f.render_widget(Paragraph::new(input_line), input_row);
+        if let Some(input) = &self.path_input {
+            let col = input_row.x + " Go to: ".len() as u16 + input.width() as u16;
+            f.set_cursor_position((col.min(input_row.right().saturating_sub(1)), input_row.y));
+        }
+
+        // Row layout: "name … size  age". The highlight symbol takes 2 cells;
+        // size and age get fixed right-hand columns.
+        const SIZE_W: usize = 9;
+        const AGE_W: usize = 5;
+        let inner_w = list_area.width as usize;
+        let name_w = inner_w.saturating_sub(SIZE_W + AGE_W + 4).max(1);
+        let now = SystemTime::now();
+
+        let items: Vec<ListItem> = self
+            .visible
+            .iter()
+            .map(|&i| {
+                let e = &self.entries[i];
+                let size = if e.is_dir { String::new() } else { format_file_size(e.size) };
+                let age = e.modified.map(|m| format_age(now, m)).unwrap_or_default();
+                let name = fit::end(&e.name, name_w, ellipsis);
+                let pad = name_w.saturating_sub(name.width());
+                let name_style = if e.is_dir {
+                    Style::default().fg(theme.accent).add_modifier(Modifier::BOLD)
+                } else {
+                    Style::default().fg(theme.text)
+                };
+                ListItem::new(Line::from(vec![
+                    Span::styled(name, name_style),
+                    Span::raw(" ".repeat(pad)),
+                    Span::styled(format!(" {size:>SIZE_W$}"), Style::default().fg(theme.text_dim)),
+                    Span::styled(format!(" {age:>AGE_W$}"), Style::default().fg(theme.timestamp)),
+                ]))
+            })
+            .collect();
+
+        let highlight = match self.current() {
+            Some(e) if e.is_dir => Style::default()
+                .fg(theme.picker_highlight_dir_fg)
+                .bg(theme.picker_highlight_dir_bg),
+            _ => Style::default()
+                .fg(theme.picker_highlight_file_fg)
+                .bg(theme.picker_highlight_file_bg),
+        };
+        let list = List::new(items)
+            .highlight_style(highlight)
+            .highlight_symbol("> ");
+        // A fresh `ListState` each frame is enough: ratatui scrolls so the
+        // selected row stays visible.
+        let mut state = ListState::default().with_selected(Some(self.selected));
+        f.render_stateful_widget(list, list_area, &mut state);
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::event::KeyEvent;
+    use std::time::Duration;
+
+    fn key(code: KeyCode) -> Event {
+        Event::Key(KeyEvent::new(code, KeyModifiers::NONE))
+    }
+
+    fn ctrl(c: char) -> Event {
+        Event::Key(KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL))
+    }
+
+    fn type_str(picker: &mut FilePicker, s: &str) {
+        for c in s.chars() {
+            picker.handle(&key(KeyCode::Char(c))).unwrap();
+        }
+    }
+
+    /// A directory with a subdirectory, a dotfile, and two files of different
+    /// sizes and ages.
+    fn fixture() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("sub")).unwrap();
+        std::fs::write(dir.path().join(".hidden"), b"x").unwrap();
+        std::fs::write(dir.path().join("a_small.txt"), b"hi").unwrap();
+        let big = dir.path().join("b_big.bin");
+        std::fs::write(&big, vec![0u8; 4096]).unwrap();
+        // Make the big file clearly older than the small one.
+        let old = SystemTime::now() - Duration::from_secs(3_600);
+        std::fs::File::options().write(true).open(&big).unwrap().set_modified(old).unwrap();
+        dir
+    }
+
+    fn names(picker: &FilePicker) -> Vec<&str> {
+        picker.visible.iter().map(|&i| picker.entries[i].name.as_str()).collect()
+    }
+
+    fn selected_name(picker: &FilePicker) -> &str {
+        &picker.current().unwrap().name
+    }
+
+    #[test]
+    fn hidden_files_toggle() {
+        let dir = fixture();
+        let mut picker = FilePicker::open_at(dir.path().to_path_buf()).unwrap();
+        assert!(!names(&picker).contains(&".hidden"));
+        picker.handle(&ctrl('a')).unwrap();
+        assert!(names(&picker).contains(&".hidden"));
+        picker.handle(&ctrl('a')).unwrap();
+        assert!(!names(&picker).contains(&".hidden"));
+    }
+
+    #[test]
+    fn sort_modes_keep_directories_first() {
+        let dir = fixture();
+        let mut picker = FilePicker::open_at(dir.path().to_path_buf()).unwrap();
+        assert_eq!(names(&picker), ["../", "sub/", "a_small.txt", "b_big.bin"]);
+
+        picker.handle(&ctrl('s')).unwrap();
+        assert_eq!(picker.sort, SortMode::Size);
+        assert_eq!(names(&picker), ["../", "sub/", "b_big.bin", "a_small.txt"]);
+
+        picker.handle(&ctrl('s')).unwrap();
+        assert_eq!(picker.sort, SortMode::Modified);
+        assert_eq!(names(&picker), ["../", "sub/", "a_small.txt", "b_big.bin"]);
+
+        picker.handle(&ctrl('s')).unwrap();
+        assert_eq!(picker.sort, SortMode::Name);
+    }
+
+    #[test]
+    fn resort_keeps_selection() {
+        let dir = fixture();
+        let mut picker = FilePicker::open_at(dir.path().to_path_buf()).unwrap();
+        picker.handle(&key(KeyCode::End)).unwrap();
+        assert_eq!(selected_name(&picker), "b_big.bin");
+        picker.handle(&ctrl('s')).unwrap();
+        assert_eq!(selected_name(&picker), "b_big.bin");
+    }
+
+    #[test]
+    fn enter_directory_and_back() {
+        let dir = fixture();
+        let mut picker = FilePicker::open_at(dir.path().to_path_buf()).unwrap();
+        picker.handle(&key(KeyCode::Down)).unwrap();
+        picker.handle(&key(KeyCode::Enter)).unwrap();
+        assert_eq!(picker.cwd(), dir.path().join("sub"));
+        assert_eq!(names(&picker), ["../"]);
+        // Going back up re-selects the directory we left.
+        picker.handle(&key(KeyCode::Left)).unwrap();
+        assert_eq!(picker.cwd(), dir.path());
+        assert_eq!(selected_name(&picker), "sub/");
+    }
+
+    #[test]
+    fn ctrl_d_picks_a_folder() {
+        let dir = fixture();
+        let mut picker = FilePicker::open_at(dir.path().to_path_buf()).unwrap();
+        // On `../` it's the folder being browsed…
+        match picker.handle(&ctrl('d')).unwrap() {
+            FilePickerResult::Folder(p) => assert_eq!(p, dir.path()),
+            _ => panic!("expected Folder"),
+        }
+        // …on a directory, that directory.
+        picker.handle(&key(KeyCode::Down)).unwrap();
+        match picker.handle(&ctrl('d')).unwrap() {
+            FilePickerResult::Folder(p) => assert_eq!(p, dir.path().join("sub")),
+            _ => panic!("expected Folder"),
+        }
+    }
+
+    #[test]
+    fn enter_on_file_selects_it() {
+        let dir = fixture();
+        let mut picker = FilePicker::open_at(dir.path().to_path_buf()).unwrap();
+        picker.handle(&key(KeyCode::End)).unwrap();
+        match picker.handle(&key(KeyCode::Enter)).unwrap() {
+            FilePickerResult::Selected(p) => assert_eq!(p, dir.path().join("b_big.bin")),
+            _ => panic!("expected Selected"),
+        }
+        assert!(matches!(picker.handle(&key(KeyCode::Esc)).unwrap(), FilePickerResult::Cancelled));
+    }
+
+    #[test]
+    fn typing_filters_entries() {
+        let dir = fixture();
+        let mut picker = FilePicker::open_at(dir.path().to_path_buf()).unwrap();
+        type_str(&mut picker, "BIG");
+        assert_eq!(names(&picker), ["b_big.bin"]);
+        // Enter picks the only match straight away.
+        assert!(matches!(
+            picker.handle(&key(KeyCode::Enter)).unwrap(),
+            FilePickerResult::Selected(_)
+        ));
+        // Backspace edits the filter; Esc clears it before cancelling.
+        picker.handle(&key(KeyCode::Backspace)).unwrap();
+        assert_eq!(picker.filter, "BI");
+        assert!(matches!(picker.handle(&key(KeyCode::Esc)).unwrap(), FilePickerResult::Browsing));
+        assert_eq!(names(&picker).len(), 4);
+    }
+
+    #[test]
+    fn dot_filter_reveals_hidden_files() {
+        let dir = fixture();
+        let mut picker = FilePicker::open_at(dir.path().to_path_buf()).unwrap();
+        type_str(&mut picker, ".hid");
+        assert_eq!(names(&picker), [".hidden"]);
+    }
+
+    #[test]
+    fn path_box_navigates_and_selects() {
+        let dir = fixture();
+        let mut picker = FilePicker::open_at(std::env::temp_dir()).unwrap();
+        picker.handle(&ctrl('l')).unwrap();
+        picker.handle(&Event::Paste(dir.path().join("sub").display().to_string())).unwrap();
+        picker.handle(&key(KeyCode::Enter)).unwrap();
+        assert_eq!(picker.cwd(), dir.path().join("sub"));
+        assert!(picker.path_input.is_none());
+
+        // Relative paths resolve against the listed directory.
+        picker.handle(&ctrl('l')).unwrap();
+        type_str(&mut picker, "../a_small.txt");
+        match picker.handle(&key(KeyCode::Enter)).unwrap() {
+            FilePickerResult::Selected(p) => assert!(p.ends_with("a_small.txt")),
+            _ => panic!("expected Selected"),
+        }
+    }
+
+    #[test]
+    fn path_box_reports_missing_path() {
+        let dir = fixture();
+        let mut picker = FilePicker::open_at(dir.path().to_path_buf()).unwrap();
+        picker.handle(&ctrl('l')).unwrap();
+        type_str(&mut picker, "nope");
+        picker.handle(&key(KeyCode::Enter)).unwrap();
+        assert!(picker.error.as_deref().unwrap().starts_with("not found"));
+        assert_eq!(picker.cwd(), dir.path());
+        // Esc closes the path box, not the picker.
+        picker.handle(&key(KeyCode::Esc)).unwrap();
+        assert!(picker.path_input.is_none());
+    }
+
+    #[test]
+    fn pasted_absolute_path_opens_path_box() {
+        let dir = fixture();
+        let mut picker = FilePicker::open_at(dir.path().to_path_buf()).unwrap();
+        picker.handle(&Event::Paste("/tmp".into())).unwrap();
+        assert_eq!(picker.path_input.as_deref(), Some("/tmp"));
+    }
+
+    #[test]
+    fn bookmark_jumps() {
+        let dir = fixture();
+        let mut picker = FilePicker::open_at(std::env::temp_dir()).unwrap();
+        picker.bookmarks = vec![("Fixture", dir.path().join("sub"))];
+        let alt1 = Event::Key(KeyEvent::new(KeyCode::Char('1'), KeyModifiers::ALT));
+        picker.handle(&alt1).unwrap();
+        assert_eq!(picker.cwd(), dir.path().join("sub"));
+    }
+
+    #[test]
+    fn age_formatting() {
+        let now = SystemTime::now();
+        assert_eq!(format_age(now, now - Duration::from_secs(5)), "5s");
+        assert_eq!(format_age(now, now - Duration::from_secs(300)), "5m");
+        assert_eq!(format_age(now, now - Duration::from_secs(7_200)), "2h");
+        assert_eq!(format_age(now, now - Duration::from_secs(3 * 86_400)), "3d");
+        assert_eq!(format_age(now, now + Duration::from_secs(60)), "now");
     }
 }
@@ -0,0 +1,312 @@
+//! Message filters: pluggable checks that can rewrite or drop chat text.
+//!
+//! A `MessageFilter` sees every chat message twice over its lifetime in a
+//! session: `inbound` for text arriving from peers, `outbound` for text we're
+//! about to send. Each returns a `Verdict` — keep the text, replace it, or
+//! drop the message entirely. `Filters` runs a chain of them in registration
+//! order; the first `Drop` wins and later filters never see the message.
+//!
+//! Three filters are built in and enabled from the `[filters]` config table:
+//!
+//! - `ProfanityMask` — stars out a small list of swear words
+//! - `Blocklist`     — hides messages from listed nicknames
+//! - `RateLimit`     — drops a peer's messages past N per minute
+//!
+//! Anything else can be added with `Filters::register` on `App::filters`.
+//! Filters only see chat text: joins, file offers and protocol messages pass
+//! straight through, and a dropped inbound message is still acked (we did
+//! receive it, we just don't show it). They apply to live messages only;
+//! history synced from a peer on join is shown as that peer kept it.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use serde::{Deserialize, Serialize};
+
+/// The window `RateLimit` counts messages over.
+const RATE_WINDOW_MS: u64 = 60_000;
+
+/// Words `ProfanityMask` stars out, matched whole-word and case-insensitively.
+const PROFANITY: &[&str] = &[
+    "arse", "arsehole", "ass", "asshole", "bastard", "bitch", "bollocks", "bullshit", "crap",
+    "damn", "dick", "fuck", "fucking", "piss", "shit", "wanker",
+];
+
+/// What a filter decided about a message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    /// Pass the text on unchanged.
+    Keep,
+    /// Pass this text on instead.
+    Replace(String),
+    /// Don't show (inbound) or send (outbound) the message at all.
+    Drop,
+}
+
+/// A check run on chat text. Both directions default to `Keep`, so a filter
+/// only implements the side it cares about.
+///
+/// `Send` lets the filter chain live in `App`, which the event loop owns
+/// across `.await` points.
+pub trait MessageFilter: Send {
+    /// Short name used in status messages, e.g. "rate limit".
+    fn name(&self) -> &str;
+
+    /// Look at a message from `nickname` that arrived at `now_ms`.
+    fn inbound(&mut self, _nickname: &str, _text: &str, _now_ms: u64) -> Verdict {
+        Verdict::Keep
+    }
+
+    /// Look at a message we're about to send.
+    fn outbound(&mut self, _text: &str) -> Verdict {
+        Verdict::Keep
+    }
+}
+
+/// Settings for the built-in filters (the `[filters]` config table).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FilterConfig {
+    /// Star out profanity in both directions.
+    pub profanity: bool,
+    /// Nicknames whose messages are hidden.
+    pub blocklist: Vec<String>,
+    /// Most messages shown per peer per minute; the rest are dropped.
+    pub rate_limit: Option<usize>,
+}
+
+/// An ordered chain of filters.
+#[derive(Default)]
+pub struct Filters {
+    filters: Vec<Box<dyn MessageFilter>>,
+}
+
+impl Filters {
+    /// The built-in filters `config` turns on.
+    pub fn from_config(config: &FilterConfig) -> Self {
+        let mut filters = Self::default();
+        if !config.blocklist.is_empty() {
+            filters.register(Box::new(Blocklist::new(&config.blocklist)));
+        }
+        if let Some(limit) = config.rate_limit {
+            filters.register(Box::new(RateLimit::new(limit)));
+        }
+        if config.profanity {
+            filters.register(Box::new(ProfanityMask));
+        }
+        filters
+    }
+
+    /// Add `filter` to the end of the chain.
+    pub fn register(&mut self, filter: Box<dyn MessageFilter>) {
+        self.filters.push(filter);
+    }
+
+    /// Whether no filters are registered.
+    pub fn is_empty(&self) -> bool {
+        self.filters.is_empty()
+    }
+
+    /// Run an incoming message through the chain: the text to show, or
+    /// `None` if a filter dropped it.
+    pub fn inbound(&mut self, nickname: &str, text: String, now_ms: u64) -> Option<String> {
+        self.run(text, |f, t| f.inbound(nickname, t, now_ms)).ok()
+    }
+
+    /// Run an outgoing message through the chain: the text to send, or the
+    /// name of the filter that dropped it.
+    pub fn outbound(&mut self, text: String) -> Result<String, String> {
+        self.run(text, |f, t| f.outbound(t))
+    }
+
+    /// Apply `check` to each filter in turn, threading replacements through.
+    fn run(
+        &mut self,
+        mut text: String,
+        mut check: impl FnMut(&mut dyn MessageFilter, &str) -> Verdict,
+    ) -> Result<String, String> {
+        for filter in &mut self.filters {
+            match check(filter.as_mut(), &text) {
+                Verdict::Keep => {}
+                Verdict::Replace(new) => text = new,
+                Verdict::Drop => return Err(filter.name().to_string()),
+            }
+        }
+        Ok(text)
+    }
+}
+
+// ── Built-in filters ─────────────────────────────────────────────────────────
+
+/// Replaces listed swear words with asterisks, in both directions.
+pub struct ProfanityMask;
+
+impl ProfanityMask {
+    /// `text` with each profane word starred out, or `None` if it's clean.
+    fn mask(text: &str) -> Option<String> {
+        let mut out = String::with_capacity(text.len());
+        let mut changed = false;
+        // Walk alternating runs of word and non-word characters, so
+        // punctuation and spacing come through untouched.
+        let mut rest = text;
+        while let Some(first) = rest.chars().next() {
+            let is_word = first.is_alphanumeric();
+            let end = rest
+                .find(|c: char| c.is_alphanumeric() != is_word)
+                .unwrap_or(rest.len());
+            let (run, tail) = rest.split_at(end);
+            if is_word && PROFANITY.contains(&run.to_lowercase().as_str()) {
+                out.extend(std::iter::repeat_n('*', run.chars().count()));
+                changed = true;
+            } else {
+                out.push_str(run);
+            }
+            rest = tail;
+        }
+        changed.then_some(out)
+    }
+}
+
+impl MessageFilter for ProfanityMask {
+    fn name(&self) -> &str {
+        "profanity"
+    }
+
+    fn inbound(&mut self, _nickname: &str, text: &str, _now_ms: u64) -> Verdict {
+        Self::mask(text).map_or(Verdict::Keep, Verdict::Replace)
+    }
+
+    fn outbound(&mut self, text: &str) -> Verdict {
+        Self::mask(text).map_or(Verdict::Keep, Verdict::Replace)
+    }
+}
+
+/// Drops messages from blocked nicknames (case-insensitive).
+pub struct Blocklist {
+    blocked: HashSet<String>,
+}
+
+impl Blocklist {
+    pub fn new(nicknames: &[String]) -> Self {
+        Self {
+            blocked: nicknames.iter().map(|n| n.to_lowercase()).collect(),
+        }
+    }
+}
+
+impl MessageFilter for Blocklist {
+    fn name(&self) -> &str {
+        "blocklist"
+    }
+
+    fn inbound(&mut self, nickname: &str, _text: &str, _now_ms: u64) -> Verdict {
+        if self.blocked.contains(&nickname.to_lowercase()) {
+            Verdict::Drop
+        } else {
+            Verdict::Keep
+        }
+    }
+}
+
+/// Drops a peer's messages once they've sent `limit` within the last minute.
+///
+/// Each nickname keeps a queue of its recent arrival times; entries older
+/// than the window fall off the front before each check.
+pub struct RateLimit {
+    limit: usize,
+    recent: HashMap<String, VecDeque<u64>>,
+}
+
+impl RateLimit {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            recent: HashMap::new(),
+        }
+    }
+}
+
+impl MessageFilter for RateLimit {
+    fn name(&self) -> &str {
+        "rate limit"
+    }
+
+    fn inbound(&mut self, nickname: &str, _text: &str, now_ms: u64) -> Verdict {
+        let times = self.recent.entry(nickname.to_string()).or_default();
+        while times
+            .front()
+            .is_some_and(|&t| now_ms.saturating_sub(t) >= RATE_WINDOW_MS)
+        {
+            times.pop_front();
+        }
+        if times.len() >= self.limit {
+            return Verdict::Drop;
+        }
+        times.push_back(now_ms);
+        Verdict::Keep
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profanity_is_masked_whole_word() {
+        let mut filters = Filters::default();
+        filters.register(Box::new(ProfanityMask));
+        assert_eq!(
+            filters.outbound("Well, DAMN. Classic assessment!".into()),
+            Ok("Well, ****. Classic assessment!".into())
+        );
+        assert_eq!(
+            filters.inbound("Bob", "oh shit".into(), 0),
+            Some("oh ****".into())
+        );
+    }
+
+    #[test]
+    fn blocklist_and_rate_limit_drop() {
+        let config = FilterConfig {
+            blocklist: vec!["Mallory".into()],
+            rate_limit: Some(2),
+            ..Default::default()
+        };
+        let mut filters = Filters::from_config(&config);
+        assert_eq!(filters.inbound("mallory", "hi".into(), 0), None);
+        assert_eq!(filters.inbound("Bob", "1".into(), 0), Some("1".into()));
+        assert_eq!(filters.inbound("Bob", "2".into(), 10), Some("2".into()));
+        assert_eq!(filters.inbound("Bob", "3".into(), 20), None);
+        // Carol has her own budget, and Bob's refills after a minute.
+        assert_eq!(filters.inbound("Carol", "a".into(), 30), Some("a".into()));
+        assert_eq!(
+            filters.inbound("Bob", "4".into(), RATE_WINDOW_MS + 5),
+            Some("4".into())
+        );
+        // Nothing in the config touches outgoing text.
+        assert_eq!(filters.outbound("damn".into()), Ok("damn".into()));
+    }
+
+    #[test]
+    fn custom_filter_can_rewrite_and_drop() {
+        struct Shout;
+        impl MessageFilter for Shout {
+            fn name(&self) -> &str {
+                "shout"
+            }
+            fn outbound(&mut self, text: &str) -> Verdict {
+                if text.is_empty() {
+                    Verdict::Drop
+                } else {
+                    Verdict::Replace(text.to_uppercase())
+                }
+            }
+        }
+        let mut filters = Filters::default();
+        filters.register(Box::new(Shout));
+        filters.register(Box::new(ProfanityMask));
+        assert_eq!(filters.outbound("hell yes, damn".into()), Ok("HELL YES, ****".into()));
+        assert_eq!(filters.outbound(String::new()), Err("shout".into()));
+    }
+}
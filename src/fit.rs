@@ -0,0 +1,103 @@
+//! Clamp text to a number of terminal columns.
+//!
+//! Nicknames and filenames come from peers and can be long — 32 wide CJK
+//! characters are 64 columns — so wherever one shares a row with something
+//! that must stay visible (a state tag, a progress bar, the message text),
+//! it's cut to fit first. Widths are measured in display columns with
+//! `unicode-width`, not bytes or chars.
+//!
+//! - `end()` keeps the start: `a-very-long-na…`. Right for directory
+//!   listings, where the start is what you scan.
+//! - `middle()` keeps both ends: `holiday-ph…-2024.jpg`. Right for names
+//!   that differ at the end, like numbered files and extensions.
+
+use std::borrow::Cow;
+
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+fn char_width(c: char) -> usize {
+    c.width().unwrap_or(0)
+}
+
+/// `s` cut to at most `width` columns, ending in `ellipsis` (`…`, or
+/// `...` in ASCII mode) if cut.
+pub fn end<'a>(s: &'a str, width: usize, ellipsis: &str) -> Cow<'a, str> {
+    if s.width() <= width {
+        return Cow::Borrowed(s);
+    }
+    let budget = width.saturating_sub(ellipsis.width());
+    let mut out = String::new();
+    let mut used = 0;
+    for c in s.chars() {
+        if used + char_width(c) > budget {
+            break;
+        }
+        used += char_width(c);
+        out.push(c);
+    }
+    out.push_str(ellipsis);
+    Cow::Owned(out)
+}
+
+/// `s` cut to at most `width` columns by replacing its middle with
+/// `ellipsis`. The start gets the extra column when the room left is odd.
+pub fn middle<'a>(s: &'a str, width: usize, ellipsis: &str) -> Cow<'a, str> {
+    if s.width() <= width {
+        return Cow::Borrowed(s);
+    }
+    let budget = width.saturating_sub(ellipsis.width());
+    let (head_budget, tail_budget) = (budget.div_ceil(2), budget / 2);
+
+    let mut head = String::new();
+    let mut used = 0;
+    for c in s.chars() {
+        if used + char_width(c) > head_budget {
+            break;
+        }
+        used += char_width(c);
+        head.push(c);
+    }
+    let mut tail = Vec::new();
+    let mut used = 0;
+    for c in s.chars().rev() {
+        if used + char_width(c) > tail_budget {
+            break;
+        }
+        used += char_width(c);
+        tail.push(c);
+    }
+    head.push_str(ellipsis);
+    head.extend(tail.into_iter().rev());
+    Cow::Owned(head)
+}
+
+// ── Tests ────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_text_is_borrowed() {
+        assert!(matches!(middle("a.txt", 5, "…"), Cow::Borrowed("a.txt")));
+        assert!(matches!(end("a.txt", 10, "…"), Cow::Borrowed("a.txt")));
+    }
+
+    #[test]
+    fn cuts_keep_to_the_width() {
+        assert_eq!(end("a_long_file_name.txt", 8, "…"), "a_long_…");
+        assert_eq!(end("a_long_file_name.txt", 8, "..."), "a_lon...");
+        assert_eq!(middle("holiday-photo-2024.jpg", 12, "…"), "holida…4.jpg");
+        assert_eq!(middle("holiday-photo-2024.jpg", 12, "..."), "holid....jpg");
+        assert_eq!(middle("abc", 0, "…"), "…");
+    }
+
+    #[test]
+    fn wide_characters_count_two_columns() {
+        // Each of these takes two columns; a half-fitting one is left out.
+        let cut = middle("日本語のファイル名.txt", 11, "…");
+        assert_eq!(cut, "日本….txt");
+        assert!(cut.width() <= 11);
+        assert_eq!(end("日本語", 4, "…"), "日…");
+    }
+}
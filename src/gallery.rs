@@ -0,0 +1,542 @@
+//! `/files`: a full-screen gallery of every file offered in the room.
+//!
+//! The files pane lists this session's transfers. The gallery reads the
+//! room's history instead — synced from peers when we join, and kept in
+//! the room archive (see `rooms`) — so it also lists files offered before
+//! we joined or in an earlier session, next to our own shares. A row takes
+//! its state from the `TransferManager` when that has an entry for the
+//! offer; one it doesn't know is *earlier*.
+//!
+//! `s`, `t` and `f` cycle the filters: by sender, by kind of file (from
+//! the extension) and by state. Enter does whatever fits the row: download
+//! an offer, open a download, or re-request a failed or earlier one — a
+//! fetch from the sender that falls back to everyone else in the room,
+//! since any peer holding the same hash holds the same file.
+//!
+//! Like the palette, the gallery doesn't act itself: `Gallery::key` hands
+//! main an `Action`, which main runs through the files pane's code paths.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use crossterm::event::{KeyCode, KeyEvent};
+use iroh::EndpointId;
+use iroh_gossip::proto::TopicId;
+use ratatui::{
+    Frame,
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Paragraph},
+};
+use unicode_width::UnicodeWidthStr;
+
+use crate::chat::{civil_date, format_timestamp};
+use crate::fit;
+use crate::i18n::Lang;
+use crate::net::{HistoryEntry, HistoryEntryKind};
+use crate::seal;
+use crate::t;
+use crate::theme::Theme;
+use crate::transfer::{EntryId, FileOffer, TransferManager, TransferState, format_file_size};
+
+/// Columns for the sender's name in a row.
+const SENDER_COLUMNS: usize = 12;
+
+/// What kind of file an offer is, going by its extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Image,
+    Video,
+    Audio,
+    Document,
+    Archive,
+    Other,
+}
+
+impl Kind {
+    const ALL: [Kind; 6] = [Kind::Image, Kind::Video, Kind::Audio, Kind::Document, Kind::Archive, Kind::Other];
+
+    pub fn of(filename: &str) -> Self {
+        let ext = filename.rsplit_once('.').map(|(_, ext)| ext.to_lowercase()).unwrap_or_default();
+        match ext.as_str() {
+            "png" | "jpg" | "jpeg" | "gif" | "webp" | "svg" | "bmp" | "heic" => Kind::Image,
+            "mp4" | "webm" | "mov" | "mkv" | "avi" => Kind::Video,
+            "mp3" | "wav" | "flac" | "ogg" | "m4a" | "opus" => Kind::Audio,
+            "pdf" | "txt" | "md" | "doc" | "docx" | "odt" | "rtf" | "csv" | "xls" | "xlsx" | "ppt" | "pptx" => {
+                Kind::Document
+            }
+            "zip" | "tar" | "gz" | "tgz" | "7z" | "rar" | "xz" | "bz2" | "zst" => Kind::Archive,
+            _ => Kind::Other,
+        }
+    }
+
+    fn label(self, lang: Lang) -> String {
+        match self {
+            Kind::Image => t!(lang, "gallery.kind.image"),
+            Kind::Video => t!(lang, "gallery.kind.video"),
+            Kind::Audio => t!(lang, "gallery.kind.audio"),
+            Kind::Document => t!(lang, "gallery.kind.document"),
+            Kind::Archive => t!(lang, "gallery.kind.archive"),
+            Kind::Other => t!(lang, "gallery.kind.other"),
+        }
+    }
+}
+
+/// Where a file stands for us: the files pane's state, folded down to
+/// what's worth filtering by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    /// Offered to us; not downloaded yet.
+    Offered,
+    Downloading,
+    Downloaded,
+    Failed,
+    /// Ours, and still offered.
+    Shared,
+    /// Ours, withdrawn because the file changed on disk.
+    Withdrawn,
+    /// Offered before this session: in the history, not the files pane.
+    Earlier,
+}
+
+impl Status {
+    const ALL: [Status; 7] = [
+        Status::Offered,
+        Status::Downloading,
+        Status::Downloaded,
+        Status::Failed,
+        Status::Shared,
+        Status::Withdrawn,
+        Status::Earlier,
+    ];
+
+    fn of(state: &TransferState) -> Self {
+        match state {
+            TransferState::Pending => Status::Offered,
+            TransferState::Downloading { .. } => Status::Downloading,
+            TransferState::Complete(_) => Status::Downloaded,
+            TransferState::Failed(_) => Status::Failed,
+            TransferState::Sharing | TransferState::Packing { .. } => Status::Shared,
+            TransferState::Invalidated(_) => Status::Withdrawn,
+        }
+    }
+
+    fn label(self, lang: Lang) -> String {
+        match self {
+            Status::Offered => t!(lang, "gallery.state.offered"),
+            Status::Downloading => t!(lang, "gallery.state.downloading"),
+            Status::Downloaded => t!(lang, "gallery.state.downloaded"),
+            Status::Failed => t!(lang, "gallery.state.failed"),
+            Status::Shared => t!(lang, "gallery.state.shared"),
+            Status::Withdrawn => t!(lang, "gallery.state.withdrawn"),
+            Status::Earlier => t!(lang, "gallery.state.earlier"),
+        }
+    }
+}
+
+/// One file in the gallery.
+#[derive(Debug, Clone)]
+pub struct Item {
+    pub offer: FileOffer,
+    /// When it was offered (unix ms).
+    pub timestamp_ms: u64,
+    pub kind: Kind,
+    pub status: Status,
+    /// Where the download was saved, once it's `Downloaded`.
+    pub path: Option<PathBuf>,
+    /// Why the download failed, if it's `Failed`.
+    pub error: Option<String>,
+    /// We offered it.
+    pub ours: bool,
+}
+
+/// Every file offered in the room, newest first: the offers in `history`
+/// (minus those targeted at someone other than `nickname`), with state
+/// from `transfers`, then the files pane's entries the history doesn't
+/// have — our own shares. Sealed offers get their key from the history's
+/// `FileKey` entries, unwrapped for `room`.
+pub fn items(
+    history: &[HistoryEntry],
+    transfers: &TransferManager,
+    nickname: &str,
+    our_id: EndpointId,
+    room: Option<TopicId>,
+) -> Vec<Item> {
+    let item = |offer: FileOffer, timestamp_ms: u64| {
+        let entry = transfers.entry(&offer.entry_id());
+        let status = entry.map_or(Status::Earlier, |e| Status::of(&e.state));
+        let path = entry.and_then(|e| match &e.state {
+            TransferState::Complete(path) => Some(path.clone()),
+            _ => None,
+        });
+        let error = entry.and_then(|e| match &e.state {
+            TransferState::Failed(error) => Some(error.clone()),
+            _ => None,
+        });
+        let ours = offer.sender_id == our_id;
+        Item { kind: Kind::of(&offer.filename), offer, timestamp_ms, status, path, error, ours }
+    };
+
+    let mut keys = HashMap::new();
+    let mut items = Vec::new();
+    for entry in history {
+        match &entry.kind {
+            HistoryEntryKind::FileKey { hash, wrapped_key } => {
+                if let Some(key) = room.and_then(|room| seal::unwrap(&room, wrapped_key)) {
+                    keys.insert(*hash, key);
+                }
+            }
+            HistoryEntryKind::FileOffer { nickname: name, endpoint_id, filename, size, hash, target, .. } => {
+                if target.as_ref().is_some_and(|t| t != nickname) {
+                    continue;
+                }
+                let offer = FileOffer {
+                    sender_nickname: name.clone(),
+                    sender_id: *endpoint_id,
+                    filename: filename.clone(),
+                    size: *size,
+                    hash: iroh_blobs::Hash::from_bytes(*hash),
+                    key: keys.get(hash).copied(),
+                    offer_id: entry.message_id,
+                };
+                items.push(item(offer, entry.timestamp_ms));
+            }
+            _ => {}
+        }
+    }
+    for entry in &transfers.entries {
+        let id = entry.offer.entry_id();
+        if !items.iter().any(|i| i.offer.entry_id() == id) {
+            let offered_ms = entry.timeline.first().map_or(0, |(ms, _)| *ms);
+            items.push(item(entry.offer.clone(), offered_ms));
+        }
+    }
+    items.sort_by_key(|i| std::cmp::Reverse(i.timestamp_ms));
+    items
+}
+
+/// What the gallery asks main to do.
+#[derive(Debug, Clone)]
+pub enum Action {
+    /// Download this offer, as Enter in the files pane would.
+    Download(EntryId),
+    /// Open this finished download.
+    Open(PathBuf),
+    /// Fetch it again: from the sender, then anyone else in the room.
+    /// `error` is why the last try failed, if there was one this session.
+    Rerequest { offer: FileOffer, error: Option<String> },
+}
+
+impl Item {
+    /// What Enter does on this row, if anything.
+    pub fn action(&self) -> Option<Action> {
+        match self.status {
+            Status::Offered => Some(Action::Download(self.offer.entry_id())),
+            Status::Downloaded => self.path.clone().map(Action::Open),
+            Status::Failed | Status::Earlier if !self.ours => {
+                Some(Action::Rerequest { offer: self.offer.clone(), error: self.error.clone() })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// What a key did to the gallery.
+#[derive(Debug)]
+pub enum Outcome {
+    /// Still open.
+    Open,
+    Close,
+    Act(Action),
+}
+
+/// The open gallery: which row is selected, and the filters.
+#[derive(Debug, Default)]
+pub struct Gallery {
+    /// Index into `visible()`.
+    pub selected: usize,
+    pub sender: Option<EndpointId>,
+    pub kind: Option<Kind>,
+    pub status: Option<Status>,
+}
+
+impl Gallery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The items that pass the filters.
+    pub fn visible<'a>(&self, items: &'a [Item]) -> Vec<&'a Item> {
+        items
+            .iter()
+            .filter(|i| self.sender.is_none_or(|s| i.offer.sender_id == s))
+            .filter(|i| self.kind.is_none_or(|k| i.kind == k))
+            .filter(|i| self.status.is_none_or(|s| i.status == s))
+            .collect()
+    }
+
+    /// Handle a key while the gallery is open. `items` is what it shows.
+    pub fn key(&mut self, key: &KeyEvent, items: &[Item]) -> Outcome {
+        let visible = self.visible(items);
+        let selected = visible.get(self.selected).copied();
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => return Outcome::Close,
+            KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down => self.selected = (self.selected + 1).min(visible.len().saturating_sub(1)),
+            KeyCode::Home => self.selected = 0,
+            KeyCode::End => self.selected = visible.len().saturating_sub(1),
+            KeyCode::Char('s') => {
+                let mut senders: Vec<EndpointId> = Vec::new();
+                for item in items {
+                    if !senders.contains(&item.offer.sender_id) {
+                        senders.push(item.offer.sender_id);
+                    }
+                }
+                self.sender = next(&senders, self.sender);
+                self.selected = 0;
+            }
+            KeyCode::Char('t') => {
+                self.kind = next(&Kind::ALL, self.kind);
+                self.selected = 0;
+            }
+            KeyCode::Char('f') => {
+                self.status = next(&Status::ALL, self.status);
+                self.selected = 0;
+            }
+            KeyCode::Enter => {
+                if let Some(action) = selected.and_then(Item::action) {
+                    return Outcome::Act(action);
+                }
+            }
+            // The explicit keys only do their own action.
+            KeyCode::Char(c @ ('d' | 'o' | 'r')) => {
+                let action = selected.and_then(Item::action).filter(|action| {
+                    matches!(
+                        (c, action),
+                        ('d', Action::Download(_)) | ('o', Action::Open(_)) | ('r', Action::Rerequest { .. })
+                    )
+                });
+                if let Some(action) = action {
+                    return Outcome::Act(action);
+                }
+            }
+            _ => {}
+        }
+        Outcome::Open
+    }
+
+    /// Draw the gallery over the whole frame.
+    pub fn render(&self, f: &mut Frame, items: &[Item], lang: Lang, theme: &Theme) {
+        let area = f.area();
+        f.render_widget(Clear, area);
+        let hint = |key: &'static str, label: String| {
+            [
+                Span::styled(key, Style::default().fg(theme.hint_key)),
+                Span::styled(format!(" {label}  "), Style::default().fg(theme.hint_text)),
+            ]
+        };
+        let mut hints = vec![Span::raw(" ")];
+        hints.extend(hint("Enter", t!(lang, "gallery.act")));
+        hints.extend(hint("s", t!(lang, "gallery.sender")));
+        hints.extend(hint("t", t!(lang, "gallery.type")));
+        hints.extend(hint("f", t!(lang, "gallery.state")));
+        hints.extend(hint("Esc", t!(lang, "gallery.close")));
+        let title = t!(lang, "gallery.title", count = items.len());
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .border_set(theme.border_set())
+            .style(Style::default().bg(theme.bg))
+            .border_style(Style::default().fg(theme.border_focused))
+            .title(Line::styled(format!(" {title} "), Style::default().fg(theme.title).add_modifier(Modifier::BOLD)).centered())
+            .title_bottom(Line::from(hints));
+        let inner = block.inner(area);
+        f.render_widget(block, area);
+
+        let all = t!(lang, "gallery.all");
+        let sender = self
+            .sender
+            .map(|id| items.iter().find(|i| i.offer.sender_id == id).map_or_else(|| id.fmt_short().to_string(), |i| i.offer.sender_nickname.clone()))
+            .unwrap_or_else(|| all.clone());
+        let kind = self.kind.map_or_else(|| all.clone(), |k| k.label(lang));
+        let state = self.status.map_or_else(|| all.clone(), |s| s.label(lang));
+        let mut lines = vec![
+            Line::styled(
+                format!(" {}", t!(lang, "gallery.filters", sender = sender, kind = kind, state = state)),
+                Style::default().fg(theme.text_muted),
+            ),
+            Line::from(""),
+        ];
+
+        let visible = self.visible(items);
+        if visible.is_empty() {
+            let empty = if items.is_empty() { t!(lang, "gallery.empty") } else { t!(lang, "gallery.no_match") };
+            lines.push(Line::styled(format!(" {empty}"), Style::default().fg(theme.text_muted)));
+        }
+        // Keep the selection in view.
+        let rows = (inner.height as usize).saturating_sub(lines.len()).max(1);
+        let first = self.selected.saturating_sub(rows - 1);
+        let ellipsis = theme.glyph("…", "...");
+        let status_width = Status::ALL.iter().map(|s| s.label(lang).width()).max().unwrap_or(0);
+        for (i, item) in visible.iter().enumerate().skip(first).take(rows) {
+            let (y, m, d) = civil_date(item.timestamp_ms);
+            let when = format!("{y}-{m:02}-{d:02} {}", format_timestamp(item.timestamp_ms));
+            let who = pad(&fit::end(&item.offer.sender_nickname, SENDER_COLUMNS, ellipsis), SENDER_COLUMNS);
+            let size = format!("{:>9}", format_file_size(item.offer.size));
+            let status = pad(&item.status.label(lang), status_width);
+            // The filename gets whatever the rest of the row leaves.
+            let rest = 1 + when.width() + 2 + SENDER_COLUMNS + 2 + 2 + size.width() + 2 + status_width + 1;
+            let room = (inner.width as usize).saturating_sub(rest).max(8);
+            let name = pad(&fit::middle(&item.offer.filename, room, ellipsis), room);
+            let status_color = match item.status {
+                Status::Offered => theme.transfer_pending,
+                Status::Downloading | Status::Downloaded => theme.transfer_complete,
+                Status::Failed | Status::Withdrawn => theme.transfer_failed,
+                Status::Shared => theme.transfer_sharing,
+                Status::Earlier => theme.text_muted,
+            };
+            let row = if i == self.selected {
+                let selected = Style::default().fg(theme.accent_on_bg).bg(theme.accent_bg);
+                Line::from(Span::styled(format!(" {when}  {who}  {name}  {size}  {status} "), selected))
+            } else {
+                Line::from(vec![
+                    Span::styled(format!(" {when}  "), Style::default().fg(theme.text_dim)),
+                    Span::styled(format!("{who}  "), Style::default().fg(theme.accent)),
+                    Span::styled(format!("{name}  "), Style::default().fg(theme.text)),
+                    Span::styled(format!("{size}  "), Style::default().fg(theme.text_muted)),
+                    Span::styled(format!("{status} "), Style::default().fg(status_color)),
+                ])
+            };
+            lines.push(row);
+        }
+        f.render_widget(Paragraph::new(lines), inner);
+    }
+}
+
+/// The option after `current` in `options`, cycling through `None` (all)
+/// after the last.
+fn next<T: Copy + PartialEq>(options: &[T], current: Option<T>) -> Option<T> {
+    match current.and_then(|c| options.iter().position(|o| *o == c)) {
+        None => options.first().copied(),
+        Some(i) => options.get(i + 1).copied(),
+    }
+}
+
+/// `s` padded with spaces to `width` columns.
+fn pad(s: &str, width: usize) -> String {
+    format!("{s}{}", " ".repeat(width.saturating_sub(s.width())))
+}
+
+// ── Tests ────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::event::KeyModifiers;
+
+    fn peer(b: u8) -> EndpointId {
+        iroh::SecretKey::from_bytes(&[b; 32]).public()
+    }
+
+    fn offer_entry(id: u8, name: &str, from: u8, filename: &str, timestamp_ms: u64) -> HistoryEntry {
+        HistoryEntry {
+            message_id: [id; 16],
+            timestamp_ms,
+            kind: HistoryEntryKind::FileOffer {
+                nickname: name.into(),
+                endpoint_id: peer(from),
+                filename: filename.into(),
+                size: 2048,
+                hash: [id; 32],
+                mime_type: None,
+                target: None,
+            },
+        }
+    }
+
+    fn press(gallery: &mut Gallery, items: &[Item], code: KeyCode) -> Outcome {
+        gallery.key(&KeyEvent::new(code, KeyModifiers::NONE), items)
+    }
+
+    #[test]
+    fn history_and_this_session_make_one_list() {
+        let history = vec![
+            offer_entry(1, "Bob", 2, "old-build.zip", 1_000),
+            offer_entry(2, "Carol", 3, "photo.jpg", 2_000),
+            HistoryEntry {
+                kind: HistoryEntryKind::FileOffer {
+                    nickname: "Bob".into(),
+                    endpoint_id: peer(2),
+                    filename: "for-dave.txt".into(),
+                    size: 1,
+                    hash: [9; 32],
+                    mime_type: None,
+                    target: Some("Dave".into()),
+                },
+                ..offer_entry(3, "Bob", 2, "", 3_000)
+            },
+        ];
+        let mut transfers = TransferManager::new();
+        // This session: Carol's photo is being downloaded, and we share a file.
+        let photo = items(&history, &transfers, "Alice", peer(1), None)[0].offer.clone();
+        transfers.add_offer(photo.clone());
+        transfers.start_download(&photo.entry_id());
+        transfers.add_sent(FileOffer {
+            sender_nickname: "You".into(),
+            sender_id: peer(1),
+            filename: "notes.md".into(),
+            size: 10,
+            hash: iroh_blobs::Hash::from_bytes([4; 32]),
+            key: None,
+            offer_id: [4; 16],
+        });
+
+        let items = items(&history, &transfers, "Alice", peer(1), None);
+        let rows: Vec<(&str, Status)> = items.iter().map(|i| (i.offer.filename.as_str(), i.status)).collect();
+        // Newest first; the offer targeted at Dave isn't ours to see.
+        assert_eq!(rows, [("notes.md", Status::Shared), ("photo.jpg", Status::Downloading), ("old-build.zip", Status::Earlier)]);
+        assert_eq!(items[2].kind, Kind::Archive);
+        assert!(items[0].ours);
+        assert!(items[0].action().is_none());
+        assert!(matches!(items[2].action(), Some(Action::Rerequest { error: None, .. })));
+    }
+
+    #[test]
+    fn filters_cycle_and_keys_act() {
+        let history = vec![
+            offer_entry(1, "Bob", 2, "a.png", 1_000),
+            offer_entry(2, "Carol", 3, "b.pdf", 2_000),
+            offer_entry(3, "Bob", 2, "c.mp3", 3_000),
+        ];
+        let mut transfers = TransferManager::new();
+        let all = items(&history, &transfers, "Alice", peer(1), None);
+        transfers.add_offer(all[0].offer.clone());
+        let all = items(&history, &transfers, "Alice", peer(1), None);
+
+        let mut gallery = Gallery::new();
+        assert_eq!(gallery.visible(&all).len(), 3);
+        // Senders in list order: Bob (c.mp3 is newest), then Carol, then all again.
+        press(&mut gallery, &all, KeyCode::Char('s'));
+        assert_eq!(gallery.visible(&all).iter().map(|i| i.offer.filename.as_str()).collect::<Vec<_>>(), ["c.mp3", "a.png"]);
+        press(&mut gallery, &all, KeyCode::Char('s'));
+        assert_eq!(gallery.visible(&all).len(), 1);
+        press(&mut gallery, &all, KeyCode::Char('s'));
+        assert_eq!(gallery.sender, None);
+
+        press(&mut gallery, &all, KeyCode::Char('t'));
+        assert_eq!(gallery.kind, Some(Kind::Image));
+        assert_eq!(gallery.visible(&all)[0].offer.filename, "a.png");
+        press(&mut gallery, &all, KeyCode::Char('t'));
+        assert!(gallery.visible(&all).is_empty());
+        gallery.kind = None;
+
+        press(&mut gallery, &all, KeyCode::Char('f'));
+        assert_eq!(gallery.status, Some(Status::Offered));
+        let id = gallery.visible(&all)[0].offer.entry_id();
+        // `o` and `r` don't download; Enter and `d` do.
+        assert!(matches!(press(&mut gallery, &all, KeyCode::Char('o')), Outcome::Open));
+        assert!(matches!(press(&mut gallery, &all, KeyCode::Char('r')), Outcome::Open));
+        assert!(matches!(press(&mut gallery, &all, KeyCode::Char('d')), Outcome::Act(Action::Download(d)) if d == id));
+        assert!(matches!(press(&mut gallery, &all, KeyCode::Enter), Outcome::Act(Action::Download(d)) if d == id));
+        assert!(matches!(press(&mut gallery, &all, KeyCode::Esc), Outcome::Close));
+    }
+}
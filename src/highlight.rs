@@ -0,0 +1,322 @@
+//! Fenced code blocks and a small built-in syntax highlighter.
+//!
+//! Chat messages may contain Markdown-style fences:
+//!
+//! ````text
+//! look at this:
+//! ```rust
+//! fn main() { println!("hi"); }
+//! ```
+//! ````
+//!
+//! `rows()` splits a message into prose lines, fence lines and code lines,
+//! tokenizing code lines when the fence names a language we know. The
+//! tokenizer is deliberately simple — keywords, strings, comments and numbers
+//! cover most of what makes code readable at a glance, without pulling in a
+//! grammar engine and its bundled syntax definitions. Colors are chosen by
+//! the renderer from the `Theme`, so this module knows nothing about styles.
+
+/// What a piece of a code line is, for choosing its color.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Token {
+    Plain,
+    Keyword,
+    Str,
+    Comment,
+    Number,
+}
+
+/// One line of a message, classified.
+#[derive(Debug, PartialEq)]
+pub enum Row<'a> {
+    /// Text outside any fence.
+    Prose(&'a str),
+    /// An opening fence, with its language tag if it has one.
+    Open(Option<&'a str>),
+    /// A line inside a fence, split into tokens.
+    Code(Vec<(Token, &'a str)>),
+    /// A closing fence.
+    Close,
+}
+
+/// Lexical rules for one language.
+struct Syntax {
+    keywords: &'static [&'static str],
+    /// Markers that comment out the rest of the line.
+    line_comments: &'static [&'static str],
+    /// Start and end of a block comment, which may span lines.
+    block_comment: Option<(&'static str, &'static str)>,
+}
+
+const RUST: Syntax = Syntax {
+    keywords: &[
+        "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum",
+        "extern", "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move",
+        "mut", "pub", "ref", "return", "self", "Self", "static", "struct", "super", "trait",
+        "true", "type", "unsafe", "use", "where", "while",
+    ],
+    line_comments: &["//"],
+    block_comment: Some(("/*", "*/")),
+};
+
+const PYTHON: Syntax = Syntax {
+    keywords: &[
+        "and", "as", "assert", "async", "await", "break", "class", "continue", "def", "del",
+        "elif", "else", "except", "False", "finally", "for", "from", "global", "if", "import",
+        "in", "is", "lambda", "None", "nonlocal", "not", "or", "pass", "raise", "return", "True",
+        "try", "while", "with", "yield",
+    ],
+    line_comments: &["#"],
+    block_comment: None,
+};
+
+const JAVASCRIPT: Syntax = Syntax {
+    keywords: &[
+        "async", "await", "break", "case", "catch", "class", "const", "continue", "default",
+        "delete", "else", "export", "extends", "false", "finally", "for", "from", "function",
+        "if", "import", "in", "instanceof", "interface", "let", "new", "null", "of", "return",
+        "switch", "this", "throw", "true", "try", "type", "typeof", "undefined", "var", "while",
+        "yield",
+    ],
+    line_comments: &["//"],
+    block_comment: Some(("/*", "*/")),
+};
+
+const GO: Syntax = Syntax {
+    keywords: &[
+        "break", "case", "chan", "const", "continue", "default", "defer", "else", "false", "for",
+        "func", "go", "if", "import", "interface", "map", "nil", "package", "range", "return",
+        "select", "struct", "switch", "true", "type", "var",
+    ],
+    line_comments: &["//"],
+    block_comment: Some(("/*", "*/")),
+};
+
+const C: Syntax = Syntax {
+    keywords: &[
+        "auto", "bool", "break", "case", "char", "class", "const", "continue", "default",
+        "delete", "do", "double", "else", "enum", "false", "float", "for", "if", "include",
+        "int", "long", "namespace", "new", "nullptr", "public", "private", "return", "short",
+        "sizeof", "static", "struct", "switch", "template", "true", "typedef", "union",
+        "unsigned", "void", "while",
+    ],
+    line_comments: &["//"],
+    block_comment: Some(("/*", "*/")),
+};
+
+const SHELL: Syntax = Syntax {
+    keywords: &[
+        "case", "do", "done", "echo", "elif", "else", "esac", "export", "fi", "for", "function",
+        "if", "in", "local", "return", "then", "until", "while",
+    ],
+    line_comments: &["#"],
+    block_comment: None,
+};
+
+const DATA: Syntax = Syntax {
+    keywords: &["false", "null", "true"],
+    line_comments: &["#"],
+    block_comment: None,
+};
+
+/// The rules for a fence's language tag, if it's one we know.
+fn syntax_for(tag: &str) -> Option<&'static Syntax> {
+    Some(match tag.to_ascii_lowercase().as_str() {
+        "rust" | "rs" => &RUST,
+        "python" | "py" => &PYTHON,
+        "javascript" | "js" | "typescript" | "ts" | "jsx" | "tsx" => &JAVASCRIPT,
+        "go" => &GO,
+        "c" | "h" | "cpp" | "c++" | "cc" | "hpp" => &C,
+        "sh" | "bash" | "shell" | "zsh" => &SHELL,
+        "json" | "toml" | "yaml" | "yml" => &DATA,
+        _ => return None,
+    })
+}
+
+/// The fence marker at the start of a line.
+const FENCE: &str = "```";
+
+/// Whether `text` contains a fenced code block.
+pub fn has_code(text: &str) -> bool {
+    text.lines().any(|line| line.trim_start().starts_with(FENCE))
+}
+
+/// The contents of every fenced block in `text`, in order. An unclosed fence
+/// runs to the end of the message.
+pub fn code_blocks(text: &str) -> Vec<String> {
+    let mut blocks = Vec::new();
+    let mut current: Option<Vec<&str>> = None;
+    for line in text.lines() {
+        if line.trim_start().starts_with(FENCE) {
+            match current.take() {
+                Some(block) => blocks.push(block.join("\n")),
+                None => current = Some(Vec::new()),
+            }
+        } else if let Some(block) = &mut current {
+            block.push(line);
+        }
+    }
+    blocks.extend(current.map(|block| block.join("\n")));
+    blocks
+}
+
+/// Classify every line of `text`, highlighting code in known languages.
+pub fn rows(text: &str) -> Vec<Row<'_>> {
+    let mut rows = Vec::new();
+    // `Some(syntax)` while inside a fence; the inner `None` means the
+    // language is unknown and lines stay plain.
+    let mut fence: Option<Option<&Syntax>> = None;
+    let mut in_block_comment = false;
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        if let Some(tag) = trimmed.strip_prefix(FENCE) {
+            if fence.take().is_some() {
+                rows.push(Row::Close);
+            } else {
+                let tag = tag.trim();
+                let tag = (!tag.is_empty()).then_some(tag);
+                fence = Some(tag.and_then(syntax_for));
+                in_block_comment = false;
+                rows.push(Row::Open(tag));
+            }
+            continue;
+        }
+        rows.push(match fence {
+            None => Row::Prose(line),
+            Some(None) => Row::Code(vec![(Token::Plain, line)]),
+            Some(Some(syntax)) => Row::Code(tokenize(line, syntax, &mut in_block_comment)),
+        });
+    }
+    rows
+}
+
+/// Split one code line into tokens. `in_block_comment` carries an open
+/// block comment over from the previous line, and out to the next.
+fn tokenize<'a>(line: &'a str, syntax: &Syntax, in_block_comment: &mut bool) -> Vec<(Token, &'a str)> {
+    let mut tokens = Vec::new();
+    let mut plain_start = 0;
+    let mut i = 0;
+    // Flush the plain text before `i`, then push `(kind, line[i..end])`.
+    let emit = |tokens: &mut Vec<(Token, &'a str)>, plain_start: &mut usize, i: usize, end: usize, kind| {
+        if *plain_start < i {
+            tokens.push((Token::Plain, &line[*plain_start..i]));
+        }
+        tokens.push((kind, &line[i..end]));
+        *plain_start = end;
+    };
+
+    while i < line.len() {
+        let rest = &line[i..];
+        if *in_block_comment {
+            let end = syntax
+                .block_comment
+                .and_then(|(_, close)| rest.find(close).map(|at| i + at + close.len()));
+            *in_block_comment = end.is_none();
+            let end = end.unwrap_or(line.len());
+            emit(&mut tokens, &mut plain_start, i, end, Token::Comment);
+            i = end;
+        } else if syntax.line_comments.iter().any(|marker| rest.starts_with(marker)) {
+            emit(&mut tokens, &mut plain_start, i, line.len(), Token::Comment);
+            i = line.len();
+        } else if let Some((open, close)) = syntax.block_comment
+            && rest.starts_with(open)
+        {
+            // Search after the opener so `/*/` doesn't close itself.
+            let after = i + open.len();
+            let end = line[after..].find(close).map(|at| after + at + close.len());
+            *in_block_comment = end.is_none();
+            let end = end.unwrap_or(line.len());
+            emit(&mut tokens, &mut plain_start, i, end, Token::Comment);
+            i = end;
+        } else if let Some(quote @ ('"' | '\'' | '`')) = rest.chars().next() {
+            let end = string_end(line, i, quote);
+            emit(&mut tokens, &mut plain_start, i, end, Token::Str);
+            i = end;
+        } else if rest.starts_with(|c: char| c.is_alphanumeric() || c == '_') {
+            // Numbers may contain `.` (3.14); identifiers stop at it.
+            let number = rest.starts_with(|c: char| c.is_ascii_digit());
+            let len = rest
+                .find(|c: char| !(c.is_alphanumeric() || c == '_' || (number && c == '.')))
+                .unwrap_or(rest.len());
+            let word = &rest[..len];
+            let end = i + len;
+            if number {
+                emit(&mut tokens, &mut plain_start, i, end, Token::Number);
+            } else if syntax.keywords.contains(&word) {
+                emit(&mut tokens, &mut plain_start, i, end, Token::Keyword);
+            }
+            i = end;
+        } else {
+            i += rest.chars().next().map_or(1, char::len_utf8);
+        }
+    }
+    if plain_start < line.len() {
+        tokens.push((Token::Plain, &line[plain_start..]));
+    }
+    tokens
+}
+
+/// Byte index just past the string literal opened by `quote` at `start`,
+/// honouring backslash escapes. Unterminated strings run to end of line.
+fn string_end(line: &str, start: usize, quote: char) -> usize {
+    let mut escaped = false;
+    for (at, c) in line[start + quote.len_utf8()..].char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            c if c == quote => return start + quote.len_utf8() + at + c.len_utf8(),
+            _ => {}
+        }
+    }
+    line.len()
+}
+
+// ── Tests ────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rows_split_prose_fences_and_code() {
+        let text = "look:\n```rust\nlet x = \"hi\"; // greet\n```\nnice";
+        assert_eq!(
+            rows(text),
+            [
+                Row::Prose("look:"),
+                Row::Open(Some("rust")),
+                Row::Code(vec![
+                    (Token::Keyword, "let"),
+                    (Token::Plain, " x = "),
+                    (Token::Str, "\"hi\""),
+                    (Token::Plain, "; "),
+                    (Token::Comment, "// greet"),
+                ]),
+                Row::Close,
+                Row::Prose("nice"),
+            ]
+        );
+        // Unknown languages stay plain; block comments span lines.
+        assert_eq!(rows("```\nlet x")[1], Row::Code(vec![(Token::Plain, "let x")]));
+        let rows = rows("```c\nint n = 42; /* a\nb */ return");
+        assert_eq!(
+            rows[1],
+            Row::Code(vec![
+                (Token::Keyword, "int"),
+                (Token::Plain, " n = "),
+                (Token::Number, "42"),
+                (Token::Plain, "; "),
+                (Token::Comment, "/* a"),
+            ])
+        );
+        assert_eq!(rows[2], Row::Code(vec![(Token::Comment, "b */"), (Token::Plain, " "), (Token::Keyword, "return")]));
+    }
+
+    #[test]
+    fn code_blocks_extracts_fence_contents() {
+        assert!(!has_code("no code here"));
+        let text = "a\n```py\nprint(1)\nprint(2)\n```\nb\n```\nopen";
+        assert!(has_code(text));
+        assert_eq!(code_blocks(text), ["print(1)\nprint(2)", "open"]);
+    }
+}
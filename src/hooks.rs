@@ -0,0 +1,133 @@
+//! Scripting hooks: run a command of your own when something happens.
+//!
+//! Hooks are shell commands set in the `[hooks]` table of the config file:
+//!
+//! ```toml
+//! [hooks]
+//! on_message = "notify-send piper-chat \"$(jq -r '.nickname + \": \" + .text')\""
+//! on_file_complete = "tmux display-message 'download done'"
+//! on_peer_join = "printf '\\a'"
+//! ```
+//!
+//! Each run gets the event as one JSON object on stdin (see `HookEvent`;
+//! the `"event"` field names the hook). Commands run through `sh -c` (`cmd /C`
+//! on Windows) in the background: the chat never waits for them, and a hook
+//! that fails only affects itself.
+
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, ExitStatus, Stdio};
+
+use anyhow::Result;
+use iroh::EndpointId;
+use serde::{Deserialize, Serialize};
+
+/// Commands to run per event. Unset hooks do nothing.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Hooks {
+    /// Run for each chat message from a peer.
+    pub on_message: Option<String>,
+    /// Run when a download finishes.
+    pub on_file_complete: Option<String>,
+    /// Run when a peer announces itself in the room.
+    pub on_peer_join: Option<String>,
+}
+
+/// What a hook is told, serialized as its stdin.
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum HookEvent<'a> {
+    Message {
+        nickname: &'a str,
+        text: &'a str,
+        timestamp_ms: u64,
+    },
+    FileComplete {
+        filename: &'a str,
+        path: &'a Path,
+    },
+    PeerJoin {
+        nickname: &'a str,
+        #[serde(serialize_with = "display")]
+        endpoint_id: EndpointId,
+    },
+}
+
+/// Serialize a value through its `Display` impl (endpoint IDs as hex).
+fn display<S: serde::Serializer>(value: &EndpointId, s: S) -> Result<S::Ok, S::Error> {
+    s.collect_str(value)
+}
+
+impl Hooks {
+    /// Start the hook for `event`, if one is configured, without waiting
+    /// for it. Must be called from within the tokio runtime.
+    pub fn fire(&self, event: HookEvent) {
+        let command = match event {
+            HookEvent::Message { .. } => &self.on_message,
+            HookEvent::FileComplete { .. } => &self.on_file_complete,
+            HookEvent::PeerJoin { .. } => &self.on_peer_join,
+        };
+        let Some(command) = command.clone() else { return };
+        let Ok(input) = serde_json::to_string(&event) else { return };
+        // Spawning and waiting on a process blocks, so it gets a blocking
+        // thread; waiting also reaps the child so no zombies are left.
+        tokio::task::spawn_blocking(move || run(&command, &input));
+    }
+}
+
+/// Run `command` through the shell with `input` on stdin and wait for it.
+pub fn run(command: &str, input: &str) -> Result<ExitStatus> {
+    let mut child = shell(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // A hook that doesn't read its input closes the pipe early; that's fine.
+        let _ = stdin.write_all(input.as_bytes());
+    }
+    Ok(child.wait()?)
+}
+
+/// The platform shell invocation for `command`.
+fn shell(command: &str) -> Command {
+    if cfg!(windows) {
+        let mut c = Command::new("cmd");
+        c.args(["/C", command]);
+        c
+    } else {
+        let mut c = Command::new("sh");
+        c.args(["-c", command]);
+        c
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_serialize_with_their_name() {
+        let event = HookEvent::PeerJoin {
+            nickname: "Bob",
+            endpoint_id: EndpointId::from_bytes(&[1u8; 32]).unwrap(),
+        };
+        let json: serde_json::Value = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["event"], "peer_join");
+        assert_eq!(json["nickname"], "Bob");
+        assert_eq!(json["endpoint_id"], EndpointId::from_bytes(&[1u8; 32]).unwrap().to_string());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn command_gets_event_on_stdin() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("event.json");
+        let status = run(&format!("cat > '{}'", out.display()), "{\"event\":\"message\"}").unwrap();
+        assert!(status.success());
+        assert_eq!(std::fs::read_to_string(&out).unwrap(), "{\"event\":\"message\"}");
+    }
+}
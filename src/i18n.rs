@@ -0,0 +1,754 @@
+//! Translations of the chat screen's user-facing text.
+//!
+//! Every string is looked up by key in a per-language catalog — a plain
+//! slice of `(key, template)` pairs — and placeholders are filled in by
+//! name, so a translation can put them in whatever order its grammar needs:
+//!
+//! ```ignore
+//! app.system(t!(app.config.lang, "peer.joined", name = name));
+//! // en: "Bob joined"     es: "Bob se ha unido"
+//! ```
+//!
+//! English is the reference catalog. A key missing from another language
+//! falls back to English, so a new string can land before it's translated;
+//! the tests check that translations only use keys and placeholders English
+//! has. The language comes from `lang` in the config file.
+//!
+//! Not everything is translated yet: the welcome screen, file picker and
+//! file pane are still English, as are error causes from the library.
+
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+/// A UI language (the `lang` config setting).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Lang {
+    /// English.
+    #[default]
+    En,
+    /// Spanish.
+    Es,
+}
+
+impl Lang {
+    /// The template for `key`, falling back to English and then to the key
+    /// itself (so a typo shows up on screen rather than as a blank line).
+    pub fn text(self, key: &'static str) -> &'static str {
+        lookup(self.catalog(), key)
+            .or_else(|| lookup(EN, key))
+            .unwrap_or(key)
+    }
+
+    fn catalog(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Lang::En => EN,
+            Lang::Es => ES,
+        }
+    }
+}
+
+fn lookup(catalog: &[(&str, &'static str)], key: &str) -> Option<&'static str> {
+    catalog.iter().find(|(k, _)| *k == key).map(|(_, v)| *v)
+}
+
+/// Fill `{name}` placeholders in `template` from `args`. Braces that don't
+/// name an argument are left as they are.
+pub fn format(template: &str, args: &[(&str, &dyn Display)]) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        out.push_str(&rest[..open]);
+        let after = &rest[open + 1..];
+        let arg = after
+            .find('}')
+            .and_then(|close| Some((args.iter().find(|(n, _)| *n == &after[..close])?, close)));
+        match arg {
+            Some(((_, value), close)) => {
+                out.push_str(&value.to_string());
+                rest = &after[close + 1..];
+            }
+            None => {
+                out.push('{');
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Translate a catalog key, filling placeholders by name:
+/// `t!(lang, "key")` or `t!(lang, "key", name = value, ...)`. Always
+/// returns a `String`.
+#[macro_export]
+macro_rules! t {
+    ($lang:expr, $key:literal) => {
+        $lang.text($key).to_string()
+    };
+    ($lang:expr, $key:literal, $($name:ident = $value:expr),+ $(,)?) => {
+        $crate::i18n::format(
+            $lang.text($key),
+            &[$((stringify!($name), &$value as &dyn ::std::fmt::Display)),+],
+        )
+    };
+}
+
+// ── Catalogs ─────────────────────────────────────────────────────────────────
+
+/// English: the reference catalog every key must appear in.
+const EN: &[(&str, &str)] = &[
+    // Startup
+    ("start.share", "share the ticket above with others to join"),
+    ("start.id", "your ID: {id} (for `piper-chat dm`)"),
+    ("start.profile", "profile: {name}"),
+    ("start.hint", "type /help for commands | waiting for peers..."),
+    // Chat pane
+    ("line.system", "[system] {text}"),
+    ("line.ticket", "Ticket: "),
+    ("line.large", "┌ {count} lines"),
+    ("code.collapse", "collapse: select + Enter"),
+    ("code.more", "… {count} more lines — expand: select + Enter"),
+    ("code.copied", "Code copied to the clipboard"),
+    ("export.empty", "nothing to export yet"),
+    ("export.done", "exported {count} entries to {path}"),
+    ("export.failed", "couldn't write {path}: {error}"),
+    ("code.none", "No code in this message"),
+    // Peers pane and ticket button
+    ("peers.title", "peers"),
+    ("peers.sort.joined", "joined"),
+    ("peers.sort.name", "name"),
+    ("peers.sort.latency", "latency"),
+    ("peers.sort.connection", "connection"),
+    ("peers.more", "…and {count} more"),
+    ("peers.top", "…back to top"),
+    ("ticket.copy", " Copy Ticket (Ctrl+Y) "),
+    ("ticket.copied", " Copied! "),
+    // Input bar
+    ("input.draft", " (draft) "),
+    ("input.afk", " (afk) "),
+    ("input.thread", " ↳ replying in thread "),
+    ("input.reply", " replying to {name} · Esc cancels "),
+    ("quote.missing", "(earlier message not loaded)"),
+    // Threads
+    ("thread.reply", "1 reply"),
+    ("thread.replies", "{count} replies"),
+    ("thread.title", "thread · {count} replies"),
+    ("thread.close", "close"),
+    ("paste.large", "[send {count} lines as attachment instead? Ctrl+A attach, Enter send as message, Esc discard]"),
+    ("paste.pending", "[pasted {count} lines — Enter to send, Esc to discard]"),
+    // Commands
+    ("usage", "usage: {usage}"),
+    ("command.unclosed", "a quote was never closed — {usage}"),
+    ("command.unknown_flag", "unknown option {flag} — {usage}"),
+    ("command.no_value", "{flag} needs a value — {usage}"),
+    ("command.bad_value", "{value} is no good for {flag} — {usage}"),
+    ("command.choices", "commands: {list}"),
+    ("command.unknown", "unknown command {name} — see /help"),
+    ("palette.title", "commands"),
+    ("lock.title", "locked"),
+    ("lock.prompt", "type your passphrase and press Enter"),
+    ("lock.choose", "choose a passphrase to unlock with"),
+    ("lock.confirm", "type it again"),
+    ("lock.choose_hint", "kept as a hash on this machine · Esc cancels"),
+    ("lock.mismatch", "those didn't match; try again"),
+    ("lock.wrong", "wrong passphrase ({count} tries)"),
+    ("lock.since", "locked at {time} · {count} new messages"),
+    ("lock.save_failed", "passphrase not saved: {error}"),
+    ("lock.checking", "checking…"),
+    ("palette.run", "run"),
+    ("palette.close", "close"),
+    ("palette.none", " nothing matches"),
+    ("palette.theme", "Toggle dark/light theme"),
+    ("palette.file_picker", "Share a file"),
+    ("palette.sort_peers", "Sort peers"),
+    ("palette.copy_ticket", "Copy invite ticket"),
+    ("palette.file_pane", "Focus the file pane"),
+    ("palette.room", "Rejoin room: {label}"),
+    ("afk.on", "You are AFK: {message} (any key to return)"),
+    ("afk.off", "Welcome back — AFK cleared"),
+    ("afk.reply", "{nickname} is AFK: {message}"),
+    ("contacts.none", "no contacts yet — peers are added when they join a room with you"),
+    ("contacts.header", "── Contacts ──────────────────────────────"),
+    ("contacts.row", "  {mark} {nickname} {id}  seen {seen}  {rooms}"),
+    ("contacts.rooms.one", "1 room"),
+    ("contacts.rooms", "{count} rooms"),
+    ("contacts.footer", "  (✓ verified with /verify <name>)"),
+    ("contacts.verified", "verified {name} ({id})"),
+    ("contacts.unknown", "no contact named {name}"),
+    ("contacts.save_failed", "failed to save contacts: {error}"),
+    ("mentions.none", "no mentions yet — messages that @mention you or contain a highlight word are listed here"),
+    ("mentions.header", "── Mentions ({count}) ─────────────────────"),
+    ("mentions.row", "  {time} {nickname}: {text}"),
+    ("mute.on", "room muted — no bell or toasts here (/mute again to undo)"),
+    ("mute.off", "room unmuted"),
+    ("focus.on", "focus mode — system messages and the peers pane are hidden"),
+    ("focus.off", "focus mode off"),
+    ("status.muted", "muted"),
+    ("status.focus", "focus"),
+    ("prefs.save_failed", "failed to save room settings: {error}"),
+    ("wake.resumed", "back after {away} asleep — reconnecting to the room"),
+    ("wake.rejoin_failed", "couldn't rejoin the room: {error}"),
+    ("net.header", "── Network ───────────────────────────────"),
+    ("net.pending", "  no network report yet — try again in a few seconds"),
+    ("net.up", "  {family}: reachable, public address {addr}"),
+    ("net.up.bare", "  {family}: reachable"),
+    ("net.down", "  {family}: unreachable (no UDP round trip)"),
+    ("net.relay", "  home relay: {relay}"),
+    ("net.v6_only", "  IPv6 only: peers without IPv6 are reached through the relay"),
+    ("net.v4_only", "  no IPv6: peers with only IPv6 are reached through the relay"),
+    ("net.no_udp", "  no UDP: every peer is reached through the relay"),
+    ("net.peer.direct", "  {name}: direct over {family} ({addr})"),
+    ("net.peer.direct.bare", "  {name}: direct"),
+    ("net.peer.relay", "  {name}: relay"),
+    ("net.peer.unknown", "  {name}: no path yet"),
+    ("config.save_failed", "failed to save config: {error}"),
+    ("sendto.unknown", "unknown peer: {name}"),
+    ("dm.unknown", "no peer or contact named {name}"),
+    ("dm.failed", "couldn't open a private room with {name}: {error}"),
+    ("dm.waiting", "private room with {name} — waiting for them to connect"),
+    ("join.failed", "couldn't join that room: {error}"),
+    ("join.switched", "joined another room — waiting for peers..."),
+    ("joining.title", "nobody here yet"),
+    ("joining.nobody", "No peer has connected in {secs}s."),
+    ("joining.retry", "retry: dial the ticket's peers again"),
+    ("joining.edit", "edit ticket: join with a different one"),
+    ("joining.wait", "keep waiting"),
+    ("joining.diagnose", "diagnostics: network and peer addresses"),
+    ("joining.diag.header", "── Bootstrap peers ───────────────────────"),
+    ("joining.diag.peer", "  {id}: {known}"),
+    ("filter.blocked", "message not sent: blocked by {filter} filter"),
+    ("rollcall.sent", "roll call sent, waiting for replies..."),
+    ("timer.started", "{name} started a timer: {label} ({time})"),
+    ("timer.done", "⏰ time's up: {label} (set by {name})"),
+    ("paths.relay", "relay only"),
+    ("paths.direct", "direct only"),
+    ("rollcall.header", "── Roll call: {count} here ──"),
+    ("rollcall.silent", "no reply"),
+    ("map.title", "connection map"),
+    ("map.close", "close"),
+    ("map.empty", "no peers connected yet"),
+    ("gallery.title", "files in this room ({count})"),
+    ("gallery.filters", "sender: {sender}   type: {kind}   state: {state}"),
+    ("gallery.all", "all"),
+    ("gallery.empty", "no files have been offered in this room yet"),
+    ("gallery.no_match", "no files match these filters"),
+    ("gallery.act", "download / open / re-request"),
+    ("gallery.sender", "sender"),
+    ("gallery.type", "type"),
+    ("gallery.state", "state"),
+    ("gallery.close", "close"),
+    ("gallery.kind.image", "images"),
+    ("gallery.kind.video", "video"),
+    ("gallery.kind.audio", "audio"),
+    ("gallery.kind.document", "documents"),
+    ("gallery.kind.archive", "archives"),
+    ("gallery.kind.other", "other"),
+    ("gallery.state.offered", "offered"),
+    ("gallery.state.downloading", "downloading"),
+    ("gallery.state.downloaded", "downloaded"),
+    ("gallery.state.failed", "failed"),
+    ("gallery.state.shared", "shared"),
+    ("gallery.state.withdrawn", "withdrawn"),
+    ("gallery.state.earlier", "earlier"),
+    ("gallery.rerequesting", "re-requesting {filename} from {name}, then anyone in the room"),
+    // Peers coming and going
+    ("peer.connected", "peer connected: {id}"),
+    ("peer.joined", "{name} joined"),
+    ("nick.clash", "two peers are called {name}; showing them as {first} and {second}"),
+    ("nick.clash_you", "someone else joined as {name} too; they show as {shown}"),
+    ("peer.left", "{name} left"),
+    // Knock-to-join and member caps
+    ("knock.waiting", "this room is knock-to-join: asking its creator to let you in…"),
+    ("knock.asks", "{name} asks to join — y to let them in, n to turn them away"),
+    ("knock.asks_creator", "{name} asks to join; the room's owners decide"),
+    ("knock.let_in", "{name} was let in"),
+    ("knock.denied", "{name} was turned away"),
+    ("knock.full", "{name} was turned away: the room is full"),
+    ("knock.you_in", "you're in — the room's creator let you in"),
+    ("knock.you_denied", "the room's creator turned you away"),
+    ("knock.title", "knock knock"),
+    ("knock.asks_short", "asks to join the room."),
+    ("knock.more", "{count} more waiting"),
+    ("knock.approve", "let in"),
+    ("knock.deny", "turn away"),
+    ("owner.granted", "{name} is now an owner of this room"),
+    ("owner.you", "you're now an owner of this room: you can let people in"),
+    ("owner.list", "owners: {names}"),
+    ("owner.already", "{name} is already an owner"),
+    ("owner.not_owner", "only the room's owners can name new owners"),
+    ("owner.no_policy", "this room has no owners: it was created without --max-members or --knock"),
+    // Files
+    ("file.shared", "{name} shared: {filename} ({size})"),
+    ("file.offer_refused", "Ignored {name}'s offer of {filename} (offers_from setting)"),
+    ("file.shared_with_you", "{name} shared (with you): {filename} ({size})"),
+    ("file.unshared", "{name} unshared: {filename}"),
+    ("file.you_unshared", "You unshared: {filename}"),
+    ("file.you_expire", "Your offer of {filename} expires in {left}"),
+    ("file.expires", "{name}'s offer of {filename} expires in {left}"),
+    ("file.expired", "{name}'s offer expired: {filename}"),
+    ("file.not_shared", "Row {row} of the files pane isn't a file you're sharing"),
+    ("file.cleanup_failed", "Couldn't release {filename} from the blob store: {error}"),
+    ("file.invalidated", "Withdrew {filename}: the source file was {reason} since you shared it"),
+    ("source.missing", "moved or deleted"),
+    ("source.resized", "resized"),
+    ("source.modified", "modified"),
+    ("file.sharing", "sharing: {filename}"),
+    ("file.reused", "already in store — instant share"),
+    ("file.reacted", "You told {name} you got {filename} 👍"),
+    ("file.received_by", "{name} 👍 received {filename}"),
+    ("file.sharing_to", "sharing (to {target}): {filename}"),
+    ("file.share_failed", "failed to share file: {error}"),
+    ("download.complete", "download complete: {filename}"),
+    ("download.failed", "download failed: {filename} — {error}"),
+    ("download.retrying", "retrying download: {filename}"),
+    ("download.complete_via", "download complete: {filename} (via {via})"),
+    ("preview.fetching", "fetching {filename} into memory for a preview..."),
+    ("preview.failed", "preview failed: {filename} — {error}"),
+    ("preview.too_big", "{filename} is {size}; only files up to {max} can be previewed"),
+    ("strategy.default", "a fresh connection"),
+    ("strategy.relay", "the relay only"),
+    ("strategy.peer", "{name}'s copy"),
+    ("verify.started", "Checking {filename}..."),
+    ("verify.ok", "{filename} matches its hash"),
+    ("verify.mismatch", "{filename} doesn't match its hash: it changed after download, so it won't be opened"),
+    ("verify.failed", "Couldn't check {filename}: {error}"),
+    ("verify.not_downloaded", "Row {row} of the files pane isn't a finished download"),
+    ("verify.sidecar_failed", "Couldn't write the checksum file for {filename}: {error}"),
+    // History
+    ("history.syncing", "syncing {count} messages from history..."),
+    ("history.synced", "history sync complete: {count} new messages"),
+    ("history.invalid", "history sync failed: invalid data ({error})"),
+    ("history.sync_failed", "history sync failed: {error}"),
+    ("history.recovered", "recovered {count} missed messages"),
+    ("history.restored", "restored {count} messages from your last visit"),
+    ("history.restore_failed", "couldn't restore history: {error}"),
+    ("history.crashed", "your last visit ended unexpectedly; recovered its unsaved messages"),
+    ("session.recovered", "resumed the last session: {files} file(s) back in the pane, {resumed} download(s) restarted"),
+    ("session.save_failed", "couldn't save the session for crash recovery: {error}"),
+    ("artifacts.save_failed", "couldn't save the list of shared artifacts: {error}"),
+    ("artifacts.swept", "removed {count} leftover shared artifact(s) from an earlier session"),
+    ("replay.started", "replaying {count} events from {path}: Space pauses, Right steps, +/- change speed, q quits"),
+    ("replay.skipped", "skipped {count} line(s) that didn't parse"),
+    ("replay.paused", "paused"),
+    ("replay.resumed", "playing"),
+    ("replay.speed", "speed {speed}x"),
+    ("replay.finished", "end of the recording"),
+    ("room.archive_failed", "failed to archive room: {error}"),
+    // Network
+    ("gossip.lagged", "warning: gossip stream lagged"),
+    ("gossip.requesting", "requesting missed messages from {name}"),
+    ("gossip.closed", "gossip stream closed"),
+    ("gossip.error", "gossip error: {error}"),
+    ("send.failed", "message not sent: {error}"),
+    ("send.retrying", "{error} — will keep retrying"),
+    ("send.gave_up", "gave up sending a message after {attempts} attempts: {error}"),
+    ("send.recovered", "connection recovered — queued messages sent"),
+    // `/help`, one system line per line.
+    ("help", "\
+── Commands ──────────────────────────────
+  /help           Show this help
+  /send           Open file picker to share a file
+  /sendto <name>  Send a file to a specific peer
+  /shrug [text]   Append ¯\\_(ツ)_/¯ to a message
+  /spoiler <text> Send text hidden until revealed
+  /afk [message]  Auto-reply to mentions until you return
+  /contacts       List peers you've met
+  /dm <name>      Switch to a private room with a peer or contact
+  /join <ticket>  Leave this room for the one in another ticket
+  /verify <name>  Mark a contact as verified
+  /owner [name]   Make a peer co-owner of this room, or list owners
+  /who            Roll call: list who is in the room
+  /timer <t> [label] Start a countdown for everyone (10m, 90s)
+  /map            Show a live map of connections to peers
+  /files          Every file offered in this room, filterable
+  /mute           Toggle the bell and toasts for this room
+  /mentions       List messages that mention you or a highlight word
+  /focus          Toggle hiding system messages and the peers pane
+  /net            IPv4/IPv6 reachability and each peer's path
+  /lock           Hide the chat until you type your passphrase
+  /unshare <n>    Stop sharing your file on row n of the files pane
+  /expire <n> <t> Unshare row n after t (30m, 2h, 1d)
+  /verifyfile <n> Re-hash the download on row n and check it
+  /export [file]  Save the transcript (--format html for a web page)
+── Keys (chat) ───────────────────────────
+  Enter        Send message
+  Shift+Enter  New line (Alt+Enter / Ctrl+J on older terminals)
+  Ctrl+F       Open file picker
+  Ctrl+A       Send a large paste as an attachment
+  Ctrl+T       Toggle dark/light theme
+  Ctrl+O       Sort peers by join order, name, latency or connection
+  Ctrl+Y       Copy invite ticket to clipboard
+  Ctrl+K       Command palette: search every action
+  Tab          Complete a /command, else focus the file pane
+  Up           Select messages (when input is empty)
+  Esc          Quit
+── Keys (file pane) ──────────────────────
+  Up/Down      Select entry
+  Enter        Download / open folder / unshare
+  o / O        Open the downloaded file / its folder
+  i            Transfer details: a timeline of each step
+  r            Retry a failed download (falls back to relay / peers)
+  +            React 👍 received to a download (the sender sees it)
+  p            Preview a small offer in memory (s in the preview saves it)
+  Tab/Esc      Return to chat (Shift+Tab cycles back)
+── Keys (message select) ─────────────────
+  Up/Down      Select message
+  Enter/Space  Reveal / hide spoiler, expand / collapse long message
+  c            Copy the message's code
+  t            Open its thread (Esc in chat closes it)
+  r            Quote-reply to it (Esc in chat cancels)
+  Esc          Return to input
+── Keys (file picker) ────────────────────
+  Up/Down      Navigate files
+  Left/Right   Parent / enter directory
+  Enter        Select file to share
+  Esc          Cancel
+── Mouse ─────────────────────────────────
+  Click        Focus pane / trigger action
+  Scroll       Scroll messages up/down
+──────────────────────────────────────────"),
+];
+
+/// Spanish.
+const ES: &[(&str, &str)] = &[
+    ("start.share", "comparte el ticket de arriba para que otros se unan"),
+    ("start.id", "tu ID: {id} (para `piper-chat dm`)"),
+    ("start.profile", "perfil: {name}"),
+    ("start.hint", "escribe /help para ver los comandos | esperando a otros..."),
+    ("line.system", "[sistema] {text}"),
+    ("line.ticket", "Ticket: "),
+    ("line.large", "┌ {count} líneas"),
+    ("code.collapse", "contraer: seleccionar + Enter"),
+    ("code.more", "… {count} líneas más — expandir: seleccionar + Enter"),
+    ("code.copied", "Código copiado al portapapeles"),
+    ("export.empty", "todavía no hay nada que exportar"),
+    ("export.done", "{count} entradas exportadas a {path}"),
+    ("export.failed", "no se pudo escribir {path}: {error}"),
+    ("code.none", "Este mensaje no tiene código"),
+    ("peers.title", "participantes"),
+    ("peers.sort.joined", "llegada"),
+    ("peers.sort.name", "nombre"),
+    ("peers.sort.latency", "latencia"),
+    ("peers.sort.connection", "conexión"),
+    ("peers.more", "…y {count} más"),
+    ("peers.top", "…volver al principio"),
+    ("ticket.copy", " Copiar ticket (Ctrl+Y) "),
+    ("ticket.copied", " ¡Copiado! "),
+    ("input.draft", " (borrador) "),
+    ("input.afk", " (ausente) "),
+    ("input.thread", " ↳ respondiendo en el hilo "),
+    ("input.reply", " respondiendo a {name} · Esc cancela "),
+    ("quote.missing", "(mensaje anterior no cargado)"),
+    ("thread.reply", "1 respuesta"),
+    ("thread.replies", "{count} respuestas"),
+    ("thread.title", "hilo · {count} respuestas"),
+    ("thread.close", "cerrar"),
+    ("paste.large", "[¿enviar {count} líneas como adjunto? Ctrl+A adjuntar, Enter enviar como mensaje, Esc descartar]"),
+    ("paste.pending", "[{count} líneas pegadas — Enter para enviar, Esc para descartar]"),
+    ("usage", "uso: {usage}"),
+    ("command.unclosed", "falta cerrar unas comillas — {usage}"),
+    ("command.unknown_flag", "opción desconocida {flag} — {usage}"),
+    ("command.no_value", "{flag} necesita un valor — {usage}"),
+    ("command.bad_value", "{value} no vale para {flag} — {usage}"),
+    ("command.choices", "comandos: {list}"),
+    ("command.unknown", "comando desconocido {name} — mira /help"),
+    ("palette.title", "comandos"),
+    ("lock.title", "bloqueado"),
+    ("lock.prompt", "escribe tu frase de paso y pulsa Enter"),
+    ("lock.choose", "elige una frase de paso para desbloquear"),
+    ("lock.confirm", "escríbela otra vez"),
+    ("lock.choose_hint", "se guarda como hash en este equipo · Esc cancela"),
+    ("lock.mismatch", "no coinciden; prueba otra vez"),
+    ("lock.wrong", "frase incorrecta ({count} intentos)"),
+    ("lock.since", "bloqueado a las {time} · {count} mensajes nuevos"),
+    ("lock.save_failed", "frase de paso no guardada: {error}"),
+    ("lock.checking", "comprobando…"),
+    ("palette.run", "ejecutar"),
+    ("palette.close", "cerrar"),
+    ("palette.none", " nada coincide"),
+    ("palette.theme", "Alternar tema oscuro/claro"),
+    ("palette.file_picker", "Compartir un archivo"),
+    ("palette.sort_peers", "Ordenar participantes"),
+    ("palette.copy_ticket", "Copiar el ticket de invitación"),
+    ("palette.file_pane", "Ir al panel de archivos"),
+    ("palette.room", "Volver a la sala: {label}"),
+    ("afk.on", "Estás ausente: {message} (pulsa una tecla para volver)"),
+    ("afk.off", "Bienvenido de nuevo — ya no estás ausente"),
+    ("afk.reply", "{nickname} está ausente: {message}"),
+    ("contacts.none", "aún no hay contactos — se añaden al coincidir con alguien en una sala"),
+    ("contacts.header", "── Contactos ─────────────────────────────"),
+    ("contacts.row", "  {mark} {nickname} {id}  visto {seen}  {rooms}"),
+    ("contacts.rooms.one", "1 sala"),
+    ("contacts.rooms", "{count} salas"),
+    ("contacts.footer", "  (✓ verificado con /verify <nombre>)"),
+    ("contacts.verified", "{name} verificado ({id})"),
+    ("contacts.unknown", "no hay ningún contacto llamado {name}"),
+    ("contacts.save_failed", "no se pudieron guardar los contactos: {error}"),
+    ("mentions.none", "aún no hay menciones — aquí aparecen los mensajes que te @mencionan o contienen una palabra destacada"),
+    ("mentions.header", "── Menciones ({count}) ───────────────────"),
+    ("mentions.row", "  {time} {nickname}: {text}"),
+    ("mute.on", "sala silenciada — sin campana ni avisos aquí (/mute otra vez para deshacer)"),
+    ("mute.off", "sala con sonido"),
+    ("focus.on", "modo concentración — mensajes del sistema y participantes ocultos"),
+    ("focus.off", "modo concentración desactivado"),
+    ("status.muted", "silenciada"),
+    ("status.focus", "concentración"),
+    ("prefs.save_failed", "no se pudieron guardar los ajustes de la sala: {error}"),
+    ("wake.resumed", "de vuelta tras {away} en suspensión — reconectando a la sala"),
+    ("wake.rejoin_failed", "no se pudo volver a entrar en la sala: {error}"),
+    ("net.header", "── Red ───────────────────────────────────"),
+    ("net.pending", "  aún no hay informe de red — prueba de nuevo en unos segundos"),
+    ("net.up", "  {family}: accesible, dirección pública {addr}"),
+    ("net.up.bare", "  {family}: accesible"),
+    ("net.down", "  {family}: inaccesible (sin ida y vuelta UDP)"),
+    ("net.relay", "  relay principal: {relay}"),
+    ("net.v6_only", "  solo IPv6: a quien no tenga IPv6 se llega por el relay"),
+    ("net.v4_only", "  sin IPv6: a quien solo tenga IPv6 se llega por el relay"),
+    ("net.no_udp", "  sin UDP: a todos se llega por el relay"),
+    ("net.peer.direct", "  {name}: directa por {family} ({addr})"),
+    ("net.peer.direct.bare", "  {name}: directa"),
+    ("net.peer.relay", "  {name}: relay"),
+    ("net.peer.unknown", "  {name}: aún sin ruta"),
+    ("config.save_failed", "no se pudo guardar la configuración: {error}"),
+    ("sendto.unknown", "participante desconocido: {name}"),
+    ("dm.unknown", "no hay ningún participante ni contacto llamado {name}"),
+    ("dm.failed", "no se pudo abrir una sala privada con {name}: {error}"),
+    ("dm.waiting", "sala privada con {name} — esperando a que se conecte"),
+    ("join.failed", "no se pudo entrar en esa sala: {error}"),
+    ("join.switched", "entraste en otra sala — esperando participantes..."),
+    ("joining.title", "todavía no hay nadie"),
+    ("joining.nobody", "Nadie se ha conectado en {secs}s."),
+    ("joining.retry", "reintentar: volver a llamar a los pares del ticket"),
+    ("joining.edit", "editar ticket: entrar con otro"),
+    ("joining.wait", "seguir esperando"),
+    ("joining.diagnose", "diagnóstico: red y direcciones de los pares"),
+    ("joining.diag.header", "── Pares de arranque ─────────────────────"),
+    ("joining.diag.peer", "  {id}: {known}"),
+    ("filter.blocked", "mensaje no enviado: bloqueado por el filtro {filter}"),
+    ("rollcall.sent", "pase de lista enviado, esperando respuestas..."),
+    ("timer.started", "{name} puso un temporizador: {label} ({time})"),
+    ("timer.done", "⏰ se acabó el tiempo: {label} (de {name})"),
+    ("paths.relay", "solo relay"),
+    ("paths.direct", "solo directo"),
+    ("rollcall.header", "── Pase de lista: {count} presentes ──"),
+    ("rollcall.silent", "sin respuesta"),
+    ("map.title", "mapa de conexiones"),
+    ("map.close", "cerrar"),
+    ("map.empty", "todavía no hay nadie conectado"),
+    ("gallery.title", "archivos de esta sala ({count})"),
+    ("gallery.filters", "remitente: {sender}   tipo: {kind}   estado: {state}"),
+    ("gallery.all", "todos"),
+    ("gallery.empty", "todavía no se ha ofrecido ningún archivo en esta sala"),
+    ("gallery.no_match", "ningún archivo coincide con estos filtros"),
+    ("gallery.act", "descargar / abrir / volver a pedir"),
+    ("gallery.sender", "remitente"),
+    ("gallery.type", "tipo"),
+    ("gallery.state", "estado"),
+    ("gallery.close", "cerrar"),
+    ("gallery.kind.image", "imágenes"),
+    ("gallery.kind.video", "vídeo"),
+    ("gallery.kind.audio", "audio"),
+    ("gallery.kind.document", "documentos"),
+    ("gallery.kind.archive", "archivos comprimidos"),
+    ("gallery.kind.other", "otros"),
+    ("gallery.state.offered", "ofrecido"),
+    ("gallery.state.downloading", "descargando"),
+    ("gallery.state.downloaded", "descargado"),
+    ("gallery.state.failed", "fallido"),
+    ("gallery.state.shared", "compartido"),
+    ("gallery.state.withdrawn", "retirado"),
+    ("gallery.state.earlier", "anterior"),
+    ("gallery.rerequesting", "volviendo a pedir {filename} a {name} y, si no, a cualquiera de la sala"),
+    ("peer.connected", "participante conectado: {id}"),
+    ("peer.joined", "{name} se ha unido"),
+    ("nick.clash", "dos participantes se llaman {name}; se muestran como {first} y {second}"),
+    ("nick.clash_you", "alguien más entró como {name}; se muestra como {shown}"),
+    ("peer.left", "{name} se ha ido"),
+    ("knock.waiting", "esta sala pide permiso para entrar: pidiéndoselo a quien la creó…"),
+    ("knock.asks", "{name} pide entrar — y para dejarle pasar, n para rechazarle"),
+    ("knock.asks_creator", "{name} pide entrar; deciden los dueños de la sala"),
+    ("knock.let_in", "{name} ha entrado"),
+    ("knock.denied", "{name} ha sido rechazado"),
+    ("knock.full", "{name} ha sido rechazado: la sala está llena"),
+    ("knock.you_in", "estás dentro — quien creó la sala te ha dejado pasar"),
+    ("knock.you_denied", "quien creó la sala te ha rechazado"),
+    ("knock.title", "toc toc"),
+    ("knock.asks_short", "pide entrar en la sala."),
+    ("knock.more", "{count} más esperando"),
+    ("knock.approve", "dejar pasar"),
+    ("knock.deny", "rechazar"),
+    ("owner.granted", "{name} ahora es dueño de esta sala"),
+    ("owner.you", "ahora eres dueño de esta sala: puedes dejar entrar a otros"),
+    ("owner.list", "dueños: {names}"),
+    ("owner.already", "{name} ya es dueño"),
+    ("owner.not_owner", "solo los dueños de la sala pueden nombrar nuevos dueños"),
+    ("owner.no_policy", "esta sala no tiene dueños: se creó sin --max-members ni --knock"),
+    ("file.shared", "{name} ha compartido: {filename} ({size})"),
+    ("file.offer_refused", "Se ignoró el archivo {filename} de {name} (ajuste offers_from)"),
+    ("file.shared_with_you", "{name} ha compartido contigo: {filename} ({size})"),
+    ("file.unshared", "{name} ha dejado de compartir: {filename}"),
+    ("file.you_unshared", "Has dejado de compartir: {filename}"),
+    ("file.you_expire", "Tu archivo {filename} caduca en {left}"),
+    ("file.expires", "El archivo {filename} de {name} caduca en {left}"),
+    ("file.expired", "Ha caducado el archivo de {name}: {filename}"),
+    ("file.not_shared", "La fila {row} del panel de archivos no es un archivo que compartas"),
+    ("file.cleanup_failed", "No se pudo liberar {filename} del almacén de blobs: {error}"),
+    ("file.invalidated", "Se retiró {filename}: el archivo de origen fue {reason} después de compartirlo"),
+    ("source.missing", "movido o borrado"),
+    ("source.resized", "cambiado de tamaño"),
+    ("source.modified", "modificado"),
+    ("file.sharing", "compartiendo: {filename}"),
+    ("file.reused", "ya estaba en el almacén — compartido al instante"),
+    ("file.reacted", "Le has dicho a {name} que recibiste {filename} 👍"),
+    ("file.received_by", "{name} 👍 recibió {filename}"),
+    ("file.sharing_to", "compartiendo (con {target}): {filename}"),
+    ("file.share_failed", "no se pudo compartir el archivo: {error}"),
+    ("download.complete", "descarga completa: {filename}"),
+    ("download.failed", "falló la descarga: {filename} — {error}"),
+    ("download.retrying", "reintentando la descarga: {filename}"),
+    ("download.complete_via", "descarga completa: {filename} (mediante {via})"),
+    ("preview.fetching", "trayendo {filename} a memoria para la vista previa..."),
+    ("preview.failed", "falló la vista previa: {filename} — {error}"),
+    ("preview.too_big", "{filename} ocupa {size}; solo se pueden previsualizar archivos de hasta {max}"),
+    ("strategy.default", "una conexión nueva"),
+    ("strategy.relay", "solo el relay"),
+    ("strategy.peer", "la copia de {name}"),
+    ("verify.started", "Comprobando {filename}..."),
+    ("verify.ok", "{filename} coincide con su hash"),
+    ("verify.mismatch", "{filename} no coincide con su hash: cambió tras la descarga, así que no se abrirá"),
+    ("verify.failed", "No se pudo comprobar {filename}: {error}"),
+    ("verify.not_downloaded", "La fila {row} del panel de archivos no es una descarga terminada"),
+    ("verify.sidecar_failed", "No se pudo escribir el archivo de suma de {filename}: {error}"),
+    ("history.syncing", "sincronizando {count} mensajes del historial..."),
+    ("history.synced", "historial sincronizado: {count} mensajes nuevos"),
+    ("history.invalid", "falló la sincronización del historial: datos no válidos ({error})"),
+    ("history.sync_failed", "falló la sincronización del historial: {error}"),
+    ("history.recovered", "recuperados {count} mensajes perdidos"),
+    ("history.restored", "restaurados {count} mensajes de tu última visita"),
+    ("history.restore_failed", "no se pudo restaurar el historial: {error}"),
+    ("history.crashed", "tu última visita terminó de forma inesperada; se recuperaron sus mensajes sin guardar"),
+    ("session.recovered", "se reanudó la última sesión: {files} archivo(s) de vuelta en el panel, {resumed} descarga(s) reiniciada(s)"),
+    ("session.save_failed", "no se pudo guardar la sesión para recuperarla tras un fallo: {error}"),
+    ("artifacts.save_failed", "no se pudo guardar la lista de artefactos compartidos: {error}"),
+    ("artifacts.swept", "se eliminaron {count} artefacto(s) compartido(s) de una sesión anterior"),
+    ("replay.started", "reproduciendo {count} eventos de {path}: Espacio pausa, Derecha avanza, +/- cambian la velocidad, q sale"),
+    ("replay.skipped", "se omitieron {count} línea(s) que no se pudieron leer"),
+    ("replay.paused", "en pausa"),
+    ("replay.resumed", "reproduciendo"),
+    ("replay.speed", "velocidad {speed}x"),
+    ("replay.finished", "fin de la grabación"),
+    ("room.archive_failed", "no se pudo archivar la sala: {error}"),
+    ("gossip.lagged", "aviso: el flujo de gossip se ha retrasado"),
+    ("gossip.requesting", "pidiendo los mensajes perdidos a {name}"),
+    ("gossip.closed", "el flujo de gossip se ha cerrado"),
+    ("gossip.error", "error de gossip: {error}"),
+    ("send.failed", "mensaje no enviado: {error}"),
+    ("send.retrying", "{error} — se seguirá reintentando"),
+    ("send.gave_up", "se abandonó el envío de un mensaje tras {attempts} intentos: {error}"),
+    ("send.recovered", "conexión recuperada — mensajes pendientes enviados"),
+    ("help", "\
+── Comandos ──────────────────────────────
+  /help             Muestra esta ayuda
+  /send             Abre el selector para compartir un archivo
+  /sendto <nombre>  Envía un archivo a un participante
+  /shrug [texto]    Añade ¯\\_(ツ)_/¯ a un mensaje
+  /spoiler <texto>  Envía texto oculto hasta que se revele
+  /afk [mensaje]    Responde a las menciones hasta que vuelvas
+  /contacts         Lista a quienes has conocido
+  /dm <nombre>      Pasa a una sala privada con alguien
+  /join <ticket>    Deja esta sala por la de otro ticket
+  /verify <nombre>  Marca un contacto como verificado
+  /owner [nombre]   Hace copropietario a alguien, o lista los dueños
+  /who              Pase de lista: quién está en la sala
+  /timer <t> [nota] Cuenta atrás para todos (10m, 90s)
+  /map              Muestra un mapa en vivo de las conexiones
+  /files            Todos los archivos ofrecidos en la sala, con filtros
+  /mute             Activa o quita la campana y los avisos en esta sala
+  /mentions         Lista los mensajes que te mencionan o destacan
+  /focus            Oculta o muestra mensajes del sistema y participantes
+  /net              Alcance IPv4/IPv6 y la ruta de cada participante
+  /lock             Oculta el chat hasta que escribas tu frase de paso
+  /unshare <n>      Deja de compartir tu archivo de la fila n
+  /expire <n> <t>   Deja de compartir la fila n tras t (30m, 2h, 1d)
+  /verifyfile <n>   Recalcula el hash de la descarga de la fila n
+  /export [archivo] Guarda la conversación (--format html: página web)
+── Teclas (chat) ─────────────────────────
+  Enter        Envía el mensaje
+  Shift+Enter  Nueva línea (Alt+Enter / Ctrl+J en terminales antiguos)
+  Ctrl+F       Abre el selector de archivos
+  Ctrl+A       Envía un pegado largo como adjunto
+  Ctrl+T       Alterna tema oscuro/claro
+  Ctrl+O       Ordena por llegada, nombre, latencia o conexión
+  Ctrl+Y       Copia el ticket de invitación
+  Ctrl+K       Paleta de comandos: busca cualquier acción
+  Tab          Completa un /comando, si no pasa al panel de archivos
+  Arriba       Selecciona mensajes (con la entrada vacía)
+  Esc          Salir
+── Teclas (panel de archivos) ────────────
+  Arriba/Abajo Selecciona una entrada
+  Enter        Descargar / abrir carpeta / dejar de compartir
+  o / O        Abre el archivo descargado / su carpeta
+  i            Detalles: cronología de la transferencia
+  r            Reintenta una descarga fallida (relay / otros pares)
+  +            Reacciona 👍 recibido a una descarga (lo ve quien la envió)
+  p            Vista previa en memoria de un archivo pequeño (s la guarda)
+  Tab/Esc      Vuelve al chat (Shift+Tab retrocede)
+── Teclas (selección de mensajes) ────────
+  Arriba/Abajo Selecciona un mensaje
+  Enter/Espacio Revela / oculta spoiler, expande / contrae mensaje largo
+  c            Copia el código del mensaje
+  t            Abre su hilo (Esc en el chat lo cierra)
+  r            Responde citándolo (Esc en el chat cancela)
+  Esc          Vuelve a la entrada
+── Teclas (selector de archivos) ─────────
+  Arriba/Abajo Navega por los archivos
+  Izq./Der.    Carpeta superior / entrar en carpeta
+  Enter        Elige el archivo a compartir
+  Esc          Cancelar
+── Ratón ─────────────────────────────────
+  Clic         Enfoca panel / activa acción
+  Rueda        Desplaza los mensajes
+──────────────────────────────────────────"),
+];
+
+// ── Tests ────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Placeholder names in a template, in order of appearance.
+    fn placeholders(template: &str) -> Vec<&str> {
+        let mut names: Vec<&str> = template
+            .split('{')
+            .skip(1)
+            .filter_map(|s| s.split_once('}').map(|(name, _)| name))
+            .filter(|name| !name.is_empty() && name.chars().all(|c| c.is_ascii_lowercase() || c == '_'))
+            .collect();
+        names.sort_unstable();
+        names
+    }
+
+    #[test]
+    fn named_arguments_in_any_order() {
+        let text = format("{b} then {a}, {missing} and {", &[("a", &1), ("b", &"two")]);
+        assert_eq!(text, "two then 1, {missing} and {");
+        assert_eq!(t!(Lang::Es, "peer.joined", name = "Bob"), "Bob se ha unido");
+        assert_eq!(t!(Lang::En, "peer.joined", name = "Bob"), "Bob joined");
+        assert_eq!(Lang::Es.text("no.such.key"), "no.such.key");
+    }
+
+    #[test]
+    fn translations_match_english() {
+        for lang in [Lang::Es] {
+            for (key, template) in lang.catalog() {
+                let english = lookup(EN, key).unwrap_or_else(|| panic!("{lang:?} has unknown key {key}"));
+                assert_eq!(placeholders(template), placeholders(english), "{lang:?} {key}");
+            }
+        }
+        // Every key is translated (a missing one would silently show English).
+        assert_eq!(ES.len(), EN.len());
+    }
+}
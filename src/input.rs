@@ -0,0 +1,189 @@
+//! Line-editing helpers shared by the chat input bar and the welcome form.
+//!
+//! Text fields store their content as a `String` plus a cursor measured in
+//! **bytes**. Rust strings are UTF-8, so a single user-perceived character
+//! can span several bytes (`é` is 2, `中` is 3) or even several `char`s
+//! (`e` + a combining accent). These helpers always move the cursor by whole
+//! *grapheme clusters* so it never lands inside a character — which would
+//! make `String::insert`/`String::remove` panic.
+//!
+//! For rendering, `cursor_column()` converts the byte cursor into a terminal
+//! column using display widths: CJK ideographs occupy two cells, combining
+//! marks occupy zero.
+
+// `UnicodeSegmentation` adds `.grapheme_indices()` to `&str` — iterating over
+// user-perceived characters rather than raw `char`s (Unicode scalar values).
+use unicode_segmentation::UnicodeSegmentation;
+// `UnicodeWidthStr` adds `.width()` to `&str` — the number of terminal cells
+// the string occupies when printed.
+use unicode_width::UnicodeWidthStr;
+
+/// Insert a character (or composed text from an IME) at the cursor and
+/// advance the cursor past it.
+pub fn insert_str(text: &mut String, cursor: &mut usize, s: &str) {
+    text.insert_str(*cursor, s);
+    *cursor += s.len();
+}
+
+/// Insert a single `char` at the cursor. `c.len_utf8()` is the number of
+/// bytes the character occupies once encoded.
+pub fn insert_char(text: &mut String, cursor: &mut usize, c: char) {
+    text.insert(*cursor, c);
+    *cursor += c.len_utf8();
+}
+
+/// Byte offset of the grapheme boundary immediately before `cursor`.
+pub fn prev_boundary(text: &str, cursor: usize) -> usize {
+    text[..cursor]
+        .grapheme_indices(true)
+        .next_back()
+        .map(|(i, _)| i)
+        .unwrap_or(0)
+}
+
+/// Byte offset of the grapheme boundary immediately after `cursor`.
+pub fn next_boundary(text: &str, cursor: usize) -> usize {
+    text[cursor..]
+        .graphemes(true)
+        .next()
+        .map(|g| cursor + g.len())
+        .unwrap_or(text.len())
+}
+
+/// Delete the grapheme before the cursor (Backspace).
+pub fn backspace(text: &mut String, cursor: &mut usize) {
+    if *cursor > 0 {
+        let start = prev_boundary(text, *cursor);
+        text.replace_range(start..*cursor, "");
+        *cursor = start;
+    }
+}
+
+/// Move the cursor one grapheme to the left.
+pub fn move_left(text: &str, cursor: &mut usize) {
+    *cursor = prev_boundary(text, *cursor);
+}
+
+/// Move the cursor one grapheme to the right.
+pub fn move_right(text: &str, cursor: &mut usize) {
+    *cursor = next_boundary(text, *cursor);
+}
+
+/// Terminal column of the cursor relative to the start of `text`.
+pub fn cursor_column(text: &str, cursor: usize) -> u16 {
+    text[..cursor].width() as u16
+}
+
+// ── Keyboard enhancement ─────────────────────────────────────────────────────
+//
+// Legacy terminal input encodes many keys ambiguously (e.g. Esc vs. Alt+key,
+// Enter vs. Shift+Enter, Tab vs. Ctrl+Tab, or an AltGr-composed character vs.
+// a Ctrl chord). Terminals implementing the kitty keyboard protocol can be
+// asked to send unambiguous escape codes, which crossterm decodes into key
+// events with accurate modifiers.
+
+/// Opt into unambiguous key reporting if the terminal supports it.
+///
+/// Must be called after `enable_raw_mode()` and before creating the
+/// `EventStream`, because the support check reads the terminal's reply from
+/// stdin. Returns `true` if the flags were pushed (and must later be popped
+/// with `disable_keyboard_enhancement()`).
+pub fn enable_keyboard_enhancement() -> bool {
+    use crossterm::event::{KeyboardEnhancementFlags, PushKeyboardEnhancementFlags};
+    // `matches!(.., Ok(true))` treats a failed query the same as "unsupported".
+    if !matches!(crossterm::terminal::supports_keyboard_enhancement(), Ok(true)) {
+        return false;
+    }
+    crossterm::execute!(
+        std::io::stdout(),
+        PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES)
+    )
+    .is_ok()
+}
+
+/// Restore the terminal's previous keyboard mode.
+pub fn disable_keyboard_enhancement() {
+    let _ = crossterm::execute!(
+        std::io::stdout(),
+        crossterm::event::PopKeyboardEnhancementFlags
+    );
+}
+
+/// Whether a key event is a "newline" chord for multi-line compose.
+///
+/// With keyboard enhancement active, terminals report Shift+Enter and
+/// Ctrl+Enter as distinct from plain Enter. Legacy terminals can't, so
+/// Alt+Enter (sent as ESC + CR) and Ctrl+J (a raw line feed) are accepted
+/// as fallbacks that work everywhere.
+pub fn is_newline_chord(key: &crossterm::event::KeyEvent) -> bool {
+    use crossterm::event::{KeyCode, KeyModifiers};
+    match key.code {
+        KeyCode::Enter => key
+            .modifiers
+            .intersects(KeyModifiers::SHIFT | KeyModifiers::CONTROL | KeyModifiers::ALT),
+        KeyCode::Char('j') => key.modifiers.contains(KeyModifiers::CONTROL),
+        _ => false,
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn insert_multibyte_advances_by_bytes() {
+        let mut text = String::new();
+        let mut cursor = 0;
+        insert_char(&mut text, &mut cursor, 'é');
+        insert_char(&mut text, &mut cursor, '中');
+        assert_eq!(text, "é中");
+        assert_eq!(cursor, text.len());
+    }
+
+    #[test]
+    fn backspace_removes_whole_grapheme() {
+        // "e" followed by U+0301 COMBINING ACUTE ACCENT is one grapheme.
+        let mut text = "ae\u{301}".to_string();
+        let mut cursor = text.len();
+        backspace(&mut text, &mut cursor);
+        assert_eq!(text, "a");
+        assert_eq!(cursor, 1);
+    }
+
+    #[test]
+    fn left_right_step_over_graphemes() {
+        let text = "a中b";
+        let mut cursor = text.len();
+        move_left(text, &mut cursor);
+        assert_eq!(cursor, 4);
+        move_left(text, &mut cursor);
+        assert_eq!(cursor, 1);
+        move_right(text, &mut cursor);
+        assert_eq!(cursor, 4);
+        move_right(text, &mut cursor);
+        move_right(text, &mut cursor);
+        assert_eq!(cursor, text.len());
+    }
+
+    #[test]
+    fn newline_chords() {
+        use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+        let k = |code, mods| KeyEvent::new(code, mods);
+        assert!(is_newline_chord(&k(KeyCode::Enter, KeyModifiers::SHIFT)));
+        assert!(is_newline_chord(&k(KeyCode::Enter, KeyModifiers::CONTROL)));
+        assert!(is_newline_chord(&k(KeyCode::Enter, KeyModifiers::ALT)));
+        assert!(is_newline_chord(&k(KeyCode::Char('j'), KeyModifiers::CONTROL)));
+        assert!(!is_newline_chord(&k(KeyCode::Enter, KeyModifiers::NONE)));
+        assert!(!is_newline_chord(&k(KeyCode::Char('j'), KeyModifiers::NONE)));
+    }
+
+    #[test]
+    fn cursor_column_counts_wide_chars() {
+        let text = "中文ab";
+        assert_eq!(cursor_column(text, 6), 4);
+        assert_eq!(cursor_column(text, text.len()), 6);
+        assert_eq!(cursor_column("e\u{301}", 3), 1);
+    }
+}
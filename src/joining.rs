@@ -0,0 +1,126 @@
+//! The join phase: noticing when a room we joined stays empty.
+//!
+//! After the join screen (`welcome::run_join_screen`) lets us into the chat,
+//! gossip still has to find a neighbor. If none turns up within
+//! `join_timeout_secs` (config, default `DEFAULT_JOIN_TIMEOUT_SECS`) an
+//! overlay says so and offers what to do next, instead of the chat sitting
+//! silently at "waiting for peers...":
+//!
+//! ```text
+//! Idle ──join──► Waiting ──timeout, no peers──► Stalled (overlay)
+//!                   │  ▲                          │
+//!             a peer│  └──r retry / e edit / d ───┤
+//!                   ▼                             │
+//!                 Idle ◄──────w keep waiting──────┘
+//! ```
+//!
+//! Rooms we created and DMs start `Idle`: there's nobody to wait for yet.
+
+use ratatui::layout::Rect;
+use ratatui::style::{Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Paragraph};
+
+use crate::i18n::Lang;
+use crate::t;
+use crate::theme::Theme;
+
+/// Default for config `join_timeout_secs`.
+pub const DEFAULT_JOIN_TIMEOUT_SECS: u64 = 30;
+
+/// Where joining the room stands.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum JoinPhase {
+    /// Not watching: we made the room, someone has connected, or the user
+    /// chose to keep waiting.
+    #[default]
+    Idle,
+    /// Joined at `since_ms` and waiting for a first neighbor.
+    Waiting { since_ms: u64 },
+    /// Nobody came in time; the overlay is up.
+    Stalled { since_ms: u64 },
+}
+
+impl JoinPhase {
+    /// Start (or restart) waiting at `now_ms`, unless `timeout_secs` is 0.
+    pub fn wait(timeout_secs: u64, now_ms: u64) -> Self {
+        if timeout_secs == 0 { JoinPhase::Idle } else { JoinPhase::Waiting { since_ms: now_ms } }
+    }
+
+    /// Move on at `now_ms`: a `connected` peer ends the wait, and running
+    /// out of `timeout_secs` stalls it.
+    pub fn tick(&mut self, connected: bool, timeout_secs: u64, now_ms: u64) {
+        match *self {
+            _ if connected => *self = JoinPhase::Idle,
+            JoinPhase::Waiting { since_ms } if now_ms.saturating_sub(since_ms) >= timeout_secs * 1000 => {
+                *self = JoinPhase::Stalled { since_ms };
+            }
+            _ => {}
+        }
+    }
+
+    pub fn stalled(&self) -> bool {
+        matches!(self, JoinPhase::Stalled { .. })
+    }
+}
+
+/// Draw the overlay for a stalled join, centered over the chat.
+pub fn render_overlay(f: &mut ratatui::Frame, phase: JoinPhase, now_ms: u64, lang: Lang, theme: &Theme) {
+    let JoinPhase::Stalled { since_ms } = phase else { return };
+
+    let area = f.area();
+    let width = 56.min(area.width);
+    let card = Rect::new(area.width.saturating_sub(width) / 2, area.height.saturating_sub(9) / 2, width, 9.min(area.height));
+    f.render_widget(ratatui::widgets::Clear, card);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_set(theme.border_set())
+        .style(Style::default().bg(theme.bg))
+        .border_style(Style::default().fg(theme.title))
+        .title(Line::styled(format!(" {} ", t!(lang, "joining.title")), Style::default().fg(theme.title).add_modifier(Modifier::BOLD)).centered());
+    let secs = now_ms.saturating_sub(since_ms) / 1000;
+    let option = |key: &'static str, label: String| {
+        Line::from(vec![
+            Span::styled(format!("  {key}  "), Style::default().fg(theme.hint_key).add_modifier(Modifier::BOLD)),
+            Span::styled(label, Style::default().fg(theme.text)),
+        ])
+    };
+    let lines = vec![
+        Line::styled(t!(lang, "joining.nobody", secs = secs), Style::default().fg(theme.text)).centered(),
+        Line::from(""),
+        option("r", t!(lang, "joining.retry")),
+        option("e", t!(lang, "joining.edit")),
+        option("w", t!(lang, "joining.wait")),
+        option("d", t!(lang, "joining.diagnose")),
+    ];
+    f.render_widget(Paragraph::new(lines).block(block), card);
+}
+
+// ── Tests ────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn waiting_stalls_after_the_timeout_unless_someone_came() {
+        let mut phase = JoinPhase::wait(30, 1_000);
+        phase.tick(false, 30, 30_000);
+        assert_eq!(phase, JoinPhase::Waiting { since_ms: 1_000 });
+        phase.tick(false, 30, 31_000);
+        assert!(phase.stalled());
+        // A late neighbor still clears the overlay.
+        phase.tick(true, 30, 32_000);
+        assert_eq!(phase, JoinPhase::Idle);
+        assert_eq!(JoinPhase::wait(0, 1_000), JoinPhase::Idle);
+    }
+
+    #[test]
+    fn snapshot_stalled_overlay() {
+        let theme = Theme::dark();
+        let mut terminal = ratatui::Terminal::new(ratatui::backend::TestBackend::new(60, 11)).unwrap();
+        let phase = JoinPhase::Stalled { since_ms: 0 };
+        terminal.draw(|f| render_overlay(f, phase, 42_000, Lang::En, &theme)).unwrap();
+        insta::assert_snapshot!(terminal.backend());
+    }
+}
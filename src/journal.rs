@@ -0,0 +1,299 @@
+//! Write-behind journal of a room's history, for crash recovery.
+//!
+//! The archive in `rooms` is only written when we leave a room, so a crash
+//! (or a killed terminal) would lose everything said since joining. While in
+//! a room, every new `HistoryEntry` is also handed to a `HistoryWriter`,
+//! which appends it to a journal on a dedicated blocking thread — the event
+//! loop only pushes onto a channel and never waits for the disk.
+//!
+//! ## On-disk format
+//!
+//! A room's journal is a directory of numbered segment files
+//! (`00000001.seg`, `00000002.seg`, …). Each segment is a run of records:
+//!
+//! ```text
+//! [len: u32 LE][checksum: u32 LE][payload: `len` bytes of postcard HistoryEntry]
+//! ```
+//!
+//! The checksum (FNV-1a over the payload) catches a record torn by a crash
+//! mid-write; `replay` stops reading a segment at the first bad record and
+//! moves on to the next. A writer never appends to an existing segment — it
+//! starts a new one — so a torn tail can't have good records written after
+//! it. Segments roll over at `SEGMENT_BYTES`.
+//!
+//! On a clean exit the room is archived and its journal removed (`clear`);
+//! on the next join, anything left over is replayed on top of the archive.
+
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+
+use crate::error::{self, ResultExt};
+use crate::net::HistoryEntry;
+
+/// Size at which the writer moves on to a new segment file.
+const SEGMENT_BYTES: u64 = 256 * 1024;
+
+/// Most entries written together in one batch.
+const BATCH_MAX: usize = 64;
+
+/// How often `SyncPolicy::Periodic` calls fsync.
+const SYNC_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Bytes before each record's payload: length and checksum.
+const RECORD_HEADER: usize = 8;
+
+/// When the writer forces journal data onto the disk with fsync.
+///
+/// Without fsync, written data sits in the OS page cache: it survives the
+/// app crashing but not the machine losing power.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SyncPolicy {
+    /// Leave it to the OS.
+    Never,
+    /// After every batch of writes. Chat is low-volume, so this is cheap.
+    #[default]
+    Batch,
+    /// At most once per second.
+    Periodic,
+}
+
+/// Requests from the event loop to the writer thread.
+enum Command {
+    Append(HistoryEntry),
+    /// Write and fsync everything queued so far, then reply.
+    Flush(oneshot::Sender<()>),
+}
+
+/// Handle to a room's background journal writer.
+///
+/// `append` never blocks. Dropping the handle lets the writer finish what's
+/// queued and exit; `shutdown` does the same but waits for it.
+pub struct HistoryWriter {
+    tx: mpsc::UnboundedSender<Command>,
+    task: JoinHandle<Result<()>>,
+}
+
+impl HistoryWriter {
+    /// Start a writer for the journal in `dir`. Must be called from within
+    /// the tokio runtime.
+    pub fn spawn(dir: PathBuf, policy: SyncPolicy) -> Self {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let task = tokio::task::spawn_blocking(move || write_loop(&dir, policy, rx));
+        Self { tx, task }
+    }
+
+    /// Queue `entry` to be journaled.
+    pub fn append(&self, entry: HistoryEntry) {
+        // A send only fails if the writer died on an I/O error; that
+        // surfaces from `shutdown`, and the chat itself carries on.
+        let _ = self.tx.send(Command::Append(entry));
+    }
+
+    /// Wait until everything queued so far is on disk.
+    pub async fn flush(&self) -> error::Result<()> {
+        let (done, wait) = oneshot::channel();
+        self.tx.send(Command::Flush(done)).ok().context("journal writer stopped").storage()?;
+        wait.await.context("journal writer stopped").storage()
+    }
+
+    /// Flush, stop the writer and report any error it hit.
+    pub async fn shutdown(self) -> error::Result<()> {
+        let flushed = self.flush().await;
+        drop(self.tx);
+        self.task.await.storage()?.storage()?;
+        flushed
+    }
+}
+
+/// The writer thread: batch up commands, append them, sync per `policy`.
+fn write_loop(dir: &Path, policy: SyncPolicy, mut rx: mpsc::UnboundedReceiver<Command>) -> Result<()> {
+    std::fs::create_dir_all(dir)?;
+    let mut seq = segments(dir)?.last().map_or(0, |(n, _)| *n);
+    let mut file: Option<File> = None;
+    let mut written = 0u64;
+    let mut last_sync = Instant::now();
+    let mut buf = Vec::new();
+    let mut waiting = Vec::new();
+    while let Some(first) = rx.blocking_recv() {
+        // Take whatever else is already queued, up to a batch.
+        let mut batch = vec![first];
+        while batch.len() < BATCH_MAX {
+            match rx.try_recv() {
+                Ok(cmd) => batch.push(cmd),
+                Err(_) => break,
+            }
+        }
+        buf.clear();
+        for cmd in batch {
+            match cmd {
+                Command::Append(entry) => encode_record(&mut buf, &entry)?,
+                Command::Flush(done) => waiting.push(done),
+            }
+        }
+        if !buf.is_empty() {
+            if file.is_none() || written >= SEGMENT_BYTES {
+                seq += 1;
+                file = Some(OpenOptions::new().create_new(true).append(true).open(segment_path(dir, seq))?);
+                written = 0;
+            }
+            let f = file.as_mut().expect("segment opened above");
+            f.write_all(&buf)?;
+            written += buf.len() as u64;
+        }
+        let sync = !waiting.is_empty()
+            || match policy {
+                SyncPolicy::Never => false,
+                SyncPolicy::Batch => !buf.is_empty(),
+                SyncPolicy::Periodic => last_sync.elapsed() >= SYNC_INTERVAL,
+            };
+        if sync && let Some(f) = &file {
+            f.sync_data()?;
+            last_sync = Instant::now();
+        }
+        for done in waiting.drain(..) {
+            let _ = done.send(());
+        }
+    }
+    if let Some(f) = &file {
+        f.sync_data()?;
+    }
+    Ok(())
+}
+
+/// Append one framed record for `entry` to `buf`.
+fn encode_record(buf: &mut Vec<u8>, entry: &HistoryEntry) -> Result<()> {
+    let payload = postcard::to_stdvec(entry)?;
+    buf.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    buf.extend_from_slice(&checksum(&payload).to_le_bytes());
+    buf.extend_from_slice(&payload);
+    Ok(())
+}
+
+/// 32-bit FNV-1a: tiny and good enough to spot a torn write.
+fn checksum(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x811c_9dc5, |hash, b| (hash ^ *b as u32).wrapping_mul(0x0100_0193))
+}
+
+fn segment_path(dir: &Path, seq: u64) -> PathBuf {
+    dir.join(format!("{seq:08}.seg"))
+}
+
+/// Segment files in `dir`, oldest first.
+fn segments(dir: &Path) -> Result<Vec<(u64, PathBuf)>> {
+    let mut found = Vec::new();
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(found),
+        Err(e) => return Err(e.into()),
+    };
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().is_some_and(|e| e == "seg")
+            && let Some(seq) = path.file_stem().and_then(|s| s.to_str()?.parse().ok())
+        {
+            found.push((seq, path));
+        }
+    }
+    found.sort();
+    Ok(found)
+}
+
+/// Read back every intact entry journaled in `dir`, oldest first.
+pub fn replay(dir: &Path) -> Vec<HistoryEntry> {
+    let mut entries = Vec::new();
+    for (_, path) in segments(dir).unwrap_or_default() {
+        let Ok(bytes) = std::fs::read(&path) else { continue };
+        let mut rest = &bytes[..];
+        while rest.len() >= RECORD_HEADER {
+            let len = u32::from_le_bytes(rest[..4].try_into().unwrap()) as usize;
+            let sum = u32::from_le_bytes(rest[4..8].try_into().unwrap());
+            let Some(payload) = rest.get(RECORD_HEADER..RECORD_HEADER + len) else { break };
+            if checksum(payload) != sum {
+                break;
+            }
+            let Ok(entry) = postcard::from_bytes(payload) else { break };
+            entries.push(entry);
+            rest = &rest[RECORD_HEADER + len..];
+        }
+    }
+    entries
+}
+
+/// Remove the journal in `dir` once its contents are safely archived.
+pub fn clear(dir: &Path) -> error::Result<()> {
+    match std::fs::remove_dir_all(dir) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e).storage(),
+        _ => Ok(()),
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net::HistoryEntryKind;
+
+    fn entry(id: u8) -> HistoryEntry {
+        HistoryEntry {
+            message_id: [id; 16],
+            timestamp_ms: id as u64,
+            kind: HistoryEntryKind::System(format!("entry {id}")),
+        }
+    }
+
+    #[tokio::test]
+    async fn writes_survive_a_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let writer = HistoryWriter::spawn(dir.path().into(), SyncPolicy::Never);
+        writer.append(entry(1));
+        writer.append(entry(2));
+        writer.flush().await.unwrap();
+        assert_eq!(replay(dir.path()).len(), 2);
+        writer.append(entry(3));
+        writer.shutdown().await.unwrap();
+
+        // A second writer starts a new segment after the first.
+        let writer = HistoryWriter::spawn(dir.path().into(), SyncPolicy::Batch);
+        writer.append(entry(4));
+        writer.shutdown().await.unwrap();
+        assert_eq!(segments(dir.path()).unwrap().len(), 2);
+        let ids: Vec<u8> = replay(dir.path()).iter().map(|e| e.message_id[0]).collect();
+        assert_eq!(ids, [1, 2, 3, 4]);
+
+        clear(dir.path()).unwrap();
+        assert!(replay(dir.path()).is_empty());
+    }
+
+    #[test]
+    fn torn_record_ends_its_segment() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut buf = Vec::new();
+        encode_record(&mut buf, &entry(1)).unwrap();
+        let good = buf.len();
+        encode_record(&mut buf, &entry(2)).unwrap();
+        // Simulate a crash halfway through writing the second record.
+        buf.truncate(good + (buf.len() - good) / 2);
+        std::fs::write(segment_path(dir.path(), 1), &buf).unwrap();
+        // A corrupted record is rejected by its checksum.
+        let mut bad = Vec::new();
+        encode_record(&mut bad, &entry(3)).unwrap();
+        *bad.last_mut().unwrap() ^= 0xff;
+        encode_record(&mut bad, &entry(4)).unwrap();
+        std::fs::write(segment_path(dir.path(), 2), &bad).unwrap();
+        let mut later = Vec::new();
+        encode_record(&mut later, &entry(5)).unwrap();
+        std::fs::write(segment_path(dir.path(), 3), &later).unwrap();
+
+        let ids: Vec<u8> = replay(dir.path()).iter().map(|e| e.message_id[0]).collect();
+        assert_eq!(ids, [1, 5]);
+    }
+}
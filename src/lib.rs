@@ -0,0 +1,109 @@
+//! piper-chat — P2P terminal chat over iroh gossip.
+//!
+//! This is the library root. It declares the module tree shared by the
+//! `piper-chat` binary (`main.rs`) and the integration tests in `tests/`.
+//!
+//! ## Module structure
+//!
+//! - `access`     — Who may offer us files and download ours
+//! - `admission`  — Room member cap and knock-to-join approvals
+//! - `blobstore`  — Blob store backends: on disk, or in memory (`--mem-store`)
+//! - `net`        — Wire protocol, tickets, and connection tracking
+//! - `node`       — Endpoint + gossip + blobs setup, file share/download
+//! - `welcome`    — Interactive welcome screen (room setup form)
+//! - `chat`       — Chat UI state (`App`) and rendering (`ui()`)
+//! - `command`    — Slash command table, argument parsing, Tab completion
+//! - `transfer`   — File transfer state machine and file share pane
+//! - `filepicker` — Modal file picker overlay
+//! - `gallery`    — `/files` overlay: every file offered in the room
+//! - `error`      — Library error type (network / ticket / storage / …)
+//! - `eventlog`   — `--json-log`: significant events as JSON lines
+//! - `replay`     — `piper-chat replay`: play a `--json-log` back through the UI
+//! - `export`     — Transcript export as text or standalone HTML
+//! - `filter`     — Pluggable filters that rewrite or drop chat text
+//! - `fit`        — Clamp names to a column width with an ellipsis
+//! - `highlight`  — Fenced code blocks and syntax highlighting
+//! - `hooks`      — User commands run on chat events (config `[hooks]`)
+//! - `i18n`       — Translated UI text (`t!`) and the `lang` setting
+//! - `joining`    — Join phase and the overlay for a room that stays empty
+//! - `journal`    — Write-behind history journal for crash recovery
+//! - `lock`       — `/lock` and idle lock behind a local passphrase
+//! - `pack`       — Pack a folder into a tar archive for sharing
+//! - `palette`    — Ctrl+K command palette with fuzzy filtering
+//! - `proxy`      — SOCKS5 / HTTP proxy support for relay connections
+//! - `metrics`    — Counters for monitoring, in Prometheus format
+//! - `map`        — `/map` overlay: live picture of our gossip connections
+//! - `input`      — UTF-8 aware line-editing helpers
+//! - `clipboard`  — OSC 52 copy and system clipboard paste
+//! - `config`     — Persistent user settings (TOML)
+//! - `contacts`   — Address book of peers met in rooms
+//! - `drafts`     — Unsent input saved per room across restarts
+//! - `prefs`      — Per-room `/mute` and `/focus` settings
+//! - `profile`    — `--profile`: separate config and data dirs per persona
+//! - `rooms`      — Archive of past rooms and their history, for rejoining
+//! - `sanitize`   — Strip escapes and control characters from peers' text
+//! - `scratch`    — Temporary share artifacts and scratch stores, cleaned up
+//! - `seal`       — Encrypt shared files with a per-offer key
+//! - `send`       — One-shot mode: post a message from a script and exit
+//! - `session`    — Session state saved for recovery after a crash
+//! - `share`      — File drop mode: offer a file from a script and serve it
+//! - `spell`      — Word-list spellcheck for the input bar
+//! - `tail`       — Watch mode: print a room to stdout without the TUI
+//! - `timers`     — Shared `/timer` countdowns
+//! - `webhook`    — Bridge a room to and from an HTTP endpoint
+
+// ── Module declarations ─────────────────────────────────────────────────────
+// `mod` declarations tell Rust to look for a file named `<name>.rs` (or
+// `<name>/mod.rs`) in the `src/` directory and include it as a child module.
+// Modules form a tree rooted at `lib.rs` (for libraries) or `main.rs` (for
+// binaries). `pub mod` makes them reachable from the binary and tests, which
+// link against this library as the external crate `piper_chat`.
+pub mod access;
+pub mod admission;
+pub mod blobstore;
+pub mod chat;
+pub mod checksum;
+pub mod clipboard;
+pub mod command;
+pub mod config;
+pub mod contacts;
+pub mod drafts;
+pub mod error;
+pub mod eventlog;
+pub mod replay;
+pub mod export;
+pub mod filepicker;
+pub mod filter;
+pub mod gallery;
+pub mod fit;
+pub mod highlight;
+pub mod hooks;
+pub mod i18n;
+pub mod input;
+pub mod joining;
+pub mod journal;
+pub mod lock;
+pub mod map;
+pub mod metrics;
+pub mod net;
+pub mod pack;
+pub mod palette;
+pub mod prefs;
+pub mod profile;
+pub mod node;
+pub mod proxy;
+pub mod rooms;
+pub mod sanitize;
+pub mod scratch;
+pub mod seal;
+pub mod send;
+pub mod session;
+pub mod share;
+pub mod spell;
+pub mod tail;
+pub mod theme;
+pub mod timers;
+pub mod toast;
+pub mod transfer;
+pub mod webhook;
+pub mod welcome;
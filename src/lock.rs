@@ -0,0 +1,296 @@
+//! Session lock: `/lock` and the idle timeout.
+//!
+//! A locked session blanks the whole screen behind a passphrase prompt, so
+//! a terminal left alone doesn't show the conversation. Everything else
+//! carries on underneath: messages arrive, transfers run, the history grows.
+//! Only the prompt takes keys; mouse and paste events are dropped.
+//!
+//! The passphrase is local to this machine and never leaves it: config
+//! keeps only an Argon2 hash of it (`lock_passphrase`, a PHC string). The
+//! first `/lock` asks for one, twice, and saves the hash. With
+//! `idle_lock_secs` set and a passphrase chosen, the session also locks
+//! itself after that long without a key press.
+//!
+//! Argon2 is slow on purpose, so the lock never runs it itself: Enter hands
+//! back `Outcome::Work`, the caller does it on a blocking thread (`run`),
+//! and the result goes to `Lock::done`. Keys are ignored in between.
+//!
+//! ```text
+//! /lock (no passphrase yet) ─► Choose ─Enter─► Confirm ─match─► Locked
+//!                                 ▲               │               │
+//!                                 └───mismatch────┘       right passphrase
+//! /lock or idle ─────────────────────────────────────────►        ▼
+//!                                                            (unlocked)
+//! ```
+
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::layout::Rect;
+use ratatui::style::{Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Borders, Clear, Paragraph};
+
+use crate::chat::format_timestamp;
+use crate::i18n::Lang;
+use crate::t;
+use crate::theme::Theme;
+
+/// The PHC hash of `passphrase`, for config `lock_passphrase`.
+pub fn hash(passphrase: &str) -> String {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(passphrase.as_bytes(), &salt)
+        .expect("default Argon2 parameters always hash")
+        .to_string()
+}
+
+/// Whether `passphrase` matches the PHC `hash`. A hash that doesn't parse
+/// matches nothing.
+pub fn verify(hash: &str, passphrase: &str) -> bool {
+    PasswordHash::new(hash).is_ok_and(|h| Argon2::default().verify_password(passphrase.as_bytes(), &h).is_ok())
+}
+
+/// Where the lock screen is.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Stage {
+    /// Picking a passphrase; `mismatch` after a confirmation that differed.
+    Choose { mismatch: bool },
+    /// Typing it again.
+    Confirm(String),
+    Locked,
+}
+
+/// What a key did to the lock.
+#[derive(Debug, PartialEq)]
+pub enum Outcome {
+    Typing,
+    /// Esc while choosing a passphrase: nothing was locked.
+    Cancelled,
+    /// Argon2 to run off the event loop; its result goes to `Lock::done`.
+    Work(Work),
+    /// A passphrase was chosen; here's its hash to save. Now locked.
+    Chosen(String),
+    Unlocked,
+}
+
+/// A passphrase to hash or check, for `run`.
+#[derive(Debug, PartialEq)]
+pub enum Work {
+    Hash(String),
+    Verify { hash: String, passphrase: String },
+}
+
+/// The result of `run`.
+#[derive(Debug)]
+pub enum Done {
+    Hashed(String),
+    Verified(bool),
+}
+
+/// Do the Argon2 `work`. Slow: call it from a blocking thread.
+pub fn run(work: Work) -> Done {
+    match work {
+        Work::Hash(passphrase) => Done::Hashed(hash(&passphrase)),
+        Work::Verify { hash, passphrase } => Done::Verified(verify(&hash, &passphrase)),
+    }
+}
+
+/// A locked session (or one about to be).
+#[derive(Debug)]
+pub struct Lock {
+    pub stage: Stage,
+    input: String,
+    /// Wrong passphrases tried so far.
+    pub wrong: u32,
+    /// When it locked, for the prompt.
+    pub since_ms: u64,
+    /// Waiting for `done` after handing out `Outcome::Work`.
+    pub checking: bool,
+}
+
+impl Lock {
+    /// Lock at `now_ms`, or first choose a passphrase if none is `set`.
+    pub fn new(set: bool, now_ms: u64) -> Self {
+        let stage = if set { Stage::Locked } else { Stage::Choose { mismatch: false } };
+        Self { stage, input: String::new(), wrong: 0, since_ms: now_ms, checking: false }
+    }
+
+    /// Whether the screen is actually locked (not still choosing).
+    pub fn locked(&self) -> bool {
+        self.stage == Stage::Locked
+    }
+
+    /// Handle a key, checking a passphrase against the `stored` hash.
+    /// Ctrl and Alt chords aren't typing, so they never reach the field.
+    pub fn key(&mut self, key: &KeyEvent, stored: Option<&str>) -> Outcome {
+        if self.checking {
+            return Outcome::Typing;
+        }
+        match key.code {
+            KeyCode::Char(_) if key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) => {}
+            KeyCode::Char(c) => self.input.push(c),
+            KeyCode::Backspace => {
+                self.input.pop();
+            }
+            KeyCode::Esc if !self.locked() => return Outcome::Cancelled,
+            KeyCode::Esc => self.input.clear(),
+            KeyCode::Enter => return self.enter(stored),
+            _ => {}
+        }
+        Outcome::Typing
+    }
+
+    fn enter(&mut self, stored: Option<&str>) -> Outcome {
+        let input = std::mem::take(&mut self.input);
+        match &self.stage {
+            Stage::Choose { .. } if input.is_empty() => Outcome::Typing,
+            Stage::Choose { .. } => {
+                self.stage = Stage::Confirm(input);
+                Outcome::Typing
+            }
+            Stage::Confirm(first) if *first == input => {
+                self.checking = true;
+                Outcome::Work(Work::Hash(input))
+            }
+            Stage::Confirm(_) => {
+                self.stage = Stage::Choose { mismatch: true };
+                Outcome::Typing
+            }
+            Stage::Locked => match stored {
+                Some(hash) => {
+                    self.checking = true;
+                    Outcome::Work(Work::Verify { hash: hash.to_string(), passphrase: input })
+                }
+                None => self.done(Done::Verified(false)),
+            },
+        }
+    }
+
+    /// Take the result of the `Work` handed out by `key`.
+    pub fn done(&mut self, done: Done) -> Outcome {
+        self.checking = false;
+        match done {
+            Done::Hashed(hash) => {
+                self.stage = Stage::Locked;
+                Outcome::Chosen(hash)
+            }
+            Done::Verified(true) => Outcome::Unlocked,
+            Done::Verified(false) => {
+                self.wrong += 1;
+                Outcome::Typing
+            }
+        }
+    }
+}
+
+/// Blank the whole frame and draw the prompt; `new` messages arrived since
+/// it locked. Nothing is drawn without a lock.
+pub fn render(f: &mut ratatui::Frame, lock: Option<&Lock>, new: usize, lang: Lang, theme: &Theme) {
+    let Some(lock) = lock else { return };
+
+    let area = f.area();
+    f.render_widget(Clear, area);
+    f.render_widget(Block::default().style(Style::default().bg(theme.bg)), area);
+
+    let width = 44.min(area.width);
+    let card = Rect::new(area.width.saturating_sub(width) / 2, area.height.saturating_sub(7) / 2, width, 7.min(area.height));
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_set(theme.border_set())
+        .border_style(Style::default().fg(theme.border_focused))
+        .title(Line::styled(format!(" {} ", t!(lang, "lock.title")), Style::default().fg(theme.title).add_modifier(Modifier::BOLD)).centered());
+    let (prompt, note) = match &lock.stage {
+        Stage::Choose { mismatch } => (
+            t!(lang, "lock.choose"),
+            if *mismatch { t!(lang, "lock.mismatch") } else { t!(lang, "lock.choose_hint") },
+        ),
+        Stage::Confirm(_) => (t!(lang, "lock.confirm"), t!(lang, "lock.choose_hint")),
+        Stage::Locked if lock.wrong > 0 => (t!(lang, "lock.prompt"), t!(lang, "lock.wrong", count = lock.wrong)),
+        Stage::Locked => (
+            t!(lang, "lock.prompt"),
+            t!(lang, "lock.since", time = format_timestamp(lock.since_ms), count = new),
+        ),
+    };
+    let note = if lock.checking { t!(lang, "lock.checking") } else { note };
+    let mask: String = "•".repeat(lock.input.chars().count());
+    let lines = vec![
+        Line::styled(prompt, Style::default().fg(theme.text)).centered(),
+        Line::from(""),
+        Line::styled(format!("{mask}▏"), Style::default().fg(theme.accent)).centered(),
+        Line::from(""),
+        Line::styled(note, Style::default().fg(theme.text_muted)).centered(),
+    ];
+    f.render_widget(Paragraph::new(lines).block(block), card);
+}
+
+// ── Tests ────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::event::KeyModifiers;
+
+    /// Type `text` and press Enter, doing any Argon2 work right away as the
+    /// event loop would in the background.
+    fn type_in(lock: &mut Lock, text: &str, stored: Option<&str>) -> Outcome {
+        for c in text.chars() {
+            lock.key(&KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE), stored);
+        }
+        match lock.key(&KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE), stored) {
+            Outcome::Work(work) => lock.done(run(work)),
+            outcome => outcome,
+        }
+    }
+
+    #[test]
+    fn choosing_confirms_then_only_the_passphrase_unlocks() {
+        let mut lock = Lock::new(false, 0);
+        assert_eq!(type_in(&mut lock, "tea", None), Outcome::Typing);
+        assert_eq!(type_in(&mut lock, "coffee", None), Outcome::Typing);
+        assert_eq!(lock.stage, Stage::Choose { mismatch: true });
+        type_in(&mut lock, "tea", None);
+        let Outcome::Chosen(stored) = type_in(&mut lock, "tea", None) else { panic!("expected a hash") };
+        assert!(lock.locked() && !stored.contains("tea"));
+
+        assert_eq!(type_in(&mut lock, "coffee", Some(&stored)), Outcome::Typing);
+        assert_eq!(lock.wrong, 1);
+        // Esc clears the field rather than unlocking.
+        lock.key(&KeyEvent::new(KeyCode::Char('x'), KeyModifiers::NONE), Some(&stored));
+        lock.key(&KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE), Some(&stored));
+        assert_eq!(type_in(&mut lock, "tea", Some(&stored)), Outcome::Unlocked);
+        assert!(!verify("not a hash", "tea"));
+    }
+
+    #[test]
+    fn chords_are_not_typed_and_keys_wait_for_the_check() {
+        let stored = hash("tea");
+        let mut lock = Lock::new(true, 0);
+        for (c, modifiers) in [('c', KeyModifiers::CONTROL), ('t', KeyModifiers::NONE), ('x', KeyModifiers::ALT)] {
+            lock.key(&KeyEvent::new(KeyCode::Char(c), modifiers), Some(&stored));
+        }
+        assert_eq!(lock.input, "t");
+        lock.key(&KeyEvent::new(KeyCode::Char('e'), KeyModifiers::NONE), Some(&stored));
+        lock.key(&KeyEvent::new(KeyCode::Char('a'), KeyModifiers::SHIFT), Some(&stored));
+        let Outcome::Work(work) = lock.key(&KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE), Some(&stored)) else {
+            panic!("expected work for the event loop");
+        };
+        assert!(lock.checking);
+        // Nothing is typed while the check runs.
+        assert_eq!(lock.key(&KeyEvent::new(KeyCode::Char('z'), KeyModifiers::NONE), Some(&stored)), Outcome::Typing);
+        assert!(lock.input.is_empty());
+        assert_eq!(lock.done(run(work)), Outcome::Unlocked);
+        assert!(!lock.checking);
+    }
+
+    #[test]
+    fn snapshot_locked_screen() {
+        let theme = Theme::dark();
+        let mut terminal = ratatui::Terminal::new(ratatui::backend::TestBackend::new(50, 9)).unwrap();
+        let mut lock = Lock::new(true, 0);
+        lock.input = "secret".into();
+        terminal.draw(|f| render(f, Some(&lock), 3, Lang::En, &theme)).unwrap();
+        insta::assert_snapshot!(terminal.backend());
+    }
+}
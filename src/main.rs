@@ -1,26 +1,8 @@
 //! piper-chat — P2P terminal chat over iroh gossip.
 //!
-//! This is the crate root. It declares the module tree, defines the CLI, and
-//! runs the main event loop that ties networking, input, and rendering together.
-//!
-//! ## Module structure
-//!
-//! - `net`        — Wire protocol, tickets, and connection tracking
-//! - `welcome`    — Interactive welcome screen (room setup form)
-//! - `chat`       — Chat UI state (`App`) and rendering (`ui()`)
-//! - `transfer`   — File transfer state machine and file share pane
-//! - `filepicker` — Modal file picker overlay
-
-// ── Module declarations ─────────────────────────────────────────────────────
-// `mod` declarations tell Rust to look for a file named `<name>.rs` (or
-// `<name>/mod.rs`) in the `src/` directory and include it as a child module.
-// Modules form a tree rooted at `main.rs` (for binaries) or `lib.rs` (for libraries).
-mod chat;
-mod filepicker;
-mod net;
-mod theme;
-mod transfer;
-mod welcome;
+//! This is the binary entry point. It defines the CLI and runs the main event
+//! loop that ties networking, input, and rendering together. The modules it
+//! drives live in the `piper_chat` library (see `lib.rs`).
 
 // ── Imports ─────────────────────────────────────────────────────────────────
 
@@ -32,7 +14,7 @@ use std::path::PathBuf;
 // `anyhow::Result` is a type alias for `Result<T, anyhow::Error>`. It lets
 // you use `?` to propagate errors of any type that implements `std::error::Error`,
 // without defining custom error enums for a small application.
-use anyhow::Result;
+use anyhow::{Context, Result};
 // `clap::Parser` is a derive macro that generates a CLI argument parser from
 // struct/enum definitions. It reads `#[arg(...)]` and `#[command(...)]` attributes
 // to configure flags, subcommands, help text, etc.
@@ -47,28 +29,23 @@ use clap::Parser;
 //   buffer so the original scrollback is preserved when the app exits
 use crossterm::{
     event::{
-        DisableMouseCapture, EnableMouseCapture, Event as TermEvent, EventStream, KeyCode,
-        KeyEventKind, KeyModifiers, MouseButton, MouseEventKind,
+        DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture,
+        Event as TermEvent, EventStream, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseButton,
+        MouseEventKind,
     },
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 // `iroh_blobs` — content-addressed blob storage and streaming transfers:
-// - `FsStore`: persists blobs to disk using the `redb` embedded database
-// - `BlobsProtocol`: protocol handler that serves blobs to connecting peers
 // - `Hash`: a BLAKE3 content hash — the universal identifier for blob content
 // - `HashAndFormat`: combines a `Hash` with a format flag (raw bytes vs hash-seq)
 // - `ALPN`: the Application-Layer Protocol Negotiation identifier for the blobs
 //   protocol (tells QUIC which protocol handler should receive a connection)
-use iroh_blobs::{store::fs::FsStore, BlobsProtocol, Hash, HashAndFormat, ALPN as BLOBS_ALPN};
-// `iroh_gossip` — pub-sub messaging over iroh connections:
-// - `GossipEvent`: events from a topic subscription (NeighborUp/Down, Received, etc.)
-// - `Gossip`: the gossip protocol instance — manages subscriptions and message routing
-// - `GOSSIP_ALPN`: the ALPN identifier for the gossip protocol
-use iroh_gossip::{
-    api::Event as GossipEvent,
-    net::{Gossip, GOSSIP_ALPN},
-};
+use iroh_blobs::{Hash, HashAndFormat, ALPN as BLOBS_ALPN};
+// `GossipEvent`: events from a topic subscription (NeighborUp/Down, Received, etc.);
+// `GossipMessage`/`DeliveryScope` let direct deliveries pose as `Received`.
+use iroh_gossip::api::{Event as GossipEvent, GossipReceiver, Message as GossipMessage};
+use iroh_gossip::proto::{DeliveryScope, TopicId};
 // `Ticket` trait from iroh — provides `serialize()`/`deserialize()` for base32
 // encoding. We use fully-qualified syntax `<ChatTicket as Ticket>::serialize()`
 // because the method name could be ambiguous.
@@ -82,13 +59,52 @@ use n0_future::StreamExt;
 // - `interval`: creates a recurring timer that yields on each tick
 use tokio::time::{Duration, interval};
 
-// Imports from our own crate modules — `use chat::App` brings `chat::App`
-// into scope so we can write `App` instead of `chat::App`.
-use chat::{ui, App, AppMode, ClickAction};
-use filepicker::FilePickerResult;
-use net::{ChatTicket, ConnType, Message, PeerInfo, new_message_id, now_ms};
-use transfer::{FileOffer, TransferEvent, TransferState};
-use welcome::{run_welcome_screen, WelcomeResult};
+// Imports from our library crate (`src/lib.rs`). Cargo names it after the
+// package with `-` replaced by `_`, so `piper-chat` becomes `piper_chat`.
+use piper_chat::access::{Members, ServePolicy};
+use piper_chat::blobstore::{Blobs, Invalid, Storage};
+use piper_chat::admission::{Admission, Knock};
+use piper_chat::chat::{self, ui, App, AppMode, ClickAction, Highlights};
+use piper_chat::joining::JoinPhase;
+use piper_chat::lock::{self, Lock};
+use piper_chat::palette::{self, Outcome, Palette, Pick};
+use piper_chat::checksum;
+use piper_chat::clipboard;
+use piper_chat::command::{self, Completion};
+use piper_chat::config::Config;
+use piper_chat::contacts::Contacts;
+use piper_chat::drafts::Drafts;
+use piper_chat::eventlog::{EventLog, LogEvent};
+use piper_chat::prefs::Prefs;
+use piper_chat::profile;
+use piper_chat::export;
+use piper_chat::spell::Dictionary;
+use piper_chat::filepicker::FilePickerResult;
+use piper_chat::gallery;
+use piper_chat::filter::Filters;
+use piper_chat::hooks::HookEvent;
+use piper_chat::input;
+use piper_chat::t;
+use piper_chat::timers::Timer;
+use piper_chat::toast::ToastKind;
+use piper_chat::journal::{self, HistoryWriter};
+use piper_chat::metrics::METRICS;
+use piper_chat::net::direct::{DirectMessage, Outbox};
+use piper_chat::net::keepalive::Keepalive;
+use piper_chat::net::retry::RetryQueue;
+use piper_chat::net::wake::WakeDetector;
+use piper_chat::net::{self, ChatTicket, ConnType, Message, MessageSender, PeerInfo, RoomPolicy, new_message_id, now_ms};
+use piper_chat::node::{self, Node, OfferOptions, Paths, Sealing, Shared, Transport};
+use piper_chat::proxy::Proxy;
+use piper_chat::replay::{self, Player};
+use piper_chat::rooms::{self, Rooms};
+use piper_chat::scratch::{self, Artifacts, TempStore};
+use piper_chat::seal;
+use piper_chat::session::{self, Session};
+use piper_chat::theme::Theme;
+use piper_chat::transfer::{self, Confirm, EntryId, Expiry, FileOffer, Strategy, TransferEvent, TransferState};
+use piper_chat::webhook::{self, Webhook, WebhookEvent};
+use piper_chat::welcome::{self, run_welcome_screen, WelcomeResult};
 
 // ── CLI ──────────────────────────────────────────────────────────────────────
 
@@ -104,6 +120,51 @@ use welcome::{run_welcome_screen, WelcomeResult};
 struct Cli {
     #[command(subcommand)]
     command: Option<Command>,
+    /// Use a throwaway identity instead of the saved one (e.g. to run a
+    /// second instance on the same machine)
+    #[arg(long, global = true)]
+    ephemeral: bool,
+    /// POST each incoming chat message as JSON to this URL
+    #[arg(long, global = true, value_name = "URL")]
+    webhook: Option<String>,
+    /// Poll this URL for messages to send (a JSON array of strings)
+    #[arg(long, global = true, value_name = "URL")]
+    webhook_poll: Option<String>,
+    /// Draw with plain ASCII instead of box-drawing, block and symbol
+    /// characters (the default when the locale isn't UTF-8)
+    #[arg(long, global = true)]
+    ascii: bool,
+    /// Network paths to use: any, relay (hide your IP from peers) or
+    /// direct (no relay servers). Overrides `paths` in the config file
+    #[arg(long, global = true, value_name = "PATHS")]
+    paths: Option<Paths>,
+    /// Send relay traffic through a proxy: socks5://HOST:PORT (e.g. Tor on
+    /// 127.0.0.1:9050) or http://HOST:PORT. Implies `--paths relay`.
+    /// Overrides `proxy` in the config file
+    #[arg(long, global = true, value_name = "URL")]
+    proxy: Option<String>,
+    /// Serve Prometheus metrics over HTTP on 127.0.0.1:PORT
+    #[cfg(feature = "metrics")]
+    #[arg(long, global = true, value_name = "PORT")]
+    metrics_port: Option<u16>,
+    /// Write Prometheus metrics to this file every 15 seconds
+    #[cfg(feature = "metrics")]
+    #[arg(long, global = true, value_name = "PATH")]
+    metrics_file: Option<PathBuf>,
+    /// Append every message, join, leave, offer, transfer step and path
+    /// change to this file as JSON lines
+    #[arg(long, global = true, value_name = "PATH")]
+    json_log: Option<PathBuf>,
+    /// Keep blobs in memory instead of on disk: what we share or download
+    /// is gone when the session ends, and no crash recovery is saved.
+    /// Overrides `mem_store` in the config file
+    #[arg(long, global = true)]
+    mem_store: bool,
+    /// Run as this profile: an identity, config, contacts, rooms and blob
+    /// store of its own, apart from the default and other profiles.
+    /// Overrides `profile` in the config file
+    #[arg(long, global = true, value_name = "NAME", value_parser = profile_arg)]
+    profile: Option<String>,
 }
 
 /// Subcommands for the CLI. `#[derive(clap::Subcommand)]` generates the
@@ -118,6 +179,18 @@ enum Command {
         /// Your display name
         #[arg(short, long)]
         name: String,
+        /// Most members at once, yourself included
+        #[arg(long, value_name = "N")]
+        max_members: Option<u32>,
+        /// Newcomers must knock, and you let each one in (or not)
+        #[arg(long)]
+        knock: bool,
+        /// A name for the room, shown to everyone in it
+        #[arg(long, value_name = "NAME")]
+        room: Option<String>,
+        /// Stop the ticket letting people in after this long ("2h", "7d")
+        #[arg(long, value_name = "DURATION", value_parser = duration_arg)]
+        expires: Option<Duration>,
     },
     /// Join an existing chat room
     Join {
@@ -127,6 +200,90 @@ enum Command {
         /// Ticket string from the room creator
         ticket: String,
     },
+    /// Open a private two-person room with a peer, no ticket needed
+    Dm {
+        /// Your display name
+        #[arg(short, long)]
+        name: String,
+        /// The peer's endpoint ID, or the name of a saved contact
+        peer: String,
+    },
+    /// List archived rooms with their tickets, most recent first
+    Rooms,
+    /// Load a transcript (from `/export --format json`) into a room's local
+    /// history, shown the next time you join it
+    Import {
+        /// The JSON transcript
+        file: PathBuf,
+        /// Ticket of the room to import into
+        ticket: String,
+    },
+    /// Print a room's messages to stdout without the TUI (read-only)
+    Tail {
+        /// Ticket string of the room to watch
+        ticket: String,
+        /// One JSON object per line instead of plain text
+        #[arg(long)]
+        json: bool,
+    },
+    /// Post a message to a room and exit (reads stdin unless -m is given)
+    Send {
+        /// Ticket string of the room to post to
+        ticket: String,
+        /// Display name to post as
+        #[arg(short, long)]
+        name: String,
+        /// The message; without it, stdin is read
+        #[arg(short, long)]
+        message: Option<String>,
+        /// Send each line of input as its own message
+        #[arg(long)]
+        lines: bool,
+        /// Seconds to wait for a peer, and then for acks
+        #[arg(long, default_value_t = piper_chat::send::SEND_TIMEOUT.as_secs())]
+        timeout: u64,
+    },
+    /// Offer a file to a room and serve it until it has been downloaded
+    Share {
+        /// Ticket string of the room to share into
+        ticket: String,
+        /// The file to share
+        path: PathBuf,
+        /// Display name to share as
+        #[arg(short, long)]
+        name: String,
+        /// Exit after this many completed downloads
+        #[arg(long, default_value_t = 1)]
+        downloads: usize,
+        /// Seconds to keep serving before giving up
+        #[arg(long, default_value_t = piper_chat::share::SHARE_TIMEOUT.as_secs())]
+        timeout: u64,
+    },
+    /// Download a blob straight from a peer, without joining a room
+    Fetch {
+        /// Endpoint ID of the peer serving the blob
+        #[arg(long)]
+        provider: iroh::EndpointId,
+        /// BLAKE3 hash of the blob (as shown in offers and by `share`)
+        #[arg(long)]
+        hash: Hash,
+        /// Where to write the file
+        #[arg(long)]
+        out: PathBuf,
+        /// Ticket of the room the file was offered in, to present a room
+        /// token to providers that only serve members
+        #[arg(long)]
+        ticket: Option<String>,
+    },
+    /// Play an event log recorded with --json-log back through the chat
+    /// UI, without any networking
+    Replay {
+        /// The JSON lines log
+        log: PathBuf,
+        /// Play this many times as fast as it was recorded (0.5 is half speed)
+        #[arg(long, default_value_t = 1.0)]
+        speed: f64,
+    },
 }
 
 // ── Main ─────────────────────────────────────────────────────────────────────
@@ -150,103 +307,247 @@ async fn main() -> Result<()> {
     // arguments are invalid, it prints an error and exits automatically.
     let cli = Cli::parse();
 
+    // The profile comes first: every path below depends on it. The default
+    // config file may name one, so it's read before any profile is chosen.
+    if let Some(name) = cli.profile.clone().or_else(|| Config::load().profile)
+        && !profile::select(&name)
+    {
+        anyhow::bail!("profile {name:?} in the config file isn't valid: {}", profile_rule());
+    }
+
+    // Scratch stores of subcommands that were killed before cleaning up.
+    scratch::sweep(&std::env::temp_dir());
+
+    // Every mode counts; these flags only decide where the numbers go.
+    #[cfg(feature = "metrics")]
+    {
+        if let Some(port) = cli.metrics_port {
+            piper_chat::metrics::serve(port).await?;
+        }
+        if let Some(path) = cli.metrics_file.clone() {
+            piper_chat::metrics::spawn_writer(path);
+        }
+    }
+
+    // Replaying a recording needs no identity, network or archive.
+    if let Some(Command::Replay { log, speed }) = &cli.command {
+        let ascii = cli.ascii || !piper_chat::theme::locale_is_utf8();
+        return replay(log, *speed, ascii).await;
+    }
+
+    // Our identity: the saved secret key, so contacts and DM rooms know us
+    // next time, unless `--ephemeral` asks for a fresh one.
+    let secret_key = match node::secret_key_path() {
+        Some(path) if !cli.ephemeral => node::load_secret_key(&path)?,
+        _ => iroh::SecretKey::from_bytes(&rand::random()),
+    };
+    let contacts = Contacts::load();
+    let mut archive = Rooms::load();
+
+    // Terminals without a UTF-8 locale render box-drawing characters as tofu.
+    let ascii = cli.ascii || !piper_chat::theme::locale_is_utf8();
+    // The saved palette. Every screen from here on toggles this one theme
+    // with Ctrl+T, and the chat takes it over.
+    let mut theme = Theme::new(Config::load().theme, ascii);
+    let transport = transport(cli.paths, cli.proxy.clone()).await?;
+    // The flag can only turn the in-memory store on; the config can too.
+    let mem_store = cli.mem_store || Config::load().mem_store;
+
     // Determine the nickname and ticket based on the subcommand.
     // `match` on `Option<Command>` handles all three cases: Create, Join, or
-    // no subcommand (interactive welcome screen).
-    let (nickname, ticket) = match cli.command {
-        Some(Command::Create { name }) => (name, ChatTicket::new_random()),
+    // no subcommand (interactive welcome screen). `show_ticket` is set when a
+    // room is created from the welcome screen, so its ticket gets a screen of
+    // its own before the chat starts; `probe` when joining someone else's
+    // room, so its bootstrap peers are dialed first (see `run_join_screen`).
+
+    // `recovered` is the crashed session the user chose to go back to.
+    let mut recovered = None;
+    let (nickname, mut ticket, show_ticket, probe) = match cli.command {
+        Some(Command::Create { name, max_members, knock, room, expires }) => {
+            let mut t = ChatTicket::new_random();
+            t.creator = Some(secret_key.public());
+            if max_members.is_some() || knock {
+                t.policy = Some(RoomPolicy { creator: secret_key.public(), max_members, knock });
+            }
+            t.name = room.filter(|r| !r.trim().is_empty()).map(|r| r.chars().take(net::ticket::MAX_ROOM_NAME).collect());
+            t.expires_ms = expires.map(|ttl| now_ms() + ttl.as_millis() as u64);
+            (name, t, false, false)
+        }
+        Some(Command::Dm { name, peer }) => {
+            let peer = match peer.parse::<iroh::EndpointId>() {
+                Ok(id) => id,
+                Err(_) => match contacts.find(&peer) {
+                    Some((id, _)) => id,
+                    None => anyhow::bail!("{peer} is neither an endpoint ID nor a saved contact"),
+                },
+            };
+            (name, ChatTicket::dm(secret_key.public(), peer), false, false)
+        }
+        Some(Command::Rooms) => {
+            print_rooms(&archive);
+            return Ok(());
+        }
+        Some(Command::Import { file, ticket }) => return import(&mut archive, &file, &ticket),
+        Some(Command::Replay { .. }) => unreachable!("replay returns before any of this"),
+        Some(Command::Tail { ticket, json }) => {
+            let ticket = ChatTicket::parse(&ticket)?;
+            return tail(&ticket, json, &transport, mem_store).await;
+        }
+        Some(Command::Send { ticket, name, message, lines, timeout }) => {
+            let ticket = ChatTicket::parse(&ticket)?;
+            let input = match message {
+                Some(message) => message,
+                None => std::io::read_to_string(std::io::stdin())?,
+            };
+            let messages = piper_chat::send::messages_from(&input, lines);
+            if messages.is_empty() {
+                anyhow::bail!("nothing to send");
+            }
+            check_nickname(&name)?;
+            return send(&ticket, &name, &messages, Duration::from_secs(timeout), &transport, mem_store).await;
+        }
+        Some(Command::Share { ticket, path, name, downloads, timeout }) => {
+            let ticket = ChatTicket::parse(&ticket)?;
+            check_nickname(&name)?;
+            return share(&ticket, &name, &path, downloads, Duration::from_secs(timeout), &transport, mem_store).await;
+        }
+        Some(Command::Fetch { provider, hash, out, ticket }) => {
+            let room = ticket.map(|t| ChatTicket::parse(&t)).transpose()?.map(|t| t.topic_id);
+            return fetch(provider, hash, &out, room, &transport, mem_store).await;
+        }
         Some(Command::Join { name, ticket }) => {
-            // Fully-qualified trait method call: `<ChatTicket as Ticket>::deserialize()`
-            // This syntax is needed when a type could implement multiple traits with
-            // the same method name. Here it calls the `Ticket` trait's `deserialize`
-            // which parses a base32 string back into a `ChatTicket`.
-            let t = <ChatTicket as Ticket>::deserialize(&ticket)?;
-            (name, t)
+            // `ChatTicket::parse` wraps the `Ticket` trait's base32 `deserialize`;
+            // a bad paste comes back as `Error::Ticket`.
+            let mut t = ChatTicket::parse(&ticket)?;
+            // Archived tickets list us among the bootstrap peers.
+            t.bootstrap.remove(&secret_key.public());
+            (name, t, false, true)
         }
         // `None` — no subcommand provided, launch the interactive welcome screen.
         // The nested `match` handles the welcome screen's three outcomes:
         // create, join, or quit (user pressed Esc).
-        None => match run_welcome_screen().await? {
-            Some(WelcomeResult::Create { nickname }) => (nickname, ChatTicket::new_random()),
-            Some(WelcomeResult::Join { nickname, ticket }) => {
-                let t = <ChatTicket as Ticket>::deserialize(&ticket)?;
-                (nickname, t)
+        // A session left on disk means the last run crashed (see `session`),
+        // so first offer to go back to it.
+        None => {
+            let crashed = Rooms::dir().and_then(|dir| Session::load(&dir));
+            let resume = match &crashed {
+                Some(session) => welcome::run_recover_screen(session, &mut theme).await?,
+                None => false,
+            };
+            match crashed {
+                Some(session) if resume => {
+                    let mut t = ChatTicket::parse(&session.ticket)?;
+                    t.bootstrap.remove(&secret_key.public());
+                    let nickname = session.nickname.clone();
+                    recovered = Some(session);
+                    (nickname, t, false, true)
+                }
+                _ => {
+                    if let (Some(_), Some(dir)) = (&crashed, Rooms::dir()) {
+                        session::clear(&dir)?;
+                    }
+                    // Check a relay is reachable while the user fills in the
+                    // form, with the transport settings the chat will use.
+                    let (relay_tx, relay_rx) = tokio::sync::watch::channel(net::bootstrap::RelayCheck::Checking);
+                    if transport.paths == Paths::Direct {
+                        let _ = relay_tx.send(net::bootstrap::RelayCheck::Disabled);
+                    } else {
+                        let builder = transport.apply(iroh::Endpoint::builder());
+                        tokio::spawn(async move {
+                            let _ = relay_tx.send(net::bootstrap::check_relay(builder, net::bootstrap::RELAY_CHECK_TIMEOUT).await);
+                        });
+                    }
+                    match run_welcome_screen(&contacts, &archive, now_ms(), &mut theme, relay_rx).await? {
+                        Some(WelcomeResult::Create { nickname }) => {
+                            let mut t = ChatTicket::new_random();
+                            t.creator = Some(secret_key.public());
+                            (nickname, t, true, false)
+                        }
+                        Some(WelcomeResult::Join { nickname, ticket }) => {
+                            let mut t = ChatTicket::parse(&ticket)?;
+                            t.bootstrap.remove(&secret_key.public());
+                            (nickname, t, false, true)
+                        }
+                        Some(WelcomeResult::Dm { nickname, peer }) => {
+                            (nickname, ChatTicket::dm(secret_key.public(), peer), false, false)
+                        }
+                        // User quit the welcome screen — exit cleanly.
+                        None => return Ok(()),
+                    }
+                }
             }
-            // User quit the welcome screen — exit cleanly.
-            None => return Ok(()),
-        },
+        }
     };
+    // Peers drop messages under a blank or overlong name, so refuse to
+    // start with one rather than chat into the void.
+    check_nickname(&nickname)?;
 
     // ── Networking ───────────────────────────────────────────────────────────
 
-    // Build the iroh endpoint using the builder pattern. The endpoint is our
-    // network identity — it generates a keypair, listens for QUIC connections,
-    // and manages hole-punching and relay fallback.
-    //
-    // `.alpns()` registers the Application-Layer Protocol Negotiation identifiers.
-    // ALPN is a TLS extension that lets the client tell the server which protocol
-    // it wants to speak. By registering both GOSSIP_ALPN and BLOBS_ALPN, our
-    // endpoint can handle both gossip messages and blob transfers over the same
-    // QUIC connection.
+    // Spawn the endpoint, blob store, gossip, and router (see `node.rs`).
+    // The blob store goes under the profile's data directory, unless
+    // `--mem-store` keeps it in memory (see `blobstore`).
     //
-    // `.bind()` is async — it binds a UDP socket and starts the endpoint.
-    let endpoint = iroh::Endpoint::builder()
-        .alpns(vec![GOSSIP_ALPN.to_vec(), BLOBS_ALPN.to_vec()])
-        .bind()
-        .await?;
-
-    // Set up the blob store at a per-instance directory keyed by endpoint ID.
-    // This avoids `redb` lock contention when multiple peers run on one machine.
+    // `profile::data_dir()` returns an `Option<PathBuf>` — `piper-chat` in
+    // the platform's standard data directory, or the active profile's
+    // directory in there. `unwrap_or_else(|| ...)` provides a fallback (in
+    // the current dir) if the platform doesn't have a data directory.
+    let blob_root = profile::data_dir()
+        .unwrap_or_else(|| PathBuf::from("piper-chat"))
+        .join("blobs");
+    let storage = if mem_store { Storage::Memory } else { Storage::Disk(&blob_root) };
     //
-    // `dirs::data_dir()` returns an `Option<PathBuf>` — the platform's standard
-    // data directory. `unwrap_or_else(|| ...)` provides a fallback (current dir)
-    // if the platform doesn't have a data directory.
-    //
-    // `.join()` appends path segments using the platform's path separator.
-    // `endpoint.id().fmt_short()` returns a short hex prefix for readability.
-    let blob_dir = dirs::data_dir()
-        .unwrap_or_else(|| PathBuf::from("."))
-        .join("piper-chat")
-        .join("blobs")
-        .join(endpoint.id().fmt_short().to_string());
-    // `FsStore::load()` opens (or creates) the redb database at the given path.
-    // It's async because it may need to perform I/O to initialize the database.
-    let blob_store = FsStore::load(&blob_dir).await?;
-
-    // `Gossip::builder().spawn()` creates the gossip protocol instance and starts
-    // its background task. It takes a clone of the endpoint because it needs to
-    // open connections to peers for gossip message exchange.
-    let gossip = Gossip::builder().spawn(endpoint.clone());
-
-    // Create the blobs protocol handler so peers can download blobs from us.
-    // `BlobsProtocol` wraps the store and serves blob data over QUIC when a
-    // peer connects with the BLOBS_ALPN identifier.
-    let blobs_protocol = BlobsProtocol::new(&blob_store, None);
-
-    // The Router multiplexes multiple protocols over a single endpoint.
-    // `.accept(ALPN, handler)` registers a protocol handler for a given ALPN.
-    // When an incoming connection arrives, the router inspects the ALPN and
-    // dispatches to the matching handler. `.spawn()` starts the router's
-    // background accept loop.
-    let router = iroh::protocol::Router::builder(endpoint.clone())
-        .accept(GOSSIP_ALPN, gossip.clone())
-        .accept(BLOBS_ALPN, blobs_protocol)
-        .spawn();
-
-    // Build the ticket string to share with others. We clone the original
-    // ticket and insert our own endpoint ID, so peers who receive the ticket
-    // can bootstrap by connecting to us.
-    let mut our_ticket = ticket.clone();
-    our_ticket.bootstrap.insert(endpoint.id());
-    let ticket_str = <ChatTicket as Ticket>::serialize(&our_ticket);
-
-    // Subscribe to the gossip topic. `bootstrap` is the list of peers to
-    // initially connect to (from the ticket). `subscribe()` returns a
-    // `TopicHandle` which we `.split()` into a sender (for broadcasting)
-    // and a receiver (an async stream of gossip events).
-    let bootstrap: Vec<_> = ticket.bootstrap.iter().cloned().collect();
-    let topic = gossip.subscribe(ticket.topic_id, bootstrap).await?;
-    let (sender, mut receiver) = topic.split();
+    // With `serve_to = "members"` the blobs protocol checks every downloader
+    // against `members`, which `App` keeps in step with the peers panel. Its
+    // room is also what our downloads present tokens for.
+    let mut config = Config::load();
+    let members = Members::default();
+    members.set_room(ticket.topic_id);
+    let builder = transport.apply(iroh::Endpoint::builder().secret_key(secret_key));
+    let mut node = match config.serve_to {
+        ServePolicy::Anyone => Node::spawn(builder, storage).await?,
+        ServePolicy::Members => Node::spawn_members_only(builder, storage, members.clone()).await?,
+    };
+    let mut direct_rx = node.direct_rx.take().expect("Node::spawn sets direct_rx");
+    // Both are `Arc`-backed handles, so these clones are cheap.
+    let endpoint = node.endpoint.clone();
+    let blob_store = node.blob_store.clone();
+
+    // Build the ticket string to share with others. Give the endpoint a
+    // moment to reach its home relay first, so the ticket's address hint
+    // names it; without a network we go ahead with the direct addresses.
+    let _ = tokio::time::timeout(Duration::from_secs(2), endpoint.online()).await;
+    let ticket_str = <ChatTicket as Ticket>::serialize(&node.ticket_for(&ticket));
+
+    // The ticket only exists once our endpoint is bound, so the ticket screen
+    // runs here rather than as part of the welcome form.
+    if show_ticket && !welcome::run_ticket_screen(&ticket_str, &mut theme).await? {
+        node.shutdown().await?;
+        return Ok(());
+    }
+
+    // A DM waits for its peer to come online, so only rooms are probed.
+    if probe && !ticket.bootstrap.is_empty() {
+        node.learn_hints(&ticket);
+        let peers = ticket
+            .bootstrap
+            .iter()
+            .map(|id| {
+                let name = contacts.get(id).map(|c| c.nickname.clone());
+                let name = name.unwrap_or_else(|| if ticket.creator == Some(*id) { "creator" } else { "peer" }.to_string());
+                (*id, name)
+            })
+            .collect();
+        if !welcome::run_join_screen(&endpoint, peers, &mut theme).await? {
+            node.shutdown().await?;
+            return Ok(());
+        }
+    }
+
+    let (mut sender, mut receiver) = node.join(&ticket).await?;
+    // Sent chat messages awaiting acks, for the direct-stream fallback.
+    let mut outbox = Outbox::default();
+    let mut retries = RetryQueue::default();
 
     // ── File transfer setup ─────────────────────────────────────────────────
 
@@ -272,6 +573,9 @@ async fn main() -> Result<()> {
     let (history_tx, mut history_rx) =
         tokio::sync::mpsc::channel::<Result<Vec<u8>, String>>(4);
 
+    // Channel for the session lock's Argon2 work, run on a blocking thread.
+    let (lock_tx, mut lock_rx) = tokio::sync::mpsc::channel::<lock::Done>(1);
+
     // ── Terminal setup ───────────────────────────────────────────────────────
 
     // `enable_raw_mode()` puts the terminal into raw mode:
@@ -284,7 +588,18 @@ async fn main() -> Result<()> {
     // `EnterAlternateScreen` switches to the terminal's alternate screen buffer,
     // preserving the user's original scrollback. When we `LeaveAlternateScreen`
     // later, the original terminal content is restored — the chat UI disappears.
-    execute!(std::io::stdout(), EnterAlternateScreen, EnableMouseCapture)?;
+    // `EnableBracketedPaste` asks the terminal to wrap pasted text in escape
+    // markers, so crossterm delivers it as one `Event::Paste(String)` instead
+    // of a burst of key events (where embedded newlines would press Enter).
+    execute!(
+        std::io::stdout(),
+        EnterAlternateScreen,
+        EnableMouseCapture,
+        EnableBracketedPaste
+    )?;
+    // Ask for unambiguous key codes (kitty protocol) where supported, so
+    // composed/IME characters aren't confused with control chords.
+    let keyboard_enhanced = input::enable_keyboard_enhancement();
     // Create a ratatui `Terminal` backed by crossterm. The terminal manages a
     // double-buffer: widgets draw to a back buffer, then `draw()` diffs it against
     // the front buffer and emits only the changed cells — minimizing terminal I/O.
@@ -296,18 +611,117 @@ async fn main() -> Result<()> {
     // that uniquely identifies this node on the network.
     let our_id = endpoint.id();
     let mut app = App::new();
+    if let Some(path) = &cli.json_log {
+        app.event_log = Some(EventLog::open(path).with_context(|| format!("opening {}", path.display()))?);
+    }
+    app.transfers.risky_extensions = config.risky_extensions.clone();
+    // A palette picked before the chat is remembered like one picked in it.
+    let theme_saved = if config.theme == theme.mode {
+        Ok(())
+    } else {
+        config.theme = theme.mode;
+        config.save()
+    };
+    app.config = config;
+    app.members = members;
+    app.theme = theme;
+    if let Err(e) = theme_saved {
+        app.system(t!(app.config.lang, "config.save_failed", error = e));
+    }
+    app.paths = transport.paths;
+    app.keepalive = Keepalive::new(app.config.keepalive_secs, std::time::Instant::now());
+    app.filters = Filters::from_config(&app.config.filters);
+    app.highlights = Highlights::new(&nickname, &app.config.highlight_words);
+    app.contacts = contacts;
+    if app.config.spellcheck {
+        app.spell = Dictionary::load(app.config.spellcheck_dictionary.as_deref());
+    }
     // Add ourselves to the peers map with "(you)" suffix for the display name.
     app.peers.insert(
         our_id,
         PeerInfo {
             name: format!("{nickname} (you)"),
             conn_type: ConnType::You,
+            rtt_ms: None,
+            addr: None,
         },
     );
     app.ticket(ticket_str.clone());
     app.ticket_str = Some(ticket_str);
-    app.system("share the ticket above with others to join");
-    app.system("type /help for commands | waiting for peers...");
+    app.room_name = ticket.name.clone();
+    app.admission = ticket.policy.map(|policy| Admission::new(policy, ticket.topic_id, our_id));
+    if app.admission.as_ref().is_some_and(|a| !a.let_in()) {
+        app.system(t!(app.config.lang, "knock.waiting"));
+    }
+    // Joining someone else's room, we expect company; see `joining`.
+    if probe && !ticket.bootstrap.is_empty() {
+        app.joining = JoinPhase::wait(app.config.join_timeout_secs, now_ms());
+    }
+    app.system(t!(app.config.lang, "start.share"));
+    app.system(t!(app.config.lang, "start.id", id = our_id));
+    if let Some(name) = profile::active() {
+        app.system(t!(app.config.lang, "start.profile", name = name));
+    }
+    app.system(t!(app.config.lang, "start.hint"));
+
+    // Put back whatever was left in the input bar last time in this room.
+    let mut drafts = Drafts::load();
+    if let Some(draft) = drafts.get(&ticket.topic_id) {
+        app.restore_draft(draft);
+    }
+    // And whether it was muted or in focus mode.
+    let mut prefs = Prefs::load();
+    app.room_prefs = prefs.get(&ticket.topic_id);
+    // And the conversation itself, if we've been here before.
+    restore_room(&mut app, &ticket, &nickname);
+    // Back from a crash: refill the file pane and resume cut-off downloads.
+    if let Some(session) = recovered {
+        let resumed = session.restore(&mut app.transfers);
+        for id in &resumed {
+            if let Some(offer) = app.transfers.request_download(id, true) {
+                node::spawn_download(&blob_store, &endpoint, offer, app.members.room(), download_dir.clone(), transfer_tx.clone());
+            }
+        }
+        app.system(t!(app.config.lang, "session.recovered", files = session.transfers.len(), resumed = resumed.len()));
+    }
+    // Share artifacts a crashed run left in the store, unless the session
+    // just recovered is still offering them. An in-memory store starts
+    // empty, and its artifacts aren't worth listing on disk.
+    app.artifacts = if blob_store.persistent() { Artifacts::load() } else { Artifacts::in_memory() };
+    let sharing: Vec<Hash> = app
+        .transfers
+        .entries
+        .iter()
+        .filter(|e| matches!(e.state, TransferState::Sharing))
+        .map(|e| e.offer.hash)
+        .collect();
+    let leftovers = app.artifacts.drain_except(&sharing);
+    if !leftovers.is_empty() {
+        for hash in &leftovers {
+            let _ = node::delete_tags(&blob_store, *hash).await;
+        }
+        if let Err(e) = app.artifacts.save() {
+            app.system(t!(app.config.lang, "artifacts.save_failed", error = e));
+        }
+        app.system(t!(app.config.lang, "artifacts.swept", count = leftovers.len()));
+    }
+    // What was last written for crash recovery; rewritten when it changes.
+    // Not with an in-memory store: there'd be no shares to recover.
+    let session_dir = Rooms::dir().filter(|_| blob_store.persistent());
+    let mut saved_session: Option<Session> = None;
+    // Sealed offers' keys only go to disk when history isn't encrypted.
+    let keep_keys = rooms::passphrase().is_none();
+    // Our last keypress, for the archive's unread estimate.
+    let mut last_read_ms = now_ms();
+    // Last key press, mouse action or paste, for `idle_lock_secs`.
+    let mut last_input_ms = now_ms();
+
+    // The optional HTTP bridge (see `webhook.rs`).
+    let (webhook_tx, mut webhook_rx) = tokio::sync::mpsc::channel::<WebhookEvent>(16);
+    let mut webhook = cli.webhook.map(|url| Webhook::new(url, webhook_tx.clone()));
+    if let Some(url) = cli.webhook_poll {
+        webhook::spawn_poller(url, webhook_tx);
+    }
 
     // `EventStream::new()` creates an async stream of crossterm terminal events.
     // It uses the "event-stream" feature we enabled in Cargo.toml, which wraps
@@ -316,6 +730,12 @@ async fn main() -> Result<()> {
     // `interval()` creates an async timer that yields at a fixed rate (50ms).
     // We use this to drive periodic UI redraws and connection type polling.
     let mut tick = interval(Duration::from_millis(50));
+    // A slower second timer refreshes the status line's clock. The clock
+    // only changes once a minute, so there's no point formatting it on
+    // every 50ms tick.
+    let mut clock = interval(Duration::from_secs(1));
+    let session_start = std::time::Instant::now();
+    let mut wake = WakeDetector::new(now_ms(), session_start);
 
     // ── Event loop ───────────────────────────────────────────────────────────
     //
@@ -337,21 +757,146 @@ async fn main() -> Result<()> {
             // `events.next()` yields the next terminal event from the async stream.
             // The result is `Option<Result<Event>>` — None means the stream ended.
             ev = events.next() => {
+                // A locked session takes every event: keys go to its
+                // passphrase prompt, mouse and paste go nowhere.
+                if let Some(session_lock) = app.lock.as_mut() {
+                    if let Some(Ok(TermEvent::Key(key))) = &ev
+                        && key.kind == KeyEventKind::Press
+                    {
+                        let outcome = session_lock.key(key, app.config.lock_passphrase.as_deref());
+                        lock_outcome(&mut app, outcome, &lock_tx, &mut last_input_ms);
+                    }
+                    continue;
+                }
+                if matches!(&ev, Some(Ok(TermEvent::Key(_) | TermEvent::Mouse(_) | TermEvent::Paste(_)))) {
+                    last_input_ms = now_ms();
+                }
                 if let Some(Ok(TermEvent::Key(key))) = &ev {
                     // On Windows, crossterm sends both Press and Release events.
                     // We only care about Press events to avoid double-handling.
                     if key.kind != KeyEventKind::Press { continue; }
+                    // Any keypress means we're back at the keyboard.
+                    app.clear_afk();
+                    last_read_ms = now_ms();
+
+                    // A knock waiting for our answer (we created the room)
+                    // holds the keyboard until it gets a y or an n.
+                    if app.admission.as_ref().is_some_and(Admission::prompting) {
+                        match key.code {
+                            KeyCode::Char('y') => answer_knock(&mut app, &sender, &mut retries, endpoint.secret_key(), true).await,
+                            KeyCode::Char('n') => answer_knock(&mut app, &sender, &mut retries, endpoint.secret_key(), false).await,
+                            _ => {}
+                        }
+                        continue;
+                    }
+
+                    // So does the overlay for a join nobody has answered.
+                    if app.joining.stalled() {
+                        match key.code {
+                            KeyCode::Char('r') => {
+                                // Subscribing again re-dials every bootstrap
+                                // peer, after iroh re-probes the network.
+                                endpoint.network_change().await;
+                                match node.join(&ticket).await {
+                                    Ok(joined) => (sender, receiver) = joined,
+                                    Err(e) => app.system(t!(app.config.lang, "join.failed", error = e)),
+                                }
+                                app.joining = JoinPhase::wait(app.config.join_timeout_secs, now_ms());
+                            }
+                            KeyCode::Char('e') => {
+                                app.joining = JoinPhase::wait(app.config.join_timeout_secs, now_ms());
+                                app.focus_chat();
+                                app.prefill("/join ");
+                            }
+                            KeyCode::Char('d') => {
+                                app.joining = JoinPhase::wait(app.config.join_timeout_secs, now_ms());
+                                app.show_net(net::tracker::Reachability::of(&endpoint).as_ref());
+                                app.system(t!(app.config.lang, "joining.diag.header"));
+                                for id in &ticket.bootstrap {
+                                    let known = net::bootstrap::known(&endpoint, *id).await;
+                                    app.system(t!(app.config.lang, "joining.diag.peer", id = id.fmt_short(), known = known));
+                                }
+                            }
+                            KeyCode::Char('w') | KeyCode::Esc => app.joining = JoinPhase::Idle,
+                            _ => {}
+                        }
+                        continue;
+                    }
+
+                    // The command palette (Ctrl+K) takes the keyboard while
+                    // it's open. What it picks goes through chat mode below,
+                    // as entered text or a replayed key.
+                    let replayed;
+                    let key = match app.palette.as_mut().map(|p| p.key(key)) {
+                        None => key,
+                        Some(Outcome::Open) => continue,
+                        Some(Outcome::Close) => {
+                            app.palette = None;
+                            continue;
+                        }
+                        Some(Outcome::Picked(pick)) => {
+                            app.palette = None;
+                            app.focus_chat();
+                            match pick {
+                                Pick::Edit(text) => {
+                                    app.prefill(&text);
+                                    continue;
+                                }
+                                Pick::Run(text) => {
+                                    app.prefill(&text);
+                                    replayed = KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE);
+                                    &replayed
+                                }
+                                Pick::Key(code, modifiers) => {
+                                    replayed = KeyEvent::new(code, modifiers);
+                                    &replayed
+                                }
+                            }
+                        }
+                    };
 
                     match app.mode {
                         // ── Chat mode ────────────────────────────────────
                         AppMode::Chat => {
                             match key.code {
+                                // Esc first discards a pending multi-line paste;
+                                // only with nothing pending does it quit.
+                                KeyCode::Esc if app.pending_paste.is_some() => {
+                                    app.pending_paste = None;
+                                }
+                                // ...then cancels a pending quote-reply...
+                                KeyCode::Esc if app.replying_to.is_some() => {
+                                    app.replying_to = None;
+                                }
+                                // ...then closes an open thread view.
+                                KeyCode::Esc if app.open_thread.is_some() => {
+                                    app.open_thread = None;
+                                }
                                 KeyCode::Esc => app.should_quit = true,
-                                KeyCode::Tab => {
-                                    if app.transfers.has_entries() {
-                                        app.focus_file_pane();
+                                // Tab on a `/command` completes its name, then
+                                // shows its usage once the name is whole.
+                                KeyCode::Tab if app.input.starts_with('/') && command::complete(&app.input) != Completion::None => {
+                                    match command::complete(&app.input) {
+                                        Completion::Complete(input) => app.prefill(&input),
+                                        Completion::Choices(common, choices) => {
+                                            app.prefill(&common);
+                                            let list: Vec<&str> = choices.iter().map(|c| c.usage).collect();
+                                            app.system(t!(app.config.lang, "command.choices", list = list.join("  ")));
+                                        }
+                                        Completion::Usage(spec) => app.system(t!(app.config.lang, "usage", usage = spec.usage)),
+                                        Completion::None => {}
                                     }
                                 }
+                                // Otherwise Tab and Shift+Tab (and Ctrl+Tab, which
+                                // enhanced terminals report as Tab + CONTROL) cycle focus.
+                                KeyCode::Tab | KeyCode::BackTab if app.transfers.has_entries() => {
+                                    app.focus_file_pane();
+                                }
+                                // Shift/Ctrl/Alt+Enter or Ctrl+J insert a newline
+                                // for multi-line compose instead of sending.
+                                _ if input::is_newline_chord(key) => {
+                                    input::insert_char(&mut app.input, &mut app.cursor_pos, '\n');
+                                }
                                 // `key.modifiers.contains(KeyModifiers::CONTROL)` checks
                                 // if the Ctrl key is held. `KeyModifiers` is a bitfield,
                                 // so `.contains()` tests a specific bit flag.
@@ -360,110 +905,453 @@ async fn main() -> Result<()> {
                                 }
                                 KeyCode::Char('t') if key.modifiers.contains(KeyModifiers::CONTROL) => {
                                     app.theme.toggle();
+                                    app.config.theme = app.theme.mode;
+                                    if let Err(e) = app.config.save() {
+                                        app.system(t!(app.config.lang, "config.save_failed", error = e));
+                                    }
+                                }
+                                KeyCode::Char('o') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                                    app.cycle_peer_sort();
+                                    if let Err(e) = app.config.save() {
+                                        app.system(t!(app.config.lang, "config.save_failed", error = e));
+                                    }
                                 }
                                 KeyCode::Char('y') if key.modifiers.contains(KeyModifiers::CONTROL) => {
                                     copy_ticket_to_clipboard(&mut app);
                                 }
+                                KeyCode::Char('k') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                                    // Every room in the archive but this one.
+                                    let rooms = archive
+                                        .by_recency()
+                                        .into_iter()
+                                        .filter(|r| ChatTicket::parse(&r.ticket).is_ok_and(|t| t.topic_id != ticket.topic_id))
+                                        .map(|r| (r.label.clone(), r.describe(now_ms()), r.ticket.clone()))
+                                        .collect();
+                                    app.palette = Some(Palette::new(palette::items(app.config.lang, rooms)));
+                                }
+                                // Ctrl+A on a pending paste shares it as a text
+                                // file instead of sending it as a chat message.
+                                KeyCode::Char('a')
+                                    if key.modifiers.contains(KeyModifiers::CONTROL)
+                                        && app.pending_paste.is_some() =>
+                                {
+                                    let text = app.pending_paste.take().unwrap_or_default();
+                                    let result = node::share_bytes(
+                                        &blob_store,
+                                        &sender,
+                                        &nickname,
+                                        our_id,
+                                        "paste.txt",
+                                        text.into_bytes(),
+                                        offer_options(&app, None),
+                                    ).await;
+                                    record_share(&mut app, our_id, result, None, true);
+                                }
                                 KeyCode::Enter => {
+                                    // A confirmed multi-line paste is sent verbatim
+                                    // as a single message, bypassing slash commands.
+                                    if let Some(text) = app.pending_paste.take() {
+                                        post_chat(&mut app, &sender, &mut retries, &mut outbox, our_id, &nickname, text).await;
+                                        continue;
+                                    }
                                     // `drain(..)` removes all characters from the String
                                     // and returns them as an iterator. `.collect()` gathers
                                     // them back into a new String. This efficiently moves
                                     // the input content out while leaving `app.input` empty.
                                     let text: String = app.input.drain(..).collect();
                                     app.cursor_pos = 0;
-                                    if text.trim() == "/help" {
-                                        show_help(&mut app);
-                                    } else if text.trim() == "/send" {
+                                    app.draft_restored = false;
+                                    // Anything starting with `/` is a command: an unknown
+                                    // one, or one with bad arguments, gets a notice (with
+                                    // its usage) rather than going to the room as chat.
+                                    if let Some(notice) = command::check(&text, app.config.lang) {
+                                        app.system(notice);
+                                        continue;
+                                    }
+                                    // `/shrug [text]` decorates an ordinary chat message,
+                                    // sent as it is even if the text looks like a command.
+                                    if text.trim() == "/shrug" || text.trim_start().starts_with("/shrug ") {
+                                        let rest = text.trim().strip_prefix("/shrug").unwrap().trim();
+                                        let text = if rest.is_empty() {
+                                            SHRUG.to_string()
+                                        } else {
+                                            format!("{rest} {SHRUG}")
+                                        };
+                                        post_chat(&mut app, &sender, &mut retries, &mut outbox, our_id, &nickname, text).await;
+                                        continue;
+                                    }
+                                    if command::parse(&text, "/help").is_some() {
+                                        app.show_help();
+                                    } else if command::parse(&text, "/send").is_some() {
                                         app.pending_send_target = None;
                                         app.open_file_picker();
-                                    } else if text.trim().starts_with("/sendto ") {
-                                        let target_name = text.trim().strip_prefix("/sendto ").unwrap().trim().to_string();
-                                        if target_name.is_empty() {
-                                            app.system("usage: /sendto <nickname>");
-                                        } else if app.peers.values().any(|p| p.name == target_name) {
+                                    } else if let Some(args) = command::parse(&text, "/sendto") {
+                                        let target_name = match args {
+                                            Ok(args) => args.word(0).to_string(),
+                                            Err(e) => {
+                                                app.system(e.notice("/sendto", app.config.lang));
+                                                continue;
+                                            }
+                                        };
+                                        if app.peers.values().any(|p| p.name == target_name) {
                                             app.pending_send_target = Some(target_name);
                                             app.open_file_picker();
                                         } else {
-                                            app.system(format!("unknown peer: {target_name}"));
+                                            app.system(t!(app.config.lang, "sendto.unknown", name = target_name));
                                         }
-                                    } else if !text.is_empty() {
-                                        let mid = new_message_id();
-                                        let ts = now_ms();
-                                        let msg = Message::Chat {
-                                            nickname: nickname.clone(),
-                                            text: text.clone(),
-                                            message_id: mid,
-                                            timestamp_ms: ts,
+                                    } else if text.trim() == "/afk" || text.trim_start().starts_with("/afk ") {
+                                        let message = text.trim().strip_prefix("/afk").unwrap().trim();
+                                        let message = if message.is_empty() { "away" } else { message };
+                                        app.set_afk(message.to_string());
+                                    } else if let Some(switch) = room_switch(&app, our_id, &text) {
+                                        match switch {
+                                            Err(notice) => app.system(notice),
+                                            Ok((next, dm_name)) => {
+                                                // Join the new room before leaving this one, so a
+                                                // network failure (retryable) leaves us where we
+                                                // were; anything else is fatal and quits.
+                                                let (next_sender, next_receiver) = match node.join(&next).await {
+                                                    Ok(joined) => joined,
+                                                    Err(e) if e.is_retryable() => {
+                                                        app.system(match &dm_name {
+                                                            Some(name) => t!(app.config.lang, "dm.failed", name = name, error = e),
+                                                            None => t!(app.config.lang, "join.failed", error = e),
+                                                        });
+                                                        continue;
+                                                    }
+                                                    Err(e) => return Err(e.into()),
+                                                };
+                                                // The old subscription ends when its sender and
+                                                // receiver are dropped by the reassignment.
+                                                // The old room's input went into this command,
+                                                // so it has no draft left.
+                                                drafts.set(&ticket.topic_id, "");
+                                                if let Err(e) = archive_room(&mut archive, &mut app, &ticket, &nickname, last_read_ms).await {
+                                                    app.system(t!(app.config.lang, "room.archive_failed", error = e));
+                                                }
+                                                ticket = next;
+                                                app.members.set_room(ticket.topic_id);
+                                                (sender, receiver) = (next_sender, next_receiver);
+                                                outbox = Outbox::default();
+                                                retries = RetryQueue::default();
+                                                let next_ticket = <ChatTicket as Ticket>::serialize(&node.ticket_for(&ticket));
+                                                app.enter_room(next_ticket);
+                                                app.room_name = ticket.name.clone();
+                                                app.admission = ticket.policy.map(|policy| Admission::new(policy, ticket.topic_id, our_id));
+                                                if app.admission.as_ref().is_some_and(|a| !a.let_in()) {
+                                                    app.system(t!(app.config.lang, "knock.waiting"));
+                                                }
+                                                match &dm_name {
+                                                    Some(name) => app.system(t!(app.config.lang, "dm.waiting", name = name)),
+                                                    None => {
+                                                        app.system(t!(app.config.lang, "join.switched"));
+                                                        app.joining = JoinPhase::wait(app.config.join_timeout_secs, now_ms());
+                                                    }
+                                                }
+                                                if let Some(draft) = drafts.get(&ticket.topic_id) {
+                                                    app.restore_draft(draft);
+                                                }
+                                                app.room_prefs = prefs.get(&ticket.topic_id);
+                                                restore_room(&mut app, &ticket, &nickname);
+                                            }
+                                        }
+                                    } else if text.trim() == "/export" || text.trim_start().starts_with("/export ") {
+                                        let args = text.trim().strip_prefix("/export").unwrap();
+                                        export_transcript(&mut app, args, &download_dir);
+                                    } else if let Some(args) = command::parse(&text, "/unshare") {
+                                        match args {
+                                            Err(e) => app.system(e.notice("/unshare", app.config.lang)),
+                                            Ok(args) => match shared_row(&app, args.word(0)) {
+                                                Some(id) => revoke_share(&mut app, &sender, &mut retries, &blob_store, id, now_ms()).await,
+                                                None => app.system(t!(app.config.lang, "file.not_shared", row = args.word(0))),
+                                            },
+                                        }
+                                    } else if let Some(args) = command::parse(&text, "/verifyfile") {
+                                        match args {
+                                            Err(e) => app.system(e.notice("/verifyfile", app.config.lang)),
+                                            Ok(args) => match downloaded_row(&app, args.word(0)) {
+                                                Some((hash, filename, path)) => {
+                                                    app.system(t!(app.config.lang, "verify.started", filename = filename));
+                                                    spawn_verify(hash, filename, path, transfer_tx.clone());
+                                                }
+                                                None => app.system(t!(app.config.lang, "verify.not_downloaded", row = args.word(0))),
+                                            },
+                                        }
+                                    } else if let Some(args) = command::parse(&text, "/expire") {
+                                        let args = match args {
+                                            Ok(args) => args,
+                                            Err(e) => {
+                                                app.system(e.notice("/expire", app.config.lang));
+                                                continue;
+                                            }
+                                        };
+                                        let row = args.word(0);
+                                        match (shared_row(&app, row), chat::parse_duration(args.word(1))) {
+                                            (Some(id), Some(after)) => {
+                                                let at_ms = now_ms() + after.as_millis() as u64;
+                                                revoke_share(&mut app, &sender, &mut retries, &blob_store, id, at_ms).await;
+                                            }
+                                            (None, Some(_)) => app.system(t!(app.config.lang, "file.not_shared", row = row)),
+                                            _ => app.system(t!(app.config.lang, "usage", usage = command::usage("/expire"))),
+                                        }
+                                    } else if command::parse(&text, "/map").is_some() {
+                                        app.open_map();
+                                    } else if command::parse(&text, "/files").is_some() {
+                                        app.open_gallery();
+                                    } else if command::parse(&text, "/net").is_some() {
+                                        app.show_net(net::tracker::Reachability::of(&endpoint).as_ref());
+                                    } else if command::parse(&text, "/lock").is_some() {
+                                        app.lock = Some(Lock::new(app.config.lock_passphrase.is_some(), now_ms()));
+                                    } else if command::parse(&text, "/mute").is_some() || command::parse(&text, "/focus").is_some() {
+                                        if command::parse(&text, "/mute").is_some() {
+                                            app.toggle_mute();
+                                        } else {
+                                            app.toggle_focus();
+                                        }
+                                        prefs.set(&ticket.topic_id, app.room_prefs);
+                                        if let Err(e) = prefs.save() {
+                                            app.system(t!(app.config.lang, "prefs.save_failed", error = e));
+                                        }
+                                    } else if command::parse(&text, "/mentions").is_some() {
+                                        app.show_mentions();
+                                    } else if command::parse(&text, "/contacts").is_some() {
+                                        app.show_contacts();
+                                    } else if let Some(args) = command::parse(&text, "/verify") {
+                                        let name = match &args {
+                                            Ok(args) => args.word(0),
+                                            Err(e) => {
+                                                app.system(e.notice("/verify", app.config.lang));
+                                                continue;
+                                            }
                                         };
-                                        let encoded = postcard::to_stdvec(&msg)?;
-                                        sender.broadcast(encoded.into()).await?;
-                                        app.chat(nickname.clone(), text, mid, ts);
+                                        if let Some(id) = app.contacts.verify(name) {
+                                            app.system(t!(app.config.lang, "contacts.verified", name = name, id = id.fmt_short()));
+                                            if let Err(e) = app.contacts.save() {
+                                                app.system(t!(app.config.lang, "contacts.save_failed", error = e));
+                                            }
+                                        } else {
+                                            app.system(t!(app.config.lang, "contacts.unknown", name = name));
+                                        }
+                                    } else if let Some(args) = command::parse(&text, "/owner") {
+                                        match args {
+                                            Ok(args) => grant_owner(&mut app, &sender, &mut retries, endpoint.secret_key(), args.word(0)).await,
+                                            Err(e) => app.system(e.notice("/owner", app.config.lang)),
+                                        }
+                                    } else if command::parse(&text, "/who").is_some() {
+                                        // Everyone who hears the roll call answers with
+                                        // `Here`; replies are collected for a few seconds
+                                        // and reported by the tick branch.
+                                        let request_id = new_message_id();
+                                        let msg = Message::RollCall {
+                                            request_id,
+                                            endpoint_id: our_id,
+                                            sent_ms: now_ms(),
+                                        };
+                                        broadcast(&mut app, &sender, &mut retries, &msg).await;
+                                        app.start_roll_call(request_id);
+                                    } else if text.trim() == "/timer" || text.trim_start().starts_with("/timer ") {
+                                        let args = text.trim().strip_prefix("/timer").unwrap().trim();
+                                        let (after, label) = args.split_once(' ').unwrap_or((args, ""));
+                                        match chat::parse_duration(after) {
+                                            Some(after) => {
+                                                let timer_id = new_message_id();
+                                                let ends_ms = now_ms() + after.as_millis() as u64;
+                                                let label = label.trim().to_string();
+                                                let msg = Message::Timer { nickname: nickname.clone(), label: label.clone(), timer_id, ends_ms };
+                                                broadcast(&mut app, &sender, &mut retries, &msg).await;
+                                                app.start_timer(Timer { id: timer_id, label, set_by: nickname.clone(), ends_ms });
+                                            }
+                                            None => app.system(t!(app.config.lang, "usage", usage = command::usage("/timer"))),
+                                        }
+                                    } else if text.trim() == "/spoiler" || text.trim_start().starts_with("/spoiler ") {
+                                        let hidden = text.trim().strip_prefix("/spoiler").unwrap().trim().to_string();
+                                        if hidden.is_empty() {
+                                            app.system(t!(app.config.lang, "usage", usage = command::usage("/spoiler")));
+                                        } else {
+                                            let mid = new_message_id();
+                                            let ts = now_ms();
+                                            let msg = Message::Spoiler {
+                                                nickname: nickname.clone(),
+                                                text: hidden,
+                                                message_id: mid,
+                                                timestamp_ms: ts,
+                                            };
+                                            if let Some(hidden) = broadcast_tracked(&mut app, &sender, &mut retries, &mut outbox, our_id, msg).await {
+                                                app.spoiler(nickname.clone(), hidden, mid, ts);
+                                            }
+                                        }
+                                    } else if !text.is_empty() {
+                                        post_chat(&mut app, &sender, &mut retries, &mut outbox, our_id, &nickname, text).await;
                                     }
                                 }
+                                // Up with an empty input enters message-select mode
+                                // (for revealing spoilers and other per-message actions).
+                                KeyCode::Up if app.input.is_empty() => {
+                                    app.enter_message_select();
+                                }
+                                // Cursor movement and deletion step over whole
+                                // grapheme clusters so multi-byte characters
+                                // (accents, CJK, emoji) are never split.
                                 KeyCode::Backspace => {
-                                    if app.cursor_pos > 0 {
-                                        app.cursor_pos -= 1;
-                                        app.input.remove(app.cursor_pos);
-                                    }
+                                    input::backspace(&mut app.input, &mut app.cursor_pos);
                                 }
                                 KeyCode::Left => {
-                                    // `saturating_sub(1)` subtracts 1 but clamps at 0
-                                    // instead of panicking on unsigned underflow.
-                                    app.cursor_pos = app.cursor_pos.saturating_sub(1);
+                                    input::move_left(&app.input, &mut app.cursor_pos);
                                 }
                                 KeyCode::Right => {
-                                    if app.cursor_pos < app.input.len() {
-                                        app.cursor_pos += 1;
-                                    }
+                                    input::move_right(&app.input, &mut app.cursor_pos);
                                 }
                                 KeyCode::Char(c) => {
-                                    // `String::insert()` inserts a character at a byte
-                                    // index, shifting subsequent bytes right. O(n) but
-                                    // fine for short chat input.
-                                    app.input.insert(app.cursor_pos, c);
-                                    app.cursor_pos += 1;
+                                    // Characters committed by an IME or dead-key
+                                    // composition arrive as ordinary `Char` events
+                                    // carrying the final codepoint.
+                                    input::insert_char(&mut app.input, &mut app.cursor_pos, c);
                                 }
                                 _ => {}
                             }
                         }
 
+                        // ── Message select mode ──────────────────────────
+                        AppMode::MessageSelect => {
+                            match key.code {
+                                KeyCode::Up => app.select_prev_message(),
+                                KeyCode::Down => app.select_next_message(),
+                                // Enter/Space reveal (or re-hide) a spoiler, or expand
+                                // (or collapse) a long message.
+                                KeyCode::Enter | KeyCode::Char(' ') => app.toggle_selected(),
+                                KeyCode::Char('c') => match app.selected_code() {
+                                    Some(code) => {
+                                        clipboard::copy(&code);
+                                        app.system(t!(app.config.lang, "code.copied"));
+                                    }
+                                    None => app.system(t!(app.config.lang, "code.none")),
+                                },
+                                // `t` opens the message's thread to read and reply in.
+                                KeyCode::Char('t') => app.open_selected_thread(),
+                                // `r` quote-replies to it from the input bar.
+                                KeyCode::Char('r') => app.reply_to_selected(),
+                                KeyCode::Esc | KeyCode::Tab => app.exit_message_select(),
+                                _ => {}
+                            }
+                        }
+
+                        // ── Connection map overlay ───────────────────────
+                        AppMode::Map => {
+                            if matches!(key.code, KeyCode::Esc | KeyCode::Enter | KeyCode::Char('q')) {
+                                app.close_map();
+                            }
+                        }
+
+                        // ── File gallery overlay ─────────────────────────
+                        AppMode::Gallery => {
+                            let items = app.gallery_items();
+                            let outcome = match &mut app.gallery {
+                                Some(gallery) => gallery.key(key, &items),
+                                None => gallery::Outcome::Close,
+                            };
+                            match outcome {
+                                gallery::Outcome::Open => {}
+                                gallery::Outcome::Close => app.close_gallery(),
+                                gallery::Outcome::Act(gallery::Action::Download(id)) => {
+                                    if let Some(offer) = app.transfers.request_download(&id, false) {
+                                        node::spawn_download(&blob_store, &endpoint, offer, app.members.room(), download_dir.clone(), transfer_tx.clone());
+                                    }
+                                }
+                                gallery::Outcome::Act(gallery::Action::Open(path)) => {
+                                    if app.transfers.request_open(&path, false) {
+                                        let name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+                                        open_download(&name, &path, path.clone(), transfer_tx.clone());
+                                    }
+                                }
+                                gallery::Outcome::Act(gallery::Action::Rerequest { offer, error }) => {
+                                    let id = offer.entry_id();
+                                    let others: Vec<_> = app
+                                        .peers
+                                        .keys()
+                                        .filter(|id| **id != offer.sender_id && **id != our_id)
+                                        .copied()
+                                        .collect();
+                                    // A failed download retries as `r` would; an
+                                    // earlier offer is asked of its sender first.
+                                    let plan = match &error {
+                                        Some(error) => transfer::retry_plan(error, &others),
+                                        None => std::iter::once(Strategy::Default).chain(others.into_iter().map(Strategy::Peer)).collect(),
+                                    };
+                                    let offer = match error {
+                                        Some(_) => {
+                                            app.transfers.start_download(&id);
+                                            Some(offer)
+                                        }
+                                        None => {
+                                            app.transfers.add_offer(offer);
+                                            app.transfers.request_download(&id, false)
+                                        }
+                                    };
+                                    if let Some(offer) = offer {
+                                        let name = app.peers.get(&offer.sender_id).map_or(offer.sender_nickname.clone(), |p| p.name.clone());
+                                        app.system(t!(app.config.lang, "gallery.rerequesting", filename = offer.filename, name = name));
+                                        node::spawn_retry(&blob_store, &endpoint, offer, app.members.room(), download_dir.clone(), plan, transfer_tx.clone());
+                                    }
+                                }
+                            }
+                            // A risky file asks first, in the files pane's overlay.
+                            if app.transfers.confirm.is_some() {
+                                app.close_gallery();
+                                app.focus_file_pane();
+                            }
+                        }
+
                         // ── File picker mode ─────────────────────────────
                         AppMode::FilePicker => {
                             // Reconstruct the `TermEvent` wrapper to pass to the
-                            // ratatui-explorer widget, which expects a full `Event`.
+                            // picker, which takes a full `Event`.
                             let key_event = TermEvent::Key(*key);
                             if let Some(picker) = &mut app.file_picker {
                                 match picker.handle(&key_event)? {
                                     FilePickerResult::Selected(path) => {
                                         let send_target = app.pending_send_target.take();
                                         app.close_file_picker();
-                                        match share_file(
-                                            &blob_store,
-                                            &sender,
-                                            &nickname,
-                                            our_id,
-                                            &path,
-                                            send_target.clone(),
-                                        ).await {
-                                            Ok((hash, filename, size, _mid, _ts, _mime_type)) => {
-                                                let offer = FileOffer {
-                                                    sender_nickname: "You".to_string(),
-                                                    sender_id: our_id,
-                                                    filename: filename.clone(),
-                                                    size,
-                                                    hash,
-                                                };
-                                                app.transfers.add_sent(offer);
-                                                let target_label = send_target
-                                                    .as_ref()
-                                                    .map(|t| format!(" (to {t})"))
-                                                    .unwrap_or_default();
-                                                app.system(format!("sharing{target_label}: {filename}"));
-                                            }
-                                            Err(e) => {
-                                                app.system(format!("failed to share file: {e}"));
-                                            }
+                                        if app.remember_share_dir(&path)
+                                            && let Err(e) = app.config.save()
+                                        {
+                                            app.system(t!(app.config.lang, "config.save_failed", error = e));
                                         }
+                                        let options = offer_options(&app, send_target.clone());
+                                        // A sealed copy exists only in the store.
+                                        let sealed = options.seal.is_some();
+                                        let result = node::share_file(&blob_store, &sender, &nickname, our_id, &path, options).await;
+                                        record_share(&mut app, our_id, result, send_target.as_deref(), sealed);
+                                    }
+                                    FilePickerResult::Folder(dir) => {
+                                        let send_target = app.pending_send_target.take();
+                                        app.close_file_picker();
+                                        // Packing a big folder takes a while, so it runs
+                                        // in the background; its entry shows progress
+                                        // under a stand-in hash until the real one is known.
+                                        let provisional = Hash::new(dir.as_os_str().as_encoded_bytes());
+                                        let packing = FileOffer {
+                                            sender_nickname: "You".to_string(),
+                                            sender_id: our_id,
+                                            filename: piper_chat::pack::archive_name(&dir),
+                                            size: 0,
+                                            hash: provisional,
+                                            key: None,
+                                            offer_id: new_message_id(),
+                                        };
+                                        let id = packing.entry_id();
+                                        app.transfers.add_packing(packing);
+                                        let options = offer_options(&app, send_target);
+                                        let (store, sender, nickname, tx) = (blob_store.clone(), sender.clone(), nickname.clone(), transfer_tx.clone());
+                                        tokio::spawn(async move {
+                                            let progress_tx = tx.clone();
+                                            let progress = move |bytes_done, total_bytes| {
+                                                // Dropped updates are fine; the next one catches up.
+                                                let _ = progress_tx.try_send(TransferEvent::Packing { id, bytes_done, total_bytes });
+                                            };
+                                            let result = node::share_folder(&store, &sender, &nickname, our_id, &dir, options, progress).await;
+                                            let result = result.map(|s| (s.hash, s.size, s.message_id)).map_err(|e| e.to_string());
+                                            let _ = tx.send(TransferEvent::Packed { id, result }).await;
+                                        });
                                     }
                                     FilePickerResult::Cancelled => {
                                         app.pending_send_target = None;
@@ -477,7 +1365,120 @@ async fn main() -> Result<()> {
                         // ── File pane mode ───────────────────────────────
                         AppMode::FilePane => {
                             match key.code {
-                                KeyCode::Tab | KeyCode::Esc => {
+                                // The risky-file overlay takes the next key: `y`
+                                // goes ahead, anything else cancels.
+                                _ if app.transfers.confirm.is_some() => {
+                                    let confirm = app.transfers.confirm.take();
+                                    match confirm {
+                                        _ if key.code != KeyCode::Char('y') => {}
+                                        Some(Confirm::Download(id)) => {
+                                            if let Some(offer) = app.transfers.request_download(&id, true) {
+                                                node::spawn_download(&blob_store, &endpoint, offer, app.members.room(), download_dir.clone(), transfer_tx.clone());
+                                            }
+                                        }
+                                        Some(Confirm::Open(path)) => {
+                                            let name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+                                            open_download(&name, &path, path.clone(), transfer_tx.clone());
+                                        }
+                                        None => {}
+                                    }
+                                }
+                                // Any key closes the details popup.
+                                _ if app.transfers.details.is_some() => {
+                                    app.transfers.details = None;
+                                }
+                                // The preview popup scrolls with Up/Down and
+                                // saves with `s`; any other key closes it,
+                                // dropping the bytes.
+                                _ if app.transfers.preview.is_some() => {
+                                    match key.code {
+                                        KeyCode::Up | KeyCode::Down => {
+                                            if let Some(preview) = &mut app.transfers.preview {
+                                                preview.scroll = match key.code {
+                                                    KeyCode::Up => preview.scroll.saturating_sub(1),
+                                                    _ => preview.scroll.saturating_add(1),
+                                                };
+                                            }
+                                        }
+                                        KeyCode::Char('s') => {
+                                            if let Some(preview) = app.transfers.preview.take() {
+                                                let sealed = app.transfers.entry(&preview.id).is_some_and(|e| e.offer.key.is_some());
+                                                save_preview(preview, sealed, &download_dir, transfer_tx.clone());
+                                            }
+                                        }
+                                        _ => app.transfers.preview = None,
+                                    }
+                                }
+                                KeyCode::Char('p') => match app.transfers.request_preview() {
+                                    Some(Ok(offer)) => {
+                                        app.system(t!(app.config.lang, "preview.fetching", filename = offer.filename));
+                                        node::spawn_preview(&endpoint, offer, app.members.room(), transfer_tx.clone());
+                                    }
+                                    Some(Err(size)) => {
+                                        let filename = app.transfers.selected_entry().map(|e| e.offer.filename.clone()).unwrap_or_default();
+                                        app.system(t!(
+                                            app.config.lang,
+                                            "preview.too_big",
+                                            filename = filename,
+                                            size = transfer::format_file_size(size),
+                                            max = transfer::format_file_size(transfer::PREVIEW_MAX)
+                                        ));
+                                    }
+                                    None => {}
+                                },
+                                KeyCode::Char('r') => {
+                                    if let Some(entry) = app.transfers.selected_entry()
+                                        && let TransferState::Failed(error) = &entry.state
+                                        && entry.offer.sender_id != our_id
+                                    {
+                                        let offer = entry.offer.clone();
+                                        let others: Vec<_> = app
+                                            .peers
+                                            .keys()
+                                            .filter(|id| **id != offer.sender_id && **id != our_id)
+                                            .copied()
+                                            .collect();
+                                        let plan = transfer::retry_plan(error, &others);
+                                        app.system(t!(app.config.lang, "download.retrying", filename = offer.filename));
+                                        app.transfers.start_download(&offer.entry_id());
+                                        node::spawn_retry(&blob_store, &endpoint, offer, app.members.room(), download_dir.clone(), plan, transfer_tx.clone());
+                                    }
+                                }
+                                KeyCode::Char('i') => {
+                                    app.transfers.details = app.transfers.selected_entry().map(|e| e.offer.entry_id());
+                                }
+                                KeyCode::Char('+') => {
+                                    if let Some(id) = app.transfers.selected_entry().map(|e| e.offer.entry_id())
+                                        && let Some(offer) = app.transfers.react(&id)
+                                    {
+                                        let msg = Message::FileReceived {
+                                            nickname: nickname.clone(),
+                                            endpoint_id: our_id,
+                                            hash: *offer.hash.as_bytes(),
+                                            sender: offer.sender_id,
+                                            offer_id: offer.offer_id,
+                                        };
+                                        broadcast(&mut app, &sender, &mut retries, &msg).await;
+                                        let name = app.peers.get(&offer.sender_id).map_or(offer.sender_nickname.clone(), |p| p.name.clone());
+                                        app.system(t!(app.config.lang, "file.reacted", name = name, filename = offer.filename));
+                                    }
+                                }
+                                KeyCode::Char('o') => {
+                                    if let Some(path) = app.transfers.selected_download().map(std::path::Path::to_path_buf)
+                                        && app.transfers.request_open(&path, false)
+                                    {
+                                        let name = app.transfers.selected_entry().map(|e| e.offer.filename.clone()).unwrap_or_default();
+                                        open_download(&name, &path, path.clone(), transfer_tx.clone());
+                                    }
+                                }
+                                KeyCode::Char('O') => {
+                                    if let Some(path) = app.transfers.selected_download() {
+                                        let dir = path.parent().unwrap_or(&download_dir).to_path_buf();
+                                        let name = app.transfers.selected_entry().map(|e| e.offer.filename.clone()).unwrap_or_default();
+                                        open_download(&name, path, dir, transfer_tx.clone());
+                                    }
+                                }
+                                KeyCode::Tab | KeyCode::BackTab | KeyCode::Esc => {
                                     app.focus_chat();
                                 }
                                 KeyCode::Up => {
@@ -490,23 +1491,24 @@ async fn main() -> Result<()> {
                                     if let Some(entry) = app.transfers.selected_entry() {
                                         match &entry.state {
                                             TransferState::Pending => {
-                                                let offer = entry.offer.clone();
-                                                let hash = offer.hash;
-                                                app.transfers.start_download(&hash);
-                                                spawn_download(
-                                                    &blob_store,
-                                                    &endpoint,
-                                                    offer,
-                                                    download_dir.clone(),
-                                                    transfer_tx.clone(),
-                                                );
+                                                let id = entry.offer.entry_id();
+                                                if let Some(offer) = app.transfers.request_download(&id, false) {
+                                                    node::spawn_download(
+                                                        &blob_store,
+                                                        &endpoint,
+                                                        offer,
+                                                        app.members.room(),
+                                                        download_dir.clone(),
+                                                        transfer_tx.clone(),
+                                                    );
+                                                }
                                             }
                                             TransferState::Complete(path) => {
-                                                let dir = path.parent().unwrap_or(&download_dir);
-                                                let _ = open::that(dir);
+                                                let dir = path.parent().unwrap_or(&download_dir).to_path_buf();
+                                                open_download(&entry.offer.filename, path, dir, transfer_tx.clone());
                                             }
                                             TransferState::Sharing => {
-                                                unshare_file(&mut app, &sender, &nickname).await?;
+                                                unshare_file(&mut app, &sender, &mut retries, &blob_store).await;
                                             }
                                             _ => {}
                                         }
@@ -518,9 +1520,26 @@ async fn main() -> Result<()> {
                     }
                 }
 
+                // ── Paste events ────────────────────────────────────────
+                // Delivered as one string thanks to bracketed paste mode.
+                if let Some(Ok(TermEvent::Paste(text))) = &ev {
+                    match app.mode {
+                        AppMode::Chat => app.paste(text),
+                        // The picker takes pasted paths and filter text.
+                        AppMode::FilePicker => {
+                            if let Some(picker) = &mut app.file_picker {
+                                picker.handle(&TermEvent::Paste(text.clone()))?;
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+
                 // ── Mouse events ────────────────────────────────────────
                 if let Some(Ok(TermEvent::Mouse(mouse))) = &ev {
                     match mouse.kind {
+                        // The gallery covers the whole screen; nothing under it is clickable.
+                        _ if app.gallery.is_some() => {}
                         MouseEventKind::Down(MouseButton::Left) => {
                             let needs_unshare = handle_mouse_click(
                                 &mut app,
@@ -532,7 +1551,7 @@ async fn main() -> Result<()> {
                                 &transfer_tx,
                             );
                             if needs_unshare {
-                                unshare_file(&mut app, &sender, &nickname).await?;
+                                unshare_file(&mut app, &sender, &mut retries, &blob_store).await;
                             }
                         }
                         MouseEventKind::ScrollUp => {
@@ -549,55 +1568,208 @@ async fn main() -> Result<()> {
             }
 
             // ── Branch 2: Gossip network events ──────────────────────────
-            // `receiver.try_next()` yields the next gossip event. The result is
+            // `next_event()` yields the next gossip event, or a message that
+            // arrived over a direct stream (see `net::direct`). The result is
             // `Result<Option<GossipEvent>>` — Ok(None) means the stream ended.
-            msg = receiver.try_next() => {
+            msg = next_event(&mut receiver, &mut direct_rx) => {
                 match msg {
                     Ok(Some(GossipEvent::Received(msg))) => {
-                        // Deserialize the binary payload back into a `Message` enum.
-                        // `postcard::from_bytes()` returns `Result<Message>` — if
-                        // the bytes don't match any variant, we silently ignore them
-                        // (forward compatibility with future message types).
-                        match postcard::from_bytes(&msg.content) {
+                        app.keepalive.traffic(std::time::Instant::now());
+                        // Verify and decode the signed payload back into a `Message`.
+                        // Anything that fails — an unknown variant from a newer
+                        // peer, another protocol version, a bad signature — is
+                        // silently ignored.
+                        let decoded = net::proto::decode(&msg.content);
+                        // In a room with a member cap or knock-to-join, peers
+                        // that aren't let in go unheard (see `admission`).
+                        if let (Some(admission), Ok((from, msg))) = (app.admission.as_mut(), &decoded)
+                            && !admission.screen(*from, msg)
+                        {
+                            continue;
+                        }
+                        if let (Some(hook), Ok((from, msg))) = (webhook.as_mut(), &decoded) {
+                            hook.forward(*from, msg);
+                        }
+                        // The signer, to tell apart peers who share a nickname.
+                        // Only the `Ok` arms below read it.
+                        let from = decoded.as_ref().map_or(our_id, |(from, _)| *from);
+                        // A sealed offer goes down the `FileOffer` arm, with
+                        // its wrapped key alongside.
+                        let (decoded, wrapped_key) = match decoded.map(|(_from, msg)| msg) {
+                            Ok(Message::SealedFileOffer { nickname, endpoint_id, filename, size, hash, message_id, timestamp_ms, mime_type, target, wrapped_key, offer_id }) => {
+                                (Ok(Message::FileOffer { nickname, endpoint_id, filename, size, hash, message_id, timestamp_ms, mime_type, target, offer_id }), Some(wrapped_key))
+                            }
+                            other => (other, None),
+                        };
+                        match decoded {
                             Ok(Message::Join { nickname: name, endpoint_id }) => {
-                                app.system(format!("{name} joined"));
-                                app.peers.insert(endpoint_id, PeerInfo {
+                                app.system(t!(app.config.lang, "peer.joined", name = name));
+                                app.warn_name_clash(&endpoint_id, &name);
+                                app.config.hooks.fire(HookEvent::PeerJoin { nickname: &name, endpoint_id });
+                                if let Some(log) = &app.event_log {
+                                    log.log(&LogEvent::Join { endpoint_id, nickname: &name });
+                                }
+                                app.contacts.saw(endpoint_id, &name, &ticket.topic_id, now_ms());
+                                app.add_peer(endpoint_id, PeerInfo {
                                     name,
                                     conn_type: ConnType::Unknown,
-                                });
-                            }
-                            Ok(Message::Chat { nickname, text, message_id, timestamp_ms }) => {
-                                if !app.seen_ids.contains(&message_id) {
-                                    app.chat(nickname, text, message_id, timestamp_ms);
-                                }
+                                    rtt_ms: None,
+                                    addr: None,
+                                }, true);
                             }
-                            Ok(Message::FileOffer { nickname: name, endpoint_id, filename, size, hash, message_id, timestamp_ms, mime_type, target }) => {
-                                if app.seen_ids.contains(&message_id) {
-                                    continue;
-                                }
-                                // Skip targeted offers not meant for us.
-                                if let Some(ref t) = target
+                            Ok(Message::Chat { nickname: name, text, message_id, timestamp_ms }) => {
+                                let name = app.display_name(&from, name);
+                                broadcast(&mut app, &sender, &mut retries, &Message::Ack { message_id, endpoint_id: our_id }).await;
+                                if !app.seen_ids.contains(&message_id)
+                                    && let Some(text) = app.filters.inbound(&name, text, now_ms())
+                                {
+                                    let afk_reply = app.afk_reply(&name, &text, &nickname);
+                                    app.config.hooks.fire(HookEvent::Message { nickname: &name, text: &text, timestamp_ms });
+                                    app.chat(name, text, message_id, timestamp_ms);
+                                    if let Some(reply) = afk_reply {
+                                        let mid = new_message_id();
+                                        let ts = now_ms();
+                                        let msg = Message::Chat {
+                                            nickname: nickname.clone(),
+                                            text: reply,
+                                            message_id: mid,
+                                            timestamp_ms: ts,
+                                        };
+                                        if let Some(reply) = broadcast_tracked(&mut app, &sender, &mut retries, &mut outbox, our_id, msg).await {
+                                            app.chat(nickname.clone(), reply, mid, ts);
+                                        }
+                                    }
+                                }
+                            }
+                            Ok(Message::ThreadChat { nickname: name, text, message_id, timestamp_ms, thread_id }) => {
+                                let name = app.display_name(&from, name);
+                                broadcast(&mut app, &sender, &mut retries, &Message::Ack { message_id, endpoint_id: our_id }).await;
+                                if !app.seen_ids.contains(&message_id)
+                                    && let Some(text) = app.filters.inbound(&name, text, now_ms())
+                                {
+                                    app.config.hooks.fire(HookEvent::Message { nickname: &name, text: &text, timestamp_ms });
+                                    app.thread_chat(name, text, message_id, timestamp_ms, thread_id);
+                                }
+                            }
+                            Ok(Message::Reply { nickname: name, text, message_id, timestamp_ms, reply_to }) => {
+                                let name = app.display_name(&from, name);
+                                broadcast(&mut app, &sender, &mut retries, &Message::Ack { message_id, endpoint_id: our_id }).await;
+                                if !app.seen_ids.contains(&message_id)
+                                    && let Some(text) = app.filters.inbound(&name, text, now_ms())
+                                {
+                                    app.config.hooks.fire(HookEvent::Message { nickname: &name, text: &text, timestamp_ms });
+                                    app.reply(name, text, message_id, timestamp_ms, reply_to);
+                                }
+                            }
+                            Ok(Message::Timer { nickname, label, timer_id, ends_ms }) => {
+                                let set_by = app.display_name(&from, nickname);
+                                app.start_timer(Timer { id: timer_id, label, set_by, ends_ms });
+                            }
+                            Ok(Message::Spoiler { nickname, text, message_id, timestamp_ms }) => {
+                                let nickname = app.display_name(&from, nickname);
+                                // Acked even when seen, like a chat message: a copy
+                                // resent over a direct stream means our first ack
+                                // was lost, and the sender is still waiting.
+                                broadcast(&mut app, &sender, &mut retries, &Message::Ack { message_id, endpoint_id: our_id }).await;
+                                if !app.seen_ids.contains(&message_id)
+                                    && let Some(text) = app.filters.inbound(&nickname, text, now_ms())
+                                {
+                                    app.spoiler(nickname, text, message_id, timestamp_ms);
+                                }
+                            }
+                            Ok(Message::RollCall { request_id, sent_ms, .. }) => {
+                                let reply = Message::Here {
+                                    request_id,
+                                    nickname: nickname.clone(),
+                                    endpoint_id: our_id,
+                                    echo_ms: sent_ms,
+                                };
+                                broadcast(&mut app, &sender, &mut retries, &reply).await;
+                            }
+                            Ok(Message::Ack { message_id, endpoint_id }) => {
+                                outbox.ack(&message_id, endpoint_id);
+                            }
+                            Ok(Message::BackfillRequest { since_ms, known, endpoint_id }) => {
+                                let response = Message::BackfillResponse {
+                                    entries: app.backfill_for(since_ms, &known),
+                                    endpoint_id: our_id,
+                                };
+                                match sender.encode(&response) {
+                                    Ok(bytes) => send_direct(&endpoint, endpoint_id, bytes),
+                                    Err(e) => app.system(t!(app.config.lang, "history.sync_failed", error = e)),
+                                }
+                            }
+                            Ok(Message::BackfillResponse { entries, endpoint_id }) => {
+                                if app.backfill_from == Some(endpoint_id) {
+                                    app.backfill_from = None;
+                                    // Missed messages are recent, so they go at
+                                    // the bottom rather than before everything.
+                                    let (lines, merged) = app.absorb_history(entries, &nickname);
+                                    app.system(t!(app.config.lang, "history.recovered", count = merged));
+                                    app.messages.extend(lines);
+                                }
+                            }
+                            Ok(Message::Here { request_id, nickname: name, endpoint_id, echo_ms }) => {
+                                // `echo_ms` is our own send time, so the difference
+                                // is a full gossip round trip on our clock.
+                                let rtt_ms = now_ms().saturating_sub(echo_ms);
+                                app.record_here(request_id, endpoint_id, name, rtt_ms);
+                            }
+                            Ok(Message::FileOffer { nickname: name, endpoint_id, filename, size, hash, message_id, timestamp_ms, mime_type, target, offer_id }) => {
+                                if app.seen_ids.contains(&message_id) {
+                                    continue;
+                                }
+                                // Skip targeted offers not meant for us.
+                                if let Some(ref t) = target
                                     && *t != nickname
                                 {
                                     continue;
                                 }
+                                // A re-broadcast of an offer we already hold.
                                 let blob_hash = Hash::from_bytes(hash);
+                                if app.transfers.entry(&EntryId { hash: blob_hash, sender: endpoint_id, offer_id }).is_some() {
+                                    app.seen_ids.insert(message_id);
+                                    continue;
+                                }
+                                // `offers_from` may turn the sender away; say so
+                                // once, so a missing offer isn't a mystery.
+                                let verified = app.contacts.get(&endpoint_id).is_some_and(|c| c.verified);
+                                if !app.config.offers_from.accepts(verified) {
+                                    app.seen_ids.insert(message_id);
+                                    app.system(t!(app.config.lang, "file.offer_refused", name = name, filename = filename));
+                                    continue;
+                                }
+                                // A key that won't unwrap was sealed for some other
+                                // room; the file would be unreadable.
+                                let key = wrapped_key.as_ref().map(|wrapped| seal::unwrap(&ticket.topic_id, wrapped));
+                                if key == Some(None) {
+                                    app.seen_ids.insert(message_id);
+                                    continue;
+                                }
                                 let offer = FileOffer {
                                     sender_nickname: name.clone(),
                                     sender_id: endpoint_id,
                                     filename: filename.clone(),
                                     size,
                                     hash: blob_hash,
+                                    key: key.flatten(),
+                                    offer_id,
                                 };
                                 app.transfers.add_offer(offer);
 
-                                let target_label = target
-                                    .as_ref()
-                                    .map(|_| " (with you)".to_string())
-                                    .unwrap_or_default();
+                                let for_us = target.is_some();
                                 app.seen_ids.insert(message_id);
+                                if let Some(wrapped_key) = wrapped_key {
+                                    app.push_history(net::HistoryEntry {
+                                        message_id: new_message_id(),
+                                        timestamp_ms,
+                                        kind: net::HistoryEntryKind::FileKey { hash, wrapped_key },
+                                    });
+                                }
+                                // Under the offer's ID, which history sync keys
+                                // it by, so a re-broadcast merges with the original.
                                 app.push_history(net::HistoryEntry {
-                                    message_id,
+                                    message_id: offer_id,
                                     timestamp_ms,
                                     kind: net::HistoryEntryKind::FileOffer {
                                         nickname: name.clone(),
@@ -609,34 +1781,39 @@ async fn main() -> Result<()> {
                                         target,
                                     },
                                 });
-                                app.system(format!(
-                                    "{name} shared{target_label}: {filename} ({})",
-                                    transfer::format_file_size(size)
-                                ));
+                                let size = transfer::format_file_size(size);
+                                app.system(if for_us {
+                                    t!(app.config.lang, "file.shared_with_you", name = name, filename = filename, size = size)
+                                } else {
+                                    t!(app.config.lang, "file.shared", name = name, filename = filename, size = size)
+                                });
                             }
-                            Ok(Message::FileRetract { nickname: name, hash, message_id, timestamp_ms }) => {
+                            // Names neither the offer nor its author, so any peer
+                            // could send one for anyone's file: offers are only
+                            // withdrawn by their author's `FileRevoke`.
+                            Ok(Message::FileRetract { .. }) => {}
+                            Ok(ref revoke @ Message::FileRevoke { hash, endpoint_id, message_id, timestamp_ms, at_ms, offer_id }) => {
                                 if app.seen_ids.contains(&message_id) {
                                     continue;
                                 }
                                 app.seen_ids.insert(message_id);
-                                let blob_hash = Hash::from_bytes(hash);
-                                if let Some(filename) = app.transfers.retract(&blob_hash) {
-                                    app.system(format!("{name} unshared: {filename}"));
+                                let id = EntryId { hash: Hash::from_bytes(hash), sender: endpoint_id, offer_id };
+                                let name = app.peers.get(&endpoint_id).map_or_else(|| endpoint_id.fmt_short().to_string(), |p| p.name.clone());
+                                let expiry = Expiry { at_ms, message_id };
+                                let now = now_ms();
+                                if let Some(filename) = app.transfers.withdraw(from, revoke, now) {
+                                    app.system(t!(app.config.lang, "file.unshared", name = name, filename = filename));
+                                    record_revoke(&mut app, &id, message_id, timestamp_ms);
+                                } else if let Some(entry) = app.transfers.entries.iter().find(|e| e.expiry == Some(expiry)) {
+                                    let left = chat::format_remaining(at_ms.saturating_sub(now));
+                                    let filename = entry.offer.filename.clone();
+                                    app.system(t!(app.config.lang, "file.expires", name = name, filename = filename, left = left));
                                 }
-                                // Remove matching FileOffer entries from history.
-                                app.history.retain(|e| {
-                                    !matches!(&e.kind, net::HistoryEntryKind::FileOffer { hash: h, .. } if *h == hash)
-                                });
-                                app.push_history(net::HistoryEntry {
-                                    message_id,
-                                    timestamp_ms,
-                                    kind: net::HistoryEntryKind::FileRetract { hash },
-                                });
                             }
                             Ok(Message::HistoryOffer { message_count, hash, endpoint_id, .. }) => {
                                 if !app.history_synced {
                                     app.history_synced = true;
-                                    app.system(format!("syncing {message_count} messages from history..."));
+                                    app.system(t!(app.config.lang, "history.syncing", count = message_count));
                                     let blob_hash = Hash::from_bytes(hash);
                                     // Spawn a background task to fetch the history blob.
                                     let store = blob_store.clone();
@@ -669,29 +1846,138 @@ async fn main() -> Result<()> {
                                     });
                                 }
                             }
+                            Ok(Message::JoinRequest { nickname: name, endpoint_id }) => {
+                                let Some(admission) = app.admission.as_mut() else { continue };
+                                match admission.knock(endpoint_id, name.clone()) {
+                                    Knock::New if admission.is_owner() => {
+                                        app.system(t!(app.config.lang, "knock.asks", name = name));
+                                    }
+                                    Knock::New => app.system(t!(app.config.lang, "knock.asks_creator", name = name)),
+                                    // A member who lost track of being let in
+                                    // (say, after a restart) gets the roster again.
+                                    Knock::Member if admission.is_owner() => {
+                                        let grants = Message::GrantOwner { grants: admission.grants().to_vec(), endpoint_id: our_id };
+                                        let msg = Message::JoinApproval { approvals: admission.approvals().to_vec(), endpoint_id: our_id };
+                                        broadcast(&mut app, &sender, &mut retries, &grants).await;
+                                        broadcast(&mut app, &sender, &mut retries, &msg).await;
+                                    }
+                                    Knock::Full if admission.is_owner() => {
+                                        app.system(t!(app.config.lang, "knock.full", name = name));
+                                        let msg = Message::JoinDenial { denied: endpoint_id, endpoint_id: our_id };
+                                        broadcast(&mut app, &sender, &mut retries, &msg).await;
+                                    }
+                                    _ => {}
+                                }
+                            }
+                            Ok(Message::JoinApproval { approvals, .. }) => {
+                                let Some(admission) = app.admission.as_mut() else { continue };
+                                for (id, name) in admission.accept(approvals) {
+                                    if id == our_id {
+                                        app.system(t!(app.config.lang, "knock.you_in"));
+                                        // Our first `Join` went unheard; now it counts.
+                                        let join = Message::Join { nickname: nickname.clone(), endpoint_id: our_id };
+                                        broadcast(&mut app, &sender, &mut retries, &join).await;
+                                    } else {
+                                        let name = name.unwrap_or_else(|| id.fmt_short().to_string());
+                                        app.system(t!(app.config.lang, "knock.let_in", name = name));
+                                    }
+                                }
+                            }
+                            Ok(Message::JoinDenial { denied, endpoint_id }) => {
+                                let Some(admission) = app.admission.as_mut() else { continue };
+                                if !admission.owners().contains(&endpoint_id) {
+                                    continue;
+                                }
+                                if denied == our_id && !admission.let_in() {
+                                    app.system(t!(app.config.lang, "knock.you_denied"));
+                                } else if let Some(name) = admission.denied(&denied) {
+                                    app.system(t!(app.config.lang, "knock.denied", name = name));
+                                }
+                            }
+                            // Only there to keep the paths warm; hearing it
+                            // (like any message) already reset our own wait.
+                            Ok(Message::KeepAlive { .. }) => {}
+                            // Turned into a `FileOffer` before this match.
+                            Ok(Message::SealedFileOffer { .. }) => {}
+                            // Only reactions to our own offers concern us.
+                            Ok(Message::FileReceived { nickname: name, endpoint_id, hash, sender, offer_id }) => {
+                                if sender != our_id {
+                                    continue;
+                                }
+                                let id = EntryId { hash: Hash::from_bytes(hash), sender, offer_id };
+                                let name = app.peers.get(&endpoint_id).map_or(name, |p| p.name.clone());
+                                if let Some(filename) = app.transfers.acknowledge(&id, endpoint_id, name.clone()) {
+                                    let text = t!(app.config.lang, "file.received_by", name = name, filename = filename);
+                                    app.transfer_toast(text.clone(), ToastKind::Success);
+                                    app.system(text);
+                                }
+                            }
+                            Ok(Message::GrantOwner { grants, .. }) => {
+                                let Some(admission) = app.admission.as_mut() else { continue };
+                                for id in admission.accept_grants(grants) {
+                                    if id == our_id {
+                                        app.system(t!(app.config.lang, "owner.you"));
+                                    } else {
+                                        let name = app.peers.get(&id).map(|p| p.name.clone()).unwrap_or_else(|| id.fmt_short().to_string());
+                                        app.system(t!(app.config.lang, "owner.granted", name = name));
+                                    }
+                                }
+                            }
                             Err(_) => {}
                         }
                     }
                     // `NeighborUp` fires when a new peer joins the gossip topic.
                     // We add them to the peers map and broadcast our Join message
                     // so they learn our display name.
+                    // Past `MAX_PEERS` a newcomer may be turned away (see
+                    // `App::add_peer`); it then gets no notice or history offer.
                     Ok(Some(GossipEvent::NeighborUp(id))) => {
-                        app.peers.insert(id, PeerInfo {
+                        let added = app.add_peer(id, PeerInfo {
                             name: id.fmt_short().to_string(),
                             conn_type: ConnType::Unknown,
-                        });
-                        app.system(format!("peer connected: {}", id.fmt_short()));
-                        let join = Message::Join {
-                            nickname: nickname.clone(),
-                            endpoint_id: our_id,
+                            rtt_ms: None,
+                            addr: None,
+                        }, false);
+                        if !added {
+                            continue;
+                        }
+                        app.system(t!(app.config.lang, "peer.connected", id = id.fmt_short()));
+                        // Until we're let into a knock-to-join room we ask
+                        // rather than announce ourselves. Its owners hand
+                        // each new neighbor the owner set and the roster of
+                        // approvals, grants first so the approvals verify.
+                        let join = match &app.admission {
+                            Some(admission) if !admission.let_in() => {
+                                Message::JoinRequest { nickname: nickname.clone(), endpoint_id: our_id }
+                            }
+                            _ => Message::Join { nickname: nickname.clone(), endpoint_id: our_id },
                         };
-                        let encoded = postcard::to_stdvec(&join)?;
-                        sender.broadcast(encoded.into()).await?;
+                        broadcast(&mut app, &sender, &mut retries, &join).await;
+                        if let Some(admission) = app.admission.as_ref().filter(|a| a.is_owner()) {
+                            let grants = (!admission.grants().is_empty())
+                                .then(|| Message::GrantOwner { grants: admission.grants().to_vec(), endpoint_id: our_id });
+                            let approvals = (!admission.approvals().is_empty())
+                                .then(|| Message::JoinApproval { approvals: admission.approvals().to_vec(), endpoint_id: our_id });
+                            for msg in grants.into_iter().chain(approvals) {
+                                broadcast(&mut app, &sender, &mut retries, &msg).await;
+                            }
+                        }
 
                         // Offer our history to the new peer if we have any.
-                        if !app.history.is_empty() {
-                            let history_bytes = postcard::to_stdvec(&app.history)?;
-                            let tag_info = blob_store.blobs().add_bytes(history_bytes).await?;
+                        // A failure here costs the peer our history, not the
+                        // session, so it is reported and the loop carries on.
+                        let added = if app.history.is_empty() {
+                            None
+                        } else {
+                            Some(match postcard::to_stdvec(&app.history) {
+                                Ok(bytes) => blob_store.blobs().add_bytes(bytes).await.map_err(|e| e.to_string()),
+                                Err(e) => Err(e.to_string()),
+                            })
+                        };
+                        if let Some(Err(e)) = &added {
+                            app.system(t!(app.config.lang, "history.sync_failed", error = e));
+                        }
+                        if let Some(Ok(tag_info)) = added {
                             let history_hash = *tag_info.hash.as_bytes();
                             let oldest = app.history.first().map(|e| e.timestamp_ms).unwrap_or(0);
                             let newest = app.history.last().map(|e| e.timestamp_ms).unwrap_or(0);
@@ -702,8 +1988,7 @@ async fn main() -> Result<()> {
                                 hash: history_hash,
                                 endpoint_id: our_id,
                             };
-                            let encoded = postcard::to_stdvec(&offer)?;
-                            sender.broadcast(encoded.into()).await?;
+                            broadcast(&mut app, &sender, &mut retries, &offer).await;
                         }
                     }
                     // `NeighborDown` fires when a peer disconnects from the topic.
@@ -711,23 +1996,43 @@ async fn main() -> Result<()> {
                     // `.map(|p| p.name)` extracts the name from the PeerInfo.
                     // `.unwrap_or_else()` provides a fallback if the peer wasn't in our map.
                     Ok(Some(GossipEvent::NeighborDown(id))) => {
-                        let name = app.peers.remove(&id)
+                        if let Some(admission) = app.admission.as_mut() {
+                            admission.leave(&id);
+                        }
+                        let name = app.remove_peer(&id)
                             .map(|p| p.name)
                             .unwrap_or_else(|| id.fmt_short().to_string());
-                        app.system(format!("{name} left"));
+                        if let Some(log) = &app.event_log {
+                            log.log(&LogEvent::Leave { endpoint_id: id, nickname: &name });
+                        }
+                        app.system(t!(app.config.lang, "peer.left", name = name));
                     }
                     // `Lagged` means we fell behind on processing gossip events and
                     // some messages were dropped. This happens if the event loop is
                     // too slow to keep up with incoming traffic.
+                    // Rather than lose those messages, ask one neighbor directly
+                    // for anything newer than what we last saw.
                     Ok(Some(GossipEvent::Lagged)) => {
-                        app.system("warning: gossip stream lagged");
+                        app.system(t!(app.config.lang, "gossip.lagged"));
+                        if let Some((&peer, info)) = app.peers.iter().find(|(id, _)| **id != our_id) {
+                            app.system(t!(app.config.lang, "gossip.requesting", name = info.name));
+                            let (since_ms, known) = app.backfill_bounds();
+                            let request = Message::BackfillRequest { since_ms, known, endpoint_id: our_id };
+                            match sender.encode(&request) {
+                                Ok(bytes) => {
+                                    send_direct(&endpoint, peer, bytes);
+                                    app.backfill_from = Some(peer);
+                                }
+                                Err(e) => app.system(t!(app.config.lang, "history.sync_failed", error = e)),
+                            }
+                        }
                     }
                     Ok(None) => {
-                        app.system("gossip stream closed");
+                        app.system(t!(app.config.lang, "gossip.closed"));
                         app.should_quit = true;
                     }
                     Err(e) => {
-                        app.system(format!("gossip error: {e}"));
+                        app.system(t!(app.config.lang, "gossip.error", error = e));
                     }
                 }
             }
@@ -738,16 +2043,69 @@ async fn main() -> Result<()> {
             // all senders have been dropped (no more background tasks).
             Some(event) = transfer_rx.recv() => {
                 match event {
-                    TransferEvent::Progress { hash, bytes_received, total_bytes } => {
-                        app.transfers.update_progress(&hash, bytes_received, total_bytes);
+                    TransferEvent::Progress { id, progress } => {
+                        app.transfers.fetch_progress(&id, progress);
+                    }
+                    TransferEvent::Complete { id, filename, path, content } => {
+                        app.config.hooks.fire(HookEvent::FileComplete { filename: &filename, path: &path });
+                        if app.config.checksum_sidecar {
+                            spawn_sidecar(filename.clone(), path.clone(), transfer_tx.clone());
+                        }
+                        let via = app.transfers.current_attempt(&id).map(str::to_string);
+                        app.transfers.complete_download(&id, path);
+                        if let Some(content) = content {
+                            app.transfers.opened(&id, content);
+                        }
+                        let text = match via {
+                            Some(via) => t!(app.config.lang, "download.complete_via", filename = filename, via = via),
+                            None => t!(app.config.lang, "download.complete", filename = filename),
+                        };
+                        app.transfer_toast(text.clone(), ToastKind::Success);
+                        app.system(text);
+                    }
+                    TransferEvent::Failed { id, filename, error } => {
+                        app.transfers.fail_download(&id, error.clone());
+                        let text = t!(app.config.lang, "download.failed", filename = filename, error = error);
+                        app.transfer_toast(text.clone(), ToastKind::Error);
+                        app.system(text);
+                    }
+                    TransferEvent::Packing { id, bytes_done, total_bytes } => {
+                        app.transfers.update_packing(&id, bytes_done, total_bytes);
+                    }
+                    TransferEvent::Packed { id, result: Ok((real, size, offer_id)) } => {
+                        if let Some(filename) = app.transfers.finish_packing(&id, real, size, offer_id) {
+                            track_artifact(&mut app, real, &filename);
+                            app.system(t!(app.config.lang, "file.sharing", filename = filename));
+                        }
                     }
-                    TransferEvent::Complete { hash, filename, path } => {
-                        app.transfers.complete_download(&hash, path);
-                        app.system(format!("download complete: {filename}"));
+                    TransferEvent::Packed { id, result: Err(error) } => {
+                        app.transfers.fail_download(&id, error.clone());
+                        app.system(t!(app.config.lang, "file.share_failed", error = error));
                     }
-                    TransferEvent::Failed { hash, filename, error } => {
-                        app.transfers.fail_download(&hash, error.clone());
-                        app.system(format!("download failed: {filename} — {error}"));
+                    TransferEvent::Verified { filename, result } => {
+                        app.system(match result {
+                            Ok(true) => t!(app.config.lang, "verify.ok", filename = filename),
+                            Ok(false) => t!(app.config.lang, "verify.mismatch", filename = filename),
+                            Err(error) => t!(app.config.lang, "verify.failed", filename = filename, error = error),
+                        });
+                    }
+                    TransferEvent::Attempt { id, strategy } => {
+                        let label = strategy_label(&app, strategy);
+                        app.transfers.start_attempt(&id, label);
+                    }
+                    TransferEvent::SidecarFailed { filename, error } => {
+                        app.system(t!(app.config.lang, "verify.sidecar_failed", filename = filename, error = error));
+                    }
+                    TransferEvent::Previewed { id, filename, result: Ok(bytes) } => {
+                        // The popup's keys are the files pane's. With the
+                        // picker open it waits until the pane has focus.
+                        if !matches!(app.mode, AppMode::FilePicker) {
+                            app.focus_file_pane();
+                        }
+                        app.transfers.show_preview(id, filename, bytes);
+                    }
+                    TransferEvent::Previewed { filename, result: Err(error), .. } => {
+                        app.system(t!(app.config.lang, "preview.failed", filename = filename, error = error));
                     }
                 }
             }
@@ -758,127 +2116,203 @@ async fn main() -> Result<()> {
                     Ok(data) => {
                         match postcard::from_bytes::<Vec<net::HistoryEntry>>(&data) {
                             Ok(mut entries) => {
-                                entries.sort_by_key(|e| e.timestamp_ms);
-                                let mut merged = 0u32;
-                                // Collect historical messages to prepend.
-                                let mut historical: Vec<chat::ChatLine> = Vec::new();
-                                for entry in entries {
-                                    if app.seen_ids.contains(&entry.message_id) {
-                                        continue;
-                                    }
-                                    app.seen_ids.insert(entry.message_id);
-                                    merged += 1;
-                                    match &entry.kind {
-                                        net::HistoryEntryKind::Chat { nickname: nick, text } => {
-                                            historical.push(chat::ChatLine::Chat {
-                                                nickname: nick.clone(),
-                                                text: text.clone(),
-                                                timestamp_ms: entry.timestamp_ms,
-                                            });
-                                        }
-                                        net::HistoryEntryKind::FileOffer {
-                                            nickname: nick,
-                                            endpoint_id: eid,
-                                            filename,
-                                            size,
-                                            hash,
-                                            mime_type: _,
-                                            target,
-                                        } => {
-                                            // Skip targeted offers not meant for us.
-                                            if let Some(t) = target
-                                                && *t != nickname
-                                            {
-                                                continue;
-                                            }
-                                            // Add to TransferManager so synced offers are downloadable.
-                                            let blob_hash = Hash::from_bytes(*hash);
-                                            let offer = FileOffer {
-                                                sender_nickname: nick.clone(),
-                                                sender_id: *eid,
-                                                filename: filename.clone(),
-                                                size: *size,
-                                                hash: blob_hash,
-                                            };
-                                            app.transfers.add_offer(offer);
-
-                                            historical.push(chat::ChatLine::System(format!(
-                                                "{nick} shared: {filename} ({})",
-                                                transfer::format_file_size(*size)
-                                            )));
-                                        }
-                                        net::HistoryEntryKind::FileRetract { hash } => {
-                                            // Replay retract: remove any previously-added offer.
-                                            let blob_hash = Hash::from_bytes(*hash);
-                                            app.transfers.retract(&blob_hash);
-                                        }
-                                        net::HistoryEntryKind::System(text) => {
-                                            historical.push(chat::ChatLine::System(text.clone()));
-                                        }
-                                    }
-                                    app.history.push(entry);
-                                }
+                                // A peer wrote these, so they're scrubbed like
+                                // live messages (see `sanitize`).
+                                entries.iter_mut().for_each(|e| e.kind.sanitize());
+                                let (mut historical, merged) = app.absorb_history(entries, &nickname);
                                 // Prepend historical messages before current session messages.
                                 historical.append(&mut app.messages);
                                 app.messages = historical;
-                                // Cap history at 1000.
-                                if app.history.len() > 1000 {
-                                    app.history.drain(0..app.history.len() - 1000);
-                                }
-                                app.system(format!("history sync complete: {merged} new messages"));
+                                app.system(t!(app.config.lang, "history.synced", count = merged));
                             }
                             Err(e) => {
-                                app.system(format!("history sync failed: invalid data ({e})"));
+                                app.system(t!(app.config.lang, "history.invalid", error = e));
                             }
                         }
                     }
                     Err(e) => {
-                        app.system(format!("history sync failed: {e}"));
+                        app.system(t!(app.config.lang, "history.sync_failed", error = e));
+                    }
+                }
+            }
+
+            // ── Branch 5: Webhook bridge ─────────────────────────────────
+            Some(event) = webhook_rx.recv() => {
+                match event {
+                    WebhookEvent::Outgoing(text) => {
+                        let mid = new_message_id();
+                        let ts = now_ms();
+                        let msg = Message::Chat {
+                            nickname: nickname.clone(),
+                            text,
+                            message_id: mid,
+                            timestamp_ms: ts,
+                        };
+                        if let Some(text) = broadcast_tracked(&mut app, &sender, &mut retries, &mut outbox, our_id, msg).await {
+                            app.chat(nickname.clone(), text, mid, ts);
+                        }
                     }
+                    WebhookEvent::Error(e) => app.system(e),
                 }
             }
 
-            // ── Branch 5: UI tick (50ms) ─────────────────────────────────
+            // ── Branch 6: UI tick (50ms) ─────────────────────────────────
             // The tick branch fires every 50ms. We query live connection info
             // from the endpoint — iroh may upgrade connections from relay to
             // direct (via UDP hole-punching) at any time.
             _ = tick.tick() => {
-                // Collect peer IDs first to avoid holding a mutable borrow
-                // on `app.peers` across the await point.
+                app.finish_roll_call_if_due(std::time::Instant::now());
+                app.toasts.tick(std::time::Instant::now());
+                app.finish_timers(now_ms());
+                // Offers whose expiry has passed go away on every peer's own
+                // clock; our own also give up their blob.
+                for entry in app.transfers.expire(now_ms()) {
+                    let Some(expiry) = entry.expiry else { continue };
+                    record_revoke(&mut app, &entry.offer.entry_id(), expiry.message_id, expiry.at_ms);
+                    let filename = entry.offer.filename;
+                    if matches!(entry.state, TransferState::Sharing) {
+                        forget_share(&mut app, &blob_store, entry.offer.hash, &filename).await;
+                    } else {
+                        let name = entry.offer.sender_nickname;
+                        app.system(t!(app.config.lang, "file.expired", name = name, filename = filename));
+                    }
+                }
+                // The open map animates, and re-measures round trips with a
+                // silent roll call every few seconds.
+                if let Some(map) = &mut app.map {
+                    map.tick();
+                    if let Some(request_id) = map.ping_due(std::time::Instant::now()) {
+                        let ping = Message::RollCall { request_id, endpoint_id: our_id, sent_ms: now_ms() };
+                        broadcast(&mut app, &sender, &mut retries, &ping).await;
+                    }
+                }
+                // Chat messages some peers never acked go to them directly.
+                for (bytes, peers) in outbox.take_overdue(std::time::Instant::now()) {
+                    for peer in peers {
+                        send_direct(&endpoint, peer, bytes.clone());
+                    }
+                }
+                // Broadcasts that failed get another go once their backoff is up.
+                let now = std::time::Instant::now();
+                let mut recovered = false;
+                for retry in retries.take_due(now) {
+                    match sender.broadcast_encoded(retry.bytes.clone()).await {
+                        Ok(()) => recovered = true,
+                        Err(e) => {
+                            let attempts = retry.attempts + 1;
+                            if !retries.failed(retry, now) {
+                                app.system(t!(app.config.lang, "send.gave_up", attempts = attempts, error = e));
+                            }
+                        }
+                    }
+                }
+                if recovered && retries.is_empty() {
+                    METRICS.reconnect();
+                    app.system(t!(app.config.lang, "send.recovered"));
+                }
+                // Relative timestamps ("2m ago") are computed against this.
+                app.now_ms = now_ms();
+                // Query every peer at once, so the whole tick waits at most
+                // one `QUERY_TIMEOUT` however many peers there are, then
+                // apply the answers.
                 let peer_ids: Vec<_> = app.peers.keys()
                     .filter(|id| **id != our_id)
                     .copied()
                     .collect();
-                for id in peer_ids {
-                    let conn_type = match endpoint.remote_info(id).await {
-                        Some(info) => {
-                            // A peer can have multiple active addresses (relay + direct).
-                            // Prefer direct (IP) if any active address is direct.
-                            use iroh::endpoint::TransportAddrUsage;
-                            let mut has_relay = false;
-                            let mut has_direct = false;
-                            for a in info.addrs().filter(|a| matches!(a.usage(), TransportAddrUsage::Active)) {
-                                if a.addr().is_ip() {
-                                    has_direct = true;
-                                } else {
-                                    has_relay = true;
-                                }
-                            }
-                            if has_direct {
-                                ConnType::Direct
-                            } else if has_relay {
-                                ConnType::Relay
-                            } else {
-                                ConnType::Unknown
+                let paths = n0_future::join_all(
+                    peer_ids.iter().map(|id| net::tracker::path(&endpoint, *id)),
+                ).await;
+                for (id, path) in peer_ids.into_iter().zip(paths) {
+                    // `None` means the endpoint was slow to answer; keep the
+                    // last known type and ask again next tick.
+                    if let Some((conn_type, addr)) = path
+                        && let Some(peer) = app.peers.get_mut(&id)
+                    {
+                        if let Some(log) = &app.event_log
+                            && std::mem::discriminant(&peer.conn_type) != std::mem::discriminant(&conn_type)
+                            && let Some(path) = match conn_type {
+                                ConnType::Direct => Some("direct"),
+                                ConnType::Relay => Some("relay"),
+                                ConnType::Unknown | ConnType::You => None,
                             }
+                        {
+                            let addr = addr.map(|a| a.to_string());
+                            log.log(&LogEvent::Path { endpoint_id: id, nickname: &peer.name, path, addr });
                         }
-                        None => ConnType::Unknown,
-                    };
-                    if let Some(peer) = app.peers.get_mut(&id) {
                         peer.conn_type = conn_type;
+                        peer.addr = addr;
+                    }
+                }
+            }
+
+            // ── Branch 7: Status clock (1s) ──────────────────────────────
+            _ = clock.tick() => {
+                app.update_clock(now_ms(), session_start.elapsed());
+                METRICS.set_peers(app.peers.keys().filter(|id| **id != our_id).count());
+                if let Some(log) = app.event_log.as_mut() {
+                    log.transfers(&app.transfers);
+                }
+                let connected = app.peers.values().any(|p| !matches!(p.conn_type, ConnType::You));
+                app.joining.tick(connected, app.config.join_timeout_secs, now_ms());
+                let idle_ms = now_ms().saturating_sub(last_input_ms);
+                if app.lock.is_none()
+                    && app.config.idle_lock_secs > 0
+                    && app.config.lock_passphrase.is_some()
+                    && idle_ms >= app.config.idle_lock_secs * 1000
+                {
+                    app.lock = Some(Lock::new(true, now_ms()));
+                }
+                if let Some(slept) = wake.check(now_ms(), std::time::Instant::now()) {
+                    // Back from a suspend: tell iroh to re-probe the network,
+                    // then subscribe again with everyone we knew as bootstrap
+                    // peers, so gossip re-dials them instead of waiting for
+                    // the dead connections to time out.
+                    let known = app.peers.keys().copied().filter(|id| *id != our_id);
+                    let rejoin = ChatTicket { bootstrap: ticket.bootstrap.iter().copied().chain(known).collect(), ..ticket.clone() };
+                    app.resumed(slept);
+                    METRICS.reconnect();
+                    endpoint.network_change().await;
+                    match node.join(&rejoin).await {
+                        Ok(joined) => (sender, receiver) = joined,
+                        Err(e) => app.system(t!(app.config.lang, "wake.rejoin_failed", error = e)),
+                    }
+                }
+                if app.keepalive.due(std::time::Instant::now()) {
+                    let msg = Message::KeepAlive { endpoint_id: our_id, sent_ms: now_ms() };
+                    broadcast(&mut app, &sender, &mut retries, &msg).await;
+                }
+                // Files shared by reference that changed on disk can't be
+                // served anymore (see `blobstore`); withdraw their offers.
+                for (hash, why) in blob_store.sources().recheck() {
+                    invalidate_share(&mut app, &sender, &mut retries, &blob_store, hash, why).await;
+                }
+                // Peers who fetched one of our files count as its downloads.
+                for (hash, by) in node.fetched.take() {
+                    let name = app.peers.get(&by).map_or_else(|| by.fmt_short().to_string(), |p| p.name.clone());
+                    app.transfers.served(&hash, by, &name);
+                }
+                if let Some(dir) = &session_dir {
+                    let mut rejoin = ticket.clone();
+                    rejoin.bootstrap.extend(app.peers.keys().copied());
+                    let rejoin = <ChatTicket as Ticket>::serialize(&rejoin);
+                    let current = Session::capture(rejoin, &nickname, app.room_name.clone(), &app.transfers, keep_keys);
+                    if saved_session.as_ref() != Some(&current) {
+                        // Reported once per change, not every second.
+                        if let Err(e) = current.save(dir) {
+                            app.system(t!(app.config.lang, "session.save_failed", error = e));
+                        }
+                        saved_session = Some(current);
                     }
                 }
             }
+
+            // ── Branch 8: Session lock passphrase checks ─────────────────
+            Some(done) = lock_rx.recv() => {
+                if let Some(session_lock) = app.lock.as_mut().filter(|l| l.checking) {
+                    let outcome = session_lock.done(done);
+                    lock_outcome(&mut app, outcome, &lock_tx, &mut last_input_ms);
+                }
+            }
         }
 
         if app.should_quit {
@@ -889,49 +2323,172 @@ async fn main() -> Result<()> {
     // ── Restore terminal ─────────────────────────────────────────────────────
     // These cleanup calls mirror the setup — we disable raw mode and leave the
     // alternate screen to restore the user's original terminal state.
+    if keyboard_enhanced {
+        input::disable_keyboard_enhancement();
+    }
     disable_raw_mode()?;
-    execute!(std::io::stdout(), LeaveAlternateScreen, DisableMouseCapture)?;
+    execute!(
+        std::io::stdout(),
+        LeaveAlternateScreen,
+        DisableMouseCapture,
+        DisableBracketedPaste
+    )?;
 
     // ── Shutdown ─────────────────────────────────────────────────────────────
-    // `router.shutdown()` gracefully stops accepting new connections and waits
-    // for in-flight protocol handlers to finish. `endpoint.close()` shuts down
-    // the QUIC endpoint and all its connections.
-    router.shutdown().await?;
-    endpoint.close().await;
+    // Save (or clear) this room's draft. The terminal is already restored,
+    // so a failure can be reported on stderr without garbling the screen.
+    drafts.set(&ticket.topic_id, &app.input);
+    if let Err(e) = drafts.save() {
+        eprintln!("failed to save draft: {e}");
+    }
+    if let Err(e) = app.contacts.save() {
+        eprintln!("failed to save contacts: {e}");
+    }
+    if let Err(e) = archive_room(&mut archive, &mut app, &ticket, &nickname, last_read_ms).await {
+        eprintln!("failed to archive room: {e}");
+    }
+    // Blobs made for our shares aren't offered any more.
+    for hash in app.artifacts.drain_except(&[]) {
+        if let Err(e) = node::delete_tags(&blob_store, hash).await {
+            eprintln!("failed to remove a shared artifact: {e}");
+        }
+    }
+    if let Err(e) = app.artifacts.save() {
+        eprintln!("failed to save the artifact list: {e}");
+    }
+    if let Some(mut log) = app.event_log.take() {
+        log.transfers(&app.transfers);
+        if let Err(e) = log.close().await {
+            eprintln!("failed to write the event log: {e}");
+        }
+    }
+    // A clean exit: nothing to recover next time.
+    if let Some(dir) = &session_dir
+        && let Err(e) = session::clear(dir)
+    {
+        eprintln!("failed to clear session: {e}");
+    }
+    node.shutdown().await?;
 
     Ok(())
 }
 
-// ── Help ─────────────────────────────────────────────────────────────────────
-
-/// Display help text as system messages.
-fn show_help(app: &mut App) {
-    app.system("── Commands ──────────────────────────────");
-    app.system("  /help           Show this help");
-    app.system("  /send           Open file picker to share a file");
-    app.system("  /sendto <name>  Send a file to a specific peer");
-    app.system("── Keys (chat) ───────────────────────────");
-    app.system("  Enter        Send message");
-    app.system("  Ctrl+F       Open file picker");
-    app.system("  Ctrl+T       Toggle dark/light theme");
-    app.system("  Ctrl+Y       Copy invite ticket to clipboard");
-    app.system("  Tab          Focus file pane (when visible)");
-    app.system("  Esc          Quit");
-    app.system("── Keys (file pane) ──────────────────────");
-    app.system("  Up/Down      Select entry");
-    app.system("  Enter        Download / open folder / unshare");
-    app.system("  Tab/Esc      Return to chat");
-    app.system("── Keys (file picker) ────────────────────");
-    app.system("  Up/Down      Navigate files");
-    app.system("  Left/Right   Parent / enter directory");
-    app.system("  Enter        Select file to share");
-    app.system("  Esc          Cancel");
-    app.system("── Mouse ─────────────────────────────────");
-    app.system("  Click        Focus pane / trigger action");
-    app.system("  Scroll       Scroll messages up/down");
-    app.system("──────────────────────────────────────────");
+/// Play the `--json-log` recording at `path` through the chat UI
+/// (`piper-chat replay`; see `replay`). Space pauses, Right applies the next
+/// event at once, + and - double and halve the speed, Up and Down scroll,
+/// q or Esc quits. The screen stays up after the last event.
+async fn replay(path: &std::path::Path, mut speed: f64, ascii: bool) -> Result<()> {
+    let (events, skipped) = replay::load(path).with_context(|| format!("reading {}", path.display()))?;
+    let mut player = Player::new(events);
+    let mut app = App::new();
+    app.config = Config::load();
+    app.theme = Theme::new(app.config.theme, ascii);
+    app.system(t!(app.config.lang, "replay.started", count = player.len(), path = path.display()));
+    if skipped > 0 {
+        app.system(t!(app.config.lang, "replay.skipped", count = skipped));
+    }
+
+    enable_raw_mode()?;
+    execute!(std::io::stdout(), EnterAlternateScreen, EnableMouseCapture)?;
+    let mut terminal = ratatui::Terminal::new(ratatui::backend::CrosstermBackend::new(std::io::stdout()))?;
+    let mut events = EventStream::new();
+    let mut tick = interval(Duration::from_millis(50));
+    let mut paused = false;
+    // When the next event is due; recomputed after each one.
+    let next_due = |player: &Player, speed| {
+        let wait = player.gap().map_or(Duration::ZERO, |(last, next)| replay::delay(last, next, speed));
+        tokio::time::Instant::now() + wait
+    };
+    let mut due = next_due(&player, speed);
+
+    loop {
+        terminal.draw(|f| ui(f, &mut app))?;
+
+        tokio::select! {
+            ev = events.next() => {
+                let Some(Ok(TermEvent::Key(key))) = ev else { continue };
+                if key.kind != KeyEventKind::Press {
+                    continue;
+                }
+                let note = match key.code {
+                    KeyCode::Char('q') | KeyCode::Esc => break,
+                    KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => break,
+                    KeyCode::Char(' ') => {
+                        paused = !paused;
+                        due = next_due(&player, speed);
+                        Some(if paused { t!(app.config.lang, "replay.paused") } else { t!(app.config.lang, "replay.resumed") })
+                    }
+                    KeyCode::Right => {
+                        player.step(&mut app);
+                        due = next_due(&player, speed);
+                        None
+                    }
+                    KeyCode::Char('+') | KeyCode::Char('-') => {
+                        speed = if key.code == KeyCode::Char('+') { speed * 2.0 } else { speed / 2.0 }.clamp(1.0 / 16.0, 64.0);
+                        due = next_due(&player, speed);
+                        Some(t!(app.config.lang, "replay.speed", speed = speed))
+                    }
+                    KeyCode::Up => {
+                        app.scroll_offset = app.scroll_offset.saturating_add(3);
+                        None
+                    }
+                    KeyCode::Down => {
+                        app.scroll_offset = app.scroll_offset.saturating_sub(3);
+                        None
+                    }
+                    _ => None,
+                };
+                if let Some(note) = note {
+                    app.toasts.push(note, ToastKind::Success, std::time::Instant::now());
+                }
+            }
+            _ = tokio::time::sleep_until(due), if !paused && player.position() < player.len() => {
+                player.step(&mut app);
+                if player.position() == player.len() {
+                    app.system(t!(app.config.lang, "replay.finished"));
+                }
+                due = next_due(&player, speed);
+            }
+            _ = tick.tick() => app.toasts.tick(std::time::Instant::now()),
+        }
+    }
+
+    disable_raw_mode()?;
+    execute!(std::io::stdout(), LeaveAlternateScreen, DisableMouseCapture)?;
+    Ok(())
 }
 
+/// Fail unless `name` is a nickname peers will accept (`net::valid_nickname`).
+fn check_nickname(name: &str) -> Result<()> {
+    if !net::valid_nickname(name) {
+        anyhow::bail!("nicknames must be 1 to {} characters and not blank", net::MAX_NICKNAME);
+    }
+    Ok(())
+}
+
+/// What a profile name may be (`profile::valid_name`).
+fn profile_rule() -> String {
+    format!("1 to {} ASCII letters, digits, '-' or '_'", profile::MAX_NAME)
+}
+
+/// Check a `--profile` name before anything uses it.
+fn profile_arg(text: &str) -> Result<String, String> {
+    if profile::valid_name(text) {
+        Ok(text.to_string())
+    } else {
+        Err(format!("profile names are {}", profile_rule()))
+    }
+}
+
+/// Parse a duration flag the way slash commands take one (`chat::parse_duration`).
+fn duration_arg(text: &str) -> Result<Duration, String> {
+    chat::parse_duration(text).ok_or_else(|| format!("{text:?} isn't a duration like 90s, 10m, 2h or 7d"))
+}
+
+/// The shrug kaomoji appended by `/shrug`. The backslash is doubled in
+/// source because `\\` is Rust's escape for a literal `\`.
+const SHRUG: &str = "¯\\_(ツ)_/¯";
+
 // ── Mouse handling ───────────────────────────────────────────────────────────
 
 /// Handle a left mouse click by checking registered click regions.
@@ -942,7 +2499,7 @@ fn handle_mouse_click(
     app: &mut App,
     col: u16,
     row: u16,
-    store: &FsStore,
+    store: &Blobs,
     endpoint: &iroh::Endpoint,
     download_dir: &std::path::Path,
     transfer_tx: &tokio::sync::mpsc::Sender<TransferEvent>,
@@ -964,44 +2521,39 @@ fn handle_mouse_click(
                 ClickAction::CopyTicket => {
                     copy_ticket_to_clipboard(app);
                 }
-                ClickAction::DownloadTransfer(hash) => {
-                    let hash = *hash;
-                    if let Some(entry) = app
-                        .transfers
-                        .entries
-                        .iter()
-                        .find(|e| e.offer.hash == hash && matches!(e.state, TransferState::Pending))
-                    {
-                        let offer = entry.offer.clone();
-                        app.transfers.start_download(&hash);
-                        spawn_download(
+                ClickAction::NextPeersPage => {
+                    app.peers_page += 1;
+                }
+                ClickAction::DownloadTransfer(id) => {
+                    if let Some(offer) = app.transfers.request_download(id, false) {
+                        node::spawn_download(
                             store,
                             endpoint,
                             offer,
+                            app.members.room(),
                             download_dir.to_path_buf(),
                             transfer_tx.clone(),
                         );
+                    } else if app.transfers.confirm.is_some() {
+                        // The overlay's answer is read in file pane mode.
+                        app.mode = AppMode::FilePane;
                     }
                 }
-                ClickAction::OpenTransfer(hash) => {
-                    if let Some(entry) = app
-                        .transfers
-                        .entries
-                        .iter()
-                        .find(|e| e.offer.hash == *hash)
+                ClickAction::OpenTransfer(id) => {
+                    if let Some(entry) = app.transfers.entry(id)
                         && let TransferState::Complete(path) = &entry.state
                     {
-                        let dir = path.parent().unwrap_or(download_dir);
-                        let _ = open::that(dir);
+                        let dir = path.parent().unwrap_or(download_dir).to_path_buf();
+                        open_download(&entry.offer.filename, path, dir, transfer_tx.clone());
                     }
                 }
-                ClickAction::UnshareTransfer(hash) => {
+                ClickAction::UnshareTransfer(id) => {
                     // Select the entry so unshare_file() operates on it.
                     if let Some(idx) = app
                         .transfers
                         .entries
                         .iter()
-                        .position(|e| e.offer.hash == *hash && matches!(e.state, TransferState::Sharing))
+                        .position(|e| e.offer.entry_id() == *id && matches!(e.state, TransferState::Sharing))
                     {
                         app.transfers.selected_index = idx;
                         return true;
@@ -1014,242 +2566,809 @@ fn handle_mouse_click(
     false
 }
 
+// ── Export ───────────────────────────────────────────────────────────────────
+
+/// Handle `/export [--format text|html|json] [file]`: write the room's history
+/// to `file`, or to a dated file in the download directory.
+fn export_transcript(app: &mut App, args: &str, download_dir: &std::path::Path) {
+    let lang = app.config.lang;
+    let args = match export::parse_args(args) {
+        Ok(args) => args,
+        Err(e) => {
+            app.system(e.notice("/export", lang));
+            return;
+        }
+    };
+    if app.history.is_empty() {
+        app.system(t!(lang, "export.empty"));
+        return;
+    }
+    let now = now_ms();
+    let path = args
+        .path
+        .unwrap_or_else(|| download_dir.join(export::default_file_name(args.format, now)));
+    let contents = export::render(&app.history, args.format, &app.theme, now);
+    match std::fs::write(&path, contents) {
+        Ok(()) => app.system(t!(lang, "export.done", count = app.history.len(), path = path.display())),
+        Err(e) => app.system(t!(lang, "export.failed", path = path.display(), error = e)),
+    }
+}
+
 // ── Clipboard helpers ────────────────────────────────────────────────────────
 
-/// Copy the room ticket to the terminal clipboard using the OSC 52 escape
-/// sequence. This is supported by most modern terminals (kitty, iTerm2,
-/// alacritty, wezterm, Windows Terminal, etc.). Shows brief "Copied!" feedback.
+/// Copy the room ticket to the terminal clipboard (OSC 52, see
+/// `clipboard.rs`). Shows brief "Copied!" feedback.
 fn copy_ticket_to_clipboard(app: &mut App) {
-    use base64::Engine;
     if let Some(ref ticket) = app.ticket_str {
-        let b64 = base64::engine::general_purpose::STANDARD.encode(ticket.as_bytes());
-        // OSC 52: set clipboard. `c` = system clipboard.
-        let osc = format!("\x1b]52;c;{b64}\x07");
-        let _ = std::io::Write::write_all(&mut std::io::stdout(), osc.as_bytes());
-        let _ = std::io::Write::flush(&mut std::io::stdout());
+        clipboard::copy(ticket);
         app.copy_feedback_until = Some(std::time::Instant::now() + std::time::Duration::from_secs(2));
     }
 }
 
-// ── File sharing helpers ─────────────────────────────────────────────────────
+// ── Delivery helpers ─────────────────────────────────────────────────────────
 
-/// Import a file into the blob store and broadcast a `FileOffer` over gossip.
-///
-/// Returns `(hash, filename, size)` on success.
+/// The next gossip event, or a message that arrived over a direct stream.
 ///
-/// This function demonstrates several Rust patterns:
-/// - `&FsStore` / `&GossipSender`: borrowed references (we don't need ownership)
-/// - `&str` for `nickname`: a borrowed string slice (cheaper than `&String`)
-/// - `&std::path::Path` for `path`: a borrowed path slice (accepts both `&Path` and `&PathBuf`)
-/// - Returns `(hash, filename, size, message_id, timestamp, mime_type)` on success
-async fn share_file(
-    store: &FsStore,
-    sender: &iroh_gossip::api::GossipSender,
-    nickname: &str,
-    endpoint_id: iroh::EndpointId,
-    path: &std::path::Path,
-    target: Option<String>,
-) -> Result<(Hash, String, u64, net::MessageId, u64, Option<String>)> {
-    let filename = path
-        .file_name()
-        .map(|n| n.to_string_lossy().to_string())
-        .unwrap_or_else(|| "unnamed".to_string());
+/// Direct deliveries are dressed up as `GossipEvent::Received` so the event
+/// loop handles both paths with the same code — including the signature
+/// check in `decode()` and the message-ID dedup.
+async fn next_event(
+    receiver: &mut GossipReceiver,
+    direct_rx: &mut tokio::sync::mpsc::Receiver<DirectMessage>,
+) -> Result<Option<GossipEvent>> {
+    tokio::select! {
+        event = receiver.try_next() => Ok(event?),
+        Some((from, bytes)) = direct_rx.recv() => Ok(Some(GossipEvent::Received(GossipMessage {
+            content: bytes.into(),
+            scope: DeliveryScope::Neighbors,
+            delivered_from: from,
+        }))),
+    }
+}
 
-    let size = tokio::fs::metadata(path).await?.len();
+/// Deliver encoded message `bytes` to `peer` over a direct stream in the
+/// background. Best effort: the peer may have left, or run a build without
+/// the direct protocol, and either way there is nothing more to do.
+fn send_direct(endpoint: &iroh::Endpoint, peer: iroh::EndpointId, bytes: Vec<u8>) {
+    let endpoint = endpoint.clone();
+    tokio::spawn(async move {
+        let _ = net::direct::send(&endpoint, peer, &bytes).await;
+    });
+}
 
-    let tag_info = store.blobs().add_path(path).await?;
-    let hash = tag_info.hash;
+/// Broadcast `msg` without letting a failure end the session: it is
+/// reported in the chat and the signed bytes queued in `retries` for the
+/// tick branch to send again. Returns the bytes, or `None` if `msg` could
+/// not even be encoded.
+async fn broadcast(
+    app: &mut App,
+    sender: &MessageSender,
+    retries: &mut RetryQueue,
+    msg: &Message,
+) -> Option<Vec<u8>> {
+    let bytes = match sender.encode(msg) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            app.system(t!(app.config.lang, "send.failed", error = e));
+            return None;
+        }
+    };
+    app.keepalive.traffic(std::time::Instant::now());
+    if let Err(e) = sender.broadcast_encoded(bytes.clone()).await {
+        // Only the first failure of an outage is reported; otherwise every
+        // ack sent while the network is down would add a line.
+        if retries.is_empty() {
+            app.system(t!(app.config.lang, "send.retrying", error = e));
+        }
+        retries.push(bytes.clone(), std::time::Instant::now());
+    }
+    Some(bytes)
+}
 
+/// Send `text` as our chat message — into the open thread, or as the
+/// pending quote-reply, if there is one — and add it to our own log, as
+/// the outbound filters left it. A dropped message keeps the quote pending.
+async fn post_chat(
+    app: &mut App,
+    sender: &MessageSender,
+    retries: &mut RetryQueue,
+    outbox: &mut Outbox,
+    our_id: iroh::EndpointId,
+    nickname: &str,
+    text: String,
+) {
     let mid = new_message_id();
     let ts = now_ms();
-    let mime_type = transfer::mime_from_extension(&filename);
-
-    let msg = Message::FileOffer {
-        nickname: nickname.to_string(),
-        endpoint_id,
-        filename: filename.clone(),
-        size,
-        hash: *hash.as_bytes(),
-        message_id: mid,
-        timestamp_ms: ts,
-        mime_type: mime_type.clone(),
-        target,
+    let nickname = nickname.to_string();
+    let quote = app.replying_to.take();
+    let reply_to = quote.as_ref().map(|quote| quote.message_id);
+    let msg = match (app.open_thread, reply_to) {
+        (Some(thread_id), _) => Message::ThreadChat { nickname: nickname.clone(), text, message_id: mid, timestamp_ms: ts, thread_id },
+        (None, Some(reply_to)) => Message::Reply { nickname: nickname.clone(), text, message_id: mid, timestamp_ms: ts, reply_to },
+        (None, None) => Message::Chat { nickname: nickname.clone(), text, message_id: mid, timestamp_ms: ts },
     };
-    let encoded = postcard::to_stdvec(&msg)?;
-    sender.broadcast(encoded.into()).await?;
-
-    Ok((hash, filename, size, mid, ts, mime_type))
+    let Some(text) = broadcast_tracked(app, sender, retries, outbox, our_id, msg).await else {
+        app.replying_to = quote;
+        return;
+    };
+    match (app.open_thread, reply_to) {
+        (Some(thread_id), _) => app.thread_chat(nickname, text, mid, ts, thread_id),
+        (None, Some(reply_to)) => app.reply(nickname, text, mid, ts, reply_to),
+        (None, None) => app.chat(nickname, text, mid, ts),
+    }
 }
 
-/// Unshare the currently selected file in the file pane.
+/// Broadcast a chat-style message and track it in `outbox`, so peers that
+/// don't ack it in time get it again over a direct stream.
 ///
-/// Broadcasts a `FileRetract` message, removes the entry from the transfer
-/// manager, and records the retraction in history.
-async fn unshare_file(
+/// Every chat message we send passes through here — typed, pasted, a
+/// webhook's, an away reply — so this is where the outbound filters run:
+/// they may rewrite the text, or drop the message with a notice. Returns
+/// the text as sent, for our own log; `None` if it was dropped (or `msg`
+/// isn't chat-style).
+async fn broadcast_tracked(
     app: &mut App,
-    sender: &iroh_gossip::api::GossipSender,
+    sender: &MessageSender,
+    retries: &mut RetryQueue,
+    outbox: &mut Outbox,
+    our_id: iroh::EndpointId,
+    mut msg: Message,
+) -> Option<String> {
+    let (text, message_id) = msg.chat_mut()?;
+    match app.filters.outbound(std::mem::take(text)) {
+        Ok(filtered) => *text = filtered,
+        Err(filter) => {
+            app.system(t!(app.config.lang, "filter.blocked", filter = filter));
+            return None;
+        }
+    }
+    let sent = text.clone();
+    if let Some(bytes) = broadcast(app, sender, retries, &msg).await {
+        let peers = app.peers.keys().copied().filter(|id| *id != our_id);
+        outbox.track(message_id, bytes, peers, std::time::Instant::now());
+    }
+    Some(sent)
+}
+
+// ── Watch mode ───────────────────────────────────────────────────────────────
+
+/// Work out the paths and proxy from the flags and the config file. A
+/// proxy only carries relay traffic (direct paths are UDP), so it implies
+/// relay-only paths and can't be combined with direct-only ones. A SOCKS5
+/// proxy's bridge starts here and serves the rest of the run.
+async fn transport(paths: Option<Paths>, proxy: Option<String>) -> Result<Transport> {
+    let config = Config::load();
+    let paths = paths.unwrap_or(config.paths);
+    let Some(proxy) = proxy.or(config.proxy).filter(|p| !p.trim().is_empty()) else {
+        return Ok(Transport { paths, proxy: None });
+    };
+    if paths == Paths::Direct {
+        anyhow::bail!("a proxy only carries relay traffic; it can't be used with direct-only paths");
+    }
+    let proxy = Proxy::parse(&proxy).map_err(anyhow::Error::msg)?;
+    Ok(Transport { paths: Paths::Relay, proxy: Some(proxy.start().await?) })
+}
+
+/// Endpoint settings for the scripting subcommands: a throwaway identity
+/// and a temporary blob store, so they never clash with a chat session
+/// running on the same machine. The store's directory goes when the
+/// returned `TempStore` drops, so keep it until the node has shut down.
+/// With `mem_store` there is no directory: the store is in memory.
+fn headless(transport: &Transport, mem_store: bool) -> Result<(iroh::endpoint::Builder, Option<TempStore>)> {
+    let store = if mem_store { None } else { Some(TempStore::create(&std::env::temp_dir())?) };
+    let secret_key = iroh::SecretKey::from_bytes(&rand::random());
+    Ok((transport.apply(iroh::Endpoint::builder().secret_key(secret_key)), store))
+}
+
+/// Where a `headless()` node keeps blobs: its temporary store, if it has one.
+fn headless_storage(store: &Option<TempStore>) -> Storage<'_> {
+    store.as_ref().map_or(Storage::Memory, |s| Storage::Disk(s.path()))
+}
+
+/// Spawn a node with `headless()` settings.
+async fn spawn_headless(transport: &Transport, mem_store: bool) -> Result<(Node, Option<TempStore>)> {
+    let (builder, store) = headless(transport, mem_store)?;
+    Ok((Node::spawn(builder, headless_storage(&store)).await?, store))
+}
+
+/// Run `piper-chat tail` until Ctrl+C or the room goes away.
+async fn tail(ticket: &ChatTicket, json: bool, transport: &Transport, mem_store: bool) -> Result<()> {
+    let (node, _store) = spawn_headless(transport, mem_store).await?;
+    let result = tokio::select! {
+        result = piper_chat::tail::run(&node, ticket, json) => result,
+        _ = tokio::signal::ctrl_c() => Ok(()),
+    };
+    node.shutdown().await?;
+    result
+}
+
+/// Run `piper-chat share` until enough downloads finish, time runs out,
+/// or Ctrl+C. Running out of time is an error, so scripts can tell.
+async fn share(
+    ticket: &ChatTicket,
     nickname: &str,
+    path: &std::path::Path,
+    downloads: usize,
+    timeout: Duration,
+    transport: &Transport,
+    mem_store: bool,
 ) -> Result<()> {
-    if let Some(entry) = app.transfers.selected_entry()
-        && matches!(entry.state, TransferState::Sharing)
+    let (builder, store) = headless(transport, mem_store)?;
+    let (node, events) = Node::spawn_with_events(builder, headless_storage(&store)).await?;
+    let outcome = tokio::select! {
+        result = piper_chat::share::run(&node, events, ticket, nickname, path, downloads, timeout) => Some(result),
+        _ = tokio::signal::ctrl_c() => None,
+    };
+    node.shutdown().await?;
+    match outcome {
+        Some(result) => {
+            let done = result?;
+            if done < downloads {
+                anyhow::bail!("only {done} of {downloads} downloads completed");
+            }
+            Ok(())
+        }
+        None => Ok(()),
+    }
+}
+
+/// Run `piper-chat fetch`, showing bytes received on stderr.
+async fn fetch(
+    provider: iroh::EndpointId,
+    hash: Hash,
+    out: &std::path::Path,
+    room: Option<TopicId>,
+    transport: &Transport,
+    mem_store: bool,
+) -> Result<()> {
+    let (node, _store) = spawn_headless(transport, mem_store).await?;
+    if let Some(room) = &room
+        && !piper_chat::access::present(&node.endpoint, provider, room).await.unwrap_or(true)
     {
-        let hash = entry.offer.hash;
-        let hash_bytes = *hash.as_bytes();
-        let mid = new_message_id();
-        let ts = now_ms();
-        let msg = Message::FileRetract {
-            nickname: nickname.to_string(),
-            hash: hash_bytes,
-            message_id: mid,
-            timestamp_ms: ts,
+        eprintln!("the provider refused our room token; it may be in another room");
+    }
+    let result = node::fetch_blob(&node.blob_store, &node.endpoint, provider, hash, out, |progress| {
+        eprint!("\rreceived {}   ", transfer::format_file_size(progress.received));
+        std::future::ready(())
+    })
+    .await;
+    node.shutdown().await?;
+    result?;
+    eprintln!("\rsaved {}          ", out.display());
+    Ok(())
+}
+
+/// Run `piper-chat send`, reporting on stderr if only some messages were
+/// acknowledged.
+async fn send(
+    ticket: &ChatTicket,
+    nickname: &str,
+    messages: &[String],
+    timeout: Duration,
+    transport: &Transport,
+    mem_store: bool,
+) -> Result<()> {
+    let (node, _store) = spawn_headless(transport, mem_store).await?;
+    let result = piper_chat::send::run(&node, ticket, nickname, messages, timeout).await;
+    node.shutdown().await?;
+    let acked = result?;
+    if acked < messages.len() {
+        eprintln!("warning: {} of {} messages not acknowledged", messages.len() - acked, messages.len());
+    }
+    Ok(())
+}
+
+// ── Room archive helpers ─────────────────────────────────────────────────────
+
+/// Where a `/dm <name>` or `/join <ticket>` command wants to go: the room's
+/// ticket, plus the peer's name for a DM. `None` if `text` is neither
+/// command, `Some(Err(notice))` if its argument is no good.
+fn room_switch(app: &App, our_id: iroh::EndpointId, text: &str) -> Option<Result<(ChatTicket, Option<String>), String>> {
+    let lang = app.config.lang;
+    if let Some(args) = command::parse(text, "/dm") {
+        let args = match args {
+            Ok(args) => args,
+            Err(e) => return Some(Err(e.notice("/dm", lang))),
         };
-        let encoded = postcard::to_stdvec(&msg)?;
-        sender.broadcast(encoded.into()).await?;
-        if let Some(filename) = app.transfers.retract(&hash) {
-            app.seen_ids.insert(mid);
-            app.push_history(net::HistoryEntry {
-                message_id: mid,
-                timestamp_ms: ts,
-                kind: net::HistoryEntryKind::FileRetract { hash: hash_bytes },
-            });
-            app.system(format!("You unshared: {filename}"));
+        let name = args.word(0);
+        return Some(match app.dm_target(name) {
+            None => Err(t!(lang, "dm.unknown", name = name)),
+            Some(peer) => Ok((ChatTicket::dm(our_id, peer), Some(name.to_string()))),
+        });
+    }
+    if let Some(args) = command::parse(text, "/join") {
+        let args = match args {
+            Ok(args) => args,
+            Err(e) => return Some(Err(e.notice("/join", lang))),
+        };
+        return Some(match ChatTicket::parse(args.word(0)) {
+            Ok(mut next) => {
+                // Archived tickets list us among the bootstrap peers.
+                next.bootstrap.remove(&our_id);
+                Ok((next, None))
+            }
+            Err(e) => Err(t!(lang, "join.failed", error = e)),
+        });
+    }
+    None
+}
+
+/// Save the current room's history into the archive, with a ticket that
+/// bootstraps from everyone we saw in it. Rooms with no history are skipped.
+async fn archive_room(
+    archive: &mut Rooms,
+    app: &mut App,
+    ticket: &ChatTicket,
+    nickname: &str,
+    last_read_ms: u64,
+) -> Result<()> {
+    // Stop journaling first: whatever happens below, every entry of this
+    // visit is then on disk in either the journal or the archive.
+    if let Some(journal) = app.journal.take() {
+        journal.shutdown().await?;
+    }
+    let Some(dir) = Rooms::dir() else { return Ok(()) };
+    if app.history.is_empty() {
+        return Ok(());
+    }
+    let mut rejoin = ticket.clone();
+    rejoin.bootstrap.extend(app.peers.keys().copied());
+    let rejoin = <ChatTicket as Ticket>::serialize(&rejoin);
+    let passphrase = rooms::passphrase();
+    // An archive we couldn't decrypt (no or wrong passphrase) is left alone
+    // rather than overwritten by this session's shorter history.
+    if rooms::load_history(&dir, &ticket.topic_id, passphrase.as_deref()).is_err() {
+        return Ok(());
+    }
+    rooms::save_history(&dir, &ticket.topic_id, &app.history, passphrase.as_deref())?;
+    journal::clear(&rooms::journal_dir(&dir, &ticket.topic_id))?;
+    archive.record(&ticket.topic_id, &rejoin, &app.history, nickname, last_read_ms);
+    Ok(archive.save_to(&dir)?)
+}
+
+/// Load the archived history of `ticket`'s room, if we've been there before,
+/// plus anything journaled by a visit that ended without archiving (a
+/// crash). Lines go above everything else, as if a peer had synced them.
+///
+/// Then start journaling this visit — unless archives are encrypted, since
+/// the journal is plain postcard.
+fn restore_room(app: &mut App, ticket: &ChatTicket, nickname: &str) {
+    let Some(dir) = Rooms::dir() else { return };
+    let passphrase = rooms::passphrase();
+    let mut archived = match rooms::load_history(&dir, &ticket.topic_id, passphrase.as_deref()) {
+        Ok(archived) => archived,
+        Err(e) => {
+            app.system(t!(app.config.lang, "history.restore_failed", error = e));
+            return;
         }
+    };
+    let journal_dir = rooms::journal_dir(&dir, &ticket.topic_id);
+    let recovered = journal::replay(&journal_dir);
+    let crashed = !recovered.is_empty();
+    archived.extend(recovered);
+    if !archived.is_empty() {
+        let (mut lines, restored) = app.absorb_history(archived, nickname);
+        lines.append(&mut app.messages);
+        app.messages = lines;
+        app.system(t!(app.config.lang, "history.restored", count = restored));
+        if crashed {
+            app.system(t!(app.config.lang, "history.crashed"));
+        }
+    }
+    if passphrase.is_none() {
+        app.journal = Some(HistoryWriter::spawn(journal_dir, app.config.journal_sync));
     }
+}
+
+/// Merge a JSON transcript into the archived history of `ticket`'s room
+/// (`piper-chat import`). The room gets an archive record, so it shows up
+/// under `rooms` and the welcome screen's Rejoin mode.
+fn import(archive: &mut Rooms, file: &std::path::Path, ticket: &str) -> Result<()> {
+    let room = ChatTicket::parse(ticket)?;
+    let json = std::fs::read_to_string(file).with_context(|| format!("reading {}", file.display()))?;
+    let entries = export::parse_transcript(&json)?;
+    let total = entries.len();
+    let dir = Rooms::dir().context("no data directory on this platform")?;
+    let passphrase = rooms::passphrase();
+    // Fails on an archive we can't decrypt, rather than replacing it.
+    let mut history = rooms::load_history(&dir, &room.topic_id, passphrase.as_deref())?;
+    let added = rooms::merge_history(&mut history, entries);
+    rooms::save_history(&dir, &room.topic_id, &history, passphrase.as_deref())?;
+    // Everything imported counts as read.
+    let last_read_ms = history.iter().map(|e| e.timestamp_ms).max().unwrap_or(0);
+    archive.record(&room.topic_id, ticket.trim(), &history, "", last_read_ms);
+    archive.save_to(&dir)?;
+    println!("imported {added} entries ({} already there)", total - added);
+    println!("  piper-chat join --name <you> {}", ticket.trim());
     Ok(())
 }
 
-/// Spawn a background task that downloads a blob from a remote peer and exports
-/// it to the download directory. Progress/completion/failure is reported via
-/// the `tx` channel.
+/// Print the archive for `piper-chat rooms`.
+fn print_rooms(archive: &Rooms) {
+    if archive.is_empty() {
+        println!("no archived rooms yet");
+        return;
+    }
+    let now = now_ms();
+    for room in archive.by_recency() {
+        println!("{}  ({})", room.label, room.describe(now));
+        println!("  piper-chat join --name <you> {}", room.ticket);
+    }
+}
+
+// ── File sharing helpers ─────────────────────────────────────────────────────
+
+/// How to offer a file to `target` (or everyone): sealed for the current
+/// room when `seal_files` is on.
+fn offer_options(app: &App, target: Option<String>) -> OfferOptions {
+    let seal = app.members.room().filter(|_| app.config.seal_files).map(Sealing::new);
+    let reference_min = (app.config.reference_min_mb > 0).then(|| app.config.reference_min_mb * 1024 * 1024);
+    OfferOptions { target, seal, reference_min }
+}
+
+/// Add a just-shared file to the file pane, or report why sharing failed.
 ///
-/// `tokio::spawn()` launches a new asynchronous task — like a lightweight green
-/// thread. The task runs concurrently with the main event loop. We use this for
-/// downloads because they're long-running and shouldn't block the UI.
+/// `result` is what `node::share_file` / `node::share_bytes` returned;
+/// `target` is the DM recipient, if the offer was addressed to one.
+/// `artifact` when the blob was made for the share (see `scratch`).
+fn record_share(
+    app: &mut App,
+    our_id: iroh::EndpointId,
+    result: piper_chat::error::Result<Shared>,
+    target: Option<&str>,
+    artifact: bool,
+) {
+    match result {
+        Ok(Shared { hash, filename, size, message_id: offer_id, reused, .. }) => {
+            if artifact {
+                track_artifact(app, hash, &filename);
+            }
+            app.transfers.add_sent(FileOffer {
+                sender_nickname: "You".to_string(),
+                sender_id: our_id,
+                filename: filename.clone(),
+                size,
+                hash,
+                key: None,
+                offer_id,
+            });
+            app.system(match target {
+                Some(target) => t!(app.config.lang, "file.sharing_to", target = target, filename = filename),
+                None => t!(app.config.lang, "file.sharing", filename = filename),
+            });
+            if reused {
+                app.system(t!(app.config.lang, "file.reused"));
+            }
+        }
+        Err(e) => app.system(t!(app.config.lang, "file.share_failed", error = e)),
+    }
+}
+
+/// Unshare the currently selected file in the file pane.
+async fn unshare_file(
+    app: &mut App,
+    sender: &MessageSender,
+    retries: &mut RetryQueue,
+    blob_store: &Blobs,
+) {
+    if let Some(entry) = app.transfers.selected_entry()
+        && matches!(entry.state, TransferState::Sharing)
+    {
+        let id = entry.offer.entry_id();
+        revoke_share(app, sender, retries, blob_store, id, now_ms()).await;
+    }
+}
+
+/// Revoke our offer `id` at `at_ms`: now, or later as an expiry.
 ///
-/// The function takes owned/cloned values (not references) because `tokio::spawn`
-/// requires the future to be `'static` — it can't borrow from the caller's stack
-/// since it runs independently. We clone `store` and `endpoint` (both are cheap
-/// Arc-based clones) to satisfy this requirement.
-fn spawn_download(
-    store: &FsStore,
-    endpoint: &iroh::Endpoint,
-    offer: FileOffer,
-    download_dir: PathBuf,
-    tx: tokio::sync::mpsc::Sender<TransferEvent>,
+/// Broadcasts a `FileRevoke` (a failed broadcast is queued in `retries`
+/// like any other) and applies it to our own files pane. An immediate
+/// revocation also drops the blob; an expiry does that when it fires.
+async fn revoke_share(
+    app: &mut App,
+    sender: &MessageSender,
+    retries: &mut RetryQueue,
+    blob_store: &Blobs,
+    id: EntryId,
+    at_ms: u64,
 ) {
-    // Clone `store` and `endpoint` so the spawned future owns its data.
-    // These types use `Arc` internally, so cloning is O(1) — it just
-    // increments a reference count, not deep-copying the data.
-    let store = store.clone();
-    let endpoint = endpoint.clone();
+    let (hash, offer_id) = (id.hash, id.offer_id);
+    let message_id = new_message_id();
+    let timestamp_ms = now_ms();
+    let msg = Message::FileRevoke { hash: *hash.as_bytes(), endpoint_id: id.sender, message_id, timestamp_ms, at_ms, offer_id };
+    broadcast(app, sender, retries, &msg).await;
+    app.seen_ids.insert(message_id);
+    let expiry = Expiry { at_ms, message_id };
+    if let Some(filename) = app.transfers.revoke(&id, expiry, timestamp_ms) {
+        record_revoke(app, &id, message_id, timestamp_ms);
+        forget_share(app, blob_store, hash, &filename).await;
+    } else if let Some(entry) = app.transfers.entries.iter().find(|e| e.expiry == Some(expiry)) {
+        let left = chat::format_remaining(at_ms.saturating_sub(timestamp_ms));
+        let filename = entry.offer.filename.clone();
+        app.system(t!(app.config.lang, "file.you_expire", filename = filename, left = left));
+    }
+}
+
+/// Answer the oldest knock on our room: a yes signs an approval and
+/// broadcasts the whole roster; a no (or a room filled up meanwhile) tells
+/// the room the peer was turned away.
+async fn answer_knock(app: &mut App, sender: &MessageSender, retries: &mut RetryQueue, key: &iroh::SecretKey, yes: bool) {
+    let Some(admission) = app.admission.as_mut() else { return };
+    let our_id = key.public();
+    if yes && !admission.full() {
+        let Some((_, name)) = admission.approve(key) else { return };
+        let msg = Message::JoinApproval { approvals: admission.approvals().to_vec(), endpoint_id: our_id };
+        app.system(t!(app.config.lang, "knock.let_in", name = name));
+        broadcast(app, sender, retries, &msg).await;
+    } else {
+        let full = yes;
+        let Some((id, name)) = admission.deny() else { return };
+        app.system(if full {
+            t!(app.config.lang, "knock.full", name = name)
+        } else {
+            t!(app.config.lang, "knock.denied", name = name)
+        });
+        broadcast(app, sender, retries, &Message::JoinDenial { denied: id, endpoint_id: our_id }).await;
+    }
+}
+
+/// `/owner <name>`: make a peer a co-owner of our room and broadcast every
+/// grant so far. With no name, list the owners.
+async fn grant_owner(app: &mut App, sender: &MessageSender, retries: &mut RetryQueue, key: &iroh::SecretKey, name: &str) {
+    let lang = app.config.lang;
+    let Some(admission) = app.admission.as_ref() else {
+        app.system(t!(lang, "owner.no_policy"));
+        return;
+    };
+    if name.is_empty() {
+        let names: Vec<String> = admission
+            .owners()
+            .iter()
+            .map(|id| app.peers.get(id).map(|p| p.name.clone()).unwrap_or_else(|| id.fmt_short().to_string()))
+            .collect();
+        app.system(t!(lang, "owner.list", names = names.join(", ")));
+        return;
+    }
+    if !admission.is_owner() {
+        app.system(t!(lang, "owner.not_owner"));
+        return;
+    }
+    let Some(id) = app.dm_target(name) else {
+        app.system(t!(lang, "dm.unknown", name = name));
+        return;
+    };
+    let Some(admission) = app.admission.as_mut() else { return };
+    if !admission.grant(key, id) {
+        app.system(t!(lang, "owner.already", name = name));
+        return;
+    }
+    let msg = Message::GrantOwner { grants: admission.grants().to_vec(), endpoint_id: key.public() };
+    app.system(t!(lang, "owner.granted", name = name));
+    broadcast(app, sender, retries, &msg).await;
+}
+
+/// Note that the blob `hash` was made to share `filename`, so it's deleted
+/// on quit (or at the next start, after a crash).
+fn track_artifact(app: &mut App, hash: Hash, filename: &str) {
+    app.artifacts.track(hash, filename);
+    if let Err(e) = app.artifacts.save() {
+        app.system(t!(app.config.lang, "artifacts.save_failed", error = e));
+    }
+}
+
+/// Withdraw our offers of `hash`, a file shared by reference that changed
+/// on disk (`why`): each is revoked like `/unshare` would, but stays in the
+/// files pane as invalidated, so it's clear what happened to it.
+async fn invalidate_share(
+    app: &mut App,
+    sender: &MessageSender,
+    retries: &mut RetryQueue,
+    blob_store: &Blobs,
+    hash: Hash,
+    why: Invalid,
+) {
+    let ours: Vec<EntryId> = app
+        .transfers
+        .entries
+        .iter()
+        .filter(|e| e.offer.hash == hash && matches!(e.state, TransferState::Sharing))
+        .map(|e| e.offer.entry_id())
+        .collect();
+    for id in ours {
+        let message_id = new_message_id();
+        let timestamp_ms = now_ms();
+        let msg = Message::FileRevoke {
+            hash: *hash.as_bytes(),
+            endpoint_id: id.sender,
+            message_id,
+            timestamp_ms,
+            at_ms: timestamp_ms,
+            offer_id: id.offer_id,
+        };
+        broadcast(app, sender, retries, &msg).await;
+        app.seen_ids.insert(message_id);
+        if let Some(filename) = app.transfers.invalidate(&id, why.to_string()) {
+            record_revoke(app, &id, message_id, timestamp_ms);
+            let reason = match why {
+                Invalid::Missing => t!(app.config.lang, "source.missing"),
+                Invalid::Resized => t!(app.config.lang, "source.resized"),
+                Invalid::Modified => t!(app.config.lang, "source.modified"),
+            };
+            app.system(t!(app.config.lang, "file.invalidated", filename = filename, reason = reason));
+        }
+    }
+    blob_store.sources().forget(&hash);
+    if let Err(e) = node::delete_tags(blob_store, hash).await {
+        app.system(t!(app.config.lang, "file.cleanup_failed", filename = hash.fmt_short(), error = e));
+    }
+}
+
+/// Drop our own revoked share: delete its blob tags so garbage collection
+/// can reclaim the data, and say it's gone.
+async fn forget_share(app: &mut App, blob_store: &Blobs, hash: Hash, filename: &str) {
+    blob_store.sources().forget(&hash);
+    if let Err(e) = node::delete_tags(blob_store, hash).await {
+        app.system(t!(app.config.lang, "file.cleanup_failed", filename = filename, error = e));
+    }
+    if app.artifacts.untrack(&hash)
+        && let Err(e) = app.artifacts.save()
+    {
+        app.system(t!(app.config.lang, "artifacts.save_failed", error = e));
+    }
+    app.system(t!(app.config.lang, "file.you_unshared", filename = filename));
+}
 
-    // `tokio::spawn` takes a future and returns a `JoinHandle`. We don't
-    // store the handle — this is a "fire-and-forget" pattern. The task will
-    // run until completion (or until the runtime shuts down).
-    // The `async move` block takes ownership of all captured variables
-    // (`store`, `endpoint`, `offer`, etc.) via the `move` keyword.
+/// Record in history that the offer `id` was withdrawn, dropping that offer
+/// (and only that one: other peers may offer the same file) so a replay
+/// doesn't bring it back.
+fn record_revoke(app: &mut App, id: &EntryId, message_id: net::MessageId, timestamp_ms: u64) {
+    let hash = *id.hash.as_bytes();
+    app.history.retain(|e| {
+        !matches!(&e.kind, net::HistoryEntryKind::FileOffer { hash: h, endpoint_id, .. }
+            if *h == hash && *endpoint_id == id.sender && e.message_id == id.offer_id)
+    });
+    app.push_history(net::HistoryEntry {
+        message_id,
+        timestamp_ms,
+        kind: net::HistoryEntryKind::FileRevoke { hash, endpoint_id: id.sender, offer_id: id.offer_id },
+    });
+}
+
+/// The entry of our own shared file on row `n` (counting from 1) of the
+/// files pane, from a `/unshare` or `/expire` argument.
+fn shared_row(app: &App, n: &str) -> Option<EntryId> {
+    let n: usize = n.parse().ok()?;
+    let entry = app.transfers.entries.get(n.checked_sub(1)?)?;
+    matches!(entry.state, TransferState::Sharing).then(|| entry.offer.entry_id())
+}
+
+/// How a retry attempt is described, in the timeline and in the message
+/// when it succeeds.
+fn strategy_label(app: &App, strategy: Strategy) -> String {
+    let lang = app.config.lang;
+    match strategy {
+        Strategy::Default => t!(lang, "strategy.default"),
+        Strategy::RelayOnly => t!(lang, "strategy.relay"),
+        Strategy::Peer(id) => {
+            let name = app.peers.get(&id).map_or_else(|| id.fmt_short().to_string(), |p| p.name.clone());
+            t!(lang, "strategy.peer", name = name)
+        }
+    }
+}
+
+/// The finished download on row `n` of the files pane, from a
+/// `/verifyfile` argument: the hash its file should have (the opened
+/// content's, for a sealed offer), file name and path on disk.
+fn downloaded_row(app: &App, n: &str) -> Option<(Hash, String, PathBuf)> {
+    let n: usize = n.parse().ok()?;
+    let entry = app.transfers.entries.get(n.checked_sub(1)?)?;
+    match &entry.state {
+        TransferState::Complete(path) => Some((entry.content_hash(), entry.offer.filename.clone(), path.clone())),
+        _ => None,
+    }
+}
+
+/// Act on what the session lock said: run its Argon2 work in the background
+/// (see `spawn_lock_work`), save a chosen passphrase, or unlock.
+fn lock_outcome(app: &mut App, outcome: lock::Outcome, tx: &tokio::sync::mpsc::Sender<lock::Done>, last_input_ms: &mut u64) {
+    match outcome {
+        lock::Outcome::Typing => {}
+        lock::Outcome::Cancelled => app.lock = None,
+        lock::Outcome::Work(work) => spawn_lock_work(work, tx.clone()),
+        lock::Outcome::Chosen(hash) => {
+            app.config.lock_passphrase = Some(hash);
+            if let Err(e) = app.config.save() {
+                app.system(t!(app.config.lang, "lock.save_failed", error = e));
+            }
+        }
+        lock::Outcome::Unlocked => {
+            app.lock = None;
+            *last_input_ms = now_ms();
+        }
+    }
+}
+
+/// Run the lock's Argon2 `work` on a blocking thread, so the UI and gossip
+/// keep going meanwhile, and hand the result back to the event loop. A
+/// task that died counts as a failed check rather than leaving the prompt
+/// waiting.
+fn spawn_lock_work(work: lock::Work, tx: tokio::sync::mpsc::Sender<lock::Done>) {
     tokio::spawn(async move {
-        let hash = offer.hash;
-        let filename = offer.filename.clone();
-        let target = download_dir.join(&filename);
-
-        // Connect to the sender's endpoint for the blobs protocol.
-        // `endpoint.connect()` establishes a QUIC connection to the given
-        // peer, using BLOBS_ALPN to indicate we want to speak the blobs protocol.
-        let conn = match endpoint.connect(offer.sender_id, BLOBS_ALPN).await {
-            Ok(conn) => conn,
-            Err(e) => {
-                // `let _ = tx.send(...)` discards the send result. The channel
-                // might be closed if the main loop has already exited — that's
-                // fine, we just silently drop the error notification.
-                let _ = tx
-                    .send(TransferEvent::Failed {
-                        hash,
-                        filename,
-                        error: format!("connect: {e}"),
-                    })
-                    .await;
-                return;
+        let done = tokio::task::spawn_blocking(move || lock::run(work))
+            .await
+            .unwrap_or(lock::Done::Verified(false));
+        let _ = tx.send(done).await;
+    });
+}
+
+/// Re-hash the download at `path` on a blocking thread and report whether
+/// it still matches `hash` as a `TransferEvent::Verified`.
+fn spawn_verify(hash: Hash, filename: String, path: PathBuf, tx: tokio::sync::mpsc::Sender<TransferEvent>) {
+    tokio::spawn(async move {
+        let result = tokio::task::spawn_blocking(move || checksum::blake3_file(&path))
+            .await
+            .map_err(|e| e.to_string())
+            .and_then(|r| r.map_err(|e| e.to_string()))
+            .map(|on_disk| on_disk == hash);
+        let _ = tx.send(TransferEvent::Verified { filename, result }).await;
+    });
+}
+
+/// Write the `.sha256` sidecar for a finished download in the background.
+/// Only a failure is reported.
+fn spawn_sidecar(filename: String, path: PathBuf, tx: tokio::sync::mpsc::Sender<TransferEvent>) {
+    tokio::spawn(async move {
+        let result = tokio::task::spawn_blocking(move || checksum::write_sidecar(&path)).await;
+        let error = match result {
+            Ok(Ok(())) => return,
+            Ok(Err(e)) => e.to_string(),
+            Err(e) => e.to_string(),
+        };
+        let _ = tx.send(TransferEvent::SidecarFailed { filename, error }).await;
+    });
+}
+
+/// Write a previewed file to the download dir, finishing its offer as if it
+/// had been downloaded: reported as `Complete` (or `Failed`) like any other.
+/// The bytes of a `sealed` offer were opened for the preview, so they're
+/// hashed for `/verifyfile` as a sealed download's are.
+fn save_preview(preview: transfer::Preview, sealed: bool, download_dir: &std::path::Path, tx: tokio::sync::mpsc::Sender<TransferEvent>) {
+    let path = transfer::download_path(download_dir, &preview.filename);
+    tokio::spawn(async move {
+        let transfer::Preview { id, filename, bytes, .. } = preview;
+        let Some(path) = path else {
+            let error = format!("bad file name: {filename}");
+            let _ = tx.send(TransferEvent::Failed { id, filename, error }).await;
+            return;
+        };
+        let event = match tokio::fs::write(&path, &bytes).await {
+            Ok(()) => {
+                let content = sealed.then(|| Hash::new(&bytes));
+                TransferEvent::Complete { id, filename, path, content }
             }
+            Err(e) => TransferEvent::Failed { id, filename, error: format!("write file: {e}") },
         };
+        let _ = tx.send(event).await;
+    });
+}
 
-        // Fetch the blob using iroh-blobs' verified streaming download.
-        // `HashAndFormat::raw(hash)` specifies we want a raw blob (not a hash
-        // sequence / collection). The "raw" format means the hash directly
-        // corresponds to the file content, verified chunk-by-chunk during download.
-        // `.stream()` returns an async stream of `GetProgressItem` events.
-        let content = HashAndFormat::raw(hash);
-        let mut progress_stream = store.remote().fetch(conn, content).stream();
-
-        // Consume the progress stream. Each item is either a progress update,
-        // completion notification, or error.
-        while let Some(item) = progress_stream.next().await {
-            match item {
-                iroh_blobs::api::remote::GetProgressItem::Progress(bytes) => {
-                    let _ = tx
-                        .send(TransferEvent::Progress {
-                            hash,
-                            bytes_received: bytes,
-                            total_bytes: offer.size,
-                        })
-                        .await;
-                }
-                iroh_blobs::api::remote::GetProgressItem::Done(_stats) => {
-                    // Blob downloaded into store — read it out and write to disk.
-                    // We use `get_bytes()` instead of `export()` because export
-                    // requires the entry to be in `Complete` state, which may not
-                    // be the case immediately after a fetch finishes.
-                    //
-                    // `get_bytes()` returns `Bytes` — a cheaply-clonable byte buffer.
-                    match store.blobs().get_bytes(hash).await {
-                        Ok(data) => {
-                            // `tokio::fs::write()` is the async version of `std::fs::write()`.
-                            // It creates the file (or truncates if it exists) and writes
-                            // all bytes atomically.
-                            match tokio::fs::write(&target, &data).await {
-                                Ok(_) => {
-                                    let _ = tx
-                                        .send(TransferEvent::Complete {
-                                            hash,
-                                            filename: filename.clone(),
-                                            path: target.clone(),
-                                        })
-                                        .await;
-                                }
-                                Err(e) => {
-                                    let _ = tx
-                                        .send(TransferEvent::Failed {
-                                            hash,
-                                            filename: filename.clone(),
-                                            error: format!("write file: {e}"),
-                                        })
-                                        .await;
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            let _ = tx
-                                .send(TransferEvent::Failed {
-                                    hash,
-                                    filename: filename.clone(),
-                                    error: format!("read blob: {e}"),
-                                })
-                                .await;
-                        }
-                    }
-                    return;
-                }
-                iroh_blobs::api::remote::GetProgressItem::Error(e) => {
-                    let _ = tx
-                        .send(TransferEvent::Failed {
-                            hash,
-                            filename: filename.clone(),
-                            error: format!("download: {e}"),
-                        })
-                        .await;
-                    return;
-                }
+/// Open `target` for the finished download at `path`, first checking the
+/// file against its `.sha256` sidecar if it has one. A file that no longer
+/// matches isn't opened; the mismatch is reported as a `Verified` event.
+fn open_download(filename: &str, path: &std::path::Path, target: PathBuf, tx: tokio::sync::mpsc::Sender<TransferEvent>) {
+    let filename = filename.to_string();
+    let path = path.to_path_buf();
+    tokio::spawn(async move {
+        let checked = tokio::task::spawn_blocking(move || checksum::check_sidecar(&path)).await;
+        let result = match checked {
+            Ok(Ok(None | Some(true))) => {
+                let _ = open::that(target);
+                return;
             }
-        }
+            Ok(Ok(Some(false))) => Ok(false),
+            Ok(Err(e)) => Err(e.to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+        let _ = tx.send(TransferEvent::Verified { filename, result }).await;
     });
 }
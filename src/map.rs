@@ -0,0 +1,229 @@
+//! `/map`: a live overlay of our view of the gossip mesh.
+//!
+//! We sit in the center; every peer in `App::peers` (our gossip neighbors,
+//! plus anyone heard from through them) is placed evenly around a circle
+//! with an edge back to us. Edges are colored by connection type — the same
+//! `conn_type` the UI tick refreshes from the endpoint for the peers panel —
+//! and labelled with the peer's round-trip time.
+//!
+//! Round-trip times come from roll calls: while the map is open it sends a
+//! silent one every `PING_INTERVAL` (no roster is printed), and the `Here`
+//! replies update `PeerInfo::rtt_ms`. A dot travels along each edge so a
+//! frozen map is easy to tell apart from a quiet one.
+//!
+//! The edges are drawn on a ratatui `Canvas` in Braille dots. In ASCII mode
+//! the map falls back to one line per peer.
+
+use std::collections::BTreeMap;
+use std::f64::consts::TAU;
+use std::time::{Duration, Instant};
+
+use iroh::EndpointId;
+use ratatui::{
+    Frame,
+    layout::Rect,
+    style::{Color, Style},
+    symbols::Marker,
+    text::{Line, Span},
+    widgets::{
+        Block, Borders, Clear, Paragraph,
+        canvas::{Canvas, Line as CanvasLine, Points},
+    },
+};
+
+use crate::i18n::Lang;
+use crate::net::{ConnType, MessageId, PeerInfo, new_message_id, tracker};
+use crate::t;
+use crate::theme::Theme;
+
+/// How often the open map re-measures round-trip times.
+pub const PING_INTERVAL: Duration = Duration::from_secs(3);
+
+/// Animation frames for a dot to travel from us to a peer.
+const TRAVEL_FRAMES: u64 = 20;
+
+/// Open `/map` overlay state.
+pub struct ConnMap {
+    /// Animation frame, advanced by the UI tick.
+    pub frame: u64,
+    /// Request ID of the latest ping, so only replies to our own pings
+    /// (whose echoed send time is our clock) update round-trip times.
+    pub ping_id: Option<MessageId>,
+    last_ping: Option<Instant>,
+}
+
+impl ConnMap {
+    pub fn new() -> Self {
+        Self { frame: 0, ping_id: None, last_ping: None }
+    }
+
+    /// Advance the animation by one frame.
+    pub fn tick(&mut self) {
+        self.frame = self.frame.wrapping_add(1);
+    }
+
+    /// A fresh ping request ID if one is due at `now`. The first ping goes
+    /// out as soon as the map opens.
+    pub fn ping_due(&mut self, now: Instant) -> Option<MessageId> {
+        if self.last_ping.is_some_and(|last| now.duration_since(last) < PING_INTERVAL) {
+            return None;
+        }
+        self.last_ping = Some(now);
+        let id = new_message_id();
+        self.ping_id = Some(id);
+        Some(id)
+    }
+
+    /// Draw the overlay over the whole frame.
+    pub fn render(
+        &self,
+        f: &mut Frame,
+        peers: &BTreeMap<EndpointId, PeerInfo>,
+        lang: Lang,
+        theme: &Theme,
+    ) {
+        let area = f.area();
+        let card_w = (area.width * 80 / 100).max(30).min(area.width);
+        let card_h = (area.height * 80 / 100).max(10).min(area.height);
+        let card = Rect::new(
+            area.width.saturating_sub(card_w) / 2,
+            area.height.saturating_sub(card_h) / 2,
+            card_w,
+            card_h,
+        );
+        f.render_widget(Clear, card);
+
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .border_set(theme.border_set())
+            .style(Style::default().bg(theme.bg))
+            .border_style(Style::default().fg(theme.border_focused))
+            .title(Line::styled(format!(" {} ", t!(lang, "map.title")), Style::default().fg(theme.title)).centered())
+            .title_bottom(Line::from(vec![
+                Span::styled(" Esc", Style::default().fg(theme.hint_key)),
+                Span::styled(format!(" {} ", t!(lang, "map.close")), Style::default().fg(theme.hint_text)),
+            ]));
+        let inner = block.inner(card);
+        f.render_widget(block, card);
+
+        let others: Vec<&PeerInfo> = peers.values().filter(|p| !matches!(p.conn_type, ConnType::You)).collect();
+        if others.is_empty() {
+            let text = Line::styled(t!(lang, "map.empty"), Style::default().fg(theme.text_muted)).centered();
+            f.render_widget(Paragraph::new(text), Rect { y: inner.y + inner.height / 2, height: 1, ..inner });
+            return;
+        }
+        if theme.ascii {
+            let lines: Vec<Line> = others
+                .iter()
+                .map(|peer| {
+                    Line::from(vec![
+                        Span::styled("  you ---- ", Style::default().fg(conn_color(&peer.conn_type, theme))),
+                        Span::styled(edge_label(peer), Style::default().fg(theme.text)),
+                    ])
+                })
+                .collect();
+            f.render_widget(Paragraph::new(lines), inner);
+            return;
+        }
+
+        // The canvas maps [-1, 1] on both axes onto the area. Terminal cells
+        // are about twice as tall as wide, so widen the x range to keep the
+        // circle round.
+        let aspect = (inner.width as f64 / (inner.height as f64 * 2.0)).max(1.0);
+        let count = others.len();
+        let frame = self.frame;
+        let canvas = Canvas::default()
+            .marker(Marker::Braille)
+            .background_color(theme.bg)
+            .x_bounds([-aspect, aspect])
+            .y_bounds([-1.0, 1.0])
+            .paint(move |ctx| {
+                for (i, peer) in others.iter().enumerate() {
+                    let (x, y) = position(i, count);
+                    let color = conn_color(&peer.conn_type, theme);
+                    ctx.draw(&CanvasLine { x1: 0.0, y1: 0.0, x2: x, y2: y, color });
+                    // Stagger the dots so they don't all move in step.
+                    let t = ((frame + i as u64 * 7) % TRAVEL_FRAMES) as f64 / TRAVEL_FRAMES as f64;
+                    ctx.draw(&Points { coords: &[(x * t, y * t)], color: theme.accent });
+                }
+                // Labels go on a layer above the edges.
+                ctx.layer();
+                ctx.print(0.0, 0.0, Span::styled("you", Style::default().fg(theme.conn_you)));
+                for (i, peer) in others.iter().enumerate() {
+                    let (x, y) = position(i, count);
+                    // Labels on the left half end at the node, not start there.
+                    let label = edge_label(peer);
+                    let x = if x < 0.0 { x - label.len() as f64 * 2.0 * aspect / inner.width as f64 } else { x };
+                    ctx.print(x, y, Span::styled(label, Style::default().fg(theme.text)));
+                }
+            });
+        f.render_widget(canvas, inner);
+    }
+}
+
+impl Default for ConnMap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Where the `i`th of `count` peers sits on the circle, starting at the top.
+fn position(i: usize, count: usize) -> (f64, f64) {
+    let angle = TAU * i as f64 / count as f64 + TAU / 4.0;
+    (0.8 * angle.cos(), 0.8 * angle.sin())
+}
+
+fn conn_color(conn: &ConnType, theme: &Theme) -> Color {
+    match conn {
+        ConnType::Direct => theme.conn_direct,
+        ConnType::Relay => theme.conn_relay,
+        ConnType::Unknown => theme.conn_unknown,
+        ConnType::You => theme.conn_you,
+    }
+}
+
+/// "Bob direct IPv6 23 ms": name, connection type (with the address
+/// family of a direct path) and last round trip.
+fn edge_label(peer: &PeerInfo) -> String {
+    let conn = match (&peer.conn_type, &peer.addr) {
+        (ConnType::Direct, Some(addr)) => format!("direct {}", tracker::family(addr)),
+        (ConnType::Direct, None) => "direct".into(),
+        (ConnType::Relay, _) => "relay".into(),
+        _ => "?".into(),
+    };
+    match peer.rtt_ms {
+        Some(rtt) => format!("{} {conn} {rtt} ms", peer.name),
+        None => format!("{} {conn}", peer.name),
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pings_are_spaced_out() {
+        let mut map = ConnMap::new();
+        let start = Instant::now();
+        let first = map.ping_due(start).unwrap();
+        assert_eq!(map.ping_id, Some(first));
+        assert!(map.ping_due(start + PING_INTERVAL / 2).is_none());
+        let second = map.ping_due(start + PING_INTERVAL).unwrap();
+        assert_ne!(first, second);
+        assert_eq!(map.ping_id, Some(second));
+    }
+
+    #[test]
+    fn labels_and_positions() {
+        let peer = PeerInfo { name: "Bob".into(), conn_type: ConnType::Direct, rtt_ms: Some(23), addr: None };
+        assert_eq!(edge_label(&peer), "Bob direct 23 ms");
+        let peer = PeerInfo { addr: Some("[2001:db8::1]:4433".parse().unwrap()), ..peer };
+        assert_eq!(edge_label(&peer), "Bob direct IPv6 23 ms");
+        let peer = PeerInfo { name: "Carol".into(), conn_type: ConnType::Unknown, rtt_ms: None, addr: None };
+        assert_eq!(edge_label(&peer), "Carol ?");
+        let (x, y) = position(0, 4);
+        assert!(x.abs() < 1e-9 && (y - 0.8).abs() < 1e-9);
+    }
+}
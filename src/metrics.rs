@@ -0,0 +1,176 @@
+//! Counters for monitoring a long-running instance, in Prometheus format.
+//!
+//! The counters themselves are always compiled in — a handful of relaxed
+//! atomic adds — and live in one process-wide `METRICS`, so the places that
+//! count don't need a handle threaded through to them:
+//!
+//! - messages in: every gossip message that verifies (`proto::decode`)
+//! - messages out: every successful broadcast (`MessageSender`)
+//! - peers: the chat's current neighbors, set on the status clock tick
+//! - transfer bytes received: blob fetches (`node::fetch_bytes`)
+//! - transfer bytes sent: blob get requests served, by `access::gate` or,
+//!   for the `share` subcommand, its own provider events
+//! - reconnects: the network coming back after broadcasts failed, and
+//!   rejoining the room after a suspend
+//!
+//! Exposing them takes the `metrics` cargo feature, which adds two global
+//! flags: `--metrics-port <PORT>` serves `render()` over HTTP on
+//! `127.0.0.1:<PORT>` (any path), and `--metrics-file <PATH>` rewrites the
+//! file every `WRITE_INTERVAL` — the layout node_exporter's textfile
+//! collector reads.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// How often `--metrics-file` is rewritten.
+#[cfg(feature = "metrics")]
+pub const WRITE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(15);
+
+/// The process-wide counters.
+pub static METRICS: Metrics = Metrics::new();
+
+/// Counters and gauges, all `Relaxed`: each is read on its own, so there's
+/// no ordering between them to keep.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    messages_in: AtomicU64,
+    messages_out: AtomicU64,
+    peers: AtomicU64,
+    bytes_received: AtomicU64,
+    bytes_sent: AtomicU64,
+    reconnects: AtomicU64,
+}
+
+impl Metrics {
+    pub const fn new() -> Self {
+        Self {
+            messages_in: AtomicU64::new(0),
+            messages_out: AtomicU64::new(0),
+            peers: AtomicU64::new(0),
+            bytes_received: AtomicU64::new(0),
+            bytes_sent: AtomicU64::new(0),
+            reconnects: AtomicU64::new(0),
+        }
+    }
+
+    pub fn message_in(&self) {
+        self.messages_in.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn message_out(&self) {
+        self.messages_out.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn set_peers(&self, peers: usize) {
+        self.peers.store(peers as u64, Ordering::Relaxed);
+    }
+
+    pub fn received(&self, bytes: u64) {
+        self.bytes_received.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub fn sent(&self, bytes: u64) {
+        self.bytes_sent.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub fn reconnect(&self) {
+        self.reconnects.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Everything in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let rows = [
+            ("piper_chat_messages_received_total", "counter", "Gossip messages received and verified.", &self.messages_in),
+            ("piper_chat_messages_sent_total", "counter", "Messages broadcast.", &self.messages_out),
+            ("piper_chat_peers", "gauge", "Peers currently connected.", &self.peers),
+            ("piper_chat_transfer_received_bytes_total", "counter", "File bytes downloaded.", &self.bytes_received),
+            ("piper_chat_transfer_sent_bytes_total", "counter", "File bytes served.", &self.bytes_sent),
+            ("piper_chat_reconnects_total", "counter", "Times the room was reached again after losing it.", &self.reconnects),
+        ];
+        let mut text = String::new();
+        for (name, kind, help, value) in rows {
+            text.push_str(&format!("# HELP {name} {help}\n# TYPE {name} {kind}\n{name} {}\n", value.load(Ordering::Relaxed)));
+        }
+        text
+    }
+}
+
+/// Serve `METRICS` over HTTP on `127.0.0.1:port` until the process exits.
+/// Every request gets the metrics, whatever its path.
+#[cfg(feature = "metrics")]
+pub async fn serve(port: u16) -> crate::error::Result<()> {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use crate::error::ResultExt;
+
+    let listener = tokio::net::TcpListener::bind(("127.0.0.1", port)).await.network()?;
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            tokio::spawn(async move {
+                // The request itself doesn't matter; read it so the client
+                // isn't reset before it sees the answer.
+                let mut request = [0u8; 1024];
+                let _ = stream.read(&mut request).await;
+                let body = METRICS.render();
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+                let _ = stream.write_all(response.as_bytes()).await;
+            });
+        }
+    });
+    Ok(())
+}
+
+/// Rewrite `path` with `METRICS` every `WRITE_INTERVAL` until the process
+/// exits, through a temporary file so a reader never sees half of it.
+#[cfg(feature = "metrics")]
+pub fn spawn_writer(path: std::path::PathBuf) {
+    tokio::spawn(async move {
+        let tmp = path.with_extension("tmp");
+        let mut every = tokio::time::interval(WRITE_INTERVAL);
+        loop {
+            every.tick().await;
+            if tokio::fs::write(&tmp, METRICS.render()).await.is_ok() {
+                let _ = tokio::fs::rename(&tmp, &path).await;
+            }
+        }
+    });
+}
+
+// ── Tests ────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_lists_every_metric_once() {
+        let metrics = Metrics::new();
+        metrics.message_in();
+        metrics.message_in();
+        metrics.received(1500);
+        metrics.set_peers(3);
+        let text = metrics.render();
+        assert!(text.contains("piper_chat_messages_received_total 2\n"));
+        assert!(text.contains("# TYPE piper_chat_peers gauge\npiper_chat_peers 3\n"));
+        assert!(text.contains("piper_chat_transfer_received_bytes_total 1500\n"));
+        assert_eq!(text.lines().filter(|l| !l.starts_with('#')).count(), 6);
+    }
+
+    #[cfg(feature = "metrics")]
+    #[tokio::test]
+    async fn serves_over_http() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // Find a free port, then serve on it.
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        serve(port).await.unwrap();
+        let mut stream = tokio::net::TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+        stream.write_all(b"GET /metrics HTTP/1.1\r\n\r\n").await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("piper_chat_reconnects_total"));
+    }
+}
@@ -0,0 +1,192 @@
+//! Dialing a ticket's bootstrap peers one by one before joining.
+//!
+//! Gossip's `subscribe` hands every bootstrap peer to the swarm at once and
+//! returns straight away; if none of them can be reached, nothing says so
+//! and the chat just sits at "waiting for peers...". `probe()` dials each
+//! peer on its own, under its own `PROBE_TIMEOUT`, and says why a failed
+//! one failed, for the join screen (`welcome::run_join_screen`) to list.
+//!
+//! The reasons are a best guess from what the endpoint knows when the dial
+//! gives up:
+//!
+//! - no address at all for the peer → it's offline (or was never online)
+//! - a timeout while we have no home relay → our side can't reach a relay
+//! - a timeout with a relay on both sides → the relay couldn't find the
+//!   peer, so it's offline
+//! - anything else → the connect error itself
+//!
+//! `known()` sums up what the endpoint knows of a peer's addresses, for the
+//! diagnostics the join overlay (`joining`) prints on request.
+//!
+//! Before any of that, `check_relay()` tells the welcome screen whether a
+//! relay is reachable at all, so a user about to create a room learns that
+//! peers on other networks won't find them before the room exists rather
+//! than after.
+
+use std::time::Duration;
+
+use iroh::{Endpoint, EndpointId};
+
+use crate::net::direct::DIRECT_ALPN;
+
+/// How long one bootstrap peer gets to answer.
+pub const PROBE_TIMEOUT: Duration = Duration::from_secs(8);
+
+/// Why a bootstrap peer couldn't be reached.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Failure {
+    /// No address is known for the peer: neither the ticket's hints nor
+    /// address lookup had one.
+    Offline,
+    /// We timed out without a home relay of our own.
+    RelayTimeout,
+    /// The relay knows no connection to the peer and it didn't answer.
+    PeerOffline,
+    /// It didn't answer in time over the direct addresses we have.
+    Timeout,
+    /// The connect failed outright.
+    Error(String),
+}
+
+impl Failure {
+    /// Short reason for the join screen.
+    pub fn label(&self) -> String {
+        match self {
+            Failure::Offline => "peer offline (no address known)".to_string(),
+            Failure::RelayTimeout => "relay timeout".to_string(),
+            Failure::PeerOffline => "peer offline".to_string(),
+            Failure::Timeout => "no answer".to_string(),
+            Failure::Error(e) => e.clone(),
+        }
+    }
+}
+
+/// Where one bootstrap peer's probe is.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Probe {
+    Trying,
+    Reached,
+    Failed(Failure),
+}
+
+/// The reason for a dial that timed out, given whether we have a home relay
+/// and whether the peer has a relay address we know of.
+pub fn timeout_reason(our_relay: bool, peer_relay: bool) -> Failure {
+    match (our_relay, peer_relay) {
+        (false, _) => Failure::RelayTimeout,
+        (true, true) => Failure::PeerOffline,
+        (true, false) => Failure::Timeout,
+    }
+}
+
+/// Dial `peer` and hang up as soon as the connection is up.
+pub async fn probe(endpoint: &Endpoint, peer: EndpointId, timeout: Duration) -> Result<(), Failure> {
+    use iroh::endpoint::{ConnectError, ConnectWithOptsError};
+    match tokio::time::timeout(timeout, endpoint.connect(peer, DIRECT_ALPN)).await {
+        Ok(Ok(connection)) => {
+            connection.close(0u32.into(), b"probe");
+            Ok(())
+        }
+        Ok(Err(ConnectError::Connect { source: ConnectWithOptsError::NoAddress { .. }, .. })) => Err(Failure::Offline),
+        Ok(Err(e)) => Err(Failure::Error(e.to_string())),
+        Err(_) => {
+            let our_relay = endpoint.addr().relay_urls().next().is_some();
+            let peer_relay = endpoint
+                .remote_info(peer)
+                .await
+                .is_some_and(|info| info.into_addrs().any(|a| a.into_addr().is_relay()));
+            Err(timeout_reason(our_relay, peer_relay))
+        }
+    }
+}
+
+/// What the endpoint knows of how to reach `peer`, for the join overlay's
+/// diagnostics.
+pub async fn known(endpoint: &Endpoint, peer: EndpointId) -> String {
+    let Some(info) = endpoint.remote_info(peer).await else {
+        return "no address known".to_string();
+    };
+    let (mut relay, mut direct) = (false, 0);
+    for addr in info.into_addrs() {
+        if addr.into_addr().is_relay() {
+            relay = true;
+        } else {
+            direct += 1;
+        }
+    }
+    match (relay, direct) {
+        (false, 0) => "no address known".to_string(),
+        (true, 0) => "relay only".to_string(),
+        (relay, n) => format!("{}{n} direct address{}", if relay { "relay and " } else { "" }, if n == 1 { "" } else { "es" }),
+    }
+}
+
+// ── Relay check ──────────────────────────────────────────────────────────────
+
+/// How long the welcome screen's relay check waits for a home relay.
+pub const RELAY_CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// What the welcome screen knows about reaching a relay.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RelayCheck {
+    /// Still waiting for a home relay.
+    Checking,
+    /// `relay` (its host name) became our home relay, `after` this long.
+    Reachable { relay: String, after: Duration },
+    /// No relay answered within the timeout.
+    Unreachable,
+    /// Relays are turned off (`--paths direct`), so there's nothing to check.
+    Disabled,
+    /// The throwaway endpoint couldn't even be bound.
+    Error(String),
+}
+
+/// Bind a throwaway endpoint from `builder` and wait up to `timeout` for it
+/// to get a home relay.
+///
+/// The builder should carry the same transport settings (paths, proxy) the
+/// chat will use, but no secret key: the check runs under a fresh random
+/// identity, so it says nothing to anyone about ours. The endpoint is closed
+/// again before returning.
+pub async fn check_relay(builder: iroh::endpoint::Builder, timeout: Duration) -> RelayCheck {
+    let endpoint = match builder.bind().await {
+        Ok(endpoint) => endpoint,
+        Err(e) => return RelayCheck::Error(e.to_string()),
+    };
+    let started = std::time::Instant::now();
+    let check = match tokio::time::timeout(timeout, endpoint.online()).await {
+        Ok(()) => match endpoint.addr().relay_urls().next() {
+            Some(url) => RelayCheck::Reachable {
+                relay: url.host_str().unwrap_or_default().to_string(),
+                after: started.elapsed(),
+            },
+            None => RelayCheck::Unreachable,
+        },
+        Err(_) => RelayCheck::Unreachable,
+    };
+    endpoint.close().await;
+    check
+}
+
+// ── Tests ────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timeouts_blame_the_missing_side() {
+        assert_eq!(timeout_reason(false, true), Failure::RelayTimeout);
+        assert_eq!(timeout_reason(true, true), Failure::PeerOffline);
+        assert_eq!(timeout_reason(true, false), Failure::Timeout);
+        assert_eq!(Failure::RelayTimeout.label(), "relay timeout");
+    }
+
+    /// A relay nothing listens on never becomes the home relay.
+    #[tokio::test]
+    async fn dead_relay_is_unreachable() {
+        let relay: iroh::RelayUrl = "http://127.0.0.1:1".parse().unwrap();
+        let builder = Endpoint::empty_builder(iroh::RelayMode::custom([relay])).bind_addr("127.0.0.1:0").unwrap();
+        assert_eq!(check_relay(builder, Duration::from_millis(300)).await, RelayCheck::Unreachable);
+    }
+}
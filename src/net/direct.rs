@@ -0,0 +1,210 @@
+//! Direct-stream delivery: a fallback for when gossip doesn't get through.
+//!
+//! Gossip only delivers once the swarm has formed a mesh, and in a room of
+//! two or three peers that can take a while (or never happen if a link is
+//! flaky). So chat messages are *acknowledged*: every peer that receives one
+//! broadcasts a small `Message::Ack`. The sender keeps each message in an
+//! `Outbox` until every peer it expected has acked, and any peer still
+//! missing after `ACK_TIMEOUT` gets the same signed bytes again over a
+//! direct QUIC connection on `DIRECT_ALPN`.
+//!
+//! A direct delivery is one connection carrying one unidirectional stream:
+//! the sender writes the encoded message and finishes the stream, the
+//! receiver reads it to the end and closes the connection, which tells the
+//! sender it has arrived. Received bytes go through the same `decode()` and
+//! message-ID dedup as gossip, so a message that arrives both ways shows once.
+
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+use iroh::endpoint::Connection;
+use iroh::protocol::{AcceptError, ProtocolHandler};
+use iroh::{Endpoint, EndpointId};
+use tokio::sync::mpsc;
+
+use crate::error::{Result, ResultExt};
+
+use super::MessageId;
+
+/// ALPN for direct message delivery. The trailing number is a version, so
+/// an incompatible change can register a new ALPN alongside the old one.
+pub const DIRECT_ALPN: &[u8] = b"piper-chat/direct/0";
+
+/// How long to wait for acks before re-sending over direct streams.
+pub const ACK_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Largest message accepted over a direct stream. Chat messages are small;
+/// anything bigger than this is not something we sent.
+const MAX_DIRECT_MESSAGE: usize = 256 * 1024;
+
+/// A message received over a direct stream: the connecting peer and the
+/// encoded (signed) message bytes.
+pub type DirectMessage = (EndpointId, Vec<u8>);
+
+// ── Protocol handler ─────────────────────────────────────────────────────────
+
+/// Accepts direct deliveries and forwards their bytes to the event loop.
+///
+/// Registered on the router under `DIRECT_ALPN` by `Node::spawn`.
+#[derive(Debug, Clone)]
+pub struct DirectProtocol {
+    tx: mpsc::Sender<DirectMessage>,
+}
+
+impl DirectProtocol {
+    /// Create the handler and the receiving end of its channel.
+    pub fn new() -> (Self, mpsc::Receiver<DirectMessage>) {
+        let (tx, rx) = mpsc::channel(64);
+        (Self { tx }, rx)
+    }
+}
+
+impl ProtocolHandler for DirectProtocol {
+    async fn accept(&self, connection: Connection) -> Result<(), AcceptError> {
+        let from = connection.remote_id();
+        let mut stream = connection.accept_uni().await?;
+        let bytes = stream
+            .read_to_end(MAX_DIRECT_MESSAGE)
+            .await
+            .map_err(AcceptError::from_err)?;
+        // Closing is the receipt: the sender waits for it before hanging up.
+        connection.close(0u32.into(), b"received");
+        // A full channel means the UI is gone or badly behind; dropping the
+        // message is fine, since gossip may still deliver it.
+        let _ = self.tx.try_send((from, bytes));
+        Ok(())
+    }
+}
+
+/// Deliver already-encoded message `bytes` to `peer` over a direct stream.
+pub async fn send(endpoint: &Endpoint, peer: EndpointId, bytes: &[u8]) -> Result<()> {
+    let connection = endpoint.connect(peer, DIRECT_ALPN).await.network()?;
+    let mut stream = connection.open_uni().await.network()?;
+    stream.write_all(bytes).await.network()?;
+    stream.finish().network()?;
+    // Wait for the receiver to close the connection once it has read
+    // everything; dropping it sooner could cut the stream short.
+    connection.closed().await;
+    Ok(())
+}
+
+// ── Ack tracking ─────────────────────────────────────────────────────────────
+
+/// A sent message still waiting on acks.
+struct Pending {
+    bytes: Vec<u8>,
+    sent_at: Instant,
+    /// Peers that should ack but haven't yet.
+    waiting_on: HashSet<EndpointId>,
+}
+
+/// Sent chat messages awaiting acknowledgement.
+#[derive(Default)]
+pub struct Outbox {
+    pending: HashMap<MessageId, Pending>,
+}
+
+impl Outbox {
+    /// Start tracking message `id` (encoded as `bytes`), expecting an ack
+    /// from each of `peers`. With no peers there is no one to re-send to,
+    /// so nothing is tracked.
+    pub fn track(
+        &mut self,
+        id: MessageId,
+        bytes: Vec<u8>,
+        peers: impl IntoIterator<Item = EndpointId>,
+        now: Instant,
+    ) {
+        let waiting_on: HashSet<_> = peers.into_iter().collect();
+        if !waiting_on.is_empty() {
+            self.pending.insert(
+                id,
+                Pending {
+                    bytes,
+                    sent_at: now,
+                    waiting_on,
+                },
+            );
+        }
+    }
+
+    /// Record that `from` acknowledged message `id`. Once everyone has, the
+    /// message is forgotten.
+    pub fn ack(&mut self, id: &MessageId, from: EndpointId) {
+        if let Some(pending) = self.pending.get_mut(id) {
+            pending.waiting_on.remove(&from);
+            if pending.waiting_on.is_empty() {
+                self.pending.remove(id);
+            }
+        }
+    }
+
+    /// Take every message whose ack window has passed, with the peers that
+    /// never acked it. Each message is re-sent at most once: it leaves the
+    /// outbox here whether or not the direct delivery then succeeds.
+    pub fn take_overdue(&mut self, now: Instant) -> Vec<(Vec<u8>, Vec<EndpointId>)> {
+        let overdue: Vec<MessageId> = self
+            .pending
+            .iter()
+            .filter(|(_, p)| now.duration_since(p.sent_at) >= ACK_TIMEOUT)
+            .map(|(id, _)| *id)
+            .collect();
+        overdue
+            .into_iter()
+            .filter_map(|id| self.pending.remove(&id))
+            .map(|p| (p.bytes, p.waiting_on.into_iter().collect()))
+            .collect()
+    }
+
+    /// Number of messages still awaiting acks.
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Whether nothing is awaiting acks.
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn peer(byte: u8) -> EndpointId {
+        EndpointId::from_bytes(&[byte; 32]).unwrap()
+    }
+
+    #[test]
+    fn fully_acked_messages_are_dropped() {
+        let mut outbox = Outbox::default();
+        let now = Instant::now();
+        outbox.track([1; 16], vec![1, 2, 3], [peer(1), peer(3)], now);
+        outbox.ack(&[1; 16], peer(1));
+        assert_eq!(outbox.len(), 1);
+        outbox.ack(&[1; 16], peer(3));
+        assert!(outbox.is_empty());
+    }
+
+    #[test]
+    fn overdue_messages_go_to_missing_peers_once() {
+        let mut outbox = Outbox::default();
+        let now = Instant::now();
+        outbox.track([1; 16], vec![9], [peer(1), peer(3)], now);
+        outbox.ack(&[1; 16], peer(1));
+
+        assert!(outbox.take_overdue(now + ACK_TIMEOUT / 2).is_empty());
+        let overdue = outbox.take_overdue(now + ACK_TIMEOUT);
+        assert_eq!(overdue, vec![(vec![9], vec![peer(3)])]);
+        assert!(outbox.take_overdue(now + ACK_TIMEOUT * 2).is_empty());
+    }
+
+    #[test]
+    fn nothing_tracked_without_peers() {
+        let mut outbox = Outbox::default();
+        outbox.track([1; 16], vec![9], [], Instant::now());
+        assert!(outbox.is_empty());
+    }
+}
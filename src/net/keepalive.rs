@@ -0,0 +1,71 @@
+//! Keepalives for quiet rooms.
+//!
+//! NAT mappings and relay connections are dropped after a stretch without
+//! traffic, often 30 seconds to a few minutes. A room where nobody types
+//! for a while would lose its paths silently and only notice when the next
+//! message has to find a new one. So when nothing has gone either way for
+//! `keepalive_secs` (config), the event loop broadcasts a `KeepAlive` — a
+//! few dozen bytes that every neighbor forwards, which is enough to refresh
+//! the mappings along each hop. Any other message resets the wait, so a
+//! busy room never sends one.
+//!
+//! `keepalive_secs = 0` turns them off, for metered connections: iroh's own
+//! QUIC pings still keep live connections open, but quiet ones may need to
+//! be re-established.
+
+use std::time::{Duration, Instant};
+
+/// Default wait before a keepalive: under the 30 s UDP timeout of the
+/// strictest common NATs.
+pub const DEFAULT_KEEPALIVE_SECS: u64 = 25;
+
+/// When the room last saw traffic, and how long it may stay quiet.
+pub struct Keepalive {
+    every: Option<Duration>,
+    last: Instant,
+}
+
+impl Keepalive {
+    /// Send a keepalive after `secs` seconds of silence (never if 0),
+    /// counting from `now`.
+    pub fn new(secs: u64, now: Instant) -> Self {
+        Self { every: (secs > 0).then(|| Duration::from_secs(secs)), last: now }
+    }
+
+    /// Note a message sent or received at `now`.
+    pub fn traffic(&mut self, now: Instant) {
+        self.last = now;
+    }
+
+    /// Whether the room has been quiet long enough for a keepalive at `now`.
+    /// Returning `true` restarts the wait, so a failed send isn't retried
+    /// every tick.
+    pub fn due(&mut self, now: Instant) -> bool {
+        let due = self.every.is_some_and(|every| now.duration_since(self.last) >= every);
+        if due {
+            self.last = now;
+        }
+        due
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keepalives_fill_silence_only() {
+        let start = Instant::now();
+        let mut keepalive = Keepalive::new(25, start);
+        assert!(!keepalive.due(start + Duration::from_secs(24)));
+        keepalive.traffic(start + Duration::from_secs(20));
+        assert!(!keepalive.due(start + Duration::from_secs(30)));
+        assert!(keepalive.due(start + Duration::from_secs(45)));
+        assert!(!keepalive.due(start + Duration::from_secs(46)));
+
+        let mut off = Keepalive::new(0, start);
+        assert!(!off.due(start + Duration::from_secs(3600)));
+    }
+}
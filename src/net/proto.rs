@@ -0,0 +1,1133 @@
+//! Wire protocol: the messages peers exchange over gossip.
+//!
+//! Every `Message` is postcard-encoded, signed with the author's endpoint key,
+//! and wrapped in a versioned envelope before it is broadcast. `MessageSender`
+//! does all of that in one place; `decode()` undoes it on the receiving side.
+
+// `anyhow::Result` is a convenient alias for `Result<T, anyhow::Error>`.
+// It lets any error type that implements `std::error::Error` be returned with `?`.
+// `bail!` returns early with an ad-hoc error; `Context` adds a message to
+// `Option`/`Result` failures.
+use anyhow::{Context, Result, bail};
+// `EndpointId` is a unique cryptographic identifier for each peer node — it is
+// an Ed25519 public key, so it can verify signatures made by the matching
+// `SecretKey`.
+use iroh::{EndpointId, SecretKey, Signature};
+// The raw, byte-oriented sender half of a gossip topic subscription.
+use iroh_gossip::api::GossipSender;
+// A gossip topic — one room. Approvals are bound to it.
+use iroh_gossip::proto::TopicId;
+// `Serialize` and `Deserialize` are derive macros from the `serde` crate.
+// They auto-generate code to convert structs/enums to/from formats like JSON,
+// postcard (binary), etc. — a cornerstone of Rust's zero-boilerplate approach.
+use serde::{Deserialize, Serialize};
+
+use std::borrow::Cow;
+
+use crate::error::{self, ResultExt};
+use crate::metrics::METRICS;
+use crate::sanitize;
+
+// ── Message identity & timestamps ────────────────────────────────────────────
+
+/// A 128-bit random message identifier for deduplication during history merge.
+pub type MessageId = [u8; 16];
+
+/// Generate a new random 128-bit message ID.
+pub fn new_message_id() -> MessageId {
+    rand::random()
+}
+
+/// Current wall-clock time as milliseconds since UNIX epoch.
+pub fn now_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
+
+// ── Wire protocol ────────────────────────────────────────────────────────────
+//
+// Every message sent over the gossip network is one of these variants.
+// We use `postcard` (a compact binary format) to serialize them.
+
+/// Messages exchanged between peers over the gossip network.
+///
+/// This is a Rust *enum* with named fields — sometimes called a "tagged union"
+/// or "algebraic data type". Each variant is a distinct message kind, and
+/// pattern matching (`match`) ensures you handle every case.
+///
+/// The `#[derive(...)]` attribute invokes procedural macros at compile time
+/// to auto-implement the `Serialize` and `Deserialize` traits. No runtime
+/// reflection — all the serialization code is generated at compile time.
+#[derive(Serialize, Deserialize)]
+pub enum Message {
+    /// Sent when a peer first connects, so others learn its display name.
+    Join {
+        nickname: String,
+        endpoint_id: EndpointId,
+    },
+    /// A regular chat message from a peer.
+    Chat {
+        nickname: String,
+        text: String,
+        message_id: MessageId,
+        timestamp_ms: u64,
+    },
+    /// A file offer — the sender has imported a file into their blob store
+    /// and is advertising it so peers can download via iroh-blobs.
+    FileOffer {
+        nickname: String,
+        endpoint_id: EndpointId,
+        filename: String,
+        size: u64,
+        /// The BLAKE3 hash of the file content, stored as raw bytes for
+        /// compact serialization with postcard.
+        hash: [u8; 32],
+        message_id: MessageId,
+        timestamp_ms: u64,
+        /// MIME type inferred from the file extension (e.g. "image/png").
+        mime_type: Option<String>,
+        /// If `Some(name)`, only the named peer should accept this offer.
+        /// `None` means broadcast to all peers.
+        target: Option<String>,
+        /// Names the offer itself, which a re-broadcast keeps while getting
+        /// a fresh `message_id` (gossip drops repeated bytes). Receivers add
+        /// an offer once per `(hash, endpoint_id, offer_id)`. The first
+        /// broadcast uses its own `message_id`.
+        offer_id: MessageId,
+    },
+    /// Retract a previously shared file offer. No longer acted on: it names
+    /// neither the offer nor its author, so any peer could retract anyone's
+    /// file (see `FileRevoke`). Kept so the variants after it keep their
+    /// wire tags.
+    FileRetract {
+        nickname: String,
+        hash: [u8; 32],
+        message_id: MessageId,
+        timestamp_ms: u64,
+    },
+    /// A peer is offering its chat history as a downloadable blob.
+    HistoryOffer {
+        message_count: u32,
+        oldest_timestamp_ms: u64,
+        newest_timestamp_ms: u64,
+        hash: [u8; 32],
+        endpoint_id: EndpointId,
+    },
+    /// A chat message whose text is hidden behind redaction blocks until
+    /// each viewer chooses to reveal it.
+    ///
+    /// New variants are always appended: postcard encodes the variant by its
+    /// index, so inserting one in the middle would break older peers.
+    Spoiler {
+        nickname: String,
+        text: String,
+        message_id: MessageId,
+        timestamp_ms: u64,
+    },
+    /// A roll-call request (`/who`): every peer that receives it answers
+    /// with `Here`, echoing `sent_ms` so the requester can measure latency.
+    RollCall {
+        request_id: MessageId,
+        endpoint_id: EndpointId,
+        sent_ms: u64,
+    },
+    /// A reply to a `RollCall`.
+    Here {
+        request_id: MessageId,
+        nickname: String,
+        endpoint_id: EndpointId,
+        /// The `sent_ms` of the roll call being answered, echoed back.
+        echo_ms: u64,
+    },
+    /// Acknowledges receipt of a `Chat` or `Spoiler`. The author re-sends
+    /// over a direct stream to peers whose ack doesn't arrive in time (see
+    /// `net::direct`).
+    Ack {
+        message_id: MessageId,
+        endpoint_id: EndpointId,
+    },
+    /// Sent over a direct stream to one neighbor after our gossip stream
+    /// lagged: "send me what I missed". `known` holds the IDs of our most
+    /// recent history entries and `since_ms` the oldest of their timestamps,
+    /// so the neighbor only returns newer entries we don't already have.
+    BackfillRequest {
+        since_ms: u64,
+        known: Vec<MessageId>,
+        endpoint_id: EndpointId,
+    },
+    /// The answer to a `BackfillRequest`, sent back over a direct stream.
+    BackfillResponse {
+        entries: Vec<HistoryEntry>,
+        endpoint_id: EndpointId,
+    },
+    /// Withdraw one of our own file offers at `at_ms`. Unlike `FileRetract`
+    /// it is tied to the offer's author through `endpoint_id`, so nobody
+    /// else can revoke it. An `at_ms` in the future attaches an expiry that
+    /// every peer enforces on its own clock; otherwise it takes effect now.
+    FileRevoke {
+        hash: [u8; 32],
+        endpoint_id: EndpointId,
+        message_id: MessageId,
+        timestamp_ms: u64,
+        at_ms: u64,
+        /// Which of the author's offers of `hash` is revoked.
+        offer_id: MessageId,
+    },
+    /// Asks to be let into a knock-to-join room (see `admission`). Only the
+    /// room's creator can answer, with a `JoinApproval` or a `JoinDenial`.
+    JoinRequest {
+        nickname: String,
+        endpoint_id: EndpointId,
+    },
+    /// The creator's approvals for a knock-to-join room: everyone let in so
+    /// far, so a newcomer learns the whole roster at once. Each approval
+    /// carries its own creator signature, so anyone may pass them on.
+    JoinApproval {
+        approvals: Vec<Approval>,
+        endpoint_id: EndpointId,
+    },
+    /// The creator turned `denied`'s `JoinRequest` away. Only counts when
+    /// `endpoint_id` (the signer) is the room's creator.
+    JoinDenial {
+        denied: EndpointId,
+        endpoint_id: EndpointId,
+    },
+    /// A chat message posted in a thread: `thread_id` is the `message_id`
+    /// of the message the thread hangs off (its root). Peers that predate
+    /// threads ignore it, like any unknown variant.
+    ThreadChat {
+        nickname: String,
+        text: String,
+        message_id: MessageId,
+        timestamp_ms: u64,
+        thread_id: MessageId,
+    },
+    /// A chat message quoting an earlier one: `reply_to` is the quoted
+    /// message's `message_id`. Receivers show an excerpt of it above.
+    Reply {
+        nickname: String,
+        text: String,
+        message_id: MessageId,
+        timestamp_ms: u64,
+        reply_to: MessageId,
+    },
+    /// A shared countdown (`/timer 10m standup`) ending at `ends_ms` on the
+    /// wall clock; every client shows it and announces when it's up.
+    Timer {
+        nickname: String,
+        label: String,
+        timer_id: MessageId,
+        ends_ms: u64,
+    },
+    /// Sent when the room has been quiet for a while, to keep NAT mappings
+    /// and relay connections from expiring (see `net::keepalive`). Carries
+    /// `sent_ms` so each one is a distinct gossip message, not a duplicate
+    /// of the last. Receivers ignore it.
+    KeepAlive {
+        endpoint_id: EndpointId,
+        sent_ms: u64,
+    },
+    /// A `FileOffer` whose blob is sealed (see `seal`): `wrapped_key` is
+    /// the content key, wrapped for the room. Peers that predate sealing
+    /// ignore it, which is right, since they couldn't open the file anyway.
+    SealedFileOffer {
+        nickname: String,
+        endpoint_id: EndpointId,
+        filename: String,
+        /// Size of the content, not of the (slightly bigger) sealed blob.
+        size: u64,
+        hash: [u8; 32],
+        message_id: MessageId,
+        timestamp_ms: u64,
+        mime_type: Option<String>,
+        target: Option<String>,
+        wrapped_key: Vec<u8>,
+        offer_id: MessageId,
+    },
+    /// Every co-owner grant made in a room with a `RoomPolicy` so far (see
+    /// `admission`), so whoever hears it learns the whole owner set. Like
+    /// `JoinApproval`, each grant carries its own signature, so anyone may
+    /// pass them on.
+    GrantOwner {
+        grants: Vec<OwnerGrant>,
+        endpoint_id: EndpointId,
+    },
+    /// A "👍 received" reaction: `endpoint_id` finished downloading
+    /// `sender`'s offer `offer_id` of blob `hash`. Only the sender does
+    /// anything with it, listing who has fetched the file in the offer's
+    /// transfer details.
+    FileReceived {
+        nickname: String,
+        endpoint_id: EndpointId,
+        hash: [u8; 32],
+        sender: EndpointId,
+        offer_id: MessageId,
+    },
+}
+
+// ── Join approvals ───────────────────────────────────────────────────────────
+//
+// In a knock-to-join room only the room's owners (its creator and whoever
+// they made co-owner) decide who gets in. Their decision must hold up when
+// relayed by anyone, so it's a token signed with an owner's key over the
+// room's topic and the member's ID: a token for one room is useless in
+// another, and nobody else can mint one.
+
+/// An owner's signed word that `endpoint_id` may take part in a room.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Approval {
+    pub endpoint_id: EndpointId,
+    signature: Signature,
+}
+
+impl Approval {
+    /// The bytes an approval signs.
+    fn signed_bytes(topic: &TopicId, member: &EndpointId) -> Vec<u8> {
+        let mut bytes = b"piper-chat approval ".to_vec();
+        bytes.extend_from_slice(topic.as_bytes());
+        bytes.extend_from_slice(member.as_bytes());
+        bytes
+    }
+
+    /// Approve `member` for the room `topic`, signing as one of its owners.
+    pub fn sign(owner: &SecretKey, topic: &TopicId, member: EndpointId) -> Self {
+        Self { endpoint_id: member, signature: owner.sign(&Self::signed_bytes(topic, &member)) }
+    }
+
+    /// Whether `owner` signed this approval for the room `topic`.
+    pub fn verify(&self, topic: &TopicId, owner: &EndpointId) -> bool {
+        owner.verify(&Self::signed_bytes(topic, &self.endpoint_id), &self.signature).is_ok()
+    }
+}
+
+/// The members `approvals` let into the room `topic`, keeping only those
+/// genuinely signed by one of its `owners`. An approval doesn't say who
+/// signed it, so each owner's key is tried in turn.
+pub fn verify_approvals(approvals: &[Approval], topic: &TopicId, owners: &[EndpointId]) -> Vec<EndpointId> {
+    approvals.iter().filter(|a| owners.iter().any(|o| a.verify(topic, o))).map(|a| a.endpoint_id).collect()
+}
+
+// ── Owner grants ─────────────────────────────────────────────────────────────
+//
+// The creator can share ownership, so letting people in outlives the
+// creator leaving. A grant is signed by an existing owner over the room's
+// topic and the new owner's ID; the owner set is whatever chain of grants
+// leads back to the creator named in the ticket.
+
+/// `granted_by`'s signed word that `owner` co-owns a room.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct OwnerGrant {
+    pub owner: EndpointId,
+    pub granted_by: EndpointId,
+    signature: Signature,
+}
+
+impl OwnerGrant {
+    /// The bytes a grant signs.
+    fn signed_bytes(topic: &TopicId, owner: &EndpointId) -> Vec<u8> {
+        let mut bytes = b"piper-chat owner ".to_vec();
+        bytes.extend_from_slice(topic.as_bytes());
+        bytes.extend_from_slice(owner.as_bytes());
+        bytes
+    }
+
+    /// Make `owner` a co-owner of the room `topic`, signing as `granter`.
+    pub fn sign(granter: &SecretKey, topic: &TopicId, owner: EndpointId) -> Self {
+        Self { owner, granted_by: granter.public(), signature: granter.sign(&Self::signed_bytes(topic, &owner)) }
+    }
+
+    /// Whether `granted_by` really signed this grant for the room `topic`.
+    /// Says nothing about whether `granted_by` was an owner.
+    pub fn verify(&self, topic: &TopicId) -> bool {
+        self.granted_by.verify(&Self::signed_bytes(topic, &self.owner), &self.signature).is_ok()
+    }
+}
+
+/// The owners of the room `topic`: its `creator` first, then everyone a
+/// genuine grant from an owner reaches, in the order they were reached.
+pub fn verify_owners(grants: &[OwnerGrant], topic: &TopicId, creator: &EndpointId) -> Vec<EndpointId> {
+    let mut owners = vec![*creator];
+    // A grant can arrive before the one making its granter an owner, so
+    // keep sweeping until a pass adds nobody.
+    loop {
+        let before = owners.len();
+        for grant in grants {
+            if !owners.contains(&grant.owner) && owners.contains(&grant.granted_by) && grant.verify(topic) {
+                owners.push(grant.owner);
+            }
+        }
+        if owners.len() == before {
+            return owners;
+        }
+    }
+}
+
+// ── History entry (serializable storage format) ──────────────────────────────
+
+/// A single entry in the chat history log, serialized into a blob for
+/// history sync. Separate from the wire `Message` enum so we can evolve
+/// the storage format independently.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct HistoryEntry {
+    pub message_id: MessageId,
+    pub timestamp_ms: u64,
+    pub kind: HistoryEntryKind,
+}
+
+/// The payload of a history entry.
+#[derive(Serialize, Deserialize, Clone)]
+pub enum HistoryEntryKind {
+    Chat {
+        nickname: String,
+        text: String,
+    },
+    FileOffer {
+        nickname: String,
+        endpoint_id: EndpointId,
+        filename: String,
+        size: u64,
+        hash: [u8; 32],
+        mime_type: Option<String>,
+        target: Option<String>,
+    },
+    /// No longer written or acted on: it names neither the offer nor who
+    /// withdrew it (see `FileRevoke`). Kept so the variants after it keep
+    /// their tags.
+    FileRetract {
+        hash: [u8; 32],
+    },
+    System(String),
+    Spoiler {
+        nickname: String,
+        text: String,
+    },
+    ThreadChat {
+        nickname: String,
+        text: String,
+        thread_id: MessageId,
+    },
+    Reply {
+        nickname: String,
+        text: String,
+        reply_to: MessageId,
+    },
+    /// The wrapped key for the sealed `FileOffer` of blob `hash`, recorded
+    /// just before it.
+    FileKey {
+        hash: [u8; 32],
+        wrapped_key: Vec<u8>,
+    },
+    /// `endpoint_id` withdrew its offer `offer_id` of blob `hash` (the
+    /// offer's `message_id` in history). Only that one offer goes.
+    FileRevoke {
+        hash: [u8; 32],
+        endpoint_id: EndpointId,
+        offer_id: MessageId,
+    },
+}
+
+/// Longest nickname accepted from a peer, in characters. Longer names
+/// would push everything else off the peers pane and message lines.
+pub const MAX_NICKNAME: usize = 32;
+
+/// Whether `name` is acceptable as a nickname: not blank, and no longer
+/// than `MAX_NICKNAME`.
+pub fn valid_nickname(name: &str) -> bool {
+    !name.trim().is_empty() && name.chars().count() <= MAX_NICKNAME
+}
+
+/// Replace `s` with its scrubbed form if scrubbing changed it.
+fn scrub(s: &mut String, one_line: bool) {
+    let clean = if one_line { sanitize::line(s) } else { sanitize::text(s) };
+    if let Cow::Owned(clean) = clean {
+        *s = clean;
+    }
+}
+
+impl HistoryEntryKind {
+    /// Strip escape sequences and control characters from every field a
+    /// peer wrote (see `crate::sanitize`). Names stay on one line.
+    pub fn sanitize(&mut self) {
+        match self {
+            HistoryEntryKind::Chat { nickname, text }
+            | HistoryEntryKind::Spoiler { nickname, text }
+            | HistoryEntryKind::ThreadChat { nickname, text, .. }
+            | HistoryEntryKind::Reply { nickname, text, .. } => {
+                scrub(nickname, true);
+                scrub(text, false);
+            }
+            HistoryEntryKind::FileOffer { nickname, filename, mime_type, target, .. } => {
+                scrub(nickname, true);
+                scrub(filename, true);
+                mime_type.iter_mut().chain(target.iter_mut()).for_each(|s| scrub(s, true));
+            }
+            HistoryEntryKind::System(text) => scrub(text, false),
+            HistoryEntryKind::FileRetract { .. } | HistoryEntryKind::FileKey { .. } | HistoryEntryKind::FileRevoke { .. } => {}
+        }
+    }
+}
+
+impl Message {
+    /// Strip escape sequences and control characters from every field a
+    /// peer wrote (see `crate::sanitize`). `decode()` does this to every
+    /// message it returns.
+    pub fn sanitize(&mut self) {
+        match self {
+            Message::Chat { nickname, text, .. }
+            | Message::Spoiler { nickname, text, .. }
+            | Message::ThreadChat { nickname, text, .. }
+            | Message::Reply { nickname, text, .. } => {
+                scrub(nickname, true);
+                scrub(text, false);
+            }
+            Message::FileOffer { nickname, filename, mime_type, target, .. }
+            | Message::SealedFileOffer { nickname, filename, mime_type, target, .. } => {
+                scrub(nickname, true);
+                scrub(filename, true);
+                mime_type.iter_mut().chain(target.iter_mut()).for_each(|s| scrub(s, true));
+            }
+            Message::Timer { nickname, label, .. } => {
+                scrub(nickname, true);
+                scrub(label, true);
+            }
+            Message::Join { nickname, .. }
+            | Message::FileRetract { nickname, .. }
+            | Message::Here { nickname, .. }
+            | Message::JoinRequest { nickname, .. }
+            | Message::FileReceived { nickname, .. } => scrub(nickname, true),
+            Message::BackfillResponse { entries, .. } => entries.iter_mut().for_each(|e| e.kind.sanitize()),
+            Message::HistoryOffer { .. }
+            | Message::RollCall { .. }
+            | Message::Ack { .. }
+            | Message::BackfillRequest { .. }
+            | Message::FileRevoke { .. }
+            | Message::JoinApproval { .. }
+            | Message::JoinDenial { .. }
+            | Message::KeepAlive { .. }
+            | Message::GrantOwner { .. } => {}
+        }
+    }
+
+    /// The text and ID of a chat-style message (`Chat`, `Spoiler`,
+    /// `ThreadChat`, `Reply`): what message filters see and acks track.
+    pub fn chat_mut(&mut self) -> Option<(&mut String, MessageId)> {
+        match self {
+            Message::Chat { text, message_id, .. }
+            | Message::Spoiler { text, message_id, .. }
+            | Message::ThreadChat { text, message_id, .. }
+            | Message::Reply { text, message_id, .. } => Some((text, *message_id)),
+            _ => None,
+        }
+    }
+
+    /// The nickname a message carries, for variants that have one.
+    /// `decode()` rejects messages whose nickname isn't `valid_nickname`.
+    fn nickname(&self) -> Option<&str> {
+        match self {
+            Message::Join { nickname, .. }
+            | Message::Chat { nickname, .. }
+            | Message::FileOffer { nickname, .. }
+            | Message::FileRetract { nickname, .. }
+            | Message::Spoiler { nickname, .. }
+            | Message::Here { nickname, .. }
+            | Message::JoinRequest { nickname, .. }
+            | Message::ThreadChat { nickname, .. }
+            | Message::Reply { nickname, .. }
+            | Message::Timer { nickname, .. }
+            | Message::SealedFileOffer { nickname, .. }
+            | Message::FileReceived { nickname, .. } => Some(nickname),
+            Message::HistoryOffer { .. }
+            | Message::RollCall { .. }
+            | Message::Ack { .. }
+            | Message::BackfillRequest { .. }
+            | Message::BackfillResponse { .. }
+            | Message::FileRevoke { .. }
+            | Message::JoinApproval { .. }
+            | Message::JoinDenial { .. }
+            | Message::KeepAlive { .. }
+            | Message::GrantOwner { .. } => None,
+        }
+    }
+
+    /// The endpoint ID a message claims to come from, for variants that
+    /// carry one. `decode()` checks it against the signing key.
+    fn endpoint_id(&self) -> Option<EndpointId> {
+        match self {
+            Message::Join { endpoint_id, .. }
+            | Message::FileOffer { endpoint_id, .. }
+            | Message::HistoryOffer { endpoint_id, .. }
+            | Message::RollCall { endpoint_id, .. }
+            | Message::Here { endpoint_id, .. }
+            | Message::Ack { endpoint_id, .. }
+            | Message::BackfillRequest { endpoint_id, .. }
+            | Message::BackfillResponse { endpoint_id, .. }
+            | Message::FileRevoke { endpoint_id, .. }
+            | Message::JoinRequest { endpoint_id, .. }
+            | Message::JoinApproval { endpoint_id, .. }
+            | Message::JoinDenial { endpoint_id, .. }
+            | Message::KeepAlive { endpoint_id, .. }
+            | Message::SealedFileOffer { endpoint_id, .. }
+            | Message::GrantOwner { endpoint_id, .. }
+            | Message::FileReceived { endpoint_id, .. } => Some(*endpoint_id),
+            Message::Chat { .. }
+            | Message::FileRetract { .. }
+            | Message::Spoiler { .. }
+            | Message::ThreadChat { .. }
+            | Message::Reply { .. }
+            | Message::Timer { .. } => None,
+        }
+    }
+}
+
+// ── Signed envelope ──────────────────────────────────────────────────────────
+//
+// Gossip forwards messages hop by hop, so the neighbor that delivered a
+// message is not necessarily the peer that wrote it. The author signs the
+// encoded `Message` with their endpoint's secret key, and receivers verify
+// the signature against the public key carried alongside it.
+//
+// On the wire a message is one version byte followed by a postcard-encoded
+// `SignedMessage`. The version sits *outside* the envelope so a future
+// release can change the envelope itself without older peers misparsing it.
+
+/// Wire format version. Bump it when the envelope or `Message` encoding
+/// changes incompatibly. 2: offers and revocations carry an `offer_id`.
+pub const PROTOCOL_VERSION: u8 = 2;
+
+/// A `Message` signed by its author.
+#[derive(Serialize, Deserialize)]
+struct SignedMessage {
+    /// The author's endpoint ID (an Ed25519 public key).
+    from: EndpointId,
+    /// The postcard-encoded `Message`.
+    data: Vec<u8>,
+    /// Signature over `data` by `from`.
+    signature: Signature,
+}
+
+/// Encode, sign, and version-tag a message for broadcast.
+pub fn encode(secret_key: &SecretKey, msg: &Message) -> error::Result<Vec<u8>> {
+    let data = postcard::to_stdvec(msg).network()?;
+    let signature = secret_key.sign(&data);
+    let envelope = SignedMessage {
+        from: secret_key.public(),
+        data,
+        signature,
+    };
+    let mut bytes = vec![PROTOCOL_VERSION];
+    bytes.extend(postcard::to_stdvec(&envelope).network()?);
+    Ok(bytes)
+}
+
+/// Verify and decode a received message, returning its author.
+///
+/// Fails on an unknown version, a bad signature, or a message whose
+/// `endpoint_id` field names someone other than the signer.
+pub fn decode(bytes: &[u8]) -> Result<(EndpointId, Message)> {
+    let (&version, rest) = bytes.split_first().context("empty message")?;
+    if version != PROTOCOL_VERSION {
+        bail!("unsupported protocol version {version}");
+    }
+    let envelope: SignedMessage = postcard::from_bytes(rest)?;
+    envelope
+        .from
+        .verify(&envelope.data, &envelope.signature)
+        .context("bad signature")?;
+    let mut msg: Message = postcard::from_bytes(&envelope.data)?;
+    if let Some(claimed) = msg.endpoint_id()
+        && claimed != envelope.from
+    {
+        bail!(
+            "message claims to be from {} but is signed by {}",
+            claimed.fmt_short(),
+            envelope.from.fmt_short()
+        );
+    }
+    // Scrub first, so a name made only of control characters counts as blank.
+    msg.sanitize();
+    if msg.nickname().is_some_and(|name| !valid_nickname(name)) {
+        bail!("blank or overlong nickname from {}", envelope.from.fmt_short());
+    }
+    METRICS.message_in();
+    Ok((envelope.from, msg))
+}
+
+/// A typed handle for broadcasting `Message`s on a gossip topic.
+///
+/// iroh-gossip's `GossipSender` only deals in bytes. This wrapper owns the
+/// secret key so callers just hand it a `Message` and encoding, versioning,
+/// and signing happen in one place. `Clone` is cheap — both fields are
+/// handles/small keys.
+#[derive(Clone)]
+pub struct MessageSender {
+    inner: GossipSender,
+    secret_key: SecretKey,
+}
+
+impl MessageSender {
+    /// Wrap a gossip sender, signing outgoing messages with `secret_key`.
+    pub fn new(inner: GossipSender, secret_key: SecretKey) -> Self {
+        Self { inner, secret_key }
+    }
+
+    /// Encode and sign `msg` exactly as `broadcast` would, for delivery by
+    /// some other path (see `net::direct`).
+    pub fn encode(&self, msg: &Message) -> error::Result<Vec<u8>> {
+        encode(&self.secret_key, msg)
+    }
+
+    /// Broadcast a message to every peer in the topic.
+    pub async fn broadcast(&self, msg: &Message) -> error::Result<()> {
+        self.broadcast_encoded(self.encode(msg)?).await
+    }
+
+    /// Broadcast bytes already produced by `encode`, e.g. a message being
+    /// retried from a `net::retry::RetryQueue`.
+    pub async fn broadcast_encoded(&self, bytes: Vec<u8>) -> error::Result<()> {
+        self.inner.broadcast(bytes.into()).await.network()?;
+        METRICS.message_out();
+        Ok(())
+    }
+}
+// ── Tests ────────────────────────────────────────────────────────────────────
+//
+// `#[cfg(test)]` means this module is only compiled when running `cargo test`.
+// It won't bloat the release binary. Test modules conventionally live at the
+// bottom of the file they test and have access to all private items in the
+// parent module.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test that `Message::Chat` survives a postcard round-trip.
+    #[test]
+    fn message_chat_roundtrip() {
+        let mid = new_message_id();
+        let msg = Message::Chat {
+            nickname: "Alice".into(),
+            text: "hello!".into(),
+            message_id: mid,
+            timestamp_ms: 1700000000000,
+        };
+        let bytes = postcard::to_stdvec(&msg).unwrap();
+        let decoded: Message = postcard::from_bytes(&bytes).unwrap();
+        match decoded {
+            Message::Chat {
+                nickname,
+                text,
+                message_id,
+                timestamp_ms,
+            } => {
+                assert_eq!(nickname, "Alice");
+                assert_eq!(text, "hello!");
+                assert_eq!(message_id, mid);
+                assert_eq!(timestamp_ms, 1700000000000);
+            }
+            _ => panic!("expected Chat variant"),
+        }
+    }
+
+    /// Test that `Message::Join` survives a postcard round-trip.
+    #[test]
+    fn message_join_roundtrip() {
+        let id = iroh::EndpointId::from_bytes(&[1u8; 32]).unwrap();
+        let msg = Message::Join {
+            nickname: "Bob".into(),
+            endpoint_id: id,
+        };
+        let bytes = postcard::to_stdvec(&msg).unwrap();
+        let decoded: Message = postcard::from_bytes(&bytes).unwrap();
+        match decoded {
+            Message::Join {
+                nickname,
+                endpoint_id,
+            } => {
+                assert_eq!(nickname, "Bob");
+                assert_eq!(endpoint_id, id);
+            }
+            _ => panic!("expected Join variant"),
+        }
+    }
+
+    /// Test that `Message::FileOffer` survives a postcard round-trip.
+    #[test]
+    fn message_file_offer_roundtrip() {
+        let id = iroh::EndpointId::from_bytes(&[3u8; 32]).unwrap();
+        let hash = [7u8; 32];
+        let mid = new_message_id();
+        let msg = Message::FileOffer {
+            nickname: "Alice".into(),
+            endpoint_id: id,
+            filename: "photo.png".into(),
+            size: 123456,
+            hash,
+            message_id: mid,
+            timestamp_ms: 1700000000000,
+            mime_type: Some("image/png".into()),
+            target: None,
+            offer_id: mid,
+        };
+        let bytes = postcard::to_stdvec(&msg).unwrap();
+        let decoded: Message = postcard::from_bytes(&bytes).unwrap();
+        match decoded {
+            Message::FileOffer {
+                nickname,
+                endpoint_id,
+                filename,
+                size,
+                hash: h,
+                message_id,
+                timestamp_ms,
+                mime_type,
+                target,
+                offer_id,
+            } => {
+                assert_eq!(nickname, "Alice");
+                assert_eq!(endpoint_id, id);
+                assert_eq!(filename, "photo.png");
+                assert_eq!(size, 123456);
+                assert_eq!(h, hash);
+                assert_eq!(message_id, mid);
+                assert_eq!(timestamp_ms, 1700000000000);
+                assert_eq!(mime_type, Some("image/png".into()));
+                assert_eq!(target, None);
+                assert_eq!(offer_id, mid);
+            }
+            _ => panic!("expected FileOffer variant"),
+        }
+    }
+
+    /// Test that `Message::HistoryOffer` survives a postcard round-trip.
+    #[test]
+    fn message_history_offer_roundtrip() {
+        let id = iroh::EndpointId::from_bytes(&[1u8; 32]).unwrap();
+        let hash = [9u8; 32];
+        let msg = Message::HistoryOffer {
+            message_count: 42,
+            oldest_timestamp_ms: 1700000000000,
+            newest_timestamp_ms: 1700000060000,
+            hash,
+            endpoint_id: id,
+        };
+        let bytes = postcard::to_stdvec(&msg).unwrap();
+        let decoded: Message = postcard::from_bytes(&bytes).unwrap();
+        match decoded {
+            Message::HistoryOffer {
+                message_count,
+                oldest_timestamp_ms,
+                newest_timestamp_ms,
+                hash: h,
+                endpoint_id,
+            } => {
+                assert_eq!(message_count, 42);
+                assert_eq!(oldest_timestamp_ms, 1700000000000);
+                assert_eq!(newest_timestamp_ms, 1700000060000);
+                assert_eq!(h, hash);
+                assert_eq!(endpoint_id, id);
+            }
+            _ => panic!("expected HistoryOffer variant"),
+        }
+    }
+
+    /// Test that `Message::Spoiler` survives a postcard round-trip.
+    #[test]
+    fn message_spoiler_roundtrip() {
+        let mid = new_message_id();
+        let msg = Message::Spoiler {
+            nickname: "Alice".into(),
+            text: "the butler did it".into(),
+            message_id: mid,
+            timestamp_ms: 1700000000000,
+        };
+        let bytes = postcard::to_stdvec(&msg).unwrap();
+        let decoded: Message = postcard::from_bytes(&bytes).unwrap();
+        match decoded {
+            Message::Spoiler { nickname, text, message_id, .. } => {
+                assert_eq!(nickname, "Alice");
+                assert_eq!(text, "the butler did it");
+                assert_eq!(message_id, mid);
+            }
+            _ => panic!("expected Spoiler variant"),
+        }
+    }
+
+    /// Test that `Message::RollCall` and `Message::Here` survive a round-trip.
+    #[test]
+    fn message_roll_call_roundtrip() {
+        let id = iroh::EndpointId::from_bytes(&[3u8; 32]).unwrap();
+        let rid = new_message_id();
+        let call = Message::RollCall {
+            request_id: rid,
+            endpoint_id: id,
+            sent_ms: 42,
+        };
+        let bytes = postcard::to_stdvec(&call).unwrap();
+        assert!(matches!(
+            postcard::from_bytes::<Message>(&bytes).unwrap(),
+            Message::RollCall { request_id, sent_ms: 42, .. } if request_id == rid
+        ));
+        let here = Message::Here {
+            request_id: rid,
+            nickname: "Bob".into(),
+            endpoint_id: id,
+            echo_ms: 42,
+        };
+        let bytes = postcard::to_stdvec(&here).unwrap();
+        assert!(matches!(
+            postcard::from_bytes::<Message>(&bytes).unwrap(),
+            Message::Here { nickname, echo_ms: 42, .. } if nickname == "Bob"
+        ));
+    }
+
+    /// Test `HistoryEntry` postcard round-trip.
+    #[test]
+    fn history_entry_roundtrip() {
+        let mid = new_message_id();
+        let entry = HistoryEntry {
+            message_id: mid,
+            timestamp_ms: 1700000000000,
+            kind: HistoryEntryKind::Chat {
+                nickname: "Bob".into(),
+                text: "hi".into(),
+            },
+        };
+        let bytes = postcard::to_stdvec(&entry).unwrap();
+        let decoded: HistoryEntry = postcard::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded.message_id, mid);
+        assert_eq!(decoded.timestamp_ms, 1700000000000);
+        match decoded.kind {
+            HistoryEntryKind::Chat { nickname, text } => {
+                assert_eq!(nickname, "Bob");
+                assert_eq!(text, "hi");
+            }
+            _ => panic!("expected Chat kind"),
+        }
+    }
+
+    /// Test `Vec<HistoryEntry>` round-trip (the history blob format).
+    #[test]
+    fn history_vec_roundtrip() {
+        let entries = vec![
+            HistoryEntry {
+                message_id: new_message_id(),
+                timestamp_ms: 1000,
+                kind: HistoryEntryKind::System("room created".into()),
+            },
+            HistoryEntry {
+                message_id: new_message_id(),
+                timestamp_ms: 2000,
+                kind: HistoryEntryKind::Chat {
+                    nickname: "Alice".into(),
+                    text: "hello".into(),
+                },
+            },
+        ];
+        let bytes = postcard::to_stdvec(&entries).unwrap();
+        let decoded: Vec<HistoryEntry> = postcard::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded.len(), 2);
+        assert_eq!(decoded[0].timestamp_ms, 1000);
+        assert_eq!(decoded[1].timestamp_ms, 2000);
+    }
+
+    /// A signed message decodes back to the same content and author.
+    #[test]
+    fn signed_envelope_roundtrip() {
+        let key = SecretKey::from_bytes(&[7u8; 32]);
+        let msg = Message::Join {
+            nickname: "Alice".into(),
+            endpoint_id: key.public(),
+        };
+        let bytes = encode(&key, &msg).unwrap();
+        assert_eq!(bytes[0], PROTOCOL_VERSION);
+        let (from, decoded) = decode(&bytes).unwrap();
+        assert_eq!(from, key.public());
+        assert!(matches!(decoded, Message::Join { nickname, .. } if nickname == "Alice"));
+    }
+
+    /// Tampering with the payload or version is rejected.
+    #[test]
+    fn signed_envelope_rejects_tampering() {
+        let key = SecretKey::from_bytes(&[7u8; 32]);
+        let msg = Message::Chat {
+            nickname: "Alice".into(),
+            text: "hi".into(),
+            message_id: new_message_id(),
+            timestamp_ms: 0,
+        };
+        let bytes = encode(&key, &msg).unwrap();
+
+        let mut wrong_version = bytes.clone();
+        wrong_version[0] = PROTOCOL_VERSION + 1;
+        assert!(decode(&wrong_version).is_err());
+
+        // Flip a byte inside the signed payload ("hi" → "hj").
+        let mut tampered = bytes.clone();
+        let pos = tampered.windows(2).position(|w| w == b"hi").unwrap();
+        tampered[pos + 1] = b'j';
+        assert!(decode(&tampered).is_err());
+
+        assert!(decode(&[]).is_err());
+    }
+
+    /// A message naming another endpoint than its signer is rejected.
+    #[test]
+    fn signed_envelope_rejects_impersonation() {
+        let key = SecretKey::from_bytes(&[7u8; 32]);
+        let other = SecretKey::from_bytes(&[8u8; 32]).public();
+        let msg = Message::Join {
+            nickname: "Mallory".into(),
+            endpoint_id: other,
+        };
+        let bytes = encode(&key, &msg).unwrap();
+        assert!(decode(&bytes).is_err());
+    }
+
+    /// Blank and overlong nicknames never get past `decode()`.
+    #[test]
+    fn nicknames_are_checked_on_receive() {
+        let key = SecretKey::from_bytes(&[7u8; 32]);
+        let chat = |nickname: String| Message::Chat { nickname, text: "hi".into(), message_id: [0; 16], timestamp_ms: 0 };
+        assert!(decode(&encode(&key, &chat("Ana".into())).unwrap()).is_ok());
+        assert!(decode(&encode(&key, &chat("é".repeat(MAX_NICKNAME))).unwrap()).is_ok());
+        assert!(decode(&encode(&key, &chat("x".repeat(MAX_NICKNAME + 1))).unwrap()).is_err());
+        assert!(decode(&encode(&key, &chat(" \t ".into())).unwrap()).is_err());
+        assert!(decode(&encode(&key, &chat(String::new())).unwrap()).is_err());
+    }
+
+    /// Escape sequences in any peer-written field are gone after `decode()`,
+    /// and a name made only of them is rejected.
+    #[test]
+    fn decode_scrubs_control_characters() {
+        let key = SecretKey::from_bytes(&[7u8; 32]);
+        let chat = Message::Chat {
+            nickname: "Eve\u{1b}[2J".into(),
+            text: "line one\n\u{202E}gnp.exe\u{1b}]52;c;aGk=\u{7}".into(),
+            message_id: [0; 16],
+            timestamp_ms: 0,
+        };
+        let Ok((_, Message::Chat { nickname, text, .. })) = decode(&encode(&key, &chat).unwrap()) else {
+            panic!("expected a Chat");
+        };
+        assert_eq!((nickname.as_str(), text.as_str()), ("Eve", "line one\ngnp.exe"));
+
+        let join = Message::Join { nickname: "\u{1b}[31m\r".into(), endpoint_id: key.public() };
+        assert!(decode(&encode(&key, &join).unwrap()).is_err());
+    }
+
+    /// `Ack` names its sender, so a forged ack fails `decode()`.
+    #[test]
+    fn ack_must_match_signer() {
+        let alice = SecretKey::from_bytes(&[1u8; 32]);
+        let bob = SecretKey::from_bytes(&[3u8; 32]);
+        let ack = Message::Ack {
+            message_id: [4; 16],
+            endpoint_id: bob.public(),
+        };
+        assert!(decode(&encode(&bob, &ack).unwrap()).is_ok());
+        assert!(decode(&encode(&alice, &ack).unwrap()).is_err());
+    }
+
+    /// Only the offer's author can sign a `FileRevoke` for it.
+    #[test]
+    fn file_revoke_must_match_signer() {
+        let alice = SecretKey::from_bytes(&[1u8; 32]);
+        let mallory = SecretKey::from_bytes(&[9u8; 32]);
+        let revoke = Message::FileRevoke {
+            hash: [5; 32],
+            endpoint_id: alice.public(),
+            message_id: [6; 16],
+            timestamp_ms: 1_000,
+            at_ms: 61_000,
+            offer_id: [4; 16],
+        };
+        let Ok((_, Message::FileRevoke { at_ms, .. })) = decode(&encode(&alice, &revoke).unwrap()) else {
+            panic!("expected a FileRevoke");
+        };
+        assert_eq!(at_ms, 61_000);
+        assert!(decode(&encode(&mallory, &revoke).unwrap()).is_err());
+    }
+
+    /// Filters reach the text of every chat-style message, and nothing else.
+    #[test]
+    fn chat_mut_covers_chat_style_messages() {
+        let mut reply = Message::Reply { nickname: "a".into(), text: "hi".into(), message_id: [1; 16], timestamp_ms: 0, reply_to: [2; 16] };
+        let (text, id) = reply.chat_mut().unwrap();
+        text.push('!');
+        assert_eq!(id, [1; 16]);
+        assert!(matches!(reply, Message::Reply { text, .. } if text == "hi!"));
+        let mut keepalive = Message::KeepAlive { endpoint_id: iroh::SecretKey::from_bytes(&[1; 32]).public(), sent_ms: 0 };
+        assert!(keepalive.chat_mut().is_none());
+    }
+
+    /// A reaction names whoever fetched the file, so only they can sign it.
+    #[test]
+    fn file_received_must_match_signer() {
+        let alice = SecretKey::from_bytes(&[1u8; 32]);
+        let bob = SecretKey::from_bytes(&[3u8; 32]);
+        let received = Message::FileReceived {
+            nickname: "bob".into(),
+            endpoint_id: bob.public(),
+            hash: [5; 32],
+            sender: alice.public(),
+            offer_id: [4; 16],
+        };
+        let Ok((_, Message::FileReceived { sender, .. })) = decode(&encode(&bob, &received).unwrap()) else {
+            panic!("expected a FileReceived");
+        };
+        assert_eq!(sender, alice.public());
+        assert!(decode(&encode(&alice, &received).unwrap()).is_err());
+    }
+
+    /// Only the creator's approvals count, and only in the room they were
+    /// made for.
+    #[test]
+    fn approvals_are_bound_to_creator_and_room() {
+        let creator = SecretKey::from_bytes(&[1u8; 32]);
+        let mallory = SecretKey::from_bytes(&[9u8; 32]);
+        let bob = SecretKey::from_bytes(&[3u8; 32]).public();
+        let room = TopicId::from_bytes([5; 32]);
+        let other_room = TopicId::from_bytes([6; 32]);
+
+        let genuine = Approval::sign(&creator, &room, bob);
+        let forged = Approval::sign(&mallory, &room, mallory.public());
+        assert!(genuine.verify(&room, &creator.public()));
+        assert!(!genuine.verify(&other_room, &creator.public()));
+        assert!(!forged.verify(&room, &creator.public()));
+
+        // Relayed by anyone, the message still carries only what verifies.
+        let msg = Message::JoinApproval { approvals: vec![genuine, forged], endpoint_id: mallory.public() };
+        let Ok((_, Message::JoinApproval { approvals, .. })) = decode(&encode(&mallory, &msg).unwrap()) else {
+            panic!("expected a JoinApproval");
+        };
+        assert_eq!(verify_approvals(&approvals, &room, &[creator.public()]), vec![bob]);
+    }
+
+    /// Ownership chains back to the creator whatever order grants arrive
+    /// in; a grant from a non-owner, or for another room, adds nobody.
+    #[test]
+    fn owner_grants_chain_back_to_the_creator() {
+        let creator = SecretKey::from_bytes(&[1u8; 32]);
+        let alice = SecretKey::from_bytes(&[2u8; 32]);
+        let bob = SecretKey::from_bytes(&[3u8; 32]);
+        let mallory = SecretKey::from_bytes(&[9u8; 32]);
+        let room = TopicId::from_bytes([5; 32]);
+        let other_room = TopicId::from_bytes([6; 32]);
+
+        let grants = vec![
+            OwnerGrant::sign(&alice, &room, bob.public()),
+            OwnerGrant::sign(&creator, &room, alice.public()),
+            OwnerGrant::sign(&mallory, &room, mallory.public()),
+            OwnerGrant::sign(&creator, &other_room, mallory.public()),
+        ];
+        let owners = verify_owners(&grants, &room, &creator.public());
+        assert_eq!(owners, vec![creator.public(), alice.public(), bob.public()]);
+
+        // Approvals from any owner count.
+        let member = SecretKey::from_bytes(&[4u8; 32]).public();
+        let approvals = vec![Approval::sign(&bob, &room, member), Approval::sign(&mallory, &room, mallory.public())];
+        assert_eq!(verify_approvals(&approvals, &room, &owners), vec![member]);
+    }
+}
@@ -0,0 +1,108 @@
+//! Retry queue for gossip broadcasts that failed.
+//!
+//! `MessageSender::broadcast` fails when the gossip actor can't take the
+//! message — typically a transient network problem. Rather than ending the
+//! session, the event loop reports the failure in the chat and parks the
+//! already-signed bytes here. The tick branch takes whatever is due and
+//! broadcasts it again with `MessageSender::broadcast_encoded`; each further
+//! failure doubles the wait, and after `MAX_ATTEMPTS` the message is given up
+//! on (and the user told).
+//!
+//! Re-sending the same bytes keeps the message ID, so peers that did get an
+//! earlier attempt drop the duplicate.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Wait before the first retry; doubled after every failed attempt.
+pub const RETRY_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Broadcast attempts (including the first) before a message is dropped.
+pub const MAX_ATTEMPTS: u32 = 5;
+
+/// A failed broadcast waiting for another try.
+pub struct Retry {
+    /// The encoded, signed message.
+    pub bytes: Vec<u8>,
+    /// Attempts made so far.
+    pub attempts: u32,
+    due: Instant,
+}
+
+/// Failed broadcasts, oldest first.
+#[derive(Default)]
+pub struct RetryQueue {
+    queue: VecDeque<Retry>,
+}
+
+impl RetryQueue {
+    /// Queue `bytes` after their first broadcast failed at `now`.
+    pub fn push(&mut self, bytes: Vec<u8>, now: Instant) {
+        self.queue.push_back(Retry {
+            bytes,
+            attempts: 1,
+            due: now + RETRY_BACKOFF,
+        });
+    }
+
+    /// Take every message due for another attempt at `now`, oldest first.
+    pub fn take_due(&mut self, now: Instant) -> Vec<Retry> {
+        let (due, waiting): (Vec<_>, Vec<_>) = self.queue.drain(..).partition(|r| r.due <= now);
+        self.queue = waiting.into();
+        due
+    }
+
+    /// Put back a message whose retry failed at `now`, with a doubled wait.
+    /// Returns `false` (and drops it) once it has had `MAX_ATTEMPTS`.
+    pub fn failed(&mut self, mut retry: Retry, now: Instant) -> bool {
+        retry.attempts += 1;
+        if retry.attempts >= MAX_ATTEMPTS {
+            return false;
+        }
+        retry.due = now + RETRY_BACKOFF * 2u32.pow(retry.attempts - 1);
+        self.queue.push_back(retry);
+        true
+    }
+
+    /// Number of messages waiting to be retried.
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Whether nothing is waiting to be retried.
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retries_back_off_then_give_up() {
+        let mut queue = RetryQueue::default();
+        let start = Instant::now();
+        queue.push(vec![1], start);
+        assert!(queue.take_due(start).is_empty());
+
+        let mut now = start + RETRY_BACKOFF;
+        let mut waits = Vec::new();
+        loop {
+            let mut due = queue.take_due(now);
+            assert_eq!(due.len(), 1);
+            let retry = due.pop().unwrap();
+            assert_eq!(retry.bytes, [1]);
+            if !queue.failed(retry, now) {
+                break;
+            }
+            let wait = queue.queue[0].due - now;
+            waits.push(wait.as_secs());
+            now += wait;
+        }
+        assert_eq!(waits, [2, 4, 8]);
+        assert!(queue.is_empty());
+    }
+}
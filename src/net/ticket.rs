@@ -0,0 +1,458 @@
+//! Room tickets.
+//!
+//! A `ChatTicket` is the base32 string users copy-paste to join a room. It
+//! carries the gossip topic plus a few endpoint IDs to bootstrap from, and
+//! optionally the room's `RoomPolicy` (member cap, knock-to-join), a name,
+//! its creator, address hints and an expiry.
+//!
+//! ## Layout
+//!
+//! Tickets have changed shape twice, and every release must still read
+//! every shape, so each one extends the last rather than replacing it:
+//!
+//! ```text
+//! v1:  [topic_id][bootstrap]
+//!      [policy: Option<RoomPolicy>]                  (knock/cap releases)
+//! v2:  [topic_id][bootstrap][policy][version: u8][fields: Vec<Field>]
+//! ```
+//!
+//! Postcard ignores bytes past the end of what it was asked to read, so an
+//! older release reads a v2 ticket's topic, bootstrap and policy and never
+//! notices the rest. The optional v2 data travels as tagged `Field`s, each
+//! its own postcard blob: a reader skips tags it doesn't know, so adding a
+//! field later doesn't need a new version. The version tag is for a change
+//! that can't be made that way.
+
+// Standard library imports — `BTreeSet` is a sorted set backed by a B-tree.
+use std::collections::BTreeSet;
+
+// `EndpointId` is a unique cryptographic identifier for each peer node.
+use std::net::SocketAddr;
+
+use iroh::{EndpointAddr, EndpointId, RelayUrl, TransportAddr};
+// `TopicId` identifies a gossip topic (chat room) — a 32-byte hash.
+use iroh_gossip::proto::TopicId;
+// The `Ticket` trait from iroh provides base32 serialization for sharing
+// connection info out-of-band (e.g. pasting a string into another terminal).
+use iroh_tickets::Ticket;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result, ResultExt};
+use crate::net::proto::now_ms;
+use crate::sanitize;
+
+// ── Ticket ───────────────────────────────────────────────────────────────────
+//
+// A `ChatTicket` is shared out-of-band (copy-paste) to let others join a room.
+// It encodes the gossip topic ID plus a set of known peers to bootstrap from.
+
+/// Ticket containing everything needed to join a chat room.
+///
+/// `#[derive(Clone)]` generates a `.clone()` method that deep-copies the struct.
+/// This is needed because we modify a copy of the ticket (to add our own
+/// endpoint) without mutating the original.
+///
+/// Struct fields are `pub` because `main.rs` needs to read/write `bootstrap`
+/// and `topic_id` directly. In Rust, visibility is *module-scoped* by default —
+/// everything is private unless marked `pub`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChatTicket {
+    pub topic_id: TopicId,
+    /// `BTreeSet` keeps endpoint IDs sorted and deduplicated. Unlike `HashSet`,
+    /// iteration order is deterministic, which gives consistent serialization.
+    pub bootstrap: BTreeSet<EndpointId>,
+    /// Who created the room and who may get in, if the creator set any
+    /// limits (see `admission`). `None` is an open room.
+    pub policy: Option<RoomPolicy>,
+    /// A name for the room, shown in the chat pane's title.
+    pub name: Option<String>,
+    /// The endpoint that created the room. Rooms with a policy always know
+    /// it (`RoomPolicy::creator`); for open ones it's informational.
+    pub creator: Option<EndpointId>,
+    /// How to reach some of the bootstrap peers without address lookup:
+    /// `Node::ticket_for` adds the sharer's own relay and addresses, and
+    /// `Node::join` hands them to the endpoint before subscribing.
+    pub hints: Vec<AddrHint>,
+    /// When the ticket stops letting people in, ms since the epoch.
+    pub expires_ms: Option<u64>,
+}
+
+/// A room's admission rules, fixed by its creator when the room is made and
+/// carried in every ticket for it.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct RoomPolicy {
+    /// The creator's endpoint ID: the only key whose approvals count.
+    pub creator: EndpointId,
+    /// Most members at once, the creator included; `None` for no cap.
+    pub max_members: Option<u32>,
+    /// Newcomers must ask (`Message::JoinRequest`) and wait for the creator
+    /// to let them in.
+    pub knock: bool,
+}
+
+/// Where one endpoint could be reached when the ticket was made: its home
+/// relay and its direct addresses (LAN and public, as the endpoint saw them).
+///
+/// Normally peers find each other through iroh's address lookup, which
+/// needs a lookup service both can reach. Hints let a joiner dial straight
+/// away, and still connect on networks where that lookup is blocked.
+/// They're only hints — an address may have changed since — so the endpoint
+/// falls back to lookup when none of them answer.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct AddrHint {
+    pub id: EndpointId,
+    pub relays: Vec<RelayUrl>,
+    pub addrs: Vec<SocketAddr>,
+}
+
+/// Most direct addresses kept per hint, so an endpoint with many network
+/// interfaces doesn't blow up the ticket.
+pub const MAX_HINT_ADDRS: usize = 8;
+
+impl AddrHint {
+    /// The hint for `addr`, an endpoint's current address.
+    pub fn from_addr(addr: &EndpointAddr) -> Self {
+        Self {
+            id: addr.id,
+            relays: addr.relay_urls().cloned().collect(),
+            addrs: addr.ip_addrs().copied().take(MAX_HINT_ADDRS).collect(),
+        }
+    }
+
+    /// The hint as an address iroh can dial.
+    pub fn to_addr(&self) -> EndpointAddr {
+        let relays = self.relays.iter().cloned().map(TransportAddr::Relay);
+        EndpointAddr::from_parts(self.id, relays.chain(self.addrs.iter().copied().map(TransportAddr::Ip)))
+    }
+}
+
+/// The ticket layout from before `policy` existed: the prefix every later
+/// layout starts with.
+#[derive(Serialize, Deserialize)]
+struct TicketV1 {
+    topic_id: TopicId,
+    bootstrap: BTreeSet<EndpointId>,
+}
+
+/// The version written after the policy. Readers accept this or any later
+/// version, taking the fields they know from it.
+pub const TICKET_VERSION: u8 = 2;
+
+/// Longest room name kept from a ticket, in characters.
+pub const MAX_ROOM_NAME: usize = 48;
+
+/// One optional piece of v2 ticket data: a tag saying what it is, and its
+/// value postcard-encoded on its own so unknown tags can be skipped.
+#[derive(Serialize, Deserialize)]
+struct Field {
+    tag: u16,
+    value: Vec<u8>,
+}
+
+/// The tags `Field` knows. New ones are added at the end; a tag is never
+/// reused for something else.
+mod tag {
+    pub const NAME: u16 = 0;
+    pub const CREATOR: u16 = 1;
+    pub const HINTS: u16 = 2;
+    pub const EXPIRES: u16 = 3;
+}
+
+impl ChatTicket {
+    /// Create a ticket for a brand-new chat room with a random topic ID.
+    ///
+    /// `Self` is a type alias for the impl's type (`ChatTicket`). Using `Self`
+    /// means if you rename the struct, this code still compiles.
+    ///
+    /// `rand::random()` returns a `[u8; 32]` here — Rust infers the array size
+    /// from `TopicId::from_bytes`'s parameter type. Type inference in Rust
+    /// flows both forward (from arguments) and backward (from expected return).
+    pub fn new_random() -> Self {
+        Self::new(TopicId::from_bytes(rand::random()))
+    }
+
+    /// A ticket for `topic_id` with nothing else in it yet.
+    pub fn new(topic_id: TopicId) -> Self {
+        Self {
+            topic_id,
+            bootstrap: BTreeSet::new(),
+            policy: None,
+            name: None,
+            creator: None,
+            hints: Vec::new(),
+            expires_ms: None,
+        }
+    }
+
+    /// Add `hint`, replacing any older one for the same endpoint.
+    pub fn add_hint(&mut self, hint: AddrHint) {
+        self.hints.retain(|h| h.id != hint.id);
+        self.hints.push(hint);
+    }
+
+    /// Whether the ticket has an expiry that has passed by `now_ms`.
+    pub fn expired(&self, now_ms: u64) -> bool {
+        self.expires_ms.is_some_and(|at| at <= now_ms)
+    }
+
+    /// Parse a pasted base32 ticket, trimming surrounding whitespace.
+    ///
+    /// A thin wrapper over `Ticket::deserialize` whose error is the crate's
+    /// `Error::Ticket`, so callers can tell a bad paste from a network failure.
+    /// An expired ticket is refused the same way.
+    pub fn parse(s: &str) -> Result<Self> {
+        let ticket = <Self as Ticket>::deserialize(s.trim()).ticket()?;
+        if ticket.expired(now_ms()) {
+            return Err(Error::Ticket("this ticket has expired".into()));
+        }
+        Ok(ticket)
+    }
+
+    /// The private two-party room between us (`ours`) and `theirs`.
+    ///
+    /// The topic is a BLAKE3 hash of both IDs in sorted order, so either
+    /// side derives the same room without exchanging a ticket; the other
+    /// peer is the bootstrap, found through address lookup by its ID.
+    pub fn dm(ours: EndpointId, theirs: EndpointId) -> Self {
+        let (lo, hi) = if ours < theirs { (ours, theirs) } else { (theirs, ours) };
+        let mut seed = b"piper-chat dm ".to_vec();
+        seed.extend_from_slice(lo.as_bytes());
+        seed.extend_from_slice(hi.as_bytes());
+        let mut ticket = Self::new(TopicId::from_bytes(*iroh_blobs::Hash::new(&seed).as_bytes()));
+        ticket.bootstrap.insert(theirs);
+        ticket
+    }
+
+    /// The v2 fields this ticket has, in tag order.
+    fn fields(&self) -> Vec<Field> {
+        fn field(tag: u16, value: &impl Serialize) -> Field {
+            Field { tag, value: postcard::to_stdvec(value).unwrap() }
+        }
+        let mut fields = Vec::new();
+        if let Some(name) = &self.name {
+            fields.push(field(tag::NAME, name));
+        }
+        // A policy already names the creator; don't spend 32 bytes on it twice.
+        if let Some(creator) = self.creator.filter(|c| self.policy.is_none_or(|p| p.creator != *c)) {
+            fields.push(field(tag::CREATOR, &creator));
+        }
+        if !self.hints.is_empty() {
+            fields.push(field(tag::HINTS, &self.hints));
+        }
+        if let Some(at) = self.expires_ms {
+            fields.push(field(tag::EXPIRES, &at));
+        }
+        fields
+    }
+
+    /// Fill in the fields we know from a v2 ticket's list, skipping the rest.
+    /// The name is scrubbed like any text from a peer.
+    fn read_fields(&mut self, fields: Vec<Field>) -> postcard::Result<()> {
+        for Field { tag, value } in fields {
+            match tag {
+                tag::NAME => {
+                    let name: String = postcard::from_bytes(&value)?;
+                    let name: String = sanitize::line(&name).trim().chars().take(MAX_ROOM_NAME).collect();
+                    self.name = Some(name).filter(|n| !n.is_empty());
+                }
+                tag::CREATOR => self.creator = Some(postcard::from_bytes(&value)?),
+                tag::HINTS => self.hints = postcard::from_bytes(&value)?,
+                tag::EXPIRES => self.expires_ms = Some(postcard::from_bytes(&value)?),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Implement the iroh `Ticket` trait so `ChatTicket` can be serialized to a
+/// human-friendly base32 string (for copy-paste in the terminal).
+///
+/// Trait implementations in Rust are separate `impl` blocks from the type's
+/// inherent methods — this is how Rust achieves polymorphism without
+/// inheritance. Any type can implement any trait (subject to orphan rules).
+///
+/// `const KIND` is an *associated constant* — a value tied to the trait
+/// implementation rather than to any particular instance.
+impl Ticket for ChatTicket {
+    const KIND: &'static str = "chat";
+
+    /// Serialize to bytes using postcard (a compact, no-std-friendly binary format).
+    /// `.unwrap()` panics on failure — safe here because serialization of
+    /// known-good types never fails with postcard.
+    ///
+    /// Always the v2 layout (see the module docs), which older releases
+    /// read as far as they understand it.
+    fn to_bytes(&self) -> Vec<u8> {
+        let prefix = TicketV1 { topic_id: self.topic_id, bootstrap: self.bootstrap.clone() };
+        postcard::to_stdvec(&(prefix, self.policy, TICKET_VERSION, self.fields())).unwrap()
+    }
+
+    /// Deserialize from bytes. Returns a `ParseError` on invalid input.
+    /// The `?` operator converts postcard's error into `ParseError` automatically
+    /// because `ParseError` implements `From<postcard::Error>`.
+    ///
+    /// Reads each layout in turn: a ticket that ends after the bootstrap is
+    /// an original v1 one, one that ends after the policy is from before v2.
+    /// `take_from_bytes` decodes one value and hands back the bytes after it.
+    fn from_bytes(bytes: &[u8]) -> Result<Self, iroh_tickets::ParseError> {
+        let (TicketV1 { topic_id, bootstrap }, rest) = postcard::take_from_bytes(bytes)?;
+        let mut ticket = Self { bootstrap, ..Self::new(topic_id) };
+        if rest.is_empty() {
+            return Ok(ticket);
+        }
+        let (policy, rest): (Option<RoomPolicy>, _) = postcard::take_from_bytes(rest)?;
+        ticket.policy = policy;
+        ticket.creator = policy.map(|p| p.creator);
+        if rest.is_empty() {
+            return Ok(ticket);
+        }
+        let (version, rest): (u8, _) = postcard::take_from_bytes(rest)?;
+        if version < TICKET_VERSION {
+            return Err(postcard::Error::DeserializeBadEncoding.into());
+        }
+        let fields: Vec<Field> = postcard::from_bytes(rest)?;
+        ticket.read_fields(fields)?;
+        Ok(ticket)
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────────────────
+//
+// `#[cfg(test)]` means this module is only compiled when running `cargo test`.
+// It won't bloat the release binary. Test modules conventionally live at the
+// bottom of the file they test and have access to all private items in the
+// parent module.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test that a `ChatTicket` survives a serialize→deserialize round-trip.
+    ///
+    /// This verifies our `Ticket` trait implementation (to_bytes/from_bytes)
+    /// produces consistent results — critical since tickets are copy-pasted
+    /// between users.
+    #[test]
+    fn ticket_roundtrip() {
+        let original = ChatTicket::new_random();
+        let bytes = original.to_bytes();
+        let decoded = ChatTicket::from_bytes(&bytes).expect("should decode");
+        assert_eq!(original.topic_id, decoded.topic_id);
+        assert_eq!(original.bootstrap, decoded.bootstrap);
+    }
+
+    /// Test the human-friendly base32 serialization provided by the `Ticket`
+    /// trait's `serialize`/`deserialize` methods (which wrap to_bytes/from_bytes).
+    #[test]
+    fn ticket_base32_roundtrip() {
+        let original = ChatTicket::new_random();
+        let base32_str = <ChatTicket as Ticket>::serialize(&original);
+        let decoded =
+            <ChatTicket as Ticket>::deserialize(&base32_str).expect("should decode base32");
+        assert_eq!(original.topic_id, decoded.topic_id);
+    }
+
+    /// Verify that invalid base32 strings produce an error rather than panicking.
+    #[test]
+    fn ticket_deserialize_invalid() {
+        let result = <ChatTicket as Ticket>::deserialize("not-a-valid-ticket");
+        assert!(result.is_err());
+        let err = ChatTicket::parse("not-a-valid-ticket").expect_err("should fail");
+        assert!(matches!(err, crate::error::Error::Ticket(_)));
+        assert!(!err.is_retryable());
+    }
+
+    /// Both sides of a DM derive the same topic; different pairs don't.
+    #[test]
+    fn dm_topic_is_symmetric() {
+        let a = EndpointId::from_bytes(&[1u8; 32]).unwrap();
+        let b = EndpointId::from_bytes(&[3u8; 32]).unwrap();
+        let ab = ChatTicket::dm(a, b);
+        let ba = ChatTicket::dm(b, a);
+        assert_eq!(ab.topic_id, ba.topic_id);
+        assert_eq!(ab.bootstrap, BTreeSet::from([b]));
+        assert_eq!(ba.bootstrap, BTreeSet::from([a]));
+        assert_ne!(ChatTicket::dm(a, a).topic_id, ab.topic_id);
+    }
+
+    /// The ticket layout of releases that knew about policies but not v2.
+    #[derive(Serialize, Deserialize)]
+    struct TicketWithPolicy {
+        topic_id: TopicId,
+        bootstrap: BTreeSet<EndpointId>,
+        policy: Option<RoomPolicy>,
+    }
+
+    /// A v2 ticket survives the round trip, and both older layouts still
+    /// get the topic, bootstrap and policy out of one.
+    #[test]
+    fn ticket_v2_roundtrip_and_old_readers() {
+        let creator = EndpointId::from_bytes(&[1u8; 32]).unwrap();
+        let policy = RoomPolicy { creator, max_members: Some(4), knock: true };
+        let mut ticket = ChatTicket::new_random();
+        ticket.bootstrap.insert(creator);
+        ticket.policy = Some(policy);
+        ticket.creator = Some(creator);
+        ticket.name = Some("book club".into());
+        ticket.hints = vec![AddrHint {
+            id: creator,
+            relays: vec!["https://relay.example.com".parse().unwrap()],
+            addrs: vec!["192.0.2.1:4433".parse().unwrap()],
+        }];
+        ticket.expires_ms = Some(u64::MAX);
+
+        let bytes = ticket.to_bytes();
+        assert_eq!(ChatTicket::from_bytes(&bytes).unwrap(), ticket);
+        let old: TicketV1 = postcard::from_bytes(&bytes).unwrap();
+        assert_eq!((old.topic_id, &old.bootstrap), (ticket.topic_id, &ticket.bootstrap));
+        let newer: TicketWithPolicy = postcard::from_bytes(&bytes).unwrap();
+        assert_eq!(newer.policy, Some(policy));
+    }
+
+    /// Tickets written by older releases still read, with no v2 fields.
+    #[test]
+    fn old_layouts_still_read() {
+        let creator = EndpointId::from_bytes(&[1u8; 32]).unwrap();
+        let topic_id = TopicId::from_bytes([3; 32]);
+        let bootstrap = BTreeSet::from([creator]);
+
+        let v1 = postcard::to_stdvec(&TicketV1 { topic_id, bootstrap: bootstrap.clone() }).unwrap();
+        let ticket = ChatTicket::from_bytes(&v1).unwrap();
+        assert_eq!(ticket, ChatTicket { bootstrap: bootstrap.clone(), ..ChatTicket::new(topic_id) });
+
+        let policy = RoomPolicy { creator, max_members: None, knock: true };
+        let with_policy = postcard::to_stdvec(&TicketWithPolicy { topic_id, bootstrap, policy: Some(policy) }).unwrap();
+        let ticket = ChatTicket::from_bytes(&with_policy).unwrap();
+        assert_eq!((ticket.policy, ticket.creator, ticket.name), (Some(policy), Some(creator), None));
+    }
+
+    /// Tags from a newer release are skipped; a known one that doesn't
+    /// decode fails the ticket.
+    #[test]
+    fn unknown_fields_are_skipped() {
+        let ticket = ChatTicket::new(TopicId::from_bytes([3; 32]));
+        let prefix = TicketV1 { topic_id: ticket.topic_id, bootstrap: BTreeSet::new() };
+        let future = vec![Field { tag: 99, value: vec![1, 2, 3] }, Field { tag: tag::EXPIRES, value: vec![42] }];
+        let bytes = postcard::to_stdvec(&(&prefix, None::<RoomPolicy>, TICKET_VERSION + 1, future)).unwrap();
+        assert_eq!(ChatTicket::from_bytes(&bytes).unwrap().expires_ms, Some(42));
+
+        let bad = vec![Field { tag: tag::CREATOR, value: vec![1, 2, 3] }];
+        let bytes = postcard::to_stdvec(&(&prefix, None::<RoomPolicy>, TICKET_VERSION, bad)).unwrap();
+        assert!(ChatTicket::from_bytes(&bytes).is_err());
+    }
+
+    /// Room names are scrubbed and clamped; expired tickets don't parse.
+    #[test]
+    fn names_are_scrubbed_and_expiry_is_enforced() {
+        let mut ticket = ChatTicket::new(TopicId::from_bytes([3; 32]));
+        ticket.name = Some(format!("\u{1b}[2J{}", "x".repeat(100)));
+        let read = ChatTicket::from_bytes(&ticket.to_bytes()).unwrap();
+        assert_eq!(read.name, Some("x".repeat(MAX_ROOM_NAME)));
+
+        ticket.expires_ms = Some(1);
+        assert!(ticket.expired(2) && !ticket.expired(0));
+        let err = ChatTicket::parse(&ticket.serialize()).expect_err("expired");
+        assert!(matches!(err, crate::error::Error::Ticket(_)));
+    }
+}
@@ -0,0 +1,155 @@
+//! Connection tracking.
+//!
+//! Iroh connections can be "direct" (UDP hole-punched) or "relayed" through a
+//! DERP server. We track which type each peer uses for the peers panel.
+//!
+//! There is no shared tracker state here: the UI tick asks the endpoint
+//! directly and stores the answer in `App.peers`, which only the event loop
+//! touches. With no lock there is nothing to poison or contend on; the one
+//! thing that can still hold up a tick is the endpoint being slow to answer,
+//! so each query is bounded by `QUERY_TIMEOUT`. The tick queries all peers
+//! concurrently, so a tick waits at most one `QUERY_TIMEOUT` in total no
+//! matter how many peers are in the room.
+//!
+//! Alongside the type we keep the address a direct path uses, so `/map`
+//! and `/net` can say whether it runs over IPv4 or IPv6. `Reachability`
+//! is the other half of `/net`: which families our own endpoint could
+//! reach the internet over, from iroh's latest network report.
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use iroh::{Endpoint, EndpointId, NetReport, RelayUrl};
+
+/// How long the UI tick waits for the endpoint to report on a peer before
+/// giving up until the next tick. Peers are queried together, so this is
+/// also the most the whole tick waits.
+pub const QUERY_TIMEOUT: Duration = Duration::from_millis(20);
+
+/// Whether a peer connection is direct (IP), relayed, or not yet determined.
+///
+/// Iroh's QUIC connections start as relayed (through a DERP relay server) and
+/// may upgrade to direct (UDP hole-punched) once both peers discover each other's
+/// public IP. This enum tracks the current state for display in the peers panel.
+pub enum ConnType {
+    /// Connection type not yet determined (peer just connected).
+    Unknown,
+    /// Direct UDP connection — lowest latency, no relay overhead.
+    Direct,
+    /// Traffic is being relayed through a DERP server — higher latency but
+    /// works even when both peers are behind restrictive NATs.
+    Relay,
+    /// The local user's own entry — displayed as `[you]` in the peers panel.
+    You,
+}
+
+/// Display information about a connected peer.
+///
+/// This struct bundles the peer's display name with their connection type.
+/// It's stored in `App.peers` (a `BTreeMap<EndpointId, PeerInfo>`) and
+/// rendered in the peers sidebar.
+pub struct PeerInfo {
+    /// Display name — either their chosen nickname (after receiving a Join message)
+    /// or a short hex prefix of their endpoint ID (before they identify themselves).
+    pub name: String,
+    /// Current connection type — updated periodically by querying live
+    /// connection info from the iroh `Endpoint`.
+    pub conn_type: ConnType,
+    /// Round-trip time measured by the last roll call this peer answered
+    /// (`/who`, or the pings the `/map` overlay sends while open).
+    pub rtt_ms: Option<u64>,
+    /// The address a direct connection currently uses (`None` unless
+    /// `conn_type` is `Direct`).
+    pub addr: Option<SocketAddr>,
+}
+
+/// "IPv4" or "IPv6". An IPv4 address mapped into IPv6 (`::ffff:a.b.c.d`,
+/// what a dual-stack socket reports) counts as IPv4, since that's what
+/// goes over the wire.
+pub fn family(addr: &SocketAddr) -> &'static str {
+    if addr.ip().to_canonical().is_ipv4() { "IPv4" } else { "IPv6" }
+}
+
+/// What one address family could do in the last network report.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Family {
+    /// A UDP round trip to a relay's address-discovery service worked.
+    pub udp: bool,
+    /// Our address as the relay saw it.
+    pub public: Option<SocketAddr>,
+}
+
+/// Which address families our endpoint can use, from `Endpoint::net_report`.
+/// iroh probes both families against the relays on startup and when the
+/// network changes.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Reachability {
+    pub v4: Family,
+    pub v6: Family,
+    /// The relay with the lowest latency, which peers reach us through.
+    pub relay: Option<RelayUrl>,
+}
+
+impl Reachability {
+    pub fn from_report(report: &NetReport) -> Self {
+        Self {
+            v4: Family { udp: report.udp_v4, public: report.global_v4.map(SocketAddr::V4) },
+            v6: Family { udp: report.udp_v6, public: report.global_v6.map(SocketAddr::V6) },
+            relay: report.preferred_relay.clone(),
+        }
+    }
+
+    /// The endpoint's latest report, or `None` before the first one finishes
+    /// (a few seconds after startup).
+    pub fn of(endpoint: &Endpoint) -> Option<Self> {
+        use iroh::Watcher;
+        endpoint.net_report().get().as_ref().map(Self::from_report)
+    }
+}
+
+
+/// Query the endpoint for how we are currently connected to `id`, and
+/// through which address if the connection is direct.
+///
+/// A peer can have multiple active addresses (relay + direct). We prefer
+/// direct (IP) if any active address is direct. Returns `None` if the
+/// endpoint doesn't answer within `QUERY_TIMEOUT`, so the caller can keep
+/// showing what it knew rather than flickering to unknown.
+pub async fn path(endpoint: &Endpoint, id: EndpointId) -> Option<(ConnType, Option<SocketAddr>)> {
+    use iroh::TransportAddr;
+    use iroh::endpoint::TransportAddrUsage;
+    let info = tokio::time::timeout(QUERY_TIMEOUT, endpoint.remote_info(id)).await.ok()?;
+    let Some(info) = info else {
+        return Some((ConnType::Unknown, None));
+    };
+    let mut has_relay = false;
+    let mut direct = None;
+    for a in info
+        .addrs()
+        .filter(|a| matches!(a.usage(), TransportAddrUsage::Active))
+    {
+        match a.addr() {
+            TransportAddr::Ip(addr) => direct = direct.or(Some(*addr)),
+            _ => has_relay = true,
+        }
+    }
+    Some(match direct {
+        Some(addr) => (ConnType::Direct, Some(addr)),
+        None if has_relay => (ConnType::Relay, None),
+        None => (ConnType::Unknown, None),
+    })
+}
+
+// ── Tests ────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mapped_addresses_count_as_ipv4() {
+        assert_eq!(family(&"203.0.113.7:4433".parse().unwrap()), "IPv4");
+        assert_eq!(family(&"[2001:db8::1]:4433".parse().unwrap()), "IPv6");
+        assert_eq!(family(&"[::ffff:203.0.113.7]:4433".parse().unwrap()), "IPv4");
+    }
+}
@@ -0,0 +1,60 @@
+//! Noticing that the machine slept.
+//!
+//! A laptop lid closed for an hour leaves every connection dead, but
+//! nothing in the event loop hears about it: gossip only reports a neighbor
+//! down once a connection times out, so the peers pane keeps showing
+//! `[direct]` peers that are long gone. The status clock ticks once a
+//! second, so a tick that finds much more than a second passed means the
+//! process wasn't running — suspended, or stopped with Ctrl+Z.
+//!
+//! Both clocks are checked because they disagree about sleep: the wall
+//! clock always moves on, while `Instant` stands still during suspend on
+//! Linux and macOS (and keeps going on Windows). A large step in either is
+//! taken as a wake-up. A wall clock set forward by hand looks the same,
+//! which only costs an unneeded reconnect.
+
+use std::time::{Duration, Instant};
+
+/// A gap between ticks this long means we were asleep, not just busy.
+pub const SLEEP_GAP: Duration = Duration::from_secs(15);
+
+/// Both clocks at the last tick.
+pub struct WakeDetector {
+    wall_ms: u64,
+    mono: Instant,
+}
+
+impl WakeDetector {
+    pub fn new(wall_ms: u64, mono: Instant) -> Self {
+        Self { wall_ms, mono }
+    }
+
+    /// Record a tick at `wall_ms` / `mono`. Returns how long we were away
+    /// if the gap since the last tick was `SLEEP_GAP` or more.
+    pub fn check(&mut self, wall_ms: u64, mono: Instant) -> Option<Duration> {
+        let wall = Duration::from_millis(wall_ms.saturating_sub(self.wall_ms));
+        let gap = wall.max(mono.duration_since(self.mono));
+        (self.wall_ms, self.mono) = (wall_ms, mono);
+        (gap >= SLEEP_GAP).then_some(gap)
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_gap_in_either_clock_is_a_wake_up() {
+        let start = Instant::now();
+        let mut wake = WakeDetector::new(1_000_000, start);
+        assert_eq!(wake.check(1_001_000, start + Duration::from_secs(1)), None);
+        // Suspended on Linux: the wall clock jumped, `Instant` barely moved.
+        let slept = wake.check(4_601_000, start + Duration::from_secs(2));
+        assert_eq!(slept, Some(Duration::from_secs(3600)));
+        // On Windows `Instant` moves too; a wall clock set back is ignored.
+        assert_eq!(wake.check(4_500_000, start + Duration::from_secs(62)), Some(Duration::from_secs(60)));
+        assert_eq!(wake.check(4_501_000, start + Duration::from_secs(63)), None);
+    }
+}
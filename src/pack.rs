@@ -0,0 +1,270 @@
+//! Pack a folder into a tar archive on the fly, for sharing as one file.
+//!
+//! iroh-blobs can send a whole directory as a *collection* (a hash sequence
+//! of its files), but a peer then needs collection support to rebuild it.
+//! A tar archive is one plain blob that every peer can download, and that
+//! every OS can unpack. We write the archive ourselves rather than pull in
+//! an archiver crate: the ustar format is a 512-byte header per entry, the
+//! file's bytes padded to 512, and two zero blocks at the end.
+//!
+//! The archive is never written to disk. `stream()` reads the files on a
+//! blocking thread and yields the archive in chunks, which
+//! `node::share_folder` feeds straight into the blob store. Because every
+//! header records its file's size up front, the total length is known
+//! before packing starts — which is what makes a progress bar possible.
+
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use bytes::Bytes;
+use n0_future::Stream;
+use tokio::sync::mpsc;
+
+/// The tar block size; headers and padded data are multiples of it.
+const BLOCK: usize = 512;
+
+/// How much of a file is read per chunk.
+const CHUNK: usize = 64 * 1024;
+
+/// One file or directory to put in the archive.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    /// Where it is on disk.
+    pub path: PathBuf,
+    /// Its name inside the archive, `/`-separated and starting with the
+    /// packed folder's own name; directories end in `/`.
+    pub name: String,
+    /// File size in bytes (0 for directories).
+    pub size: u64,
+    /// Modification time, seconds since the epoch.
+    pub mtime: u64,
+    pub is_dir: bool,
+}
+
+/// The archive's file name for the folder at `dir`: `photos` → `photos.tar`.
+pub fn archive_name(dir: &Path) -> String {
+    let name = dir.file_name().map(|n| n.to_string_lossy().into_owned());
+    format!("{}.tar", name.unwrap_or_else(|| "folder".to_string()))
+}
+
+/// Everything under `dir`, the folder itself first, in a stable (sorted)
+/// order. Symlinks are skipped rather than followed, so a link can't pull
+/// in files from outside the folder.
+pub fn scan(dir: &Path) -> io::Result<Vec<Entry>> {
+    let root = dir.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_else(|| "folder".to_string());
+    let mut entries = Vec::new();
+    visit(dir, &root, &mut entries)?;
+    Ok(entries)
+}
+
+fn visit(dir: &Path, name: &str, entries: &mut Vec<Entry>) -> io::Result<()> {
+    entries.push(Entry { path: dir.to_path_buf(), name: format!("{name}/"), size: 0, mtime: mtime(&fs::metadata(dir)?), is_dir: true });
+    let mut children: Vec<_> = fs::read_dir(dir)?.collect::<io::Result<_>>()?;
+    children.sort_by_key(|c| c.file_name());
+    for child in children {
+        let meta = fs::symlink_metadata(child.path())?;
+        let child_name = format!("{name}/{}", child.file_name().to_string_lossy());
+        if meta.is_dir() {
+            visit(&child.path(), &child_name, entries)?;
+        } else if meta.is_file() {
+            entries.push(Entry { path: child.path(), name: child_name, size: meta.len(), mtime: mtime(&meta), is_dir: false });
+        }
+    }
+    Ok(())
+}
+
+fn mtime(meta: &fs::Metadata) -> u64 {
+    meta.modified().ok().and_then(|t| t.duration_since(UNIX_EPOCH).ok()).map_or(0, |d| d.as_secs())
+}
+
+/// The exact length of the archive `entries` pack into.
+pub fn archive_size(entries: &[Entry]) -> u64 {
+    let data: u64 = entries.iter().map(|e| BLOCK as u64 + padded(e.size)).sum();
+    data + 2 * BLOCK as u64
+}
+
+/// `size` rounded up to a whole number of blocks.
+fn padded(size: u64) -> u64 {
+    size.div_ceil(BLOCK as u64) * BLOCK as u64
+}
+
+/// The ustar header block for `entry`.
+///
+/// Names longer than the 100-byte name field are split at a `/` into the
+/// 155-byte prefix field and the name field; a name that can't be split
+/// that way is an error.
+pub fn header(entry: &Entry) -> io::Result<[u8; BLOCK]> {
+    let mut block = [0u8; BLOCK];
+    let (prefix, name) = split_name(&entry.name)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("path too long for tar: {}", entry.name)))?;
+    block[..name.len()].copy_from_slice(name.as_bytes());
+    block[345..345 + prefix.len()].copy_from_slice(prefix.as_bytes());
+    octal(&mut block[100..108], if entry.is_dir { 0o755 } else { 0o644 });
+    octal(&mut block[108..116], 0); // uid
+    octal(&mut block[116..124], 0); // gid
+    size_field(&mut block[124..136], entry.size);
+    octal(&mut block[136..148], entry.mtime);
+    block[156] = if entry.is_dir { b'5' } else { b'0' };
+    block[257..263].copy_from_slice(b"ustar\0");
+    block[263..265].copy_from_slice(b"00");
+    // The checksum is the byte sum of the header with its own field read
+    // as spaces.
+    block[148..156].fill(b' ');
+    let sum: u32 = block.iter().map(|&b| b as u32).sum();
+    octal(&mut block[148..155], sum as u64);
+    Ok(block)
+}
+
+/// Split a name into ustar's (prefix, name) fields.
+fn split_name(name: &str) -> Option<(&str, &str)> {
+    if name.len() <= 100 {
+        return Some(("", name));
+    }
+    // Split at the first `/` that leaves a short enough name; a trailing
+    // `/` (directories) can't be the split point.
+    name[..name.len() - 1]
+        .match_indices('/')
+        .map(|(at, _)| (&name[..at], &name[at + 1..]))
+        .find(|(prefix, rest)| prefix.len() <= 155 && rest.len() <= 100)
+}
+
+/// Write `value` as zero-padded octal digits, NUL-terminated.
+fn octal(field: &mut [u8], value: u64) {
+    let digits = format!("{value:0width$o}", width = field.len() - 1);
+    field[..digits.len()].copy_from_slice(digits.as_bytes());
+    field[digits.len()] = 0;
+}
+
+/// The size field, in octal when it fits (under 8 GiB) and otherwise in the
+/// GNU base-256 form: high bit set, then the value big-endian.
+fn size_field(field: &mut [u8], size: u64) {
+    if size < 1 << 33 {
+        octal(field, size);
+    } else {
+        field.fill(0);
+        field[0] = 0x80;
+        field[4..].copy_from_slice(&size.to_be_bytes());
+    }
+}
+
+/// Write the archive of `entries` through `out`, one chunk at a time.
+/// `progress` gets the bytes written so far after every chunk. Stops early
+/// (with success) if `out` returns `false`, i.e. nobody wants the rest.
+///
+/// Files are read up to the size recorded when they were scanned: a file
+/// that grew is cut off, one that shrank is padded with zeros, so the
+/// archive always matches its headers.
+pub fn write(entries: &[Entry], mut out: impl FnMut(Bytes) -> bool, mut progress: impl FnMut(u64)) -> io::Result<()> {
+    let mut written = 0;
+    let mut emit = |chunk: Vec<u8>| {
+        written += chunk.len() as u64;
+        progress(written);
+        out(chunk.into())
+    };
+    for entry in entries {
+        if !emit(header(entry)?.to_vec()) {
+            return Ok(());
+        }
+        if entry.is_dir {
+            continue;
+        }
+        let mut file = File::open(&entry.path)?.take(entry.size);
+        let mut left = padded(entry.size);
+        while left > 0 {
+            let mut chunk = vec![0u8; CHUNK.min(left as usize)];
+            let mut filled = 0;
+            while filled < chunk.len() {
+                match file.read(&mut chunk[filled..])? {
+                    0 => break,
+                    n => filled += n,
+                }
+            }
+            left -= chunk.len() as u64;
+            if !emit(chunk) {
+                return Ok(());
+            }
+        }
+    }
+    emit(vec![0u8; 2 * BLOCK]);
+    Ok(())
+}
+
+/// The archive of `entries` as a stream of chunks, packed on a blocking
+/// thread. `progress` is called with `(bytes_done, total_bytes)` from that
+/// thread as packing goes.
+pub fn stream(
+    entries: Vec<Entry>,
+    mut progress: impl FnMut(u64, u64) + Send + 'static,
+) -> impl Stream<Item = io::Result<Bytes>> + Send + Sync + 'static {
+    let (tx, rx) = mpsc::channel::<io::Result<Bytes>>(4);
+    tokio::task::spawn_blocking(move || {
+        let total = archive_size(&entries);
+        let result = write(&entries, |chunk| tx.blocking_send(Ok(chunk)).is_ok(), |done| progress(done, total));
+        if let Err(e) = result {
+            let _ = tx.blocking_send(Err(e));
+        }
+    });
+    n0_future::stream::unfold(rx, |mut rx| async move { rx.recv().await.map(|chunk| (chunk, rx)) })
+}
+
+// ── Tests ────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Read an octal header field back.
+    fn read_octal(field: &[u8]) -> u64 {
+        let digits = std::str::from_utf8(field).unwrap().trim_end_matches(['\0', ' ']);
+        u64::from_str_radix(digits, 8).unwrap()
+    }
+
+    #[test]
+    fn packs_a_folder_tree() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("photos");
+        fs::create_dir_all(root.join("2024")).unwrap();
+        fs::write(root.join("a.txt"), b"hello").unwrap();
+        fs::write(root.join("2024").join("b.bin"), vec![7u8; 600]).unwrap();
+
+        let entries = scan(&root).unwrap();
+        let names: Vec<&str> = entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["photos/", "photos/2024/", "photos/2024/b.bin", "photos/a.txt"]);
+        assert_eq!(archive_name(&root), "photos.tar");
+
+        let mut archive = Vec::new();
+        let mut last = 0;
+        write(&entries, |chunk| { archive.extend_from_slice(&chunk); true }, |done| last = done).unwrap();
+        assert_eq!(archive.len() as u64, archive_size(&entries));
+        assert_eq!(last, archive.len() as u64);
+
+        // The third header is b.bin: 600 bytes, padded to two blocks.
+        let at = 2 * BLOCK;
+        let block = &archive[at..at + BLOCK];
+        assert!(block.starts_with(b"photos/2024/b.bin\0"));
+        assert_eq!(&block[257..263], b"ustar\0");
+        assert_eq!(read_octal(&block[124..136]), 600);
+        let mut check = block.to_vec();
+        check[148..156].fill(b' ');
+        assert_eq!(read_octal(&block[148..155]), check.iter().map(|&b| b as u64).sum::<u64>());
+        assert_eq!(&archive[at + BLOCK..at + BLOCK + 600], &[7u8; 600][..]);
+        // Then a.txt, and two zero blocks to finish.
+        let at = at + 3 * BLOCK;
+        assert!(archive[at..].starts_with(b"photos/a.txt\0"));
+        assert_eq!(&archive[at + BLOCK..at + BLOCK + 5], b"hello");
+        assert!(archive[archive.len() - 2 * BLOCK..].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn long_names_use_the_prefix_field() {
+        let long = format!("{}/{}", "d".repeat(120), "f".repeat(90));
+        assert_eq!(split_name(&long), Some((&long[..120], &long[121..])));
+        assert_eq!(split_name("short"), Some(("", "short")));
+        assert_eq!(split_name(&"x".repeat(300)), None);
+        let entry = Entry { path: PathBuf::new(), name: long.clone(), size: 0, mtime: 0, is_dir: false };
+        let block = header(&entry).unwrap();
+        assert_eq!(&block[345..465], &long.as_bytes()[..120]);
+    }
+}
@@ -0,0 +1,264 @@
+//! Command palette (Ctrl+K): every action in one filterable list.
+//!
+//! The palette lists the slash commands (from `command::COMMANDS`), the
+//! chat keybindings, and the rooms in the archive. Typing filters it with
+//! a fuzzy match (the query's characters in order, anywhere in the label),
+//! best matches first; Enter picks the highlighted item.
+//!
+//! Picking doesn't run anything itself. It hands main a `Pick`, which main
+//! feeds back through the chat input so there's one code path per action:
+//!
+//! - `Run` — text sent as if typed and entered (`/who`, `/join <ticket>`)
+//! - `Edit` — text left in the input for the rest of its arguments (`/dm `)
+//! - `Key` — a key chord replayed in chat mode (Ctrl+T for the theme)
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::layout::Rect;
+use ratatui::style::{Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Clear, Paragraph};
+
+use crate::command::COMMANDS;
+use crate::i18n::Lang;
+use crate::t;
+use crate::theme::Theme;
+
+/// Rows of matches shown at once.
+const VISIBLE: usize = 10;
+
+/// What picking an item does.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pick {
+    Run(String),
+    Edit(String),
+    Key(KeyCode, KeyModifiers),
+}
+
+/// One line of the palette.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Item {
+    pub label: String,
+    /// Shown dimmed on the right: a shortcut or a room's summary.
+    pub hint: String,
+    pub pick: Pick,
+}
+
+/// What a key did to the palette.
+#[derive(Debug, PartialEq)]
+pub enum Outcome {
+    /// Still open.
+    Open,
+    Close,
+    Picked(Pick),
+}
+
+/// The open palette.
+#[derive(Debug)]
+pub struct Palette {
+    pub query: String,
+    /// Index into `matches()`.
+    pub selected: usize,
+    items: Vec<Item>,
+}
+
+/// How well `query` matches `text`, higher is better, or `None` if its
+/// characters don't all appear in order (ignoring case). Runs of adjacent
+/// matches and matches at the start of a word score extra.
+pub fn score(query: &str, text: &str) -> Option<u32> {
+    let text: Vec<char> = text.chars().flat_map(char::to_lowercase).collect();
+    let mut score = 0;
+    let mut at = 0;
+    let mut last = None;
+    for q in query.chars().flat_map(char::to_lowercase).filter(|c| !c.is_whitespace()) {
+        let i = at + text[at..].iter().position(|&c| c == q)?;
+        score += 1;
+        if last.is_some_and(|l| l + 1 == i) {
+            score += 3;
+        }
+        if i == 0 || !text[i - 1].is_alphanumeric() {
+            score += 2;
+        }
+        last = Some(i);
+        at = i + 1;
+    }
+    Some(score)
+}
+
+impl Palette {
+    pub fn new(items: Vec<Item>) -> Self {
+        Self { query: String::new(), selected: 0, items }
+    }
+
+    /// The items matching the query, best first; ties keep list order.
+    pub fn matches(&self) -> Vec<&Item> {
+        let mut scored: Vec<(u32, &Item)> = self.items.iter().filter_map(|i| Some((score(&self.query, &i.label)?, i))).collect();
+        scored.sort_by_key(|&(score, _)| std::cmp::Reverse(score));
+        scored.into_iter().map(|(_, i)| i).collect()
+    }
+
+    /// Handle a key while the palette is open.
+    pub fn key(&mut self, key: &KeyEvent) -> Outcome {
+        let count = self.matches().len();
+        match key.code {
+            KeyCode::Esc => return Outcome::Close,
+            KeyCode::Char('k') if key.modifiers.contains(KeyModifiers::CONTROL) => return Outcome::Close,
+            KeyCode::Enter => {
+                return match self.matches().get(self.selected) {
+                    Some(item) => Outcome::Picked(item.pick.clone()),
+                    None => Outcome::Open,
+                };
+            }
+            KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down => self.selected = (self.selected + 1).min(count.saturating_sub(1)),
+            KeyCode::Backspace => {
+                self.query.pop();
+                self.selected = 0;
+            }
+            KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                self.query.push(c);
+                self.selected = 0;
+            }
+            _ => {}
+        }
+        Outcome::Open
+    }
+}
+
+/// Everything the palette offers: commands, then keybindings, then
+/// `rooms` as (label, summary, ticket) to rejoin.
+pub fn items(lang: Lang, rooms: Vec<(String, String, String)>) -> Vec<Item> {
+    let mut items: Vec<Item> = COMMANDS
+        .iter()
+        .map(|c| {
+            // Commands that need nothing more run at once; the rest wait
+            // in the input for their arguments.
+            let pick = if c.min == 0 && c.usage == c.name { Pick::Run(c.name.into()) } else { Pick::Edit(format!("{} ", c.name)) };
+            Item { label: c.usage.into(), hint: String::new(), pick }
+        })
+        .collect();
+    let ctrl = |c| Pick::Key(KeyCode::Char(c), KeyModifiers::CONTROL);
+    let keys = [
+        ("palette.theme", "Ctrl+T", ctrl('t')),
+        ("palette.file_picker", "Ctrl+F", ctrl('f')),
+        ("palette.sort_peers", "Ctrl+O", ctrl('o')),
+        ("palette.copy_ticket", "Ctrl+Y", ctrl('y')),
+        ("palette.file_pane", "Tab", Pick::Key(KeyCode::Tab, KeyModifiers::NONE)),
+    ];
+    items.extend(keys.into_iter().map(|(label, hint, pick)| Item { label: lang.text(label).to_string(), hint: hint.into(), pick }));
+    items.extend(rooms.into_iter().map(|(label, summary, ticket)| Item {
+        label: t!(lang, "palette.room", label = label),
+        hint: summary,
+        pick: Pick::Run(format!("/join {ticket}")),
+    }));
+    items
+}
+
+/// Draw the palette near the top of the frame, over the chat.
+pub fn render(f: &mut ratatui::Frame, palette: Option<&Palette>, lang: Lang, theme: &Theme) {
+    let Some(palette) = palette else { return };
+
+    let area = f.area();
+    let width = 64.min(area.width);
+    let height = (VISIBLE as u16 + 4).min(area.height);
+    let card = Rect::new(area.width.saturating_sub(width) / 2, area.height.saturating_sub(height) / 4, width, height);
+    f.render_widget(Clear, card);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_set(theme.border_set())
+        .style(Style::default().bg(theme.bg))
+        .border_style(Style::default().fg(theme.border_focused))
+        .title(Line::styled(format!(" {} ", t!(lang, "palette.title")), Style::default().fg(theme.title).add_modifier(Modifier::BOLD)).centered())
+        .title_bottom(
+            Line::from(vec![
+                Span::styled(" Enter", Style::default().fg(theme.hint_key)),
+                Span::styled(format!(" {}  ", t!(lang, "palette.run")), Style::default().fg(theme.hint_text)),
+                Span::styled("Esc", Style::default().fg(theme.hint_key)),
+                Span::styled(format!(" {} ", t!(lang, "palette.close")), Style::default().fg(theme.hint_text)),
+            ])
+            .centered(),
+        );
+    let inner_width = width.saturating_sub(2) as usize;
+
+    let matches = palette.matches();
+    // Keep the selection in view.
+    let first = palette.selected.saturating_sub(VISIBLE - 1);
+    let mut lines = vec![
+        Line::from(vec![
+            Span::styled("> ", Style::default().fg(theme.input_prompt)),
+            Span::styled(palette.query.clone(), Style::default().fg(theme.text)),
+        ]),
+        Line::from(""),
+    ];
+    if matches.is_empty() {
+        lines.push(Line::styled(t!(lang, "palette.none"), Style::default().fg(theme.text_muted)));
+    }
+    for (i, item) in matches.iter().enumerate().skip(first).take(VISIBLE) {
+        let gap = inner_width.saturating_sub(item.label.chars().count() + item.hint.chars().count() + 2);
+        let (style, hint) = if i == palette.selected {
+            let selected = Style::default().fg(theme.accent_on_bg).bg(theme.accent_bg);
+            (selected, selected)
+        } else {
+            (Style::default().fg(theme.text), Style::default().fg(theme.text_dim))
+        };
+        lines.push(Line::from(vec![
+            Span::styled(format!(" {}{}", item.label, " ".repeat(gap)), style),
+            Span::styled(format!("{} ", item.hint), hint),
+        ]));
+    }
+    f.render_widget(Paragraph::new(lines).block(block), card);
+}
+
+// ── Tests ────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(palette: &mut Palette, code: KeyCode) -> Outcome {
+        palette.key(&KeyEvent::new(code, KeyModifiers::NONE))
+    }
+
+    #[test]
+    fn fuzzy_scores_prefer_runs_and_word_starts() {
+        assert!(score("exp", "/export").is_some());
+        assert_eq!(score("xe", "/export"), None);
+        assert!(score("theme", "Toggle dark/light theme") > score("theme", "/timer <t> then more"));
+        assert!(score("dm", "/dm <name>") > score("dm", "Toggle dark/light mode"));
+        assert_eq!(score("", "anything"), Some(0));
+    }
+
+    #[test]
+    fn typing_filters_and_enter_picks() {
+        let rooms = vec![("Bob, Carol".into(), "2h ago".into(), "chatabc".into())];
+        let mut palette = Palette::new(items(Lang::En, rooms));
+        for c in "who".chars() {
+            press(&mut palette, KeyCode::Char(c));
+        }
+        assert_eq!(palette.matches()[0].label, "/who");
+        assert_eq!(press(&mut palette, KeyCode::Enter), Outcome::Picked(Pick::Run("/who".into())));
+
+        palette.query = "dm".into();
+        assert_eq!(press(&mut palette, KeyCode::Enter), Outcome::Picked(Pick::Edit("/dm ".into())));
+        palette.query = "bob".into();
+        assert_eq!(press(&mut palette, KeyCode::Enter), Outcome::Picked(Pick::Run("/join chatabc".into())));
+        palette.query = "theme".into();
+        assert_eq!(
+            press(&mut palette, KeyCode::Enter),
+            Outcome::Picked(Pick::Key(KeyCode::Char('t'), KeyModifiers::CONTROL))
+        );
+        palette.query = "qqqq".into();
+        assert_eq!(press(&mut palette, KeyCode::Enter), Outcome::Open);
+        assert_eq!(press(&mut palette, KeyCode::Esc), Outcome::Close);
+    }
+
+    #[test]
+    fn snapshot_palette() {
+        let theme = Theme::dark();
+        let mut palette = Palette::new(items(Lang::En, Vec::new()));
+        palette.query = "/m".into();
+        palette.selected = 1;
+        let mut terminal = ratatui::Terminal::new(ratatui::backend::TestBackend::new(66, 16)).unwrap();
+        terminal.draw(|f| render(f, Some(&palette), Lang::En, &theme)).unwrap();
+        insta::assert_snapshot!(terminal.backend());
+    }
+}
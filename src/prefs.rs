@@ -0,0 +1,112 @@
+//! Per-room view settings saved across restarts: `/mute` and `/focus`.
+//!
+//! A busy room you only half follow can be muted (no bell, no toasts), and
+//! any room can be read in focus mode (no system lines, no peers pane).
+//! Both are choices about one room rather than global settings, so they're
+//! stored against the room's `TopicId` and put back whenever it's joined
+//! again — the same load-or-default file pattern as `drafts.rs`, in
+//! `room-prefs.toml` under the platform data directory.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use iroh_gossip::proto::TopicId;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Result, ResultExt};
+use crate::profile;
+
+/// The settings for one room. All off by default.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RoomPrefs {
+    /// No bell and no toasts for this room.
+    pub muted: bool,
+    /// Hide system messages and the peers pane.
+    pub focus: bool,
+}
+
+/// Saved settings keyed by room.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Prefs {
+    /// Keyed by the room's topic ID as hex, like `Drafts`.
+    rooms: BTreeMap<String, RoomPrefs>,
+}
+
+impl Prefs {
+    /// Default location of the file, if the platform has a data dir.
+    pub fn path() -> Option<PathBuf> {
+        profile::data_dir().map(|d| d.join("room-prefs.toml"))
+    }
+
+    /// Load from the default location, falling back to defaults.
+    pub fn load() -> Self {
+        Self::path().map(|p| Self::load_from(&p)).unwrap_or_default()
+    }
+
+    /// Load from `path`. A missing or malformed file yields defaults.
+    pub fn load_from(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|s| toml::from_str(&s).ok())
+            .unwrap_or_default()
+    }
+
+    /// Save to the default location. A no-op if there is no data dir.
+    pub fn save(&self) -> Result<()> {
+        match Self::path() {
+            Some(path) => self.save_to(&path),
+            None => Ok(()),
+        }
+    }
+
+    /// Save to `path`, creating parent directories as needed.
+    pub fn save_to(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).storage()?;
+        }
+        std::fs::write(path, toml::to_string_pretty(self).storage()?).storage()?;
+        Ok(())
+    }
+
+    /// The settings for `topic` (defaults if none were saved).
+    pub fn get(&self, topic: &TopicId) -> RoomPrefs {
+        self.rooms.get(&topic.to_string()).copied().unwrap_or_default()
+    }
+
+    /// Store the settings for `topic`. Defaults remove the entry.
+    pub fn set(&mut self, topic: &TopicId, prefs: RoomPrefs) {
+        if prefs == RoomPrefs::default() {
+            self.rooms.remove(&topic.to_string());
+        } else {
+            self.rooms.insert(topic.to_string(), prefs);
+        }
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefs_are_per_room_and_persist() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("room-prefs.toml");
+        let a = TopicId::from_bytes([1; 32]);
+        let b = TopicId::from_bytes([2; 32]);
+
+        let mut prefs = Prefs::default();
+        prefs.set(&a, RoomPrefs { muted: true, focus: false });
+        prefs.set(&b, RoomPrefs { muted: false, focus: true });
+        prefs.save_to(&path).unwrap();
+
+        let mut loaded = Prefs::load_from(&path);
+        assert_eq!(loaded.get(&a), RoomPrefs { muted: true, focus: false });
+        assert_eq!(loaded.get(&b), RoomPrefs { muted: false, focus: true });
+        loaded.set(&a, RoomPrefs::default());
+        assert_eq!(loaded, Prefs { rooms: BTreeMap::from([(b.to_string(), loaded.get(&b))]) });
+    }
+}
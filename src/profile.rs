@@ -0,0 +1,89 @@
+//! Profiles: independent personas on one machine (`--profile <name>`).
+//!
+//! Everything piper-chat keeps lives in two directories: the config dir
+//! (`config.toml`) and the data dir (identity key, blob store, room archive
+//! and history, contacts, drafts, room prefs, share artifacts). Without a
+//! profile they are `<config dir>/piper-chat` and `<data dir>/piper-chat`,
+//! as they always were. A profile gets a pair of its own under
+//! `profiles/<name>` in each, so a "work" and a "personal" persona can run
+//! at the same time with nothing in common: not the identity, not the
+//! contacts, not the blob store. (The blob store's per-endpoint
+//! subdirectories only keep instances from locking each other out; they
+//! don't separate anything else.)
+//!
+//! The profile is chosen once at startup — `--profile`, or else `profile`
+//! in the default config file — and held process-wide, like
+//! `metrics::METRICS`, so the `path()` / `dir()` functions of the modules
+//! that save things don't need it passed in.
+
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+/// Longest profile name, in characters.
+pub const MAX_NAME: usize = 32;
+
+/// The profile this process runs as; unset for the default one.
+static ACTIVE: OnceLock<String> = OnceLock::new();
+
+/// Whether `name` can name a profile: 1 to `MAX_NAME` ASCII letters,
+/// digits, `-` or `_`. It becomes a directory name, so nothing that could
+/// climb out of `profiles/` or mean something to a shell.
+pub fn valid_name(name: &str) -> bool {
+    (1..=MAX_NAME).contains(&name.len()) && name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+}
+
+/// Run as profile `name` from now on. Only the first call counts, and
+/// only with a valid name; returns whether `name` is now the profile.
+/// Main calls it before anything reads or writes a path.
+pub fn select(name: &str) -> bool {
+    valid_name(name) && ACTIVE.get_or_init(|| name.to_string()) == name
+}
+
+/// The selected profile, if any.
+pub fn active() -> Option<&'static str> {
+    ACTIVE.get().map(String::as_str)
+}
+
+/// Our data directory for the active profile, if the platform has one.
+pub fn data_dir() -> Option<PathBuf> {
+    dirs::data_dir().map(|d| within(d.join("piper-chat"), active()))
+}
+
+/// Our config directory for the active profile, if the platform has one.
+pub fn config_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|d| within(d.join("piper-chat"), active()))
+}
+
+/// `root` itself for the default profile, or `profile`'s directory in it.
+fn within(root: PathBuf, profile: Option<&str>) -> PathBuf {
+    match profile {
+        Some(name) => root.join("profiles").join(name),
+        None => root,
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_are_plain_directory_names() {
+        assert!(valid_name("work"));
+        assert!(valid_name("team_2-alt"));
+        for bad in ["", "..", "a/b", "a b", "ünï", &"x".repeat(MAX_NAME + 1)] {
+            assert!(!valid_name(bad), "{bad:?}");
+        }
+    }
+
+    #[test]
+    fn profiles_get_separate_directories() {
+        let root = PathBuf::from("/data/piper-chat");
+        assert_eq!(within(root.clone(), None), root);
+        let work = within(root.clone(), Some("work"));
+        let home = within(root.clone(), Some("home"));
+        assert_eq!(work, PathBuf::from("/data/piper-chat/profiles/work"));
+        assert_ne!(work, home);
+    }
+}
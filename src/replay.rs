@@ -0,0 +1,407 @@
+//! Replay mode: `piper-chat replay <json-log>` plays a recording made with
+//! `--json-log` back through the chat UI, with no networking at all.
+//!
+//! When someone reports a rendering bug and attaches their event log, this
+//! puts the same messages, peers, offers and transfer progress on screen in
+//! the same order, so the bug can be watched happen. `Player` holds the
+//! recording and applies one event at a time to an `App`, through the same
+//! methods the live event loop calls; `main.rs` decides when, waiting
+//! `delay` between events and drawing in between.
+//!
+//! Some of the chat can't come back: the log doesn't record system lines,
+//! our own peer entry or the content of files, and a transfer replays as its
+//! milestones (every 10%) rather than every progress tick. Lines that don't
+//! parse — say, the last one of a log cut off by a crash — are skipped and
+//! counted, so a damaged log still plays.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use iroh::EndpointId;
+use iroh_blobs::Hash;
+use serde::Deserialize;
+
+use crate::chat::App;
+use crate::error::{Result, ResultExt};
+use crate::net::{ConnType, HistoryEntry, HistoryEntryKind, PeerInfo, new_message_id};
+use crate::t;
+use crate::transfer::{self, Expiry, FileOffer, Milestone, TransferState};
+
+/// The longest pause between two events, however far apart they were
+/// recorded: nobody wants to sit through the hour a chat was idle.
+pub const MAX_GAP: Duration = Duration::from_secs(3);
+
+/// An event read back from the log (`eventlog::LogEvent`, owned).
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    Message {
+        kind: String,
+        message_id: String,
+        nickname: String,
+        text: String,
+        #[serde(default)]
+        reply_to: Option<String>,
+        #[serde(default)]
+        thread_id: Option<String>,
+    },
+    Offer {
+        message_id: String,
+        nickname: String,
+        endpoint_id: String,
+        filename: String,
+        size: u64,
+        hash: String,
+    },
+    Retract {
+        hash: String,
+        #[serde(default)]
+        endpoint_id: Option<String>,
+        #[serde(default)]
+        offer_id: Option<String>,
+    },
+    Join {
+        endpoint_id: String,
+        nickname: String,
+    },
+    Leave {
+        endpoint_id: String,
+        nickname: String,
+    },
+    Transfer {
+        hash: String,
+        #[serde(default)]
+        offer_id: Option<String>,
+        filename: String,
+        #[serde(flatten)]
+        step: Milestone,
+    },
+    Path {
+        endpoint_id: String,
+        nickname: String,
+        path: String,
+        addr: Option<String>,
+    },
+    /// An event this version doesn't know, from a newer one.
+    #[serde(other)]
+    Unknown,
+}
+
+/// One line of the log.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Recorded {
+    pub ts_ms: u64,
+    #[serde(flatten)]
+    pub event: Event,
+}
+
+/// Read the log at `path`: its events, and how many lines were skipped
+/// because they didn't parse.
+pub fn load(path: &Path) -> Result<(Vec<Recorded>, usize)> {
+    let text = std::fs::read_to_string(path).storage()?;
+    let mut skipped = 0;
+    let events = text
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| {
+            let parsed = serde_json::from_str(line).ok();
+            skipped += usize::from(parsed.is_none());
+            parsed
+        })
+        .collect();
+    Ok((events, skipped))
+}
+
+/// How long to wait between events recorded at `from_ms` and `to_ms` when
+/// playing `speed` times as fast, at most `MAX_GAP`.
+pub fn delay(from_ms: u64, to_ms: u64, speed: f64) -> Duration {
+    let gap = Duration::from_millis(to_ms.saturating_sub(from_ms));
+    gap.div_f64(speed.max(f64::MIN_POSITIVE)).min(MAX_GAP)
+}
+
+/// A message ID or hash from its hex form.
+fn from_hex<const N: usize>(hex: &str) -> Option<[u8; N]> {
+    if hex.len() != N * 2 {
+        return None;
+    }
+    let mut bytes = [0u8; N];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(hex.get(i * 2..i * 2 + 2)?, 16).ok()?;
+    }
+    Some(bytes)
+}
+
+/// A recording being played back.
+pub struct Player {
+    events: Vec<Recorded>,
+    next: usize,
+    /// Files with a `shared` step: offers of our own, shown as sharing.
+    ours: HashSet<Hash>,
+    /// Who offered each file, for the line a retraction prints.
+    senders: HashMap<Hash, String>,
+}
+
+impl Player {
+    /// Play `events` in the order they happened. Transfer steps are logged
+    /// a little after the fact, so the recording is sorted by timestamp.
+    pub fn new(mut events: Vec<Recorded>) -> Self {
+        events.sort_by_key(|r| r.ts_ms);
+        let ours = events
+            .iter()
+            .filter_map(|r| match &r.event {
+                Event::Transfer { hash, step: Milestone::Shared, .. } => hash.parse().ok(),
+                _ => None,
+            })
+            .collect();
+        Self { events, next: 0, ours, senders: HashMap::new() }
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// How many events have been applied.
+    pub fn position(&self) -> usize {
+        self.next
+    }
+
+    /// When the last applied and the next event were recorded, if there
+    /// is a next one.
+    pub fn gap(&self) -> Option<(u64, u64)> {
+        let next = self.events.get(self.next)?.ts_ms;
+        let last = self.next.checked_sub(1).map_or(next, |i| self.events[i].ts_ms);
+        Some((last, next))
+    }
+
+    /// Apply the next event to `app`. Returns `false` once there are none.
+    pub fn step(&mut self, app: &mut App) -> bool {
+        let Some(recorded) = self.events.get(self.next).cloned() else { return false };
+        self.next += 1;
+        self.play(app, recorded);
+        true
+    }
+
+    /// Apply `recorded` to `app` straight away, outside the recording: for
+    /// driving an `App` from a script (see `tests/simulation.rs`).
+    pub fn play(&mut self, app: &mut App, recorded: Recorded) {
+        app.now_ms = recorded.ts_ms;
+        self.apply(app, recorded.ts_ms, recorded.event);
+    }
+
+    fn apply(&mut self, app: &mut App, ts_ms: u64, event: Event) {
+        let lang = app.config.lang;
+        match event {
+            Event::Message { kind, message_id, nickname, text, reply_to, thread_id } => {
+                let id = from_hex(&message_id).unwrap_or_else(new_message_id);
+                let parent = |hex: Option<String>| hex.and_then(|h| from_hex::<16>(&h));
+                match (kind.as_str(), parent(reply_to), parent(thread_id)) {
+                    ("reply", Some(reply_to), _) => app.reply(nickname, text, id, ts_ms, reply_to),
+                    ("thread", _, Some(thread_id)) => app.thread_chat(nickname, text, id, ts_ms, thread_id),
+                    ("spoiler", ..) => app.spoiler(nickname, text, id, ts_ms),
+                    _ => app.chat(nickname, text, id, ts_ms),
+                }
+            }
+            Event::Offer { message_id, nickname, endpoint_id, filename, size, hash } => {
+                let (Ok(blob_hash), Ok(sender_id)) = (hash.parse::<Hash>(), endpoint_id.parse::<EndpointId>()) else { return };
+                let offer_id = from_hex(&message_id).unwrap_or_else(new_message_id);
+                let offer = FileOffer {
+                    sender_nickname: nickname.clone(),
+                    sender_id,
+                    filename: filename.clone(),
+                    size,
+                    hash: blob_hash,
+                    key: None,
+                    offer_id,
+                };
+                // Like the chat, an offer already in the pane is a re-broadcast.
+                if app.transfers.entry(&offer.entry_id()).is_some() {
+                    return;
+                }
+                if self.ours.contains(&blob_hash) {
+                    app.transfers.add_sent(offer);
+                } else {
+                    app.transfers.add_offer(offer);
+                }
+                self.senders.insert(blob_hash, nickname.clone());
+                app.push_history(HistoryEntry {
+                    message_id: offer_id,
+                    timestamp_ms: ts_ms,
+                    kind: HistoryEntryKind::FileOffer {
+                        nickname: nickname.clone(),
+                        endpoint_id: sender_id,
+                        filename: filename.clone(),
+                        size,
+                        hash: *blob_hash.as_bytes(),
+                        mime_type: None,
+                        target: None,
+                    },
+                });
+                let size = transfer::format_file_size(size);
+                app.system(t!(lang, "file.shared", name = nickname, filename = filename, size = size));
+            }
+            Event::Retract { hash, endpoint_id, offer_id } => {
+                let Ok(blob_hash) = hash.parse::<Hash>() else { return };
+                // Logs from before revocations named their offer give only
+                // the hash.
+                let sender = endpoint_id.and_then(|id| id.parse::<EndpointId>().ok());
+                let offer_id = offer_id.and_then(|h| from_hex::<16>(&h));
+                let Some(id) = app
+                    .transfers
+                    .entries
+                    .iter()
+                    .map(|e| e.offer.entry_id())
+                    .find(|id| id.hash == blob_hash && sender.is_none_or(|s| id.sender == s) && offer_id.is_none_or(|o| id.offer_id == o))
+                else {
+                    return;
+                };
+                let expiry = Expiry { at_ms: ts_ms, message_id: new_message_id() };
+                if let Some(filename) = app.transfers.revoke(&id, expiry, ts_ms) {
+                    let name = self.senders.get(&blob_hash).cloned().unwrap_or_default();
+                    app.system(t!(lang, "file.unshared", name = name, filename = filename));
+                }
+            }
+            Event::Join { endpoint_id, nickname } => {
+                let Ok(id) = endpoint_id.parse::<EndpointId>() else { return };
+                app.system(t!(lang, "peer.joined", name = nickname));
+                app.add_peer(id, PeerInfo { name: nickname, conn_type: ConnType::Unknown, rtt_ms: None, addr: None }, true);
+            }
+            Event::Leave { endpoint_id, nickname } => {
+                if let Ok(id) = endpoint_id.parse::<EndpointId>() {
+                    app.remove_peer(&id);
+                }
+                app.system(t!(lang, "peer.left", name = nickname));
+            }
+            Event::Transfer { hash, offer_id, filename, step } => {
+                let Ok(hash) = hash.parse::<Hash>() else { return };
+                // Logs from before offer IDs name only the hash.
+                let offer_id = offer_id.and_then(|h| from_hex::<16>(&h));
+                let Some(entry) = app
+                    .transfers
+                    .entries
+                    .iter()
+                    .find(|e| e.offer.hash == hash && offer_id.is_none_or(|o| e.offer.offer_id == o))
+                else {
+                    return;
+                };
+                let (id, size, downloading) =
+                    (entry.offer.entry_id(), entry.offer.size, matches!(entry.state, TransferState::Downloading { .. }));
+                match step {
+                    // The offer itself put these in place.
+                    Milestone::Offered | Milestone::Shared | Milestone::Packing | Milestone::Previewed => {}
+                    Milestone::Started => app.transfers.start_download(&id),
+                    Milestone::Reached(pct) => {
+                        if !downloading {
+                            app.transfers.start_download(&id);
+                        }
+                        app.transfers.update_progress(&id, size * u64::from(pct) / 100, size);
+                    }
+                    Milestone::Attempt(strategy) => app.transfers.start_attempt(&id, strategy),
+                    Milestone::Completed => app.transfers.complete_download(&id, PathBuf::from(filename)),
+                    Milestone::Failed(error) => app.transfers.fail_download(&id, error),
+                    Milestone::Invalidated(why) => {
+                        app.transfers.invalidate(&id, why);
+                    }
+                    Milestone::Reacted => {
+                        app.transfers.react(&id);
+                    }
+                    // The log names the peer, not their ID; one who's
+                    // joined by then is found by name.
+                    Milestone::ReceivedBy(name) => {
+                        if let Some(from) = app.peers.iter().find(|(_, p)| p.name == name).map(|(id, _)| *id) {
+                            app.transfers.acknowledge(&id, from, name);
+                        }
+                    }
+                    Milestone::DownloadedBy(name) => {
+                        if let Some(by) = app.peers.iter().find(|(_, p)| p.name == name).map(|(id, _)| *id) {
+                            app.transfers.served(&hash, by, &name);
+                        }
+                    }
+                }
+            }
+            Event::Path { endpoint_id, path, addr, .. } => {
+                let Ok(id) = endpoint_id.parse::<EndpointId>() else { return };
+                if let Some(peer) = app.peers.get_mut(&id) {
+                    peer.conn_type = if path == "direct" { ConnType::Direct } else { ConnType::Relay };
+                    peer.addr = addr.and_then(|a| a.parse().ok());
+                }
+            }
+            Event::Unknown => {}
+        }
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eventlog::{EventLog, LogEvent, history_event};
+    use crate::transfer::TransferManager;
+
+    #[tokio::test]
+    async fn a_recorded_log_plays_back_into_the_app() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("events.jsonl");
+        let bob = EndpointId::from_bytes(&[1u8; 32]).unwrap();
+        let hash = Hash::from_bytes([2; 32]);
+
+        // Record a short session the way the chat would.
+        let mut log = EventLog::open(&path).unwrap();
+        log.log(&LogEvent::Join { endpoint_id: bob, nickname: "Bob" });
+        let mut recorder = App::new();
+        recorder.chat("Bob".into(), "hi".into(), [7; 16], 5);
+        recorder.reply("Alice".into(), "hey".into(), [8; 16], 6, [7; 16]);
+        for entry in &recorder.history {
+            log.log(&history_event(entry).unwrap());
+        }
+        let offer = HistoryEntry {
+            message_id: [9; 16],
+            timestamp_ms: 7,
+            kind: HistoryEntryKind::FileOffer {
+                nickname: "Bob".into(),
+                endpoint_id: bob,
+                filename: "a.png".into(),
+                size: 1000,
+                hash: *hash.as_bytes(),
+                mime_type: None,
+                target: None,
+            },
+        };
+        log.log(&history_event(&offer).unwrap());
+        let mut transfers = TransferManager::new();
+        let file = FileOffer { sender_nickname: "Bob".into(), sender_id: bob, filename: "a.png".into(), size: 1000, hash, key: None, offer_id: [9; 16] };
+        let id = file.entry_id();
+        transfers.add_offer(file);
+        transfers.start_download(&id);
+        transfers.update_progress(&id, 500, 1000);
+        log.transfers(&transfers);
+        log.close().await.unwrap();
+        // And a line cut off by a crash.
+        std::fs::write(&path, std::fs::read_to_string(&path).unwrap() + "{\"ts_ms\":1,\"ev").unwrap();
+
+        let (events, skipped) = load(&path).unwrap();
+        assert_eq!(skipped, 1);
+        let mut player = Player::new(events);
+        let mut app = App::new();
+        while player.step(&mut app) {}
+        assert_eq!(player.position(), player.len());
+
+        assert_eq!(app.peers.get(&bob).map(|p| p.name.as_str()), Some("Bob"));
+        assert_eq!(app.history[1].message_id, [8; 16]);
+        assert!(matches!(app.history[1].kind, HistoryEntryKind::Reply { reply_to, .. } if reply_to == [7; 16]));
+        assert!(matches!(app.transfers.entries[0].state, TransferState::Downloading { bytes_received: 500, .. }));
+    }
+
+    #[test]
+    fn delay_scales_and_caps_gaps() {
+        assert_eq!(delay(1_000, 2_000, 1.0), Duration::from_secs(1));
+        assert_eq!(delay(1_000, 2_000, 4.0), Duration::from_millis(250));
+        assert_eq!(delay(0, 3_600_000, 1.0), MAX_GAP);
+        assert_eq!(delay(2_000, 1_000, 1.0), Duration::ZERO);
+    }
+}
@@ -0,0 +1,407 @@
+//! Archive of rooms we've been in, so they can be rejoined later.
+//!
+//! On quit (or when `/dm` switches away) the room's ticket and a summary go
+//! into an index (`~/.local/share/piper-chat/rooms.toml` on Linux), and its
+//! history log is written next to it as a postcard blob under `history/`.
+//! Rejoining restores that history before any peer syncs theirs, so the
+//! conversation picks up where it left off.
+//!
+//! Unread counts are estimates: messages from others newer than our last
+//! keypress in the room. Anything sent while we were away isn't known until
+//! we rejoin and sync.
+//!
+//! ## Encryption at rest
+//!
+//! With `PIPER_CHAT_PASSPHRASE` set, history files are written encrypted so
+//! a stolen laptop doesn't expose past conversations. The design:
+//!
+//! - **Key derivation.** Argon2id (the `argon2` crate's defaults: 19 MiB,
+//!   2 passes) stretches the passphrase with a random 16-byte salt into a
+//!   32-byte key. A fresh salt per write means every file has its own key.
+//! - **Cipher.** ChaCha20-Poly1305 with a random 12-byte nonce. The header
+//!   and the topic ID are authenticated as associated data, so a file can't
+//!   be swapped in for another room's without detection.
+//! - **File format.** `MAGIC` (8 bytes) · version (1) · salt (16) ·
+//!   nonce (12) · ciphertext of the postcard-encoded history + 16-byte tag.
+//!
+//! Plain files from before encryption was enabled still load, and are
+//! rewritten encrypted on the next save. The index (`rooms.toml`) is not
+//! encrypted: it holds tickets and who-was-there labels, not messages.
+
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, anyhow, bail};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use iroh_gossip::proto::TopicId;
+use serde::{Deserialize, Serialize};
+
+use crate::chat::format_relative;
+use crate::error::{Error, Result, ResultExt};
+use crate::net::{HistoryEntry, HistoryEntryKind, MessageId};
+use crate::profile;
+
+/// Most names shown in a room's label.
+const LABEL_NAMES: usize = 3;
+
+/// Environment variable holding the history passphrase.
+pub const PASSPHRASE_VAR: &str = "PIPER_CHAT_PASSPHRASE";
+
+/// First bytes of an encrypted history file.
+const MAGIC: &[u8; 8] = b"PIPERENC";
+/// Encrypted file format version.
+const VERSION: u8 = 1;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+/// Bytes before the ciphertext: magic, version, salt, nonce.
+const HEADER_LEN: usize = MAGIC.len() + 1 + SALT_LEN + NONCE_LEN;
+
+/// Summary of one archived room.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RoomRecord {
+    /// Ticket to rejoin with.
+    pub ticket: String,
+    /// Who was talking, e.g. "Bob, Carol".
+    pub label: String,
+    /// Timestamp of the newest message (unix ms).
+    pub last_activity_ms: u64,
+    /// Messages in the archived history.
+    pub message_count: usize,
+    /// Messages from others after our last keypress in the room.
+    pub unread: usize,
+}
+
+impl RoomRecord {
+    /// One-line summary, e.g. "2h ago · 14 messages · ~3 unread".
+    pub fn describe(&self, now_ms: u64) -> String {
+        let mut text = format!(
+            "{} · {} messages",
+            format_relative(self.last_activity_ms, now_ms),
+            self.message_count
+        );
+        if self.unread > 0 {
+            text.push_str(&format!(" · ~{} unread", self.unread));
+        }
+        text
+    }
+}
+
+/// The archive index, keyed by topic.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Rooms {
+    /// Records keyed by topic ID as hex.
+    rooms: BTreeMap<String, RoomRecord>,
+}
+
+impl Rooms {
+    /// Directory holding the index and histories, if the platform has one.
+    pub fn dir() -> Option<PathBuf> {
+        profile::data_dir()
+    }
+
+    /// Load the index from the default location, falling back to empty.
+    pub fn load() -> Self {
+        Self::dir().map(|d| Self::load_from(&d)).unwrap_or_default()
+    }
+
+    /// Load the index from `dir`. A missing or malformed file yields an
+    /// empty archive.
+    pub fn load_from(dir: &Path) -> Self {
+        std::fs::read_to_string(dir.join("rooms.toml"))
+            .ok()
+            .and_then(|s| toml::from_str(&s).ok())
+            .unwrap_or_default()
+    }
+
+    /// Save the index to the default location. A no-op without a data dir.
+    pub fn save(&self) -> Result<()> {
+        match Self::dir() {
+            Some(dir) => self.save_to(&dir),
+            None => Ok(()),
+        }
+    }
+
+    /// Save the index into `dir`, creating it as needed.
+    pub fn save_to(&self, dir: &Path) -> Result<()> {
+        std::fs::create_dir_all(dir).storage()?;
+        std::fs::write(dir.join("rooms.toml"), toml::to_string_pretty(self).storage()?).storage()?;
+        Ok(())
+    }
+
+    /// Update the record for `topic` from its current `history`.
+    ///
+    /// `nickname` is ours (our own messages are never unread) and
+    /// `last_read_ms` the time of our last keypress in the room.
+    pub fn record(
+        &mut self,
+        topic: &TopicId,
+        ticket: &str,
+        history: &[HistoryEntry],
+        nickname: &str,
+        last_read_ms: u64,
+    ) {
+        let mut names = BTreeSet::new();
+        let mut unread = 0;
+        for entry in history {
+            if let Some(author) = author(entry) {
+                if author == nickname {
+                    continue;
+                }
+                names.insert(author.to_string());
+                if entry.timestamp_ms > last_read_ms {
+                    unread += 1;
+                }
+            }
+        }
+        let mut label: Vec<String> = names.into_iter().take(LABEL_NAMES).collect();
+        if label.is_empty() {
+            label.push("(no messages)".into());
+        }
+        self.rooms.insert(
+            topic.to_string(),
+            RoomRecord {
+                ticket: ticket.to_string(),
+                label: label.join(", "),
+                last_activity_ms: history.iter().map(|e| e.timestamp_ms).max().unwrap_or(0),
+                message_count: history.len(),
+                unread,
+            },
+        );
+    }
+
+    /// All archived rooms, most recently active first.
+    pub fn by_recency(&self) -> Vec<&RoomRecord> {
+        let mut all: Vec<_> = self.rooms.values().collect();
+        all.sort_by_key(|r| std::cmp::Reverse(r.last_activity_ms));
+        all
+    }
+
+    /// Whether nothing is archived.
+    pub fn is_empty(&self) -> bool {
+        self.rooms.is_empty()
+    }
+}
+
+/// Add `imported` entries to `history`, skipping any whose message ID is
+/// already there, and keep the log in timestamp order. Returns how many
+/// were added.
+pub fn merge_history(history: &mut Vec<HistoryEntry>, imported: Vec<HistoryEntry>) -> usize {
+    let mut known: HashSet<MessageId> = history.iter().map(|e| e.message_id).collect();
+    let before = history.len();
+    history.extend(imported.into_iter().filter(|e| known.insert(e.message_id)));
+    history.sort_by_key(|e| e.timestamp_ms);
+    history.len() - before
+}
+
+/// Who wrote a history entry, for entries that have an author.
+fn author(entry: &HistoryEntry) -> Option<&str> {
+    match &entry.kind {
+        HistoryEntryKind::Chat { nickname, .. }
+        | HistoryEntryKind::ThreadChat { nickname, .. }
+        | HistoryEntryKind::Reply { nickname, .. }
+        | HistoryEntryKind::Spoiler { nickname, .. }
+        | HistoryEntryKind::FileOffer { nickname, .. } => Some(nickname),
+        HistoryEntryKind::FileRetract { .. }
+        | HistoryEntryKind::FileKey { .. }
+        | HistoryEntryKind::FileRevoke { .. }
+        | HistoryEntryKind::System(_) => None,
+    }
+}
+
+/// Path of `topic`'s archived history under `dir`.
+fn history_path(dir: &Path, topic: &TopicId) -> PathBuf {
+    dir.join("history").join(format!("{topic}.bin"))
+}
+
+/// The history passphrase from the environment, if one is set.
+pub fn passphrase() -> Option<String> {
+    std::env::var(PASSPHRASE_VAR).ok().filter(|p| !p.is_empty())
+}
+
+/// Directory of `topic`'s write-behind journal under `dir` (see `journal`).
+pub fn journal_dir(dir: &Path, topic: &TopicId) -> PathBuf {
+    dir.join("journal").join(topic.to_string())
+}
+
+/// Write `topic`'s history into `dir`, encrypted if a `passphrase` is given.
+pub fn save_history(
+    dir: &Path,
+    topic: &TopicId,
+    history: &[HistoryEntry],
+    passphrase: Option<&str>,
+) -> Result<()> {
+    let path = history_path(dir, topic);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).storage()?;
+    }
+    let plain = postcard::to_stdvec(history).storage()?;
+    let bytes = match passphrase {
+        Some(passphrase) => encrypt(&plain, passphrase, topic).storage()?,
+        None => plain,
+    };
+    std::fs::write(path, bytes).storage()?;
+    Ok(())
+}
+
+/// Read `topic`'s archived history from `dir`.
+///
+/// A missing or undecodable file yields an empty history. An encrypted file
+/// is an error without a passphrase, or with the wrong one — the caller
+/// should say so rather than silently starting fresh.
+pub fn load_history(
+    dir: &Path,
+    topic: &TopicId,
+    passphrase: Option<&str>,
+) -> Result<Vec<HistoryEntry>> {
+    let Ok(bytes) = std::fs::read(history_path(dir, topic)) else {
+        return Ok(Vec::new());
+    };
+    let plain = if bytes.starts_with(MAGIC) {
+        let Some(passphrase) = passphrase else {
+            let msg = format!("archived history is encrypted; set {PASSPHRASE_VAR} to read it");
+            return Err(Error::Storage(msg.into()));
+        };
+        decrypt(&bytes, passphrase, topic).storage()?
+    } else {
+        bytes
+    };
+    Ok(postcard::from_bytes(&plain).unwrap_or_default())
+}
+
+/// Stretch `passphrase` into a cipher keyed for `salt`.
+fn cipher(passphrase: &str, salt: &[u8]) -> anyhow::Result<ChaCha20Poly1305> {
+    let mut key = [0u8; 32];
+    argon2::Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| anyhow!("derive key: {e}"))?;
+    Ok(ChaCha20Poly1305::new(Key::from_slice(&key)))
+}
+
+/// Associated data: the file header plus the room it belongs to.
+fn aad(header: &[u8], topic: &TopicId) -> Vec<u8> {
+    [header, topic.as_bytes()].concat()
+}
+
+/// Encrypt `plain` into the on-disk format described in the module docs.
+fn encrypt(plain: &[u8], passphrase: &str, topic: &TopicId) -> anyhow::Result<Vec<u8>> {
+    let salt: [u8; SALT_LEN] = rand::random();
+    let nonce: [u8; NONCE_LEN] = rand::random();
+    let mut out = Vec::with_capacity(HEADER_LEN + plain.len() + 16);
+    out.extend_from_slice(MAGIC);
+    out.push(VERSION);
+    out.extend_from_slice(&salt);
+    out.extend_from_slice(&nonce);
+    let payload = Payload { msg: plain, aad: &aad(&out, topic) };
+    let sealed = cipher(passphrase, &salt)?
+        .encrypt(Nonce::from_slice(&nonce), payload)
+        .map_err(|_| anyhow!("encrypt history"))?;
+    out.extend_from_slice(&sealed);
+    Ok(out)
+}
+
+/// Reverse `encrypt`, checking the version, key and room binding.
+fn decrypt(bytes: &[u8], passphrase: &str, topic: &TopicId) -> anyhow::Result<Vec<u8>> {
+    let header = bytes.get(..HEADER_LEN).context("archived history is truncated")?;
+    let version = header[MAGIC.len()];
+    if version != VERSION {
+        bail!("archived history has unknown format version {version}");
+    }
+    let salt = &header[MAGIC.len() + 1..MAGIC.len() + 1 + SALT_LEN];
+    let nonce = &header[HEADER_LEN - NONCE_LEN..];
+    let payload = Payload { msg: &bytes[HEADER_LEN..], aad: &aad(header, topic) };
+    cipher(passphrase, salt)?
+        .decrypt(Nonce::from_slice(nonce), payload)
+        .map_err(|_| anyhow!("wrong passphrase or corrupted archived history"))
+}
+
+// ── Tests ────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chat(id: u8, ts: u64, nickname: &str) -> HistoryEntry {
+        HistoryEntry {
+            message_id: [id; 16],
+            timestamp_ms: ts,
+            kind: HistoryEntryKind::Chat {
+                nickname: nickname.into(),
+                text: format!("msg {id}"),
+            },
+        }
+    }
+
+    #[test]
+    fn merge_skips_known_ids_and_sorts() {
+        let mut history = vec![chat(1, 100, "Bob"), chat(2, 300, "Alice")];
+        let added = merge_history(&mut history, vec![chat(2, 300, "Alice"), chat(3, 200, "Carol")]);
+        assert_eq!(added, 1);
+        let ids: Vec<u8> = history.iter().map(|e| e.message_id[0]).collect();
+        assert_eq!(ids, [1, 3, 2]);
+    }
+
+    #[test]
+    fn record_summarises_history() {
+        let topic = TopicId::from_bytes([1; 32]);
+        let history = vec![
+            chat(1, 100, "Bob"),
+            chat(2, 200, "Alice"),
+            chat(3, 300, "Carol"),
+            chat(4, 400, "Bob"),
+        ];
+        let mut rooms = Rooms::default();
+        rooms.record(&topic, "ticket", &history, "Alice", 250);
+        let record = &rooms.by_recency()[0];
+        assert_eq!(record.label, "Bob, Carol");
+        assert_eq!(record.last_activity_ms, 400);
+        assert_eq!(record.message_count, 4);
+        // Carol at 300 and Bob at 400 came after our last keypress.
+        assert_eq!(record.unread, 2);
+        assert_eq!(record.describe(400 + 7_200_000), "2h ago · 4 messages · ~2 unread");
+    }
+
+    #[test]
+    fn index_and_history_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let old = TopicId::from_bytes([1; 32]);
+        let new = TopicId::from_bytes([2; 32]);
+        let mut rooms = Rooms::default();
+        rooms.record(&old, "old", &[chat(1, 100, "Bob")], "Alice", 0);
+        rooms.record(&new, "new", &[chat(2, 900, "Carol")], "Alice", 0);
+        rooms.save_to(dir.path()).unwrap();
+        save_history(dir.path(), &new, &[chat(2, 900, "Carol")], None).unwrap();
+
+        let loaded = Rooms::load_from(dir.path());
+        assert_eq!(loaded, rooms);
+        let tickets: Vec<&str> = loaded.by_recency().iter().map(|r| r.ticket.as_str()).collect();
+        assert_eq!(tickets, ["new", "old"]);
+        assert_eq!(load_history(dir.path(), &new, None).unwrap().len(), 1);
+        assert!(load_history(dir.path(), &old, None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn encrypted_history_needs_the_passphrase() {
+        let dir = tempfile::tempdir().unwrap();
+        let topic = TopicId::from_bytes([1; 32]);
+        let history = vec![chat(1, 100, "Bob")];
+        save_history(dir.path(), &topic, &history, Some("hunter2")).unwrap();
+
+        let raw = std::fs::read(history_path(dir.path(), &topic)).unwrap();
+        assert!(raw.starts_with(MAGIC));
+        assert!(!raw.windows(5).any(|w| w == b"msg 1"));
+
+        let loaded = load_history(dir.path(), &topic, Some("hunter2")).unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].message_id, [1; 16]);
+        assert!(load_history(dir.path(), &topic, None).is_err());
+        assert!(load_history(dir.path(), &topic, Some("wrong")).is_err());
+
+        // The file is bound to its room: copied under another topic, it fails.
+        let other = TopicId::from_bytes([2; 32]);
+        std::fs::copy(history_path(dir.path(), &topic), history_path(dir.path(), &other)).unwrap();
+        assert!(load_history(dir.path(), &other, Some("hunter2")).is_err());
+    }
+}
@@ -0,0 +1,114 @@
+//! Scrub text received from peers before it reaches the terminal.
+//!
+//! Everything a peer sends — nicknames, chat text, filenames — ends up
+//! printed in our terminal, either through ratatui or (in `tail` mode)
+//! straight to stdout. A peer could embed bytes the terminal acts on
+//! instead of showing:
+//!
+//! - **Escape sequences** (`ESC [ 2 J`, OSC 52 clipboard writes, …) that
+//!   clear the screen, move the cursor or change the title.
+//! - **Other control characters**, like `\r` to overwrite the start of a
+//!   line, or C1 codes (U+0080–U+009F) some terminals treat as escapes.
+//! - **Bidirectional overrides** (U+202E and friends) that reverse how the
+//!   following text is displayed, so `exe.txt` can be made to read `txt.exe`
+//!   or a message can fake the layout of a system line.
+//!
+//! `text()` removes all of these, keeping line breaks; `line()` does the
+//! same for one-line fields, where a line break becomes a space. Tabs
+//! become a space too, since they'd render at an unpredictable width.
+//! `net::proto::decode` runs every incoming message through them.
+
+use std::borrow::Cow;
+
+/// `s` with escape sequences, control characters and bidi controls
+/// removed. Line breaks are kept.
+pub fn text(s: &str) -> Cow<'_, str> {
+    scrub(s, true)
+}
+
+/// Like `text()`, for a field that must stay on one line: line breaks
+/// become spaces.
+pub fn line(s: &str) -> Cow<'_, str> {
+    scrub(s, false)
+}
+
+/// Whether `c` is a bidirectional formatting character: the embeddings
+/// and overrides (U+202A–U+202E), the isolates (U+2066–U+2069) and the
+/// invisible direction marks.
+fn is_bidi_control(c: char) -> bool {
+    matches!(c, '\u{202A}'..='\u{202E}' | '\u{2066}'..='\u{2069}' | '\u{200E}' | '\u{200F}' | '\u{061C}')
+}
+
+fn scrub(s: &str, keep_newlines: bool) -> Cow<'_, str> {
+    let clean = |c: char| (c == '\n' && keep_newlines) || !(c.is_control() || is_bidi_control(c));
+    if s.chars().all(clean) {
+        return Cow::Borrowed(s);
+    }
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\u{1b}' => skip_escape(&mut chars),
+            '\n' if keep_newlines => out.push('\n'),
+            '\n' | '\t' => out.push(' '),
+            c if c.is_control() || is_bidi_control(c) => {}
+            c => out.push(c),
+        }
+    }
+    Cow::Owned(out)
+}
+
+/// Skip the rest of an escape sequence whose ESC was just read.
+///
+/// CSI (`ESC [`) runs to a final byte in `@`..=`~`; OSC, DCS and the other
+/// string sequences (`ESC ]`, `ESC P`, …) run to BEL or `ESC \`; anything
+/// else is ESC plus one character.
+fn skip_escape(chars: &mut std::iter::Peekable<std::str::Chars>) {
+    match chars.next() {
+        Some('[') => {
+            for c in chars.by_ref() {
+                if ('@'..='~').contains(&c) {
+                    break;
+                }
+            }
+        }
+        Some(']' | 'P' | 'X' | '^' | '_') => {
+            while let Some(c) = chars.next() {
+                if c == '\u{7}' || (c == '\u{1b}' && chars.next_if_eq(&'\\').is_some()) {
+                    break;
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_text_is_untouched() {
+        assert!(matches!(text("héllo 世界\nbye"), Cow::Borrowed("héllo 世界\nbye")));
+        assert!(matches!(line("report.pdf"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn escapes_and_controls_are_removed() {
+        assert_eq!(text("a\u{1b}[2J\u{1b}[1;31mred\u{1b}[0m b"), "ared b");
+        // An OSC 52 clipboard write, ended by BEL and by ST.
+        assert_eq!(text("x\u{1b}]52;c;aGk=\u{7}y\u{1b}]0;title\u{1b}\\z"), "xyz");
+        assert_eq!(text("over\rwrite\u{8}\u{7f}\u{9b}31m"), "overwrite31m");
+        assert_eq!(text("a\tb\nc"), "a b\nc");
+        assert_eq!(line("two\nlines"), "two lines");
+    }
+
+    #[test]
+    fn bidi_overrides_are_removed() {
+        // U+202E before "txt.exe" makes it display as "exe.txt".
+        assert_eq!(line("\u{202E}txt.exe"), "txt.exe");
+        assert_eq!(text("\u{2067}admin\u{2069}: hi"), "admin: hi");
+    }
+}
@@ -0,0 +1,221 @@
+//! Temporary artifacts made for sharing, and cleaning them up.
+//!
+//! Two kinds of things are created only so something can be shared, and
+//! outlive their use unless removed:
+//!
+//! - **Artifacts in the blob store.** A paste shared as `paste.txt`, a
+//!   folder packed into a tar archive, a sealed copy of a file: none of them
+//!   exists anywhere but in our store, under a tag that keeps it from garbage
+//!   collection. `Artifacts` lists them (`~/.local/share/piper-chat/
+//!   artifacts.toml` on Linux), so their tags can be deleted when the offer
+//!   is revoked or expires, on quit, and — for whatever a crashed session
+//!   left behind — at the next start. A plain file shared from disk isn't an
+//!   artifact: the original is still there.
+//! - **Scratch blob stores.** The scripting subcommands (`send`, `tail`,
+//!   `share`, `fetch`) run on a throwaway store in the system temp dir.
+//!   `TempStore` removes it when dropped, and holds a lock on a file inside
+//!   while it lives; `sweep` removes any such store whose lock nobody
+//!   holds, i.e. whose process died without cleaning up.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use iroh_blobs::Hash;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Result, ResultExt};
+use crate::profile;
+
+/// Name prefix of scratch stores in the temp dir; the process ID follows.
+const PREFIX: &str = "piper-chat-";
+
+/// The lock file inside a scratch store.
+const LOCK: &str = ".lock";
+
+/// How old an unlocked scratch store without a lock file must be before
+/// `sweep` takes it: its owner may be about to create the lock.
+const UNLOCKED_GRACE: Duration = Duration::from_secs(60 * 60);
+
+/// Blobs in our store that were made for a share.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Artifacts {
+    /// Filename offered, keyed by the blob's hash as hex.
+    blobs: BTreeMap<String, String>,
+    /// The store is in memory (`--mem-store`): its blobs go with the
+    /// process, so the list is never saved.
+    #[serde(skip)]
+    in_memory: bool,
+}
+
+impl Artifacts {
+    /// Default location of the list, if the platform has a data dir.
+    pub fn path() -> Option<PathBuf> {
+        profile::data_dir().map(|d| d.join("artifacts.toml"))
+    }
+
+    /// Load from the default location, falling back to none.
+    pub fn load() -> Self {
+        Self::path().map(|p| Self::load_from(&p)).unwrap_or_default()
+    }
+
+    /// Load from `path`. A missing or malformed file yields none.
+    pub fn load_from(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|s| toml::from_str(&s).ok())
+            .unwrap_or_default()
+    }
+
+    /// An empty list for an in-memory store, whose `save` does nothing.
+    pub fn in_memory() -> Self {
+        Self { in_memory: true, ..Self::default() }
+    }
+
+    /// Save to the default location. A no-op if there is no data dir, or
+    /// for an `in_memory` list.
+    pub fn save(&self) -> Result<()> {
+        match Self::path() {
+            Some(path) if !self.in_memory => self.save_to(&path),
+            _ => Ok(()),
+        }
+    }
+
+    /// Save to `path`, creating parent directories as needed.
+    pub fn save_to(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).storage()?;
+        }
+        std::fs::write(path, toml::to_string_pretty(self).storage()?).storage()?;
+        Ok(())
+    }
+
+    /// Note that `hash` was made to share `filename`.
+    pub fn track(&mut self, hash: Hash, filename: &str) {
+        self.blobs.insert(hash.to_hex(), filename.to_string());
+    }
+
+    /// Stop tracking `hash`; whether it was tracked.
+    pub fn untrack(&mut self, hash: &Hash) -> bool {
+        self.blobs.remove(&hash.to_hex()).is_some()
+    }
+
+    /// Every tracked hash but those in `keep`, untracked and returned for
+    /// their tags to be deleted.
+    pub fn drain_except(&mut self, keep: &[Hash]) -> Vec<Hash> {
+        let (kept, gone): (BTreeMap<_, _>, BTreeMap<_, _>) = std::mem::take(&mut self.blobs)
+            .into_iter()
+            .partition(|(hex, _)| hex.parse::<Hash>().is_ok_and(|h| keep.contains(&h)));
+        self.blobs = kept;
+        gone.keys().filter_map(|hex| hex.parse().ok()).collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blobs.is_empty()
+    }
+}
+
+/// A scratch blob store in the temp dir, removed when dropped.
+#[derive(Debug)]
+pub struct TempStore {
+    path: PathBuf,
+    /// Locked for as long as we live; `None` once dropping.
+    lock: Option<File>,
+}
+
+impl TempStore {
+    /// Create this process's scratch store under `root` (the system temp
+    /// dir, outside tests) and lock it.
+    pub fn create(root: &Path) -> Result<Self> {
+        let path = root.join(format!("{PREFIX}{}", std::process::id()));
+        std::fs::create_dir_all(&path).storage()?;
+        let lock = File::create(path.join(LOCK)).storage()?;
+        lock.lock().storage()?;
+        Ok(Self { path, lock: Some(lock) })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempStore {
+    fn drop(&mut self) {
+        // Close the lock first: Windows won't delete an open file.
+        drop(self.lock.take());
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
+
+/// Remove the scratch stores under `root` left by processes that died
+/// without cleaning up. Returns how many were removed.
+pub fn sweep(root: &Path) -> usize {
+    let Ok(dirs) = std::fs::read_dir(root) else { return 0 };
+    let ours = format!("{PREFIX}{}", std::process::id());
+    dirs.flatten()
+        .filter(|d| {
+            let name = d.file_name().to_string_lossy().into_owned();
+            name.starts_with(PREFIX) && name != ours && d.file_type().is_ok_and(|t| t.is_dir())
+        })
+        .filter(|d| abandoned(&d.path()))
+        .filter(|d| std::fs::remove_dir_all(d.path()).is_ok())
+        .count()
+}
+
+/// Whether the scratch store at `dir` has no live owner: its lock can be
+/// taken, or it has no lock file and is older than `UNLOCKED_GRACE`.
+fn abandoned(dir: &Path) -> bool {
+    match File::open(dir.join(LOCK)) {
+        // The lock is released as `file` drops.
+        Ok(file) => file.try_lock().is_ok(),
+        Err(_) => std::fs::metadata(dir)
+            .and_then(|m| m.modified())
+            .is_ok_and(|modified| SystemTime::now().duration_since(modified).unwrap_or_default() > UNLOCKED_GRACE),
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn artifacts_roundtrip_and_drain() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("artifacts.toml");
+        let (paste, archive) = (Hash::new(b"paste"), Hash::new(b"archive"));
+        let mut artifacts = Artifacts::default();
+        artifacts.track(paste, "paste.txt");
+        artifacts.track(archive, "photos.tar");
+        artifacts.save_to(&path).unwrap();
+
+        let mut loaded = Artifacts::load_from(&path);
+        assert_eq!(loaded, artifacts);
+        assert_eq!(loaded.drain_except(&[archive]), vec![paste]);
+        assert!(loaded.untrack(&archive) && !loaded.untrack(&archive));
+        assert!(loaded.is_empty());
+    }
+
+    #[test]
+    fn sweep_takes_only_unlocked_stores() {
+        let root = tempfile::tempdir().unwrap();
+        let live = TempStore::create(root.path()).unwrap();
+        // A store from a process that died: its lock file is there, unheld.
+        let dead = root.path().join(format!("{PREFIX}999999999"));
+        std::fs::create_dir_all(&dead).unwrap();
+        File::create(dead.join(LOCK)).unwrap();
+        // A fresh store with no lock yet is left alone, as is anything else.
+        let fresh = root.path().join(format!("{PREFIX}999999998"));
+        std::fs::create_dir_all(&fresh).unwrap();
+        std::fs::create_dir_all(root.path().join("other")).unwrap();
+
+        assert_eq!(sweep(root.path()), 1);
+        assert!(!dead.exists() && fresh.exists() && live.path().exists());
+        let path = live.path().to_path_buf();
+        drop(live);
+        assert!(!path.exists());
+    }
+}
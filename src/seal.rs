@@ -0,0 +1,229 @@
+//! Sealed blobs: file content encrypted before it enters the blob store.
+//!
+//! A blob is addressed by its hash and served to whoever asks (subject to
+//! `access`), and it sits unencrypted in our store and in every store it
+//! was fetched into. With `seal_files = true` in the config we encrypt the
+//! content first, under a fresh random key per offer, so the blob is only
+//! ever ciphertext. The key travels in the offer itself
+//! (`Message::SealedFileOffer`), wrapped under a key derived from the
+//! room's topic, so only someone holding the room's ticket can open it.
+//!
+//! ## Format
+//!
+//! The content is cut into `CHUNK`-byte pieces, each sealed on its own with
+//! ChaCha20-Poly1305 (so a file never has to fit in memory to be sealed):
+//!
+//! ```text
+//! chunk 0 ciphertext + tag (16) · chunk 1 ciphertext + tag · … · last chunk
+//! ```
+//!
+//! The nonce is the chunk's index plus a flag marking the last chunk. A
+//! reordered chunk fails its tag, and so does a blob cut short at a chunk
+//! boundary, since its new last chunk wasn't sealed as the last. An empty
+//! file is one empty last chunk.
+//!
+//! The wrapped key is a random nonce (12) followed by the sealed 32-byte key
+//! and its tag (16).
+
+use std::io;
+use std::pin::Pin;
+
+use bytes::Bytes;
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use iroh_gossip::proto::TopicId;
+use n0_future::{Stream, StreamExt};
+
+/// Plaintext bytes per sealed chunk.
+pub const CHUNK: usize = 64 * 1024;
+const TAG_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+/// A per-offer content key.
+pub type ContentKey = [u8; 32];
+
+/// A fresh random content key.
+pub fn new_key() -> ContentKey {
+    rand::random()
+}
+
+/// Size of the sealed blob for `plain` bytes of content.
+pub fn sealed_size(plain: u64) -> u64 {
+    let chunks = plain.div_ceil(CHUNK as u64).max(1);
+    plain + chunks * TAG_LEN as u64
+}
+
+/// Nonce for chunk `index`: the index, then 1 if it's the last chunk.
+fn chunk_nonce(index: u64, last: bool) -> Nonce {
+    let mut nonce = [0; NONCE_LEN];
+    nonce[..8].copy_from_slice(&index.to_be_bytes());
+    nonce[8] = last as u8;
+    nonce.into()
+}
+
+fn seal_chunk(cipher: &ChaCha20Poly1305, index: u64, last: bool, plain: &[u8]) -> Vec<u8> {
+    cipher.encrypt(&chunk_nonce(index, last), plain).expect("a chunk is well under the AEAD limit")
+}
+
+/// Seal `plain` in memory.
+pub fn seal(key: &ContentKey, plain: &[u8]) -> Vec<u8> {
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(key));
+    let count = plain.len().div_ceil(CHUNK).max(1);
+    let mut out = Vec::with_capacity(sealed_size(plain.len() as u64) as usize);
+    for index in 0..count {
+        let chunk = &plain[(index * CHUNK).min(plain.len())..((index + 1) * CHUNK).min(plain.len())];
+        out.extend(seal_chunk(&cipher, index as u64, index + 1 == count, chunk));
+    }
+    out
+}
+
+/// Open a sealed blob. `None` if the key is wrong or the blob was tampered
+/// with or cut short.
+pub fn open(key: &ContentKey, sealed: &[u8]) -> Option<Vec<u8>> {
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(key));
+    let count = sealed.len().div_ceil(CHUNK + TAG_LEN).max(1);
+    let mut out = Vec::with_capacity(sealed.len());
+    for (index, chunk) in sealed.chunks(CHUNK + TAG_LEN).enumerate() {
+        out.extend(cipher.decrypt(&chunk_nonce(index as u64, index + 1 == count), chunk).ok()?);
+    }
+    // An empty input has no chunks at all, not even the empty last one.
+    (!sealed.is_empty()).then_some(out)
+}
+
+/// Seal a stream of content `size` bytes long as it goes by, for
+/// `add_stream`. The stream must yield exactly `size` bytes; ending early
+/// is an error.
+pub fn stream(
+    plain: impl Stream<Item = io::Result<Bytes>> + Send + Sync + 'static,
+    size: u64,
+    key: &ContentKey,
+) -> impl Stream<Item = io::Result<Bytes>> + Send + Sync + 'static {
+    struct State {
+        plain: Pin<Box<dyn Stream<Item = io::Result<Bytes>> + Send + Sync>>,
+        cipher: ChaCha20Poly1305,
+        buf: Vec<u8>,
+        index: u64,
+        /// Plain bytes not yet sealed.
+        remaining: u64,
+        done: bool,
+    }
+    let state = State {
+        plain: Box::pin(plain),
+        cipher: ChaCha20Poly1305::new(Key::from_slice(key)),
+        buf: Vec::new(),
+        index: 0,
+        remaining: size,
+        done: false,
+    };
+    n0_future::stream::unfold(state, |mut s| async move {
+        if s.done {
+            return None;
+        }
+        let want = s.remaining.min(CHUNK as u64) as usize;
+        while s.buf.len() < want {
+            match s.plain.next().await {
+                Some(Ok(bytes)) => s.buf.extend_from_slice(&bytes),
+                Some(Err(e)) => {
+                    s.done = true;
+                    return Some((Err(e), s));
+                }
+                None => {
+                    s.done = true;
+                    return Some((Err(io::Error::new(io::ErrorKind::UnexpectedEof, "content ended early")), s));
+                }
+            }
+        }
+        let chunk: Vec<u8> = s.buf.drain(..want).collect();
+        s.remaining -= want as u64;
+        s.done = s.remaining == 0;
+        let sealed = seal_chunk(&s.cipher, s.index, s.done, &chunk);
+        s.index += 1;
+        Some((Ok(Bytes::from(sealed)), s))
+    })
+}
+
+/// The key content keys are wrapped under in room `topic`.
+fn room_key(topic: &TopicId) -> ChaCha20Poly1305 {
+    let key = blake3::derive_key("piper-chat 2024 sealed blob key wrap", topic.as_bytes());
+    ChaCha20Poly1305::new(Key::from_slice(&key))
+}
+
+/// `key` wrapped for room `topic`, to put in an offer.
+pub fn wrap(topic: &TopicId, key: &ContentKey) -> Vec<u8> {
+    let nonce: [u8; NONCE_LEN] = rand::random();
+    let mut out = nonce.to_vec();
+    out.extend(room_key(topic).encrypt(&nonce.into(), key.as_slice()).expect("32 bytes always seal"));
+    out
+}
+
+/// The content key in an offer's wrapped key, if it was wrapped for `topic`.
+pub fn unwrap(topic: &TopicId, wrapped: &[u8]) -> Option<ContentKey> {
+    if wrapped.len() != NONCE_LEN + 32 + TAG_LEN {
+        return None;
+    }
+    let (nonce, sealed) = wrapped.split_at(NONCE_LEN);
+    let key = room_key(topic).decrypt(Nonce::from_slice(nonce), sealed).ok()?;
+    key.try_into().ok()
+}
+
+// ── Tests ────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seal_and_open_round_trip() {
+        let key = new_key();
+        for len in [0, 1, CHUNK - 1, CHUNK, CHUNK + 1, 3 * CHUNK + 7] {
+            let plain: Vec<u8> = (0..len).map(|i| i as u8).collect();
+            let sealed = seal(&key, &plain);
+            assert_eq!(sealed.len() as u64, sealed_size(len as u64), "len {len}");
+            if len > 0 {
+                assert_ne!(sealed[..len.min(64)], plain[..len.min(64)]);
+            }
+            assert_eq!(open(&key, &sealed), Some(plain), "len {len}");
+        }
+    }
+
+    #[test]
+    fn tampering_and_truncation_are_caught() {
+        let key = new_key();
+        let sealed = seal(&key, &vec![5; 2 * CHUNK + 10]);
+        assert_eq!(open(&new_key(), &sealed), None);
+        let mut flipped = sealed.clone();
+        flipped[100] ^= 1;
+        assert_eq!(open(&key, &flipped), None);
+        // Cut at a chunk boundary: the new last chunk wasn't sealed as last.
+        assert_eq!(open(&key, &sealed[..2 * (CHUNK + TAG_LEN)]), None);
+        assert_eq!(open(&key, &[]), None);
+    }
+
+    #[tokio::test]
+    async fn stream_matches_in_memory_sealing() {
+        let key = new_key();
+        let plain: Vec<u8> = (0..CHUNK * 2 + 300).map(|i| (i % 251) as u8).collect();
+        // Pieces that don't line up with chunks.
+        let pieces: Vec<io::Result<Bytes>> = plain.chunks(1000).map(|c| Ok(Bytes::copy_from_slice(c))).collect();
+        let sealed: Vec<u8> = stream(n0_future::stream::iter(pieces), plain.len() as u64, &key)
+            .map(|chunk| chunk.unwrap())
+            .collect::<Vec<_>>()
+            .await
+            .concat();
+        assert_eq!(open(&key, &sealed), Some(plain));
+
+        let short = stream(n0_future::stream::iter(vec![Ok(Bytes::from_static(b"abc"))]), 10, &key);
+        let results: Vec<_> = short.collect().await;
+        assert!(results.last().unwrap().is_err());
+    }
+
+    #[test]
+    fn keys_only_unwrap_in_their_room() {
+        let (room, other) = (TopicId::from_bytes([1; 32]), TopicId::from_bytes([2; 32]));
+        let key = new_key();
+        let wrapped = wrap(&room, &key);
+        assert_eq!(unwrap(&room, &wrapped), Some(key));
+        assert_eq!(unwrap(&other, &wrapped), None);
+        assert_eq!(unwrap(&room, &wrapped[1..]), None);
+    }
+}
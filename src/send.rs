@@ -0,0 +1,111 @@
+//! One-shot mode: `piper-chat send <ticket>` posts a message and exits.
+//!
+//! Meant for cron jobs and shell scripts ("build finished", "disk nearly
+//! full"). The sender joins the room, waits until it has at least one gossip
+//! neighbor (nobody would hear a broadcast before that), announces its
+//! nickname with `Join`, broadcasts each message as a `Chat`, and then waits
+//! for peers' `Ack`s — the same acks the chat uses for its direct-stream
+//! fallback — so the exit status says whether anyone actually got it.
+
+use std::collections::HashSet;
+use std::time::Duration;
+
+use anyhow::{Result, bail};
+use iroh_gossip::api::Event;
+use n0_future::StreamExt;
+
+use crate::net::{self, ChatTicket, Message};
+use crate::node::Node;
+
+/// Default time to wait for a neighbor, and then again for acks.
+pub const SEND_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Split script input into messages: the whole text as one message, or
+/// (`lines`) each non-blank line as its own. Trailing newlines are dropped
+/// either way; empty input yields no messages.
+pub fn messages_from(input: &str, lines: bool) -> Vec<String> {
+    if lines {
+        input
+            .lines()
+            .filter(|l| !l.trim().is_empty())
+            .map(str::to_string)
+            .collect()
+    } else {
+        let text = input.trim_end();
+        if text.trim().is_empty() { Vec::new() } else { vec![text.to_string()] }
+    }
+}
+
+/// Post `messages` to `ticket`'s room as `nickname`. Returns how many were
+/// acknowledged by at least one peer.
+///
+/// Fails if no neighbor turns up within `timeout`, or if none of the
+/// messages is acknowledged within `timeout` of being sent.
+pub async fn run(
+    node: &Node,
+    ticket: &ChatTicket,
+    nickname: &str,
+    messages: &[String],
+    timeout: Duration,
+) -> Result<usize> {
+    let (sender, mut receiver) = node.join(ticket).await?;
+    if tokio::time::timeout(timeout, receiver.joined()).await.is_err() {
+        bail!("no peers reachable within {}s", timeout.as_secs());
+    }
+
+    sender
+        .broadcast(&Message::Join {
+            nickname: nickname.to_string(),
+            endpoint_id: node.endpoint.id(),
+        })
+        .await?;
+    let mut unacked = HashSet::new();
+    for text in messages {
+        let message_id = net::new_message_id();
+        sender
+            .broadcast(&Message::Chat {
+                nickname: nickname.to_string(),
+                text: text.clone(),
+                message_id,
+                timestamp_ms: net::now_ms(),
+            })
+            .await?;
+        unacked.insert(message_id);
+    }
+
+    // Collect acks until every message has one or the timeout passes.
+    let wait = async {
+        while !unacked.is_empty() {
+            let Some(event) = receiver.try_next().await? else { break };
+            if let Event::Received(m) = event
+                && let Ok((_, Message::Ack { message_id, .. })) = net::proto::decode(&m.content)
+            {
+                unacked.remove(&message_id);
+            }
+        }
+        anyhow::Ok(())
+    };
+    if let Ok(result) = tokio::time::timeout(timeout, wait).await {
+        result?;
+    }
+    let acked = messages.len() - unacked.len();
+    if acked == 0 && !messages.is_empty() {
+        bail!("no peer acknowledged the message within {}s", timeout.as_secs());
+    }
+    Ok(acked)
+}
+
+// ── Tests ────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn whole_input_or_lines() {
+        assert_eq!(messages_from("deploy done\n", false), ["deploy done"]);
+        assert_eq!(messages_from("a\n\nb\n", false), ["a\n\nb"]);
+        assert_eq!(messages_from("a\n\n  \nb\n", true), ["a", "b"]);
+        assert!(messages_from(" \n", false).is_empty());
+    }
+}
@@ -0,0 +1,217 @@
+//! Session state saved while chatting, for recovery after a crash.
+//!
+//! The history journal (`journal`) brings back what was said, but not where
+//! we were or what the file pane held. So while the chat runs, a small
+//! `Session` is kept on disk next to the archive (`session.bin`): the room's
+//! ticket, our nickname, and every offer in the file pane with its state.
+//! The main loop rewrites it whenever that changes; a clean exit removes it.
+//!
+//! A session file found at startup therefore means the last run never got
+//! to exit cleanly. The welcome screen is preceded by an offer to go back
+//! (`welcome::run_recover_screen`); going back rejoins the room, puts the
+//! offers back in the file pane, and picks up the downloads that were
+//! running. The blob store keeps what had arrived, so a resumed download
+//! only fetches the missing chunks.
+//!
+//! The file is written to a temporary name and renamed over the old one, so
+//! a crash mid-write leaves the previous session rather than a torn one.
+//! With `PIPER_CHAT_PASSPHRASE` set, sealed offers are left out: their
+//! content keys would otherwise sit on disk unencrypted.
+
+use std::path::{Path, PathBuf};
+
+use iroh::EndpointId;
+use iroh_blobs::Hash;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Result, ResultExt};
+use crate::net::MessageId;
+use crate::seal::ContentKey;
+use crate::transfer::{EntryId, FileOffer, TransferManager, TransferState};
+
+const FILE: &str = "session.bin";
+
+/// Where a saved transfer had got to.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum SavedState {
+    /// Offered to us, not downloaded (or failed, to be retried).
+    Pending,
+    /// Cut off mid-download with this many bytes in.
+    Downloading { bytes_received: u64 },
+    Complete(PathBuf),
+    /// One of ours, still being served from the blob store.
+    Sharing,
+}
+
+/// One file pane entry.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedTransfer {
+    pub sender_nickname: String,
+    pub sender_id: EndpointId,
+    pub filename: String,
+    pub size: u64,
+    pub hash: Hash,
+    pub key: Option<ContentKey>,
+    pub offer_id: MessageId,
+    pub state: SavedState,
+}
+
+impl SavedTransfer {
+    fn offer(&self) -> FileOffer {
+        FileOffer {
+            sender_nickname: self.sender_nickname.clone(),
+            sender_id: self.sender_id,
+            filename: self.filename.clone(),
+            size: self.size,
+            hash: self.hash,
+            key: self.key,
+            offer_id: self.offer_id,
+        }
+    }
+}
+
+/// What the last run was doing.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    /// Ticket to rejoin the room with.
+    pub ticket: String,
+    pub nickname: String,
+    pub room_name: Option<String>,
+    pub transfers: Vec<SavedTransfer>,
+}
+
+impl Session {
+    /// Capture the file pane of `transfers`. Packing folders are dropped
+    /// (the pack starts over from `/sendfile`), and so are sealed offers
+    /// unless `keep_keys`.
+    pub fn capture(ticket: String, nickname: &str, room_name: Option<String>, transfers: &TransferManager, keep_keys: bool) -> Self {
+        let transfers = transfers
+            .entries
+            .iter()
+            .filter(|e| keep_keys || e.offer.key.is_none())
+            .filter_map(|e| {
+                let state = match &e.state {
+                    TransferState::Pending | TransferState::Failed(_) => SavedState::Pending,
+                    TransferState::Downloading { bytes_received, .. } => SavedState::Downloading { bytes_received: *bytes_received },
+                    TransferState::Complete(path) => SavedState::Complete(path.clone()),
+                    TransferState::Sharing => SavedState::Sharing,
+                    TransferState::Packing { .. } | TransferState::Invalidated(_) => return None,
+                };
+                let o = &e.offer;
+                Some(SavedTransfer {
+                    sender_nickname: o.sender_nickname.clone(),
+                    sender_id: o.sender_id,
+                    filename: o.filename.clone(),
+                    size: o.size,
+                    hash: o.hash,
+                    key: o.key,
+                    offer_id: o.offer_id,
+                    state,
+                })
+            })
+            .collect();
+        Self { ticket, nickname: nickname.to_string(), room_name, transfers }
+    }
+
+    /// Downloads that were cut off.
+    pub fn interrupted(&self) -> usize {
+        self.transfers.iter().filter(|t| matches!(t.state, SavedState::Downloading { .. })).count()
+    }
+
+    /// Put the saved entries into `manager`'s file pane, updating entries
+    /// already there (offers replayed from the history) rather than adding
+    /// them twice. Returns the entries of the downloads to resume.
+    pub fn restore(&self, manager: &mut TransferManager) -> Vec<EntryId> {
+        let mut resume = Vec::new();
+        for saved in &self.transfers {
+            let id = saved.offer().entry_id();
+            if manager.entry(&id).is_none() {
+                match saved.state {
+                    SavedState::Sharing => manager.add_sent(saved.offer()),
+                    _ => manager.add_offer(saved.offer()),
+                }
+            }
+            match &saved.state {
+                SavedState::Complete(path) => manager.complete_download(&id, path.clone()),
+                SavedState::Downloading { .. } => resume.push(id),
+                SavedState::Pending | SavedState::Sharing => {}
+            }
+        }
+        resume
+    }
+
+    /// Load the session left in `dir`, if there is one that parses.
+    pub fn load(dir: &Path) -> Option<Self> {
+        let bytes = std::fs::read(dir.join(FILE)).ok()?;
+        postcard::from_bytes(&bytes).ok()
+    }
+
+    /// Save into `dir`, replacing the previous session atomically.
+    pub fn save(&self, dir: &Path) -> Result<()> {
+        std::fs::create_dir_all(dir).storage()?;
+        let tmp = dir.join(format!("{FILE}.tmp"));
+        std::fs::write(&tmp, postcard::to_allocvec(self).storage()?).storage()?;
+        std::fs::rename(&tmp, dir.join(FILE)).storage()?;
+        Ok(())
+    }
+}
+
+/// Remove the session from `dir`: the run ended cleanly, or the user chose
+/// not to go back to it.
+pub fn clear(dir: &Path) -> Result<()> {
+    match std::fs::remove_file(dir.join(FILE)) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e).storage(),
+        _ => Ok(()),
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn offer(byte: u8, key: Option<ContentKey>) -> FileOffer {
+        FileOffer {
+            sender_nickname: "Bob".into(),
+            sender_id: EndpointId::from_bytes(&[1u8; 32]).unwrap(),
+            filename: format!("file{byte}.txt"),
+            size: 1000,
+            hash: Hash::from_bytes([byte; 32]),
+            key,
+            offer_id: [byte; 16],
+        }
+    }
+
+    #[test]
+    fn saved_transfers_come_back_with_their_state() {
+        let mut before = TransferManager::new();
+        before.add_offer(offer(1, None));
+        before.add_offer(offer(2, None));
+        before.start_download(&offer(2, None).entry_id());
+        before.update_progress(&offer(2, None).entry_id(), 400, 1000);
+        before.add_offer(offer(3, None));
+        before.complete_download(&offer(3, None).entry_id(), PathBuf::from("/tmp/file3.txt"));
+        before.add_sent(offer(4, None));
+        before.add_offer(offer(5, Some([9; 32])));
+
+        let dir = tempfile::tempdir().unwrap();
+        Session::capture("chatabc".into(), "Alice", None, &before, false).save(dir.path()).unwrap();
+        let session = Session::load(dir.path()).unwrap();
+        assert_eq!((session.ticket.as_str(), session.interrupted()), ("chatabc", 1));
+        // The sealed offer isn't kept without `keep_keys`.
+        assert_eq!(session.transfers.len(), 4);
+
+        // Offer 1 is already back from the history; the rest are added.
+        let mut after = TransferManager::new();
+        after.add_offer(offer(1, None));
+        assert_eq!(session.restore(&mut after), vec![offer(2, None).entry_id()]);
+        assert_eq!(after.entries.len(), 4);
+        assert!(matches!(after.entries[2].state, TransferState::Complete(_)));
+        assert!(matches!(after.entries[3].state, TransferState::Sharing));
+
+        clear(dir.path()).unwrap();
+        assert_eq!(Session::load(dir.path()), None);
+        clear(dir.path()).unwrap();
+    }
+}
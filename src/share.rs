@@ -0,0 +1,192 @@
+//! File drop mode: `piper-chat share <ticket> <path>` offers one file to a
+//! room from a script.
+//!
+//! The sharer joins the room, imports the file into a temporary blob store,
+//! broadcasts the `FileOffer`, and then stays online serving it — printing a
+//! line to stdout as each peer starts and finishes a download — until the
+//! requested number of downloads complete or the timeout passes.
+//!
+//! Downloads are counted from the blobs protocol's own provider events (see
+//! `Node::spawn_with_events`), so a completed download means the bytes left
+//! this machine, not just that someone clicked. Peers who join after the
+//! offer went out are sent it directly over `net::direct`, since gossip
+//! would drop a re-broadcast of identical bytes as a duplicate.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::{Result, bail};
+use iroh::EndpointId;
+use iroh_blobs::provider::events::{ProviderMessage, RequestUpdate};
+use iroh_gossip::api::Event;
+use n0_future::StreamExt;
+use tokio::sync::mpsc;
+
+use crate::metrics::METRICS;
+use crate::net::{self, ChatTicket, Message};
+use crate::node::{self, Node, OfferOptions, Shared};
+use crate::transfer::format_file_size;
+
+/// Default time to keep serving before giving up on more downloads.
+pub const SHARE_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// How one request for the shared blob ended up, reported per connection.
+enum Served {
+    Started,
+    Completed,
+    Aborted,
+}
+
+/// Share `path` with `ticket`'s room as `nickname` and serve it until
+/// `downloads` complete or `timeout` passes. Returns the number of completed
+/// downloads.
+///
+/// `events` is the receiver from `Node::spawn_with_events` for `node`.
+/// Fails if no neighbor turns up within `timeout`.
+pub async fn run(
+    node: &Node,
+    mut events: mpsc::Receiver<ProviderMessage>,
+    ticket: &ChatTicket,
+    nickname: &str,
+    path: &Path,
+    downloads: usize,
+    timeout: Duration,
+) -> Result<usize> {
+    let deadline = tokio::time::Instant::now() + timeout;
+    let (sender, mut receiver) = node.join(ticket).await?;
+    if tokio::time::timeout_at(deadline, receiver.joined()).await.is_err() {
+        bail!("no peers reachable within {}s", timeout.as_secs());
+    }
+
+    let our_id = node.endpoint.id();
+    sender
+        .broadcast(&Message::Join {
+            nickname: nickname.to_string(),
+            endpoint_id: our_id,
+        })
+        .await?;
+    let Shared { hash, filename, size, message_id, timestamp_ms, mime_type, reused } =
+        node::share_file(&node.blob_store, &sender, nickname, our_id, path, OfferOptions::default()).await?;
+    println!("offered {filename} ({}) as {hash}", format_file_size(size));
+    if reused {
+        println!("already in store — instant share");
+    }
+    // Kept to hand to late joiners.
+    let offer = sender.encode(&Message::FileOffer {
+        nickname: nickname.to_string(),
+        endpoint_id: our_id,
+        filename,
+        size,
+        hash: *hash.as_bytes(),
+        message_id,
+        timestamp_ms,
+        mime_type,
+        target: None,
+        offer_id: message_id,
+    })?;
+
+    let (served_tx, mut served_rx) = mpsc::channel::<(u64, Served)>(16);
+    // Blob connections by ID, and nicknames from `Join`s, to say who's downloading.
+    let mut connections: HashMap<u64, EndpointId> = HashMap::new();
+    let mut names: HashMap<EndpointId, String> = HashMap::new();
+    let mut done = 0;
+    let expired = tokio::time::sleep_until(deadline);
+    tokio::pin!(expired);
+
+    while done < downloads {
+        tokio::select! {
+            _ = &mut expired => {
+                println!("timed out after {done} of {downloads} downloads");
+                break;
+            }
+            Some(event) = events.recv() => match event {
+                ProviderMessage::ClientConnectedNotify(msg) => {
+                    if let Some(id) = msg.inner.endpoint_id {
+                        connections.insert(msg.inner.connection_id, id);
+                    }
+                }
+                ProviderMessage::GetRequestReceivedNotify(msg) if msg.inner.request.hash == hash => {
+                    // Each request streams its own updates; follow them in
+                    // the background and report back by connection.
+                    let connection_id = msg.inner.connection_id;
+                    let mut updates = msg.rx;
+                    let tx = served_tx.clone();
+                    tokio::spawn(async move {
+                        while let Ok(Some(update)) = updates.recv().await {
+                            let served = match update {
+                                RequestUpdate::Started(_) => Served::Started,
+                                RequestUpdate::Completed(done) => {
+                                    METRICS.sent(done.stats.payload_bytes_sent);
+                                    Served::Completed
+                                }
+                                RequestUpdate::Aborted(aborted) => {
+                                    METRICS.sent(aborted.stats.payload_bytes_sent);
+                                    Served::Aborted
+                                }
+                                RequestUpdate::Progress(_) => continue,
+                            };
+                            let _ = tx.send((connection_id, served)).await;
+                        }
+                    });
+                }
+                _ => {}
+            },
+            Some((connection_id, served)) = served_rx.recv() => {
+                let who = peer_label(connections.get(&connection_id), &names);
+                match served {
+                    Served::Started => println!("{who}: downloading"),
+                    Served::Completed => {
+                        done += 1;
+                        println!("{who}: done ({done}/{downloads})");
+                    }
+                    Served::Aborted => println!("{who}: aborted"),
+                }
+            }
+            event = receiver.try_next() => match event? {
+                Some(Event::Received(m)) => {
+                    if let Ok((from, Message::Join { nickname, .. })) = net::proto::decode(&m.content) {
+                        names.insert(from, nickname);
+                        let endpoint = node.endpoint.clone();
+                        let offer = offer.clone();
+                        tokio::spawn(async move {
+                            let _ = net::direct::send(&endpoint, from, &offer).await;
+                        });
+                    }
+                }
+                Some(_) => {}
+                None => bail!("left the room unexpectedly"),
+            },
+        }
+    }
+    Ok(done)
+}
+
+/// How to refer to the peer behind a blob connection: its nickname if it
+/// announced one, else its short ID.
+fn peer_label(id: Option<&EndpointId>, names: &HashMap<EndpointId, String>) -> String {
+    match id {
+        Some(id) => names
+            .get(id)
+            .cloned()
+            .unwrap_or_else(|| id.fmt_short().to_string()),
+        None => "a peer".to_string(),
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn labels_prefer_nicknames() {
+        let bob = EndpointId::from_bytes(&[1u8; 32]).unwrap();
+        let carol = EndpointId::from_bytes(&[3u8; 32]).unwrap();
+        let names = HashMap::from([(bob, "Bob".to_string())]);
+        assert_eq!(peer_label(Some(&bob), &names), "Bob");
+        assert_eq!(peer_label(Some(&carol), &names), carol.fmt_short().to_string());
+        assert_eq!(peer_label(None, &names), "a peer");
+    }
+}
@@ -0,0 +1,173 @@
+//! Spellcheck for the input bar.
+//!
+//! A `Dictionary` is a plain word list — one word per line, the format of
+//! `/usr/share/dict/words` — loaded into a `HashSet` for O(1) lookups. No
+//! stemming or suggestions: the goal is only to underline words that aren't
+//! in the list while you type. Sent messages are never checked.
+//!
+//! Segmentation works in two passes. The input is first split on whitespace
+//! into tokens, and tokens that aren't prose (`/commands`, `@mentions`, URLs,
+//! paths, email addresses) are skipped whole. The rest are split into words
+//! with Unicode word boundaries (`unicode-segmentation`), which keeps
+//! contractions like `don't` together and drops surrounding punctuation.
+
+use std::collections::HashSet;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Word lists tried, in order, when the config doesn't name one.
+const SYSTEM_DICTIONARIES: &[&str] = &["/usr/share/dict/words", "/usr/dict/words"];
+
+/// Chat vocabulary that no system word list contains, always accepted.
+const CHAT_WORDS: &[&str] = &[
+    "afk", "brb", "btw", "dm", "dms", "fyi", "gg", "hmm", "idk", "imo", "irl", "lgtm", "lol",
+    "np", "ok", "omg", "pls", "thx", "tl", "dr", "ty", "wip", "yeah", "yep", "nope",
+];
+
+/// A set of known-good words.
+pub struct Dictionary {
+    words: HashSet<String>,
+}
+
+impl Dictionary {
+    /// Build a dictionary from newline-separated words. Words are stored
+    /// lowercased, so lookups are case-insensitive.
+    pub fn from_words(text: &str) -> Self {
+        let words = text
+            .lines()
+            .map(str::trim)
+            .filter(|w| !w.is_empty())
+            .map(str::to_lowercase)
+            .chain(CHAT_WORDS.iter().map(|w| w.to_string()))
+            .collect();
+        Self { words }
+    }
+
+    /// Load the word list at `custom` if given, otherwise the first system
+    /// word list that exists. `None` if nothing could be read — spellcheck
+    /// then stays off rather than underlining every word.
+    pub fn load(custom: Option<&Path>) -> Option<Self> {
+        let candidates: Vec<PathBuf> = match custom {
+            Some(path) => vec![path.to_path_buf()],
+            None => SYSTEM_DICTIONARIES.iter().map(PathBuf::from).collect(),
+        };
+        candidates
+            .iter()
+            .find_map(|p| std::fs::read_to_string(p).ok())
+            .map(|text| Self::from_words(&text))
+    }
+
+    /// Whether `word` is spelled correctly.
+    ///
+    /// ALL-CAPS words are taken as acronyms and accepted, and a trailing
+    /// possessive `'s` is ignored (`Alice's` checks as `Alice`).
+    pub fn is_correct(&self, word: &str) -> bool {
+        if word.chars().count() > 1 && !word.chars().any(char::is_lowercase) {
+            return true;
+        }
+        let lower = word.to_lowercase();
+        let lower = lower.replace('’', "'");
+        self.words.contains(&lower)
+            || lower.strip_suffix("'s").is_some_and(|stem| self.words.contains(stem))
+    }
+
+    /// Byte ranges within `text` of words that are misspelled.
+    pub fn misspelled(&self, text: &str) -> Vec<Range<usize>> {
+        let mut ranges = Vec::new();
+        for (start, token) in tokens(text) {
+            if !is_prose(token) {
+                continue;
+            }
+            for (offset, word) in token.unicode_word_indices() {
+                // Numbers, hex ids, `v2` and the like aren't words.
+                if !word.chars().all(|c| c.is_alphabetic() || c == '\'' || c == '’') {
+                    continue;
+                }
+                if !self.is_correct(word) {
+                    ranges.push(start + offset..start + offset + word.len());
+                }
+            }
+        }
+        ranges
+    }
+}
+
+/// Whitespace-separated tokens of `text` with their byte offsets.
+fn tokens(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    text.split_whitespace()
+        // `split_whitespace` yields subslices of `text`, so pointer
+        // arithmetic recovers each token's byte offset.
+        .map(move |t| (t.as_ptr() as usize - text.as_ptr() as usize, t))
+}
+
+/// Whether a token reads as prose rather than a command, mention, URL,
+/// path, or email address.
+fn is_prose(token: &str) -> bool {
+    !(token.starts_with('/')
+        || token.starts_with('@')
+        || token.starts_with('#')
+        || token.starts_with('~')
+        || token.contains("://")
+        || token.starts_with("www.")
+        || token.contains('@')
+        || token.trim_end_matches(['/', '.', ',', '!', '?', ')']).contains(['/', '\\', '.', '_']))
+}
+
+// ── Tests ────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dict() -> Dictionary {
+        Dictionary::from_words("hello\nworld\nthe\nquick\nfox\ndon't\nAlice\n")
+    }
+
+    fn words<'a>(text: &'a str, dict: &Dictionary) -> Vec<&'a str> {
+        dict.misspelled(text).into_iter().map(|r| &text[r]).collect()
+    }
+
+    #[test]
+    fn flags_unknown_words() {
+        let d = dict();
+        assert_eq!(words("hello wrold, the quikc fox!", &d), ["wrold", "quikc"]);
+    }
+
+    #[test]
+    fn case_contractions_and_possessives() {
+        let d = dict();
+        assert!(d.is_correct("Hello"));
+        assert!(d.is_correct("don't"));
+        assert!(d.is_correct("don’t"));
+        assert!(d.is_correct("Alice's"));
+        assert!(d.is_correct("NASA"));
+        assert!(!d.is_correct("Helo"));
+    }
+
+    #[test]
+    fn skips_non_prose_tokens() {
+        let d = dict();
+        let text = "/sendto @carol https://example.com me@x.org src/main.rs v2 1234 lol";
+        assert!(d.misspelled(text).is_empty());
+    }
+
+    #[test]
+    fn ranges_are_byte_offsets() {
+        let d = dict();
+        // Multi-byte characters before the word shift the byte offset.
+        let text = "fox — wrold";
+        assert_eq!(d.misspelled(text), vec![8..13]);
+    }
+
+    #[test]
+    fn load_reads_custom_list() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("words");
+        std::fs::write(&path, "zebra\n").unwrap();
+        let d = Dictionary::load(Some(&path)).unwrap();
+        assert!(d.is_correct("zebra"));
+        assert!(Dictionary::load(Some(&dir.path().join("missing"))).is_none());
+    }
+}
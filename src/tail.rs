@@ -0,0 +1,181 @@
+//! Watch mode: `piper-chat tail <ticket>` prints a room to stdout.
+//!
+//! The watcher joins the room's gossip topic but never broadcasts — no
+//! `Join`, no acks, no history offers — so it's invisible in the chat apart
+//! from being a gossip neighbor. Each chat message, join, and file offer
+//! becomes one line on stdout, either plain text or (`--json`) one JSON
+//! object per line, so the output can go straight into `grep`, a log file,
+//! or a script.
+//!
+//! A watcher uses a throwaway identity and blob store, so it can run next to
+//! a chat session on the same machine.
+
+use std::io::Write;
+
+use anyhow::Result;
+use iroh::EndpointId;
+use iroh_gossip::api::Event;
+use n0_future::StreamExt;
+use serde_json::json;
+
+use crate::chat::format_timestamp;
+use crate::net::{self, ChatTicket, Message};
+use crate::node::Node;
+use crate::transfer::format_file_size;
+
+/// Watch `ticket`'s room until the subscription ends or stdout closes
+/// (e.g. the reader of a pipe exits).
+pub async fn run(node: &Node, ticket: &ChatTicket, json: bool) -> Result<()> {
+    // The sender half is kept but never used: dropping it would leave the topic.
+    let (_sender, mut receiver) = node.join(ticket).await?;
+    while let Some(event) = receiver.try_next().await? {
+        let Event::Received(message) = event else { continue };
+        // Invalid or forged messages are skipped, as in the chat.
+        let Ok((from, msg)) = net::proto::decode(&message.content) else { continue };
+        if let Some(line) = format_line(from, &msg, net::now_ms(), json)
+            && writeln!(std::io::stdout(), "{line}").is_err()
+        {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// One output line for `msg` signed by `from`, or `None` for protocol
+/// traffic (acks, roll calls, history) and offers addressed to someone else.
+///
+/// Plain lines look like the chat: `12:34 Bob: hi`. Newlines in a message
+/// are written as `\n` so every message stays on one line. Spoilers stay
+/// hidden in plain output; the JSON form carries their text with
+/// `"kind": "spoiler"` and leaves the choice to the reader. `now_ms` stamps
+/// joins, which carry no time of their own.
+pub fn format_line(from: EndpointId, msg: &Message, now_ms: u64, json: bool) -> Option<String> {
+    let from = from.to_string();
+    let line = match msg {
+        Message::Chat { nickname, text, timestamp_ms, .. } => {
+            if json {
+                json!({ "kind": "chat", "timestamp_ms": timestamp_ms, "from": from,
+                        "nickname": nickname, "text": text })
+                .to_string()
+            } else {
+                let text = text.replace('\n', "\\n");
+                format!("{} {nickname}: {text}", format_timestamp(*timestamp_ms))
+            }
+        }
+        // Thread replies are marked; JSON carries the root's ID in hex.
+        Message::ThreadChat { nickname, text, timestamp_ms, thread_id, .. } => {
+            if json {
+                let thread: String = thread_id.iter().map(|b| format!("{b:02x}")).collect();
+                json!({ "kind": "thread", "timestamp_ms": timestamp_ms, "from": from,
+                        "nickname": nickname, "text": text, "thread": thread })
+                .to_string()
+            } else {
+                let text = text.replace('\n', "\\n");
+                format!("{} ↳ {nickname}: {text}", format_timestamp(*timestamp_ms))
+            }
+        }
+        // Quote-replies carry the quoted message's ID in hex; the watcher
+        // keeps no log to resolve it against.
+        Message::Reply { nickname, text, timestamp_ms, reply_to, .. } => {
+            if json {
+                let reply_to: String = reply_to.iter().map(|b| format!("{b:02x}")).collect();
+                json!({ "kind": "reply", "timestamp_ms": timestamp_ms, "from": from,
+                        "nickname": nickname, "text": text, "reply_to": reply_to })
+                .to_string()
+            } else {
+                let text = text.replace('\n', "\\n");
+                format!("{} {nickname} (re): {text}", format_timestamp(*timestamp_ms))
+            }
+        }
+        Message::Timer { nickname, label, ends_ms, .. } => {
+            if json {
+                json!({ "kind": "timer", "timestamp_ms": now_ms, "from": from,
+                        "nickname": nickname, "label": label, "ends_ms": ends_ms })
+                .to_string()
+            } else {
+                format!("{} {nickname} set a timer until {}: {label}", format_timestamp(now_ms), format_timestamp(*ends_ms))
+            }
+        }
+        Message::Spoiler { nickname, text, timestamp_ms, .. } => {
+            if json {
+                json!({ "kind": "spoiler", "timestamp_ms": timestamp_ms, "from": from,
+                        "nickname": nickname, "text": text })
+                .to_string()
+            } else {
+                format!("{} {nickname}: [spoiler]", format_timestamp(*timestamp_ms))
+            }
+        }
+        Message::Join { nickname, .. } => {
+            if json {
+                json!({ "kind": "join", "timestamp_ms": now_ms, "from": from, "nickname": nickname })
+                    .to_string()
+            } else {
+                format!("{} * {nickname} joined", format_timestamp(now_ms))
+            }
+        }
+        Message::FileOffer { nickname, filename, size, hash, timestamp_ms, target: None, .. }
+        | Message::SealedFileOffer { nickname, filename, size, hash, timestamp_ms, target: None, .. } => {
+            if json {
+                json!({ "kind": "file", "timestamp_ms": timestamp_ms, "from": from,
+                        "nickname": nickname, "filename": filename, "size": size,
+                        "hash": iroh_blobs::Hash::from_bytes(*hash).to_string() })
+                .to_string()
+            } else {
+                format!(
+                    "{} * {nickname} shared {filename} ({})",
+                    format_timestamp(*timestamp_ms),
+                    format_file_size(*size)
+                )
+            }
+        }
+        _ => return None,
+    };
+    Some(line)
+}
+
+// ── Tests ────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chat(text: &str) -> Message {
+        Message::Chat {
+            nickname: "Bob".into(),
+            text: text.into(),
+            message_id: [1; 16],
+            // 12:34 UTC on day zero.
+            timestamp_ms: (12 * 3600 + 34 * 60) * 1000,
+        }
+    }
+
+    #[test]
+    fn plain_lines_are_single_line() {
+        let from = EndpointId::from_bytes(&[1u8; 32]).unwrap();
+        assert_eq!(
+            format_line(from, &chat("hi\nthere"), 0, false).unwrap(),
+            "12:34 Bob: hi\\nthere"
+        );
+        let spoiler = Message::Spoiler {
+            nickname: "Bob".into(),
+            text: "secret".into(),
+            message_id: [2; 16],
+            timestamp_ms: 0,
+        };
+        assert_eq!(format_line(from, &spoiler, 0, false).unwrap(), "00:00 Bob: [spoiler]");
+        let ack = Message::Ack { message_id: [1; 16], endpoint_id: from };
+        assert!(format_line(from, &ack, 0, false).is_none());
+    }
+
+    #[test]
+    fn json_lines_parse_back() {
+        let from = EndpointId::from_bytes(&[1u8; 32]).unwrap();
+        let line = format_line(from, &chat("hi\nthere"), 0, true).unwrap();
+        assert!(!line.contains('\n'));
+        let value: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(value["kind"], "chat");
+        assert_eq!(value["nickname"], "Bob");
+        assert_eq!(value["text"], "hi\nthere");
+        assert_eq!(value["from"], from.to_string());
+    }
+}
@@ -2,13 +2,41 @@
 //!
 //! Defines a `Theme` struct with named color slots for every semantic role used
 //! across the UI. Two palettes are provided — dark (default) and light — and a
-//! runtime toggle switches between them with Ctrl+T.
+//! runtime toggle switches between them with Ctrl+T, on the welcome screens
+//! as in the chat. The choice is saved as `theme` in the config file, so the
+//! next start opens in it.
+//!
+//! The theme also says whether to draw with ASCII only (`--ascii`, or a
+//! non-UTF-8 locale): box-drawing borders, block progress bars and symbols
+//! like ✓ show as tofu on some terminals and fonts. Renderers ask the theme
+//! for borders (`border_set`) and symbols (`glyph`), and system text goes
+//! through `text`, which transliterates the few non-ASCII characters the UI
+//! itself uses. What peers type is never rewritten.
 
-use ratatui::style::Color;
+use std::borrow::Cow;
 
-/// Which palette is currently active.
-#[derive(Debug, Clone, Copy, PartialEq)]
+use ratatui::style::Color;
+use ratatui::symbols::border;
+use serde::{Deserialize, Serialize};
+
+/// Borders drawn with `+`, `-` and `|`.
+pub const ASCII_BORDER: border::Set = border::Set {
+    top_left: "+",
+    top_right: "+",
+    bottom_left: "+",
+    bottom_right: "+",
+    vertical_left: "|",
+    vertical_right: "|",
+    horizontal_top: "-",
+    horizontal_bottom: "-",
+};
+
+/// Which palette is currently active (`theme = "dark"` or `"light"` in
+/// the config file).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum ThemeMode {
+    #[default]
     Dark,
     Light,
 }
@@ -20,6 +48,8 @@ pub enum ThemeMode {
 /// at runtime.
 pub struct Theme {
     pub mode: ThemeMode,
+    /// Draw with ASCII only. Kept across palette toggles.
+    pub ascii: bool,
 
     // ── Background ─────────────────────────────────────────────────────
     pub bg: Color,
@@ -62,6 +92,7 @@ pub struct Theme {
 
     // ── Status / hints ───────────────────────────────────────────────────
     pub error: Color,
+    pub success: Color,
     pub hint_key: Color,
     pub hint_text: Color,
 
@@ -77,6 +108,21 @@ pub struct Theme {
 
     // ── Timestamps ───────────────────────────────────────────────────────
     pub timestamp: Color,
+
+    // ── Code highlighting (fenced blocks in messages) ────────────────────
+    pub code_keyword: Color,
+    pub code_string: Color,
+    pub code_comment: Color,
+    pub code_number: Color,
+
+    // ── QR code ──────────────────────────────────────────────────────────
+    // Scanners need dark modules on a light background regardless of the
+    // UI palette, so both themes use pure black on white.
+    pub qr_dark: Color,
+    pub qr_light: Color,
+
+    // ── Message selection ────────────────────────────────────────────────
+    pub selection_bg: Color,
 }
 
 impl Theme {
@@ -84,6 +130,7 @@ impl Theme {
     pub fn dark() -> Self {
         Self {
             mode: ThemeMode::Dark,
+            ascii: false,
 
             bg: Color::Rgb(25, 20, 35),
 
@@ -117,6 +164,7 @@ impl Theme {
             transfer_sharing: Color::Rgb(140, 120, 220),
 
             error: Color::Rgb(255, 100, 100),
+            success: Color::Rgb(100, 220, 100),
             hint_key: Color::Rgb(140, 200, 140),
             hint_text: Color::Rgb(120, 115, 130),
 
@@ -129,6 +177,16 @@ impl Theme {
             cursor_blink: Color::Rgb(100, 100, 110),
 
             timestamp: Color::Rgb(100, 100, 110),
+
+            code_keyword: Color::Rgb(255, 140, 200),
+            code_string: Color::Rgb(150, 220, 140),
+            code_comment: Color::Rgb(120, 115, 130),
+            code_number: Color::Rgb(230, 190, 110),
+
+            qr_dark: Color::Black,
+            qr_light: Color::White,
+
+            selection_bg: Color::Rgb(55, 45, 80),
         }
     }
 
@@ -136,6 +194,7 @@ impl Theme {
     pub fn light() -> Self {
         Self {
             mode: ThemeMode::Light,
+            ascii: false,
 
             bg: Color::Rgb(240, 236, 245),
 
@@ -169,6 +228,7 @@ impl Theme {
             transfer_sharing: Color::Rgb(100, 60, 180),
 
             error: Color::Rgb(200, 40, 40),
+            success: Color::Rgb(30, 140, 30),
             hint_key: Color::Rgb(30, 140, 30),
             hint_text: Color::Rgb(140, 130, 150),
 
@@ -181,14 +241,140 @@ impl Theme {
             cursor_blink: Color::Rgb(140, 130, 150),
 
             timestamp: Color::Rgb(140, 130, 150),
+
+            code_keyword: Color::Rgb(170, 40, 120),
+            code_string: Color::Rgb(30, 120, 40),
+            code_comment: Color::Rgb(140, 130, 150),
+            code_number: Color::Rgb(160, 100, 20),
+
+            qr_dark: Color::Black,
+            qr_light: Color::White,
+
+            selection_bg: Color::Rgb(220, 210, 240),
         }
     }
 
+    /// The `mode` palette, drawn with ASCII only if `ascii`.
+    pub fn new(mode: ThemeMode, ascii: bool) -> Self {
+        let palette = match mode {
+            ThemeMode::Dark => Self::dark(),
+            ThemeMode::Light => Self::light(),
+        };
+        Self { ascii, ..palette }
+    }
+
     /// Toggle between dark and light palettes.
     pub fn toggle(&mut self) {
-        *self = match self.mode {
-            ThemeMode::Dark => Self::light(),
-            ThemeMode::Light => Self::dark(),
+        let mode = match self.mode {
+            ThemeMode::Dark => ThemeMode::Light,
+            ThemeMode::Light => ThemeMode::Dark,
+        };
+        *self = Self::new(mode, self.ascii);
+    }
+
+    /// Box borders: the usual single lines, or `ASCII_BORDER`.
+    pub fn border_set(&self) -> border::Set {
+        if self.ascii { ASCII_BORDER } else { border::PLAIN }
+    }
+
+    /// `unicode`, or its `ascii` stand-in in ASCII mode.
+    pub fn glyph(&self, unicode: &'static str, ascii: &'static str) -> &'static str {
+        if self.ascii { ascii } else { unicode }
+    }
+
+    /// UI text (system messages, labels) made safe for ASCII mode.
+    pub fn text<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        if self.ascii && !text.is_ascii() {
+            Cow::Owned(to_ascii(text))
+        } else {
+            Cow::Borrowed(text)
+        }
+    }
+}
+
+/// Replace the box-drawing and symbol characters the UI uses with ASCII.
+/// Anything else non-ASCII (say, accented letters in a translation) becomes
+/// `?`, which is what a non-UTF-8 terminal would have shown anyway.
+pub fn to_ascii(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            c if c.is_ascii() => out.push(c),
+            '─' | '—' | '–' | '¯' => out.push('-'),
+            '│' => out.push('|'),
+            '┌' | '└' => out.push('+'),
+            '…' => out.push_str("..."),
+            '✓' => out.push('*'),
+            '✗' => out.push('x'),
+            '█' => out.push('#'),
+            '░' => out.push('.'),
+            '⏎' => out.push_str("\\n"),
+            '↑' => out.push('^'),
+            '←' => out.push_str("<-"),
+            '→' => out.push_str("->"),
+            'ツ' => out.push_str("o_o"),
+            _ => out.push('?'),
+        }
+    }
+    out
+}
+
+/// Whether the locale in the environment uses UTF-8, judged the way the C
+/// library does: the first of `LC_ALL`, `LC_CTYPE`, `LANG` that is set and
+/// non-empty. With none set there is nothing to go on and we assume UTF-8,
+/// as every modern terminal does (and Windows never sets them).
+pub fn locale_is_utf8() -> bool {
+    is_utf8_locale(|var| std::env::var(var).ok())
+}
+
+fn is_utf8_locale(var: impl Fn(&str) -> Option<String>) -> bool {
+    ["LC_ALL", "LC_CTYPE", "LANG"]
+        .into_iter()
+        .find_map(|name| var(name).filter(|v| !v.is_empty()))
+        .is_none_or(|locale| {
+            let locale = locale.to_lowercase();
+            locale.contains("utf-8") || locale.contains("utf8")
+        })
+}
+
+// ── Tests ────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ascii_mode_survives_toggle_and_rewrites_ui_text() {
+        let mut theme = Theme::dark();
+        assert_eq!(theme.text("── Help ──"), "── Help ──");
+        theme.ascii = true;
+        theme.toggle();
+        assert!(theme.ascii);
+        assert_eq!(theme.text("── Help … ✓ ──"), "-- Help ... * --");
+        assert_eq!(theme.glyph("✓", "*"), "*");
+        assert_eq!(theme.border_set().top_left, "+");
+    }
+
+    #[test]
+    fn saved_mode_picks_the_palette() {
+        let light = Theme::new(ThemeMode::Light, true);
+        assert_eq!(light.mode, ThemeMode::Light);
+        assert_eq!(light.bg, Theme::light().bg);
+        assert!(light.ascii);
+        assert_eq!(Theme::new(ThemeMode::default(), false).bg, Theme::dark().bg);
+    }
+
+    #[test]
+    fn locale_detection() {
+        let env = |pairs: &'static [(&'static str, &'static str)]| {
+            move |name: &str| pairs.iter().find(|(k, _)| *k == name).map(|(_, v)| v.to_string())
         };
+        assert!(is_utf8_locale(env(&[])));
+        assert!(is_utf8_locale(env(&[("LANG", "en_GB.UTF-8")])));
+        assert!(is_utf8_locale(env(&[("LC_CTYPE", "C.utf8"), ("LANG", "C")])));
+        assert!(!is_utf8_locale(env(&[("LANG", "C")])));
+        // LC_ALL overrides the rest; an empty one doesn't count.
+        assert!(!is_utf8_locale(env(&[("LC_ALL", "POSIX"), ("LANG", "en_US.UTF-8")])));
+        assert!(is_utf8_locale(env(&[("LC_ALL", ""), ("LANG", "en_US.UTF-8")])));
     }
 }
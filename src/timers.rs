@@ -0,0 +1,101 @@
+//! Shared countdowns: `/timer 10m standup`.
+//!
+//! A timer is broadcast as `Message::Timer` with the wall-clock time it ends
+//! at, so every client counts down to the same moment (as far as their
+//! clocks agree — the same assumption file expiry makes). Each client keeps
+//! the running timers in a `Timers` list on `App`; the UI tick calls `due()`
+//! to take out the ones that have elapsed and announce them, and the status
+//! line under the input bar shows the soonest one counting down.
+//!
+//! Timers aren't part of the room history: a peer who joins after a timer
+//! was set doesn't see it.
+
+use crate::net::MessageId;
+
+/// One running countdown.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Timer {
+    /// The ID it was broadcast under, so a redelivered timer isn't added twice.
+    pub id: MessageId,
+    /// What it's for ("standup"); may be empty.
+    pub label: String,
+    /// Who set it.
+    pub set_by: String,
+    /// When it ends, ms since the epoch.
+    pub ends_ms: u64,
+}
+
+/// The running timers, soonest first.
+#[derive(Debug, Default)]
+pub struct Timers {
+    list: Vec<Timer>,
+}
+
+impl Timers {
+    /// Add a timer. Returns `false` if it's already running or has already
+    /// ended by `now_ms`.
+    pub fn schedule(&mut self, timer: Timer, now_ms: u64) -> bool {
+        if timer.ends_ms <= now_ms || self.list.iter().any(|t| t.id == timer.id) {
+            return false;
+        }
+        let at = self.list.partition_point(|t| t.ends_ms <= timer.ends_ms);
+        self.list.insert(at, timer);
+        true
+    }
+
+    /// Take out every timer that has ended by `now_ms`, in the order they ended.
+    pub fn due(&mut self, now_ms: u64) -> Vec<Timer> {
+        let ended = self.list.partition_point(|t| t.ends_ms <= now_ms);
+        self.list.drain(..ended).collect()
+    }
+
+    /// The soonest timer, and how many more are running.
+    pub fn next(&self) -> Option<(&Timer, usize)> {
+        self.list.first().map(|timer| (timer, self.list.len() - 1))
+    }
+
+    pub fn clear(&mut self) {
+        self.list.clear();
+    }
+}
+
+/// Time left as the status line shows it: `9:05`, or `1:02:03` past an hour.
+/// Rounds up, so a timer reads `0:01` until it actually ends.
+pub fn format_remaining(ms: u64) -> String {
+    let secs = ms.div_ceil(1000);
+    let (h, m, s) = (secs / 3_600, secs / 60 % 60, secs % 60);
+    if h > 0 { format!("{h}:{m:02}:{s:02}") } else { format!("{m}:{s:02}") }
+}
+
+// ── Tests ────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timer(id: u8, ends_ms: u64) -> Timer {
+        Timer { id: [id; 16], label: format!("t{id}"), set_by: "Alice".into(), ends_ms }
+    }
+
+    #[test]
+    fn timers_come_due_in_order() {
+        let mut timers = Timers::default();
+        assert!(timers.schedule(timer(1, 5_000), 0));
+        assert!(timers.schedule(timer(2, 2_000), 0));
+        assert!(!timers.schedule(timer(2, 2_000), 0));
+        assert!(!timers.schedule(timer(3, 500), 1_000));
+        let (next, more) = timers.next().unwrap();
+        assert_eq!((next.id, more), ([2; 16], 1));
+
+        assert!(timers.due(1_999).is_empty());
+        assert_eq!(timers.due(5_000).iter().map(|t| t.id[0]).collect::<Vec<_>>(), [2, 1]);
+        assert!(timers.next().is_none());
+    }
+
+    #[test]
+    fn remaining_time_rounds_up() {
+        assert_eq!(format_remaining(1), "0:01");
+        assert_eq!(format_remaining(545_000), "9:05");
+        assert_eq!(format_remaining(3_723_000), "1:02:03");
+    }
+}
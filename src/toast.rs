@@ -0,0 +1,116 @@
+//! Toasts: short-lived notices in the status line under the input bar.
+//!
+//! A finished or failed transfer is announced with a system line in the
+//! chat, but while you're typing (or scrolled back) that line is easy to
+//! miss. So when the files pane isn't focused, the main loop also pushes a
+//! toast here. Toasts queue up and are shown one at a time on the input
+//! bar's bottom border, each for `DURATION`; the UI tick calls `tick()` to
+//! move on to the next. With `toast_bell = true` in the config, each toast
+//! also rings the terminal bell.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// How long each toast stays up.
+pub const DURATION: Duration = Duration::from_secs(4);
+
+/// How many toasts may wait behind the one showing; older ones are dropped
+/// when a burst of transfers finishes at once.
+const MAX_QUEUED: usize = 5;
+
+/// Whether a toast reports good news or a failure (which sets its color).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ToastKind {
+    Success,
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Toast {
+    pub text: String,
+    pub kind: ToastKind,
+}
+
+/// The toast queue, front first. The front is the one on screen.
+#[derive(Debug, Default)]
+pub struct Toasts {
+    queue: VecDeque<Toast>,
+    /// When the front toast went up.
+    shown_at: Option<Instant>,
+}
+
+impl Toasts {
+    /// Queue a toast. It shows at once if nothing else is showing.
+    pub fn push(&mut self, text: String, kind: ToastKind, now: Instant) {
+        if self.queue.is_empty() {
+            self.shown_at = Some(now);
+        }
+        self.queue.push_back(Toast { text, kind });
+        // Keep the one showing; drop the oldest of those waiting.
+        if self.queue.len() > MAX_QUEUED + 1 {
+            self.queue.remove(1);
+        }
+    }
+
+    /// Retire the front toast once it has been up for `DURATION`, bringing
+    /// up the next.
+    pub fn tick(&mut self, now: Instant) {
+        if self.shown_at.is_some_and(|at| now.duration_since(at) >= DURATION) {
+            self.queue.pop_front();
+            self.shown_at = (!self.queue.is_empty()).then_some(now);
+        }
+    }
+
+    /// The toast to show, and how many wait behind it.
+    pub fn current(&self) -> Option<(&Toast, usize)> {
+        self.queue.front().map(|toast| (toast, self.queue.len() - 1))
+    }
+}
+
+/// Ring the terminal bell. Best-effort, like the clipboard escape.
+pub fn bell() {
+    use std::io::Write;
+    let mut stdout = std::io::stdout();
+    let _ = stdout.write_all(b"\x07");
+    let _ = stdout.flush();
+}
+
+// ── Tests ────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn toasts_show_in_turn_then_expire() {
+        let mut toasts = Toasts::default();
+        let start = Instant::now();
+        assert!(toasts.current().is_none());
+
+        toasts.push("one".into(), ToastKind::Success, start);
+        toasts.push("two".into(), ToastKind::Error, start + Duration::from_secs(1));
+        let (toast, waiting) = toasts.current().unwrap();
+        assert_eq!((toast.text.as_str(), waiting), ("one", 1));
+
+        // "two" only starts its time once "one" is gone.
+        toasts.tick(start + DURATION);
+        assert_eq!(toasts.current().unwrap().0.text, "two");
+        toasts.tick(start + DURATION + DURATION / 2);
+        assert_eq!(toasts.current().unwrap().0.text, "two");
+        toasts.tick(start + DURATION * 2);
+        assert!(toasts.current().is_none());
+    }
+
+    #[test]
+    fn bursts_keep_the_newest() {
+        let mut toasts = Toasts::default();
+        let now = Instant::now();
+        for i in 0..10 {
+            toasts.push(format!("t{i}"), ToastKind::Success, now);
+        }
+        let (toast, waiting) = toasts.current().unwrap();
+        assert_eq!((toast.text.as_str(), waiting), ("t0", MAX_QUEUED));
+        toasts.tick(now + DURATION);
+        assert_eq!(toasts.current().unwrap().0.text, "t5");
+    }
+}
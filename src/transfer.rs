@@ -11,6 +11,9 @@
 //!   channel, sending `TransferEvent`s that the main loop applies to the state machine.
 //! - **Content-addressed storage**: files are identified by their BLAKE3 hash — the
 //!   `Hash` type from `iroh_blobs`. Two files with the same content have the same hash.
+//!   So a file pane entry is keyed by an `EntryId` instead — the hash, the
+//!   sender, and the offer message's ID — and the same file offered by two
+//!   peers (or twice by one) gets two entries that progress separately.
 
 // `EndpointId` is a 32-byte Ed25519 public key identifying a peer on the network.
 // We store it in `FileOffer` so the receiver knows which peer to connect to for
@@ -37,10 +40,15 @@ use ratatui::{
     widgets::{Block, Borders, Paragraph},
 };
 
+use crate::chat::{format_remaining, format_timestamp};
+use crate::net::{Message, MessageId, now_ms};
+use crate::fit;
+use crate::seal::ContentKey;
 use crate::theme::Theme;
+use unicode_width::UnicodeWidthStr;
 // `PathBuf` is an owned filesystem path (the `String` of paths).
 // Used in `TransferState::Complete` to store where the downloaded file was saved.
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 // ── Types ────────────────────────────────────────────────────────────────────
 
@@ -63,8 +71,30 @@ pub struct FileOffer {
     /// File size in bytes — used for progress calculation and display.
     pub size: u64,
     /// The BLAKE3 content hash — the unique identifier for this blob.
-    /// Used to match progress/completion events back to the right transfer.
     pub hash: Hash,
+    /// The content key, when the blob is sealed (see `seal`). The download
+    /// opens it before writing the file.
+    pub key: Option<ContentKey>,
+    /// ID of the offer message (or a fresh one for an offer still being
+    /// packed): with `hash` and `sender_id`, what tells this offer apart.
+    pub offer_id: MessageId,
+}
+
+impl FileOffer {
+    /// The key of this offer's file pane entry.
+    pub fn entry_id(&self) -> EntryId {
+        EntryId { hash: self.hash, sender: self.sender_id, offer_id: self.offer_id }
+    }
+}
+
+/// Which file pane entry a transfer event or action is about. The hash alone
+/// isn't enough: two peers can offer the same file, and so can one peer
+/// twice, and each offer is downloaded (or not) on its own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EntryId {
+    pub hash: Hash,
+    pub sender: EndpointId,
+    pub offer_id: MessageId,
 }
 
 /// The lifecycle state of a single file transfer.
@@ -77,6 +107,19 @@ pub struct FileOffer {
 /// 2. `match` forces you to handle every state (exhaustiveness checking).
 /// 3. Invalid states are unrepresentable — you can't have "downloading" without
 ///    a byte count, or "complete" without a file path.
+///
+/// The methods only move an entry along these edges, and leave it alone
+/// otherwise:
+///
+/// - `Pending` → `Downloading`, `Complete` (a restored session), `Failed`
+/// - `Downloading` → `Complete`, `Failed`
+/// - `Failed` → `Downloading` (a retry)
+/// - `Packing` → `Sharing`, `Failed`
+/// - `Sharing` → `Invalidated`
+/// - `Complete` and `Invalidated` are final
+///
+/// So a late progress report can't drag a finished download back, and a
+/// peer's offer of a file we're sharing (same hash) can't touch our entry.
 #[derive(Debug)]
 pub enum TransferState {
     /// Offer received but download not yet started.
@@ -93,6 +136,15 @@ pub enum TransferState {
     Failed(String),
     /// We are the sender — the file is being shared to peers.
     Sharing,
+    /// We are the sender, still packing a folder into the archive we'll
+    /// share (see `pack`). The entry's hash is provisional until then.
+    Packing {
+        bytes_done: u64,
+        total_bytes: u64,
+    },
+    /// We were the sender, but the file we shared by reference changed on
+    /// disk (see `blobstore`), so the offer was withdrawn; says how.
+    Invalidated(String),
 }
 
 /// Events sent from background download tasks back to the main event loop
@@ -106,22 +158,120 @@ pub enum TransferState {
 pub enum TransferEvent {
     /// Periodic progress update — sent during download.
     Progress {
-        hash: Hash,
-        bytes_received: u64,
-        total_bytes: u64,
+        id: EntryId,
+        progress: FetchProgress,
     },
     /// Download completed successfully — file is saved to `path`.
     Complete {
-        hash: Hash,
+        id: EntryId,
         filename: String,
         path: PathBuf,
+        /// For a sealed offer, the BLAKE3 of the opened file (see
+        /// `TransferEntry::content`).
+        content: Option<Hash>,
     },
     /// Download failed with an error description.
     Failed {
-        hash: Hash,
+        id: EntryId,
         filename: String,
         error: String,
     },
+    /// Folder packing progress, for the entry `id` (with a provisional hash).
+    Packing {
+        id: EntryId,
+        bytes_done: u64,
+        total_bytes: u64,
+    },
+    /// A folder finished packing and its offer went out, giving the
+    /// archive's real hash, size and offer message ID — or packing failed.
+    Packed {
+        id: EntryId,
+        result: Result<(Hash, u64, MessageId), String>,
+    },
+    /// A finished download was re-hashed (`/verifyfile`, or its sidecar
+    /// checked before opening): whether it still matches, or why it
+    /// couldn't be read.
+    Verified {
+        filename: String,
+        result: Result<bool, String>,
+    },
+    /// Writing a download's `.sha256` sidecar failed.
+    SidecarFailed {
+        filename: String,
+        error: String,
+    },
+    /// A retried download is starting an attempt with `strategy`.
+    Attempt {
+        id: EntryId,
+        strategy: Strategy,
+    },
+    /// A preview fetch finished: the file's content, held in memory only,
+    /// or why it couldn't be fetched.
+    Previewed {
+        id: EntryId,
+        filename: String,
+        result: Result<Vec<u8>, String>,
+    },
+}
+
+/// Largest offer `p` previews. The whole file is held in memory, and the
+/// popup only has room for the start of it anyway.
+pub const PREVIEW_MAX: u64 = 256 * 1024;
+
+/// An offered file fetched into memory for a look, without writing it to
+/// the download dir (see `node::spawn_preview`). `s` saves it there.
+#[derive(Debug)]
+pub struct Preview {
+    pub id: EntryId,
+    pub filename: String,
+    pub bytes: Vec<u8>,
+    /// First line shown, moved with Up/Down.
+    pub scroll: u16,
+}
+
+/// How a download reaches the file's bytes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Strategy {
+    /// Dial the offering peer by ID and let iroh pick the path.
+    Default,
+    /// Dial the offering peer with only its relay address, skipping the
+    /// direct paths a failed connect may have been stuck on.
+    RelayOnly,
+    /// Fetch from another peer in the room instead. Content addressing
+    /// makes any copy as good as the original — if they downloaded it too.
+    Peer(EndpointId),
+}
+
+/// The attempts `r` makes on a download that failed with `error`. A
+/// failure to connect falls back to the sender's relay, then to each of
+/// `others` (the rest of the room); any other failure is simply tried again.
+pub fn retry_plan(error: &str, others: &[EndpointId]) -> Vec<Strategy> {
+    if error.starts_with("connect") {
+        let mut plan = vec![Strategy::RelayOnly];
+        plan.extend(others.iter().map(|id| Strategy::Peer(*id)));
+        plan
+    } else {
+        vec![Strategy::Default]
+    }
+}
+
+/// One report from a blob fetch in progress (see `node::fetch_blob`).
+///
+/// The fetch streams the blob in verified chunks, but a resumed download
+/// doesn't start from zero or fill in order: the store keeps whichever
+/// ranges an earlier attempt verified, and only the missing ones are
+/// fetched. So what came over the wire and what the store holds are
+/// counted apart.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FetchProgress {
+    /// Payload bytes this attempt has read off the wire.
+    pub received: u64,
+    /// Bytes in the store whose chunks passed BLAKE3 verification, from
+    /// this attempt or an earlier one.
+    pub verified: u64,
+    /// The blob's size, once the fetch has verified it (its last chunk
+    /// proves it). Until then all we have is the offer's claim.
+    pub size: Option<u64>,
 }
 
 /// A single entry in the file share pane — an offer paired with its state.
@@ -129,6 +279,82 @@ pub enum TransferEvent {
 pub struct TransferEntry {
     pub offer: FileOffer,
     pub state: TransferState,
+    /// The latest fetch report while downloading, for the details popup.
+    pub fetched: FetchProgress,
+    /// Set when the sender has scheduled the offer to be revoked.
+    pub expiry: Option<Expiry>,
+    /// Timestamped steps (unix ms) the transfer has been through, oldest
+    /// first, for the details popup.
+    pub timeline: Vec<(u64, Milestone)>,
+    /// The peers who've reacted "👍 received" to our offer, each once.
+    pub received_by: Vec<EndpointId>,
+    /// The peers known to have our offer's file: they fetched all of it
+    /// from us, or reacted to it. Its length is the download counter.
+    pub downloaded_by: Vec<EndpointId>,
+    /// The BLAKE3 of a sealed download as saved: `offer.hash` names the
+    /// sealed blob, not the opened file. `None` for anything else.
+    pub content: Option<Hash>,
+}
+
+/// A step in a transfer's life, as listed in its details popup (and as
+/// `{"step": ..., "detail": ...}` in the `--json-log`).
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "step", content = "detail", rename_all = "snake_case")]
+pub enum Milestone {
+    /// A peer offered us the file.
+    Offered,
+    /// We started packing a folder to share.
+    Packing,
+    /// We're sharing the file.
+    Shared,
+    /// We started downloading.
+    Started,
+    /// The download got past this percentage (recorded every 10%).
+    Reached(u8),
+    /// A retry attempt began, described by its strategy.
+    Attempt(String),
+    /// We looked at the file in memory, without downloading it.
+    Previewed,
+    Completed,
+    Failed(String),
+    /// Our shared file changed on disk and the offer was withdrawn.
+    Invalidated(String),
+    /// We told the sender we got the file ("👍 received").
+    Reacted,
+    /// This peer told us they got our file.
+    ReceivedBy(String),
+    /// This peer fetched all of our file from us.
+    DownloadedBy(String),
+}
+
+impl Milestone {
+    fn label(&self) -> String {
+        match self {
+            Milestone::Offered => "offered".to_string(),
+            Milestone::Packing => "packing started".to_string(),
+            Milestone::Shared => "sharing".to_string(),
+            Milestone::Started => "download started".to_string(),
+            Milestone::Reached(pct) => format!("{pct}% received"),
+            Milestone::Attempt(strategy) => format!("trying {strategy}"),
+            Milestone::Previewed => "previewed in memory".to_string(),
+            Milestone::Completed => "completed".to_string(),
+            Milestone::Failed(error) => format!("failed: {error}"),
+            Milestone::Invalidated(why) => format!("source file {why}: offer withdrawn"),
+            Milestone::Reacted => "you reacted: received".to_string(),
+            Milestone::ReceivedBy(name) => format!("{name} received it"),
+            Milestone::DownloadedBy(name) => format!("{name} downloaded it"),
+        }
+    }
+}
+
+/// A scheduled revocation, from a `Message::FileRevoke` with a future `at_ms`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Expiry {
+    /// When the offer goes away (unix ms).
+    pub at_ms: u64,
+    /// The revoke message's ID, reused for the history entry every peer
+    /// records when it fires so that synced histories agree.
+    pub message_id: MessageId,
 }
 
 // ── TransferManager ──────────────────────────────────────────────────────────
@@ -145,6 +371,25 @@ pub struct TransferManager {
     /// Index of the currently highlighted entry in the file pane.
     /// Used for keyboard navigation (Up/Down) and action dispatch (Enter).
     pub selected_index: usize,
+    /// A download or open of a risky file, waiting for the user to confirm
+    /// it in the overlay.
+    pub confirm: Option<Confirm>,
+    /// The entry whose details popup is open (`i` in the files pane).
+    pub details: Option<EntryId>,
+    /// The file open in the preview popup (`p` in the files pane).
+    pub preview: Option<Preview>,
+    /// File extensions (lowercase, no dot) flagged as risky: config
+    /// `risky_extensions`, or `DEFAULT_RISKY_EXTENSIONS`.
+    pub risky_extensions: Vec<String>,
+}
+
+/// An action on a risky file that needs an explicit yes first.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Confirm {
+    /// Download this pending offer.
+    Download(EntryId),
+    /// Open this finished download with the OS default handler.
+    Open(PathBuf),
 }
 
 impl TransferManager {
@@ -152,6 +397,10 @@ impl TransferManager {
         Self {
             entries: Vec::new(),
             selected_index: 0,
+            confirm: None,
+            details: None,
+            preview: None,
+            risky_extensions: DEFAULT_RISKY_EXTENSIONS.iter().map(|e| e.to_string()).collect(),
         }
     }
 
@@ -160,6 +409,12 @@ impl TransferManager {
         self.entries.push(TransferEntry {
             offer,
             state: TransferState::Pending,
+            expiry: None,
+            fetched: FetchProgress::default(),
+            timeline: vec![(now_ms(), Milestone::Offered)],
+            received_by: Vec::new(),
+            downloaded_by: Vec::new(),
+            content: None,
         });
     }
 
@@ -168,48 +423,258 @@ impl TransferManager {
         self.entries.push(TransferEntry {
             offer,
             state: TransferState::Sharing,
+            expiry: None,
+            fetched: FetchProgress::default(),
+            timeline: vec![(now_ms(), Milestone::Shared)],
+            received_by: Vec::new(),
+            downloaded_by: Vec::new(),
+            content: None,
         });
     }
 
-    /// Mark a transfer as downloading by matching on the BLAKE3 hash.
+    /// Add an entry for a folder we've started packing to share, under a
+    /// provisional hash that `finish_packing` replaces.
+    pub fn add_packing(&mut self, offer: FileOffer) {
+        self.entries.push(TransferEntry {
+            offer,
+            state: TransferState::Packing { bytes_done: 0, total_bytes: 0 },
+            expiry: None,
+            fetched: FetchProgress::default(),
+            timeline: vec![(now_ms(), Milestone::Packing)],
+            received_by: Vec::new(),
+            downloaded_by: Vec::new(),
+            content: None,
+        });
+    }
+
+    /// The entry `id`, if it's in a state `from` accepts.
+    fn entry_mut(&mut self, id: &EntryId, from: impl Fn(&TransferState) -> bool) -> Option<&mut TransferEntry> {
+        self.entries.iter_mut().find(|e| e.offer.entry_id() == *id && from(&e.state))
+    }
+
+    /// The entry `id`.
+    pub fn entry(&self, id: &EntryId) -> Option<&TransferEntry> {
+        self.entries.iter().find(|e| e.offer.entry_id() == *id)
+    }
+
+    /// Update packing progress for the entry `id`.
+    pub fn update_packing(&mut self, id: &EntryId, bytes_done: u64, total_bytes: u64) {
+        if let Some(entry) = self.entry_mut(id, |s| matches!(s, TransferState::Packing { .. })) {
+            entry.state = TransferState::Packing { bytes_done, total_bytes };
+        }
+    }
+
+    /// A packed folder is now being shared as the blob `real` of `size`
+    /// bytes, offered in the message `offer_id`. Returns the archive's
+    /// filename.
+    pub fn finish_packing(&mut self, id: &EntryId, real: Hash, size: u64, offer_id: MessageId) -> Option<String> {
+        let entry = self.entry_mut(id, |s| matches!(s, TransferState::Packing { .. }))?;
+        entry.offer.hash = real;
+        entry.offer.size = size;
+        entry.offer.offer_id = offer_id;
+        entry.state = TransferState::Sharing;
+        entry.record(Milestone::Shared);
+        Some(entry.offer.filename.clone())
+    }
+
+    /// Mark a transfer as downloading by matching on its `EntryId`.
     ///
     /// `iter_mut()` returns an iterator of `&mut TransferEntry` — mutable
     /// references to each element. `.find()` returns the first element matching
     /// the predicate, wrapped in `Option<&mut TransferEntry>`. `if let Some(entry)`
     /// unwraps it for mutation.
-    pub fn start_download(&mut self, hash: &Hash) {
-        if let Some(entry) = self.entries.iter_mut().find(|e| e.offer.hash == *hash) {
+    pub fn start_download(&mut self, id: &EntryId) {
+        if let Some(entry) = self.entry_mut(id, |s| matches!(s, TransferState::Pending | TransferState::Failed(_))) {
             entry.state = TransferState::Downloading {
                 bytes_received: 0,
                 total_bytes: entry.offer.size,
             };
+            entry.fetched = FetchProgress::default();
+            entry.record(Milestone::Started);
+        }
+    }
+
+    /// Start downloading the pending offer `id` and return it for the
+    /// fetch — unless it's a risky file type and not yet `confirmed`, in
+    /// which case `confirm` is set for the overlay to ask and nothing starts.
+    pub fn request_download(&mut self, id: &EntryId, confirmed: bool) -> Option<FileOffer> {
+        let entry = self.entry(id).filter(|e| matches!(e.state, TransferState::Pending))?;
+        if !confirmed && self.is_risky(&entry.offer.filename) {
+            self.confirm = Some(Confirm::Download(*id));
+            return None;
+        }
+        let offer = entry.offer.clone();
+        self.start_download(id);
+        Some(offer)
+    }
+
+    /// Whether the download at `path` can be opened right away. A risky
+    /// name or an execute bit sets `confirm` instead, unless `confirmed`.
+    pub fn request_open(&mut self, path: &Path, confirmed: bool) -> bool {
+        let risky = path.file_name().is_some_and(|n| self.is_risky(&n.to_string_lossy())) || is_executable(path);
+        if !confirmed && risky {
+            self.confirm = Some(Confirm::Open(path.to_path_buf()));
+            return false;
         }
+        true
     }
 
-    /// Update download progress for a transfer identified by hash.
-    pub fn update_progress(&mut self, hash: &Hash, bytes_received: u64, total_bytes: u64) {
-        if let Some(entry) = self.entries.iter_mut().find(|e| e.offer.hash == *hash) {
+    /// Update download progress for the transfer `id`.
+    pub fn update_progress(&mut self, id: &EntryId, bytes_received: u64, total_bytes: u64) {
+        if let Some(entry) = self.entry_mut(id, |s| matches!(s, TransferState::Pending | TransferState::Downloading { .. })) {
             entry.state = TransferState::Downloading {
                 bytes_received,
                 total_bytes,
             };
+            // Record each 10% step once; a jump of several records the last.
+            let step = (bytes_received.saturating_mul(10) / total_bytes.max(1)).min(10) as u8 * 10;
+            let last = entry.timeline.iter().rev().find_map(|(_, m)| match m {
+                Milestone::Reached(pct) => Some(*pct),
+                _ => None,
+            });
+            if step > last.unwrap_or(0) && step < 100 {
+                entry.record(Milestone::Reached(step));
+            }
+        }
+    }
+
+    /// Apply a fetch report to the download `id`.
+    ///
+    /// The bar follows verified bytes, which only grow within an attempt
+    /// even if reports cross. The total is the size the fetch verified once
+    /// it has; before that, the offer's claim — unless more than that has
+    /// already been verified (a missing or understated size), when the bar
+    /// holds just short of full until the real size is known.
+    pub fn fetch_progress(&mut self, id: &EntryId, progress: FetchProgress) {
+        let Some(entry) = self.entry(id) else { return };
+        let claimed = if entry.offer.key.is_some() { crate::seal::sealed_size(entry.offer.size) } else { entry.offer.size };
+        let shown = match entry.state {
+            TransferState::Downloading { bytes_received, .. } => bytes_received.max(progress.verified),
+            _ => progress.verified,
+        };
+        let total = match progress.size {
+            Some(size) => size,
+            None if shown >= claimed => shown + 1,
+            None => claimed,
+        };
+        self.update_progress(id, shown.min(total), total);
+        if let Some(entry) = self.entry_mut(id, |s| matches!(s, TransferState::Downloading { .. })) {
+            entry.fetched = progress;
+        }
+    }
+
+    /// A retry of `id` is starting an attempt described by `strategy`:
+    /// progress starts over, and the attempt goes in the timeline.
+    pub fn start_attempt(&mut self, id: &EntryId, strategy: String) {
+        let retryable = |s: &TransferState| matches!(s, TransferState::Pending | TransferState::Downloading { .. } | TransferState::Failed(_));
+        if let Some(entry) = self.entry_mut(id, retryable) {
+            entry.state = TransferState::Downloading { bytes_received: 0, total_bytes: entry.offer.size };
+            entry.fetched = FetchProgress::default();
+            entry.record(Milestone::Attempt(strategy));
+        }
+    }
+
+    /// The strategy of the attempt in progress on `id`, if it's a retry.
+    pub fn current_attempt(&self, id: &EntryId) -> Option<&str> {
+        let entry = self.entry(id)?;
+        for (_, milestone) in entry.timeline.iter().rev() {
+            match milestone {
+                Milestone::Attempt(strategy) => return Some(strategy),
+                Milestone::Started | Milestone::Failed(_) => return None,
+                _ => {}
+            }
         }
+        None
     }
 
     /// Mark a transfer as complete with the path to the downloaded file.
-    pub fn complete_download(&mut self, hash: &Hash, path: PathBuf) {
-        if let Some(entry) = self.entries.iter_mut().find(|e| e.offer.hash == *hash) {
+    pub fn complete_download(&mut self, id: &EntryId, path: PathBuf) {
+        if let Some(entry) = self.entry_mut(id, |s| matches!(s, TransferState::Pending | TransferState::Downloading { .. })) {
             entry.state = TransferState::Complete(path);
+            entry.record(Milestone::Completed);
+        }
+    }
+
+    /// Remember the hash of a finished sealed download's opened content,
+    /// for `/verifyfile` (see `TransferEntry::content_hash`).
+    pub fn opened(&mut self, id: &EntryId, content: Hash) {
+        if let Some(entry) = self.entry_mut(id, |s| matches!(s, TransferState::Complete(_))) {
+            entry.content = Some(content);
         }
     }
 
     /// Mark a transfer as failed with an error message.
-    pub fn fail_download(&mut self, hash: &Hash, error: String) {
-        if let Some(entry) = self.entries.iter_mut().find(|e| e.offer.hash == *hash) {
+    pub fn fail_download(&mut self, id: &EntryId, error: String) {
+        let running = |s: &TransferState| {
+            matches!(s, TransferState::Pending | TransferState::Downloading { .. } | TransferState::Packing { .. })
+        };
+        if let Some(entry) = self.entry_mut(id, running) {
+            entry.record(Milestone::Failed(error.clone()));
             entry.state = TransferState::Failed(error);
         }
     }
 
+    /// Our offer `id` can't be served anymore: its file, shared by
+    /// reference, changed (`why`). Returns the filename if it was sharing.
+    pub fn invalidate(&mut self, id: &EntryId, why: String) -> Option<String> {
+        let entry = self.entry_mut(id, |s| matches!(s, TransferState::Sharing))?;
+        entry.record(Milestone::Invalidated(why.clone()));
+        entry.state = TransferState::Invalidated(why);
+        Some(entry.offer.filename.clone())
+    }
+
+    /// React "👍 received" to download `id`: returns its offer if it's
+    /// complete and we haven't reacted to it before.
+    pub fn react(&mut self, id: &EntryId) -> Option<FileOffer> {
+        let entry = self.entry_mut(id, |s| matches!(s, TransferState::Complete(_))).filter(|e| !e.reacted())?;
+        entry.record(Milestone::Reacted);
+        Some(entry.offer.clone())
+    }
+
+    /// `from`, calling itself `name`, reacted "👍 received" to our offer
+    /// `id`. Returns the filename the first time each peer does.
+    pub fn acknowledge(&mut self, id: &EntryId, from: EndpointId, name: String) -> Option<String> {
+        let entry = self.entry_mut(id, |_| true).filter(|e| !e.received_by.contains(&from))?;
+        entry.received_by.push(from);
+        if !entry.downloaded_by.contains(&from) {
+            entry.downloaded_by.push(from);
+        }
+        entry.record(Milestone::ReceivedBy(name));
+        Some(entry.offer.filename.clone())
+    }
+
+    /// `by`, calling itself `name`, fetched all of blob `hash` from us:
+    /// count a download on each of our offers of it, once per peer.
+    pub fn served(&mut self, hash: &Hash, by: EndpointId, name: &str) {
+        for entry in &mut self.entries {
+            if entry.offer.hash == *hash
+                && matches!(entry.state, TransferState::Sharing | TransferState::Invalidated(_))
+                && !entry.downloaded_by.contains(&by)
+            {
+                entry.downloaded_by.push(by);
+                entry.record(Milestone::DownloadedBy(name.to_string()));
+            }
+        }
+    }
+
+    /// The selected offer, if it's pending and small enough to preview.
+    /// `Err` carries the offer's size when it's too big.
+    pub fn request_preview(&self) -> Option<Result<FileOffer, u64>> {
+        let entry = self.selected_entry().filter(|e| matches!(e.state, TransferState::Pending))?;
+        if entry.offer.size > PREVIEW_MAX {
+            return Some(Err(entry.offer.size));
+        }
+        Some(Ok(entry.offer.clone()))
+    }
+
+    /// Open the preview popup on `bytes`, the content of offer `id`.
+    pub fn show_preview(&mut self, id: EntryId, filename: String, bytes: Vec<u8>) {
+        if let Some(entry) = self.entry_mut(&id, |_| true) {
+            entry.record(Milestone::Previewed);
+        }
+        self.preview = Some(Preview { id, filename, bytes, scroll: 0 });
+    }
+
     /// Get a reference to the currently selected entry (if any).
     ///
     /// `Vec::get()` returns `Option<&T>` — it's the bounds-checked alternative
@@ -219,6 +684,20 @@ impl TransferManager {
         self.entries.get(self.selected_index)
     }
 
+    /// Whether `filename` has one of the risky extensions.
+    pub fn is_risky(&self, filename: &str) -> bool {
+        let Some((_, ext)) = filename.rsplit_once('.') else { return false };
+        self.risky_extensions.iter().any(|risky| risky.trim_start_matches('.').eq_ignore_ascii_case(ext))
+    }
+
+    /// Where the selected entry was saved, if it's a finished download.
+    pub fn selected_download(&self) -> Option<&Path> {
+        match &self.selected_entry()?.state {
+            TransferState::Complete(path) => Some(path),
+            _ => None,
+        }
+    }
+
     /// Whether there are any entries to display.
     pub fn has_entries(&self) -> bool {
         !self.entries.is_empty()
@@ -235,19 +714,69 @@ impl TransferManager {
         }
     }
 
-    /// Remove a transfer entry by its BLAKE3 hash.
-    /// Returns the filename if found and removed, or `None` if not found.
-    pub fn retract(&mut self, hash: &Hash) -> Option<String> {
-        if let Some(idx) = self.entries.iter().position(|e| e.offer.hash == *hash) {
-            let filename = self.entries[idx].offer.filename.clone();
-            self.entries.remove(idx);
-            if self.selected_index >= self.entries.len() && self.selected_index > 0 {
-                self.selected_index -= 1;
+    /// Remove the entry at `idx`, keeping the selection in bounds, and
+    /// return its filename.
+    fn remove(&mut self, idx: usize) -> String {
+        let entry = self.entries.remove(idx);
+        if self.selected_index >= self.entries.len() && self.selected_index > 0 {
+            self.selected_index -= 1;
+        }
+        entry.offer.filename
+    }
+
+    /// Apply a revocation of the offer `id` by its sender, the only peer
+    /// allowed to revoke it (the caller checks the revocation came from
+    /// `id.sender`). A future `expiry` is remembered for `expire` to act on;
+    /// otherwise the entry goes now. Either way an entry that has
+    /// been (or is being) downloaded stays — the file is already ours.
+    ///
+    /// Returns the filename if an entry was removed.
+    pub fn revoke(&mut self, id: &EntryId, expiry: Expiry, now_ms: u64) -> Option<String> {
+        let idx = self.entries.iter().position(|e| e.offer.entry_id() == *id && e.is_revocable())?;
+        if expiry.at_ms > now_ms {
+            self.entries[idx].expiry = Some(expiry);
+            return None;
+        }
+        Some(self.remove(idx))
+    }
+
+    /// Apply a withdrawal heard from the room, signed by `from`.
+    ///
+    /// Only a `FileRevoke` counts, and only from the offer's own author
+    /// (`decode` already tied its `endpoint_id` to the signer). A
+    /// `FileRetract` names neither the author nor the offer, so anyone in
+    /// the room could send one for anyone's file; it's ignored. Returns
+    /// the filename if an entry was removed, as `revoke` does.
+    pub fn withdraw(&mut self, from: EndpointId, msg: &Message, now_ms: u64) -> Option<String> {
+        let Message::FileRevoke { hash, endpoint_id, message_id, at_ms, offer_id, .. } = msg else { return None };
+        if *endpoint_id != from {
+            return None;
+        }
+        let id = EntryId { hash: Hash::from_bytes(*hash), sender: from, offer_id: *offer_id };
+        self.revoke(&id, Expiry { at_ms: *at_ms, message_id: *message_id }, now_ms)
+    }
+
+    /// Remove every revocable entry whose expiry has passed, returning them
+    /// so the caller can announce them (and drop the blob, for our own).
+    /// Entries downloaded in the meantime just lose their expiry.
+    pub fn expire(&mut self, now_ms: u64) -> Vec<TransferEntry> {
+        let mut expired = Vec::new();
+        let mut i = 0;
+        while i < self.entries.len() {
+            let entry = &mut self.entries[i];
+            if entry.expiry.is_some_and(|e| e.at_ms <= now_ms) {
+                if entry.is_revocable() {
+                    expired.push(self.entries.remove(i));
+                    continue;
+                }
+                entry.expiry = None;
             }
-            Some(filename)
-        } else {
-            None
+            i += 1;
+        }
+        if self.selected_index >= self.entries.len() {
+            self.selected_index = self.entries.len().saturating_sub(1);
         }
+        expired
     }
 
     /// Move selection to the previous entry (wrapping around).
@@ -266,6 +795,35 @@ impl TransferManager {
     }
 }
 
+impl TransferEntry {
+    /// Append `milestone` to the timeline, stamped with the current time.
+    fn record(&mut self, milestone: Milestone) {
+        self.timeline.push((now_ms(), milestone));
+    }
+
+    /// The BLAKE3 the file on disk should have: the opened content's for a
+    /// sealed download, otherwise the offer's.
+    pub fn content_hash(&self) -> Hash {
+        self.content.unwrap_or(self.offer.hash)
+    }
+
+    /// Whether we've reacted "👍 received" to this download.
+    pub fn reacted(&self) -> bool {
+        self.timeline.iter().any(|(_, m)| *m == Milestone::Reacted)
+    }
+
+    /// Whether a revocation removes this entry: anything not downloaded.
+    fn is_revocable(&self) -> bool {
+        matches!(self.state, TransferState::Pending | TransferState::Failed(_) | TransferState::Sharing)
+    }
+}
+
+impl Default for TransferManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 // ── MIME detection ───────────────────────────────────────────────────────────
 
 /// Infer a MIME type from a filename extension.
@@ -284,6 +842,37 @@ pub fn mime_from_extension(filename: &str) -> Option<String> {
     }
 }
 
+/// Extensions of files that run as programs (or install them) when opened:
+/// the default for config `risky_extensions`. Offers of these are flagged
+/// in the files pane, and downloading or opening one asks first.
+pub const DEFAULT_RISKY_EXTENSIONS: &[&str] = &[
+    "exe", "com", "bat", "cmd", "msi", "scr", "ps1", "vbs", "wsf", "lnk", "jar", "sh", "command",
+    "app", "appimage", "apk", "deb", "rpm", "dmg", "pkg", "run",
+];
+
+/// Whether the file at `path` has an execute permission bit (always false
+/// off Unix). Opening such a file may run it whatever its name.
+pub fn is_executable(path: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::metadata(path).is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = path;
+        false
+    }
+}
+
+/// Where an offered file is saved in `dir`. The name comes from a peer, so
+/// only its last component is kept, and a name that isn't one (empty, `.`
+/// or `..`) gives `None`: nothing lands outside `dir`.
+pub fn download_path(dir: &Path, filename: &str) -> Option<PathBuf> {
+    let name = Path::new(filename).file_name()?;
+    (!matches!(name.to_str(), Some("" | "." | ".."))).then(|| dir.join(name))
+}
+
 // ── Rendering ────────────────────────────────────────────────────────────────
 
 /// Format a byte count as a human-readable file size string.
@@ -307,11 +896,32 @@ pub fn format_file_size(bytes: u64) -> String {
     }
 }
 
+/// A six-cell progress bar with percentage, e.g. `[███░░░] 50%`, or
+/// `[###...] 50%` with `ascii`.
+pub fn progress_bar(bytes_received: u64, total_bytes: u64, ascii: bool) -> String {
+    let pct = if total_bytes > 0 {
+        (bytes_received as f64 / total_bytes as f64 * 100.0) as u64
+    } else {
+        0
+    };
+    let filled = (pct as usize * 6 / 100).min(6);
+    let empty = 6 - filled;
+    let (full, blank) = if ascii { ("#", ".") } else { ("\u{2588}", "\u{2591}") };
+    format!("[{}{}] {pct}%", full.repeat(filled), blank.repeat(empty))
+}
+
+/// Widest a sender's name gets in the file pane.
+const SENDER_COLUMNS: usize = 16;
+
+/// Narrowest a filename is cut to, however little room the row has left.
+const MIN_FILENAME_COLUMNS: usize = 8;
+
 /// Render the file share pane into the given area.
 ///
 /// Shows a bordered block titled "files" with one line per transfer entry.
 /// The border color is cyan when the pane is focused, default otherwise.
 /// The selected row gets a `>` prefix and bold styling when focused.
+/// Offers with an expiry show the time left before `now_ms` catches up.
 ///
 /// This function demonstrates ratatui's **composition model**:
 /// - `Block` provides the border and title
@@ -323,6 +933,7 @@ pub fn render_file_pane(
     area: Rect,
     manager: &TransferManager,
     focused: bool,
+    now_ms: u64,
     theme: &Theme,
 ) {
     let border_color = if focused {
@@ -330,12 +941,28 @@ pub fn render_file_pane(
     } else {
         theme.border
     };
-    let block = Block::default()
+    let mut block = Block::default()
         .borders(Borders::ALL)
+        .border_set(theme.border_set())
         .style(Style::default().bg(theme.bg))
         .border_style(Style::default().fg(border_color))
         .title("files")
         .title_style(Style::default().fg(theme.title));
+    // The bottom border holds the keys for opening the selected download,
+    // and for reacting to it until we have.
+    if focused && manager.selected_download().is_some() {
+        let mut hints = vec![
+            Span::styled(" o", Style::default().fg(theme.hint_key)),
+            Span::styled(" open file  ", Style::default().fg(theme.hint_text)),
+            Span::styled("O", Style::default().fg(theme.hint_key)),
+            Span::styled(" open folder ", Style::default().fg(theme.hint_text)),
+        ];
+        if manager.selected_entry().is_some_and(|e| !e.reacted()) {
+            hints.push(Span::styled(" +", Style::default().fg(theme.hint_key)));
+            hints.push(Span::styled(format!(" {} received ", theme.glyph("👍", "+1")), Style::default().fg(theme.hint_text)));
+        }
+        block = block.title_bottom(Line::from(hints));
+    }
 
     let lines: Vec<Line> = manager
         .entries
@@ -352,7 +979,8 @@ pub fn render_file_pane(
                 Style::default().fg(theme.accent)
             };
 
-            let sender = &entry.offer.sender_nickname;
+            let ellipsis = theme.glyph("…", "...");
+            let sender = fit::middle(&entry.offer.sender_nickname, SENDER_COLUMNS, ellipsis);
             let filename = &entry.offer.filename;
             let size = format_file_size(entry.offer.size);
 
@@ -364,18 +992,7 @@ pub fn render_file_pane(
                     bytes_received,
                     total_bytes,
                 } => {
-                    let pct = if *total_bytes > 0 {
-                        (*bytes_received as f64 / *total_bytes as f64 * 100.0) as u64
-                    } else {
-                        0
-                    };
-                    let filled = (pct as usize * 6 / 100).min(6);
-                    let empty = 6 - filled;
-                    let bar = format!(
-                        "[{}{}] {pct}%",
-                        "\u{2588}".repeat(filled),
-                        "\u{2591}".repeat(empty)
-                    );
+                    let bar = progress_bar(*bytes_received, *total_bytes, theme.ascii);
                     Span::styled(bar, Style::default().fg(theme.transfer_progress))
                 }
                 TransferState::Complete(_) => {
@@ -391,17 +1008,54 @@ pub fn render_file_pane(
                     Span::styled(msg, Style::default().fg(theme.transfer_failed))
                 }
                 TransferState::Sharing => {
-                    Span::styled("[unshare]", Style::default().fg(theme.transfer_sharing))
+                    let label = match entry.downloaded_by.len() {
+                        0 => "[unshare]".to_string(),
+                        1 => format!("[sharing {} 1 download]", theme.glyph("·", "-")),
+                        n => format!("[sharing {} {n} downloads]", theme.glyph("·", "-")),
+                    };
+                    Span::styled(label, Style::default().fg(theme.transfer_sharing))
+                }
+                TransferState::Packing { bytes_done, total_bytes } => {
+                    let bar = progress_bar(*bytes_done, *total_bytes, theme.ascii);
+                    Span::styled(format!("packing {bar}"), Style::default().fg(theme.transfer_progress))
+                }
+                TransferState::Invalidated(why) => {
+                    Span::styled(format!("[withdrawn: {why}]"), Style::default().fg(theme.transfer_failed))
                 }
             };
 
-            Line::from(vec![
+            let risky = manager.is_risky(filename);
+            let mark = if risky { theme.glyph("⚠ ", "! ") } else { "" };
+            let size = Span::styled(format!("({size})  "), Style::default().fg(theme.text_muted));
+            let expiry = entry.expiry.map(|expiry| {
+                let left = format_remaining(expiry.at_ms.saturating_sub(now_ms));
+                Span::styled(format!(" ({left} left)"), Style::default().fg(theme.text_muted))
+            });
+            // The filename gets whatever the rest of the row leaves, so a
+            // long one is cut in the middle instead of pushing the state
+            // off the edge.
+            let rest = prefix.width() + sender.width() + 2 + mark.width() + 1 + size.width() + state_span.width()
+                + expiry.as_ref().map_or(0, |e| e.width());
+            let room = (area.width as usize).saturating_sub(2 + rest).max(MIN_FILENAME_COLUMNS);
+            let filename = fit::middle(filename, room, ellipsis);
+
+            let mut spans = vec![
                 Span::styled(prefix, name_style),
                 Span::styled(format!("{sender}: "), name_style),
-                Span::styled(format!("{filename} "), Style::default().fg(theme.text)),
-                Span::styled(format!("({size})  "), Style::default().fg(theme.text_muted)),
+                // Risky file types get a warning mark and the error color.
+                if risky {
+                    Span::styled(
+                        format!("{mark}{filename} "),
+                        Style::default().fg(theme.error).add_modifier(Modifier::BOLD),
+                    )
+                } else {
+                    Span::styled(format!("{filename} "), Style::default().fg(theme.text))
+                },
+                size,
                 state_span,
-            ])
+            ];
+            spans.extend(expiry);
+            Line::from(spans)
         })
         .collect();
 
@@ -409,6 +1063,173 @@ pub fn render_file_pane(
     f.render_widget(widget, area);
 }
 
+/// `HH:MM:SS` (UTC) of a unix timestamp in ms.
+fn clock(ts_ms: u64) -> String {
+    format!("{}:{:02}", format_timestamp(ts_ms), ts_ms / 1000 % 60)
+}
+
+/// Draw the details popup for `manager.details`: the entry's timeline, one
+/// step per line with the time since the step before, ending with the time
+/// since the last step — so a download stuck at 60% shows how long it's
+/// been stuck.
+pub fn render_details(f: &mut ratatui::Frame, manager: &TransferManager, now_ms: u64, theme: &Theme) {
+    let Some(entry) = manager.details.and_then(|id| manager.entry(&id)) else { return };
+
+    let dot = theme.glyph("·", "-");
+    let mut lines = vec![Line::styled(
+        format!("{} {dot} {} {dot} from {}", entry.offer.filename, format_file_size(entry.offer.size), entry.offer.sender_nickname),
+        Style::default().fg(theme.text).add_modifier(Modifier::BOLD),
+    )];
+    // Verified and received differ on a resumed fetch: the store already
+    // held some ranges. An unproven size is the offer's word for it.
+    if let TransferState::Downloading { total_bytes, .. } = entry.state {
+        let fetched = entry.fetched;
+        let total = format_file_size(total_bytes);
+        let of = if fetched.size.is_some() { total } else { format!("~{total}") };
+        lines.push(Line::styled(
+            format!("verified {} of {of} {dot} received {}", format_file_size(fetched.verified), format_file_size(fetched.received)),
+            Style::default().fg(theme.text_muted),
+        ));
+    }
+    let mut prev = None;
+    for (at, milestone) in &entry.timeline {
+        let gap = prev.map_or(String::new(), |p| format!("+{}", format_remaining(at.saturating_sub(p))));
+        let color = match milestone {
+            Milestone::Failed(_) | Milestone::Invalidated(_) => theme.transfer_failed,
+            Milestone::Completed | Milestone::ReceivedBy(_) => theme.transfer_complete,
+            _ => theme.text,
+        };
+        lines.push(Line::from(vec![
+            Span::styled(format!("{} ", clock(*at)), Style::default().fg(theme.timestamp)),
+            Span::styled(format!("{gap:>5}  "), Style::default().fg(theme.text_muted)),
+            Span::styled(milestone.label(), Style::default().fg(color)),
+        ]));
+        prev = Some(*at);
+    }
+    if let Some(last) = prev
+        && matches!(entry.state, TransferState::Downloading { .. } | TransferState::Packing { .. })
+    {
+        let gap = format!("+{}", format_remaining(now_ms.saturating_sub(last)));
+        lines.push(Line::from(vec![
+            Span::styled(format!("{} ", clock(now_ms)), Style::default().fg(theme.timestamp)),
+            Span::styled(format!("{gap:>5}  "), Style::default().fg(theme.text_muted)),
+            Span::styled("now", Style::default().fg(theme.text_muted).add_modifier(Modifier::ITALIC)),
+        ]));
+    }
+
+    let area = f.area();
+    let width = 56.min(area.width);
+    let height = (lines.len() as u16 + 2).min(area.height);
+    let card = Rect::new(area.width.saturating_sub(width) / 2, area.height.saturating_sub(height) / 2, width, height);
+    f.render_widget(ratatui::widgets::Clear, card);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_set(theme.border_set())
+        .style(Style::default().bg(theme.bg))
+        .border_style(Style::default().fg(theme.border_focused))
+        .title(Line::styled(" transfer details ", Style::default().fg(theme.title)).centered())
+        .title_bottom(Line::from(vec![
+            Span::styled(" any key", Style::default().fg(theme.hint_key)),
+            Span::styled(" close ", Style::default().fg(theme.hint_text)),
+        ]));
+    f.render_widget(Paragraph::new(lines).block(block), card);
+}
+
+/// The lines a preview shows: the text, scrubbed like anything else from a
+/// peer (see `sanitize`), or a hex dump if it isn't UTF-8 text.
+fn preview_lines(bytes: &[u8]) -> Vec<String> {
+    match std::str::from_utf8(bytes) {
+        Ok(text) if !text.contains('\0') => crate::sanitize::text(text).lines().map(str::to_string).collect(),
+        _ => bytes
+            .chunks(16)
+            .enumerate()
+            .map(|(row, chunk)| {
+                let hex: Vec<String> = chunk.iter().map(|b| format!("{b:02x}")).collect();
+                let ascii: String =
+                    chunk.iter().map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' }).collect();
+                format!("{:06x}  {:<47}  {ascii}", row * 16, hex.join(" "))
+            })
+            .collect(),
+    }
+}
+
+/// Draw the preview popup for `manager.preview`, filling most of the frame.
+pub fn render_preview(f: &mut ratatui::Frame, manager: &TransferManager, theme: &Theme) {
+    let Some(preview) = &manager.preview else { return };
+
+    let area = f.area();
+    let width = area.width.saturating_sub(4).min(86);
+    let height = area.height.saturating_sub(2);
+    let card = Rect::new(area.width.saturating_sub(width) / 2, area.height.saturating_sub(height) / 2, width, height);
+    f.render_widget(ratatui::widgets::Clear, card);
+    let dot = theme.glyph("·", "-");
+    let title = format!(" {} {dot} {} ", preview.filename, format_file_size(preview.bytes.len() as u64));
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_set(theme.border_set())
+        .style(Style::default().bg(theme.bg))
+        .border_style(Style::default().fg(theme.border_focused))
+        .title(Line::styled(fit::middle(&title, width.saturating_sub(2) as usize, theme.glyph("…", "...")).into_owned(), Style::default().fg(theme.title)).centered())
+        .title_bottom(Line::from(vec![
+            Span::styled(" s", Style::default().fg(theme.hint_key)),
+            Span::styled(" save  ", Style::default().fg(theme.hint_text)),
+            Span::styled(theme.glyph("↑↓", "Up/Down"), Style::default().fg(theme.hint_key)),
+            Span::styled(" scroll  ", Style::default().fg(theme.hint_text)),
+            Span::styled("any key", Style::default().fg(theme.hint_key)),
+            Span::styled(" close ", Style::default().fg(theme.hint_text)),
+        ]));
+    let lines: Vec<Line> = preview_lines(&preview.bytes)
+        .into_iter()
+        .map(|line| Line::styled(line, Style::default().fg(theme.text)))
+        .collect();
+    f.render_widget(Paragraph::new(lines).block(block).scroll((preview.scroll, 0)), card);
+}
+
+/// Draw the confirm overlay for a pending `manager.confirm`, centered over
+/// the whole frame. Nothing is drawn when there's nothing to confirm.
+pub fn render_confirm(f: &mut ratatui::Frame, manager: &TransferManager, theme: &Theme) {
+    let Some(confirm) = &manager.confirm else { return };
+    let (name, sender, action) = match confirm {
+        Confirm::Download(id) => {
+            let Some(entry) = manager.entry(id) else { return };
+            (entry.offer.filename.clone(), entry.offer.sender_nickname.clone(), "download")
+        }
+        Confirm::Open(path) => {
+            let entry = manager.entries.iter().find(|e| matches!(&e.state, TransferState::Complete(p) if p == path));
+            let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+            (name, entry.map(|e| e.offer.sender_nickname.clone()).unwrap_or_default(), "open")
+        }
+    };
+
+    let area = f.area();
+    let width = 52.min(area.width);
+    let card = Rect::new(area.width.saturating_sub(width) / 2, area.height.saturating_sub(5) / 2, width, 5.min(area.height));
+    f.render_widget(ratatui::widgets::Clear, card);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_set(theme.border_set())
+        .style(Style::default().bg(theme.bg))
+        .border_style(Style::default().fg(theme.error))
+        .title(Line::styled(" risky file ", Style::default().fg(theme.error).add_modifier(Modifier::BOLD)).centered())
+        .title_bottom(
+            Line::from(vec![
+                Span::styled(" y", Style::default().fg(theme.hint_key)),
+                Span::styled(format!(" {action}  "), Style::default().fg(theme.hint_text)),
+                Span::styled("Esc", Style::default().fg(theme.hint_key)),
+                Span::styled(" cancel ", Style::default().fg(theme.hint_text)),
+            ])
+            .centered(),
+        );
+    let from = if sender.is_empty() { String::new() } else { format!(" from {sender}") };
+    let lines = vec![
+        Line::styled(name, Style::default().fg(theme.text).add_modifier(Modifier::BOLD)).centered(),
+        Line::styled("can run as a program when opened.", Style::default().fg(theme.text)).centered(),
+        Line::styled(format!("Only {action} it if you trust the file{from}."), Style::default().fg(theme.text_muted))
+            .centered(),
+    ];
+    f.render_widget(Paragraph::new(lines).block(block).wrap(ratatui::widgets::Wrap { trim: true }), card);
+}
+
 // ── Tests ────────────────────────────────────────────────────────────────────
 
 #[cfg(test)]
@@ -431,9 +1252,16 @@ mod tests {
             filename: "test.txt".to_string(),
             size: 1024,
             hash: test_hash(),
+            key: None,
+            offer_id: [0; 16],
         }
     }
 
+    /// The entry `test_offer` adds, whoever it names.
+    fn test_id() -> EntryId {
+        test_offer("Alice").entry_id()
+    }
+
     #[test]
     fn new_manager_is_empty() {
         let m = TransferManager::new();
@@ -461,9 +1289,9 @@ mod tests {
     #[test]
     fn start_download_transitions_state() {
         let mut m = TransferManager::new();
-        let hash = test_hash();
+        let id = test_id();
         m.add_offer(test_offer("Alice"));
-        m.start_download(&hash);
+        m.start_download(&id);
         assert!(matches!(
             m.entries[0].state,
             TransferState::Downloading {
@@ -476,10 +1304,10 @@ mod tests {
     #[test]
     fn update_progress() {
         let mut m = TransferManager::new();
-        let hash = test_hash();
+        let id = test_id();
         m.add_offer(test_offer("Alice"));
-        m.start_download(&hash);
-        m.update_progress(&hash, 512, 1024);
+        m.start_download(&id);
+        m.update_progress(&id, 512, 1024);
         assert!(matches!(
             m.entries[0].state,
             TransferState::Downloading {
@@ -492,9 +1320,9 @@ mod tests {
     #[test]
     fn complete_download() {
         let mut m = TransferManager::new();
-        let hash = test_hash();
+        let id = test_id();
         m.add_offer(test_offer("Alice"));
-        m.complete_download(&hash, PathBuf::from("/tmp/test.txt"));
+        m.complete_download(&id, PathBuf::from("/tmp/test.txt"));
         match &m.entries[0].state {
             TransferState::Complete(p) => assert_eq!(p, &PathBuf::from("/tmp/test.txt")),
             _ => panic!("expected Complete state"),
@@ -504,9 +1332,9 @@ mod tests {
     #[test]
     fn fail_download() {
         let mut m = TransferManager::new();
-        let hash = test_hash();
+        let id = test_id();
         m.add_offer(test_offer("Alice"));
-        m.fail_download(&hash, "network error".into());
+        m.fail_download(&id, "network error".into());
         match &m.entries[0].state {
             TransferState::Failed(e) => assert_eq!(e, "network error"),
             _ => panic!("expected Failed state"),
@@ -541,40 +1369,66 @@ mod tests {
         assert_eq!(m.selected_index, 0);
     }
 
+    /// An immediate revocation, as `revoke` is called for one taking effect now.
+    fn now() -> Expiry {
+        Expiry { at_ms: 0, message_id: [1; 16] }
+    }
+
     #[test]
-    fn retract_removes_entry() {
+    fn revoke_removes_entry() {
         let mut m = TransferManager::new();
-        let hash = test_hash();
+        let id = test_id();
         m.add_offer(test_offer("Alice"));
         assert_eq!(m.entries.len(), 1);
-        let removed = m.retract(&hash);
+        let removed = m.revoke(&id, now(), 10);
         assert_eq!(removed, Some("test.txt".into()));
         assert!(m.entries.is_empty());
     }
 
     #[test]
-    fn retract_returns_none_for_missing() {
+    fn identical_offers_are_separate_entries() {
+        let mut m = TransferManager::new();
+        let carol = iroh::SecretKey::from_bytes(&[3u8; 32]).public();
+        let from_carol = FileOffer { sender_nickname: "Carol".into(), sender_id: carol, ..test_offer("Carol") };
+        let again = FileOffer { offer_id: [1; 16], ..test_offer("Alice") };
+        let (carol_id, again_id) = (from_carol.entry_id(), again.entry_id());
+        m.add_offer(test_offer("Alice"));
+        m.add_offer(from_carol);
+        m.add_offer(again);
+
+        m.start_download(&carol_id);
+        m.update_progress(&carol_id, 512, 1024);
+        m.complete_download(&again_id, PathBuf::from("/tmp/test.txt"));
+        assert!(matches!(m.entries[0].state, TransferState::Pending));
+        assert!(matches!(m.entries[1].state, TransferState::Downloading { bytes_received: 512, .. }));
+        assert!(matches!(m.entries[2].state, TransferState::Complete(_)));
+        assert_eq!(m.entry(&carol_id).map(|e| e.offer.sender_nickname.as_str()), Some("Carol"));
+    }
+
+    #[test]
+    fn revoke_returns_none_for_missing() {
         let mut m = TransferManager::new();
         let hash = Hash::from_bytes([99u8; 32]);
-        assert_eq!(m.retract(&hash), None);
+        assert_eq!(m.revoke(&EntryId { hash, ..test_id() }, now(), 10), None);
     }
 
     #[test]
-    fn retract_adjusts_selected_index() {
+    fn revoke_adjusts_selected_index() {
         let mut m = TransferManager::new();
         m.add_offer(test_offer("Alice"));
         let mut offer2 = test_offer("Bob");
         offer2.hash = Hash::from_bytes([99u8; 32]);
+        let id2 = offer2.entry_id();
         m.add_offer(offer2);
         m.selected_index = 1;
         // Remove the second entry (selected)
-        m.retract(&Hash::from_bytes([99u8; 32]));
+        m.revoke(&id2, now(), 10);
         assert_eq!(m.entries.len(), 1);
         assert_eq!(m.selected_index, 0);
     }
 
     #[test]
-    fn retract_keeps_index_when_earlier_removed() {
+    fn revoke_keeps_index_when_earlier_removed() {
         let mut m = TransferManager::new();
         m.add_offer(test_offer("Alice"));
         let mut offer2 = test_offer("Bob");
@@ -585,12 +1439,23 @@ mod tests {
         m.add_offer(offer3);
         m.selected_index = 2;
         // Remove the first entry, selected should stay in range
-        m.retract(&test_hash());
+        m.revoke(&test_id(), now(), 10);
         assert_eq!(m.entries.len(), 2);
         // selected_index 2 >= len 2, so it adjusts to 1
         assert_eq!(m.selected_index, 1);
     }
 
+    #[test]
+    fn download_path_stays_in_the_download_dir() {
+        let dir = Path::new("/downloads");
+        assert_eq!(download_path(dir, "notes.txt"), Some(PathBuf::from("/downloads/notes.txt")));
+        assert_eq!(download_path(dir, "../../.bashrc"), Some(PathBuf::from("/downloads/.bashrc")));
+        assert_eq!(download_path(dir, "/etc/passwd"), Some(PathBuf::from("/downloads/passwd")));
+        for bad in ["", ".", "..", "a/..", "/"] {
+            assert_eq!(download_path(dir, bad), None, "{bad:?}");
+        }
+    }
+
     #[test]
     fn format_file_size_units() {
         assert_eq!(format_file_size(0), "0 B");
@@ -636,4 +1501,412 @@ mod tests {
         assert_eq!(mime_from_extension("noext"), None);
     }
 
+    /// Snapshot the file pane with one entry in every transfer state.
+    #[test]
+    fn snapshot_file_pane_states() {
+        let mut m = TransferManager::new();
+        let offer = |name: &str, byte: u8| FileOffer {
+            filename: name.to_string(),
+            hash: Hash::from_bytes([byte; 32]),
+            ..test_offer("Alice")
+        };
+        m.add_offer(offer("pending.txt", 1));
+        m.add_offer(offer("downloading.bin", 2));
+        m.add_offer(offer("done.png", 3));
+        m.add_offer(offer("broken.zip", 4));
+        m.add_sent(offer("shared.rs", 5));
+        m.start_download(&offer("downloading.bin", 2).entry_id());
+        m.update_progress(&offer("downloading.bin", 2).entry_id(), 768, 1024);
+        m.complete_download(&offer("done.png", 3).entry_id(), PathBuf::from("piper-files/done.png"));
+        m.fail_download(&offer("broken.zip", 4).entry_id(), "timed out".into());
+        m.select_next();
+
+        let theme = crate::theme::Theme::dark();
+        let mut terminal =
+            ratatui::Terminal::new(ratatui::backend::TestBackend::new(56, 7)).unwrap();
+        terminal
+            .draw(|f| render_file_pane(f, f.area(), &m, true, 0, &theme))
+            .unwrap();
+        insta::assert_snapshot!(terminal.backend());
+    }
+
+    #[test]
+    fn revoke_now_or_later() {
+        let mut m = TransferManager::new();
+        let id = test_id();
+        let stranger = iroh::SecretKey::from_bytes(&[2u8; 32]).public();
+        let expiry = |at_ms| Expiry { at_ms, message_id: [1; 16] };
+        m.add_offer(test_offer("Alice"));
+        // Only the offer's own sender may revoke it, and only that offer.
+        assert_eq!(m.revoke(&EntryId { sender: stranger, ..id }, expiry(0), 10), None);
+        assert_eq!(m.revoke(&EntryId { offer_id: [7; 16], ..id }, expiry(0), 10), None);
+        assert_eq!(m.entries.len(), 1);
+        // A future expiry is remembered, then acted on by `expire`.
+        assert_eq!(m.revoke(&id, expiry(100), 10), None);
+        assert_eq!(m.entries[0].expiry, Some(expiry(100)));
+        assert!(m.expire(99).is_empty());
+        let gone = m.expire(100);
+        assert_eq!(gone.len(), 1);
+        assert_eq!(gone[0].offer.filename, "test.txt");
+        assert!(m.entries.is_empty());
+        // Immediate revocation removes at once, but not a finished download.
+        m.add_offer(test_offer("Alice"));
+        assert_eq!(m.revoke(&id, expiry(10), 10), Some("test.txt".into()));
+        m.add_offer(test_offer("Alice"));
+        m.complete_download(&id, PathBuf::from("test.txt"));
+        assert_eq!(m.revoke(&id, expiry(10), 10), None);
+        assert_eq!(m.entries.len(), 1);
+    }
+
+    #[test]
+    fn packing_becomes_sharing() {
+        let mut m = TransferManager::new();
+        let provisional = Hash::new(b"/home/alice/photos");
+        m.add_packing(FileOffer { hash: provisional, size: 0, filename: "photos.tar".into(), ..test_offer("You") });
+        let id = m.entries[0].offer.entry_id();
+        m.update_packing(&id, 512, 2048);
+        assert!(matches!(m.entries[0].state, TransferState::Packing { bytes_done: 512, total_bytes: 2048 }));
+        assert_eq!(m.finish_packing(&id, test_hash(), 2048, [3; 16]), Some("photos.tar".into()));
+        assert!(matches!(m.entries[0].state, TransferState::Sharing));
+        assert_eq!(m.entries[0].offer.hash, test_hash());
+        assert_eq!(m.entries[0].offer.size, 2048);
+        assert_eq!(m.entries[0].offer.offer_id, [3; 16]);
+    }
+
+    #[test]
+    fn only_a_share_can_be_invalidated() {
+        let mut m = TransferManager::new();
+        let id = test_id();
+        m.add_offer(test_offer("Alice"));
+        assert_eq!(m.invalidate(&id, "modified".into()), None);
+        m.revoke(&id, now(), 10);
+        m.add_sent(test_offer("Alice"));
+        assert_eq!(m.invalidate(&id, "moved or deleted".into()), Some("test.txt".into()));
+        assert!(matches!(&m.entries[0].state, TransferState::Invalidated(why) if why == "moved or deleted"));
+        assert_eq!(m.entries[0].timeline.last().map(|(_, step)| step), Some(&Milestone::Invalidated("moved or deleted".into())));
+        // Final: it isn't invalidated twice or revoked away.
+        assert_eq!(m.invalidate(&id, "modified".into()), None);
+        assert_eq!(m.revoke(&id, Expiry { at_ms: 10, message_id: [1; 16] }, 10), None);
+    }
+
+    #[test]
+    fn received_reactions_are_sent_and_counted_once() {
+        let mut m = TransferManager::new();
+        let id = test_id();
+        m.add_offer(test_offer("Alice"));
+        assert!(m.react(&id).is_none(), "not downloaded yet");
+        m.complete_download(&id, PathBuf::from("/tmp/test.txt"));
+        assert_eq!(m.react(&id).map(|o| o.filename), Some("test.txt".into()));
+        assert!(m.entries[0].reacted());
+        assert!(m.react(&id).is_none(), "only once");
+
+        let mut sender = TransferManager::new();
+        sender.add_sent(test_offer("Alice"));
+        let (bob, carol) = (iroh::SecretKey::from_bytes(&[2; 32]).public(), iroh::SecretKey::from_bytes(&[3; 32]).public());
+        assert_eq!(sender.acknowledge(&id, bob, "bob".into()), Some("test.txt".into()));
+        assert_eq!(sender.acknowledge(&id, bob, "bob".into()), None);
+        assert_eq!(sender.acknowledge(&id, carol, "carol".into()), Some("test.txt".into()));
+        assert_eq!(sender.entries[0].received_by, [bob, carol]);
+        let steps: Vec<_> = sender.entries[0].timeline.iter().map(|(_, step)| step.label()).collect();
+        assert_eq!(steps, ["sharing", "bob received it", "carol received it"]);
+        assert!(sender.acknowledge(&EntryId { offer_id: [9; 16], ..id }, bob, "bob".into()).is_none());
+    }
+
+    #[test]
+    fn downloads_are_counted_once_per_peer_on_our_shares() {
+        let mut m = TransferManager::new();
+        let (bob, carol) = (iroh::SecretKey::from_bytes(&[2; 32]).public(), iroh::SecretKey::from_bytes(&[3; 32]).public());
+        m.add_offer(test_offer("Alice"));
+        m.add_sent(FileOffer { offer_id: [1; 16], ..test_offer("You") });
+        m.served(&test_hash(), bob, "bob");
+        m.served(&test_hash(), bob, "bob");
+        assert!(m.entries[0].downloaded_by.is_empty(), "not our offer");
+        assert_eq!(m.entries[1].downloaded_by, [bob]);
+
+        // A reaction counts too, but not twice for a peer already counted.
+        let ours = m.entries[1].offer.entry_id();
+        m.acknowledge(&ours, bob, "bob".into());
+        m.acknowledge(&ours, carol, "carol".into());
+        assert_eq!(m.entries[1].downloaded_by, [bob, carol]);
+        let steps: Vec<_> = m.entries[1].timeline.iter().map(|(_, step)| step.label()).collect();
+        assert_eq!(steps, ["sharing", "bob downloaded it", "bob received it", "carol received it"]);
+    }
+
+    #[test]
+    fn execute_bit_is_detected() {
+        assert!(!is_executable(Path::new("does-not-exist.sh")));
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("tool");
+            std::fs::write(&path, b"#!/bin/sh\n").unwrap();
+            assert!(!is_executable(&path));
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+            assert!(is_executable(&path));
+        }
+    }
+
+    #[test]
+    fn risky_extensions_are_configurable() {
+        let mut m = TransferManager::new();
+        assert!(m.is_risky("setup.EXE"));
+        assert!(m.is_risky("install.sh"));
+        assert!(!m.is_risky("photo.jpg"));
+        assert!(!m.is_risky("README"));
+        m.risky_extensions = vec![".jpg".into()];
+        assert!(m.is_risky("photo.jpg"));
+        assert!(!m.is_risky("setup.exe"));
+    }
+
+    #[test]
+    fn snapshot_risky_confirm_overlay() {
+        let mut m = TransferManager::new();
+        let offer = FileOffer { filename: "setup.exe".into(), ..test_offer("Alice") };
+        m.add_offer(offer);
+        m.confirm = Some(Confirm::Download(test_id()));
+
+        let theme = crate::theme::Theme::dark();
+        let mut terminal =
+            ratatui::Terminal::new(ratatui::backend::TestBackend::new(56, 9)).unwrap();
+        terminal
+            .draw(|f| {
+                render_file_pane(f, f.area(), &m, true, 0, &theme);
+                render_confirm(f, &m, &theme);
+            })
+            .unwrap();
+        insta::assert_snapshot!(terminal.backend());
+    }
+
+    #[test]
+    fn risky_downloads_wait_for_confirmation() {
+        let mut m = TransferManager::new();
+        m.add_offer(FileOffer { filename: "setup.exe".into(), ..test_offer("Alice") });
+        let id = test_id();
+        assert!(m.request_download(&id, false).is_none());
+        assert_eq!(m.confirm, Some(Confirm::Download(id)));
+        assert!(matches!(m.entries[0].state, TransferState::Pending));
+        assert_eq!(m.request_download(&id, true).unwrap().filename, "setup.exe");
+        assert!(matches!(m.entries[0].state, TransferState::Downloading { .. }));
+
+        m.confirm = None;
+        assert!(m.request_open(Path::new("/tmp/notes.txt"), false));
+        assert!(!m.request_open(Path::new("/tmp/setup.exe"), false));
+        assert_eq!(m.confirm, Some(Confirm::Open("/tmp/setup.exe".into())));
+        assert!(m.request_open(Path::new("/tmp/setup.exe"), true));
+    }
+
+    #[test]
+    fn timeline_records_steps_and_milestones() {
+        let mut m = TransferManager::new();
+        let id = test_id();
+        m.add_offer(test_offer("Alice"));
+        m.start_download(&id);
+        m.update_progress(&id, 100, 1000);
+        m.update_progress(&id, 150, 1000); // still in the 10% step
+        m.update_progress(&id, 640, 1000); // jumps straight to 60%
+        m.fail_download(&id, "timed out".into());
+        let steps: Vec<Milestone> = m.entries[0].timeline.iter().map(|(_, s)| s.clone()).collect();
+        assert_eq!(
+            steps,
+            [
+                Milestone::Offered,
+                Milestone::Started,
+                Milestone::Reached(10),
+                Milestone::Reached(60),
+                Milestone::Failed("timed out".into())
+            ]
+        );
+    }
+
+    #[test]
+    fn only_small_pending_offers_preview() {
+        let mut m = TransferManager::new();
+        m.add_offer(test_offer("Alice"));
+        assert!(matches!(m.request_preview(), Some(Ok(offer)) if offer.hash == test_hash()));
+        m.entries[0].offer.size = PREVIEW_MAX + 1;
+        assert_eq!(m.request_preview().unwrap().unwrap_err(), PREVIEW_MAX + 1);
+        m.entries[0].state = TransferState::Sharing;
+        assert!(m.request_preview().is_none());
+
+        m.show_preview(test_id(), "test.txt".into(), b"hi".to_vec());
+        assert_eq!(m.preview.as_ref().map(|p| p.bytes.as_slice()), Some(&b"hi"[..]));
+        assert_eq!(m.entries[0].timeline.last().map(|(_, s)| s), Some(&Milestone::Previewed));
+    }
+
+    #[test]
+    fn preview_shows_text_scrubbed_and_binary_as_hex() {
+        assert_eq!(preview_lines(b"one\ntwo \x1b[2Jthree"), ["one", "two three"]);
+        assert_eq!(
+            preview_lines(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"),
+            ["000000  89 50 4e 47 0d 0a 1a 0a 00 00 00 0d 49 48 44 52  .PNG........IHDR"]
+        );
+    }
+
+    #[test]
+    fn fetch_progress_follows_verified_bytes() {
+        let mut m = TransferManager::new();
+        let id = test_id();
+        m.add_offer(test_offer("Alice"));
+        m.start_download(&id);
+        let report = |received, verified, size| FetchProgress { received, verified, size };
+        let shown = |m: &TransferManager| match m.entries[0].state {
+            TransferState::Downloading { bytes_received, total_bytes } => (bytes_received, total_bytes),
+            _ => panic!("expected Downloading"),
+        };
+
+        // A resumed fetch: the store already held 300 verified bytes.
+        m.fetch_progress(&id, report(0, 300, None));
+        assert_eq!(shown(&m), (300, 1024));
+        // A stale report doesn't move the bar back.
+        m.fetch_progress(&id, report(100, 200, None));
+        assert_eq!(shown(&m), (300, 1024));
+        assert_eq!(m.entries[0].fetched, report(100, 200, None));
+        // The offer understated the size: hold short of full until it's proven.
+        m.fetch_progress(&id, report(1700, 2000, None));
+        assert_eq!(shown(&m), (2000, 2001));
+        m.fetch_progress(&id, report(1800, 2048, Some(2048)));
+        assert_eq!(shown(&m), (2048, 2048));
+    }
+
+    #[test]
+    fn snapshot_details_popup() {
+        let mut m = TransferManager::new();
+        m.add_offer(test_offer("Alice"));
+        m.entries[0].state = TransferState::Downloading { bytes_received: 600, total_bytes: 1024 };
+        // Resumed: 200 bytes were already in the store.
+        m.entries[0].fetched = FetchProgress { received: 400, verified: 600, size: None };
+        m.entries[0].timeline = vec![
+            (36_000_000, Milestone::Offered),
+            (36_005_000, Milestone::Started),
+            (36_007_000, Milestone::Reached(50)),
+            (36_012_000, Milestone::Reached(60)),
+        ];
+        m.details = Some(test_id());
+
+        let theme = crate::theme::Theme::dark();
+        let mut terminal =
+            ratatui::Terminal::new(ratatui::backend::TestBackend::new(60, 10)).unwrap();
+        terminal.draw(|f| render_details(f, &m, 36_312_000, &theme)).unwrap();
+        insta::assert_snapshot!(terminal.backend());
+    }
+
+    #[test]
+    fn retries_fall_back_after_connect_errors() {
+        let bob = iroh::SecretKey::from_bytes(&[5u8; 32]).public();
+        assert_eq!(retry_plan("download: stream ended early", &[bob]), [Strategy::Default]);
+        assert_eq!(retry_plan("connect: timed out", &[bob]), [Strategy::RelayOnly, Strategy::Peer(bob)]);
+
+        let mut m = TransferManager::new();
+        let id = test_id();
+        m.add_offer(test_offer("Alice"));
+        m.start_download(&id);
+        m.fail_download(&id, "connect: timed out".into());
+        assert_eq!(m.current_attempt(&id), None);
+        m.start_download(&id);
+        m.start_attempt(&id, "relay only".into());
+        assert!(matches!(m.entries[0].state, TransferState::Downloading { bytes_received: 0, .. }));
+        assert_eq!(m.current_attempt(&id), Some("relay only"));
+    }
+
+    // ── State machine properties ─────────────────────────────────────────
+
+    /// Short name of a state, for comparing before and after.
+    fn kind(state: &TransferState) -> &'static str {
+        match state {
+            TransferState::Pending => "pending",
+            TransferState::Downloading { .. } => "downloading",
+            TransferState::Complete(_) => "complete",
+            TransferState::Failed(_) => "failed",
+            TransferState::Sharing => "sharing",
+            TransferState::Packing { .. } => "packing",
+            TransferState::Invalidated(_) => "invalidated",
+        }
+    }
+
+    /// The edges documented on `TransferState`.
+    fn legal(from: &str, to: &str) -> bool {
+        from == to
+            || matches!(
+                (from, to),
+                ("pending", "downloading" | "complete" | "failed")
+                    | ("downloading", "complete" | "failed")
+                    | ("failed", "downloading")
+                    | ("packing", "sharing" | "failed")
+                    | ("sharing", "invalidated")
+            )
+    }
+
+    /// Random sequences of every operation, over a pool of three hashes,
+    /// two senders and two offer IDs so offers collide. After each step: the selection is in bounds, every
+    /// entry that stayed only moved along a legal edge, and at most one
+    /// entry moved at all. Like `tests/wire.rs`, a seeded `StdRng` rather
+    /// than proptest; `PIPER_FUZZ_SEED` picks another seed.
+    #[test]
+    fn random_operations_keep_the_invariants() {
+        use rand::rngs::StdRng;
+        use rand::{Rng, SeedableRng};
+
+        let seed = std::env::var("PIPER_FUZZ_SEED").ok().and_then(|v| v.parse().ok()).unwrap_or(0x5eed);
+        let mut rng = StdRng::seed_from_u64(seed);
+        let senders = [1u8, 5].map(|b| iroh::SecretKey::from_bytes(&[b; 32]).public());
+        for run in 0..300 {
+            let mut m = TransferManager::new();
+            // Filenames are unique, so an entry can be followed across steps.
+            let mut next_name = 0;
+            for step in 0..40 {
+                let hash = Hash::from_bytes([rng.random_range(0..3u8); 32]);
+                let id = EntryId { hash, sender: senders[rng.random_range(0..2)], offer_id: [rng.random_range(0..2u8); 16] };
+                let before: Vec<(String, &'static str)> = m.entries.iter().map(|e| (e.offer.filename.clone(), kind(&e.state))).collect();
+                let op = rng.random_range(0..17);
+                let mut offer = || {
+                    next_name += 1;
+                    let ext = if next_name % 4 == 0 { "exe" } else { "txt" };
+                    FileOffer {
+                        sender_nickname: "Bob".into(),
+                        sender_id: id.sender,
+                        filename: format!("f{next_name}.{ext}"),
+                        size: 1000,
+                        hash,
+                        key: None,
+                        offer_id: id.offer_id,
+                    }
+                };
+                match op {
+                    0 => m.add_offer(offer()),
+                    1 => m.add_sent(offer()),
+                    2 => m.add_packing(offer()),
+                    3 => m.start_download(&id),
+                    4 => drop(m.request_download(&id, rng.random())),
+                    5 => m.update_progress(&id, rng.random_range(0..=1000), 1000),
+                    6 => m.start_attempt(&id, "relay only".into()),
+                    7 => m.complete_download(&id, PathBuf::from("/tmp/done")),
+                    8 => m.fail_download(&id, "timed out".into()),
+                    9 => m.update_packing(&id, 10, 100),
+                    10 => drop(m.finish_packing(&id, Hash::from_bytes([rng.random_range(0..3u8); 32]), 500, id.offer_id)),
+                    11 => drop(m.revoke(&id, Expiry { at_ms: 0, message_id: [0; 16] }, 100)),
+                    12 => {
+                        let expiry = Expiry { at_ms: rng.random_range(0..200), message_id: [0; 16] };
+                        drop(m.revoke(&id, expiry, 100));
+                    }
+                    13 => drop(m.expire(rng.random_range(0..200))),
+                    14 => drop(m.invalidate(&id, "modified".into())),
+                    15 => m.select_next(),
+                    _ => m.select_prev(),
+                }
+
+                let context = format!("seed {seed:#x}, run {run}, step {step}, op {op}");
+                assert!(m.selected_index < m.entries.len().max(1), "selection out of bounds ({context})");
+                let mut moved = 0;
+                for entry in &m.entries {
+                    let to = kind(&entry.state);
+                    let Some((_, from)) = before.iter().find(|(name, _)| *name == entry.offer.filename) else { continue };
+                    assert!(legal(from, to), "{} went {from} → {to} ({context})", entry.offer.filename);
+                    moved += usize::from(*from != to);
+                }
+                assert!(moved <= 1, "one step moved {moved} entries ({context})");
+            }
+        }
+    }
 }
@@ -0,0 +1,187 @@
+//! Webhook bridge: mirror a room to and from an HTTP endpoint.
+//!
+//! With `--webhook <url>`, every chat message received from a peer is POSTed
+//! to `url` as one JSON object — the same shape `piper-chat tail --json`
+//! prints. With `--webhook-poll <url>`, that endpoint is fetched every few
+//! seconds and whatever it returns is sent into the room as if typed. Together
+//! they make a bridge to Slack, Matrix or Discord a small script rather than a
+//! bot written against the library.
+//!
+//! The poll endpoint answers with a JSON array of strings, or of objects with
+//! a `"text"` field; an empty body means nothing to send. Failures are
+//! reported once when they start, not on every attempt, so a server that's
+//! down doesn't flood the chat.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use anyhow::Result;
+use iroh::EndpointId;
+use serde::Deserialize;
+use tokio::sync::mpsc;
+
+use crate::net::{Message, MessageId, now_ms};
+use crate::tail;
+
+/// How often `--webhook-poll` fetches outgoing messages.
+pub const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Something for the event loop from the bridge's background tasks.
+pub enum WebhookEvent {
+    /// A message from the poll endpoint to send into the room.
+    Outgoing(String),
+    /// A request started failing.
+    Error(String),
+}
+
+/// Forwards incoming chat messages to the webhook URL.
+pub struct Webhook {
+    client: reqwest::Client,
+    url: String,
+    /// Message IDs already forwarded: a message can arrive both by gossip
+    /// and over a direct stream.
+    forwarded: HashSet<MessageId>,
+    /// Set while POSTs are failing, so only the first failure is reported.
+    failing: Arc<AtomicBool>,
+    events: mpsc::Sender<WebhookEvent>,
+}
+
+impl Webhook {
+    /// A bridge POSTing to `url`, reporting failures on `events`.
+    pub fn new(url: String, events: mpsc::Sender<WebhookEvent>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url,
+            forwarded: HashSet::new(),
+            failing: Arc::new(AtomicBool::new(false)),
+            events,
+        }
+    }
+
+    /// POST `msg` (signed by `from`) in the background if it's a chat
+    /// message we haven't forwarded yet.
+    pub fn forward(&mut self, from: EndpointId, msg: &Message) {
+        let Some(body) = self.body(from, msg) else { return };
+        let request = self
+            .client
+            .post(&self.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body);
+        let failing = self.failing.clone();
+        let events = self.events.clone();
+        tokio::spawn(async move {
+            let result = request.send().await.and_then(|r| r.error_for_status());
+            report(&failing, &events, result.err().map(|e| format!("webhook POST failed: {e}"))).await;
+        });
+    }
+
+    /// The JSON body for `msg`, or `None` if it isn't a new chat message.
+    fn body(&mut self, from: EndpointId, msg: &Message) -> Option<String> {
+        let Message::Chat { message_id, .. } = msg else { return None };
+        if !self.forwarded.insert(*message_id) {
+            return None;
+        }
+        tail::format_line(from, msg, now_ms(), true)
+    }
+}
+
+/// Fetch `url` every `POLL_INTERVAL` in the background, sending each message
+/// it returns to `events` as `WebhookEvent::Outgoing`. Stops when the event
+/// loop drops its receiver.
+pub fn spawn_poller(url: String, events: mpsc::Sender<WebhookEvent>) {
+    let client = reqwest::Client::new();
+    let failing = Arc::new(AtomicBool::new(false));
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+        loop {
+            interval.tick().await;
+            let result: Result<Vec<String>> = async {
+                let body = client.get(&url).send().await?.error_for_status()?.text().await?;
+                parse_outgoing(&body)
+            }
+            .await;
+            match result {
+                Ok(messages) => {
+                    report(&failing, &events, None).await;
+                    for text in messages {
+                        if events.send(WebhookEvent::Outgoing(text)).await.is_err() {
+                            return;
+                        }
+                    }
+                }
+                Err(e) => report(&failing, &events, Some(format!("webhook poll failed: {e}"))).await,
+            }
+            if events.is_closed() {
+                return;
+            }
+        }
+    });
+}
+
+/// Record a request's outcome in `failing`, sending `error` only when it
+/// starts a run of failures.
+async fn report(failing: &AtomicBool, events: &mpsc::Sender<WebhookEvent>, error: Option<String>) {
+    let was_failing = failing.swap(error.is_some(), Ordering::Relaxed);
+    if let Some(error) = error
+        && !was_failing
+    {
+        let _ = events.send(WebhookEvent::Error(error)).await;
+    }
+}
+
+/// Parse a poll response: a JSON array of strings or of `{"text": ...}`
+/// objects. Blank bodies and blank messages yield nothing.
+pub fn parse_outgoing(body: &str) -> Result<Vec<String>> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Outgoing {
+        Text(String),
+        Object { text: String },
+    }
+
+    if body.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    let items: Vec<Outgoing> = serde_json::from_str(body)?;
+    Ok(items
+        .into_iter()
+        .map(|item| match item {
+            Outgoing::Text(text) | Outgoing::Object { text } => text,
+        })
+        .filter(|text| !text.trim().is_empty())
+        .collect())
+}
+
+// ── Tests ────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn poll_responses() {
+        assert_eq!(parse_outgoing(r#"["hi", {"text": "there", "user": "x"}, " "]"#).unwrap(), ["hi", "there"]);
+        assert!(parse_outgoing("").unwrap().is_empty());
+        assert!(parse_outgoing("not json").is_err());
+    }
+
+    #[tokio::test]
+    async fn forwards_each_chat_once() {
+        let (tx, _rx) = mpsc::channel(1);
+        let mut hook = Webhook::new("http://localhost/".into(), tx);
+        let from = EndpointId::from_bytes(&[1u8; 32]).unwrap();
+        let chat = Message::Chat {
+            nickname: "Bob".into(),
+            text: "hi".into(),
+            message_id: [1; 16],
+            timestamp_ms: 0,
+        };
+        let body: serde_json::Value = serde_json::from_str(&hook.body(from, &chat).unwrap()).unwrap();
+        assert_eq!(body["text"], "hi");
+        assert!(hook.body(from, &chat).is_none());
+        let join = Message::Join { nickname: "Bob".into(), endpoint_id: from };
+        assert!(hook.body(from, &join).is_none());
+    }
+}
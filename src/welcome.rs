@@ -7,20 +7,36 @@
 //! Internally it follows a simple state machine pattern: a `WelcomeState`
 //! struct holds all form data, and key events transition between fields
 //! or trigger validation.
+//!
+//! After a room is created, `run_ticket_screen()` shows its ticket (text and
+//! QR code) before the chat starts. When joining, `run_join_screen()` dials
+//! the ticket's bootstrap peers first and lists why any it couldn't reach
+//! failed (see `net::bootstrap`). And when the last run crashed,
+//! `run_recover_screen()` offers to go back to it first (see `session`).
+//!
+//! The form sits under an animated banner (when the terminal has room for
+//! it) and shows, on its last row, whether a relay is reachable: main starts
+//! `bootstrap::check_relay()` as the screen opens and hands over the result
+//! through a `watch` channel. Both are driven by the screen's 50ms tick.
+
+use std::time::Instant;
 
-use anyhow::Result;
 use crossterm::{
     // `Event as TermEvent` is a *type alias import* — it renames crossterm's
     // `Event` to `TermEvent` to avoid collision with other `Event` types
     // (like `GossipEvent` in main.rs). The `as` keyword works at the import
     // level for renaming.
-    event::{Event as TermEvent, EventStream, KeyCode, KeyEventKind, KeyModifiers},
+    event::{
+        DisableBracketedPaste, EnableBracketedPaste, Event as TermEvent, EventStream, KeyCode,
+        KeyEventKind, KeyModifiers,
+    },
     // `execute!` is a macro that writes crossterm commands to a writer (stdout).
     // Macros in Rust are invoked with `!` and can generate arbitrary code at
     // compile time.
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
+use iroh::EndpointId;
 use iroh_tickets::Ticket;
 // `StreamExt` is an *extension trait* — it adds `.next()` to async streams.
 // In Rust, you must import extension traits to use their methods. This is the
@@ -35,7 +51,14 @@ use ratatui::{
 };
 use tokio::time::{Duration, interval};
 
+use crate::clipboard;
+use crate::contacts::Contacts;
+use crate::error::{Result, ResultExt};
+use crate::input;
 use crate::net::ChatTicket;
+use crate::net::bootstrap::{self, Probe, RelayCheck};
+use crate::rooms::Rooms;
+use crate::session::Session;
 use crate::theme::Theme;
 
 // ── Welcome screen state ────────────────────────────────────────────────────
@@ -57,6 +80,8 @@ enum WelcomeField {
     Name,
     Mode,
     Ticket,
+    Contact,
+    Room,
 }
 
 /// Whether the user is creating a new room or joining an existing one.
@@ -70,6 +95,11 @@ enum WelcomeField {
 enum RoomMode {
     Create,
     Join,
+    /// Open the private room with a saved contact (only offered when the
+    /// address book isn't empty).
+    Dm,
+    /// Rejoin an archived room (only offered when there are any).
+    Rejoin,
 }
 
 /// All mutable state for the welcome form.
@@ -88,6 +118,21 @@ struct WelcomeState {
     /// `Some(value)` or `None`. No null pointer exceptions possible.
     error: Option<String>,
     should_quit: bool,
+    /// Saved contacts offered in DM mode: nickname, ID, verified flag.
+    contacts: Vec<(String, EndpointId, bool)>,
+    /// Index into `contacts` of the chosen one.
+    contact: usize,
+    /// Archived rooms offered in Rejoin mode: label, summary, ticket.
+    rooms: Vec<(String, String, String)>,
+    /// Index into `rooms` of the chosen one.
+    room: usize,
+    /// Ticks of the screen's clock so far; drives the banner and spinner.
+    frame: u64,
+    /// The latest word from the relay check.
+    relay: RelayCheck,
+    /// Whether Enter in Create mode already warned that no relay answered,
+    /// so the next Enter goes ahead.
+    relay_warned: bool,
 }
 
 impl WelcomeState {
@@ -101,6 +146,62 @@ impl WelcomeState {
             ticket_cursor: 0,
             error: None,
             should_quit: false,
+            contacts: Vec::new(),
+            contact: 0,
+            rooms: Vec::new(),
+            room: 0,
+            frame: 0,
+            relay: RelayCheck::Checking,
+            relay_warned: false,
+        }
+    }
+
+    /// A form offering DM mode for each contact in `contacts`.
+    fn with_contacts(contacts: &Contacts) -> Self {
+        let mut state = Self::new();
+        state.contacts = contacts
+            .by_recency()
+            .into_iter()
+            .map(|(id, c)| (c.nickname.clone(), id, c.verified))
+            .collect();
+        state
+    }
+
+    /// Offer Rejoin mode for each archived room, summarised as of `now_ms`.
+    fn add_rooms(&mut self, rooms: &Rooms, now_ms: u64) {
+        self.rooms = rooms
+            .by_recency()
+            .into_iter()
+            .map(|r| (r.label.clone(), r.describe(now_ms), r.ticket.clone()))
+            .collect();
+    }
+
+    /// The modes on offer, in the order Left/Right cycles through them.
+    fn modes(&self) -> Vec<RoomMode> {
+        let mut modes = vec![RoomMode::Create, RoomMode::Join];
+        if !self.contacts.is_empty() {
+            modes.push(RoomMode::Dm);
+        }
+        if !self.rooms.is_empty() {
+            modes.push(RoomMode::Rejoin);
+        }
+        modes
+    }
+
+    /// Step the mode forwards (`1`) or backwards (`-1`), wrapping around.
+    fn cycle_mode(&mut self, step: isize) {
+        let modes = self.modes();
+        let i = modes.iter().position(|m| *m == self.mode).unwrap_or(0) as isize;
+        self.mode = modes[(i + step).rem_euclid(modes.len() as isize) as usize];
+    }
+
+    /// The field below Mode for the current mode, if any.
+    fn detail_field(&self) -> Option<WelcomeField> {
+        match self.mode {
+            RoomMode::Create => None,
+            RoomMode::Join => Some(WelcomeField::Ticket),
+            RoomMode::Dm => Some(WelcomeField::Contact),
+            RoomMode::Rejoin => Some(WelcomeField::Room),
         }
     }
 
@@ -112,32 +213,49 @@ impl WelcomeState {
     fn next_field(&mut self) {
         self.field = match self.field {
             WelcomeField::Name => WelcomeField::Mode,
-            WelcomeField::Mode => {
-                if self.mode == RoomMode::Join {
-                    WelcomeField::Ticket
-                } else {
-                    // In Create mode, skip Ticket (it's not relevant) and wrap
-                    WelcomeField::Name
-                }
-            }
-            WelcomeField::Ticket => WelcomeField::Name,
+            // In Create mode there's nothing below Mode, so wrap to Name.
+            WelcomeField::Mode => self.detail_field().unwrap_or(WelcomeField::Name),
+            WelcomeField::Ticket | WelcomeField::Contact | WelcomeField::Room => WelcomeField::Name,
         };
     }
 
     /// Cycle focus to the previous form field.
     fn prev_field(&mut self) {
         self.field = match self.field {
-            WelcomeField::Name => {
-                if self.mode == RoomMode::Join {
-                    WelcomeField::Ticket
-                } else {
-                    WelcomeField::Mode
-                }
-            }
+            WelcomeField::Name => self.detail_field().unwrap_or(WelcomeField::Mode),
             WelcomeField::Mode => WelcomeField::Name,
-            WelcomeField::Ticket => WelcomeField::Mode,
+            WelcomeField::Ticket | WelcomeField::Contact | WelcomeField::Room => WelcomeField::Mode,
         };
     }
+
+    /// Insert pasted text (bracketed paste or Ctrl+V) into the form.
+    ///
+    /// Into the name field only the first line goes. Anywhere else the text
+    /// is treated as a ticket: the form switches to Join and focuses the
+    /// ticket field. Tickets copied from a terminal often pick up line
+    /// wraps or stray spaces, so only ASCII letters and digits are kept —
+    /// base32 never contains anything else.
+    fn paste(&mut self, text: &str) {
+        self.error = None;
+        if self.field == WelcomeField::Name {
+            let line = text.lines().next().unwrap_or_default().trim();
+            input::insert_str(&mut self.name, &mut self.name_cursor, line);
+            return;
+        }
+        self.mode = RoomMode::Join;
+        self.field = WelcomeField::Ticket;
+        let cleaned: String = text.chars().filter(char::is_ascii_alphanumeric).collect();
+        input::insert_str(&mut self.ticket, &mut self.ticket_cursor, &cleaned);
+    }
+
+    /// Whether the ticket field currently decodes: `None` while empty.
+    fn ticket_valid(&self) -> Option<bool> {
+        let ticket = self.ticket.trim();
+        if ticket.is_empty() {
+            return None;
+        }
+        Some(ChatTicket::parse(ticket).is_ok())
+    }
 }
 
 /// The result returned by the welcome screen to the caller.
@@ -150,6 +268,80 @@ impl WelcomeState {
 pub enum WelcomeResult {
     Create { nickname: String },
     Join { nickname: String, ticket: String },
+    /// Open the DM room with the saved contact `peer`.
+    Dm { nickname: String, peer: EndpointId },
+}
+
+// ── Banner and relay status ─────────────────────────────────────────────────
+//
+// Both animate off `WelcomeState::frame`, so rendering stays a pure function
+// of the state and a snapshot test can pin any frame it likes.
+
+/// The name in block letters, drawn above the card. Plain ASCII, so it
+/// needs no stand-in in ASCII mode.
+const BANNER: [&str; 4] = [
+    r" _ __  _  _ __                    _           _   ",
+    r"| '_ \(_)| '_ \ ___  _ _      __ | |_   __ _ | |_ ",
+    r"| .__/| || .__// -_)| '_| __ / _|| ' \ / _` ||  _|",
+    r"|_|   |_||_|   \___||_|      \__||_||_|\__,_| \__|",
+];
+
+/// Width of `BANNER`, in columns.
+const BANNER_WIDTH: usize = 50;
+
+/// Columns the banner's highlight travels past the right edge before it
+/// starts over, so each sweep is followed by a pause.
+const SHIMMER_PAUSE: usize = 24;
+
+/// Width of the highlight band, in columns.
+const SHIMMER_WIDTH: usize = 4;
+
+const SPINNER: [&str; 10] = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+const SPINNER_ASCII: [&str; 4] = ["|", "/", "-", "\\"];
+
+/// One row of the banner at `frame`: a band of accent colour sweeps across
+/// the dimmed letters, one column per tick.
+fn banner_line(row: &'static str, frame: u64, theme: &Theme) -> Line<'static> {
+    let head = (frame % (BANNER_WIDTH + SHIMMER_PAUSE) as u64) as usize;
+    let spans = row
+        .char_indices()
+        .map(|(col, c)| {
+            let style = if col <= head && head - col < SHIMMER_WIDTH {
+                Style::default().fg(theme.accent).add_modifier(Modifier::BOLD)
+            } else {
+                Style::default().fg(theme.text_dim)
+            };
+            Span::styled(&row[col..col + c.len_utf8()], style)
+        })
+        .collect::<Vec<_>>();
+    Line::from(spans)
+}
+
+/// The spinner glyph for `frame`; it turns every other tick.
+fn spinner(frame: u64, theme: &Theme) -> &'static str {
+    let glyphs: &[&str] = if theme.ascii { &SPINNER_ASCII } else { &SPINNER };
+    glyphs[(frame / 2) as usize % glyphs.len()]
+}
+
+/// The card's status row: what the relay check found so far.
+fn relay_status(state: &WelcomeState, theme: &Theme) -> Line<'static> {
+    let (text, color) = match &state.relay {
+        RelayCheck::Checking => (format!("{} checking the relay...", spinner(state.frame, theme)), theme.text_muted),
+        RelayCheck::Reachable { relay, after } => {
+            // The first label is the relay's region, e.g. `use1-1`.
+            let name = relay.split('.').next().unwrap_or(relay);
+            (
+                format!("{} relay {name} reachable {} {} ms", theme.glyph("●", "*"), theme.glyph("·", "-"), after.as_millis()),
+                theme.success,
+            )
+        }
+        RelayCheck::Unreachable => {
+            (format!("{} no relay reachable: LAN peers only", theme.glyph("✗", "x")), theme.error)
+        }
+        RelayCheck::Disabled => ("relays off (--paths direct)".to_string(), theme.text_muted),
+        RelayCheck::Error(e) => (format!("{} relay check failed: {e}", theme.glyph("✗", "x")), theme.error),
+    };
+    Line::from(Span::styled(text, Style::default().fg(color)))
 }
 
 // ── UI rendering ────────────────────────────────────────────────────────────
@@ -170,13 +362,26 @@ fn ui_welcome(f: &mut ratatui::Frame, state: &WelcomeState, theme: &Theme) {
 
     let card_w: u16 = 52;
     let card_h: u16 = 14;
+    // The banner goes above the card, with a blank row between, only when
+    // the whole stack fits with a row to spare above and below.
+    let banner_h = BANNER.len() as u16 + 1;
+    let banner = area.width >= BANNER_WIDTH as u16 && area.height >= card_h + banner_h + 2;
+    let stack_h = if banner { card_h + banner_h } else { card_h };
     let x = area.width.saturating_sub(card_w) / 2;
-    let y = area.height.saturating_sub(card_h) / 2;
+    let top = area.height.saturating_sub(stack_h) / 2;
+    let y = if banner { top + banner_h } else { top };
     let card = Rect::new(x, y, card_w.min(area.width), card_h.min(area.height));
 
+    if banner {
+        let lines: Vec<Line> = BANNER.iter().map(|row| banner_line(row, state.frame, theme)).collect();
+        let banner_area = Rect::new(area.width.saturating_sub(BANNER_WIDTH as u16) / 2, top, BANNER_WIDTH as u16, BANNER.len() as u16);
+        f.render_widget(Paragraph::new(lines), banner_area);
+    }
+
     f.render_widget(Clear, card);
     let block = Block::default()
         .borders(Borders::ALL)
+        .border_set(theme.border_set())
         .style(Style::default().bg(theme.bg))
         .border_style(Style::default().fg(theme.border_focused))
         .title(" piper-chat ")
@@ -241,33 +446,94 @@ fn ui_welcome(f: &mut ratatui::Frame, state: &WelcomeState, theme: &Theme) {
     } else {
         "  Mode: "
     };
-    let (create_style, join_style) = match state.mode {
-        RoomMode::Create => (
+    // The chosen mode is highlighted; the others are muted.
+    let option_style = |mode: RoomMode| {
+        if state.mode == mode {
             Style::default()
                 .fg(theme.accent_on_bg)
                 .bg(theme.accent_bg)
-                .add_modifier(Modifier::BOLD),
-            Style::default().fg(theme.text_muted),
-        ),
-        RoomMode::Join => (
-            Style::default().fg(theme.text_muted),
-            Style::default()
-                .fg(theme.accent_on_bg)
-                .bg(theme.accent_bg)
-                .add_modifier(Modifier::BOLD),
-        ),
+                .add_modifier(Modifier::BOLD)
+        } else {
+            Style::default().fg(theme.text_muted)
+        }
     };
-    lines.push(Line::from(vec![
+    let mut mode_spans = vec![
         Span::styled(mode_label, mode_style),
-        Span::styled(" Create ", create_style),
+        Span::styled(" Create ", option_style(RoomMode::Create)),
         Span::raw("  "),
-        Span::styled(" Join ", join_style),
-    ]));
+        Span::styled(" Join ", option_style(RoomMode::Join)),
+    ];
+    if !state.contacts.is_empty() {
+        mode_spans.push(Span::raw("  "));
+        mode_spans.push(Span::styled(" DM ", option_style(RoomMode::Dm)));
+    }
+    if !state.rooms.is_empty() {
+        mode_spans.push(Span::raw("  "));
+        mode_spans.push(Span::styled(" Rejoin ", option_style(RoomMode::Rejoin)));
+    }
+    lines.push(Line::from(mode_spans));
     lines.push(Line::from(""));
 
+    // ── Contact field (DM mode; takes the ticket row's place) ───────────
+
+    if state.mode == RoomMode::Dm {
+        let focused = state.field == WelcomeField::Contact;
+        let label_style = if focused {
+            Style::default()
+                .fg(theme.accent)
+                .add_modifier(Modifier::BOLD)
+        } else {
+            Style::default().fg(theme.text)
+        };
+        let (name, _, verified) = &state.contacts[state.contact.min(state.contacts.len() - 1)];
+        lines.push(Line::from(vec![
+            Span::styled(if focused { "> Contact: " } else { "  Contact: " }, label_style),
+            Span::styled("< ", Style::default().fg(theme.text_muted)),
+            Span::styled(name.as_str(), Style::default().fg(theme.text)),
+            if *verified {
+                Span::styled(theme.glyph(" ✓", " *"), Style::default().fg(theme.success))
+            } else {
+                Span::raw("")
+            },
+            Span::styled(" >", Style::default().fg(theme.text_muted)),
+        ]));
+        lines.push(Line::from(""));
+    }
+
+    // ── Room field (Rejoin mode; also takes the ticket row's place) ─────
+
+    if state.mode == RoomMode::Rejoin {
+        let focused = state.field == WelcomeField::Room;
+        let label_style = if focused {
+            Style::default()
+                .fg(theme.accent)
+                .add_modifier(Modifier::BOLD)
+        } else {
+            Style::default().fg(theme.text)
+        };
+        let room = state.room.min(state.rooms.len() - 1);
+        let (label, summary, _) = &state.rooms[room];
+        lines.push(Line::from(vec![
+            Span::styled(if focused { "> Room: " } else { "  Room: " }, label_style),
+            Span::styled("< ", Style::default().fg(theme.text_muted)),
+            Span::styled(label.as_str(), Style::default().fg(theme.text)),
+            Span::styled(" >", Style::default().fg(theme.text_muted)),
+            Span::styled(
+                format!("  {}/{}", room + 1, state.rooms.len()),
+                Style::default().fg(theme.text_dim),
+            ),
+        ]));
+        lines.push(Line::from(Span::styled(
+            format!("        {summary}"),
+            Style::default().fg(theme.text_muted),
+        )));
+        lines.push(Line::from(""));
+    }
+
     // ── Ticket field ─────────────────────────────────────────────────────
 
     let ticket_active = state.mode == RoomMode::Join;
+    let ticket_shown = matches!(state.mode, RoomMode::Create | RoomMode::Join);
     let ticket_style = if !ticket_active {
         Style::default().fg(theme.text_muted)
     } else if state.field == WelcomeField::Ticket {
@@ -292,23 +558,38 @@ fn ui_welcome(f: &mut ratatui::Frame, state: &WelcomeState, theme: &Theme) {
         state.ticket.clone()
     };
 
-    lines.push(Line::from(vec![
-        Span::styled(ticket_label, ticket_style),
-        Span::styled(
-            &ticket_display,
-            if ticket_active {
-                Style::default().fg(theme.text)
+    if ticket_shown {
+        lines.push(Line::from(vec![
+            Span::styled(ticket_label, ticket_style),
+            Span::styled(
+                &ticket_display,
+                if ticket_active {
+                    Style::default().fg(theme.text)
+                } else {
+                    Style::default().fg(theme.text_muted)
+                },
+            ),
+            if state.field == WelcomeField::Ticket && ticket_active {
+                Span::styled("_", Style::default().fg(theme.cursor_blink))
             } else {
-                Style::default().fg(theme.text_muted)
+                Span::raw("")
             },
-        ),
-        if state.field == WelcomeField::Ticket && ticket_active {
-            Span::styled("_", Style::default().fg(theme.cursor_blink))
-        } else {
-            Span::raw("")
-        },
-    ]));
-    lines.push(Line::from(""));
+            // Live validation: a checkmark as soon as the ticket decodes.
+            match state.ticket_valid() {
+                Some(true) if ticket_active => Span::styled(
+                    theme.glyph(" ✓", " ok"),
+                    Style::default()
+                        .fg(theme.success)
+                        .add_modifier(Modifier::BOLD),
+                ),
+                Some(false) if ticket_active => {
+                    Span::styled(theme.glyph(" ✗", " x"), Style::default().fg(theme.error))
+                }
+                _ => Span::raw(""),
+            },
+        ]));
+        lines.push(Line::from(""));
+    }
 
     // ── Error or hint line ───────────────────────────────────────────────
 
@@ -320,37 +601,42 @@ fn ui_welcome(f: &mut ratatui::Frame, state: &WelcomeState, theme: &Theme) {
                 .add_modifier(Modifier::BOLD),
         )));
     } else {
-        lines.push(Line::from(vec![
-            Span::styled(
-                "  Enter",
-                Style::default()
-                    .fg(theme.hint_key)
-                    .add_modifier(Modifier::BOLD),
-            ),
-            Span::styled(" to start  ", Style::default().fg(theme.hint_text)),
-            Span::styled(
-                "Tab",
-                Style::default()
-                    .fg(theme.hint_key)
-                    .add_modifier(Modifier::BOLD),
-            ),
-            Span::styled(" next field  ", Style::default().fg(theme.hint_text)),
-            Span::styled(
-                "Esc",
+        // The ticket field swaps "Tab next field" for the paste shortcut.
+        let hints: &[(&str, &str)] = if state.field == WelcomeField::Ticket && ticket_active {
+            &[("Enter", " to join  "), ("Ctrl+V", " paste  "), ("Esc", " quit")]
+        } else if state.field == WelcomeField::Contact {
+            &[("Enter", " to open DM  "), (theme.glyph("←/→", "<-/->"), " choose  "), ("Esc", " quit")]
+        } else if state.field == WelcomeField::Room {
+            &[("Enter", " to rejoin  "), (theme.glyph("←/→", "<-/->"), " choose  "), ("Esc", " quit")]
+        } else {
+            &[("Enter", " to start  "), ("Tab", " next field  "), ("Esc", " quit")]
+        };
+        let mut spans = vec![Span::raw("  ")];
+        for (key, text) in hints {
+            spans.push(Span::styled(
+                *key,
                 Style::default()
                     .fg(theme.hint_key)
                     .add_modifier(Modifier::BOLD),
-            ),
-            Span::styled(" quit", Style::default().fg(theme.hint_text)),
-        ]));
+            ));
+            spans.push(Span::styled(*text, Style::default().fg(theme.hint_text)));
+        }
+        lines.push(Line::from(spans));
     }
 
     let widget = Paragraph::new(lines);
     f.render_widget(widget, inner);
 
+    // The relay status takes the card's last row, clear of the form.
+    if inner.height > 0 {
+        let status = Rect::new(inner.x, inner.y + inner.height - 1, inner.width, 1);
+        f.render_widget(Paragraph::new(relay_status(state, theme)), status);
+    }
+
     match state.field {
         WelcomeField::Name => {
-            f.set_cursor_position((inner.x + 8 + state.name_cursor as u16, inner.y + 2));
+            let col = input::cursor_column(&state.name, state.name_cursor);
+            f.set_cursor_position((inner.x + 8 + col, inner.y + 2));
         }
         WelcomeField::Ticket if state.mode == RoomMode::Join => {
             let display_cursor = if state.ticket.len() > 30 {
@@ -402,6 +688,10 @@ fn handle_welcome_key(state: &mut WelcomeState, key: crossterm::event::KeyEvent)
                 state.error = Some("Name cannot be empty".into());
                 return;
             }
+            if !crate::net::valid_nickname(&name) {
+                state.error = Some(format!("Name can be at most {} characters", crate::net::MAX_NICKNAME));
+                return;
+            }
             if state.mode == RoomMode::Join && state.ticket.trim().is_empty() {
                 state.error = Some("Ticket is required to join".into());
                 return;
@@ -414,6 +704,13 @@ fn handle_welcome_key(state: &mut WelcomeState, key: crossterm::event::KeyEvent)
                 && <ChatTicket as Ticket>::deserialize(state.ticket.trim()).is_err()
             {
                 state.error = Some("Invalid ticket format".into());
+                return;
+            }
+            // A room created without a relay can only be joined from the
+            // same network. Say so once; a second Enter creates it anyway.
+            if state.mode == RoomMode::Create && state.relay == RelayCheck::Unreachable && !state.relay_warned {
+                state.relay_warned = true;
+                state.error = Some("No relay: press Enter again to create anyway".into());
             }
             // If no error was set, the caller (run_welcome_screen) will detect
             // Enter + no error and break out of the event loop.
@@ -428,25 +725,32 @@ fn handle_welcome_key(state: &mut WelcomeState, key: crossterm::event::KeyEvent)
                 WelcomeField::Mode => {
                     // The `|` in match arms means "or" — matches any of the listed patterns.
                     match key.code {
-                        KeyCode::Left
-                        | KeyCode::Right
-                        | KeyCode::Char('h')
-                        | KeyCode::Char('l') => {
-                            // Toggle between Create and Join
-                            state.mode = match state.mode {
-                                RoomMode::Create => RoomMode::Join,
-                                RoomMode::Join => RoomMode::Create,
-                            };
-                            // If switching away from Join, move focus off the Ticket field
-                            if state.mode == RoomMode::Create
-                                && state.field == WelcomeField::Ticket
-                            {
-                                state.field = WelcomeField::Mode;
-                            }
-                        }
+                        // Cycle Create → Join (→ DM → Rejoin) and back.
+                        KeyCode::Right | KeyCode::Char('l') => state.cycle_mode(1),
+                        KeyCode::Left | KeyCode::Char('h') => state.cycle_mode(-1),
                         _ => {}
                     }
                 }
+                WelcomeField::Contact => match key.code {
+                    KeyCode::Right | KeyCode::Char('l') => {
+                        state.contact = (state.contact + 1) % state.contacts.len().max(1);
+                    }
+                    KeyCode::Left | KeyCode::Char('h') => {
+                        let n = state.contacts.len().max(1);
+                        state.contact = (state.contact + n - 1) % n;
+                    }
+                    _ => {}
+                },
+                WelcomeField::Room => match key.code {
+                    KeyCode::Right | KeyCode::Char('l') => {
+                        state.room = (state.room + 1) % state.rooms.len().max(1);
+                    }
+                    KeyCode::Left | KeyCode::Char('h') => {
+                        let n = state.rooms.len().max(1);
+                        state.room = (state.room + n - 1) % n;
+                    }
+                    _ => {}
+                },
                 WelcomeField::Ticket => {
                     if state.mode == RoomMode::Join {
                         handle_text_input(&mut state.ticket, &mut state.ticket_cursor, key);
@@ -466,30 +770,13 @@ fn handle_welcome_key(state: &mut WelcomeState, key: crossterm::event::KeyEvent)
 /// `&mut String` lets us insert and remove characters in-place.
 /// `&mut usize` lets us update the cursor position.
 fn handle_text_input(text: &mut String, cursor: &mut usize, key: crossterm::event::KeyEvent) {
+    // The cursor is a byte index; the `input` helpers keep it on grapheme
+    // boundaries so composed characters (accents, CJK) edit as one unit.
     match key.code {
-        KeyCode::Char(c) => {
-            // `String::insert` inserts a char at a byte index. For ASCII input
-            // byte index == char index, so this is safe.
-            text.insert(*cursor, c);
-            *cursor += 1;
-        }
-        KeyCode::Backspace => {
-            if *cursor > 0 {
-                *cursor -= 1;
-                // `String::remove` removes the char at the given byte index and
-                // shifts all subsequent bytes left. O(n) but fine for short inputs.
-                text.remove(*cursor);
-            }
-        }
-        KeyCode::Left => {
-            // `saturating_sub` clamps at 0 instead of panicking on underflow.
-            *cursor = cursor.saturating_sub(1);
-        }
-        KeyCode::Right => {
-            if *cursor < text.len() {
-                *cursor += 1;
-            }
-        }
+        KeyCode::Char(c) => input::insert_char(text, cursor, c),
+        KeyCode::Backspace => input::backspace(text, cursor),
+        KeyCode::Left => input::move_left(text, cursor),
+        KeyCode::Right => input::move_right(text, cursor),
         _ => {}
     }
 }
@@ -498,6 +785,18 @@ fn handle_text_input(text: &mut String, cursor: &mut usize, key: crossterm::even
 
 /// Run the interactive welcome screen and return the user's choice.
 ///
+/// Saved `contacts` are offered as a third mode, DM, which opens the
+/// private room with one of them; archived `rooms` as a fourth, Rejoin,
+/// which returns the chosen room's ticket as a `Join`.
+///
+/// `theme` is the one the chat will use: like every screen before the
+/// chat, this one toggles it in place with Ctrl+T, so the choice carries
+/// through (and main saves it).
+///
+/// `relay` delivers the relay check's progress (see
+/// `bootstrap::check_relay`); the status row follows it, and Enter in
+/// Create mode asks for confirmation once if no relay was reachable.
+///
 /// This is an `async fn` — it returns a `Future` that must be `.await`ed.
 /// The `async` keyword lets us use `tokio::select!` and `.await` inside.
 ///
@@ -506,23 +805,32 @@ fn handle_text_input(text: &mut String, cursor: &mut usize, key: crossterm::even
 ///
 /// `Option<WelcomeResult>` nested inside `Result` is a common Rust pattern:
 /// `Result` handles errors, `Option` handles "no value" — they compose cleanly.
-pub async fn run_welcome_screen() -> Result<Option<WelcomeResult>> {
-    enable_raw_mode()?;
-    execute!(std::io::stdout(), EnterAlternateScreen)?;
-    let mut terminal = ratatui::Terminal::new(ratatui::backend::CrosstermBackend::new(
-        std::io::stdout(),
-    ))?;
-
-    let mut state = WelcomeState::new();
-    let mut theme = Theme::dark();
+pub async fn run_welcome_screen(
+    contacts: &Contacts,
+    rooms: &Rooms,
+    now_ms: u64,
+    theme: &mut Theme,
+    relay: tokio::sync::watch::Receiver<RelayCheck>,
+) -> Result<Option<WelcomeResult>> {
+    let (mut terminal, keyboard_enhanced) = enter_screen()?;
+
+    let mut state = WelcomeState::with_contacts(contacts);
+    state.add_rooms(rooms, now_ms);
     let mut events = EventStream::new();
     let mut tick = interval(Duration::from_millis(50));
 
     let result = loop {
-        terminal.draw(|f| ui_welcome(f, &state, &theme))?;
+        state.relay = relay.borrow().clone();
+        terminal.draw(|f| ui_welcome(f, &state, theme)).terminal()?;
 
         tokio::select! {
             ev = events.next() => {
+                // Bracketed paste delivers the whole clipboard as one event,
+                // so a pasted ticket arrives intact instead of key by key.
+                if let Some(Ok(TermEvent::Paste(text))) = &ev {
+                    state.paste(text);
+                    continue;
+                }
                 if let Some(Ok(TermEvent::Key(key))) = ev {
                     if key.kind != KeyEventKind::Press { continue; }
 
@@ -534,6 +842,20 @@ pub async fn run_welcome_screen() -> Result<Option<WelcomeResult>> {
                         continue;
                     }
 
+                    // Ctrl+V reads the system clipboard directly, for terminals
+                    // whose paste shortcut doesn't send a bracketed paste.
+                    if key.code == KeyCode::Char('v')
+                        && key.modifiers.contains(KeyModifiers::CONTROL)
+                    {
+                        match clipboard::paste() {
+                            Some(text) => state.paste(&text),
+                            None => {
+                                state.error = Some("Clipboard unavailable, use terminal paste".into());
+                            }
+                        }
+                        continue;
+                    }
+
                     handle_welcome_key(&mut state, key);
 
                     if state.should_quit {
@@ -548,20 +870,474 @@ pub async fn run_welcome_screen() -> Result<Option<WelcomeResult>> {
                                 nickname,
                                 ticket: state.ticket.trim().to_string(),
                             }),
+                            RoomMode::Dm => Some(WelcomeResult::Dm {
+                                nickname,
+                                peer: state.contacts[state.contact].1,
+                            }),
+                            RoomMode::Rejoin => Some(WelcomeResult::Join {
+                                nickname,
+                                ticket: state.rooms[state.room].2.clone(),
+                            }),
                         };
                     }
                 }
             }
-            _ = tick.tick() => {}
+            _ = tick.tick() => state.frame += 1,
         }
     };
 
-    disable_raw_mode()?;
-    execute!(std::io::stdout(), LeaveAlternateScreen)?;
+    leave_screen(keyboard_enhanced)?;
 
     Ok(result)
 }
 
+/// The terminal type used by the setup screens.
+type SetupTerminal = ratatui::Terminal<ratatui::backend::CrosstermBackend<std::io::Stdout>>;
+
+/// Switch the terminal into TUI mode for a setup screen. Returns the
+/// terminal and whether keyboard enhancement was enabled (to undo later).
+fn enter_screen() -> Result<(SetupTerminal, bool)> {
+    enable_raw_mode().terminal()?;
+    execute!(std::io::stdout(), EnterAlternateScreen, EnableBracketedPaste).terminal()?;
+    let keyboard_enhanced = input::enable_keyboard_enhancement();
+    let terminal = ratatui::Terminal::new(ratatui::backend::CrosstermBackend::new(
+        std::io::stdout(),
+    ))
+    .terminal()?;
+    Ok((terminal, keyboard_enhanced))
+}
+
+/// Restore the terminal after a setup screen.
+fn leave_screen(keyboard_enhanced: bool) -> Result<()> {
+    if keyboard_enhanced {
+        input::disable_keyboard_enhancement();
+    }
+    disable_raw_mode().terminal()?;
+    execute!(std::io::stdout(), LeaveAlternateScreen, DisableBracketedPaste).terminal()?;
+    Ok(())
+}
+
+// ── Ticket screen ───────────────────────────────────────────────────────────
+//
+// After creating a room from the welcome screen, the ticket is shown on its
+// own screen — as text and as a QR code — before entering the chat, so it
+// can't scroll away in the message log before it has been shared.
+
+/// State for the create-room ticket screen.
+struct TicketScreen {
+    ticket: String,
+    /// QR code rows drawn with half blocks, or `None` if encoding failed.
+    qr: Option<Vec<String>>,
+    /// When set, the hint line shows "Copied!" until this instant.
+    copied_until: Option<Instant>,
+}
+
+impl TicketScreen {
+    fn new(ticket: &str) -> Self {
+        Self {
+            ticket: ticket.to_string(),
+            qr: qr_rows(ticket),
+            copied_until: None,
+        }
+    }
+}
+
+/// Encode `data` as a QR code and render it as text rows.
+///
+/// `Dense1x2` packs two QR modules into one terminal cell using the half
+/// blocks `▀`/`▄`/`█`, so the code is roughly square on screen (terminal
+/// cells are about twice as tall as they are wide). Dark modules become
+/// filled glyphs; the quiet zone (blank margin scanners need) is spaces.
+fn qr_rows(data: &str) -> Option<Vec<String>> {
+    use qrcode::{QrCode, render::unicode::Dense1x2};
+    let code = QrCode::new(data.as_bytes()).ok()?;
+    let image = code.render::<Dense1x2>().quiet_zone(true).build();
+    Some(image.lines().map(str::to_string).collect())
+}
+
+/// Render the ticket screen: a centered card with the QR code (when the
+/// terminal is big enough), the ticket text, and key hints.
+fn ui_ticket(f: &mut ratatui::Frame, screen: &TicketScreen, theme: &Theme) {
+    let area = f.area();
+    f.render_widget(Block::default().style(Style::default().bg(theme.bg)), area);
+
+    // Only draw the QR code if it fits completely — a clipped code is
+    // unscannable and just noise. Its half blocks have no ASCII equivalent.
+    let qr = screen.qr.as_ref().filter(|_| !theme.ascii).filter(|rows| {
+        let w = rows.first().map_or(0, |r| r.chars().count()) as u16;
+        w + 4 <= area.width && rows.len() as u16 + 12 <= area.height
+    });
+    let qr_width = qr.and_then(|rows| rows.first()).map_or(0, |r| r.chars().count()) as u16;
+
+    let card_w = (qr_width + 4).max(56).min(area.width);
+    let text_w = card_w.saturating_sub(4).max(1) as usize;
+    // The ticket is base32 (ASCII), so byte chunks are character chunks.
+    let ticket_rows: Vec<&str> = screen
+        .ticket
+        .as_bytes()
+        .chunks(text_w)
+        .map(|chunk| std::str::from_utf8(chunk).unwrap_or_default())
+        .collect();
+
+    let mut lines: Vec<Line> = Vec::new();
+    lines.push(Line::from(Span::styled(
+        theme.glyph(
+            "Room created — share this ticket to invite others",
+            "Room created - share this ticket to invite others",
+        ),
+        Style::default().fg(theme.text_dim).add_modifier(Modifier::ITALIC),
+    )));
+    lines.push(Line::from(""));
+    match qr {
+        Some(rows) => {
+            let qr_style = Style::default().fg(theme.qr_dark).bg(theme.qr_light);
+            for row in rows {
+                lines.push(Line::from(Span::styled(row.as_str(), qr_style)));
+            }
+        }
+        None => lines.push(Line::from(Span::styled(
+            if theme.ascii {
+                "(no QR code in ASCII mode)"
+            } else {
+                "(enlarge the terminal to show a QR code)"
+            },
+            Style::default().fg(theme.text_muted),
+        ))),
+    }
+    lines.push(Line::from(""));
+    for row in &ticket_rows {
+        lines.push(Line::from(Span::styled(
+            *row,
+            Style::default().fg(theme.ticket_value),
+        )));
+    }
+    lines.push(Line::from(""));
+
+    let copied = screen.copied_until.is_some_and(|t| t > Instant::now());
+    let hints: &[(&str, &str)] = if copied {
+        &[("Copied!", "")]
+    } else {
+        &[("Enter", " join room  "), ("c", " copy  "), ("Esc", " quit")]
+    };
+    let mut spans = Vec::new();
+    for (key, text) in hints {
+        spans.push(Span::styled(
+            *key,
+            Style::default()
+                .fg(theme.hint_key)
+                .add_modifier(Modifier::BOLD),
+        ));
+        spans.push(Span::styled(*text, Style::default().fg(theme.hint_text)));
+    }
+    lines.push(Line::from(spans));
+
+    let card_h = (lines.len() as u16 + 2).min(area.height);
+    let card = Rect::new(
+        area.x + area.width.saturating_sub(card_w) / 2,
+        area.y + area.height.saturating_sub(card_h) / 2,
+        card_w,
+        card_h,
+    );
+    f.render_widget(Clear, card);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_set(theme.border_set())
+        .style(Style::default().bg(theme.bg))
+        .border_style(Style::default().fg(theme.border_focused))
+        .title(" your room ")
+        .title_alignment(Alignment::Center)
+        .title_style(Style::default().fg(theme.title));
+    let inner = block.inner(card);
+    f.render_widget(block, card);
+    f.render_widget(Paragraph::new(lines).alignment(Alignment::Center), inner);
+}
+
+/// Show the freshly created room's ticket before entering the chat.
+///
+/// Returns `Ok(true)` when the user presses Enter to join the room, or
+/// `Ok(false)` if they press Esc to quit instead.
+pub async fn run_ticket_screen(ticket: &str, theme: &mut Theme) -> Result<bool> {
+    let (mut terminal, keyboard_enhanced) = enter_screen()?;
+
+    let mut screen = TicketScreen::new(ticket);
+    let mut events = EventStream::new();
+    let mut tick = interval(Duration::from_millis(50));
+
+    let proceed = loop {
+        terminal.draw(|f| ui_ticket(f, &screen, theme)).terminal()?;
+
+        tokio::select! {
+            ev = events.next() => {
+                let Some(Ok(TermEvent::Key(key))) = ev else { continue };
+                if key.kind != KeyEventKind::Press { continue; }
+                let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+                // `c`, or Ctrl+Y as in the chat screen, copies the ticket.
+                let copy = matches!(
+                    (key.code, ctrl),
+                    (KeyCode::Char('c'), false) | (KeyCode::Char('y'), true)
+                );
+                match key.code {
+                    KeyCode::Enter => break true,
+                    KeyCode::Esc => break false,
+                    KeyCode::Char('t') if ctrl => theme.toggle(),
+                    _ if copy => {
+                        clipboard::copy(&screen.ticket);
+                        screen.copied_until = Some(Instant::now() + Duration::from_secs(2));
+                    }
+                    _ => {}
+                }
+            }
+            _ = tick.tick() => {}
+        }
+    };
+
+    leave_screen(keyboard_enhanced)?;
+
+    Ok(proceed)
+}
+
+// ── Join screen ─────────────────────────────────────────────────────────────
+//
+// Joining dials each bootstrap peer in the ticket on its own before the chat
+// starts. The first one to answer lets us in; if none do, each row says why,
+// instead of the chat sitting silently at "waiting for peers...".
+
+/// State for the join screen: each bootstrap peer, named, and its probe.
+struct JoinScreen {
+    peers: Vec<(EndpointId, String, Probe)>,
+}
+
+impl JoinScreen {
+    fn new(peers: Vec<(EndpointId, String)>) -> Self {
+        Self { peers: peers.into_iter().map(|(id, name)| (id, name, Probe::Trying)).collect() }
+    }
+
+    /// Record how the probe of `id` went.
+    fn update(&mut self, id: EndpointId, probe: Probe) {
+        if let Some(row) = self.peers.iter_mut().find(|(peer, ..)| *peer == id) {
+            row.2 = probe;
+        }
+    }
+
+    fn reached(&self) -> bool {
+        self.peers.iter().any(|(.., probe)| *probe == Probe::Reached)
+    }
+
+    /// Every probe is over and none got through.
+    fn all_failed(&self) -> bool {
+        self.peers.iter().all(|(.., probe)| matches!(probe, Probe::Failed(_)))
+    }
+
+    /// Start probing every peer again.
+    fn restart(&mut self) {
+        for row in &mut self.peers {
+            row.2 = Probe::Trying;
+        }
+    }
+}
+
+/// Render the join screen: a card with one row per bootstrap peer.
+fn ui_join(f: &mut ratatui::Frame, screen: &JoinScreen, theme: &Theme) {
+    let area = f.area();
+    f.render_widget(Block::default().style(Style::default().bg(theme.bg)), area);
+
+    let mut lines = vec![
+        Line::from(Span::styled(
+            if screen.all_failed() { "No one in the ticket answered" } else { "Reaching the room's peers..." },
+            Style::default().fg(theme.text_dim).add_modifier(Modifier::ITALIC),
+        )),
+        Line::from(""),
+    ];
+    for (id, name, probe) in &screen.peers {
+        let (status, color) = match probe {
+            Probe::Trying => ("trying...".to_string(), theme.text_muted),
+            Probe::Reached => ("reached".to_string(), theme.transfer_complete),
+            Probe::Failed(failure) => (failure.label(), theme.error),
+        };
+        lines.push(Line::from(vec![
+            Span::styled(format!("{name} "), Style::default().fg(theme.text).add_modifier(Modifier::BOLD)),
+            Span::styled(format!("{} ", id.fmt_short()), Style::default().fg(theme.text_muted)),
+            Span::styled(status, Style::default().fg(color)),
+        ]));
+    }
+    lines.push(Line::from(""));
+    let hints: &[(&str, &str)] = if screen.all_failed() {
+        &[("Enter", " join anyway  "), ("r", " retry  "), ("Esc", " quit")]
+    } else {
+        &[("Enter", " skip  "), ("Esc", " quit")]
+    };
+    let mut spans = Vec::new();
+    for (key, text) in hints {
+        spans.push(Span::styled(*key, Style::default().fg(theme.hint_key).add_modifier(Modifier::BOLD)));
+        spans.push(Span::styled(*text, Style::default().fg(theme.hint_text)));
+    }
+    lines.push(Line::from(spans));
+
+    let card_w = 60.min(area.width);
+    let card_h = (lines.len() as u16 + 2).min(area.height);
+    let card = Rect::new(
+        area.x + area.width.saturating_sub(card_w) / 2,
+        area.y + area.height.saturating_sub(card_h) / 2,
+        card_w,
+        card_h,
+    );
+    f.render_widget(Clear, card);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_set(theme.border_set())
+        .style(Style::default().bg(theme.bg))
+        .border_style(Style::default().fg(theme.border_focused))
+        .title(" joining ")
+        .title_alignment(Alignment::Center)
+        .title_style(Style::default().fg(theme.title));
+    let inner = block.inner(card);
+    f.render_widget(block, card);
+    f.render_widget(Paragraph::new(lines).alignment(Alignment::Center), inner);
+}
+
+/// Dial each of `peers` (bootstrap peers with display names) until one
+/// answers, showing how each probe goes.
+///
+/// Returns `Ok(true)` as soon as a peer is reached, or when the user
+/// presses Enter to join regardless; `Ok(false)` if they press Esc.
+pub async fn run_join_screen(endpoint: &iroh::Endpoint, peers: Vec<(EndpointId, String)>, theme: &mut Theme) -> Result<bool> {
+    let (mut terminal, keyboard_enhanced) = enter_screen()?;
+
+    let mut screen = JoinScreen::new(peers);
+    let mut events = EventStream::new();
+    let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+    let spawn_probes = |screen: &JoinScreen| {
+        for (id, ..) in &screen.peers {
+            let (endpoint, tx, id) = (endpoint.clone(), tx.clone(), *id);
+            tokio::spawn(async move {
+                let probe = match bootstrap::probe(&endpoint, id, bootstrap::PROBE_TIMEOUT).await {
+                    Ok(()) => Probe::Reached,
+                    Err(failure) => Probe::Failed(failure),
+                };
+                let _ = tx.send((id, probe)).await;
+            });
+        }
+    };
+    spawn_probes(&screen);
+
+    let proceed = loop {
+        terminal.draw(|f| ui_join(f, &screen, theme)).terminal()?;
+
+        tokio::select! {
+            ev = events.next() => {
+                let Some(Ok(TermEvent::Key(key))) = ev else { continue };
+                if key.kind != KeyEventKind::Press { continue; }
+                match key.code {
+                    KeyCode::Enter => break true,
+                    KeyCode::Esc => break false,
+                    KeyCode::Char('r') if screen.all_failed() => {
+                        screen.restart();
+                        spawn_probes(&screen);
+                    }
+                    KeyCode::Char('t') if key.modifiers.contains(KeyModifiers::CONTROL) => theme.toggle(),
+                    _ => {}
+                }
+            }
+            Some((id, probe)) = rx.recv() => {
+                screen.update(id, probe);
+                if screen.reached() {
+                    break true;
+                }
+            }
+        }
+    };
+
+    leave_screen(keyboard_enhanced)?;
+
+    Ok(proceed)
+}
+
+// ── Recover screen ──────────────────────────────────────────────────────────
+//
+// A session file left behind means the last run crashed or was killed (see
+// `session`). Before the welcome form, offer to go back to where it was.
+
+/// Render the recover screen: a card summing up the interrupted session.
+fn ui_recover(f: &mut ratatui::Frame, session: &Session, theme: &Theme) {
+    let area = f.area();
+    f.render_widget(Block::default().style(Style::default().bg(theme.bg)), area);
+
+    let room = session.room_name.clone().unwrap_or_else(|| "an unnamed room".to_string());
+    let mut files = format!("{} file{} in the pane", session.transfers.len(), if session.transfers.len() == 1 { "" } else { "s" });
+    match session.interrupted() {
+        0 => {}
+        1 => files.push_str(", 1 download cut off"),
+        n => files.push_str(&format!(", {n} downloads cut off")),
+    }
+    let mut lines = vec![
+        Line::from(Span::styled(
+            "The last session didn't end cleanly",
+            Style::default().fg(theme.text_dim).add_modifier(Modifier::ITALIC),
+        )),
+        Line::from(""),
+        Line::from(vec![
+            Span::styled(format!("{} ", session.nickname), Style::default().fg(theme.text).add_modifier(Modifier::BOLD)),
+            Span::styled(format!("in {room}"), Style::default().fg(theme.text)),
+        ]),
+        Line::from(Span::styled(files, Style::default().fg(theme.text_muted))),
+        Line::from(""),
+    ];
+    let mut spans = Vec::new();
+    for (key, text) in [("Enter", " resume  "), ("Esc", " start fresh")] {
+        spans.push(Span::styled(key, Style::default().fg(theme.hint_key).add_modifier(Modifier::BOLD)));
+        spans.push(Span::styled(text, Style::default().fg(theme.hint_text)));
+    }
+    lines.push(Line::from(spans));
+
+    let card_w = 56.min(area.width);
+    let card_h = (lines.len() as u16 + 2).min(area.height);
+    let card = Rect::new(
+        area.x + area.width.saturating_sub(card_w) / 2,
+        area.y + area.height.saturating_sub(card_h) / 2,
+        card_w,
+        card_h,
+    );
+    f.render_widget(Clear, card);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_set(theme.border_set())
+        .style(Style::default().bg(theme.bg))
+        .border_style(Style::default().fg(theme.border_focused))
+        .title(" recover ")
+        .title_alignment(Alignment::Center)
+        .title_style(Style::default().fg(theme.title));
+    let inner = block.inner(card);
+    f.render_widget(block, card);
+    f.render_widget(Paragraph::new(lines).alignment(Alignment::Center), inner);
+}
+
+/// Offer to resume the crashed `session`: `Ok(true)` on Enter, `Ok(false)`
+/// on Esc.
+pub async fn run_recover_screen(session: &Session, theme: &mut Theme) -> Result<bool> {
+    let (mut terminal, keyboard_enhanced) = enter_screen()?;
+
+    let mut events = EventStream::new();
+
+    let resume = loop {
+        terminal.draw(|f| ui_recover(f, session, theme)).terminal()?;
+
+        let Some(Ok(TermEvent::Key(key))) = events.next().await else { continue };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        match key.code {
+            KeyCode::Enter => break true,
+            KeyCode::Esc => break false,
+            KeyCode::Char('t') if key.modifiers.contains(KeyModifiers::CONTROL) => theme.toggle(),
+            _ => {}
+        }
+    };
+
+    leave_screen(keyboard_enhanced)?;
+
+    Ok(resume)
+}
+
 // ── Tests ────────────────────────────────────────────────────────────────────
 
 #[cfg(test)]
@@ -805,4 +1581,305 @@ mod tests {
         // Any key press clears the error
         assert!(state.error.is_none());
     }
+
+    // ── Rendering snapshots ──────────────────────────────────────────────
+
+    /// Render the welcome card into an in-memory `TestBackend`.
+    fn render(state: &WelcomeState, width: u16, height: u16) -> ratatui::backend::TestBackend {
+        let theme = Theme::dark();
+        let mut terminal =
+            ratatui::Terminal::new(ratatui::backend::TestBackend::new(width, height)).unwrap();
+        terminal.draw(|f| ui_welcome(f, state, &theme)).unwrap();
+        terminal.backend().clone()
+    }
+
+    #[test]
+    fn snapshot_welcome_create() {
+        let mut state = WelcomeState::new();
+        state.name = "Alice".into();
+        state.name_cursor = 5;
+        insta::assert_snapshot!(render(&state, 60, 18));
+    }
+
+    #[test]
+    fn snapshot_welcome_join_with_error() {
+        let mut state = WelcomeState::new();
+        state.name = "Bob".into();
+        state.mode = RoomMode::Join;
+        state.field = WelcomeField::Ticket;
+        state.ticket = "chatabc".into();
+        state.ticket_cursor = 7;
+        state.error = Some("invalid ticket".into());
+        insta::assert_snapshot!(render(&state, 60, 18));
+    }
+
+    /// The card is clamped, not overflowed, on a terminal smaller than it.
+    #[test]
+    fn snapshot_welcome_tiny_terminal() {
+        insta::assert_snapshot!(render(&WelcomeState::new(), 30, 8));
+    }
+
+    /// With room above the card, the banner is drawn, and the status row
+    /// reports a reachable relay by its region.
+    #[test]
+    fn snapshot_welcome_banner_and_relay() {
+        let mut state = WelcomeState::new();
+        state.name = "Alice".into();
+        state.name_cursor = 5;
+        state.frame = 20;
+        state.relay = RelayCheck::Reachable { relay: "use1-1.relay.n0.iroh.link".into(), after: Duration::from_millis(84) };
+        insta::assert_snapshot!(render(&state, 60, 24));
+    }
+
+    /// The highlight band moves one column per tick and leaves the letters
+    /// in place.
+    #[test]
+    fn banner_shimmer_sweeps_across() {
+        let theme = Theme::dark();
+        let accented = |frame| {
+            banner_line(BANNER[1], frame, &theme)
+                .spans
+                .iter()
+                .position(|s| s.style.fg == Some(theme.accent))
+        };
+        assert_eq!(accented(0), Some(0));
+        assert_eq!(accented(10), Some(7));
+        // Past the right edge: a pause with nothing highlighted.
+        assert_eq!(accented((BANNER_WIDTH + SHIMMER_WIDTH) as u64), None);
+        let text: String = banner_line(BANNER[1], 10, &theme).spans.iter().map(|s| s.content.as_ref()).collect();
+        assert_eq!(text, BANNER[1]);
+        assert!(BANNER.iter().all(|row| row.len() == BANNER_WIDTH));
+    }
+
+    /// Creating a room with no relay reachable asks once; the second Enter
+    /// goes through. Joining isn't held up.
+    #[test]
+    fn create_without_relay_warns_once() {
+        let mut state = WelcomeState::new();
+        state.name = "Alice".into();
+        state.relay = RelayCheck::Unreachable;
+        handle_welcome_key(&mut state, key(KeyCode::Enter));
+        assert!(state.error.as_deref().is_some_and(|e| e.contains("Enter again")));
+        handle_welcome_key(&mut state, key(KeyCode::Enter));
+        assert!(state.error.is_none());
+
+        let mut state = WelcomeState::new();
+        state.name = "Alice".into();
+        state.relay = RelayCheck::Reachable { relay: "relay".into(), after: Duration::ZERO };
+        handle_welcome_key(&mut state, key(KeyCode::Enter));
+        assert!(state.error.is_none());
+    }
+
+    // ── Paste tests ──────────────────────────────────────────────────────
+
+    /// Only the first line of a paste lands in the name field.
+    #[test]
+    fn paste_into_name_takes_first_line() {
+        let mut state = WelcomeState::new();
+        state.paste("  Alice \nsecond line");
+        assert_eq!(state.name, "Alice");
+        assert_eq!(state.name_cursor, 5);
+        assert_eq!(state.mode, RoomMode::Create);
+    }
+
+    /// A ticket wrapped across lines is reassembled, switches the form to
+    /// Join, and validates.
+    #[test]
+    fn paste_ticket_switches_to_join_and_validates() {
+        let ticket = <ChatTicket as Ticket>::serialize(&ChatTicket::new_random());
+        let (a, b) = ticket.split_at(20);
+        let mut state = WelcomeState::new();
+        state.field = WelcomeField::Mode;
+        state.paste(&format!("{a}\r\n  {b}\n"));
+        assert_eq!(state.mode, RoomMode::Join);
+        assert_eq!(state.field, WelcomeField::Ticket);
+        assert_eq!(state.ticket, ticket);
+        assert_eq!(state.ticket_cursor, ticket.len());
+        assert_eq!(state.ticket_valid(), Some(true));
+    }
+
+    #[test]
+    fn ticket_validity() {
+        let mut state = WelcomeState::new();
+        assert_eq!(state.ticket_valid(), None);
+        state.ticket = "chatnotreally".into();
+        assert_eq!(state.ticket_valid(), Some(false));
+    }
+
+    /// A decoding ticket shows a checkmark and the paste hint.
+    #[test]
+    fn snapshot_welcome_valid_ticket() {
+        let mut state = WelcomeState::new();
+        state.name = "Bob".into();
+        state.field = WelcomeField::Mode;
+        // A fixed topic keeps the rendered ticket stable across runs.
+        let ticket = ChatTicket::new(iroh_gossip::proto::TopicId::from_bytes([7; 32]));
+        state.paste(&<ChatTicket as Ticket>::serialize(&ticket));
+        insta::assert_snapshot!(render(&state, 60, 18));
+    }
+
+    fn fixed_ticket() -> String {
+        let ticket = ChatTicket::new(iroh_gossip::proto::TopicId::from_bytes([7; 32]));
+        <ChatTicket as Ticket>::serialize(&ticket)
+    }
+
+    fn render_ticket(screen: &TicketScreen, width: u16, height: u16) -> ratatui::backend::TestBackend {
+        let mut terminal = ratatui::Terminal::new(ratatui::backend::TestBackend::new(width, height)).unwrap();
+        terminal.draw(|f| ui_ticket(f, screen, &Theme::dark())).unwrap();
+        terminal.backend().clone()
+    }
+
+    #[test]
+    fn qr_rows_are_rectangular() {
+        let rows = qr_rows(&fixed_ticket()).unwrap();
+        let width = rows[0].chars().count();
+        assert!(rows.len() > 10);
+        assert!(rows.iter().all(|r| r.chars().count() == width));
+    }
+
+    /// With room to spare, the ticket screen shows the QR code above the ticket.
+    #[test]
+    fn snapshot_ticket_screen() {
+        let screen = TicketScreen::new(&fixed_ticket());
+        insta::assert_snapshot!(render_ticket(&screen, 80, 45));
+    }
+
+    /// On a small terminal the QR code gives way to a hint; the ticket stays.
+    #[test]
+    fn snapshot_ticket_screen_small() {
+        let screen = TicketScreen::new(&fixed_ticket());
+        insta::assert_snapshot!(render_ticket(&screen, 60, 16));
+    }
+
+    #[test]
+    fn join_screen_tracks_each_probe() {
+        let (a, b) = (EndpointId::from_bytes(&[1u8; 32]).unwrap(), EndpointId::from_bytes(&[3u8; 32]).unwrap());
+        let mut screen = JoinScreen::new(vec![(a, "Alice".into()), (b, "Bob".into())]);
+        screen.update(a, Probe::Failed(bootstrap::Failure::PeerOffline));
+        assert!(!screen.all_failed() && !screen.reached());
+        screen.update(b, Probe::Failed(bootstrap::Failure::RelayTimeout));
+        assert!(screen.all_failed());
+        screen.restart();
+        assert!(!screen.all_failed());
+        screen.update(b, Probe::Reached);
+        assert!(screen.reached());
+    }
+
+    /// Once every probe has failed, each row gives its reason.
+    #[test]
+    fn snapshot_join_screen_failures() {
+        let (a, b) = (EndpointId::from_bytes(&[1u8; 32]).unwrap(), EndpointId::from_bytes(&[3u8; 32]).unwrap());
+        let mut screen = JoinScreen::new(vec![(a, "Alice".into()), (b, "peer".into())]);
+        screen.update(a, Probe::Failed(bootstrap::Failure::PeerOffline));
+        screen.update(b, Probe::Failed(bootstrap::Failure::RelayTimeout));
+        let mut terminal = ratatui::Terminal::new(ratatui::backend::TestBackend::new(64, 12)).unwrap();
+        terminal.draw(|f| ui_join(f, &screen, &Theme::dark())).unwrap();
+        insta::assert_snapshot!(terminal.backend());
+    }
+
+    #[test]
+    fn snapshot_recover_screen() {
+        let sender_id = EndpointId::from_bytes(&[1u8; 32]).unwrap();
+        let saved = |byte, state| crate::session::SavedTransfer {
+            sender_nickname: "Bob".into(),
+            sender_id,
+            filename: "notes.txt".into(),
+            size: 10,
+            hash: iroh_blobs::Hash::from_bytes([byte; 32]),
+            key: None,
+            offer_id: [byte; 16],
+            state,
+        };
+        let session = Session {
+            ticket: "chatabc".into(),
+            nickname: "Alice".into(),
+            room_name: Some("standup".into()),
+            transfers: vec![
+                saved(1, crate::session::SavedState::Pending),
+                saved(2, crate::session::SavedState::Downloading { bytes_received: 4 }),
+            ],
+        };
+        let mut terminal = ratatui::Terminal::new(ratatui::backend::TestBackend::new(60, 10)).unwrap();
+        terminal.draw(|f| ui_recover(f, &session, &Theme::dark())).unwrap();
+        insta::assert_snapshot!(terminal.backend());
+    }
+
+    fn state_with_contacts() -> WelcomeState {
+        let mut contacts = Contacts::default();
+        let room = iroh_gossip::proto::TopicId::from_bytes([1; 32]);
+        contacts.saw(EndpointId::from_bytes(&[1u8; 32]).unwrap(), "Bob", &room, 200);
+        contacts.saw(EndpointId::from_bytes(&[3u8; 32]).unwrap(), "Carol", &room, 100);
+        contacts.verify("Bob");
+        WelcomeState::with_contacts(&contacts)
+    }
+
+    #[test]
+    fn dm_mode_only_with_contacts() {
+        let mut state = WelcomeState::new();
+        state.field = WelcomeField::Mode;
+        handle_welcome_key(&mut state, key(KeyCode::Left));
+        assert_eq!(state.mode, RoomMode::Join);
+
+        let mut state = state_with_contacts();
+        state.field = WelcomeField::Mode;
+        handle_welcome_key(&mut state, key(KeyCode::Left));
+        assert_eq!(state.mode, RoomMode::Dm);
+        state.next_field();
+        assert_eq!(state.field, WelcomeField::Contact);
+        handle_welcome_key(&mut state, key(KeyCode::Right));
+        assert_eq!(state.contacts[state.contact].0, "Carol");
+        handle_welcome_key(&mut state, key(KeyCode::Right));
+        assert_eq!(state.contacts[state.contact].0, "Bob");
+    }
+
+    #[test]
+    fn snapshot_welcome_dm() {
+        let mut state = state_with_contacts();
+        state.name = "Alice".into();
+        state.name_cursor = 5;
+        state.mode = RoomMode::Dm;
+        state.field = WelcomeField::Contact;
+        insta::assert_snapshot!(render(&state, 60, 16));
+    }
+
+    fn state_with_rooms() -> WelcomeState {
+        let entry = |ts: u64, nickname: &str| crate::net::HistoryEntry {
+            message_id: [ts as u8; 16],
+            timestamp_ms: ts,
+            kind: crate::net::HistoryEntryKind::Chat {
+                nickname: nickname.into(),
+                text: "hi".into(),
+            },
+        };
+        let mut rooms = Rooms::default();
+        let topic = |b: u8| iroh_gossip::proto::TopicId::from_bytes([b; 32]);
+        rooms.record(&topic(1), "older", &[entry(1_000, "Dave")], "Alice", 0);
+        rooms.record(&topic(3), "newer", &[entry(5_000, "Bob"), entry(6_000, "Carol")], "Alice", 5_500);
+        let mut state = WelcomeState::new();
+        state.add_rooms(&rooms, 6_000 + 3_600_000);
+        state
+    }
+
+    #[test]
+    fn rejoin_mode_picks_archived_ticket() {
+        let mut state = state_with_rooms();
+        state.field = WelcomeField::Mode;
+        handle_welcome_key(&mut state, key(KeyCode::Left));
+        assert_eq!(state.mode, RoomMode::Rejoin);
+        state.next_field();
+        assert_eq!(state.field, WelcomeField::Room);
+        assert_eq!(state.rooms[state.room].2, "newer");
+        handle_welcome_key(&mut state, key(KeyCode::Right));
+        assert_eq!(state.rooms[state.room].2, "older");
+    }
+
+    #[test]
+    fn snapshot_welcome_rejoin() {
+        let mut state = state_with_rooms();
+        state.name = "Alice".into();
+        state.name_cursor = 5;
+        state.mode = RoomMode::Rejoin;
+        state.field = WelcomeField::Room;
+        insta::assert_snapshot!(render(&state, 60, 16));
+    }
 }
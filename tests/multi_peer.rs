@@ -0,0 +1,920 @@
+//! In-process multi-peer integration tests.
+//!
+//! Each test spins up two or three real `Node`s (QUIC endpoint + gossip +
+//! blobs) inside one process and joins them to a single room through a
+//! serialized ticket. Relays are disabled and addresses are shared through an
+//! in-memory lookup, so the tests run offline over loopback.
+
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use iroh::{Endpoint, EndpointId, RelayMode, address_lookup::memory::MemoryLookup};
+use iroh_gossip::api::{Event, GossipReceiver};
+use iroh_tickets::Ticket;
+use n0_future::StreamExt;
+use piper_chat::blobstore::Invalid;
+use piper_chat::net::{self, ChatTicket, Message, MessageSender};
+use piper_chat::node::{self, Node, OfferOptions, Shared};
+use piper_chat::transfer::{self, FileOffer, Strategy, TransferEvent};
+use tempfile::TempDir;
+
+/// How long any single wait may take before the test fails.
+const TIMEOUT: Duration = Duration::from_secs(15);
+
+// ── Harness ──────────────────────────────────────────────────────────────────
+
+/// A set of in-process peers sharing one address book.
+struct Harness {
+    lookup: MemoryLookup,
+    dir: TempDir,
+}
+
+/// One peer in the harness, joined to the room.
+struct Peer {
+    node: Node,
+    sender: MessageSender,
+    receiver: GossipReceiver,
+}
+
+impl Harness {
+    fn new() -> Result<Self> {
+        Ok(Self {
+            lookup: MemoryLookup::new(),
+            dir: tempfile::tempdir()?,
+        })
+    }
+
+    /// Spawn a node bound to loopback and publish its address to the others.
+    async fn spawn_node(&self) -> Result<Node> {
+        let builder = Endpoint::empty_builder(RelayMode::Disabled)
+            .address_lookup(self.lookup.clone())
+            .bind_addr("127.0.0.1:0")?;
+        let node = Node::spawn(builder, self.dir.path()).await?;
+        self.lookup.add_endpoint_info(node.endpoint.addr());
+        Ok(node)
+    }
+
+    /// Spawn a node and join it to the room described by `ticket`.
+    ///
+    /// The ticket goes through its base32 string form, exactly as a user
+    /// would paste it.
+    async fn join(&self, ticket: &str) -> Result<Peer> {
+        let ticket = <ChatTicket as Ticket>::deserialize(ticket)?;
+        let node = self.spawn_node().await?;
+        let (sender, receiver) = node.join(&ticket).await?;
+        Ok(Peer {
+            node,
+            sender,
+            receiver,
+        })
+    }
+}
+
+impl Peer {
+    fn id(&self) -> EndpointId {
+        self.node.endpoint.id()
+    }
+
+    /// The ticket string others use to join through this peer.
+    fn ticket(&self, room: &ChatTicket) -> String {
+        <ChatTicket as Ticket>::serialize(&self.node.ticket_for(room))
+    }
+
+    /// Wait for the next gossip event matching `f`, skipping others.
+    async fn wait_for<T>(&mut self, mut f: impl FnMut(&Event) -> Option<T>) -> Result<T> {
+        tokio::time::timeout(TIMEOUT, async {
+            loop {
+                let event = self
+                    .receiver
+                    .try_next()
+                    .await?
+                    .context("gossip stream ended")?;
+                if let Some(out) = f(&event) {
+                    return Ok(out);
+                }
+            }
+        })
+        .await
+        .context("timed out waiting for gossip event")?
+    }
+
+    /// Wait until `id` is a direct gossip neighbor.
+    async fn wait_neighbor_up(&mut self, id: EndpointId) -> Result<()> {
+        self.wait_for(|e| matches!(e, Event::NeighborUp(n) if *n == id).then_some(()))
+            .await
+    }
+
+    /// Wait for the next chat-protocol message that decodes successfully.
+    async fn wait_message(&mut self) -> Result<(EndpointId, Message)> {
+        self.wait_for(|e| match e {
+            Event::Received(msg) => net::proto::decode(&msg.content).ok(),
+            _ => None,
+        })
+        .await
+    }
+}
+
+/// Create a room with one peer and join a second peer through its ticket.
+async fn two_peers(h: &Harness) -> Result<(ChatTicket, Peer, Peer)> {
+    let room = ChatTicket::new_random();
+    let node = h.spawn_node().await?;
+    let (sender, receiver) = node.join(&room).await?;
+    let mut alice = Peer {
+        node,
+        sender,
+        receiver,
+    };
+    let mut bob = h.join(&alice.ticket(&room)).await?;
+    alice.wait_neighbor_up(bob.id()).await?;
+    bob.wait_neighbor_up(alice.id()).await?;
+    Ok((room, alice, bob))
+}
+
+// ── Tests ────────────────────────────────────────────────────────────────────
+
+#[tokio::test]
+async fn chat_message_is_delivered() -> Result<()> {
+    let h = Harness::new()?;
+    let (_room, alice, mut bob) = two_peers(&h).await?;
+
+    let message_id = net::new_message_id();
+    alice
+        .sender
+        .broadcast(&Message::Chat {
+            nickname: "Alice".into(),
+            text: "hello over gossip".into(),
+            message_id,
+            timestamp_ms: net::now_ms(),
+        })
+        .await?;
+
+    let (from, msg) = bob.wait_message().await?;
+    assert_eq!(from, alice.id());
+    assert!(matches!(
+        msg,
+        Message::Chat { text, message_id: id, .. } if text == "hello over gossip" && id == message_id
+    ));
+
+    alice.node.shutdown().await?;
+    bob.node.shutdown().await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn join_and_leave_events() -> Result<()> {
+    let h = Harness::new()?;
+    let (room, mut alice, mut bob) = two_peers(&h).await?;
+
+    // A third peer joins through Bob's ticket and announces itself.
+    let mut carol = h.join(&bob.ticket(&room)).await?;
+    bob.wait_neighbor_up(carol.id()).await?;
+    carol.wait_neighbor_up(bob.id()).await?;
+    carol
+        .sender
+        .broadcast(&Message::Join {
+            nickname: "Carol".into(),
+            endpoint_id: carol.id(),
+        })
+        .await?;
+    let carol_id = carol.id();
+    // The Join reaches Alice even if she isn't Carol's direct neighbor.
+    let (_, msg) = alice.wait_message().await?;
+    assert!(matches!(
+        msg,
+        Message::Join { nickname, endpoint_id } if nickname == "Carol" && endpoint_id == carol_id
+    ));
+
+    // When Carol leaves, Bob (her neighbor) sees `NeighborDown`.
+    carol.node.shutdown().await?;
+    bob.wait_for(|e| matches!(e, Event::NeighborDown(n) if *n == carol_id).then_some(()))
+        .await?;
+
+    alice.node.shutdown().await?;
+    bob.node.shutdown().await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn file_offer_download_roundtrip() -> Result<()> {
+    let h = Harness::new()?;
+    let (_room, alice, mut bob) = two_peers(&h).await?;
+
+    // Alice shares a file: import into her blob store + broadcast the offer.
+    let contents = b"the quick brown fox\n".repeat(1000);
+    let path = h.dir.path().join("fox.txt");
+    tokio::fs::write(&path, &contents).await?;
+    let Shared { hash, .. } = node::share_file(
+        &alice.node.blob_store,
+        &alice.sender,
+        "Alice",
+        alice.id(),
+        &path,
+        OfferOptions::default(),
+    )
+    .await?;
+
+    // Bob receives the offer and downloads it.
+    let offer = match bob.wait_message().await? {
+        (
+            _,
+            Message::FileOffer {
+                nickname,
+                endpoint_id,
+                filename,
+                size,
+                hash,
+                message_id,
+                ..
+            },
+        ) => FileOffer {
+            sender_nickname: nickname,
+            sender_id: endpoint_id,
+            filename,
+            size,
+            hash: iroh_blobs::Hash::from_bytes(hash),
+            key: None,
+            offer_id: message_id,
+        },
+        (_, _) => anyhow::bail!("expected a FileOffer"),
+    };
+    assert_eq!(offer.hash, hash);
+    assert_eq!(offer.size, contents.len() as u64);
+
+    let download_dir = h.dir.path().join("bob-downloads");
+    tokio::fs::create_dir_all(&download_dir).await?;
+    let (tx, mut rx) = tokio::sync::mpsc::channel(64);
+    node::spawn_download(&bob.node.blob_store, &bob.node.endpoint, offer, None, download_dir, tx);
+
+    let saved = tokio::time::timeout(TIMEOUT, async {
+        while let Some(event) = rx.recv().await {
+            match event {
+                TransferEvent::Complete { path, .. } => return Ok(path),
+                TransferEvent::Failed { error, .. } => anyhow::bail!("download failed: {error}"),
+                _ => {}
+            }
+        }
+        anyhow::bail!("download task ended without a result")
+    })
+    .await??;
+    assert_eq!(tokio::fs::read(&saved).await?, contents);
+
+    alice.node.shutdown().await?;
+    bob.node.shutdown().await?;
+    Ok(())
+}
+
+/// Only an offer's author can withdraw it: a third peer's `FileRetract`,
+/// or a `FileRevoke` claiming to be the author's, leaves it in place.
+#[tokio::test]
+async fn only_the_author_withdraws_an_offer() -> Result<()> {
+    let h = Harness::new()?;
+    let (room, alice, mut bob) = two_peers(&h).await?;
+    let mut carol = h.join(&bob.ticket(&room)).await?;
+    bob.wait_neighbor_up(carol.id()).await?;
+    carol.wait_neighbor_up(bob.id()).await?;
+
+    let hash = [5u8; 32];
+    let offer_id = [7u8; 16];
+    let mut transfers = transfer::TransferManager::new();
+    transfers.add_offer(FileOffer {
+        sender_nickname: "Alice".into(),
+        sender_id: alice.id(),
+        filename: "fox.txt".into(),
+        size: 20,
+        hash: iroh_blobs::Hash::from_bytes(hash),
+        key: None,
+        offer_id,
+    });
+
+    // Carol forges Alice's revoke (signed with her own key, so it never
+    // decodes), then sends an old-style retract naming only the hash.
+    let now = net::now_ms();
+    let revoke = |endpoint_id, message_id| Message::FileRevoke { hash, endpoint_id, message_id, timestamp_ms: now, at_ms: now, offer_id };
+    carol.sender.broadcast(&revoke(alice.id(), [1; 16])).await?;
+    carol
+        .sender
+        .broadcast(&Message::FileRetract { nickname: "Carol".into(), hash, message_id: [2; 16], timestamp_ms: now })
+        .await?;
+    loop {
+        let (from, msg) = bob.wait_message().await?;
+        assert_eq!(transfers.withdraw(from, &msg, now), None);
+        if matches!(msg, Message::FileRetract { .. }) {
+            break;
+        }
+    }
+    assert_eq!(transfers.entries.len(), 1);
+
+    // Alice's own revoke does remove it.
+    alice.sender.broadcast(&revoke(alice.id(), [3; 16])).await?;
+    let (from, msg) = bob.wait_message().await?;
+    assert_eq!(from, alice.id());
+    assert_eq!(transfers.withdraw(from, &msg, now), Some("fox.txt".to_string()));
+    assert!(transfers.entries.is_empty());
+
+    carol.node.shutdown().await?;
+    alice.node.shutdown().await?;
+    bob.node.shutdown().await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn sealed_file_is_ciphertext_until_opened() -> Result<()> {
+    let h = Harness::new()?;
+    let (room, alice, mut bob) = two_peers(&h).await?;
+
+    let contents = b"for the room only\n".repeat(5000);
+    let path = h.dir.path().join("private.txt");
+    tokio::fs::write(&path, &contents).await?;
+    let options = OfferOptions { target: None, seal: Some(node::Sealing::new(room.topic_id)), reference_min: None };
+    let Shared { hash, .. } = node::share_file(&alice.node.blob_store, &alice.sender, "Alice", alice.id(), &path, options).await?;
+    // What the store holds, and serves, isn't the file.
+    let stored = alice.node.blob_store.blobs().get_bytes(hash).await?;
+    assert_eq!(stored.len() as u64, piper_chat::seal::sealed_size(contents.len() as u64));
+    assert!(!stored.windows(17).any(|w| w == b"for the room only"));
+
+    let offer = match bob.wait_message().await? {
+        (_, Message::SealedFileOffer { endpoint_id, filename, size, hash, message_id, wrapped_key, .. }) => FileOffer {
+            sender_nickname: "Alice".into(),
+            sender_id: endpoint_id,
+            filename,
+            size,
+            hash: iroh_blobs::Hash::from_bytes(hash),
+            key: Some(piper_chat::seal::unwrap(&room.topic_id, &wrapped_key).context("key for another room")?),
+            offer_id: message_id,
+        },
+        (_, _) => anyhow::bail!("expected a SealedFileOffer"),
+    };
+    let download_dir = h.dir.path().join("bob-downloads");
+    tokio::fs::create_dir_all(&download_dir).await?;
+    let (tx, mut rx) = tokio::sync::mpsc::channel(64);
+    let mut transfers = transfer::TransferManager::new();
+    let id = offer.entry_id();
+    transfers.add_offer(offer.clone());
+    node::spawn_download(&bob.node.blob_store, &bob.node.endpoint, offer, None, download_dir, tx);
+    let (saved, content) = tokio::time::timeout(TIMEOUT, async {
+        while let Some(event) = rx.recv().await {
+            match event {
+                TransferEvent::Complete { path, content, .. } => return Ok((path, content)),
+                TransferEvent::Failed { error, .. } => anyhow::bail!("download failed: {error}"),
+                _ => {}
+            }
+        }
+        anyhow::bail!("download task ended without a result")
+    })
+    .await??;
+    assert_eq!(tokio::fs::read(&saved).await?, contents);
+
+    // `/verifyfile` checks the opened file, not the sealed blob it came from.
+    transfers.complete_download(&id, saved.clone());
+    transfers.opened(&id, content.context("no content hash for a sealed download")?);
+    let on_disk = piper_chat::checksum::blake3_file(&saved)?;
+    assert_ne!(on_disk, hash);
+    assert_eq!(transfers.entry(&id).map(|e| e.content_hash()), Some(on_disk));
+
+    alice.node.shutdown().await?;
+    bob.node.shutdown().await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn share_bytes_offers_in_memory_text() -> Result<()> {
+    let h = Harness::new()?;
+    let (_room, alice, mut bob) = two_peers(&h).await?;
+
+    // A large paste goes out as a text attachment without touching disk.
+    let text = "line\n".repeat(50);
+    let Shared { hash, filename, size, .. } = node::share_bytes(
+        &alice.node.blob_store,
+        &alice.sender,
+        "Alice",
+        alice.id(),
+        "paste.txt",
+        text.clone().into_bytes(),
+        OfferOptions::default(),
+    )
+    .await?;
+    assert_eq!(filename, "paste.txt");
+    assert_eq!(size, text.len() as u64);
+
+    match bob.wait_message().await? {
+        (_, Message::FileOffer { filename, hash: offered, .. }) => {
+            assert_eq!(filename, "paste.txt");
+            assert_eq!(offered, *hash.as_bytes());
+        }
+        (_, _) => anyhow::bail!("expected a FileOffer"),
+    }
+
+    alice.node.shutdown().await?;
+    bob.node.shutdown().await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn preview_fetches_into_memory_only() -> Result<()> {
+    let h = Harness::new()?;
+    let (_room, alice, mut bob) = two_peers(&h).await?;
+
+    let text = b"a quick look\n".repeat(20);
+    node::share_bytes(&alice.node.blob_store, &alice.sender, "Alice", alice.id(), "notes.txt", text.clone(), OfferOptions::default())
+        .await?;
+    let offer = match bob.wait_message().await? {
+        (_, Message::FileOffer { endpoint_id, filename, size, hash, message_id, .. }) => FileOffer {
+            sender_nickname: "Alice".into(),
+            sender_id: endpoint_id,
+            filename,
+            size,
+            hash: iroh_blobs::Hash::from_bytes(hash),
+            key: None,
+            offer_id: message_id,
+        },
+        (_, _) => anyhow::bail!("expected a FileOffer"),
+    };
+    let hash = offer.hash;
+    let (tx, mut rx) = tokio::sync::mpsc::channel(64);
+    node::spawn_preview(&bob.node.endpoint, offer, None, tx);
+    let bytes = match tokio::time::timeout(TIMEOUT, rx.recv()).await?.context("preview task ended")? {
+        TransferEvent::Previewed { result, .. } => result.map_err(anyhow::Error::msg)?,
+        other => anyhow::bail!("expected Previewed, got {other:?}"),
+    };
+    assert_eq!(bytes, text);
+    // The content never reached Bob's own blob store.
+    assert!(!bob.node.blob_store.blobs().has(hash).await?);
+
+    alice.node.shutdown().await?;
+    bob.node.shutdown().await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn bootstrap_probes_say_who_is_unreachable() -> Result<()> {
+    use piper_chat::net::bootstrap::{self, Failure};
+    let h = Harness::new()?;
+    let (alice, bob) = (h.spawn_node().await?, h.spawn_node().await?);
+
+    bootstrap::probe(&alice.endpoint, bob.endpoint.id(), TIMEOUT).await.map_err(|f| anyhow::anyhow!(f.label()))?;
+    // Nobody has ever published an address for a fresh key.
+    let stranger = iroh::SecretKey::from_bytes(&rand::random()).public();
+    assert_eq!(bootstrap::probe(&alice.endpoint, stranger, TIMEOUT).await, Err(Failure::Offline));
+
+    alice.shutdown().await?;
+    bob.shutdown().await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn direct_stream_delivers_signed_message() -> Result<()> {
+    let h = Harness::new()?;
+    let (_room, alice, mut bob) = two_peers(&h).await?;
+
+    // The fallback path: Alice re-sends a chat message straight to Bob.
+    let msg = Message::Chat {
+        nickname: "Alice".into(),
+        text: "did you get this?".into(),
+        message_id: net::new_message_id(),
+        timestamp_ms: net::now_ms(),
+    };
+    let bytes = alice.sender.encode(&msg)?;
+    net::direct::send(&alice.node.endpoint, bob.id(), &bytes).await?;
+
+    let direct_rx = bob.node.direct_rx.as_mut().context("direct receiver")?;
+    let (from, received) = tokio::time::timeout(TIMEOUT, direct_rx.recv())
+        .await?
+        .context("direct channel closed")?;
+    assert_eq!(from, alice.id());
+    match net::proto::decode(&received)? {
+        (author, Message::Chat { text, .. }) => {
+            assert_eq!(author, alice.id());
+            assert_eq!(text, "did you get this?");
+        }
+        (_, _) => anyhow::bail!("expected a Chat"),
+    }
+
+    alice.node.shutdown().await?;
+    bob.node.shutdown().await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn send_posts_and_waits_for_ack() -> Result<()> {
+    let h = Harness::new()?;
+    let room = ChatTicket::new_random();
+    let node = h.spawn_node().await?;
+    let (sender, receiver) = node.join(&room).await?;
+    let mut alice = Peer {
+        node,
+        sender,
+        receiver,
+    };
+
+    // The one-shot sender joins through Alice and posts as "bot".
+    let ticket = <ChatTicket as Ticket>::deserialize(&alice.ticket(&room))?;
+    let bot = h.spawn_node().await?;
+    let task = tokio::spawn(async move {
+        let messages = ["build finished".to_string()];
+        let acked = piper_chat::send::run(&bot, &ticket, "bot", &messages, TIMEOUT).await;
+        bot.shutdown().await?;
+        acked
+    });
+
+    // Alice sees the announcement, then the message, and acks it.
+    let (_, msg) = alice.wait_message().await?;
+    assert!(matches!(msg, Message::Join { nickname, .. } if nickname == "bot"));
+    let (_, msg) = alice.wait_message().await?;
+    let Message::Chat { text, message_id, .. } = msg else {
+        panic!("expected a chat message");
+    };
+    assert_eq!(text, "build finished");
+    alice
+        .sender
+        .broadcast(&Message::Ack {
+            message_id,
+            endpoint_id: alice.id(),
+        })
+        .await?;
+
+    assert_eq!(task.await??, 1);
+    alice.node.shutdown().await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn share_serves_until_downloaded() -> Result<()> {
+    let h = Harness::new()?;
+    let room = ChatTicket::new_random();
+    let node = h.spawn_node().await?;
+    let (sender, receiver) = node.join(&room).await?;
+    let mut bob = Peer {
+        node,
+        sender,
+        receiver,
+    };
+
+    // The file-drop sharer joins through Bob with provider events enabled.
+    let file = h.dir.path().join("artifact.txt");
+    std::fs::write(&file, b"build output")?;
+    let ticket = <ChatTicket as Ticket>::deserialize(&bob.ticket(&room))?;
+    let builder = Endpoint::empty_builder(RelayMode::Disabled)
+        .address_lookup(h.lookup.clone())
+        .bind_addr("127.0.0.1:0")?;
+    let (ci, events) = Node::spawn_with_events(builder, h.dir.path()).await?;
+    h.lookup.add_endpoint_info(ci.endpoint.addr());
+    let task = tokio::spawn(async move {
+        let served = piper_chat::share::run(&ci, events, &ticket, "ci", &file, 1, TIMEOUT).await;
+        ci.shutdown().await?;
+        served
+    });
+
+    // Bob gets the offer and downloads it; the sharer then exits.
+    let offer = bob
+        .wait_for(|e| match e {
+            Event::Received(m) => match net::proto::decode(&m.content).ok()? {
+                (from, Message::FileOffer { filename, size, hash, message_id, .. }) => Some(FileOffer {
+                    sender_nickname: "ci".into(),
+                    sender_id: from,
+                    filename,
+                    size,
+                    hash: iroh_blobs::Hash::from_bytes(hash),
+                    key: None,
+                    offer_id: message_id,
+                }),
+                _ => None,
+            },
+            _ => None,
+        })
+        .await?;
+    let downloads = h.dir.path().join("downloads");
+    std::fs::create_dir_all(&downloads)?;
+    let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+    node::spawn_download(&bob.node.blob_store, &bob.node.endpoint, offer, None, downloads.clone(), tx);
+    loop {
+        match tokio::time::timeout(TIMEOUT, rx.recv()).await?.context("download channel closed")? {
+            TransferEvent::Complete { .. } => break,
+            TransferEvent::Failed { error, .. } => anyhow::bail!("download failed: {error}"),
+            _ => {}
+        }
+    }
+    assert_eq!(std::fs::read(downloads.join("artifact.txt"))?, b"build output");
+    assert_eq!(tokio::time::timeout(TIMEOUT, task).await???, 1);
+
+    bob.node.shutdown().await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn fetch_blob_without_a_room() -> Result<()> {
+    let h = Harness::new()?;
+    let alice = h.spawn_node().await?;
+    let bob = h.spawn_node().await?;
+    let tag = alice.blob_store.blobs().add_bytes(b"by hash alone".to_vec()).await?;
+
+    let out = h.dir.path().join("fetched.txt");
+    let mut reports = 0;
+    node::fetch_blob(&bob.blob_store, &bob.endpoint, alice.endpoint.id(), tag.hash, &out, |_| {
+        reports += 1;
+        std::future::ready(())
+    })
+    .await?;
+    assert_eq!(std::fs::read(&out)?, b"by hash alone");
+    assert!(reports > 0);
+    // The provider's gate credits the finished fetch to bob, a moment later.
+    let fetched = tokio::time::timeout(TIMEOUT, async {
+        loop {
+            let fetched = alice.fetched.take();
+            if !fetched.is_empty() {
+                return fetched;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await?;
+    assert_eq!(fetched, [(tag.hash, bob.endpoint.id())]);
+
+    // A hash the provider doesn't have fails instead of writing anything.
+    let missing = iroh_blobs::Hash::new(b"nobody has this");
+    let out = h.dir.path().join("missing.txt");
+    let result = tokio::time::timeout(
+        TIMEOUT,
+        node::fetch_blob(&bob.blob_store, &bob.endpoint, alice.endpoint.id(), missing, &out, |_| {
+            std::future::ready(())
+        }),
+    )
+    .await?;
+    assert!(result.is_err());
+    assert!(!out.exists());
+
+    alice.shutdown().await?;
+    bob.shutdown().await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn sharing_stored_bytes_again_reuses_the_blob() -> Result<()> {
+    let h = Harness::new()?;
+    let (_room, alice, bob) = two_peers(&h).await?;
+
+    let path = h.dir.path().join("build.zip");
+    tokio::fs::write(&path, b"release artifact\n".repeat(1000)).await?;
+    let share = |peer: &Peer, path: std::path::PathBuf| {
+        let (store, sender, id) = (peer.node.blob_store.clone(), peer.sender.clone(), peer.id());
+        async move { node::share_file(&store, &sender, "Someone", id, &path, OfferOptions::default()).await }
+    };
+    let first = share(&alice, path.clone()).await?;
+    assert!(!first.reused);
+    let again = share(&alice, path.clone()).await?;
+    assert!(again.reused);
+    assert_eq!(again.hash, first.hash);
+    assert_ne!(again.message_id, first.message_id, "still a new offer");
+
+    // Downloaded bytes count too, whatever the file is called now.
+    let out = h.dir.path().join("renamed.zip");
+    node::fetch_blob(&bob.node.blob_store, &bob.node.endpoint, alice.id(), first.hash, &out, |_| std::future::ready(())).await?;
+    let reshared = share(&bob, out).await?;
+    assert!(reshared.reused);
+    assert_eq!(reshared.hash, first.hash);
+
+    // Different bytes are imported as usual.
+    tokio::fs::write(&path, b"next release\n".repeat(1000)).await?;
+    assert!(!share(&alice, path).await?.reused);
+
+    alice.node.shutdown().await?;
+    bob.node.shutdown().await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn referenced_file_is_refused_once_changed() -> Result<()> {
+    let h = Harness::new()?;
+    let (_room, alice, bob) = two_peers(&h).await?;
+    let carol = h.spawn_node().await?;
+
+    let path = h.dir.path().join("big.bin");
+    tokio::fs::write(&path, b"large enough to reference\n".repeat(1000)).await?;
+    let options = OfferOptions { reference_min: Some(0), ..OfferOptions::default() };
+    let Shared { hash, .. } = node::share_file(&alice.node.blob_store, &alice.sender, "Alice", alice.id(), &path, options).await?;
+
+    // Untouched, it's served straight from the file.
+    let out = h.dir.path().join("bob.bin");
+    node::fetch_blob(&bob.node.blob_store, &bob.node.endpoint, alice.id(), hash, &out, |_| std::future::ready(())).await?;
+    assert_eq!(std::fs::read(&out)?, std::fs::read(&path)?);
+
+    // Rewritten, it no longer matches its hash: refused, and reported.
+    tokio::fs::write(&path, b"something else entirely\n".repeat(1000)).await?;
+    let out = h.dir.path().join("carol.bin");
+    let result = tokio::time::timeout(
+        TIMEOUT,
+        node::fetch_blob(&carol.blob_store, &carol.endpoint, alice.id(), hash, &out, |_| std::future::ready(())),
+    )
+    .await?;
+    assert!(result.is_err());
+    assert_eq!(alice.node.blob_store.sources().recheck(), [(hash, Invalid::Resized)]);
+
+    alice.node.shutdown().await?;
+    bob.node.shutdown().await?;
+    carol.shutdown().await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn retry_falls_back_to_another_peer() -> Result<()> {
+    let h = Harness::new()?;
+    let bob = h.spawn_node().await?;
+    let carol = h.spawn_node().await?;
+    let tag = bob.blob_store.blobs().add_bytes(b"a copy of a copy".to_vec()).await?;
+
+    // The original sender is unreachable (it never existed, so there's no
+    // relay to fall back to either), but Bob has the same bytes.
+    let gone = iroh::SecretKey::from_bytes(&[9; 32]).public();
+    let offer = FileOffer {
+        sender_nickname: "Alice".into(),
+        sender_id: gone,
+        filename: "copy.txt".into(),
+        size: 16,
+        hash: tag.hash,
+        key: None,
+        offer_id: [1; 16],
+    };
+    let plan = transfer::retry_plan("connect: timed out", &[bob.endpoint.id()]);
+    let downloads = h.dir.path().join("carol-downloads");
+    tokio::fs::create_dir_all(&downloads).await?;
+    let (tx, mut rx) = tokio::sync::mpsc::channel(64);
+    node::spawn_retry(&carol.blob_store, &carol.endpoint, offer, None, downloads, plan, tx);
+
+    let mut attempts = Vec::new();
+    let saved = tokio::time::timeout(TIMEOUT, async {
+        while let Some(event) = rx.recv().await {
+            match event {
+                TransferEvent::Attempt { strategy, .. } => attempts.push(strategy),
+                TransferEvent::Complete { path, .. } => return Ok(path),
+                TransferEvent::Failed { error, .. } => anyhow::bail!("retry failed: {error}"),
+                _ => {}
+            }
+        }
+        anyhow::bail!("retry task ended without a result")
+    })
+    .await??;
+    assert_eq!(attempts, [Strategy::RelayOnly, Strategy::Peer(bob.endpoint.id())]);
+    assert_eq!(tokio::fs::read(&saved).await?, b"a copy of a copy");
+
+    bob.shutdown().await?;
+    carol.shutdown().await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn members_only_provider_refuses_strangers() -> Result<()> {
+    let h = Harness::new()?;
+    let members = piper_chat::access::Members::default();
+    let builder = Endpoint::empty_builder(RelayMode::Disabled)
+        .address_lookup(h.lookup.clone())
+        .bind_addr("127.0.0.1:0")?;
+    let alice = Node::spawn_members_only(builder, h.dir.path(), members.clone()).await?;
+    h.lookup.add_endpoint_info(alice.endpoint.addr());
+    let bob = h.spawn_node().await?;
+    let tag = alice.blob_store.blobs().add_bytes(b"members only".to_vec()).await?;
+
+    // Bob isn't in the room yet: the connection is refused.
+    let out = h.dir.path().join("refused.txt");
+    let result = tokio::time::timeout(
+        TIMEOUT,
+        node::fetch_blob(&bob.blob_store, &bob.endpoint, alice.endpoint.id(), tag.hash, &out, |_| {
+            std::future::ready(())
+        }),
+    )
+    .await?;
+    assert!(result.is_err());
+    assert!(!out.exists());
+
+    // Once he's a member the same fetch goes through.
+    members.insert(bob.endpoint.id());
+    let out = h.dir.path().join("allowed.txt");
+    tokio::time::timeout(
+        TIMEOUT,
+        node::fetch_blob(&bob.blob_store, &bob.endpoint, alice.endpoint.id(), tag.hash, &out, |_| {
+            std::future::ready(())
+        }),
+    )
+    .await??;
+    assert_eq!(std::fs::read(&out)?, b"members only");
+
+    alice.shutdown().await?;
+    bob.shutdown().await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn room_token_lets_a_downloader_in() -> Result<()> {
+    let h = Harness::new()?;
+    let room = iroh_gossip::proto::TopicId::from_bytes([7; 32]);
+    let members = piper_chat::access::Members::default();
+    members.set_room(room);
+    let builder = Endpoint::empty_builder(RelayMode::Disabled)
+        .address_lookup(h.lookup.clone())
+        .bind_addr("127.0.0.1:0")?;
+    let alice = Node::spawn_members_only(builder, h.dir.path(), members.clone()).await?;
+    h.lookup.add_endpoint_info(alice.endpoint.addr());
+    let bob = h.spawn_node().await?;
+    let tag = alice.blob_store.blobs().add_bytes(b"for the room".to_vec()).await?;
+
+    // A token for another room is refused, and doesn't open the door.
+    let elsewhere = iroh_gossip::proto::TopicId::from_bytes([8; 32]);
+    assert!(!piper_chat::access::present(&bob.endpoint, alice.endpoint.id(), &elsewhere).await?);
+    assert!(!members.contains(&bob.endpoint.id()));
+
+    // The right one is accepted, and the fetch goes through without Bob
+    // ever being in Alice's peers.
+    assert!(piper_chat::access::present(&bob.endpoint, alice.endpoint.id(), &room).await?);
+    let out = h.dir.path().join("vouched.txt");
+    tokio::time::timeout(
+        TIMEOUT,
+        node::fetch_blob(&bob.blob_store, &bob.endpoint, alice.endpoint.id(), tag.hash, &out, |_| {
+            std::future::ready(())
+        }),
+    )
+    .await??;
+    assert_eq!(std::fs::read(&out)?, b"for the room");
+
+    alice.shutdown().await?;
+    bob.shutdown().await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn folder_is_shared_as_a_tar_archive() -> Result<()> {
+    let h = Harness::new()?;
+    let (_room, alice, mut bob) = two_peers(&h).await?;
+
+    let folder = h.dir.path().join("album");
+    std::fs::create_dir_all(folder.join("raw"))?;
+    std::fs::write(folder.join("cover.txt"), b"front")?;
+    std::fs::write(folder.join("raw").join("track.bin"), vec![1u8; 70_000])?;
+    let mut reports = Vec::new();
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    let Shared { hash, filename, size, .. } = node::share_folder(
+        &alice.node.blob_store,
+        &alice.sender,
+        "Alice",
+        alice.id(),
+        &folder,
+        OfferOptions::default(),
+        move |done, total| {
+            let _ = tx.send((done, total));
+        },
+    )
+    .await?;
+    while let Some(report) = rx.recv().await {
+        reports.push(report);
+    }
+    assert_eq!(filename, "album.tar");
+    assert_eq!(size, piper_chat::pack::archive_size(&piper_chat::pack::scan(&folder)?));
+    // Progress ends at the full size.
+    assert_eq!(reports.last(), Some(&(size, size)));
+
+    let offered = match bob.wait_message().await? {
+        (_, Message::FileOffer { filename, hash, .. }) => (filename, hash),
+        (_, _) => anyhow::bail!("expected a FileOffer"),
+    };
+    assert_eq!(offered, ("album.tar".to_string(), *hash.as_bytes()));
+    let out = h.dir.path().join("album.tar");
+    node::fetch_blob(&bob.node.blob_store, &bob.node.endpoint, alice.id(), hash, &out, |_| {
+        std::future::ready(())
+    })
+    .await?;
+    let archive = std::fs::read(&out)?;
+    assert_eq!(archive.len() as u64, size);
+    assert!(archive.starts_with(b"album/\0"));
+
+    alice.node.shutdown().await?;
+    bob.node.shutdown().await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn ticket_hints_connect_without_address_lookup() -> Result<()> {
+    // Neither node publishes its address anywhere: the only way Bob can
+    // find Alice is the hint in her ticket.
+    let dir = tempfile::tempdir()?;
+    let spawn = async || -> Result<Node> {
+        let builder = Endpoint::empty_builder(RelayMode::Disabled).bind_addr("127.0.0.1:0")?;
+        Ok(Node::spawn(builder, dir.path()).await?)
+    };
+    let room = ChatTicket::new_random();
+    let node = spawn().await?;
+    let (sender, receiver) = node.join(&room).await?;
+    let mut alice = Peer { node, sender, receiver };
+
+    let ticket = alice.node.ticket_for(&room);
+    assert_eq!(ticket.hints.len(), 1);
+    assert_eq!(ticket.hints[0].id, alice.id());
+    assert!(!ticket.hints[0].addrs.is_empty());
+
+    let ticket = <ChatTicket as Ticket>::deserialize(&<ChatTicket as Ticket>::serialize(&ticket))?;
+    let node = spawn().await?;
+    let (sender, receiver) = node.join(&ticket).await?;
+    let mut bob = Peer { node, sender, receiver };
+    bob.wait_neighbor_up(alice.id()).await?;
+    alice.wait_neighbor_up(bob.id()).await?;
+
+    alice.node.shutdown().await?;
+    bob.node.shutdown().await?;
+    Ok(())
+}
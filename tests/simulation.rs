@@ -0,0 +1,302 @@
+//! Deterministic simulation tests for the chat's state.
+//!
+//! A `Sim` holds an `App` and feeds it a scripted sequence of steps — events
+//! arriving over gossip, keys pressed in the file pane, reports from
+//! background transfer tasks — then the test asserts on the resulting state
+//! and on the effects the steps asked for (a download to start, a file to
+//! open). No tokio, no network, no clock: every run is the same run.
+//!
+//! Gossip goes through `replay::Player`, which applies events to an `App`
+//! with the same methods the live loop uses. Keys and transfer reports are
+//! handled inside `main.rs`'s select loop, which has no pure core to call
+//! yet; `Sim::key` and `Sim::transfer` mirror its state changes by hand, so
+//! they need to follow that loop when it changes.
+
+use std::path::PathBuf;
+
+use iroh::EndpointId;
+use iroh_blobs::Hash;
+use piper_chat::chat::{App, ChatLine};
+use piper_chat::replay::{Event, Player, Recorded};
+use piper_chat::transfer::{Confirm, EntryId, FetchProgress, FileOffer, Milestone, TransferEvent, TransferState};
+
+// ── Harness ──────────────────────────────────────────────────────────────────
+
+/// A key in the file pane.
+#[derive(Debug, Clone, Copy)]
+enum Key {
+    Up,
+    Down,
+    Enter,
+    /// `o`: open the selected download.
+    Open,
+    /// `y`: go ahead past the risky-file overlay.
+    Yes,
+}
+
+/// Something a step asked the outside world to do.
+#[derive(Debug, Clone)]
+enum Effect {
+    Download(FileOffer),
+    Open(PathBuf),
+}
+
+/// One scripted input.
+enum Step {
+    Gossip(Event),
+    Key(Key),
+    Transfer(TransferEvent),
+}
+
+struct Sim {
+    app: App,
+    player: Player,
+    /// Simulated time: each step happens a second after the last.
+    now_ms: u64,
+    effects: Vec<Effect>,
+}
+
+impl Sim {
+    fn new() -> Self {
+        let mut app = App::new();
+        app.focus_file_pane();
+        Self { app, player: Player::new(Vec::new()), now_ms: 1_700_000_000_000, effects: Vec::new() }
+    }
+
+    fn run(&mut self, steps: impl IntoIterator<Item = Step>) {
+        for step in steps {
+            self.now_ms += 1_000;
+            match step {
+                Step::Gossip(event) => self.player.play(&mut self.app, Recorded { ts_ms: self.now_ms, event }),
+                Step::Key(key) => self.key(key),
+                Step::Transfer(event) => self.transfer(event),
+            }
+        }
+    }
+
+    /// The file pane's key handling in `main.rs`.
+    fn key(&mut self, key: Key) {
+        let transfers = &mut self.app.transfers;
+        if let Some(confirm) = transfers.confirm.take() {
+            match (key, confirm) {
+                (Key::Yes, Confirm::Download(id)) => {
+                    self.effects.extend(transfers.request_download(&id, true).map(Effect::Download));
+                }
+                (Key::Yes, Confirm::Open(path)) => self.effects.push(Effect::Open(path)),
+                _ => {}
+            }
+            return;
+        }
+        match key {
+            Key::Up => transfers.select_prev(),
+            Key::Down => transfers.select_next(),
+            Key::Enter => {
+                let Some(entry) = transfers.selected_entry() else { return };
+                match &entry.state {
+                    TransferState::Pending => {
+                        let id = entry.offer.entry_id();
+                        self.effects.extend(transfers.request_download(&id, false).map(Effect::Download));
+                    }
+                    // Enter on a finished download opens its folder.
+                    TransferState::Complete(path) => self.effects.extend(path.parent().map(|dir| Effect::Open(dir.to_path_buf()))),
+                    _ => {}
+                }
+            }
+            Key::Open => {
+                if let Some(path) = transfers.selected_download().map(PathBuf::from)
+                    && transfers.request_open(&path, false)
+                {
+                    self.effects.push(Effect::Open(path));
+                }
+            }
+            Key::Yes => {}
+        }
+    }
+
+    /// The transfer branch of `main.rs`'s select loop, minus its hooks,
+    /// toasts and background work.
+    fn transfer(&mut self, event: TransferEvent) {
+        let transfers = &mut self.app.transfers;
+        match event {
+            TransferEvent::Progress { id, progress } => transfers.fetch_progress(&id, progress),
+            TransferEvent::Complete { id, filename, path, .. } => {
+                transfers.complete_download(&id, path);
+                self.app.system(format!("download complete: {filename}"));
+            }
+            TransferEvent::Failed { id, filename, error } => {
+                transfers.fail_download(&id, error.clone());
+                self.app.system(format!("download failed: {filename}: {error}"));
+            }
+            TransferEvent::Attempt { id, strategy } => transfers.start_attempt(&id, format!("{strategy:?}")),
+            other => panic!("not simulated: {other:?}"),
+        }
+    }
+
+    fn state(&self, id: &EntryId) -> &TransferState {
+        &self.app.transfers.entry(id).expect("no such entry").state
+    }
+
+    fn last_system(&self) -> Option<&str> {
+        self.app.messages.iter().rev().find_map(|line| match line {
+            ChatLine::System(text) => Some(text.as_str()),
+            _ => None,
+        })
+    }
+}
+
+fn bob() -> EndpointId {
+    EndpointId::from_bytes(&[1u8; 32]).unwrap()
+}
+
+/// `sender` offering a file; its message ID and hash repeat `byte`.
+fn offer_from(sender: EndpointId, byte: u8, filename: &str, size: u64) -> (EntryId, Event) {
+    let id = EntryId { hash: Hash::from_bytes([byte; 32]), sender, offer_id: [byte; 16] };
+    let event = Event::Offer {
+        message_id: format!("{byte:02x}").repeat(16),
+        nickname: "Bob".into(),
+        endpoint_id: sender.to_string(),
+        filename: filename.into(),
+        size,
+        hash: id.hash.to_hex(),
+    };
+    (id, event)
+}
+
+fn offer(byte: u8, filename: &str, size: u64) -> (EntryId, Event) {
+    offer_from(bob(), byte, filename, size)
+}
+
+/// The author of offer `id` withdrawing it.
+fn retract(id: &EntryId) -> Event {
+    Event::Retract {
+        hash: id.hash.to_hex(),
+        endpoint_id: Some(id.sender.to_string()),
+        offer_id: Some(id.offer_id.iter().map(|b| format!("{b:02x}")).collect()),
+    }
+}
+
+/// A fetch report: `bytes` received and verified, size not yet proven.
+fn verified(bytes: u64) -> FetchProgress {
+    FetchProgress { received: bytes, verified: bytes, size: None }
+}
+
+// ── Scenarios ────────────────────────────────────────────────────────────────
+
+#[test]
+fn offer_download_complete_open() {
+    let mut sim = Sim::new();
+    let (id, offered) = offer(2, "notes.txt", 1000);
+    let path = PathBuf::from("/downloads/notes.txt");
+    sim.run([
+        Step::Gossip(Event::Join { endpoint_id: bob().to_string(), nickname: "Bob".into() }),
+        Step::Gossip(offered),
+    ]);
+    assert!(matches!(sim.state(&id), TransferState::Pending));
+    assert_eq!(sim.last_system(), Some("Bob shared: notes.txt (1000 B)"));
+
+    sim.run([
+        Step::Key(Key::Enter),
+        Step::Transfer(TransferEvent::Progress { id, progress: verified(400) }),
+    ]);
+    assert!(matches!(&sim.effects[..], [Effect::Download(o)] if o.entry_id() == id));
+    assert!(matches!(sim.state(&id), TransferState::Downloading { bytes_received: 400, total_bytes: 1000 }));
+
+    sim.run([
+        Step::Transfer(TransferEvent::Complete { id, filename: "notes.txt".into(), path: path.clone(), content: None }),
+        Step::Key(Key::Open),
+    ]);
+    assert!(matches!(sim.state(&id), TransferState::Complete(p) if *p == path));
+    assert!(matches!(&sim.effects[1..], [Effect::Open(p)] if *p == path));
+    let timeline: Vec<_> = sim.app.transfers.entries[0].timeline.iter().map(|(_, m)| m.clone()).collect();
+    assert_eq!(timeline[..2], [Milestone::Offered, Milestone::Started]);
+    assert_eq!(timeline.last(), Some(&Milestone::Completed));
+}
+
+#[test]
+fn risky_files_wait_for_confirmation() {
+    let mut sim = Sim::new();
+    let (id, offered) = offer(3, "setup.exe", 5000);
+    let path = PathBuf::from("/downloads/setup.exe");
+
+    // Enter only raises the overlay; any key but `y` dismisses it.
+    sim.run([Step::Gossip(offered), Step::Key(Key::Enter), Step::Key(Key::Up)]);
+    assert!(sim.effects.is_empty());
+    assert_eq!(sim.app.transfers.confirm, None);
+    assert!(matches!(sim.state(&id), TransferState::Pending));
+
+    sim.run([Step::Key(Key::Enter), Step::Key(Key::Yes)]);
+    assert!(matches!(&sim.effects[..], [Effect::Download(o)] if o.entry_id() == id));
+
+    sim.run([
+        Step::Transfer(TransferEvent::Complete { id, filename: "setup.exe".into(), path: path.clone(), content: None }),
+        Step::Key(Key::Open),
+    ]);
+    assert_eq!(sim.app.transfers.confirm, Some(Confirm::Open(path.clone())));
+    sim.run([Step::Key(Key::Yes)]);
+    assert!(matches!(&sim.effects[1..], [Effect::Open(p)] if *p == path));
+}
+
+#[test]
+fn a_failed_download_and_a_retraction_leave_the_pane_consistent() {
+    let mut sim = Sim::new();
+    let (first, first_offer) = offer(4, "a.bin", 100);
+    let (second, second_offer) = offer(5, "b.bin", 100);
+    sim.run([
+        Step::Gossip(first_offer),
+        Step::Gossip(second_offer),
+        Step::Key(Key::Enter),
+        Step::Transfer(TransferEvent::Failed { id: first, filename: "a.bin".into(), error: "timed out".into() }),
+        Step::Key(Key::Down),
+        Step::Gossip(retract(&second)),
+    ]);
+    assert!(matches!(sim.state(&first), TransferState::Failed(e) if e == "timed out"));
+    assert_eq!(sim.app.transfers.entries.len(), 1);
+    assert_eq!(sim.last_system(), Some("Bob unshared: b.bin"));
+    // The selection falls back onto what's left.
+    assert_eq!(sim.app.transfers.selected_entry().map(|e| e.offer.entry_id()), Some(first));
+}
+
+#[test]
+fn the_same_file_from_two_senders_downloads_separately() {
+    let mut sim = Sim::new();
+    let carol = iroh::SecretKey::from_bytes(&[3; 32]).public();
+    let (from_bob, bob_offer) = offer(6, "song.ogg", 800);
+    let (from_carol, carol_offer) = offer_from(carol, 6, "song.ogg", 800);
+    assert_eq!(from_bob.hash, from_carol.hash);
+
+    sim.run([Step::Gossip(bob_offer), Step::Gossip(carol_offer), Step::Key(Key::Down), Step::Key(Key::Enter)]);
+    assert_eq!(sim.app.transfers.entries.len(), 2);
+    assert!(matches!(&sim.effects[..], [Effect::Download(o)] if o.sender_id == carol));
+
+    sim.run([
+        Step::Transfer(TransferEvent::Progress { id: from_carol, progress: verified(200) }),
+        Step::Transfer(TransferEvent::Complete { id: from_carol, filename: "song.ogg".into(), path: "/downloads/song.ogg".into(), content: None }),
+    ]);
+    // Only Carol's entry moved; Bob's is still there to download.
+    assert!(matches!(sim.state(&from_carol), TransferState::Complete(_)));
+    assert!(matches!(sim.state(&from_bob), TransferState::Pending));
+}
+
+#[test]
+fn a_retraction_withdraws_only_the_offer_it_names() {
+    let mut sim = Sim::new();
+    let carol = iroh::SecretKey::from_bytes(&[3; 32]).public();
+    let (from_bob, bob_offer) = offer(8, "map.png", 500);
+    let (from_carol, carol_offer) = offer_from(carol, 8, "map.png", 500);
+    sim.run([Step::Gossip(bob_offer), Step::Gossip(carol_offer), Step::Gossip(retract(&from_carol))]);
+    assert_eq!(sim.app.transfers.entries.len(), 1);
+    assert!(matches!(sim.state(&from_bob), TransferState::Pending));
+}
+
+#[test]
+fn a_rebroadcast_offer_is_not_added_twice() {
+    let mut sim = Sim::new();
+    let (id, offered) = offer(7, "slides.pdf", 300);
+    let (_, again) = offer(7, "slides.pdf", 300);
+    sim.run([Step::Gossip(offered), Step::Key(Key::Enter), Step::Gossip(again)]);
+    assert_eq!(sim.app.transfers.entries.len(), 1);
+    // The download already under way isn't reset by the repeat.
+    assert!(matches!(sim.state(&id), TransferState::Downloading { .. }));
+    let shared = sim.app.messages.iter().filter(|l| matches!(l, ChatLine::System(t) if t.starts_with("Bob shared"))).count();
+    assert_eq!(shared, 1);
+}
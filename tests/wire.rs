@@ -0,0 +1,355 @@
+//! Wire format tests: a corpus of every gossip message, and fuzzing.
+//!
+//! Everything in this file goes through the crate's public decoding entry
+//! points exactly as bytes off the network would:
+//!
+//! - **Corpus** — one sample of every `Message` variant, encoded, signed and
+//!   decoded again. The postcard encoding of each is pinned in an insta
+//!   snapshot, so a change that would break older peers (a reordered
+//!   variant, a retyped field) shows up as a snapshot diff in review.
+//! - **Fuzzing** — random bytes and mutations of the corpus fed to every
+//!   decoder: `postcard::from_bytes::<Message>`, `decode`, history blobs and
+//!   tickets. The only assertion is that nothing panics; errors are fine.
+//!   Mutated messages are re-signed before decoding, so they get past the
+//!   signature check and exercise sanitizing and the nickname check too.
+//!
+//! The fuzzer is a small hand-rolled loop over a seeded `StdRng` rather than
+//! `proptest` or `cargo fuzz`: it needs no extra dependencies, runs as part
+//! of `cargo test`, and a failure reproduces exactly. To try more inputs,
+//! set `PIPER_FUZZ_ITERATIONS` (default 2000 per decoder) and
+//! `PIPER_FUZZ_SEED`. Inputs that have to be signed or verified run a tenth
+//! as many, since Ed25519 is slow in an unoptimized test build. A new wire
+//! layer (an envelope, encryption) gets its own line in
+//! `decoders_never_panic`.
+
+use std::collections::BTreeSet;
+
+use iroh::SecretKey;
+use iroh_gossip::proto::TopicId;
+use iroh_tickets::Ticket;
+use piper_chat::net::proto::{PROTOCOL_VERSION, decode, encode};
+use piper_chat::net::{AddrHint, Approval, ChatTicket, HistoryEntry, HistoryEntryKind, Message, OwnerGrant, RoomPolicy};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+// ── Corpus ───────────────────────────────────────────────────────────────────
+
+fn key(n: u8) -> SecretKey {
+    SecretKey::from_bytes(&[n; 32])
+}
+
+/// The signer of every corpus message.
+fn author() -> SecretKey {
+    key(7)
+}
+
+fn entry(n: u8, kind: HistoryEntryKind) -> HistoryEntry {
+    HistoryEntry { message_id: [n; 16], timestamp_ms: 1_700_000_000_000 + n as u64, kind }
+}
+
+/// A few history entries covering every `HistoryEntryKind`.
+fn history() -> Vec<HistoryEntry> {
+    vec![
+        entry(1, HistoryEntryKind::Chat { nickname: "Alice".into(), text: "hi\nthere".into() }),
+        entry(
+            2,
+            HistoryEntryKind::FileOffer {
+                nickname: "Alice".into(),
+                endpoint_id: author().public(),
+                filename: "photo.png".into(),
+                size: 123_456,
+                hash: [9; 32],
+                mime_type: Some("image/png".into()),
+                target: Some("Bob".into()),
+            },
+        ),
+        entry(3, HistoryEntryKind::FileRetract { hash: [9; 32] }),
+        entry(4, HistoryEntryKind::System("Bob joined".into())),
+        entry(5, HistoryEntryKind::Spoiler { nickname: "Bob".into(), text: "the butler".into() }),
+        entry(6, HistoryEntryKind::ThreadChat { nickname: "Bob".into(), text: "in a thread".into(), thread_id: [1; 16] }),
+        entry(7, HistoryEntryKind::Reply { nickname: "Alice".into(), text: "quoting".into(), reply_to: [1; 16] }),
+        entry(8, HistoryEntryKind::FileKey { hash: [9; 32], wrapped_key: vec![3; 60] }),
+        entry(9, HistoryEntryKind::FileRevoke { hash: [9; 32], endpoint_id: author().public(), offer_id: [2; 16] }),
+    ]
+}
+
+/// One sample of every `Message` variant, in declaration order, all with
+/// `author()` as their `endpoint_id` so they pass `decode`'s signer check.
+fn corpus() -> Vec<Message> {
+    let id = author().public();
+    let topic = TopicId::from_bytes([5; 32]);
+    vec![
+        Message::Join { nickname: "Alice".into(), endpoint_id: id },
+        Message::Chat { nickname: "Alice".into(), text: "hello, world".into(), message_id: [1; 16], timestamp_ms: 1_700_000_000_000 },
+        Message::FileOffer {
+            nickname: "Alice".into(),
+            endpoint_id: id,
+            filename: "photo.png".into(),
+            size: 123_456,
+            hash: [9; 32],
+            message_id: [2; 16],
+            timestamp_ms: 1_700_000_000_001,
+            mime_type: Some("image/png".into()),
+            target: None,
+            offer_id: [2; 16],
+        },
+        Message::FileRetract { nickname: "Alice".into(), hash: [9; 32], message_id: [3; 16], timestamp_ms: 1_700_000_000_002 },
+        Message::HistoryOffer {
+            message_count: 7,
+            oldest_timestamp_ms: 1_700_000_000_001,
+            newest_timestamp_ms: 1_700_000_000_007,
+            hash: [8; 32],
+            endpoint_id: id,
+        },
+        Message::Spoiler { nickname: "Alice".into(), text: "the butler".into(), message_id: [4; 16], timestamp_ms: 1_700_000_000_003 },
+        Message::RollCall { request_id: [5; 16], endpoint_id: id, sent_ms: 1_700_000_000_004 },
+        Message::Here { request_id: [5; 16], nickname: "Alice".into(), endpoint_id: id, echo_ms: 1_700_000_000_004 },
+        Message::Ack { message_id: [1; 16], endpoint_id: id },
+        Message::BackfillRequest { since_ms: 1_700_000_000_000, known: vec![[1; 16], [2; 16]], endpoint_id: id },
+        Message::BackfillResponse { entries: history(), endpoint_id: id },
+        Message::FileRevoke { hash: [9; 32], endpoint_id: id, message_id: [6; 16], timestamp_ms: 1_700_000_000_005, at_ms: 1_700_000_060_000, offer_id: [2; 16] },
+        Message::JoinRequest { nickname: "Alice".into(), endpoint_id: id },
+        Message::JoinApproval { approvals: vec![Approval::sign(&author(), &topic, key(8).public())], endpoint_id: id },
+        Message::JoinDenial { denied: key(9).public(), endpoint_id: id },
+        Message::ThreadChat {
+            nickname: "Alice".into(),
+            text: "in a thread".into(),
+            message_id: [7; 16],
+            timestamp_ms: 1_700_000_000_006,
+            thread_id: [1; 16],
+        },
+        Message::Reply { nickname: "Alice".into(), text: "quoting".into(), message_id: [8; 16], timestamp_ms: 1_700_000_000_007, reply_to: [1; 16] },
+        Message::Timer { nickname: "Alice".into(), label: "standup".into(), timer_id: [9; 16], ends_ms: 1_700_000_600_000 },
+        Message::KeepAlive { endpoint_id: id, sent_ms: 1_700_000_000_008 },
+        Message::SealedFileOffer {
+            nickname: "Alice".into(),
+            endpoint_id: id,
+            filename: "secret.pdf".into(),
+            size: 2048,
+            hash: [10; 32],
+            message_id: [10; 16],
+            timestamp_ms: 1_700_000_000_009,
+            mime_type: Some("application/pdf".into()),
+            target: None,
+            wrapped_key: vec![3; 60],
+            offer_id: [10; 16],
+        },
+        Message::GrantOwner { grants: vec![OwnerGrant::sign(&author(), &topic, key(8).public())], endpoint_id: id },
+        Message::FileReceived { nickname: "Alice".into(), endpoint_id: id, hash: [2; 32], sender: key(8).public(), offer_id: [11; 16] },
+    ]
+}
+
+/// The variant's name. The match is exhaustive on purpose: adding a
+/// variant breaks the build here until it's added to `corpus()` too.
+fn variant(msg: &Message) -> &'static str {
+    match msg {
+        Message::Join { .. } => "Join",
+        Message::Chat { .. } => "Chat",
+        Message::FileOffer { .. } => "FileOffer",
+        Message::FileRetract { .. } => "FileRetract",
+        Message::HistoryOffer { .. } => "HistoryOffer",
+        Message::Spoiler { .. } => "Spoiler",
+        Message::RollCall { .. } => "RollCall",
+        Message::Here { .. } => "Here",
+        Message::Ack { .. } => "Ack",
+        Message::BackfillRequest { .. } => "BackfillRequest",
+        Message::BackfillResponse { .. } => "BackfillResponse",
+        Message::FileRevoke { .. } => "FileRevoke",
+        Message::JoinRequest { .. } => "JoinRequest",
+        Message::JoinApproval { .. } => "JoinApproval",
+        Message::JoinDenial { .. } => "JoinDenial",
+        Message::ThreadChat { .. } => "ThreadChat",
+        Message::Reply { .. } => "Reply",
+        Message::Timer { .. } => "Timer",
+        Message::KeepAlive { .. } => "KeepAlive",
+        Message::SealedFileOffer { .. } => "SealedFileOffer",
+        Message::GrantOwner { .. } => "GrantOwner",
+        Message::FileReceived { .. } => "FileReceived",
+    }
+}
+
+/// Tickets with and without a policy and the optional v2 fields.
+fn tickets() -> Vec<ChatTicket> {
+    let open = ChatTicket { bootstrap: BTreeSet::from([author().public()]), ..ChatTicket::new(TopicId::from_bytes([5; 32])) };
+    let policy = RoomPolicy { creator: author().public(), max_members: Some(8), knock: true };
+    let v2 = ChatTicket {
+        name: Some("book club".into()),
+        hints: vec![AddrHint {
+            id: author().public(),
+            relays: vec!["https://relay.example.com".parse().unwrap()],
+            addrs: vec!["192.0.2.1:4433".parse().unwrap(), "[2001:db8::1]:4433".parse().unwrap()],
+        }],
+        expires_ms: Some(u64::MAX),
+        ..open.clone()
+    };
+    vec![open.clone(), ChatTicket { policy: Some(policy), creator: Some(policy.creator), ..open }, v2]
+}
+
+/// Sign `data` as `key` and wrap it in the versioned envelope, the way
+/// `encode` does — but for bytes that needn't be a valid `Message`.
+fn sign_raw(key: &SecretKey, data: Vec<u8>) -> Vec<u8> {
+    let signature = key.sign(&data);
+    let mut bytes = vec![PROTOCOL_VERSION];
+    bytes.extend(postcard::to_stdvec(&(key.public(), data, signature)).unwrap());
+    bytes
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[test]
+fn corpus_covers_every_variant() {
+    // Postcard writes the variant index first, so a corpus in declaration
+    // order starts with 0, 1, 2, … — a gap means a variant is missing.
+    let indices: Vec<u8> = corpus().iter().map(|msg| postcard::to_stdvec(msg).unwrap()[0]).collect();
+    assert_eq!(indices, (0..indices.len() as u8).collect::<Vec<_>>());
+}
+
+#[test]
+fn corpus_roundtrips_through_the_envelope() {
+    for msg in corpus() {
+        let bytes = encode(&author(), &msg).unwrap();
+        let (from, decoded) = decode(&bytes).unwrap_or_else(|e| panic!("{} failed to decode: {e}", variant(&msg)));
+        assert_eq!(from, author().public());
+        assert_eq!(
+            postcard::to_stdvec(&decoded).unwrap(),
+            postcard::to_stdvec(&msg).unwrap(),
+            "{} changed in a round-trip",
+            variant(&msg)
+        );
+    }
+}
+
+#[test]
+fn wire_encoding_is_stable() {
+    // Older peers decode these exact bytes. If this snapshot changes, the
+    // change must be backward compatible or come with a PROTOCOL_VERSION bump.
+    let lines: Vec<String> =
+        corpus().iter().map(|msg| format!("{}: {}", variant(msg), hex(&postcard::to_stdvec(msg).unwrap()))).collect();
+    insta::assert_snapshot!(lines.join("\n"));
+}
+
+#[test]
+fn only_the_current_version_decodes() {
+    let bytes = encode(&author(), &corpus()[1]).unwrap();
+    for version in 0..=u8::MAX {
+        let mut bytes = bytes.clone();
+        bytes[0] = version;
+        assert_eq!(decode(&bytes).is_ok(), version == PROTOCOL_VERSION, "version {version}");
+    }
+}
+
+#[test]
+fn history_and_tickets_roundtrip() {
+    let bytes = postcard::to_stdvec(&history()).unwrap();
+    let decoded: Vec<HistoryEntry> = postcard::from_bytes(&bytes).unwrap();
+    assert_eq!(postcard::to_stdvec(&decoded).unwrap(), bytes);
+
+    for ticket in tickets() {
+        assert_eq!(ChatTicket::parse(&ticket.serialize()).unwrap(), ticket);
+    }
+}
+
+// ── Fuzzing ──────────────────────────────────────────────────────────────────
+
+fn seed() -> u64 {
+    std::env::var("PIPER_FUZZ_SEED").ok().and_then(|v| v.parse().ok()).unwrap_or(0x5eed)
+}
+
+/// Inputs per decoder, divided by `cost` for the ones that sign or verify.
+fn iterations(cost: u64) -> u64 {
+    std::env::var("PIPER_FUZZ_ITERATIONS").ok().and_then(|v| v.parse().ok()).unwrap_or(2_000) / cost
+}
+
+/// Change `bytes` in one random way: flip a bit, overwrite, insert or
+/// remove a byte, truncate, or repeat a slice. Lengths and variant indices
+/// are varints near the front, so small edits there reach deep into the
+/// decoder.
+fn mutate(rng: &mut StdRng, bytes: &mut Vec<u8>) {
+    if bytes.is_empty() {
+        bytes.push(rng.random());
+        return;
+    }
+    let at = rng.random_range(0..bytes.len());
+    match rng.random_range(0..6) {
+        0 => bytes[at] ^= 1 << rng.random_range(0..8),
+        1 => bytes[at] = rng.random(),
+        2 => bytes.insert(at, rng.random()),
+        3 => {
+            bytes.remove(at);
+        }
+        4 => bytes.truncate(at),
+        _ => {
+            let end = rng.random_range(at..=bytes.len());
+            let slice = bytes[at..end].to_vec();
+            bytes.splice(at..at, slice);
+        }
+    }
+}
+
+/// Feed `decoder` pure noise and mutations of each of `seeds`. A panic
+/// reports the seed and iteration, and the input that caused it.
+fn fuzz(name: &str, cost: u64, seeds: &[Vec<u8>], decoder: impl Fn(&[u8])) {
+    let seed = seed();
+    let mut rng = StdRng::seed_from_u64(seed);
+    for i in 0..iterations(cost) {
+        let input = if seeds.is_empty() || i % 8 == 0 {
+            let mut noise = vec![0; rng.random_range(0..256)];
+            rng.fill(&mut noise[..]);
+            noise
+        } else {
+            let mut input = seeds[rng.random_range(0..seeds.len())].clone();
+            for _ in 0..rng.random_range(1..=4) {
+                mutate(&mut rng, &mut input);
+            }
+            input
+        };
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| decoder(&input)));
+        assert!(result.is_ok(), "{name} panicked (seed {seed:#x}, iteration {i}) on {}", hex(&input));
+    }
+}
+
+#[test]
+fn decoders_never_panic() {
+    let messages: Vec<Vec<u8>> = corpus().iter().map(|msg| postcard::to_stdvec(msg).unwrap()).collect();
+    let envelopes: Vec<Vec<u8>> = corpus().iter().map(|msg| encode(&author(), msg).unwrap()).collect();
+    let history = vec![postcard::to_stdvec(&history()).unwrap()];
+    let tickets: Vec<Vec<u8>> = tickets().iter().map(Ticket::to_bytes).collect();
+
+    fuzz("postcard::from_bytes::<Message>", 1, &messages, |bytes| {
+        let _ = postcard::from_bytes::<Message>(bytes);
+    });
+    fuzz("decode", 10, &envelopes, |bytes| {
+        let _ = decode(bytes);
+    });
+    // Mutated messages in a valid signature, so `decode` gets past the
+    // signature check and runs its own checks on whatever postcard accepts.
+    fuzz("decode (re-signed)", 10, &messages, |bytes| {
+        let _ = decode(&sign_raw(&author(), bytes.to_vec()));
+    });
+    fuzz("history blob", 1, &history, |bytes| {
+        if let Ok(mut entries) = postcard::from_bytes::<Vec<HistoryEntry>>(bytes) {
+            entries.iter_mut().for_each(|e| e.kind.sanitize());
+        }
+    });
+    fuzz("ticket", 1, &tickets, |bytes| {
+        let _ = ChatTicket::from_bytes(bytes);
+    });
+}
+
+#[test]
+fn decoded_text_never_carries_control_characters() {
+    // Chat text made of characters the sanitizer has to deal with, mixed
+    // with plain ones. Whatever survives `decode` must be printable.
+    let pool = ['a', 'é', '世', ' ', '\n', '\t', '\r', '\u{1b}', '[', ']', ';', '\u{7}', '\\', '\u{9b}', '\u{202e}', '\u{2066}', '\u{0}'];
+    let mut rng = StdRng::seed_from_u64(seed());
+    for _ in 0..iterations(10) {
+        let text: String = (0..rng.random_range(0..40)).map(|_| pool[rng.random_range(0..pool.len())]).collect();
+        let msg = Message::Chat { nickname: format!("A{text}"), text, message_id: [1; 16], timestamp_ms: 0 };
+        let Ok((_, Message::Chat { nickname, text, .. })) = decode(&encode(&author(), &msg).unwrap()) else { continue };
+        assert!(text.chars().all(|c| c == '\n' || !c.is_control()), "{text:?}");
+        assert!(nickname.chars().all(|c| !c.is_control()), "{nickname:?}");
+        assert!(!text.contains(['\u{202e}', '\u{2066}']), "{text:?}");
+    }
+}